@@ -0,0 +1,602 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use gregex::regex::InvalidPositions;
+use gregex::sink::{SpanVecSink, WriterSink};
+use gregex::{Builder, CgStrategy, Document, EngineKind, MultiRegex, Regex, util::Input};
+
+/// Returns every general engine available on this platform, with a name for
+/// reporting.
+fn engines(pattern: &str) -> Vec<(&'static str, Regex)> {
+    let builder = Builder::new(pattern).specialize(false);
+    let mut engines = vec![("pike_vm", builder.pike_vm().unwrap())];
+    if let Ok(re) = builder.pike_jit() {
+        engines.push(("pike_jit", re));
+    }
+    engines
+}
+
+/// Some english-looking text, repeated to reach the given size in bytes.
+fn haystack(size: usize) -> String {
+    const TEXT: &str = "To Sherlock Holmes she is always the woman. I have seldom heard \
+        him mention her under any other name. In his eyes she eclipses and predominates \
+        the whole of her sex. It was not that he felt any emotion akin to love for Irene \
+        Adler. All emotions, and that one particularly, were abhorrent to his cold, \
+        precise but admirably balanced mind. 1234 = 5678\n";
+    TEXT.repeat(size / TEXT.len() + 1)
+}
+
+fn bench_find_all(c: &mut Criterion, name: &str, pattern: &str, haystack: &str) {
+    bench_engines_find_all(c, name, engines(pattern), haystack);
+}
+
+fn bench_engines_find_all(
+    c: &mut Criterion,
+    name: &str,
+    engines: Vec<(&'static str, Regex)>,
+    haystack: &str,
+) {
+    let mut group = c.benchmark_group(name);
+    for (engine_name, re) in engines {
+        group.bench_function(engine_name, |b| b.iter(|| re.find_all(haystack).count()));
+    }
+    group.finish();
+}
+
+fn literals(c: &mut Criterion) {
+    let haystack = haystack(100_000);
+    bench_find_all(c, "literal", "Sherlock Holmes", &haystack);
+    bench_find_all(
+        c,
+        "alternation_of_names",
+        "Sherlock Holmes|John Watson|Irene Adler|Inspector Lestrade|Professor Moriarty",
+        &haystack,
+    );
+    bench_find_all(c, "dot_star", ".*d", &haystack);
+    bench_find_all(
+        c,
+        "classes",
+        r"[A-Z][a-z]+ [A-Z][a-z]+|\d+ = \d+",
+        &haystack,
+    );
+}
+
+fn assertions(c: &mut Criterion) {
+    // Every other line is empty
+    let haystack = haystack(10_000_000).replace('\n', "\n\n");
+    let mut engines = engines("(?m)^$");
+    engines.push(("assertion_only", Regex::pike_vm("(?m)^$").unwrap()));
+    bench_engines_find_all(c, "empty_lines", engines, &haystack);
+}
+
+fn captures(c: &mut Criterion) {
+    let haystack = haystack(100_000);
+    let pattern = r"(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)";
+    let mut group = c.benchmark_group("fifteen_groups");
+    for (engine_name, re) in engines(pattern) {
+        for n in [2, 16] {
+            group.bench_function(format!("{engine_name}/n={n}"), |b| {
+                b.iter(|| {
+                    let mut input = Input::new(&haystack);
+                    let mut count = 0;
+                    while let Some(caps) = re.find_captures_n(input.clone(), n) {
+                        let from = caps.group0().next_match_start();
+                        input = input.span((from..haystack.len()).into());
+                        count += 1;
+                    }
+                    count
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+fn cg_strategies(c: &mut Criterion) {
+    let haystack = haystack(100_000);
+    let patterns = [
+        ("one_group", r"(\w+) \d+"),
+        ("four_groups", r"([A-Z])(\w+) (\w+)(,|\.)"),
+        (
+            "fifteen_groups",
+            r"(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)",
+        ),
+        // Many threads alive at once, with few groups.
+        (
+            "long_alternation",
+            r"(\w+) (?:she|him|her|his|that|emotion|abhorrent)(\w*)",
+        ),
+        // Many groups, and then many forks without any write.
+        (
+            "groups_then_forks",
+            r"(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w) \w+ \w+ \w+ \d",
+        ),
+    ];
+    for (name, pattern) in patterns {
+        let mut group = c.benchmark_group(format!("cg_strategy/{name}"));
+        let builder = Builder::new(pattern).specialize(false);
+        for strategy in [
+            CgStrategy::Auto,
+            CgStrategy::Tree,
+            CgStrategy::Array,
+            CgStrategy::CowArray,
+        ] {
+            let Ok(re) = builder.clone().jit_cg_strategy(strategy).pike_jit() else {
+                continue;
+            };
+            group.bench_function(format!("{strategy:?}"), |b| {
+                b.iter(|| re.find_all_captures(haystack.as_str()).count())
+            });
+        }
+        group.finish();
+    }
+}
+
+fn prefilter(c: &mut Criterion) {
+    // No '=' at all, so the prefilter rejects the haystack without running
+    // the engine.
+    let haystack = haystack(1_000_000).replace('=', " ");
+    let pattern = r"\d+=\d+";
+    let mut group = c.benchmark_group("no_required_byte");
+    for (prefilter, builder) in [
+        ("prefilter", Builder::new(pattern)),
+        ("no_prefilter", Builder::new(pattern).prefilter(false)),
+    ] {
+        group.bench_function(format!("pike_vm/{prefilter}"), |b| {
+            let re = builder.pike_vm().unwrap();
+            b.iter(|| re.find(haystack.as_str()))
+        });
+        if let Ok(re) = builder.pike_jit() {
+            group.bench_function(format!("pike_jit/{prefilter}"), |b| {
+                b.iter(|| re.find(haystack.as_str()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn leading_wildcard(c: &mut Criterion) {
+    // No match, so the engines without the specialization run the `.*` over
+    // the whole haystack, while the others only look for `needle`.
+    let haystack = haystack(1_000_000);
+    let pattern = r".*needle";
+    let mut group = c.benchmark_group("leading_wildcard");
+    for (name, builder) in [
+        ("specialized", Builder::new(pattern)),
+        ("general", Builder::new(pattern).specialize(false)),
+    ] {
+        group.bench_function(format!("pike_vm/{name}"), |b| {
+            let re = builder.pike_vm().unwrap();
+            b.iter(|| re.find(haystack.as_str()))
+        });
+        if let Ok(re) = builder.pike_jit() {
+            group.bench_function(format!("pike_jit/{name}"), |b| {
+                b.iter(|| re.find(haystack.as_str()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn end_anchored(c: &mut Criterion) {
+    // A long line, where the engines without the specialization start a
+    // thread at every char, while the others only search its last bytes. The
+    // prefilter is disabled, since the `.` would otherwise narrow the search
+    // too.
+    let haystack = format!("{}.log", "x".repeat(1_000_000));
+    let pattern = r"\.log$";
+    let mut group = c.benchmark_group("end_anchored");
+    for (name, builder) in [
+        ("specialized", Builder::new(pattern).prefilter(false)),
+        (
+            "general",
+            Builder::new(pattern).prefilter(false).specialize(false),
+        ),
+    ] {
+        group.bench_function(format!("pike_vm/{name}"), |b| {
+            let re = builder.pike_vm().unwrap();
+            b.iter(|| re.find(haystack.as_str()))
+        });
+        if let Ok(re) = builder.pike_jit() {
+            group.bench_function(format!("pike_jit/{name}"), |b| {
+                b.iter(|| re.find(haystack.as_str()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn prefix_literals(c: &mut Criterion) {
+    // Every match starts with one of the names, which are looked for with
+    // `memmem` before running the engine, unless the prefilter is disabled.
+    let haystack = haystack(1_000_000);
+    for (name, pattern) in [
+        (
+            "prefix_literals/alternation_of_names",
+            "Sherlock Holmes|John Watson|Irene Adler|Inspector Lestrade|Professor Moriarty",
+        ),
+        ("prefix_literals/literal_then_class", r"Sherlock [A-Z]\w+"),
+    ] {
+        let mut group = c.benchmark_group(name);
+        for (mode, builder) in [
+            ("prefixes", Builder::new(pattern)),
+            ("general", Builder::new(pattern).prefilter(false)),
+        ] {
+            group.bench_function(format!("pike_vm/{mode}"), |b| {
+                let re = builder.pike_vm().unwrap();
+                b.iter(|| re.find_all(haystack.as_str()).count())
+            });
+            if let Ok(re) = builder.pike_jit() {
+                group.bench_function(format!("pike_jit/{mode}"), |b| {
+                    b.iter(|| re.find_all(haystack.as_str()).count())
+                });
+            }
+        }
+        group.finish();
+    }
+}
+
+fn class_repetition(c: &mut Criterion) {
+    // Identifiers and words of some source code, where the JIT checks the
+    // runs of a class at once, and follows a single thread through them.
+    let haystack = include_str!("../src/regex.rs").repeat(10);
+    bench_find_all(c, "identifiers", r"[A-Za-z_][A-Za-z0-9_]{0,63}", &haystack);
+    bench_find_all(c, "long_words", r"[a-z]{8,64}", &haystack);
+    bench_find_all(c, "calls", r"[a-z_]{2,32}\(", &haystack);
+}
+
+fn bounded_repetition(c: &mut Criterion) {
+    // The only `X` is at the end, so up to 200 threads, one per start
+    // position, are alive in the unrolled repetition at each step. The lazy
+    // and greedy repetitions keep the same number of threads.
+    let haystack = haystack(100_000) + "X";
+    bench_find_all(c, "bounded_lazy_late", r".{0,200}?X", &haystack);
+    bench_find_all(c, "bounded_greedy_late", r".{0,200}X", &haystack);
+}
+
+fn candidate_positions(c: &mut Criterion) {
+    // 10k candidate positions spread over 1MB, where only the last one
+    // matches, searched at once or by a loop of anchored searches. Each
+    // search of the loop also runs the prefilter up to the end of the
+    // haystack, which is what makes it about 40 times slower.
+    let haystack = haystack(1_000_000) + "{key}";
+    let step = haystack.len() / 10_000;
+    let mut positions: Vec<_> = (0..9_999)
+        .map(|i| i * step)
+        .filter(|p| haystack.is_char_boundary(*p))
+        .collect();
+    positions.push(haystack.len() - "{key}".len());
+    let mut group = c.benchmark_group("candidate_positions");
+    for (engine_name, re) in engines(r"\{(\w+)\}") {
+        group.bench_function(format!("{engine_name}/at_positions"), |b| {
+            b.iter(|| re.find_at_positions(&haystack, &positions, InvalidPositions::Skip))
+        });
+        group.bench_function(format!("{engine_name}/loop"), |b| {
+            b.iter(|| {
+                positions.iter().find_map(|&position| {
+                    let input = Input::new(&haystack)
+                        .span((position..haystack.len()).into())
+                        .anchored(true);
+                    re.find(input)
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn scan_into(c: &mut Criterion) {
+    // About 800k matches, collected or written by the iterator and by a scan
+    // into a sink. The searches dominate: the scan is only a few percent
+    // faster, mostly from the writes without the formatting machinery.
+    let haystack = haystack(1_000_000);
+    let mut group = c.benchmark_group("scan_into");
+    for (engine_name, re) in engines(r"\w") {
+        let mut spans = Vec::new();
+        group.bench_function(format!("{engine_name}/iterator_vec"), |b| {
+            b.iter(|| {
+                spans.clear();
+                spans.extend(re.find_all(&*haystack).map(|m| (m.start(), m.end())));
+                spans.len()
+            })
+        });
+        let mut sink = SpanVecSink::new();
+        group.bench_function(format!("{engine_name}/sink_vec"), |b| {
+            b.iter(|| {
+                sink.spans.clear();
+                re.scan_into(&*haystack, &mut sink).unwrap()
+            })
+        });
+        group.bench_function(format!("{engine_name}/iterator_writer"), |b| {
+            b.iter(|| {
+                let mut writer = io::BufWriter::new(io::sink());
+                for m in re.find_all(&*haystack) {
+                    writeln!(writer, "{}\t{}", m.start(), m.end()).unwrap();
+                }
+                writer.flush().unwrap()
+            })
+        });
+        group.bench_function(format!("{engine_name}/sink_writer"), |b| {
+            b.iter(|| {
+                let mut sink = WriterSink::new(io::sink());
+                re.scan_into(&*haystack, &mut sink).unwrap();
+                sink.finish().unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn match_only_jit(c: &mut Criterion) {
+    // No match, so `find` reads the whole haystack, writing the groups of
+    // every thread unless the JIT also compiled the pattern without them.
+    // `four_groups` only writes a group after an `a`, and gains nothing
+    // measurable. `twelve_groups` writes groups on every word char, and
+    // gains about 10%.
+    let haystack = haystack(1_000_000);
+    for (name, pattern) in [
+        ("four_groups", r"a(b)(c)(d)(e)"),
+        (
+            "twelve_groups",
+            r"(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w) \d{9}",
+        ),
+    ] {
+        let mut group = c.benchmark_group(format!("match_only_jit/{name}"));
+        for match_only in [false, true] {
+            let builder = Builder::new(pattern).match_only_jit(match_only);
+            let Ok(re) = builder.pike_jit() else {
+                continue;
+            };
+            group.bench_function(format!("{match_only}"), |b| {
+                b.iter(|| re.find(haystack.as_str()))
+            });
+        }
+        group.finish();
+    }
+}
+
+fn interrupt_check(c: &mut Criterion) {
+    // A search reading the whole haystack, without a match. The interrupt
+    // check, loading an atomic every 4096 chars, costs a few percent at
+    // most. Searches without a check still pay for the countdown of the
+    // JIT: up to about 5% on `classes`, nothing measurable on `dot_star`.
+    let haystack = haystack(1_000_000);
+    let stop = AtomicBool::new(false);
+    let check = || stop.load(Ordering::Relaxed);
+    let mut group = c.benchmark_group("interrupt_check");
+    for (engine_name, re) in engines(r"[A-Z][a-z]+ \d{5}") {
+        group.bench_function(format!("{engine_name}/find"), |b| {
+            b.iter(|| re.find(&*haystack))
+        });
+        group.bench_function(format!("{engine_name}/interruptible"), |b| {
+            b.iter(|| re.find_interruptible(&*haystack, 4096, &check).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn single_char_alternation(c: &mut Criterion) {
+    // 30 branches of a single char, compiled as a single class. With the
+    // `\d`, regex-syntax doesn't merge them itself. Compiled as 30 branches,
+    // the search was about twice as slow with the VM, and three times with
+    // the JIT.
+    let pattern = "q|w|e|r|t|y|u|i|o|p|a|s|d|f|g|h|j|k|l|z|x|c|v|b|n|m|Q|W|E|\\d";
+    bench_find_all(c, "single_char_alternation", pattern, &haystack(100_000));
+}
+
+fn possessive(c: &mut Criterion) {
+    // Each `a` the greedy repetition gives back keeps a thread alive in
+    // `a{20}`, while the possessive one kills them as it goes.
+    let haystack = "a".repeat(100_000);
+    let mut group = c.benchmark_group("possessive_give_back");
+    for (name, pattern) in [("greedy", "a*a{20}b"), ("possessive", "a*+a{20}b")] {
+        let re = Builder::new(pattern)
+            .possessive_quantifiers(true)
+            .pike_vm()
+            .unwrap();
+        group.bench_function(format!("pike_vm/{name}"), |b| {
+            b.iter(|| re.find_captures(haystack.as_str()).is_some())
+        });
+    }
+    group.finish();
+}
+
+fn lexer(c: &mut Criterion) {
+    // The token rules of a small language, by priority, over a source file.
+    let rules = [
+        r"\s+",
+        r"//[^\n]*",
+        r"/\*(?s:.)*?\*/",
+        r#""((?:[^"\\]|\\.)*)""#,
+        r"'(.)'",
+        r"(\d+)\.(\d+)",
+        r"\d+",
+        r"[A-Za-z_][A-Za-z0-9_]*",
+        r"->",
+        r"[=!<>]=",
+        r"[-+*/%=<>!&|]",
+        r"[(){}\[\];,.:]",
+    ];
+    const SOURCE: &str = "/* Computes the mean of the values. */
+fn mean(values: &[f64]) -> f64 {
+    // An empty slice has no mean.
+    if values.len() == 0 { return 0.0; }
+    let mut sum = 0.0;
+    for v in values { sum = sum + v * 1.5 - 'x' as f64; }
+    println(\"mean: {}\", sum / 12.25);
+    sum % 3 != 1
+}
+";
+    let source = SOURCE.repeat(100);
+    let mut group = c.benchmark_group("lexer_12_rules");
+    let regexes: Vec<_> = rules
+        .iter()
+        .map(|rule| Regex::pike_vm(rule).unwrap())
+        .collect();
+    group.bench_function("pike_vm/sequential", |b| {
+        b.iter(|| {
+            let mut count = 0;
+            let mut from = 0;
+            'tokens: while from < source.len() {
+                let input = Input::new(&source).span((from..source.len()).into());
+                for re in &regexes {
+                    if let Some(caps) = re.find_captures(input.clone().anchored(true)) {
+                        from = caps.get(0).unwrap().end();
+                        count += 1;
+                        continue 'tokens;
+                    }
+                }
+                break;
+            }
+            count
+        })
+    });
+    let multi = MultiRegex::new(&rules).unwrap();
+    group.bench_function("pike_vm/multi", |b| {
+        b.iter(|| multi.tokenize(source.as_str()).count())
+    });
+    group.finish();
+}
+
+fn tokenizer_loop(c: &mut Criterion) {
+    // A search per token, anchored at the end of the previous one, where the
+    // per-call overhead shows more than in `find_all`.
+    let source = haystack(100_000);
+    let mut group = c.benchmark_group("tokenizer_loop");
+    for (engine_name, re) in engines(r"(?m)^\d+|\w+|\s+|[^\w\s]") {
+        group.bench_function(format!("{engine_name}/span"), |b| {
+            b.iter(|| {
+                let mut count = 0;
+                let mut input = Input::new(&source).anchored(true);
+                while let Some(m) = re.find(input.clone()) {
+                    input.span.from = m.end();
+                    count += 1;
+                }
+                count
+            })
+        });
+        group.bench_function(format!("{engine_name}/advance_past"), |b| {
+            b.iter(|| {
+                let mut count = 0;
+                let mut input = Input::new(&source).anchored(true);
+                while let Some(m) = re.find(input.clone()) {
+                    input.advance_past(&m);
+                    count += 1;
+                }
+                count
+            })
+        });
+    }
+    group.finish();
+}
+
+fn document(c: &mut Criterion) {
+    // Many patterns over the same haystack, as a linter would run them. Some
+    // are line anchored or only made of assertions, and some require bytes
+    // which are not in the haystack.
+    let haystack = haystack(100_000);
+    let patterns = [
+        r"Holmes",
+        r"Irene Adler",
+        r"\d+ = \d+",
+        r"(?m)^To",
+        r"(?m)^\w+ \w+",
+        r"(?m)^$",
+        r"(?m)$",
+        r"(?m)^",
+        r"(?m)\d$",
+        r" the ",
+        r"[A-Z]\w+",
+        r"emotion\w*",
+        r"mind\.",
+        r"\w+ly ",
+        r"(?m)^precise",
+        r"Watson",
+        r"Moriarty|Lestrade",
+        r"#\w+",
+        r"\{\}",
+        r"@",
+        r"(?i)sherlock",
+        r"she (is|was)",
+        r"[,.]",
+        r"\d{4}",
+        r"(?m)^To Sherlock",
+        r"\s{2,}",
+        r"(?m)^\d",
+        r"ab\w*",
+        r"under any",
+        r"1234",
+    ];
+    let mut group = c.benchmark_group("document_30_patterns");
+    for kind in [EngineKind::PikeVM, EngineKind::PikeJIT] {
+        let Ok(regexes) = patterns
+            .iter()
+            .map(|pattern| Builder::new(*pattern).build(kind))
+            .collect::<Result<Vec<_>, _>>()
+        else {
+            continue;
+        };
+        let name = kind.name();
+        group.bench_function(format!("{name}/plain"), |b| {
+            b.iter(|| {
+                regexes
+                    .iter()
+                    .map(|re| re.find_all(haystack.as_str()).count())
+                    .sum::<usize>()
+            })
+        });
+        group.bench_function(format!("{name}/document"), |b| {
+            b.iter(|| {
+                let document = Document::new(&haystack);
+                regexes
+                    .iter()
+                    .map(|re| re.find_all_in_document(&document).count())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn compilation(c: &mut Criterion) {
+    let noqa = r"(\s*)((?:# [Nn][Oo][Qq][Aa])(?::\s?(([A-Z]+[0-9]+(?:[,\s]+)?)+))?)";
+    // An alternation of 500 words of 10 chars, about 5k instructions
+    let words = (0..500)
+        .map(|i| format!("word{i:04}ab"))
+        .collect::<Vec<_>>()
+        .join("|");
+    for (name, pattern) in [("compile_noqa", noqa), ("compile_5k", words.as_str())] {
+        let mut group = c.benchmark_group(name);
+        group.bench_function("pike_vm", |b| b.iter(|| Regex::pike_vm(pattern).unwrap()));
+        if Regex::pike_jit(pattern).is_ok() {
+            group.bench_function("pike_jit", |b| b.iter(|| Regex::pike_jit(pattern).unwrap()));
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(
+    benches,
+    literals,
+    assertions,
+    captures,
+    cg_strategies,
+    match_only_jit,
+    prefilter,
+    leading_wildcard,
+    end_anchored,
+    prefix_literals,
+    class_repetition,
+    bounded_repetition,
+    candidate_positions,
+    scan_into,
+    interrupt_check,
+    single_char_alternation,
+    possessive,
+    lexer,
+    tokenizer_loop,
+    document,
+    compilation
+);
+criterion_main!(benches);
@@ -0,0 +1,232 @@
+//! A minimal grep, built on gregex.
+//!
+//! ```text
+//! cargo run --example gregex-grep -- [-i] [-n] [-o] [-c] [-E vm|jit|auto] [--captures] PATTERN [FILE...]
+//! ```
+//!
+//! Reads the files (or stdin, if there are none or for `-`) and prints the
+//! lines matching the pattern, prefixed by the file name if there are several
+//! files. Like grep, it exits with 0 if a line matched, 1 if none did, and 2 on
+//! errors (e.g. an invalid pattern, or a file which can't be read).
+use std::io::{self, BufWriter, Read, Write};
+use std::process::ExitCode;
+
+use gregex::{Builder, Regex};
+
+const USAGE: &str =
+    "usage: gregex-grep [-i] [-n] [-o] [-c] [-E vm|jit|auto] [--captures] PATTERN [FILE...]";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Engine {
+    Vm,
+    Jit,
+    /// The JIT, or the Pike VM if the JIT can't be built (e.g. it isn't
+    /// available on this platform).
+    Auto,
+}
+
+#[derive(Debug)]
+struct Options {
+    case_insensitive: bool,
+    line_numbers: bool,
+    only_matching: bool,
+    count: bool,
+    captures: bool,
+    engine: Engine,
+    pattern: String,
+    files: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        case_insensitive: false,
+        line_numbers: false,
+        only_matching: false,
+        count: false,
+        captures: false,
+        engine: Engine::Auto,
+        pattern: String::new(),
+        files: Vec::new(),
+    };
+    let mut operands = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => {
+                operands.extend(args.by_ref());
+                break;
+            }
+            "--captures" => options.captures = true,
+            "-" => operands.push(arg),
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ if arg.starts_with('-') => {
+                // Short flags can be combined, e.g. `-in`, and `-E` takes the
+                // rest of the argument or the next one.
+                for (i, flag) in arg.char_indices().skip(1) {
+                    match flag {
+                        'i' => options.case_insensitive = true,
+                        'n' => options.line_numbers = true,
+                        'o' => options.only_matching = true,
+                        'c' => options.count = true,
+                        'E' => {
+                            let value = match &arg[i + 1..] {
+                                "" => args.next().ok_or("-E needs an engine")?,
+                                value => value.to_string(),
+                            };
+                            options.engine = match value.as_str() {
+                                "vm" => Engine::Vm,
+                                "jit" => Engine::Jit,
+                                "auto" => Engine::Auto,
+                                _ => return Err(format!("unknown engine {value}")),
+                            };
+                            break;
+                        }
+                        _ => return Err(format!("unknown option -{flag}")),
+                    }
+                }
+            }
+            _ => operands.push(arg),
+        }
+    }
+    let mut operands = operands.into_iter();
+    options.pattern = operands.next().ok_or("missing pattern")?;
+    options.files = operands.collect();
+    Ok(options)
+}
+
+fn build(options: &Options) -> Result<Regex, String> {
+    let builder = Builder::new(options.pattern.as_str())
+        .case_insensitive(options.case_insensitive)
+        .cg(options.captures);
+    let regex = match options.engine {
+        Engine::Vm => builder.pike_vm(),
+        Engine::Jit => builder.pike_jit(),
+        Engine::Auto => builder.pike_jit().or_else(|_| builder.pike_vm()),
+    };
+    regex.map_err(|err| format!("invalid pattern {:?}: {err}", options.pattern))
+}
+
+fn read(file: &str) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    if file == "-" {
+        io::stdin().lock().read_to_end(&mut bytes)?;
+    } else {
+        bytes = std::fs::read(file)?;
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Prints the matches of the regex in the haystack, and returns whether there
+/// was any.
+fn grep(
+    regex: &Regex,
+    options: &Options,
+    prefix: Option<&str>,
+    haystack: &str,
+    out: &mut impl Write,
+) -> io::Result<bool> {
+    let lines = haystack
+        .split_inclusive('\n')
+        .map(|line| line.strip_suffix('\n').unwrap_or(line));
+    if options.count {
+        let count = lines.filter(|line| regex.is_match(*line)).count();
+        if let Some(prefix) = prefix {
+            write!(out, "{prefix}:")?;
+        }
+        writeln!(out, "{count}")?;
+        return Ok(count > 0);
+    }
+    let mut matched = false;
+    for (number, line) in lines.enumerate() {
+        let line_prefix = |out: &mut dyn Write| {
+            if let Some(prefix) = prefix {
+                write!(out, "{prefix}:")?;
+            }
+            if options.line_numbers {
+                write!(out, "{}:", number + 1)?;
+            }
+            Ok::<_, io::Error>(())
+        };
+        if options.captures {
+            for caps in regex.find_all_captures(line) {
+                matched = true;
+                line_prefix(out)?;
+                // The groups, or the whole match if there are none.
+                let first = if caps.group_len() > 1 { 1 } else { 0 };
+                for index in first..caps.group_len() {
+                    if index > first {
+                        out.write_all(b"\t")?;
+                    }
+                    if let Some(group) = caps.get(index) {
+                        out.write_all(group.as_str().as_bytes())?;
+                    }
+                }
+                writeln!(out)?;
+            }
+        } else if options.only_matching {
+            for found in regex.find_all(line) {
+                matched = true;
+                line_prefix(out)?;
+                writeln!(out, "{}", found.as_str())?;
+            }
+        } else if regex.is_match(line) {
+            matched = true;
+            line_prefix(out)?;
+            writeln!(out, "{line}")?;
+        }
+    }
+    Ok(matched)
+}
+
+fn run(options: &Options) -> Result<ExitCode, String> {
+    let regex = build(options)?;
+    let files = if options.files.is_empty() {
+        vec!["-".to_string()]
+    } else {
+        options.files.clone()
+    };
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut matched = false;
+    let mut failed = false;
+    for file in &files {
+        let haystack = match read(file) {
+            Ok(haystack) => haystack,
+            Err(err) => {
+                eprintln!("gregex-grep: {file}: {err}");
+                failed = true;
+                continue;
+            }
+        };
+        let prefix = (files.len() > 1).then_some(file.as_str());
+        match grep(&regex, options, prefix, &haystack, &mut out) {
+            Ok(found) => matched |= found,
+            // The reader is gone (e.g. `| head`), nothing more to do.
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => return Ok(ExitCode::SUCCESS),
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    match out.flush() {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => return Err(err.to_string()),
+        _ => {}
+    }
+    Ok(if failed {
+        ExitCode::from(2)
+    } else if matched {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    })
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("gregex-grep: {err}\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    run(&options).unwrap_or_else(|err| {
+        eprintln!("gregex-grep: {err}");
+        ExitCode::from(2)
+    })
+}
@@ -0,0 +1,230 @@
+//! A micro-benchmark comparing the engines on a pattern and a haystack.
+//!
+//! This is meant to quickly answer whether an engine is faster than another
+//! on a given input (see the `:bench` command of the REPL). For serious
+//! measurements, prefer the criterion benchmarks of the crate. Since the
+//! matches of every engine are compared, it also doubles as a correctness
+//! probe.
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::regex::EngineWithState;
+use crate::util::{BuildTimings, ExecTimings, Input, Match, Span};
+use crate::{Builder, EngineKind, Regex};
+
+/// The measurements of one engine, see [`compare`].
+#[derive(Debug, Clone)]
+pub struct EngineReport {
+    pub engine: EngineKind,
+    /// Total time of the timed iterations.
+    pub total: Duration,
+    /// Average time per match, if there is any match.
+    pub per_match: Option<Duration>,
+    /// Number of matches found in the haystack.
+    pub match_count: usize,
+    /// Whether this engine found the same matches as the first one of the
+    /// report, and its other searches agree with them: `is_match` is true iff
+    /// there is a match, and `find` and `find_captures` report the first one.
+    pub consistent: bool,
+    /// How long building the regex took, see [`crate::Builder::timings`].
+    pub build: BuildTimings,
+    /// The phases of the first search of the regex for captures, which also
+    /// allocates its state, see [`crate::Regex::find_captures_timed`].
+    pub first_search: ExecTimings,
+}
+
+/// The result of [`compare`].
+#[derive(Debug, Clone)]
+pub struct ComparisonReport {
+    pub pattern: String,
+    pub haystack_len: usize,
+    pub iterations: usize,
+    /// The engines which could be built, in the order of [`EngineKind::ALL`].
+    pub engines: Vec<EngineReport>,
+    /// The engines which could not be built, with the reason.
+    pub unavailable: Vec<(EngineKind, String)>,
+}
+
+impl ComparisonReport {
+    /// Whether every engine found the same matches.
+    pub fn is_consistent(&self) -> bool {
+        self.engines.iter().all(|report| report.consistent)
+    }
+
+    /// The report as tab-separated values, with a header line, one line per
+    /// engine, and durations in nanoseconds. Meant to be consumed by scripts.
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::from(
+            "engine\ttotal_ns\tper_match_ns\tbuild_ns\tfirst_search_ns\tmatches\tconsistent\n",
+        );
+        for report in &self.engines {
+            let per_match = report
+                .per_match
+                .map_or(String::from("-"), |d| d.as_nanos().to_string());
+            tsv.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                report.engine.name(),
+                report.total.as_nanos(),
+                per_match,
+                report.build.total.as_nanos(),
+                report.first_search.total.as_nanos(),
+                report.match_count,
+                report.consistent
+            ));
+        }
+        tsv
+    }
+}
+
+/// Displays the report as a table, for humans.
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "pattern {:?} on {} bytes, {} iterations",
+            self.pattern, self.haystack_len, self.iterations
+        )?;
+        writeln!(
+            f,
+            "{:<20} {:>14} {:>14} {:>10}  consistent",
+            "engine", "total", "per match", "matches"
+        )?;
+        for report in &self.engines {
+            let per_match = report
+                .per_match
+                .map_or(String::from("-"), |d| format!("{d:.2?}"));
+            writeln!(
+                f,
+                "{:<20} {:>14} {:>14} {:>10}  {}",
+                report.engine.name(),
+                format!("{:.2?}", report.total),
+                per_match,
+                report.match_count,
+                if report.consistent { "yes" } else { "NO" }
+            )?;
+        }
+        for (engine, reason) in &self.unavailable {
+            writeln!(f, "{:<20} unavailable: {reason}", engine.name())?;
+        }
+        if self.engines.is_empty() {
+            return Ok(());
+        }
+        writeln!(
+            f,
+            "{:<20} {:>11} {:>11} {:>11} {:>11}   {:>11} {:>11} {:>11}",
+            "build/first search",
+            "parse",
+            "compile",
+            "assemble",
+            "total",
+            "setup",
+            "scan",
+            "extract"
+        )?;
+        for report in &self.engines {
+            let (build, search) = (&report.build, &report.first_search);
+            writeln!(
+                f,
+                "{:<20} {:>11} {:>11} {:>11} {:>11}   {:>11} {:>11} {:>11}",
+                report.engine.name(),
+                format!("{:.2?}", build.parse),
+                format!("{:.2?}", build.compile),
+                format!("{:.2?}", build.assemble),
+                format!("{:.2?}", build.total),
+                format!("{:.2?}", search.setup),
+                format!("{:.2?}", search.scan),
+                format!("{:.2?}", search.extract),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds every available engine for the pattern (without specialized
+/// engines, see [`Builder::specialize`]) and times `iterations` searches of
+/// all the matches in the haystack with each of them, after a warmup. The
+/// state of each engine is reused across iterations. The build and the first
+/// search of each regex are timed by phase.
+pub fn compare(pattern: &str, haystack: &str, iterations: usize) -> ComparisonReport {
+    let builder = Builder::new(pattern).specialize(false).timings(true);
+    let iterations = iterations.max(1);
+    let warmup = iterations.div_ceil(10);
+    let mut reference: Option<Vec<Span>> = None;
+    let mut engines = Vec::new();
+    let mut unavailable = Vec::new();
+    for engine in EngineKind::ALL {
+        let re = match builder.build(engine) {
+            Ok(re) => re,
+            Err(err) => {
+                unavailable.push((engine, err.to_string()));
+                continue;
+            }
+        };
+        let mut first_search = ExecTimings::default();
+        re.find_captures_timed(haystack, Some(&mut first_search));
+        let mut imp = re.engine_with_state();
+        let mut spans = Vec::new();
+        for_each_match(&mut imp, haystack, |span| spans.push(span));
+        for _ in 1..warmup {
+            for_each_match(&mut imp, haystack, |_| ());
+        }
+        let start = Instant::now();
+        for _ in 0..iterations {
+            for_each_match(&mut imp, haystack, |_| ());
+        }
+        let total = start.elapsed();
+
+        let match_count = spans.len();
+        let consistent = searches_agree(&re, haystack, spans.first())
+            && match &reference {
+                Some(reference) => {
+                    reference.len() == spans.len()
+                        && reference
+                            .iter()
+                            .zip(&spans)
+                            .all(|(a, b)| a.from == b.from && a.to == b.to)
+                }
+                None => true,
+            };
+        if reference.is_none() {
+            reference = Some(spans);
+        }
+        let per_match = (match_count > 0)
+            .then(|| total / u32::try_from(iterations * match_count).unwrap_or(u32::MAX));
+        engines.push(EngineReport {
+            engine,
+            total,
+            per_match,
+            match_count,
+            consistent,
+            build: re.build_timings().unwrap_or_default(),
+            first_search,
+        });
+    }
+    ComparisonReport {
+        pattern: pattern.to_string(),
+        haystack_len: haystack.len(),
+        iterations,
+        engines,
+        unavailable,
+    }
+}
+
+/// Whether the one-shot searches of the regex agree with the first match of
+/// its `find_all`, if any.
+fn searches_agree(re: &Regex, haystack: &str, first: Option<&Span>) -> bool {
+    let bounds = |m: Match<'_>| (m.start(), m.end());
+    let first = first.map(|span| (span.from, span.to));
+    re.is_match(haystack) == first.is_some()
+        && re.find(haystack).map(bounds) == first
+        && re.find_captures(haystack).map(|caps| bounds(caps.group0())) == first
+}
+
+/// Same as [`crate::Regex::find_all`], but reusing the state.
+fn for_each_match(imp: &mut EngineWithState<'_>, haystack: &str, mut f: impl FnMut(Span)) {
+    let mut input = Input::new(haystack);
+    let mut spans = [Span::invalid()];
+    while imp.exec_next(&mut input, &mut spans) {
+        f(spans[0]);
+    }
+}
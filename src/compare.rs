@@ -0,0 +1,191 @@
+//! Where two regexes disagree over a corpus, e.g. the old and new versions of
+//! a pattern being migrated.
+//!
+//! [`diff_patterns`] runs both regexes over each haystack, compares the spans
+//! of all their matches (group 0 only: the groups of a migrated pattern
+//! often move), and reports the haystacks where they differ.
+//!
+//! ```rust
+//! use gregex::Regex;
+//! use gregex::compare::{DiffLimits, diff_patterns};
+//!
+//! let old = Regex::pike_vm(r"\d+").unwrap();
+//! let new = Regex::pike_vm(r"\d{1,3}").unwrap();
+//! let corpus = ["a 12 b", "1234", "none"];
+//! let diff = diff_patterns(&old, &new, corpus, DiffLimits::default());
+//! assert_eq!((diff.identical, diff.different), (2, 1));
+//! let divergence = &diff.divergences[0];
+//! assert_eq!((divergence.haystack_index, divergence.position), (1, 0));
+//! ```
+use std::fmt;
+
+use crate::Regex;
+use crate::regex::EngineWithState;
+use crate::util::{Input, Span};
+
+/// Bounds the work of [`diff_patterns`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffLimits {
+    /// The maximum number of haystacks compared, the others are ignored.
+    /// None to compare all of them (default).
+    pub max_haystacks: Option<usize>,
+    /// The maximum number of divergences recorded (default: 10). The
+    /// haystacks with a divergence past this limit are still counted.
+    pub max_divergences: usize,
+}
+
+impl Default for DiffLimits {
+    fn default() -> Self {
+        Self {
+            max_haystacks: None,
+            max_divergences: 10,
+        }
+    }
+}
+
+/// The first difference between the matches of two regexes in a haystack.
+#[derive(Debug, Clone, Copy)]
+pub struct Divergence {
+    /// The index of the haystack in the corpus.
+    pub haystack_index: usize,
+    /// Where the differing matches start, the smaller of the two starts.
+    pub position: usize,
+    /// The index of the differing matches among the matches of the haystack.
+    pub match_index: usize,
+    /// The match of the old regex, None if it found fewer matches.
+    pub old: Option<Span>,
+    /// The match of the new regex, None if it found fewer matches.
+    pub new: Option<Span>,
+}
+
+/// The result of [`diff_patterns`].
+#[derive(Debug, Clone)]
+pub struct PatternDiff {
+    pub old_pattern: String,
+    pub new_pattern: String,
+    /// The number of haystacks compared.
+    pub haystacks: usize,
+    /// The number of haystacks where both regexes found the same matches.
+    pub identical: usize,
+    /// The number of haystacks where they found different matches.
+    pub different: usize,
+    /// Among the different ones, the number of haystacks where they found a
+    /// different number of matches.
+    pub count_differs: usize,
+    /// The first difference in each of the first different haystacks, in the
+    /// order of the corpus, see [`DiffLimits::max_divergences`].
+    pub divergences: Vec<Divergence>,
+}
+
+impl PatternDiff {
+    /// Whether both regexes found the same matches in every haystack.
+    pub fn is_identical(&self) -> bool {
+        self.different == 0
+    }
+}
+
+/// Displays the diff as a report, for humans.
+impl fmt::Display for PatternDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "old pattern {:?}", self.old_pattern)?;
+        writeln!(f, "new pattern {:?}", self.new_pattern)?;
+        writeln!(
+            f,
+            "{} haystacks: {} identical, {} different ({} with a different number of matches)",
+            self.haystacks, self.identical, self.different, self.count_differs
+        )?;
+        let span = |span: Option<Span>| match span {
+            Some(span) => format!("{}..{}", span.from, span.to),
+            None => String::from("no match"),
+        };
+        for divergence in &self.divergences {
+            writeln!(
+                f,
+                "haystack {} at {}: match {} is {} (old) vs {} (new)",
+                divergence.haystack_index,
+                divergence.position,
+                divergence.match_index,
+                span(divergence.old),
+                span(divergence.new)
+            )?;
+        }
+        let unreported = self.different - self.divergences.len();
+        if unreported > 0 {
+            writeln!(f, "... and {unreported} more different haystacks")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the matches of `find_all` of the two regexes over each haystack,
+/// see the [module documentation](self). The state of each regex is reused
+/// across the haystacks.
+pub fn diff_patterns<'h>(
+    old: &Regex,
+    new: &Regex,
+    haystacks: impl IntoIterator<Item = &'h str>,
+    limits: DiffLimits,
+) -> PatternDiff {
+    let mut diff = PatternDiff {
+        old_pattern: old.pattern().to_string(),
+        new_pattern: new.pattern().to_string(),
+        haystacks: 0,
+        identical: 0,
+        different: 0,
+        count_differs: 0,
+        divergences: Vec::new(),
+    };
+    let mut old_imp = old.engine_with_state();
+    let mut new_imp = new.engine_with_state();
+    let mut old_spans = Vec::new();
+    let mut new_spans = Vec::new();
+    let haystacks = haystacks
+        .into_iter()
+        .take(limits.max_haystacks.unwrap_or(usize::MAX));
+    for (haystack_index, haystack) in haystacks.enumerate() {
+        diff.haystacks += 1;
+        all_matches(&mut old_imp, haystack, &mut old_spans);
+        all_matches(&mut new_imp, haystack, &mut new_spans);
+        let Some(match_index) = first_difference(&old_spans, &new_spans) else {
+            diff.identical += 1;
+            continue;
+        };
+        diff.different += 1;
+        if old_spans.len() != new_spans.len() {
+            diff.count_differs += 1;
+        }
+        if diff.divergences.len() < limits.max_divergences {
+            let old = old_spans.get(match_index).copied();
+            let new = new_spans.get(match_index).copied();
+            let position = [old, new].into_iter().flatten().map(|span| span.from).min();
+            diff.divergences.push(Divergence {
+                haystack_index,
+                position: position.unwrap_or(0),
+                match_index,
+                old,
+                new,
+            });
+        }
+    }
+    diff
+}
+
+/// Collects the spans of all the matches, like [`Regex::find_all`].
+fn all_matches(imp: &mut EngineWithState<'_>, haystack: &str, spans: &mut Vec<Span>) {
+    spans.clear();
+    let mut input = Input::new(haystack);
+    let mut captures = [Span::invalid()];
+    while imp.exec_next(&mut input, &mut captures) {
+        spans.push(captures[0]);
+    }
+}
+
+/// The index of the first match which differs, if any.
+fn first_difference(old: &[Span], new: &[Span]) -> Option<usize> {
+    let same = |(a, b): (&Span, &Span)| a.from == b.from && a.to == b.to;
+    match old.iter().zip(new).position(|pair| !same(pair)) {
+        Some(index) => Some(index),
+        None if old.len() != new.len() => Some(old.len().min(new.len())),
+        None => None,
+    }
+}
@@ -0,0 +1,444 @@
+//! A facade with the most used API of the [`regex`](https://docs.rs/regex)
+//! crate, to try gregex in code written for it with
+//! `use gregex::compat as regex;`.
+//!
+//! The types and methods have the same names and signatures as in the regex
+//! crate, and are thin wrappers over the native API. Each [`Regex`] uses the
+//! JIT when it is available, and the Pike VM otherwise.
+//!
+//! # Differences with the regex crate
+//!
+//! - [`Error`] has a single kind, [`Error::Syntax`], also used for patterns
+//!   which are valid for the regex crate but not supported by gregex.
+//! - [`Regex`] is not `Clone`.
+//! - [`Regex::find_at`] and [`Regex::captures_at`] panic if `start` is not a
+//!   char boundary of the haystack.
+//! - Only a subset of the API is provided: there is no `RegexBuilder`,
+//!   `RegexSet` or `bytes` module.
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::{Index, Range};
+use std::str::FromStr;
+
+pub use crate::replace::NoExpand;
+use crate::replace::{self, no_expansion_str};
+use crate::util;
+use crate::{Builder, regex};
+
+/// An error which occurred while building a [`Regex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The pattern is invalid, or uses a feature gregex doesn't support.
+    Syntax(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Escapes all the meta characters of the text, so that it can be matched
+/// literally.
+pub fn escape(text: &str) -> String {
+    regex_syntax::escape(text)
+}
+
+/// A compiled regular expression, see the [module documentation](self).
+pub struct Regex {
+    inner: regex::Regex,
+}
+
+impl Regex {
+    pub fn new(re: &str) -> Result<Regex, Error> {
+        let builder = Builder::new(re);
+        let syntax = |err: &dyn fmt::Display| Error::Syntax(err.to_string());
+        let inner = builder
+            .pike_jit()
+            .or_else(|_| builder.pike_vm())
+            .map_err(|err| syntax(&err))?;
+        Ok(Regex { inner })
+    }
+
+    /// Returns the pattern of this regex.
+    pub fn as_str(&self) -> &str {
+        self.inner.pattern()
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.inner.is_match(haystack)
+    }
+
+    pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        self.find_at(haystack, 0)
+    }
+
+    pub fn find_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Match<'h>> {
+        self.inner.find_at(haystack, start).map(Match::from)
+    }
+
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches {
+            regex: self,
+            haystack,
+            inner: self.inner.find_all(haystack),
+        }
+    }
+
+    pub fn captures<'h>(&self, haystack: &'h str) -> Option<Captures<'h>> {
+        self.captures_at(haystack, 0)
+    }
+
+    pub fn captures_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Captures<'h>> {
+        self.inner
+            .captures_at(haystack, start)
+            .map(|inner| Captures { inner })
+    }
+
+    pub fn captures_iter<'r, 'h>(&'r self, haystack: &'h str) -> CaptureMatches<'r, 'h> {
+        CaptureMatches {
+            regex: self,
+            inner: self.inner.find_all_captures(haystack),
+        }
+    }
+
+    /// Returns the number of capture groups, including the overall match.
+    pub fn captures_len(&self) -> usize {
+        self.inner.group_len()
+    }
+
+    /// Returns the name of every capture group, in order, None for the groups
+    /// without names (always the overall match).
+    pub fn capture_names(&self) -> impl ExactSizeIterator<Item = Option<&str>> + '_ {
+        self.inner.group_names()
+    }
+
+    pub fn split<'r, 'h>(&'r self, haystack: &'h str) -> Split<'r, 'h> {
+        Split {
+            regex: self,
+            inner: self.inner.split(haystack),
+        }
+    }
+
+    /// Replaces the leftmost match. See [`Regex::replace_all`].
+    pub fn replace<'h, R: Replacer>(&self, haystack: &'h str, rep: R) -> Cow<'h, str> {
+        self.replacen(haystack, 1, rep)
+    }
+
+    /// Replaces every non-overlapping match with the replacement, see
+    /// [`Replacer`]. The haystack is borrowed if there is no match.
+    pub fn replace_all<'h, R: Replacer>(&self, haystack: &'h str, rep: R) -> Cow<'h, str> {
+        self.replacen(haystack, 0, rep)
+    }
+
+    /// Replaces the first `limit` matches, or all of them if `limit` is 0.
+    /// See [`Regex::replace_all`].
+    pub fn replacen<'h, R: Replacer>(
+        &self,
+        haystack: &'h str,
+        limit: usize,
+        rep: R,
+    ) -> Cow<'h, str> {
+        self.inner.replacen(haystack, limit, Native(rep))
+    }
+}
+
+impl FromStr for Regex {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Regex, Error> {
+        Regex::new(s)
+    }
+}
+
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Regex").field(&self.as_str()).finish()
+    }
+}
+
+/// A match, borrowing the haystack it was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.range()]
+    }
+}
+
+impl<'h> From<util::Match<'h>> for Match<'h> {
+    fn from(m: util::Match<'h>) -> Self {
+        Match {
+            haystack: m.subject,
+            start: m.start(),
+            end: m.end(),
+        }
+    }
+}
+
+/// The capture groups of a match.
+#[derive(Debug, Clone)]
+pub struct Captures<'h> {
+    inner: util::Captures<'h>,
+}
+
+impl<'h> Captures<'h> {
+    /// Returns the match of the group, or None if it did not participate in
+    /// the match.
+    pub fn get(&self, i: usize) -> Option<Match<'h>> {
+        self.inner.get(i).map(Match::from)
+    }
+
+    /// Returns the match of the group with this name, or None if it did not
+    /// participate in the match, or if there is no such group.
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        self.inner.name(name).map(Match::from)
+    }
+
+    /// Returns the match of every group, in order, None for the ones which
+    /// did not participate in the match.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Option<Match<'h>>> + '_ {
+        self.inner.iter().map(|m| m.map(Match::from))
+    }
+
+    /// Returns the text of the overall match and of the `N` groups, see
+    /// [`util::Captures::extract`].
+    pub fn extract<const N: usize>(&self) -> (&'h str, [&'h str; N]) {
+        self.inner.extract()
+    }
+
+    /// Returns the number of groups, including the overall match.
+    // Same API as the regex crate, which has no is_empty.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.inner.group_len()
+    }
+
+    /// Appends the replacement to `dst`, with every reference to a group
+    /// (`$1` or `${1}`) replaced by its match, and `$$` by `$`. Like in the
+    /// regex crate, a reference is the longest run of letters, digits and
+    /// underscores after the `$`, referring to a group by number if it is
+    /// one, and by name otherwise. References to groups which don't exist, or
+    /// did not participate, are replaced by nothing.
+    pub fn expand(&self, replacement: &str, dst: &mut String) {
+        self.inner.expand(replacement, dst);
+    }
+}
+
+/// Returns the match of the group, and panics if it did not participate.
+impl Index<usize> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        &self.inner[i]
+    }
+}
+
+/// Returns the match of the group with this name, and panics if it did not
+/// participate, or if there is no such group.
+impl Index<&str> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        &self.inner[name]
+    }
+}
+
+/// Iterator over the matches of a haystack, see [`Regex::find_iter`]. Like
+/// in the regex crate, an empty match right after the previous match is
+/// skipped (e.g. `a*` in "ab" matches at 0..1, and at 2..2, but not at 1..1),
+/// as [`regex::Regex::find_all`] does.
+pub struct Matches<'r, 'h> {
+    regex: &'r Regex,
+    haystack: &'h str,
+    inner: regex::AllMatch<'r, 'h>,
+}
+
+impl<'h> Iterator for Matches<'_, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        self.inner.next().map(Match::from)
+    }
+}
+
+impl fmt::Debug for Matches<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Matches")
+            .field("regex", self.regex)
+            .field("haystack", &self.haystack)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Iterator over the captures of the matches of a haystack, see
+/// [`Regex::captures_iter`]. The matches are the ones of [`Matches`].
+pub struct CaptureMatches<'r, 'h> {
+    regex: &'r Regex,
+    inner: regex::AllCaptures<'r, 'h>,
+}
+
+impl<'h> Iterator for CaptureMatches<'_, 'h> {
+    type Item = Captures<'h>;
+
+    fn next(&mut self) -> Option<Captures<'h>> {
+        self.inner.next().map(|inner| Captures { inner })
+    }
+}
+
+impl fmt::Debug for CaptureMatches<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaptureMatches")
+            .field("regex", self.regex)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Iterator over the substrings between the matches, see [`Regex::split`].
+pub struct Split<'r, 'h> {
+    regex: &'r Regex,
+    inner: regex::Split<'r, 'h>,
+}
+
+impl<'h> Iterator for Split<'_, 'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<&'h str> {
+        self.inner.next()
+    }
+}
+
+impl fmt::Debug for Split<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Split")
+            .field("regex", self.regex)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A replacement for the matches of [`Regex::replace_all`]. Strings are
+/// expanded with [`Captures::expand`], closures are called with the captures
+/// of each match, and [`NoExpand`] is used as is.
+pub trait Replacer {
+    /// Appends the replacement of the match to `dst`.
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String);
+
+    /// Returns the replacement if it doesn't depend on the match, in which
+    /// case the capture groups are not computed.
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        None
+    }
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl Replacer for &String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl Replacer for Cow<'_, str> {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&Captures<'_>) -> T,
+    T: AsRef<str>,
+{
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str((*self)(caps).as_ref());
+    }
+}
+
+impl Replacer for NoExpand<'_> {
+    fn replace_append(&mut self, _caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(self.0);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self.0))
+    }
+}
+
+/// A [`Replacer`] of this module, used by the native replacements, which give
+/// it the captures of this module.
+struct Native<R>(R);
+
+impl<R: Replacer> replace::Replacer for Native<R> {
+    fn replace_append(&mut self, caps: &util::Captures<'_>, dst: &mut String) {
+        let caps = Captures {
+            inner: caps.clone(),
+        };
+        self.0.replace_append(&caps, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        self.0.no_expansion()
+    }
+}
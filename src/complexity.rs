@@ -0,0 +1,241 @@
+//! Estimates of how much work a pattern costs per char of the haystack.
+//!
+//! The engines never backtrack, so the nested quantifiers feared with the
+//! backtracking engines (e.g. `(a+)+`) are harmless here. What costs is the
+//! number of threads alive at once, each of which runs at every char: the
+//! large alternations under a repetition, the counted repetitions (which
+//! the compiler unrolls, see [`mod@crate::validate`]), and the large classes,
+//! whose ranges are looked up for every char a thread sees.
+//!
+//! [`Regex::complexity`](crate::Regex::complexity) measures these on the
+//! compiled bytecode. The numbers are meant to compare patterns, not to
+//! predict search times, and the notes point at the parts of the pattern
+//! which weigh the most. [`crate::lint()`] reports the notes of the patterns
+//! likely to be slow as warnings.
+use std::collections::HashMap;
+use std::fmt;
+
+use regex_syntax::hir::{Hir, HirKind};
+
+use crate::regex::Config;
+use crate::thompson::bytecode::{Bytecode, Compiler, Instruction::*};
+use crate::util::Char;
+
+/// A counted repetition is noted from this many copies of its expression.
+const UNROLLED_REPETITION_NOTE: u32 = 100;
+/// A class is noted from this many ranges, e.g. `\w` has about 770.
+const LARGE_CLASS_NOTE: usize = 100;
+/// An alternation under a repetition is noted from this many branches.
+const REPEATED_ALTERNATION_NOTE: usize = 8;
+/// See [`ComplexityEstimate::likely_slow`].
+const SLOW_PER_CHAR_COST: usize = 10_000;
+const SLOW_MAX_THREADS: usize = 1_000;
+
+/// The cost of a pattern, see [`crate::complexity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComplexityEstimate {
+    /// The maximum number of threads alive between two chars: one per
+    /// consuming instruction and Accept reachable from the start (the
+    /// barriers keep a single thread per instruction), and the one starting
+    /// a match at the next char. The queues of the engines never need more.
+    pub max_threads_bound: usize,
+    /// The work of a step when every thread is alive: one comparison per
+    /// reachable instruction consuming a char, and one per range for the
+    /// classes, which the Pike VM scans in order.
+    pub per_char_cost_estimate: usize,
+    /// The number of instructions of the bytecode.
+    pub bytecode_len: usize,
+    /// The main contributors to the estimates, in the order of the pattern
+    /// for the repetitions, then of the bytecode.
+    pub notes: Vec<ComplexityNote>,
+}
+
+impl ComplexityEstimate {
+    /// Whether the searches are likely to be slow, with a step costing
+    /// 10 000 comparisons or more, or 1 000 threads or more alive at once.
+    /// A single large class (e.g. `\w+`) is not. [`crate::lint()`] only
+    /// reports the notes of these patterns.
+    pub fn likely_slow(&self) -> bool {
+        self.per_char_cost_estimate >= SLOW_PER_CHAR_COST
+            || self.max_threads_bound >= SLOW_MAX_THREADS
+    }
+}
+
+/// A part of a pattern which makes it costly, see [`ComplexityEstimate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComplexityNote {
+    /// A counted repetition, such as `\w{500}` or `a{2,500}`, compiled to a
+    /// copy of its expression per repetition, into the given number of
+    /// instructions.
+    UnrolledRepetition { times: u32, instructions: usize },
+    /// A class with many ranges, such as `\w`, looked up by a thread at every
+    /// char, with the number of copies of it in the bytecode.
+    LargeClass { ranges: usize, copies: usize },
+    /// An alternation under an unbounded repetition, such as
+    /// `(?:foo|bar|baz|...)+`, whose branches may all keep a thread alive.
+    RepeatedAlternation { branches: usize },
+}
+
+impl fmt::Display for ComplexityNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComplexityNote::UnrolledRepetition {
+                times,
+                instructions,
+            } => write!(
+                f,
+                "Bounded repetition unrolled {times}x, into {instructions} instructions"
+            ),
+            ComplexityNote::LargeClass { ranges, copies: 1 } => {
+                write!(f, "Class with {ranges} ranges consulted on every char")
+            }
+            ComplexityNote::LargeClass { ranges, copies } => write!(
+                f,
+                "Class with {ranges} ranges consulted on every char, {copies} times"
+            ),
+            ComplexityNote::RepeatedAlternation { branches } => write!(
+                f,
+                "Alternation of {branches} branches under a repetition, each of which may keep a thread alive"
+            ),
+        }
+    }
+}
+
+/// The estimate for the bytecode compiled from the hir with the config.
+pub(crate) fn estimate(hir: &Hir, bytecode: &Bytecode, config: &Config) -> ComplexityEstimate {
+    let instructions = &bytecode.instructions;
+    let reachable = reachable(bytecode);
+    let in_loop = in_loop(bytecode);
+    let mut notes = unrolled_repetitions(hir, config);
+    let mut max_threads_bound = 1usize;
+    let mut per_char_cost_estimate = 0usize;
+    let mut large_classes: Vec<(&[(Char, Char)], usize)> = Vec::new();
+    let mut large_class_index = HashMap::new();
+    for (pc, instruction) in instructions.iter().enumerate() {
+        if !reachable[pc] {
+            continue;
+        }
+        let class: &[(Char, Char)] = match instruction {
+            Consume(_) => &[],
+            ConsumeClass(class) => class,
+            ConsumeOutlined(id) => &bytecode.outlined_classes[*id],
+            ForkN(branches) if in_loop[pc] && branches.len() >= REPEATED_ALTERNATION_NOTE => {
+                notes.push(ComplexityNote::RepeatedAlternation {
+                    branches: branches.len(),
+                });
+                continue;
+            }
+            Accept(_) => {
+                max_threads_bound = max_threads_bound.saturating_add(1);
+                continue;
+            }
+            _ => continue,
+        };
+        max_threads_bound = max_threads_bound.saturating_add(1);
+        per_char_cost_estimate = per_char_cost_estimate.saturating_add(class.len().max(1));
+        if class.len() >= LARGE_CLASS_NOTE {
+            let index = *large_class_index.entry(class).or_insert_with(|| {
+                large_classes.push((class, 0));
+                large_classes.len() - 1
+            });
+            large_classes[index].1 += 1;
+        }
+    }
+    notes.extend(
+        large_classes
+            .into_iter()
+            .map(|(class, copies)| ComplexityNote::LargeClass {
+                ranges: class.len(),
+                copies,
+            }),
+    );
+    ComplexityEstimate {
+        max_threads_bound,
+        per_char_cost_estimate,
+        bytecode_len: instructions.len(),
+        notes,
+    }
+}
+
+/// The notes about the counted repetitions of the hir, in the order of the
+/// pattern. The nesting depth is only bounded by the config, so the tree is
+/// walked with a stack.
+fn unrolled_repetitions(hir: &Hir, config: &Config) -> Vec<ComplexityNote> {
+    let mut notes = Vec::new();
+    let mut stack = vec![hir];
+    while let Some(hir) = stack.pop() {
+        match hir.kind() {
+            HirKind::Repetition(repetition) => {
+                let times = repetition.max.unwrap_or(repetition.min);
+                // Without the final Accept
+                let instructions = Compiler::instruction_count(hir, config) - 1;
+                if times >= UNROLLED_REPETITION_NOTE && instructions >= times as usize {
+                    notes.push(ComplexityNote::UnrolledRepetition {
+                        times,
+                        instructions,
+                    });
+                }
+                stack.push(&repetition.sub);
+            }
+            HirKind::Capture(capture) => stack.push(&capture.sub),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+                stack.extend(hirs.iter().rev());
+            }
+            _ => {}
+        }
+    }
+    notes
+}
+
+/// Whether a thread can reach each instruction from the start. A class
+/// without any char (e.g. `[^\s\S]`) never lets a thread through.
+fn reachable(bytecode: &Bytecode) -> Vec<bool> {
+    let instructions = &bytecode.instructions;
+    let mut reachable = vec![false; instructions.len()];
+    let mut stack = vec![0];
+    while let Some(pc) = stack.pop() {
+        if pc >= instructions.len() || reachable[pc] {
+            continue;
+        }
+        reachable[pc] = true;
+        match &instructions[pc] {
+            Fork2(a, b) => stack.extend([*a, *b]),
+            ForkN(branches) => stack.extend(branches.iter()),
+            Jmp(target) => stack.push(*target),
+            Accept(_) => {}
+            ConsumeClass(class) if class.is_empty() => {}
+            ConsumeOutlined(id) if bytecode.outlined_classes[*id].is_empty() => {}
+            _ => stack.push(pc + 1),
+        }
+    }
+    reachable
+}
+
+/// Whether each instruction is in the body of a loop. The compiler lays out
+/// the body of a loop between its head and the jump (or fork) back to it, so
+/// the bodies are the ranges spanned by the backward jumps.
+fn in_loop(bytecode: &Bytecode) -> Vec<bool> {
+    let instructions = &bytecode.instructions;
+    // How many more loops start than end at each instruction.
+    let mut starts = vec![0isize; instructions.len() + 1];
+    for (pc, instruction) in instructions.iter().enumerate() {
+        let targets: &[usize] = match instruction {
+            Fork2(a, b) => &[*a, *b],
+            ForkN(branches) => branches,
+            Jmp(target) => &[*target],
+            _ => &[],
+        };
+        for &target in targets.iter().filter(|&&target| target <= pc) {
+            starts[target] += 1;
+            starts[pc + 1] -= 1;
+        }
+    }
+    let mut loops = 0;
+    starts[..instructions.len()]
+        .iter()
+        .map(|start| {
+            loops += start;
+            loops > 0
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+//! A haystack preprocessed once, and searched by many regexes.
+//!
+//! A [`Document`] records where the newlines of its text are, and how many
+//! times each byte occurs in it. The searches of a [`crate::Regex`] in a
+//! document use them to skip work the plain searches redo for every pattern:
+//!
+//! - The prefilter (see [`crate::Regex::required_bytes`]) rejects the whole
+//!   document at once when none of the required bytes occurs in it, and
+//!   otherwise only looks for the ones which do.
+//! - Patterns whose matches all start at the start of a line (e.g.
+//!   `(?m)^\w+:`), or which only match empty at the end of a line (e.g.
+//!   `(?m)$`), are only run at these positions, which the newline index gives
+//!   without scanning the text.
+//!
+//! The results are always the same as the ones of the plain searches on the
+//! text of the document.
+use regex_syntax::hir::{Hir, Look};
+
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Document<'s> {
+    text: &'s str,
+    /// The positions of the `\n` bytes, in increasing order.
+    newlines: Box<[usize]>,
+    /// The number of occurrences of each byte.
+    byte_counts: Box<[usize; 256]>,
+}
+
+impl<'s> Document<'s> {
+    /// Preprocesses the text, in a single pass over its bytes.
+    pub fn new(text: &'s str) -> Self {
+        let mut newlines = Vec::new();
+        let mut byte_counts = Box::new([0; 256]);
+        for (i, byte) in text.bytes().enumerate() {
+            byte_counts[usize::from(byte)] += 1;
+            if byte == b'\n' {
+                newlines.push(i);
+            }
+        }
+        Self {
+            text,
+            newlines: newlines.into_boxed_slice(),
+            byte_counts,
+        }
+    }
+
+    pub fn text(&self) -> &'s str {
+        self.text
+    }
+
+    /// Returns the positions of the `\n` bytes of the text, in increasing
+    /// order.
+    pub fn newlines(&self) -> &[usize] {
+        &self.newlines
+    }
+
+    /// Returns the number of lines of the text, the last one being the
+    /// (possibly empty) one after the last `\n`.
+    pub fn line_count(&self) -> usize {
+        self.newlines.len() + 1
+    }
+
+    /// Returns how many times the byte occurs in the text.
+    pub fn byte_count(&self, byte: u8) -> usize {
+        self.byte_counts[usize::from(byte)]
+    }
+
+    /// Returns the positions between `from` and `to` (inclusive) where a
+    /// match of a pattern with the given anchor can be, in increasing order.
+    pub(crate) fn candidates(
+        &self,
+        anchor: LineAnchor,
+        from: usize,
+        to: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        let (first, rest): (Option<usize>, &[usize]) = match anchor {
+            LineAnchor::Start => {
+                let i = self.newlines.partition_point(|newline| newline + 1 < from);
+                ((from == 0).then_some(0), &self.newlines[i..])
+            }
+            LineAnchor::End => {
+                let i = self.newlines.partition_point(|newline| *newline < from);
+                (None, &self.newlines[i..])
+            }
+        };
+        let rest = rest.iter().map(move |newline| match anchor {
+            LineAnchor::Start => newline + 1,
+            LineAnchor::End => *newline,
+        });
+        let end = (anchor == LineAnchor::End).then_some(self.text.len());
+        first
+            .into_iter()
+            .chain(rest)
+            .chain(end)
+            .take_while(move |position| *position <= to)
+    }
+}
+
+/// Where all the matches of a pattern are relative to the lines of the
+/// haystack, when it is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LineAnchor {
+    /// Every match starts at the start of a line.
+    Start,
+    /// Every match is empty, and at the end of a line.
+    End,
+}
+
+impl LineAnchor {
+    pub(crate) fn new(hir: &Hir) -> Option<Self> {
+        let properties = hir.properties();
+        let prefix = properties.look_set_prefix();
+        let suffix = properties.look_set_suffix();
+        if prefix.contains(Look::Start) || prefix.contains(Look::StartLF) {
+            Some(LineAnchor::Start)
+        } else if properties.maximum_len() == Some(0)
+            && (suffix.contains(Look::End) || suffix.contains(Look::EndLF))
+        {
+            Some(LineAnchor::End)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Searches for the patterns whose matches end at the end of the haystack,
+//! such as `\.log$`, and are bounded in length.
+//!
+//! An unanchored search starts a thread at every char of the haystack, and
+//! each of them only finds out at the end that it doesn't match, unless it
+//! died before. Yet when every match ends at the end of the haystack (the
+//! pattern ends with `$` or `\z` on every path), and its length is at most
+//! `maximum_len` bytes, no match can start before `end - maximum_len`, where
+//! `end` is the end of the searched span. So the search starts there instead,
+//! which skips most of a long haystack.
+//!
+//! The matches are the same as the ones of the full search: the matches
+//! starting before the window don't exist, and the others are found the same
+//! way, since the engines still read the chars before the span for the
+//! assertions (e.g. the `\b` of `\bfoo$`).
+use regex_syntax::hir::{Hir, Look};
+
+use crate::regex::{EngineState, RegexEngine};
+use crate::util::{Input, Span};
+
+/// Returns the maximum length of the matches of the pattern, if they all end
+/// at the end of the haystack and their length is bounded.
+pub(crate) fn maximum_len(hir: &Hir) -> Option<usize> {
+    let properties = hir.properties();
+    if !properties.look_set_suffix().contains(Look::End) {
+        return None;
+    }
+    properties.maximum_len()
+}
+
+/// Narrows the span of the input to the window where a match ending at its
+/// end, at most `maximum_len` bytes long, can start. The anchored searches
+/// must start at the start of the span, and the invalid inputs are left to
+/// the engines.
+pub(crate) fn window(input: Input<'_>, maximum_len: usize) -> Input<'_> {
+    if input.anchored || !input.valid() || input.span.to > input.subject.len() {
+        return input;
+    }
+    let Span { from, to } = input.span;
+    let mut start = to.saturating_sub(maximum_len);
+    while !input.subject.is_char_boundary(start) {
+        start -= 1;
+    }
+    if start <= from {
+        return input;
+    }
+    input.span(Span { from: start, to })
+}
+
+/// See the [module documentation](self).
+pub(crate) struct EndAnchored {
+    /// The engine of the whole pattern, which shares its state with this one.
+    pub(crate) inner: RegexEngine,
+    pub(crate) maximum_len: usize,
+}
+
+impl EndAnchored {
+    pub(crate) fn exec(
+        &self,
+        input: Input<'_>,
+        state: &mut EngineState,
+        captures: &mut [Span],
+    ) -> bool {
+        self.inner
+            .exec(window(input, self.maximum_len), state, captures)
+    }
+}
@@ -0,0 +1,167 @@
+//! The error of the builds of the regexes, see [`Error`].
+//!
+//! Its kind tells why the build failed, e.g. to fall back to the Pike VM when
+//! the JIT can't handle the pattern or the platform:
+//!
+//! ```rust
+//! use gregex::{Builder, Error, Regex};
+//! use gregex::error::UnsupportedFeature;
+//!
+//! fn fastest(builder: &Builder) -> Result<Regex, Error> {
+//!     match builder.pike_jit() {
+//!         Err(Error::Unsupported(UnsupportedFeature::EngineUnavailable(_))) => builder.pike_vm(),
+//!         Err(Error::Jit(_)) => builder.pike_vm(),
+//!         result => result,
+//!     }
+//! }
+//!
+//! assert!(fastest(&Builder::new(r"\w+")).is_ok());
+//! assert!(matches!(fastest(&Builder::new("a(")), Err(Error::Syntax(_))));
+//! ```
+use std::error;
+use std::fmt;
+
+use crate::regex::{EngineUnavailable, PatternError, UnsupportedCgStrategy, UnsupportedMatchKind};
+use crate::thompson::bytecode::CompileError;
+
+/// An error which occurred while building a regex.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The pattern is invalid, with the part of it at fault: a syntax error
+    /// of regex-syntax, or a Unicode property class used while Unicode mode
+    /// is disabled.
+    Syntax(PatternError),
+    /// The pattern or the configuration needs something the engine doesn't
+    /// support.
+    Unsupported(UnsupportedFeature),
+    /// The pattern is over one of the [`crate::limits`] of the compiler, or
+    /// compiles to a program over the size limit of the configuration, see
+    /// [`crate::Builder::size_limit`].
+    CompileLimitExceeded(CompileError),
+    /// The JIT failed to compile the pattern, which the Pike VM may still
+    /// run, e.g. when the platform denies executable memory.
+    Jit(JitError),
+}
+
+/// Why the JIT failed to compile a pattern, see [`Error::Jit`].
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+pub use crate::thompson::pike_jit::CompileError as JitError;
+
+/// Why the JIT failed to compile a pattern, see [`Error::Jit`]. This build
+/// has no JIT, so this type has no value.
+#[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+#[derive(Debug)]
+pub enum JitError {}
+
+/// What an engine doesn't support, see [`Error::Unsupported`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum UnsupportedFeature {
+    /// The engine isn't available on this platform, or in this build.
+    EngineUnavailable(EngineUnavailable),
+    /// The engine doesn't implement the match kind of the configuration.
+    MatchKind(UnsupportedMatchKind),
+    /// The CG strategy of the JIT can't track the groups of the pattern.
+    CgStrategy(UnsupportedCgStrategy),
+    /// The pattern or the configuration is rejected by the compiler, e.g. a
+    /// pattern matching invalid UTF-8.
+    Pattern(CompileError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax(err) => err.fmt(f),
+            Error::Unsupported(feature) => feature.fmt(f),
+            Error::CompileLimitExceeded(err) => err.fmt(f),
+            Error::Jit(err) => err.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for UnsupportedFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnsupportedFeature::EngineUnavailable(err) => err.fmt(f),
+            UnsupportedFeature::MatchKind(err) => err.fmt(f),
+            UnsupportedFeature::CgStrategy(err) => err.fmt(f),
+            UnsupportedFeature::Pattern(err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Syntax(err) => err.source(),
+            Error::Unsupported(_) | Error::CompileLimitExceeded(_) => None,
+            Error::Jit(err) => err.source(),
+        }
+    }
+}
+
+impl From<PatternError> for Error {
+    fn from(err: PatternError) -> Self {
+        Error::Syntax(err)
+    }
+}
+
+impl From<CompileError> for Error {
+    fn from(err: CompileError) -> Self {
+        match err {
+            CompileError::TooManyInstructions { .. }
+            | CompileError::RepetitionTooLarge { .. }
+            | CompileError::TooManyForkBranches { .. }
+            | CompileError::TooManyOutlinedClasses { .. }
+            | CompileError::CompiledProgramTooLarge { .. } => Error::CompileLimitExceeded(err),
+            _ => Error::Unsupported(UnsupportedFeature::Pattern(err)),
+        }
+    }
+}
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+impl From<JitError> for Error {
+    fn from(err: JitError) -> Self {
+        match err {
+            JitError::CompiledProgramTooLarge { size, limit } => {
+                Error::CompileLimitExceeded(CompileError::CompiledProgramTooLarge { size, limit })
+            }
+            err => Error::Jit(err),
+        }
+    }
+}
+
+#[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+impl fmt::Display for JitError {
+    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {}
+    }
+}
+
+#[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+impl error::Error for JitError {}
+
+impl From<UnsupportedFeature> for Error {
+    fn from(feature: UnsupportedFeature) -> Self {
+        Error::Unsupported(feature)
+    }
+}
+
+impl From<EngineUnavailable> for Error {
+    fn from(err: EngineUnavailable) -> Self {
+        Error::Unsupported(UnsupportedFeature::EngineUnavailable(err))
+    }
+}
+
+impl From<UnsupportedMatchKind> for Error {
+    fn from(err: UnsupportedMatchKind) -> Self {
+        Error::Unsupported(UnsupportedFeature::MatchKind(err))
+    }
+}
+
+impl From<UnsupportedCgStrategy> for Error {
+    fn from(err: UnsupportedCgStrategy) -> Self {
+        Error::Unsupported(UnsupportedFeature::CgStrategy(err))
+    }
+}
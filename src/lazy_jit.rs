@@ -0,0 +1,126 @@
+//! Regexes which start on the Pike VM and switch to the JIT once they are
+//! used enough, see [`crate::Builder::lazy_jit`].
+//!
+//! Compiling to machine code costs more than compiling to bytecode, which
+//! only pays off over many searches. A regex built from user input is often
+//! searched a few times and then dropped, so the lazy JIT runs the Pike VM
+//! first, and only compiles the bytecode of the VM once the regex ran
+//! [`crate::Config::lazy_jit_after`] searches, or when asked to with
+//! [`crate::Regex::force_jit`].
+//!
+//! The JIT is compiled once, by the search which reaches the threshold,
+//! which waits for it. The other searches keep running the VM meanwhile. If
+//! the JIT can't compile the pattern (e.g. a possessive repetition, or
+//! [`crate::MatchKind::LeftmostLongest`]), the VM runs every search.
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::regex::{Config, EngineState, RegexEngine};
+
+/// See the [module documentation](self).
+pub(crate) struct LazyJit {
+    /// The engine running the searches until the JIT is compiled, always a
+    /// Pike VM.
+    pub(crate) vm: RegexEngine,
+    /// The JIT, or None if it failed to compile.
+    jit: OnceLock<Option<RegexEngine>>,
+    config: Config,
+    /// The searches run so far, until the JIT is compiled.
+    searches: AtomicUsize,
+}
+
+/// The states of the engines of a [`LazyJit`]. The one of the JIT is created
+/// by the first search which runs it.
+pub(crate) struct LazyJitState {
+    vm: EngineState,
+    jit: Option<EngineState>,
+}
+
+impl LazyJit {
+    pub(crate) fn new(vm: RegexEngine, config: Config) -> Self {
+        debug_assert!(matches!(vm, RegexEngine::PikeVM(_)));
+        Self {
+            vm,
+            jit: OnceLock::new(),
+            config,
+            searches: AtomicUsize::new(0),
+        }
+    }
+
+    pub(crate) fn new_state(&self) -> LazyJitState {
+        LazyJitState {
+            vm: self.vm.new_state(),
+            jit: None,
+        }
+    }
+
+    /// The JIT, if it is compiled.
+    pub(crate) fn jit(&self) -> Option<&RegexEngine> {
+        self.jit.get().and_then(Option::as_ref)
+    }
+
+    /// Compiles the JIT if it isn't yet, and returns whether the searches
+    /// now run it.
+    pub(crate) fn force(&self) -> bool {
+        self.jit.get_or_init(|| self.compile()).is_some()
+    }
+
+    /// The engine of the next search, and its state. Counts the search,
+    /// and compiles the JIT if it is the one crossing the threshold.
+    pub(crate) fn engine<'a>(
+        &'a self,
+        state: &'a mut LazyJitState,
+    ) -> (&'a RegexEngine, &'a mut EngineState) {
+        let jit = match self.jit.get() {
+            Some(jit) => jit.as_ref(),
+            None => {
+                let searches = self.searches.fetch_add(1, Ordering::Relaxed) + 1;
+                match self.config.lazy_jit_after {
+                    Some(after) if searches == after.max(1) => {
+                        self.jit.get_or_init(|| self.compile()).as_ref()
+                    }
+                    _ => None,
+                }
+            }
+        };
+        match jit {
+            Some(jit) => (jit, state.jit.get_or_insert_with(|| jit.new_state())),
+            None => (&self.vm, &mut state.vm),
+        }
+    }
+
+    pub(crate) fn memory_usage(state: &LazyJitState) -> usize {
+        state.vm.memory_usage() + state.jit.as_ref().map_or(0, EngineState::memory_usage)
+    }
+
+    /// Compiles the bytecode of the VM to machine code, or returns None if
+    /// the JIT doesn't support the pattern or the configuration.
+    #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+    fn compile(&self) -> Option<RegexEngine> {
+        use crate::MatchKind;
+        use crate::thompson::pike_jit::JittedRegex;
+        use crate::thompson::pike_jit::executable::SystemAllocator;
+
+        let RegexEngine::PikeVM(vm) = &self.vm else {
+            unreachable!("the lazy JIT starts on the Pike VM");
+        };
+        if self.config.match_kind != MatchKind::LeftmostFirst {
+            return None;
+        }
+        JittedRegex::from_bytecode(
+            vm.bytecode(),
+            vm.capture_count(),
+            vm.maximum_len(),
+            &self.config,
+            &SystemAllocator,
+        )
+        .ok()
+        .map(RegexEngine::JittedRegex)
+    }
+
+    /// The JIT isn't available, so the VM runs every search.
+    #[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+    fn compile(&self) -> Option<RegexEngine> {
+        None
+    }
+}
@@ -0,0 +1,100 @@
+//! Searches for the patterns starting with `.*`, such as `.*foo.*bar`.
+//!
+//! An unanchored search already looks for a match at every position, so a
+//! leading `.*` (or `.*?`) doesn't change whether there is a match, only where
+//! it starts: the leftmost match starts at the start of the line of the
+//! leftmost match of the rest of the pattern (or at the start of the search,
+//! when the dot matches newlines). Yet the engines pay for the `.*` at every
+//! char, along with every thread it keeps alive.
+//!
+//! So the rest of the pattern is searched first, by its own engine. If it
+//! doesn't match, neither does the pattern, which is the common case on long
+//! haystacks. Otherwise, the whole pattern is run anchored at the start found
+//! above, which gives the same match and groups as the unanchored search, for
+//! both match kinds: the start is the leftmost one, and the rest of the match
+//! is decided from there.
+use regex_syntax::hir::{Dot, Hir, HirKind};
+
+use crate::regex::{EngineState, RegexEngine};
+use crate::util::{Input, Span};
+
+/// Splits a pattern starting with `.*` or `.*?` (with or without the `s`
+/// flag) into the rest of the pattern, and whether the dot matches newlines.
+/// Patterns made only of the repetition have no rest to search for first.
+pub(crate) fn split(hir: &Hir) -> Option<(Hir, bool)> {
+    let HirKind::Concat(hirs) = hir.kind() else {
+        return None;
+    };
+    let (first, rest) = hirs.split_first()?;
+    let HirKind::Repetition(repetition) = first.kind() else {
+        return None;
+    };
+    if repetition.min != 0 || repetition.max.is_some() {
+        return None;
+    }
+    let dot_all = if *repetition.sub == Hir::dot(Dot::AnyChar) {
+        true
+    } else if *repetition.sub == Hir::dot(Dot::AnyCharExceptLF) {
+        false
+    } else {
+        return None;
+    };
+    Some((Hir::concat(rest.to_vec()), dot_all))
+}
+
+/// See the [module documentation](self).
+pub(crate) struct LeadingWildcard {
+    /// The engine of the whole pattern.
+    pub(crate) full: RegexEngine,
+    /// The engine of the pattern without its leading `.*`, which only reports
+    /// group 0.
+    pub(crate) rest: RegexEngine,
+    pub(crate) dot_all: bool,
+}
+
+/// The states of the two engines of a [`LeadingWildcard`].
+pub(crate) struct LeadingWildcardState {
+    pub(crate) full: EngineState,
+    pub(crate) rest: EngineState,
+}
+
+impl LeadingWildcard {
+    pub(crate) fn new_state(&self) -> LeadingWildcardState {
+        LeadingWildcardState {
+            full: self.full.new_state(),
+            rest: self.rest.new_state(),
+        }
+    }
+
+    pub(crate) fn exec(
+        &self,
+        input: Input<'_>,
+        state: &mut LeadingWildcardState,
+        captures: &mut [Span],
+    ) -> bool {
+        // The match must start at the start of the input anyway.
+        if input.anchored || !input.valid() {
+            return self.full.exec(input, &mut state.full, captures);
+        }
+        if captures.is_empty() {
+            return self.rest.exec(input, &mut state.rest, captures);
+        }
+        let mut rest = [Span::invalid()];
+        let rest_input = input.clone().first_match(false);
+        if !self.rest.exec(rest_input, &mut state.rest, &mut rest) {
+            return false;
+        }
+        let mut input = input;
+        if !self.dot_all {
+            let before = &input.subject.as_bytes()[input.span.from..rest[0].from];
+            if let Some(newline) = memchr::memrchr(b'\n', before) {
+                input.span.from += newline + 1;
+            }
+        }
+        let found = self
+            .full
+            .exec(input.anchored(true), &mut state.full, captures);
+        debug_assert!(found, "the rest of the pattern matches after the start");
+        found
+    }
+}
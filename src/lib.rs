@@ -21,21 +21,71 @@
 //! ## Engines
 //!
 //! - [`thompson::pike_vm`] — Interpreted Pike VM engine.
-//! - [`thompson::pike_jit`] — JIT-compiled Pike VM engine.
+//! - `thompson::pike_jit` — JIT-compiled Pike VM engine, with the `jit`
+//!   feature (enabled by default).
 //!
 //! ## Crate Organization
 //!
 //! - `regex`: Core API and engine dispatch
+//! - `bench`: Comparison of the engines on a given pattern and haystack
+//! - `compare`: Where two regexes disagree over a corpus, e.g. for pattern migrations
+//! - `compat`: Facade with the API of the `regex` crate
+//! - `complexity`: Estimates of how much work a pattern costs per char
+//! - `document`: Haystacks preprocessed once and searched by many regexes
+//! - `end_anchored`: Searches for the patterns whose matches end at the end of the haystack (private)
+//! - `error`: The error of the builds of the regexes
+//! - `lazy_jit`: Regexes which switch from the Pike VM to the JIT once used enough (private)
+//! - `leading_wildcard`: Searches for the patterns starting with `.*` (private)
+//! - `limits`: The limits of the compiler, over which building a regex fails
+//! - `lint`: Warnings about likely mistakes in patterns
+//! - `multi`: Several patterns searched at once, e.g. the rules of a lexer
+//! - `parallel`: Parallel search over large haystacks (`rayon` feature)
+//! - `pattern`: Patterns composed in code, without pattern syntax
+//! - `pool`: Pool of engine states, reused across searches (private)
+//! - `prefilter`: Quick reject of haystacks without the bytes a match requires (private)
+//! - `prefix`: Searches for the patterns whose matches start with one of a few literals (private)
+//! - `replace`: Replacements of the matches, with references to their groups
+//! - `sink`: Destinations of the matches of bulk scans, without iterators
 //! - `thompson`: Engine implementations based on thompson's constrcution
 //! - `util`: Shared types and helpers
+//! - `validate`: Checks of user-supplied patterns, without compiling them
 //!
 //! ## License
 //!
 //! Licensed under MIT or Apache-2.0.
 
+pub mod bench;
+pub mod compare;
+pub mod compat;
+pub mod complexity;
+pub mod document;
+mod end_anchored;
+pub mod error;
+mod lazy_jit;
+mod leading_wildcard;
+pub mod limits;
+pub mod lint;
+pub mod multi;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod pattern;
+mod pool;
+mod prefilter;
+mod prefix;
 pub mod regex;
+pub mod replace;
+pub mod sink;
 pub mod thompson;
 pub mod util;
+pub mod validate;
 
+pub use document::Document;
+pub use error::Error;
+pub use lint::lint;
+pub use multi::MultiRegex;
 pub use regex::Builder;
+pub use regex::CgStrategy;
+pub use regex::EngineKind;
+pub use regex::MatchKind;
 pub use regex::Regex;
+pub use validate::validate;
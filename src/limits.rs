@@ -0,0 +1,71 @@
+//! The limits of the compiler, over which building a regex fails with an
+//! [`Error::CompileLimitExceeded`], whose [`CompileError`] carries the limit
+//! and the value of the pattern, instead
+//! of using unbounded time and memory, or breaking the assumptions of the
+//! engines (e.g. the JIT addresses its state with 32 bits offsets).
+//!
+//! The limits are the same for every engine and every [`crate::regex::Config`].
+//! The size of the compiled program is bounded separately, by the
+//! configuration, see [`crate::Builder::size_limit`].
+//! They are also available as functions, for the validation layers which
+//! mirror them, see [`mod@crate::validate`]. The JIT may still reject a pattern
+//! within the limits, if the state it needs for the capture groups doesn't
+//! fit its addressing.
+//!
+//! ```rust
+//! use gregex::thompson::bytecode::CompileError;
+//! use gregex::{Error, Regex};
+//!
+//! let err = Regex::pike_vm(r"(?:(?:a{100}){100}){100}").err().unwrap();
+//! assert!(matches!(
+//!     err,
+//!     Error::CompileLimitExceeded(CompileError::RepetitionTooLarge {
+//!         product: 1_000_000,
+//!         max: 100_000
+//!     })
+//! ));
+//! assert_eq!(gregex::limits::max_unrolled_repetition_product(), 100_000);
+//! ```
+//!
+//! [`CompileError`]: crate::thompson::bytecode::CompileError
+//! [`Error::CompileLimitExceeded`]: crate::Error::CompileLimitExceeded
+
+/// The maximum number of instructions of a bytecode, including the final
+/// accept, see [`crate::thompson::bytecode::Compiler::instruction_count`].
+/// Checked before compiling.
+pub const MAX_INSTRUCTIONS: usize = 1 << 20;
+
+/// The maximum number of copies of a sub-pattern unrolled by nested counted
+/// repetitions: the product of their counts (the maximum, or the minimum if
+/// they are unbounded), e.g. 10,000 for `(?:a{100}){100}`. Checked before
+/// compiling.
+pub const MAX_UNROLLED_REPETITION_PRODUCT: usize = 100_000;
+
+/// The maximum number of branches of a fork, e.g. of an alternation whose
+/// branches couldn't be merged into a class, or of the patterns of a
+/// [`crate::MultiRegex`].
+pub const MAX_FORK_BRANCHES: usize = 1 << 16;
+
+/// The maximum number of distinct outlined classes, see
+/// [`crate::thompson::bytecode::MAX_INLINE_CLASS_RANGES`].
+pub const MAX_OUTLINED_CLASSES: usize = 1 << 16;
+
+/// See [`MAX_INSTRUCTIONS`].
+pub const fn max_instructions() -> usize {
+    MAX_INSTRUCTIONS
+}
+
+/// See [`MAX_UNROLLED_REPETITION_PRODUCT`].
+pub const fn max_unrolled_repetition_product() -> usize {
+    MAX_UNROLLED_REPETITION_PRODUCT
+}
+
+/// See [`MAX_FORK_BRANCHES`].
+pub const fn max_fork_branches() -> usize {
+    MAX_FORK_BRANCHES
+}
+
+/// See [`MAX_OUTLINED_CLASSES`].
+pub const fn max_outlined_classes() -> usize {
+    MAX_OUTLINED_CLASSES
+}
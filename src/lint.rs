@@ -0,0 +1,401 @@
+//! Warnings about constructs which are valid, but likely mistakes.
+//!
+//! [`lint`] looks at the ast of a pattern, before it is compiled, for things
+//! like an unescaped `.` in a domain name (`example.com`), a class range
+//! which includes punctuation (`[A-z]`), or an alternation branch which can
+//! never be reported because an earlier branch always wins (`foo|foobar`).
+//! Each warning points at the part of the pattern it is about. The parts
+//! which make the searches slow are also reported, see [`crate::complexity`].
+//!
+//! The lints are heuristics: a warning doesn't mean the pattern is wrong,
+//! and the regex constructors ignore them.
+use std::fmt;
+
+use regex_syntax::ast::{
+    self, AssertionKind, Ast, ClassSet, ClassSetItem, RepetitionKind, RepetitionRange,
+};
+
+use crate::complexity;
+use crate::regex::{Config, MatchKind};
+use crate::thompson::bytecode::Compiler;
+use crate::util::Span;
+use crate::validate::ValidateLimits;
+
+/// The kinds of warnings reported by [`lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintKind {
+    /// A `.` between letters or digits, e.g. `example.com`, which most
+    /// likely meant a literal dot (`\.`).
+    UnescapedDot,
+    /// A class range between two kinds of ASCII letters or digits, e.g.
+    /// `[A-z]`, which also includes the punctuation between them.
+    SuspiciousRange,
+    /// A class item which is already included in a previous item of the
+    /// same class, e.g. the second `a` of `[a-za]`.
+    DuplicateClassRange,
+    /// A repetition of an unbounded repetition, e.g. `(a+)+`, which matches
+    /// the same as a single repetition.
+    NestedQuantifier,
+    /// An empty branch of an alternation, e.g. in `a||b` or `(a|)`, which
+    /// makes the whole alternation optional.
+    EmptyAlternative,
+    /// A `$` followed, or a `^` preceded, by a character other than a line
+    /// terminator, which the anchor can never match next to, e.g. `a$b`.
+    MisplacedAnchor,
+    /// An alternation branch which is never reported, because an earlier
+    /// branch matches a prefix of it and takes priority, e.g. `foo|foobar`.
+    ShadowedBranch,
+    /// A repetition of exactly zero times, e.g. `a{0}`, which matches the
+    /// empty string only.
+    EmptyRepetition,
+    /// Something which makes every search slow, such as a large counted
+    /// repetition, see [`crate::complexity::ComplexityEstimate::likely_slow`].
+    /// The estimate is made on the compiled pattern, so the span is the whole
+    /// pattern.
+    SlowPattern,
+}
+
+/// A warning about a part of a pattern, see [`lint`].
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    pub kind: LintKind,
+    /// A description of the issue, for humans.
+    pub message: String,
+    /// The byte span of the issue in the pattern.
+    pub span: Span,
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (at {}..{})",
+            self.message, self.span.from, self.span.to
+        )
+    }
+}
+
+/// Returns the warnings about likely mistakes in the pattern, ordered by
+/// their position in it. A pattern which can't be parsed has no warnings:
+/// compiling it reports the error.
+pub fn lint(pattern: &str, config: &Config) -> Vec<LintWarning> {
+    let Ok(ast) = ast::parse::ParserBuilder::new()
+        .nest_limit(config.nest_limit)
+        .build()
+        .parse(pattern)
+    else {
+        return Vec::new();
+    };
+    let mut linter = Linter {
+        pattern,
+        config,
+        warnings: Vec::new(),
+    };
+    // Whether nothing follows the node in the pattern. The nesting depth is
+    // only bounded by the config, so the tree is walked with a stack.
+    let mut stack = vec![(&ast, true)];
+    while let Some((ast, at_end)) = stack.pop() {
+        linter.check(ast, at_end);
+        match ast {
+            Ast::Repetition(repetition) => stack.push((&repetition.ast, false)),
+            Ast::Group(group) => stack.push((&group.ast, at_end)),
+            Ast::Alternation(alternation) => {
+                stack.extend(alternation.asts.iter().map(|ast| (ast, at_end)));
+            }
+            Ast::Concat(concat) => {
+                let last = concat.asts.len().saturating_sub(1);
+                stack.extend(
+                    concat
+                        .asts
+                        .iter()
+                        .enumerate()
+                        .map(|(i, ast)| (ast, at_end && i == last)),
+                );
+            }
+            _ => {}
+        }
+    }
+    linter.check_complexity();
+    linter
+        .warnings
+        .sort_by_key(|warning| (warning.span.from, warning.span.to));
+    linter.warnings
+}
+
+struct Linter<'p> {
+    pattern: &'p str,
+    config: &'p Config,
+    warnings: Vec<LintWarning>,
+}
+
+impl Linter<'_> {
+    fn warn(&mut self, kind: LintKind, span: &ast::Span, message: String) {
+        self.warnings.push(LintWarning {
+            kind,
+            message,
+            span: Span::from(span.start.offset..span.end.offset),
+        });
+    }
+
+    /// Reports the notes of the complexity estimate of the patterns likely
+    /// to be slow. The patterns which compile to more instructions than
+    /// [`crate::validate`] accepts by default are reported as such, without
+    /// compiling them.
+    fn check_complexity(&mut self) {
+        let Ok(hir) = Compiler::parse(self.pattern, self.config) else {
+            return;
+        };
+        let span = Span::from(0..self.pattern.len());
+        let instructions = Compiler::instruction_count(&hir, self.config);
+        let max = ValidateLimits::default().max_instructions;
+        if instructions > max {
+            self.warnings.push(LintWarning {
+                kind: LintKind::SlowPattern,
+                message: format!(
+                    "The pattern compiles to {instructions} instructions, over the limit of {max}"
+                ),
+                span,
+            });
+            return;
+        }
+        let Ok(bytecode) = Compiler::compile(hir.clone(), self.config.clone()) else {
+            return;
+        };
+        let estimate = complexity::estimate(&hir, &bytecode, self.config);
+        if !estimate.likely_slow() {
+            return;
+        }
+        let mut messages: Vec<String> = estimate.notes.iter().map(ToString::to_string).collect();
+        if messages.is_empty() {
+            messages.push(format!(
+                "Each char costs up to {} comparisons, over {} threads",
+                estimate.per_char_cost_estimate, estimate.max_threads_bound
+            ));
+        }
+        self.warnings
+            .extend(messages.into_iter().map(|message| LintWarning {
+                kind: LintKind::SlowPattern,
+                message,
+                span,
+            }));
+    }
+
+    fn text(&self, span: &ast::Span) -> &str {
+        &self.pattern[span.start.offset..span.end.offset]
+    }
+
+    /// Checks the node itself, its children are checked by the caller.
+    fn check(&mut self, ast: &Ast, at_end: bool) {
+        match ast {
+            Ast::Concat(concat) => self.check_concat(&concat.asts),
+            Ast::Alternation(alternation) => self.check_alternation(alternation, at_end),
+            Ast::Repetition(repetition) => self.check_repetition(repetition),
+            Ast::ClassBracketed(class) => self.check_class(&class.kind),
+            _ => {}
+        }
+    }
+
+    fn check_concat(&mut self, asts: &[Ast]) {
+        for (i, ast) in asts.iter().enumerate() {
+            let prev = i.checked_sub(1).and_then(|i| literal_char(&asts[i]));
+            let next = asts.get(i + 1).and_then(literal_char);
+            match ast {
+                Ast::Dot(span) => {
+                    let alphanumeric = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+                    if alphanumeric(prev) && alphanumeric(next) {
+                        self.warn(
+                            LintKind::UnescapedDot,
+                            span,
+                            "`.` matches any character, use `\\.` to match a dot".to_string(),
+                        );
+                    }
+                }
+                Ast::Assertion(assertion) => {
+                    let (neighbour, anchor) = match assertion.kind {
+                        AssertionKind::EndLine => (next, "`$` is followed"),
+                        AssertionKind::StartLine => (prev, "`^` is preceded"),
+                        _ => continue,
+                    };
+                    if let Some(c) = neighbour
+                        && c != '\n'
+                        && c != '\r'
+                    {
+                        self.warn(
+                            LintKind::MisplacedAnchor,
+                            &assertion.span,
+                            format!("{anchor} by {c:?}, so it can never match"),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_alternation(&mut self, alternation: &ast::Alternation, at_end: bool) {
+        let mut literals: Vec<String> = Vec::new();
+        for branch in &alternation.asts {
+            if let Ast::Empty(span) = branch {
+                self.warn(
+                    LintKind::EmptyAlternative,
+                    span,
+                    "Empty alternation branch, which makes the alternation optional".to_string(),
+                );
+                continue;
+            }
+            // With leftmost-longest semantics, the longer branch wins. The
+            // branch also matters if something follows the alternation, e.g.
+            // `(a|ab)c`.
+            if self.config.match_kind != MatchKind::LeftmostFirst || !at_end {
+                continue;
+            }
+            let Some(literal) = literal_string(branch) else {
+                continue;
+            };
+            if let Some(earlier) = literals
+                .iter()
+                .find(|earlier| literal.starts_with(earlier.as_str()))
+            {
+                let message = format!(
+                    "Branch {:?} is never matched, the earlier branch {earlier:?} matches first",
+                    self.text(branch.span())
+                );
+                self.warn(LintKind::ShadowedBranch, branch.span(), message);
+            }
+            literals.push(literal);
+        }
+    }
+
+    fn check_repetition(&mut self, repetition: &ast::Repetition) {
+        if let RepetitionKind::Range(RepetitionRange::Exactly(0) | RepetitionRange::Bounded(_, 0)) =
+            repetition.op.kind
+        {
+            let message = format!(
+                "{:?} only matches the empty string",
+                self.text(&repetition.span)
+            );
+            self.warn(LintKind::EmptyRepetition, &repetition.span, message);
+            return;
+        }
+        if !repeats(&repetition.op.kind) {
+            return;
+        }
+        let mut inner = &*repetition.ast;
+        while let Ast::Group(group) = inner {
+            inner = &group.ast;
+        }
+        let Ast::Repetition(inner) = inner else {
+            return;
+        };
+        // `a*+` is a possessive `a*`, see `Config::possessive_quantifiers`.
+        let possessive = self.config.possessive_quantifiers
+            && repetition.greedy
+            && repetition.op.kind == RepetitionKind::OneOrMore
+            && repetition.op.span.start == inner.op.span.end;
+        if unbounded(&inner.op.kind) && !possessive {
+            let message = format!(
+                "Nested quantifiers in {:?}, the outer one doesn't match more than the inner one",
+                self.text(&repetition.span)
+            );
+            self.warn(LintKind::NestedQuantifier, &repetition.span, message);
+        }
+    }
+
+    fn check_class(&mut self, set: &ClassSet) {
+        // The items of the union, including the nested unions, but not the
+        // nested classes (which are checked on their own).
+        let mut items = Vec::new();
+        let mut stack = vec![set];
+        while let Some(set) = stack.pop() {
+            match set {
+                ClassSet::Item(ClassSetItem::Union(union)) => items.extend(&union.items),
+                ClassSet::Item(item) => items.push(item),
+                ClassSet::BinaryOp(op) => {
+                    stack.push(&op.lhs);
+                    stack.push(&op.rhs);
+                }
+            }
+        }
+        items.sort_by_key(|item| item.span().start.offset);
+
+        let mut ranges: Vec<(char, char)> = Vec::new();
+        for item in items {
+            let (start, end) = match item {
+                ClassSetItem::Literal(literal) => (literal.c, literal.c),
+                ClassSetItem::Range(range) => (range.start.c, range.end.c),
+                ClassSetItem::Bracketed(class) => {
+                    self.check_class(&class.kind);
+                    continue;
+                }
+                _ => continue,
+            };
+            if start != end && ascii_kind(start).is_some_and(|kind| Some(kind) != ascii_kind(end)) {
+                let message = format!(
+                    "Range {:?} also includes the characters between {start:?} and {end:?}",
+                    self.text(item.span())
+                );
+                self.warn(LintKind::SuspiciousRange, item.span(), message);
+            }
+            if ranges.iter().any(|&(from, to)| from <= start && end <= to) {
+                let message = format!("{:?} is already in the class", self.text(item.span()));
+                self.warn(LintKind::DuplicateClassRange, item.span(), message);
+            }
+            ranges.push((start, end));
+        }
+    }
+}
+
+/// Whether the repetition can match its expression more than once.
+fn repeats(kind: &RepetitionKind) -> bool {
+    match kind {
+        RepetitionKind::ZeroOrOne => false,
+        RepetitionKind::ZeroOrMore | RepetitionKind::OneOrMore => true,
+        RepetitionKind::Range(RepetitionRange::Exactly(n)) => *n > 1,
+        RepetitionKind::Range(RepetitionRange::AtLeast(_)) => true,
+        RepetitionKind::Range(RepetitionRange::Bounded(_, max)) => *max > 1,
+    }
+}
+
+/// Whether the repetition has no maximum.
+fn unbounded(kind: &RepetitionKind) -> bool {
+    matches!(
+        kind,
+        RepetitionKind::ZeroOrMore
+            | RepetitionKind::OneOrMore
+            | RepetitionKind::Range(RepetitionRange::AtLeast(_))
+    )
+}
+
+/// The kind of an ASCII letter or digit: digit, upper or lower case.
+fn ascii_kind(c: char) -> Option<u8> {
+    match c {
+        '0'..='9' => Some(0),
+        'A'..='Z' => Some(1),
+        'a'..='z' => Some(2),
+        _ => None,
+    }
+}
+
+/// The character of a literal node.
+fn literal_char(ast: &Ast) -> Option<char> {
+    match ast {
+        Ast::Literal(literal) => Some(literal.c),
+        _ => None,
+    }
+}
+
+/// The string matched by a node made of literals only.
+fn literal_string(ast: &Ast) -> Option<String> {
+    match ast {
+        Ast::Literal(literal) => Some(literal.c.to_string()),
+        Ast::Concat(concat) => concat
+            .asts
+            .iter()
+            .map(|ast| match ast {
+                Ast::Literal(literal) => Some(literal.c),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
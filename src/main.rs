@@ -17,7 +17,9 @@ fn main() {
             break;
         }
 
-        let jitted = match gregex::Builder::new(pattern).pike_jit() {
+        // The Pike VM if the JIT isn't available, see `EngineUnavailable`.
+        let builder = gregex::Builder::new(pattern);
+        let regex = match builder.pike_jit().or_else(|_| builder.pike_vm()) {
             Ok(regex) => regex,
             Err(e) => {
                 print!("Error: {}", e);
@@ -25,6 +27,7 @@ fn main() {
             }
         };
 
+        let mut last_input: Option<String> = None;
         loop {
             println!("Type exit to go back to the regex prompt.");
             print!("input> ");
@@ -38,12 +41,32 @@ fn main() {
             if input == "exit" {
                 break;
             }
-            match jitted.find_captures(input) {
+            if let Some(iterations) = input.strip_prefix(":bench") {
+                let iterations = match iterations.trim() {
+                    "" => 100,
+                    n => match n.parse() {
+                        Ok(n) => n,
+                        Err(_) => {
+                            println!("Usage: :bench <iterations>");
+                            continue;
+                        }
+                    },
+                };
+                match &last_input {
+                    Some(haystack) => {
+                        print!("{}", gregex::bench::compare(pattern, haystack, iterations))
+                    }
+                    None => println!("Type an input to benchmark first."),
+                }
+                continue;
+            }
+            match regex.find_captures(input) {
                 Some(m) => {
                     println!("Matched: {}", m.group0().as_str());
                 }
                 None => println!("No match."),
             }
+            last_input = Some(input.to_string());
         }
     }
 }
@@ -0,0 +1,178 @@
+//! Several patterns searched at once, reporting which one matched.
+//!
+//! A [`MultiRegex`] compiles its patterns into a single program, which
+//! matches like their alternation: the leftmost match wins, and among the
+//! matches starting there, the one of the first pattern (by priority order,
+//! with [`MatchKind::LeftmostFirst`]). Unlike an alternation, the match
+//! reports which pattern produced it, along with the groups of that pattern,
+//! numbered as in the pattern alone. This is what a lexer needs: one search
+//! per token, instead of one per token rule.
+//!
+//! Only the Pike VM runs several patterns.
+//!
+//! [`MatchKind::LeftmostFirst`]: crate::MatchKind::LeftmostFirst
+use std::sync::Arc;
+
+use crate::Error;
+use crate::pool::StatePool;
+use crate::regex::{Config, RegexImpl};
+use crate::thompson::bytecode::Compiler;
+use crate::thompson::pike_vm::{PikeVM, State};
+use crate::util::{Captures, GroupInfo, Input, Span};
+
+/// Several regexes searched at once, see the [module documentation](self).
+///
+/// ```rust
+/// use gregex::MultiRegex;
+///
+/// let re = MultiRegex::new(&[r"(\d+)\.(\d+)", r"\d+", r"[a-z]+"]).unwrap();
+/// let (pattern, caps) = re.find("x = 3.14").unwrap();
+/// assert_eq!((pattern, caps.get(0).unwrap().as_str()), (2, "x"));
+/// let (pattern, caps) = re.find("= 3.14").unwrap();
+/// assert_eq!((pattern, caps.get(2).unwrap().as_str()), (0, "14"));
+/// ```
+pub struct MultiRegex {
+    pike_vm: PikeVM,
+    /// For each pattern, the offset of its groups in the ones reported by the
+    /// VM, and its groups (including group 0), see [`Compiler::compile_many`].
+    groups: Vec<(usize, Arc<GroupInfo>)>,
+    pool: StatePool<State>,
+}
+
+impl MultiRegex {
+    /// Compiles the patterns with the default configuration. Their order is
+    /// their priority.
+    pub fn new(patterns: &[&str]) -> Result<Self, Error> {
+        Self::with_config(patterns, Config::default())
+    }
+
+    /// Compiles the patterns with the given configuration, which applies to
+    /// all of them. Fails with the error of the first pattern which doesn't
+    /// compile.
+    pub fn with_config(patterns: &[&str], config: Config) -> Result<Self, Error> {
+        let hirs = patterns
+            .iter()
+            .map(|pattern| Compiler::parse(pattern, &config))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut groups = Vec::with_capacity(hirs.len());
+        let mut offset = 0;
+        for hir in &hirs {
+            let group_info = GroupInfo::new(Compiler::group_names(hir, &config))
+                .with_static_len(Compiler::static_group_len(hir, &config));
+            let group_len = group_info.len();
+            groups.push((offset, Arc::new(group_info)));
+            offset += group_len - 1;
+        }
+        Ok(Self {
+            pike_vm: PikeVM::new_many(hirs, config)?,
+            groups,
+            pool: StatePool::new(),
+        })
+    }
+
+    /// The number of patterns.
+    pub fn pattern_len(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// The number of groups of the pattern, including group 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such pattern.
+    pub fn group_len(&self, pattern: usize) -> usize {
+        self.groups[pattern].1.len()
+    }
+
+    /// The groups of the pattern, with their names.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no such pattern.
+    pub fn group_info(&self, pattern: usize) -> &GroupInfo {
+        &self.groups[pattern].1
+    }
+
+    /// Returns the leftmost match of any pattern, as the index of the pattern
+    /// which matched and its groups.
+    pub fn find<'s>(&self, input: impl Into<Input<'s>>) -> Option<(usize, Captures<'s>)> {
+        let mut state = self.pool.get(|| self.pike_vm.new_state());
+        self.exec(input.into(), &mut state)
+    }
+
+    /// Returns an iterator over the tokens of the input: each token is an
+    /// anchored match starting where the previous one ended, as the index of
+    /// the pattern which matched and its groups.
+    ///
+    /// The iteration stops at the end of the input, at the first position
+    /// where no pattern matches, or after an empty token (the next one would
+    /// be the same). [`Tokens::offset`] then tells where it stopped.
+    ///
+    /// ```rust
+    /// use gregex::MultiRegex;
+    ///
+    /// let lexer = MultiRegex::new(&[r"\s+", r"\d+", r"[a-z]+", r"[=+]"]).unwrap();
+    /// let mut tokens = lexer.tokenize("x = y + 12 ?");
+    /// let kinds: Vec<usize> = tokens.by_ref().map(|(pattern, _)| pattern).collect();
+    /// assert_eq!(kinds, [2, 0, 3, 0, 2, 0, 3, 0, 1, 0]);
+    /// assert_eq!(tokens.offset(), 11);
+    /// ```
+    pub fn tokenize<'r, 's>(&'r self, input: impl Into<Input<'s>>) -> Tokens<'r, 's> {
+        Tokens {
+            regex: self,
+            input: input.into(),
+            state: self.pike_vm.new_state(),
+            done: false,
+        }
+    }
+
+    fn exec<'s>(&self, input: Input<'s>, state: &mut State) -> Option<(usize, Captures<'s>)> {
+        let mut spans = vec![Span::invalid(); self.pike_vm.capture_count()];
+        self.pike_vm.reset_state(state);
+        let pattern = self
+            .pike_vm
+            .exec_pattern(input.clone(), state, &mut spans)?;
+        let (offset, group_info) = &self.groups[pattern];
+        let pattern_spans = std::iter::once(spans[0])
+            .chain(spans[offset + 1..offset + group_info.len()].iter().copied())
+            .collect();
+        let captures = Captures::new(input.subject, pattern_spans);
+        Some((pattern, captures.with_group_info(group_info.clone())))
+    }
+}
+
+/// The iterator returned by [`MultiRegex::tokenize`].
+pub struct Tokens<'r, 's> {
+    regex: &'r MultiRegex,
+    input: Input<'s>,
+    state: State,
+    done: bool,
+}
+
+impl Tokens<'_, '_> {
+    /// Where the next token starts. Once the iteration is over, this is the
+    /// end of the input if it was tokenized entirely, or the position where
+    /// no pattern matched (or where the empty token was).
+    pub fn offset(&self) -> usize {
+        self.input.span.from
+    }
+}
+
+impl<'s> Iterator for Tokens<'_, 's> {
+    type Item = (usize, Captures<'s>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.input.span.from >= self.input.span.to {
+            return None;
+        }
+        let input = self.input.clone().anchored(true);
+        let Some((pattern, captures)) = self.regex.exec(input, &mut self.state) else {
+            self.done = true;
+            return None;
+        };
+        let span = captures.get(0).unwrap().span;
+        self.done = span.from == span.to;
+        self.input.span.from = span.to;
+        Some((pattern, captures))
+    }
+}
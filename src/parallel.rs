@@ -0,0 +1,197 @@
+//! Parallel search of all matches over large haystacks.
+//!
+//! The haystack is split in chunks which are scanned in parallel. The scan of
+//! a chunk may continue past its end by the maximum length of a match, so that
+//! every match starting in the chunk is found exactly as the sequential
+//! iterator would find it. The results are then merged in order: a chunk is
+//! scanned from its start, while the sequential iterator might resume from a
+//! position past it (when the last match of the previous chunk crosses the
+//! boundary). In that case the matches are re-verified sequentially from that
+//! position, until they coincide again with the ones of the chunk.
+//!
+//! Only available with the `rayon` feature.
+use rayon::prelude::*;
+
+use crate::Regex;
+use crate::regex::EngineWithState;
+use crate::util::{Input, Match, Span};
+
+/// Options of [`Regex::find_all_parallel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelOptions {
+    chunk_size: usize,
+    threads: Option<usize>,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            chunk_size: 1 << 20,
+            threads: None,
+        }
+    }
+}
+
+impl ParallelOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size in bytes of the chunks scanned in parallel (default: 1MiB). The
+    /// scan of a chunk also reads the following `maximum_len` bytes, so this
+    /// should be much larger than the longest match.
+    pub fn chunk_size(mut self, value: usize) -> Self {
+        self.chunk_size = value.max(1);
+        self
+    }
+
+    /// Number of threads to use (default: the global rayon pool).
+    pub fn threads(mut self, value: usize) -> Self {
+        self.threads = Some(value.max(1));
+        self
+    }
+}
+
+impl Regex {
+    /// Returns all non-overlapping matches in the haystack, exactly like
+    /// [`Regex::find_all`], but scans chunks of it in parallel.
+    ///
+    /// This falls back to a sequential scan when the result can't be
+    /// guaranteed to be identical, i.e. when the length of a match is not
+    /// bounded (see [`Regex::maximum_len`]), or when the haystack fits in a
+    /// single chunk.
+    pub fn find_all_parallel<'s>(
+        &self,
+        haystack: &'s str,
+        opts: ParallelOptions,
+    ) -> Vec<Match<'s>> {
+        let Some(maximum_len) = self.maximum_len() else {
+            return self.find_all(haystack).collect();
+        };
+        let starts = chunk_starts(haystack, opts.chunk_size);
+        if starts.len() <= 1 || opts.threads == Some(1) {
+            return self.find_all(haystack).collect();
+        }
+        let scan_all = || {
+            (0..starts.len())
+                .into_par_iter()
+                .map_init(
+                    || self.engine_with_state(),
+                    |imp, i| scan_chunk(imp, haystack, &starts, i, maximum_len),
+                )
+                .collect::<Vec<_>>()
+        };
+        let chunks = match opts.threads {
+            Some(threads) => match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+                Ok(pool) => pool.install(scan_all),
+                Err(_) => return self.find_all(haystack).collect(),
+            },
+            None => scan_all(),
+        };
+        self.merge(haystack, &starts, chunks)
+    }
+
+    fn merge<'s>(
+        &self,
+        haystack: &'s str,
+        starts: &[usize],
+        chunks: Vec<Vec<Span>>,
+    ) -> Vec<Match<'s>> {
+        let mut imp = self.engine_with_state();
+        let mut spans = [Span::invalid()];
+        let mut result = Vec::with_capacity(chunks.iter().map(Vec::len).sum());
+        // Where the sequential iterator would resume its search.
+        let mut input = Input::new(haystack);
+        for (i, chunk) in chunks.iter().enumerate() {
+            let end = chunk_end(haystack, starts, i);
+            let mut rest = chunk.as_slice();
+            // The chunk was scanned as if no match ended at its start, so it
+            // may have an empty match there, which the sequential iterator
+            // skips.
+            if input.span.from > starts[i] || input.previous_match_end == Some(starts[i]) {
+                rest = loop {
+                    let resumed = input.clone();
+                    if !imp.exec_next(&mut input, &mut spans) {
+                        return result;
+                    }
+                    let found = spans[0];
+                    if found.from >= end {
+                        // The match belongs to the next chunk.
+                        input = resumed;
+                        break &[];
+                    }
+                    result.push(Match::new(haystack, found));
+                    // Once a match coincides, the following ones are the same.
+                    if let Some(j) = rest.iter().position(|span| same_span(*span, found)) {
+                        break &rest[j + 1..];
+                    }
+                };
+            }
+            for span in rest {
+                let found = Match::new(haystack, *span);
+                result.push(found);
+                input.advance_past(&found);
+            }
+        }
+        result
+    }
+}
+
+/// Returns the start of each chunk, on char boundaries.
+fn chunk_starts(haystack: &str, chunk_size: usize) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut start = 0;
+    while haystack.len() - start > chunk_size {
+        start = ceil_char_boundary(haystack, start + chunk_size);
+        if start >= haystack.len() {
+            break;
+        }
+        starts.push(start);
+    }
+    starts
+}
+
+/// End of the chunk: only the matches starting before it belong to it. The
+/// last chunk also owns the empty match at the end of the haystack.
+fn chunk_end(haystack: &str, starts: &[usize], i: usize) -> usize {
+    starts.get(i + 1).copied().unwrap_or(haystack.len() + 1)
+}
+
+/// Finds the matches starting within the chunk, as if the search started at
+/// its beginning.
+fn scan_chunk(
+    imp: &mut EngineWithState<'_>,
+    haystack: &str,
+    starts: &[usize],
+    i: usize,
+    maximum_len: usize,
+) -> Vec<Span> {
+    let end = chunk_end(haystack, starts, i);
+    // A match starting in the chunk ends before `end + maximum_len`, so no
+    // match can observe the artificial end of the input at `limit`.
+    let limit = ceil_char_boundary(haystack, end.saturating_add(maximum_len));
+    let mut input = Input::new(haystack).span(Span {
+        from: starts[i],
+        to: limit,
+    });
+    let mut spans = [Span::invalid()];
+    let mut matches = Vec::new();
+    while imp.exec_next(&mut input, &mut spans) && spans[0].from < end {
+        matches.push(spans[0]);
+    }
+    matches
+}
+
+fn ceil_char_boundary(haystack: &str, mut index: usize) -> usize {
+    if index >= haystack.len() {
+        return haystack.len();
+    }
+    while !haystack.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+fn same_span(a: Span, b: Span) -> bool {
+    a.from == b.from && a.to == b.to
+}
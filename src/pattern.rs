@@ -0,0 +1,287 @@
+//! Patterns composed in code, without pattern syntax.
+//!
+//! A [`Fragment`] is a piece of a pattern: a literal, a [`Class`], or a
+//! sequence, alternation, repetition or group of other fragments. Literals are
+//! taken as is, so nothing needs escaping, and fragments can be stored and
+//! reused to compose bigger patterns.
+//!
+//! ```rust
+//! use gregex::pattern::{Class, Fragment};
+//!
+//! let number = Fragment::class(Class::DIGIT).repeat(1, None, true).group(true);
+//! let version = Fragment::seq([
+//!     number.clone(),
+//!     Fragment::literal("."),
+//!     number.clone(),
+//!     Fragment::literal("."),
+//!     number,
+//! ]);
+//! let re = version.builder().pike_vm().unwrap();
+//! let caps = re.find_captures("v1.10.2").unwrap();
+//! assert_eq!(caps.get(2).unwrap().as_str(), "10");
+//! ```
+//!
+//! The fragments are turned into a [`Hir`] with the builders of
+//! `regex_syntax`. The engines compile patterns, so the regexes are built
+//! from the pattern printed from that [`Hir`] (see [`Fragment::builder`]),
+//! which parses back to it.
+use std::borrow::Cow;
+use std::fmt;
+
+use regex_syntax::ParserBuilder;
+use regex_syntax::hir::{self, Capture, ClassUnicode, ClassUnicodeRange, Hir, HirKind, Repetition};
+
+use crate::Error;
+use crate::regex::{Builder, EngineKind, Regex};
+
+/// A set of chars, matched by [`Fragment::class`].
+///
+/// The named classes are Unicode aware, e.g. [`Class::DIGIT`] matches the
+/// digits of every script, like `\d`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Class(ClassKind);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassKind {
+    /// A Perl class, from the Unicode tables of `regex_syntax`.
+    Perl(&'static str),
+    Ranges(Cow<'static, [(char, char)]>),
+    Union(Vec<Class>),
+    Negate(Box<Class>),
+}
+
+impl Class {
+    /// The word chars, like `\w`.
+    pub const WORD: Class = Class(ClassKind::Perl(r"\w"));
+    /// The decimal digits, like `\d`.
+    pub const DIGIT: Class = Class(ClassKind::Perl(r"\d"));
+    /// The whitespace, like `\s`.
+    pub const SPACE: Class = Class(ClassKind::Perl(r"\s"));
+    /// The whitespace which doesn't end a line: tabs and spaces, like `\h` in
+    /// PCRE.
+    pub const HORIZONTAL_SPACE: Class = Class(ClassKind::Ranges(Cow::Borrowed(&[
+        ('\t', '\t'),
+        (' ', ' '),
+        ('\u{a0}', '\u{a0}'),
+        ('\u{1680}', '\u{1680}'),
+        ('\u{2000}', '\u{200a}'),
+        ('\u{202f}', '\u{202f}'),
+        ('\u{205f}', '\u{205f}'),
+        ('\u{3000}', '\u{3000}'),
+    ])));
+    /// The chars ending a line in Unicode: `\n`, `\r`, vertical tab, form
+    /// feed, next line, and the line and paragraph separators.
+    pub const LINE_TERMINATOR: Class = Class(ClassKind::Ranges(Cow::Borrowed(&[
+        ('\n', '\r'),
+        ('\u{85}', '\u{85}'),
+        ('\u{2028}', '\u{2029}'),
+    ])));
+
+    /// The given chars.
+    pub fn any_of(chars: &str) -> Class {
+        Class(ClassKind::Ranges(chars.chars().map(|c| (c, c)).collect()))
+    }
+
+    /// The chars from `start` to `end`, inclusive. Empty if `end` is before
+    /// `start`.
+    pub fn range(start: char, end: char) -> Class {
+        let ranges = if start <= end {
+            vec![(start, end)]
+        } else {
+            Vec::new()
+        };
+        Class(ClassKind::Ranges(ranges.into()))
+    }
+
+    /// The chars in either class.
+    pub fn union(self, other: Class) -> Class {
+        match self.0 {
+            ClassKind::Union(mut classes) => {
+                classes.push(other);
+                Class(ClassKind::Union(classes))
+            }
+            _ => Class(ClassKind::Union(vec![self, other])),
+        }
+    }
+
+    /// The chars not in this class.
+    pub fn negate(self) -> Class {
+        match self.0 {
+            ClassKind::Negate(class) => *class,
+            _ => Class(ClassKind::Negate(Box::new(self))),
+        }
+    }
+
+    fn to_unicode(&self) -> ClassUnicode {
+        match &self.0 {
+            ClassKind::Perl(pattern) => {
+                let hir = ParserBuilder::new()
+                    .build()
+                    .parse(pattern)
+                    .expect("Perl classes are supported");
+                match hir.into_kind() {
+                    HirKind::Class(hir::Class::Unicode(class)) => class,
+                    _ => unreachable!("{pattern} is a Unicode class"),
+                }
+            }
+            ClassKind::Ranges(ranges) => ClassUnicode::new(
+                ranges
+                    .iter()
+                    .map(|&(start, end)| ClassUnicodeRange::new(start, end)),
+            ),
+            ClassKind::Union(classes) => {
+                let mut union = ClassUnicode::empty();
+                for class in classes {
+                    union.union(&class.to_unicode());
+                }
+                union
+            }
+            ClassKind::Negate(class) => {
+                let mut class = class.to_unicode();
+                class.negate();
+                class
+            }
+        }
+    }
+}
+
+/// A piece of a pattern, see the [module documentation](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment(FragmentKind);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum FragmentKind {
+    Literal(String),
+    Class(Class),
+    Seq(Vec<Fragment>),
+    Alt(Vec<Fragment>),
+    Repeat {
+        sub: Box<Fragment>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+    Group {
+        sub: Box<Fragment>,
+        capture: bool,
+    },
+}
+
+impl Fragment {
+    /// Matches the string, taken literally.
+    pub fn literal(literal: &str) -> Fragment {
+        Fragment(FragmentKind::Literal(literal.to_string()))
+    }
+
+    /// Matches one char of the class.
+    pub fn class(class: Class) -> Fragment {
+        Fragment(FragmentKind::Class(class))
+    }
+
+    /// Matches the fragments one after the other. An empty sequence matches
+    /// the empty string.
+    pub fn seq(fragments: impl IntoIterator<Item = Fragment>) -> Fragment {
+        Fragment(FragmentKind::Seq(fragments.into_iter().collect()))
+    }
+
+    /// Matches any of the fragments, preferring the first ones. An empty
+    /// alternation never matches.
+    pub fn alt(fragments: impl IntoIterator<Item = Fragment>) -> Fragment {
+        Fragment(FragmentKind::Alt(fragments.into_iter().collect()))
+    }
+
+    /// Matches this fragment from `min` to `max` times (without bound if
+    /// `max` is `None`), as many times as possible if `greedy`, else as few.
+    pub fn repeat(self, min: u32, max: Option<u32>, greedy: bool) -> Fragment {
+        Fragment(FragmentKind::Repeat {
+            sub: Box::new(self),
+            min,
+            max,
+            greedy,
+        })
+    }
+
+    /// Groups this fragment, capturing it if `capture`. The capture groups
+    /// are numbered in the order they appear in the whole pattern, from 1.
+    pub fn group(self, capture: bool) -> Fragment {
+        Fragment(FragmentKind::Group {
+            sub: Box::new(self),
+            capture,
+        })
+    }
+
+    /// The [`Hir`] of the pattern made of this fragment.
+    pub fn to_hir(&self) -> Hir {
+        self.hir(&mut 1)
+    }
+
+    /// A [`Builder`] for the pattern made of this fragment, which can be
+    /// configured like any other. The flags changing how the pattern is
+    /// parsed also apply, e.g. [`Builder::case_insensitive`].
+    pub fn builder(&self) -> Builder<'static> {
+        Builder::new(self.to_string())
+    }
+
+    /// Builds a regex with the given engine, from the [`Fragment::builder`]
+    /// configured by `f`.
+    pub fn build(
+        &self,
+        engine: EngineKind,
+        f: impl FnOnce(Builder<'static>) -> Builder<'static>,
+    ) -> Result<Regex, Error> {
+        f(self.builder()).build(engine)
+    }
+
+    /// The [`Hir`] of this fragment, where `next_index` is the index of its
+    /// first capture group.
+    fn hir(&self, next_index: &mut u32) -> Hir {
+        match &self.0 {
+            FragmentKind::Literal(literal) => Hir::literal(literal.as_bytes()),
+            FragmentKind::Class(class) => Hir::class(hir::Class::Unicode(class.to_unicode())),
+            FragmentKind::Seq(fragments) => Hir::concat(
+                fragments
+                    .iter()
+                    .map(|fragment| fragment.hir(next_index))
+                    .collect(),
+            ),
+            FragmentKind::Alt(fragments) if fragments.is_empty() => Hir::fail(),
+            FragmentKind::Alt(fragments) => Hir::alternation(
+                fragments
+                    .iter()
+                    .map(|fragment| fragment.hir(next_index))
+                    .collect(),
+            ),
+            FragmentKind::Repeat {
+                sub,
+                min,
+                max,
+                greedy,
+            } => Hir::repetition(Repetition {
+                min: *min,
+                max: *max,
+                greedy: *greedy,
+                sub: Box::new(sub.hir(next_index)),
+            }),
+            FragmentKind::Group {
+                sub,
+                capture: false,
+            } => sub.hir(next_index),
+            FragmentKind::Group { sub, capture: true } => {
+                let index = *next_index;
+                *next_index += 1;
+                Hir::capture(Capture {
+                    index,
+                    name: None,
+                    sub: Box::new(sub.hir(next_index)),
+                })
+            }
+        }
+    }
+}
+
+/// Prints the pattern made of this fragment, with the literals escaped.
+impl fmt::Display for Fragment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_hir().fmt(f)
+    }
+}
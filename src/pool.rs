@@ -0,0 +1,117 @@
+//! A pool of engine states, shared by the searches of a [`crate::Regex`].
+//!
+//! One-shot searches (`is_match`, `find`, `find_captures`) check a state out
+//! of the pool, and the [`PoolGuard`] puts it back when dropped, so repeated
+//! searches don't allocate a new state each time. Iterators never use the
+//! pool: they own their state for their whole lifetime. This is what makes
+//! interleaving them with other searches on the same regex, or nesting them,
+//! safe. Callers who want to manage the states themselves, e.g. one per
+//! thread without the lock of the pool, use a [`crate::regex::ScratchState`].
+//!
+//! A state is moved out of the pool when checked out, so it can't be handed
+//! out twice. Each pooled state still carries a generation counter, odd while
+//! checked out and even while pooled, which debug builds check on every
+//! checkout and return.
+//!
+//! A search may unwind while it holds a state, e.g. when a callback of
+//! [`crate::Regex::for_each_match`] panics, and leave the state in the middle
+//! of an execution. The engines reset their state before each execution, so
+//! such a state would still be correct to reuse, but the guard doesn't rely
+//! on it: a state returned while unwinding is dropped instead of pooled. The
+//! pool itself is never left inconsistent, see [`StatePool::lock`].
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Maximum number of idle states kept by a pool. The states returned when the
+/// pool is full (after many concurrent searches) are dropped.
+const MAX_POOLED: usize = 8;
+
+pub(crate) struct StatePool<T> {
+    states: Mutex<Vec<Pooled<T>>>,
+}
+
+struct Pooled<T> {
+    state: T,
+    generation: u64,
+}
+
+impl<T> StatePool<T> {
+    pub(crate) fn new() -> Self {
+        // Reserved upfront, so that returning a state never allocates.
+        Self {
+            states: Mutex::new(Vec::with_capacity(MAX_POOLED)),
+        }
+    }
+
+    /// Checks out an idle state, or a new one built by `create` if there is
+    /// none. The state is not reset.
+    pub(crate) fn get(&self, create: impl FnOnce() -> T) -> PoolGuard<'_, T> {
+        let pooled = self.lock().pop();
+        let mut pooled = pooled.unwrap_or_else(|| Pooled {
+            state: create(),
+            generation: 0,
+        });
+        debug_assert!(
+            pooled.generation.is_multiple_of(2),
+            "state checked out twice"
+        );
+        pooled.generation += 1;
+        PoolGuard {
+            pool: self,
+            pooled: Some(pooled),
+        }
+    }
+
+    /// The sum of the memory of the idle states, as measured by `usage`.
+    pub(crate) fn memory_usage(&self, usage: impl Fn(&T) -> usize) -> usize {
+        self.lock().iter().map(|pooled| usage(&pooled.state)).sum()
+    }
+
+    fn put(&self, mut pooled: Pooled<T>) {
+        debug_assert!(!pooled.generation.is_multiple_of(2), "state returned twice");
+        pooled.generation += 1;
+        let mut states = self.lock();
+        if states.len() < MAX_POOLED {
+            states.push(pooled);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Pooled<T>>> {
+        // The states are reset before each use, so a search which panicked
+        // can't leave the pool in an inconsistent state.
+        self.states.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A state checked out of a [`StatePool`], returned to it on drop.
+pub(crate) struct PoolGuard<'p, T> {
+    pool: &'p StatePool<T>,
+    // Only None while being dropped.
+    pooled: Option<Pooled<T>>,
+}
+
+impl<T> Deref for PoolGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.pooled.as_ref().unwrap().state
+    }
+}
+
+impl<T> DerefMut for PoolGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.pooled.as_mut().unwrap().state
+    }
+}
+
+impl<T> Drop for PoolGuard<'_, T> {
+    fn drop(&mut self) {
+        // The state of a search which panicked is dropped, see the module
+        // documentation.
+        if let Some(pooled) = self.pooled.take()
+            && !std::thread::panicking()
+        {
+            self.pool.put(pooled);
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! A quick reject of the haystacks which can't contain a match.
+//!
+//! From the HIR, we compute a small set of bytes such that every match of the
+//! pattern contains at least one of them, e.g. `=` for `\d+=\d+`, or `f` and
+//! `b` for `foo|bar`. Before running an engine, the one-shot searches of a
+//! [`crate::Regex`] look for these bytes with `memchr`: if none of them is in
+//! the searched span, there is no match, and the engine state isn't even
+//! checked out of the pool. Otherwise, the first occurrence also bounds where
+//! the leftmost match can start, when the length of the matches is bounded.
+//!
+//! The analysis is conservative: patterns for which no small enough set of
+//! required bytes is known (e.g. `\w+`, or `a?`) get no prefilter.
+use regex_syntax::hir::{Capture, Class, Hir, HirKind, Literal, Repetition};
+
+use crate::document::Document;
+use crate::util::{Input, Span};
+
+/// Maximum number of bytes in a set, the most `memchr` can search at once.
+const MAX_BYTES: usize = 3;
+
+/// See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub(crate) struct RequiredBytes {
+    /// Sorted, without duplicates, between 1 and [`MAX_BYTES`] long.
+    bytes: Vec<u8>,
+    maximum_len: Option<usize>,
+}
+
+impl RequiredBytes {
+    /// Returns the required bytes of the pattern, if any is known.
+    pub(crate) fn new(hir: &Hir) -> Option<Self> {
+        Some(Self {
+            bytes: required(hir)?,
+            maximum_len: hir.properties().maximum_len(),
+        })
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Restricts the input to where a match can be, or returns None if there
+    /// can't be any. Matches must lie in the span of the input, so only the
+    /// span is searched.
+    pub(crate) fn apply<'s>(&self, input: Input<'s>) -> Option<Input<'s>> {
+        self.apply_bytes(&self.bytes, input)
+    }
+
+    /// Like `apply`, but only looks for the required bytes which occur in
+    /// the document the input is the text of, if any.
+    pub(crate) fn apply_in_document<'s>(
+        &self,
+        input: Input<'s>,
+        document: &Document<'_>,
+    ) -> Option<Input<'s>> {
+        let mut present = [0; MAX_BYTES];
+        let mut len = 0;
+        for byte in &self.bytes {
+            if document.byte_count(*byte) > 0 {
+                present[len] = *byte;
+                len += 1;
+            }
+        }
+        self.apply_bytes(&present[..len], input)
+    }
+
+    fn apply_bytes<'s>(&self, bytes: &[u8], mut input: Input<'s>) -> Option<Input<'s>> {
+        // Leave the invalid inputs to the engines, whatever they do with them.
+        if !input.valid() || input.span.to > input.subject.len() {
+            return Some(input);
+        }
+        let haystack = &input.subject.as_bytes()[input.span.from..input.span.to];
+        let found = match *bytes {
+            [] => None,
+            [a] => memchr::memchr(a, haystack),
+            [a, b] => memchr::memchr2(a, b, haystack),
+            [a, b, c] => memchr::memchr3(a, b, c, haystack),
+            _ => unreachable!("at most {MAX_BYTES} required bytes"),
+        };
+        let position = input.span.from + found?;
+        // A match starting before `position - maximum_len + 1` would end
+        // before `position`, without any required byte.
+        if let Some(maximum_len) = self.maximum_len
+            && !input.anchored
+        {
+            let mut from = (position + 1).saturating_sub(maximum_len);
+            while !input.subject.is_char_boundary(from) {
+                from -= 1;
+            }
+            if from > input.span.from {
+                input.span = Span {
+                    from,
+                    to: input.span.to,
+                };
+            }
+        }
+        Some(input)
+    }
+}
+
+/// Returns a set of bytes such that every match of `hir` contains at least
+/// one of them, or None if none is known.
+fn required(hir: &Hir) -> Option<Vec<u8>> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => None,
+        // Any byte of the literal would do.
+        HirKind::Literal(Literal(bytes)) => bytes.first().map(|byte| vec![*byte]),
+        HirKind::Class(class) => first_bytes(class),
+        HirKind::Repetition(Repetition { min, sub, .. }) => {
+            if *min == 0 {
+                None
+            } else {
+                required(sub)
+            }
+        }
+        HirKind::Capture(Capture { sub, .. }) => required(sub),
+        // Every part is matched, so the smallest set of any of them works.
+        HirKind::Concat(hirs) => hirs
+            .iter()
+            .filter_map(required)
+            .min_by_key(|bytes| bytes.len()),
+        // Any of the alternatives may be matched, so all their sets are needed.
+        HirKind::Alternation(hirs) => {
+            let mut union = Vec::new();
+            for hir in hirs {
+                union.extend(required(hir)?);
+            }
+            small_set(union)
+        }
+    }
+}
+
+/// The set of the first bytes of the (UTF-8 encoded) chars of a class, if it
+/// is small enough.
+fn first_bytes(class: &Class) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut push_range = |start: u8, end: u8| {
+        if usize::from(end - start) >= MAX_BYTES {
+            return false;
+        }
+        bytes.extend(start..=end);
+        true
+    };
+    match class {
+        // The first byte of an encoded char grows with the char.
+        Class::Unicode(class) => {
+            for range in class.iter() {
+                let first_byte = |c: char| *c.encode_utf8(&mut [0; 4]).as_bytes().first().unwrap();
+                if !push_range(first_byte(range.start()), first_byte(range.end())) {
+                    return None;
+                }
+            }
+        }
+        Class::Bytes(class) => {
+            for range in class.iter() {
+                if !push_range(range.start(), range.end()) {
+                    return None;
+                }
+            }
+        }
+    }
+    small_set(bytes)
+}
+
+fn small_set(mut bytes: Vec<u8>) -> Option<Vec<u8>> {
+    bytes.sort_unstable();
+    bytes.dedup();
+    (1..=MAX_BYTES).contains(&bytes.len()).then_some(bytes)
+}
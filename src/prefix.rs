@@ -0,0 +1,117 @@
+//! Searches for the patterns whose matches start with one of a few literals,
+//! such as `Sherlock [A-Z]\w+` or `Sherlock Holmes|John Watson`.
+//!
+//! An unanchored search starts a thread at every char of the haystack, most
+//! of which die on their first char. When every match starts with one of the
+//! prefixes extracted from the HIR (by regex-syntax's literal extractor), no
+//! match can start before the first occurrence of a prefix. So it is looked
+//! for first, with `memmem`, and the engine only searches from there, which
+//! gives the same match and groups as the search of the whole span, for both
+//! match kinds and with `first_match`. The engines still read the chars
+//! before the span for the assertions (e.g. the `\b` of `\bfoo`).
+//!
+//! The engine then runs unanchored from the occurrence, rather than anchored
+//! at each occurrence in turn: a failed anchored search may read up to the
+//! end of the haystack (e.g. `a+b` on a run of `a`), so that would make the
+//! searches quadratic. Each search of an iterator looks for the next
+//! occurrence again, from the end of the previous match.
+//!
+//! The patterns without a small set of non-empty prefixes (e.g. `\w+`, `a*`
+//! or `.*foo`) are searched as before.
+use memchr::memmem::Finder;
+use regex_syntax::hir::Hir;
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+
+use crate::regex::{EngineState, RegexEngine};
+use crate::util::{Input, Span};
+
+/// Maximum number of prefixes, each of which is searched separately.
+const MAX_LITERALS: usize = 8;
+
+/// See the [module documentation](self).
+pub(crate) struct Prefixed {
+    /// The engine of the whole pattern, which shares its state with this one.
+    pub(crate) inner: RegexEngine,
+    pub(crate) prefixes: Prefixes,
+}
+
+/// The literals one of which starts every match of a pattern.
+pub(crate) struct Prefixes {
+    /// Not empty, at most [`MAX_LITERALS`] long, and none of the literals is
+    /// empty.
+    finders: Vec<Finder<'static>>,
+}
+
+impl Prefixes {
+    /// Returns the prefixes of the pattern, if there is a small enough set of
+    /// them, without the empty one.
+    pub(crate) fn new(hir: &Hir) -> Option<Self> {
+        let mut seq = Extractor::new().kind(ExtractKind::Prefix).extract(hir);
+        seq.optimize_for_prefix_by_preference();
+        let literals = seq.literals()?;
+        if literals.is_empty()
+            || literals.len() > MAX_LITERALS
+            || literals.iter().any(|literal| literal.is_empty())
+        {
+            return None;
+        }
+        let finders = literals
+            .iter()
+            .map(|literal| Finder::new(literal.as_bytes()).into_owned())
+            .collect();
+        Some(Self { finders })
+    }
+
+    pub(crate) fn literals(&self) -> impl Iterator<Item = &[u8]> {
+        self.finders.iter().map(Finder::needle)
+    }
+
+    /// The leftmost position in `from..to` where one of the prefixes starts,
+    /// and fits before `to`.
+    fn find(&self, subject: &str, from: usize, to: usize) -> Option<usize> {
+        let bytes = subject.as_bytes();
+        let mut leftmost: Option<usize> = None;
+        for finder in &self.finders {
+            // Only an occurrence starting before the leftmost one so far
+            // matters.
+            let end = match leftmost {
+                Some(start) => (start + finder.needle().len() - 1).min(to),
+                None => to,
+            };
+            if let Some(i) = finder.find(&bytes[from..end]) {
+                leftmost = Some(from + i);
+            }
+        }
+        leftmost
+    }
+}
+
+impl Prefixed {
+    pub(crate) fn exec(
+        &self,
+        input: Input<'_>,
+        state: &mut EngineState,
+        captures: &mut [Span],
+    ) -> bool {
+        // Leave the invalid inputs to the engine, whatever it does with them,
+        // and the anchored searches only have one position to try anyway.
+        if input.anchored || !input.valid() || input.span.to > input.subject.len() {
+            return self.inner.exec(input, state, captures);
+        }
+        let Span { from, to } = input.span;
+        let Some(mut start) = self.prefixes.find(input.subject, from, to) else {
+            return false;
+        };
+        // The literals of the classes which may match invalid UTF-8 may start
+        // inside a char.
+        while !input.subject.is_char_boundary(start) {
+            start -= 1;
+        }
+        let input = if start > from {
+            input.span(Span { from: start, to })
+        } else {
+            input
+        };
+        self.inner.exec(input, state, captures)
+    }
+}
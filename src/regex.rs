@@ -3,20 +3,60 @@
 //! This module defines the [`Regex`] struct, which is
 //! a nice wrapper under one of the available [`RegexImpl`].
 
+use std::borrow::Cow;
 use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
 
-use crate::thompson::pike_jit::JittedRegex;
-use crate::thompson::pike_vm::PikeVM;
-use crate::util::{Captures, Input, Match, Span};
+use regex_syntax::hir::Hir;
 
-type CompileError = Box<dyn Error + Send + Sync + 'static>;
+use crate::complexity::{self, ComplexityEstimate};
+use crate::document::{Document, LineAnchor};
+use crate::end_anchored::{self, EndAnchored};
+use crate::lazy_jit::{LazyJit, LazyJitState};
+use crate::leading_wildcard::{self, LeadingWildcard, LeadingWildcardState};
+use crate::pool::StatePool;
+use crate::prefilter::RequiredBytes;
+use crate::prefix::{Prefixed, Prefixes};
+use crate::replace::Replacer;
+use crate::sink::{MatchError, MatchSink, ScanSummary};
+use crate::thompson::assertion_only::AssertionOnly;
+use crate::thompson::bytecode::{ClassCache, CompileNote, Compiler};
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+use crate::thompson::pike_jit::{JittedRegex, executable::SystemAllocator};
+use crate::thompson::pike_vm::{GroupEvent, PikeVM};
+#[cfg(feature = "unicode-segmentation")]
+use crate::util::GraphemeMatch;
+use crate::util::{
+    BuildTimings, CaptureLocations, Captures, Char, ExecTimings, Explanation, GroupInfo, Input,
+    Match, MatchStats, MultiSliceMatch, OwnedCaptures, OwnedInput, OwnedMatch, Span, Utf8ErrorAt,
+};
 
 /// A regular expression
 pub struct Regex {
     engine: RegexEngine,
-    // TODO: Replace that with group info once we have named
-    // cg support
-    capture_count: usize,
+    /// The groups of the pattern, shared with the captures of its matches.
+    group_info: Arc<GroupInfo>,
+    /// States of the one-shot searches, see [`crate::pool`].
+    pool: StatePool<EngineState>,
+    /// Tells the [`ScratchState`]s of this regex from the ones of the others.
+    id: u64,
+    /// Quick reject of the one-shot searches, see [`crate::prefilter`].
+    prefilter: Option<RequiredBytes>,
+    /// Where the matches are in the lines, for the searches in a [`Document`].
+    line_anchor: Option<LineAnchor>,
+    // What the regex was built from, see `Regex::rebuild`.
+    pattern: String,
+    config: Config,
+    kind: EngineKind,
+    /// See [`Regex::build_timings`].
+    build_timings: Option<BuildTimings>,
+    /// The Pike VM of the pattern, for the regexes of the other engines, built
+    /// on the first call of [`Regex::find_longest_prefix`].
+    longest_prefix_vm: OnceLock<PikeVM>,
 }
 
 impl Regex {
@@ -24,119 +64,1373 @@ impl Regex {
     /// returning the bounds of the match. This returns true iff find returns
     /// Some(...), but it may be faster in some cases.
     pub fn is_match<'s>(&self, input: impl Into<Input<'s>>) -> bool {
-        match &self.engine {
+        self.exec(input.into().first_match(true), &mut [])
+    }
+
+    /// Match the regex against the input and returns the bounds of the match or
+    /// None.
+    pub fn find<'s>(&self, input: impl Into<Input<'s>>) -> Option<Match<'s>> {
+        let input = input.into();
+        let subject = input.subject;
+        let mut span = Span::invalid();
+        if !self.exec(input, std::slice::from_mut(&mut span)) {
+            return None;
+        }
+        Some(Match::new(subject, span))
+    }
+
+    /// Like `find`, but starts the search at the byte offset `start` of the
+    /// haystack, like the method of the same name of the regex crate. Unlike
+    /// searching `&haystack[start..]`, the assertions at `start` see the char
+    /// before it, e.g. `^` doesn't match there, unless in multi-line mode
+    /// after a `\n`, and neither does `\b` between two word chars.
+    ///
+    /// # Panics
+    ///
+    /// If `start` is not a char boundary of the haystack, or is past its end.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"\bday").unwrap();
+    /// assert_eq!(re.find("today, day").map(|m| m.start()), Some(7));
+    /// assert_eq!(re.find_at("today, day", 2).map(|m| m.start()), Some(7));
+    /// assert_eq!(re.find(&"today, day"[2..]).map(|m| m.start()), Some(0));
+    /// ```
+    pub fn find_at<'s>(&self, haystack: &'s str, start: usize) -> Option<Match<'s>> {
+        self.find(Self::input_at(haystack, start))
+    }
+
+    /// Like [`Regex::find_captures`], from the byte offset `start` of the
+    /// haystack, see [`Regex::find_at`].
+    ///
+    /// # Panics
+    ///
+    /// If `start` is not a char boundary of the haystack, or is past its end.
+    pub fn captures_at<'s>(&self, haystack: &'s str, start: usize) -> Option<Captures<'s>> {
+        self.find_captures(Self::input_at(haystack, start))
+    }
+
+    /// The input of [`Regex::find_at`] and [`Regex::captures_at`].
+    fn input_at(haystack: &str, start: usize) -> Input<'_> {
+        let span = (start..haystack.len()).into();
+        Input::new(haystack)
+            .try_span(span)
+            .unwrap_or_else(|err| panic!("Invalid start {start}: {err}"))
+    }
+
+    /// Like `find`, but the reported span is extended to the nearest grapheme
+    /// cluster boundaries, see [`Match::snap_to_graphemes`]. This never
+    /// changes whether there is a match, and the raw match is still available.
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn find_graphemes<'s>(&self, input: impl Into<Input<'s>>) -> Option<GraphemeMatch<'s>> {
+        let raw = self.find(input)?;
+        Some(GraphemeMatch {
+            raw,
+            span: raw.snap_to_graphemes(),
+        })
+    }
+
+    /// Searches the slices as if they were a single haystack, with the
+    /// separator (if any) between each of them, e.g. `Some('\n')` for lines.
+    /// The separator is a real char of that haystack, which the pattern and
+    /// its assertions see. The Pike VM searches the slices without copying
+    /// them, other engines (including the specialized ones) search a joined
+    /// copy.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(?m)end$\s+^start").unwrap();
+    /// let paragraphs = ["the end", "start again"];
+    /// let m = re.find_in_slices(&paragraphs, Some('\n')).unwrap();
+    /// assert_eq!((m.start(), m.end()), ((0, 4), (1, 5)));
+    /// assert_eq!(m.extract_to_string(), "end\nstart");
+    /// ```
+    pub fn find_in_slices<'s>(
+        &self,
+        slices: &'s [&'s str],
+        separator: Option<char>,
+    ) -> Option<MultiSliceMatch<'s>> {
+        let mut result = [Span::invalid()];
+        let found = match self.engine.general() {
             RegexEngine::PikeVM(pike_vm) => {
                 let mut state = pike_vm.new_state();
-                pike_vm.exec(input.into().first_match(true), &mut state, &mut [])
+                pike_vm.exec_slices(slices, separator, &mut state, &mut result)
             }
-            RegexEngine::JittedRegex(jitted_regex) => {
-                let mut state = jitted_regex.new_state();
-                jitted_regex.exec(input.into().first_match(true), &mut state, &mut [])
+            _ => {
+                let separator = separator.map(String::from).unwrap_or_default();
+                let joined = slices.join(&separator);
+                self.exec(Input::new(&joined), &mut result)
             }
+        };
+        found.then_some(MultiSliceMatch {
+            slices,
+            separator,
+            span: result[0],
+        })
+    }
+
+    /// Like `find`, on bytes which are probably, but not certainly, valid
+    /// UTF-8. The engine checks the chars as it decodes them, which saves the
+    /// separate pass of [`std::str::from_utf8`] over the haystack.
+    ///
+    /// Returns an error at the first invalid sequence the search reads. Since
+    /// the search may stop before the end of the haystack, what is checked
+    /// depends on the outcome:
+    /// - On a match, the bytes up to the end of the match are valid, and are
+    ///   the subject of the match. The bytes after it may not be checked.
+    /// - Without a match, the whole haystack is valid.
+    ///
+    /// The prefilter (see [`Regex::required_bytes`]) is not used, since it
+    /// skips bytes without checking them.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"\d+").unwrap();
+    /// let m = re.find_validating(b"abc 123 \xFF").unwrap().unwrap();
+    /// assert_eq!(m.as_str(), "123");
+    /// let err = re.find_validating(b"abc \xFF 123").unwrap_err();
+    /// assert_eq!(err.offset, 4);
+    /// ```
+    pub fn find_validating<'s>(
+        &self,
+        haystack: &'s [u8],
+    ) -> Result<Option<Match<'s>>, Utf8ErrorAt> {
+        let mut result = [Span::invalid()];
+        let mut state = self.pool.get(|| self.engine.new_state());
+        if !self
+            .engine
+            .exec_validating(haystack, &mut state, &mut result)?
+        {
+            return Ok(None);
         }
+        let end = result[0].to;
+        // SAFETY: The engine checked the chars of the haystack in order, at
+        // least up to the end of the match.
+        let subject = unsafe { std::str::from_utf8_unchecked(&haystack[..end]) };
+        Ok(Some(Match::new(subject, result[0])))
     }
 
-    /// Match the regex against the input and returns the bounds of the match or
-    /// None.
-    pub fn find<'s>(&self, input: impl Into<Input<'s>>) -> Option<Match<'s>> {
+    /// Searches for a match starting exactly at one of the given positions,
+    /// tried in their order, and returns the first one found with the index
+    /// of its position. This is the loop of anchored `find`s at each
+    /// position, with its setup done once: a single state is checked out for
+    /// all the searches, and the prefilter only runs once.
+    ///
+    /// The positions which are not char boundaries of the haystack (or are
+    /// past its end) are skipped or rejected, see [`InvalidPositions`]. They
+    /// are all checked before searching, so an invalid position is rejected
+    /// even if a match is found before it.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    /// use gregex::regex::InvalidPositions;
+    ///
+    /// let re = Regex::pike_vm(r"\{(\w+)").unwrap();
+    /// let found = re.find_at_positions("a {b} {{c}", &[4, 6, 7], InvalidPositions::Skip);
+    /// let (index, m) = found.unwrap().unwrap();
+    /// assert_eq!((index, m.as_str()), (2, "{c"));
+    /// ```
+    pub fn find_at_positions<'s>(
+        &self,
+        haystack: &'s str,
+        positions: &[usize],
+        invalid: InvalidPositions,
+    ) -> Result<Option<(usize, Match<'s>)>, InvalidPosition> {
+        let is_valid = |position: usize| haystack.is_char_boundary(position);
+        if invalid == InvalidPositions::Reject
+            && let Some(index) = positions.iter().position(|p| !is_valid(*p))
+        {
+            return Err(InvalidPosition {
+                index,
+                position: positions[index],
+            });
+        }
+        let Some(first) = positions.iter().copied().filter(|p| is_valid(*p)).min() else {
+            return Ok(None);
+        };
+        // Every match lies after the first position.
+        if let Some(prefilter) = &self.prefilter {
+            let rest = Input::new(haystack).span((first..haystack.len()).into());
+            if prefilter.apply(rest).is_none() {
+                return Ok(None);
+            }
+        }
+        let mut state = self.pool.get(|| self.engine.new_state());
+        let mut span = [Span::invalid()];
+        for (index, &position) in positions.iter().enumerate() {
+            if !is_valid(position) {
+                continue;
+            }
+            let input = Input::new(haystack)
+                .span((position..haystack.len()).into())
+                .anchored(true);
+            if self.engine.exec(input, &mut state, &mut span) {
+                return Ok(Some((index, Match::new(haystack, span[0]))));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like `find`, but also explains how the match was chosen, e.g. why it
+    /// starts where it does and not earlier. Only the Pike VM records
+    /// explanations, other engines return [`Explanation::Unavailable`].
+    pub fn find_explain<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+    ) -> Option<(Match<'s>, Explanation)> {
         let input = input.into();
         let subject = input.subject;
         let mut result = [Span::invalid()];
-        let found = match &self.engine {
+        let explanation = match self.engine.general() {
             RegexEngine::PikeVM(pike_vm) => {
                 let mut state = pike_vm.new_state();
-                pike_vm.exec(input, &mut state, &mut result)
+                // The trace of the last search, which found the match.
+                let mut trace = None;
+                let found = exec_after_match(&input, &mut result, |input, result| {
+                    let (found, last) = pike_vm.exec_explain(input, &mut state, result);
+                    trace = Some(last);
+                    found
+                });
+                match trace {
+                    Some(trace) if found => Explanation::PikeVM(trace),
+                    _ => return None,
+                }
             }
-            RegexEngine::JittedRegex(jitted_regex) => {
-                let mut state = jitted_regex.new_state();
-                jitted_regex.exec(input, &mut state, &mut result)
+            _ => {
+                let found = self.find(input)?;
+                return Some((found, Explanation::Unavailable));
             }
         };
-        if !found {
+        Some((Match::new(subject, result[0]), explanation))
+    }
+
+    /// Returns the longest match starting at the start of the span of the
+    /// input, if any. Unlike `find`, which prefers the branches in the order
+    /// of the pattern, every accepted match is considered, and the one ending
+    /// last is kept: the anchoring and `first_match` of the input are ignored.
+    ///
+    /// ```rust
+    /// # use gregex::Regex;
+    /// let re = Regex::pike_vm("a|ab|abc").unwrap();
+    /// assert_eq!(re.find("abcd").unwrap().as_str(), "a");
+    /// assert_eq!(re.find_longest_prefix("abcd").unwrap().as_str(), "abc");
+    /// assert!(re.find_longest_prefix("xabc").is_none());
+    /// ```
+    ///
+    /// Only the Pike VM supports this search: the regexes of the other
+    /// engines (the JIT, or a specialized engine picked by the builder) build
+    /// a Pike VM of their pattern on the first call.
+    pub fn find_longest_prefix<'s>(&self, input: impl Into<Input<'s>>) -> Option<Match<'s>> {
+        let input = input.into();
+        let subject = input.subject;
+        let mut result = [Span::invalid()];
+        if !self.exec_longest_prefix(input, &mut result) {
             return None;
         }
         Some(Match::new(subject, result[0]))
     }
 
+    /// Like `find_longest_prefix`, but also returns the capture groups. They
+    /// are the ones of the thread which accepted the longest match, and if
+    /// several did, of the one `find_captures` would prefer, e.g. `(a|ab)(b?)`
+    /// on `ab` reports `a` and `b`.
+    pub fn find_longest_prefix_captures<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+    ) -> Option<Captures<'s>> {
+        let input = input.into();
+        let subject = input.subject;
+        let mut spans = vec![Span::invalid(); self.group_info.len()].into_boxed_slice();
+        if !self.exec_longest_prefix(input, &mut spans) {
+            return None;
+        }
+        Some(self.captures(subject, spans))
+    }
+
+    fn exec_longest_prefix(&self, input: Input<'_>, captures: &mut [Span]) -> bool {
+        let pike_vm = match self.engine.general() {
+            RegexEngine::PikeVM(pike_vm) => pike_vm,
+            _ => self.longest_prefix_vm.get_or_init(|| {
+                PikeVM::new(&self.pattern, self.config.clone())
+                    .expect("The pattern was already compiled")
+            }),
+        };
+        let mut state = pike_vm.new_state();
+        pike_vm.exec_longest_prefix(input, &mut state, captures)
+    }
+
+    /// Like `find` on the text of the document, but skips the work the
+    /// document already did, see [`Document`]. This is meant for documents
+    /// searched by many regexes.
+    pub fn find_in_document<'s>(&self, document: &Document<'s>) -> Option<Match<'s>> {
+        let mut result = [Span::invalid()];
+        let mut state = self.pool.get(|| self.engine.new_state());
+        let input = Input::new(document.text());
+        if !self.exec_in_document(document, input, &mut state, &mut result) {
+            return None;
+        }
+        Some(Match::new(document.text(), result[0]))
+    }
+
+    /// Like `find_all` on the text of the document, see `find_in_document`.
+    pub fn find_all_in_document<'r, 'd, 's>(
+        &'r self,
+        document: &'d Document<'s>,
+    ) -> DocumentMatches<'r, 'd, 's> {
+        DocumentMatches {
+            regex: self,
+            document,
+            input: Input::new(document.text()),
+            spans: [Span::invalid()],
+            state: self.engine.new_state(),
+        }
+    }
+
     /// Returns an iterator over all non-overlapping match in the input.
+    ///
+    /// Like in the regex crate, an empty match which starts right at the end
+    /// of the previous match is not reported, and the search goes on from the
+    /// next char instead.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"a*").unwrap();
+    /// let spans: Vec<_> = re.find_all("baaab").map(|m| (m.start(), m.end())).collect();
+    /// assert_eq!(spans, [(0, 0), (1, 4), (5, 5)]);
+    /// // No empty match at 3, right after the match of `aaa`.
+    /// assert_eq!(re.find_all("aaa").count(), 1);
+    /// ```
     pub fn find_all<'r, 's>(&'r self, input: impl Into<Input<'s>>) -> AllMatch<'r, 's> {
-        let imp = match &self.engine {
-            RegexEngine::PikeVM(pike_vm) => {
-                let state = pike_vm.new_state();
-                EngineWithState::PikeVM(pike_vm, state)
+        AllMatch {
+            input: input.into(),
+            spans: [Span::invalid()],
+            imp: self.engine_with_state(),
+        }
+    }
+
+    /// Calls `f` on each match of `find_all`, in order. Unlike `find_all`,
+    /// this doesn't allocate a state for the search, one is checked out of
+    /// the regex for the duration of the call. `f` may search with the same
+    /// regex, which then uses another state.
+    ///
+    /// If `f` panics, the search stops and the panic is propagated. The regex
+    /// is still usable, e.g. after catching the panic, and the state of the
+    /// interrupted search is not reused.
+    pub fn for_each_match<'s>(&self, input: impl Into<Input<'s>>, mut f: impl FnMut(Match<'s>)) {
+        let mut input = input.into();
+        let mut state = self.pool.get(|| self.engine.new_state());
+        let mut spans = [Span::invalid()];
+        while search_next(&mut input, &mut spans, |input, spans| {
+            self.engine.exec(input, &mut state, spans)
+        }) {
+            f(Match::new(input.subject, spans[0]));
+        }
+    }
+
+    /// Reports the matches of `find_all` to the sink, from the search loop
+    /// itself: unlike the iterator, this allocates nothing per match, and
+    /// checks a single state out of the regex for the whole scan. The sink
+    /// may stop the scan, see [`MatchSink`], and gets the captures if it
+    /// wants them.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    /// use gregex::sink::SpanVecSink;
+    ///
+    /// let re = Regex::pike_vm(r"\d+").unwrap();
+    /// let mut sink = SpanVecSink::new();
+    /// let summary = re.scan_into("a1b22", &mut sink).unwrap();
+    /// assert_eq!((summary.matches, summary.position), (2, 5));
+    /// assert_eq!((sink.spans[1].from, sink.spans[1].to), (3, 5));
+    /// ```
+    ///
+    /// Fails without searching if the span of the input is not valid (see
+    /// [`Input::valid`]).
+    pub fn scan_into<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        sink: &mut dyn MatchSink,
+    ) -> Result<ScanSummary, MatchError> {
+        let mut input = input.into();
+        if !input.valid() {
+            let Span { from, to } = input.span;
+            return Err(MatchError::InvalidSpan { from, to });
+        }
+        let end = input.span.to;
+        let captures = sink.wants_captures();
+        let mut state = self.pool.get(|| self.engine.new_state());
+        let mut group0 = [Span::invalid()];
+        let mut groups = Vec::new();
+        let spans: &mut [Span] = if captures {
+            groups.resize(self.group_info.len(), Span::invalid());
+            &mut groups
+        } else {
+            &mut group0
+        };
+        let mut matches = 0;
+        while search_next(&mut input, spans, |input, spans| {
+            self.engine.exec(input, &mut state, spans)
+        }) {
+            let next = input.span.from;
+            matches += 1;
+            let flow = if captures {
+                sink.on_captures(spans)
+            } else {
+                sink.on_match(spans[0])
+            };
+            if flow.is_break() {
+                return Ok(ScanSummary {
+                    matches,
+                    position: next.min(end),
+                    stopped: true,
+                });
             }
-            RegexEngine::JittedRegex(jitted_regex) => {
-                let state = jitted_regex.new_state();
-                EngineWithState::JittedRegex(jitted_regex, state)
+        }
+        Ok(ScanSummary {
+            matches,
+            position: end,
+            stopped: false,
+        })
+    }
+
+    /// Like `find`, but calls `interrupt` every `interval` chars read by the
+    /// engine, and gives up with [`MatchError::Interrupted`] as soon as it
+    /// returns true. This bounds how long a search runs without giving a
+    /// chance to stop it, e.g. from a flag set by another thread, or to yield
+    /// to a cooperative scheduler, which can then run the search again.
+    ///
+    /// An interval of 0 is taken as 1. Without interruption, this costs a
+    /// branch per char, and the check itself, which should then be cheap,
+    /// like loading an atomic. If `interrupt` panics, the search stops and
+    /// the panic is propagated.
+    ///
+    /// ```rust
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use gregex::Regex;
+    /// use gregex::sink::MatchError;
+    ///
+    /// let re = Regex::pike_vm(r"(a|aa)+$").unwrap();
+    /// let stop = AtomicBool::new(false);
+    /// let check = || stop.load(Ordering::Relaxed);
+    /// let m = re.find_interruptible("aaa", 1024, &check).unwrap().unwrap();
+    /// assert_eq!(m.as_str(), "aaa");
+    /// stop.store(true, Ordering::Relaxed);
+    /// let haystack = "a".repeat(10_000);
+    /// let err = re.find_interruptible(&*haystack, 1024, &check).unwrap_err();
+    /// assert_eq!(err, MatchError::Interrupted);
+    /// ```
+    ///
+    /// Fails without searching if the span of the input is not valid (see
+    /// [`Input::valid`]).
+    pub fn find_interruptible<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        interval: usize,
+        interrupt: &dyn Fn() -> bool,
+    ) -> Result<Option<Match<'s>>, MatchError> {
+        let input = input.into();
+        if !input.valid() {
+            let Span { from, to } = input.span;
+            return Err(MatchError::InvalidSpan { from, to });
+        }
+        let subject = input.subject;
+        let mut span = [Span::invalid()];
+        let mut state = self.pool.get(|| self.engine.new_state());
+        let mut interrupted = Ok(());
+        let found = exec_after_match(&input, &mut span, |input, span| {
+            let Some(input) = self.prefiltered(input) else {
+                return false;
+            };
+            self.engine
+                .exec_interruptible(input, &mut state, span, interval, interrupt)
+                .unwrap_or_else(|err| {
+                    interrupted = Err(err);
+                    false
+                })
+        });
+        interrupted?;
+        Ok(found.then(|| Match::new(subject, span[0])))
+    }
+
+    /// Returns an iterator over the regions of the input between the matches
+    /// of `find_all`, as [`Match`] values even though nothing matched them.
+    /// There is one more gap than there are matches: the gaps before the
+    /// first match and after the last one are reported even if empty, and so
+    /// are the ones between adjacent matches. Like with `find_all`, an empty
+    /// match right after the previous match doesn't end a gap, so the texts
+    /// of the gaps are the pieces of [`Regex::split`], and the gaps and the
+    /// matches, interleaved, cover the whole span of the input.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"\d+").unwrap();
+    /// let gaps: Vec<_> = re.find_gaps("a1b22").map(|gap| gap.as_str()).collect();
+    /// assert_eq!(gaps, ["a", "b", ""]);
+    /// let gap = re.find_gaps("no digits").next().unwrap();
+    /// assert_eq!((gap.start(), gap.end()), (0, 9));
+    /// ```
+    ///
+    /// An input whose span is not valid (see [`Input::valid`]) has no gap.
+    pub fn find_gaps<'r, 's>(&'r self, input: impl Into<Input<'s>>) -> Gaps<'r, 's> {
+        let input = input.into();
+        let (last, end) = (input.valid().then_some(input.span.from), input.span.to);
+        Gaps {
+            matches: self.find_all(input),
+            last,
+            end,
+        }
+    }
+
+    /// Returns an iterator over the pieces of the haystack between the matches,
+    /// like the `split` of the regex crate: the pieces before the first match
+    /// and after the last one are reported even if empty. Like with
+    /// [`Regex::find_all`], an empty match right after the previous match
+    /// doesn't split, e.g. the one at the end of `aaa` for `a*`.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r",\s*").unwrap();
+    /// let pieces: Vec<_> = re.split("a, b,,c,").collect();
+    /// assert_eq!(pieces, ["a", "b", "", "c", ""]);
+    /// let re = Regex::pike_vm(r"").unwrap();
+    /// assert_eq!(re.split("ab").collect::<Vec<_>>(), ["", "a", "b", ""]);
+    /// ```
+    pub fn split<'r, 's>(&'r self, haystack: &'s str) -> Split<'r, 's> {
+        Split {
+            matches: self.find_all(haystack),
+            last: Some(0),
+        }
+    }
+
+    /// Like [`Regex::split`], but returns at most `limit` pieces, the last one
+    /// being the rest of the haystack, unsplit.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"\s+").unwrap();
+    /// let pieces: Vec<_> = re.splitn("a b  c d", 3).collect();
+    /// assert_eq!(pieces, ["a", "b", "c d"]);
+    /// ```
+    pub fn splitn<'r, 's>(&'r self, haystack: &'s str, limit: usize) -> SplitN<'r, 's> {
+        SplitN {
+            split: self.split(haystack),
+            limit,
+        }
+    }
+
+    /// Returns the first `n` matches of `find_all`, and whether there are
+    /// more. The search stops after the `n`th match, and looks for exactly
+    /// one more match to tell if there are more, which costs one extra search
+    /// (up to the end of the input when there is none). With `n == 0`, this
+    /// only tells whether there is a match.
+    pub fn find_n<'s>(&self, input: impl Into<Input<'s>>, n: usize) -> (Vec<Match<'s>>, bool) {
+        let mut input = input.into();
+        let mut imp = self.engine_with_state();
+        let mut matches = Vec::with_capacity(n);
+        let mut span = [Span::invalid()];
+        while matches.len() < n && imp.exec_next(&mut input, &mut span) {
+            matches.push(Match::new(input.subject, span[0]));
+        }
+        let more = matches.len() == n && imp.exec_next(&mut input, &mut span);
+        (matches, more)
+    }
+
+    /// Like `find_n`, with the capture groups of the matches, like
+    /// `find_all_captures`. Not to be confused with `find_captures_n`, which
+    /// only reports the first groups of the first match.
+    pub fn captures_n<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        n: usize,
+    ) -> (Vec<Captures<'s>>, bool) {
+        let mut input = input.into();
+        let mut imp = self.engine_with_state();
+        let mut matches = Vec::with_capacity(n);
+        let mut spans = None;
+        while matches.len() < n {
+            let mut next = next_spans(&mut spans, self.group_info.len());
+            if !imp.exec_next(&mut input, &mut next) {
+                return (matches, false);
             }
+            matches.push(self.captures(input.subject, next));
+        }
+        // Only the overall match is needed to tell if there is another one.
+        let more = imp.exec_next(&mut input, &mut [Span::invalid()]);
+        (matches, more)
+    }
+
+    /// Returns statistics of the matches of `find_all`: the distribution of
+    /// their lengths, and of the gaps between them. They are computed in a
+    /// single pass, without keeping the matches. Like the one-shot searches,
+    /// each search runs after the prefilter (see [`Regex::required_bytes`]).
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"\d+").unwrap();
+    /// let stats = re.match_stats("1 22 333 4444");
+    /// assert_eq!(stats.lengths.count, 4);
+    /// assert_eq!((stats.lengths.min, stats.lengths.max), (1, 4));
+    /// assert_eq!(stats.gaps.total, 3);
+    /// ```
+    pub fn match_stats<'s>(&self, input: impl Into<Input<'s>>) -> MatchStats {
+        let mut input = input.into();
+        let mut imp = self.engine_with_state();
+        let mut span = [Span::invalid()];
+        let mut stats = MatchStats::default();
+        let mut previous_end = None;
+        let mut search = |input: Input<'_>, spans: &mut [Span]| {
+            let input = match &self.prefilter {
+                Some(prefilter) => match prefilter.apply(input) {
+                    Some(input) => input,
+                    None => return false,
+                },
+                None => input,
+            };
+            imp.exec(input, spans)
         };
-        AllMatch {
+        while search_next(&mut input, &mut span, &mut search) {
+            let Span { from, to } = span[0];
+            stats.lengths.add(to - from);
+            if let Some(end) = previous_end {
+                stats.gaps.add(from - end);
+            }
+            previous_end = Some(to);
+        }
+        stats
+    }
+
+    /// The memory held by the idle states of the one-shot searches (such as
+    /// [`Regex::find`]), in bytes, which are kept in a pool for the next
+    /// searches. The iterators own their state instead, see
+    /// [`AllMatch::state_memory_usage`].
+    ///
+    /// A state keeps the memory of the largest search it ran. It only depends
+    /// on the pattern, not on the haystack, except with
+    /// [`CgStrategy::Tree`]:
+    ///
+    /// - The Pike VM and the JIT with [`CgStrategy::Registers`],
+    ///   [`CgStrategy::Array`] or [`CgStrategy::CowArray`] hold a few words
+    ///   per instruction and capture group, since a search never has more
+    ///   threads than instructions.
+    /// - The JIT with [`CgStrategy::Tree`] also holds the nodes of the tree
+    ///   of captures, one per capture written during the search: it grows
+    ///   with the part of the haystack the longest search ran over.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(\w+)@(\w+)").unwrap();
+    /// assert_eq!(re.state_memory_usage(), 0);
+    /// re.find_captures("mail me at me@example");
+    /// let usage = re.state_memory_usage();
+    /// assert!(usage > 0);
+    /// let long = "no address here, ".repeat(1000) + "me@example";
+    /// re.find_captures(long.as_str());
+    /// assert_eq!(re.state_memory_usage(), usage);
+    /// ```
+    pub fn state_memory_usage(&self) -> usize {
+        self.pool.memory_usage(EngineState::memory_usage)
+    }
+
+    /// Like `find_all`, but the iterator owns the input (through an
+    /// [`std::sync::Arc<str>`]), and so does every match it yields. This allows
+    /// returning the iterator from a function owning the haystack.
+    pub fn find_all_owned(&self, input: impl Into<OwnedInput>) -> AllMatchOwned<'_> {
+        AllMatchOwned {
             input: input.into(),
+            look_behind: None,
+            previous_match_end: None,
             spans: [Span::invalid()],
-            imp,
+            imp: self.engine_with_state(),
         }
     }
 
     /// Match the regex against the input and returns a match with all its
     /// capture groups bounds or None If only the overall match is needed, you
-    /// should prefer the use of `find` since it can be faster.
+    /// should prefer the use of `find` since it can be faster. If capture
+    /// groups are disabled (see [`Regex::captures_enabled`]), the match only
+    /// has group 0.
     pub fn find_captures<'s>(&self, input: impl Into<Input<'s>>) -> Option<Captures<'s>> {
+        self.find_captures_n(input, self.group_info.len())
+    }
+
+    /// Returns a buffer for the groups of the matches of this regex, to reuse
+    /// across the searches of [`Regex::captures_read`].
+    pub fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations::new(self.group_info.len())
+    }
+
+    /// Like `find_captures`, but writes the groups to `locs` instead of
+    /// allocating them, and only returns the overall match. The groups of
+    /// `locs` are cleared first, so after a search which finds nothing, none
+    /// of them participated.
+    ///
+    /// `locs` should come from [`Regex::capture_locations`]: if it has fewer
+    /// groups than the regex, only the first ones are reported, like with
+    /// [`Regex::find_captures_n`], and if it has more, the others never
+    /// participate.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(\w+)@(\w+)").unwrap();
+    /// let mut locs = re.capture_locations();
+    /// let mut users = Vec::new();
+    /// for line in ["from: me@host", "nobody", "to: you@there"] {
+    ///     if re.captures_read(&mut locs, line).is_some() {
+    ///         let (start, end) = locs.get(1).unwrap();
+    ///         users.push(&line[start..end]);
+    ///     }
+    /// }
+    /// assert_eq!(users, ["me", "you"]);
+    /// ```
+    pub fn captures_read<'s>(
+        &self,
+        locs: &mut CaptureLocations,
+        input: impl Into<Input<'s>>,
+    ) -> Option<Match<'s>> {
         let input = input.into();
         let subject = input.subject;
-        let mut spans = vec![Span::invalid(); self.capture_count].into_boxed_slice();
-        match &self.engine {
-            RegexEngine::PikeVM(pike_vm) => {
-                let mut state = pike_vm.new_state();
-                if !pike_vm.exec(input, &mut state, &mut spans) {
-                    return None;
-                }
+        let spans = locs.clear();
+        if !self.exec(input, spans) {
+            return None;
+        }
+        Some(Match::new(subject, spans[0]))
+    }
+
+    /// Returns a new state for the searches of this regex which take one, such
+    /// as [`Regex::find_with`], see [`ScratchState`].
+    pub fn scratch_state(&self) -> ScratchState {
+        ScratchState {
+            state: self.engine.new_state(),
+            regex_id: self.id,
+        }
+    }
+
+    /// Like `is_match`, but searches with the given state instead of one of
+    /// the pool of the regex, see [`ScratchState`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state was created by another regex.
+    pub fn is_match_with<'s>(
+        &self,
+        scratch: &mut ScratchState,
+        input: impl Into<Input<'s>>,
+    ) -> bool {
+        self.exec_with(scratch, input.into().first_match(true), &mut [])
+    }
+
+    /// Like `find`, but searches with the given state, see
+    /// [`Regex::is_match_with`].
+    pub fn find_with<'s>(
+        &self,
+        scratch: &mut ScratchState,
+        input: impl Into<Input<'s>>,
+    ) -> Option<Match<'s>> {
+        let input = input.into();
+        let subject = input.subject;
+        let mut span = [Span::invalid()];
+        if !self.exec_with(scratch, input, &mut span) {
+            return None;
+        }
+        Some(Match::new(subject, span[0]))
+    }
+
+    /// Like `captures_read`, but searches with the given state, see
+    /// [`Regex::is_match_with`]. Neither allocates.
+    pub fn captures_read_with<'s>(
+        &self,
+        scratch: &mut ScratchState,
+        locs: &mut CaptureLocations,
+        input: impl Into<Input<'s>>,
+    ) -> Option<Match<'s>> {
+        let input = input.into();
+        let subject = input.subject;
+        let spans = locs.clear();
+        if !self.exec_with(scratch, input, spans) {
+            return None;
+        }
+        Some(Match::new(subject, spans[0]))
+    }
+
+    /// Like `find_captures`, but only reports the first `n` groups (the
+    /// overall match being group 0, it is always reported). The engines skip
+    /// the work for the other groups, which makes this faster than
+    /// `find_captures` when only a few groups of a large pattern are needed.
+    pub fn find_captures_n<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        n: usize,
+    ) -> Option<Captures<'s>> {
+        let input = input.into();
+        let subject = input.subject;
+        let n = n.clamp(1, self.group_info.len());
+        let mut spans = vec![Span::invalid(); n].into_boxed_slice();
+        if !self.exec(input, &mut spans) {
+            return None;
+        }
+        Some(self.captures(subject, spans))
+    }
+
+    /// Like `find`, but adds the durations of the phases of the search to
+    /// `timings`, if any. Without timings, this is exactly `find`.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    /// use gregex::util::ExecTimings;
+    ///
+    /// let re = Regex::pike_vm(r"\d+").unwrap();
+    /// let mut timings = ExecTimings::default();
+    /// for haystack in ["a 1", "b 22", "c"] {
+    ///     re.find_timed(haystack, Some(&mut timings));
+    /// }
+    /// assert_eq!(timings.searches, 3);
+    /// assert!(timings.total >= timings.scan);
+    /// ```
+    pub fn find_timed<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        timings: Option<&mut ExecTimings>,
+    ) -> Option<Match<'s>> {
+        let Some(timings) = timings else {
+            return self.find(input);
+        };
+        let input = input.into();
+        let subject = input.subject;
+        let mut span = Span::invalid();
+        let (found, (started, set_up, scanned)) =
+            self.exec_timed(input, std::slice::from_mut(&mut span));
+        let found = found.then(|| Match::new(subject, span));
+        timings.add(started, set_up, scanned);
+        found
+    }
+
+    /// Like `find_captures`, but adds the durations of the phases of the
+    /// search to `timings`, if any, see `find_timed`.
+    pub fn find_captures_timed<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        timings: Option<&mut ExecTimings>,
+    ) -> Option<Captures<'s>> {
+        let Some(timings) = timings else {
+            return self.find_captures(input);
+        };
+        let input = input.into();
+        let subject = input.subject;
+        let mut spans = vec![Span::invalid(); self.group_info.len()].into_boxed_slice();
+        let (found, (started, set_up, scanned)) = self.exec_timed(input, &mut spans);
+        let found = found.then(|| self.captures(subject, spans));
+        timings.add(started, set_up, scanned);
+        found
+    }
+
+    /// Like `find_captures`, but reports the capture groups to the observer
+    /// while the search is running, see [`GroupEvent`]. This is useful for
+    /// very long matches, when only a few groups near their start are needed.
+    ///
+    /// Only the Pike VM reports the events as they happen. Other engines (the
+    /// JIT, or a specialized engine picked by the builder) report the groups
+    /// of the final match once it is found, as a single committed candidate.
+    pub fn find_captures_streaming<'s>(
+        &self,
+        input: impl Into<Input<'s>>,
+        mut observer: impl FnMut(GroupEvent),
+    ) -> Option<Captures<'s>> {
+        let input = input.into();
+        if let RegexEngine::PikeVM(pike_vm) = self.engine.general() {
+            let subject = input.subject;
+            let mut spans = vec![Span::invalid(); self.group_info.len()].into_boxed_slice();
+            let mut state = pike_vm.new_state();
+            // The observer must not see the empty match skipped right after
+            // the previous match, so it is looked for first, without it.
+            let input = skip_empty_after_match(input, |input, spans| {
+                pike_vm.exec_with_observer(input, &mut state, spans, |_| {})
+            })?;
+            if !pike_vm.exec_with_observer(input, &mut state, &mut spans, observer) {
+                return None;
             }
-            RegexEngine::JittedRegex(jitted_regex) => {
-                let mut state = jitted_regex.new_state();
-                if !jitted_regex.exec(input, &mut state, &mut spans) {
-                    return None;
-                }
+            return Some(self.captures(subject, spans));
+        }
+        let captures = self.find_captures(input)?;
+        let candidate = 0;
+        for group in 0..captures.group_len() {
+            if let Some(m) = captures.get(group) {
+                let (position, end) = (m.start(), m.end());
+                observer(GroupEvent::Open {
+                    candidate,
+                    group,
+                    position,
+                });
+                observer(GroupEvent::Close {
+                    candidate,
+                    group,
+                    position: end,
+                });
             }
         }
-        Some(Captures::new(subject, spans.clone()))
+        observer(GroupEvent::Commit { candidate });
+        Some(captures)
     }
 
     /// Rerturns an interator over all non-overlapping match in the input, with
     /// their capture group bounds. If only the overall match is needed, you
     /// should prefer the use of `find_all` since it can be faster.
     pub fn find_all_captures<'r, 's>(&'r self, input: impl Into<Input<'s>>) -> AllCaptures<'r, 's> {
-        let imp = match &self.engine {
-            RegexEngine::PikeVM(pike_vm) => {
-                let state = pike_vm.new_state();
-                EngineWithState::PikeVM(pike_vm, state)
-            }
-            RegexEngine::JittedRegex(jitted_regex) => {
-                let state = jitted_regex.new_state();
-                EngineWithState::JittedRegex(jitted_regex, state)
-            }
-        };
-        let spans = vec![Span::invalid(); self.capture_count].into_boxed_slice();
         AllCaptures {
             input: input.into(),
-            spans,
-            imp,
+            group_info: self.group_info.clone(),
+            spans: None,
+            imp: self.engine_with_state(),
+        }
+    }
+
+    /// Like `find_all_captures`, but the iterator owns the input, see
+    /// `find_all_owned`.
+    pub fn find_all_captures_owned(&self, input: impl Into<OwnedInput>) -> AllCapturesOwned<'_> {
+        AllCapturesOwned {
+            input: input.into(),
+            look_behind: None,
+            previous_match_end: None,
+            group_info: self.group_info.clone(),
+            spans: None,
+            imp: self.engine_with_state(),
+        }
+    }
+
+    /// Replaces the leftmost match with the replacement, see
+    /// [`Regex::replace_all`].
+    pub fn replace<'s>(&self, haystack: &'s str, rep: impl Replacer) -> Cow<'s, str> {
+        self.replacen(haystack, 1, rep)
+    }
+
+    /// Replaces every non-overlapping match with the replacement, and returns
+    /// the haystack as is if there is none. The matches are the ones of
+    /// [`Regex::find_all`], which like the regex crate doesn't report an empty
+    /// match right after the previous match. The replacement is a string
+    /// whose references to groups are expanded with the captures of each
+    /// match (see [`Captures::expand`]), a closure, or a
+    /// [`crate::replace::NoExpand`] string, see [`crate::replace`].
+    ///
+    /// If capture groups are disabled (see [`Regex::captures_enabled`]), the
+    /// references to groups other than group 0 are replaced by nothing.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(?P<y>\d{4})-(\d{2})").unwrap();
+    /// let replaced = re.replace_all("2024-03, 1999-12", "$2/${y}");
+    /// assert_eq!(replaced, "03/2024, 12/1999");
+    /// // Like in the regex crate, an empty match right after another one is
+    /// // not replaced.
+    /// let re = Regex::pike_vm(r"a*").unwrap();
+    /// assert_eq!(re.replace_all("baac", "-"), "-b-c-");
+    /// ```
+    pub fn replace_all<'s>(&self, haystack: &'s str, rep: impl Replacer) -> Cow<'s, str> {
+        self.replacen(haystack, 0, rep)
+    }
+
+    /// Replaces the first `limit` matches with the replacement, or all of
+    /// them if `limit` is 0, like the regex crate does, see
+    /// [`Regex::replace_all`].
+    pub fn replacen<'s>(
+        &self,
+        haystack: &'s str,
+        limit: usize,
+        mut rep: impl Replacer,
+    ) -> Cow<'s, str> {
+        let limit = if limit == 0 { usize::MAX } else { limit };
+        if let Some(text) = rep.no_expansion() {
+            let matches = self.find_all(haystack).map(|m| m.span);
+            replace_matches(
+                haystack,
+                limit,
+                matches,
+                |span| *span,
+                |_, dst| dst.push_str(&text),
+            )
+        } else {
+            let matches = self.find_all_captures(haystack);
+            replace_matches(
+                haystack,
+                limit,
+                matches,
+                |caps| caps.group0().span,
+                |caps, dst| rep.replace_append(caps, dst),
+            )
+        }
+    }
+
+    /// Returns the engine with a new state, owned by the caller. This is what
+    /// iterators use, so that other searches on this regex can't observe or
+    /// modify their state.
+    pub(crate) fn engine_with_state(&self) -> EngineWithState<'_> {
+        EngineWithState {
+            engine: &self.engine,
+            state: self.engine.new_state(),
+            interrupt: None,
+            interrupted: false,
+        }
+    }
+
+    /// Runs a one-shot search, with a state checked out of the pool for its
+    /// duration. The prefilter runs first, and may answer without the engine.
+    fn exec(&self, input: Input<'_>, captures: &mut [Span]) -> bool {
+        exec_after_match(&input, captures, |input, captures| {
+            let Some(input) = self.prefiltered(input) else {
+                return false;
+            };
+            let mut state = self.pool.get(|| self.engine.new_state());
+            self.engine.exec(input, &mut state, captures)
+        })
+    }
+
+    /// Like `exec`, with the caller's state.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state was created by another regex.
+    fn exec_with(
+        &self,
+        scratch: &mut ScratchState,
+        input: Input<'_>,
+        captures: &mut [Span],
+    ) -> bool {
+        assert_eq!(
+            scratch.regex_id, self.id,
+            "ScratchState used with a regex which didn't create it"
+        );
+        exec_after_match(&input, captures, |input, captures| {
+            let Some(input) = self.prefiltered(input) else {
+                return false;
+            };
+            self.engine.exec(input, &mut scratch.state, captures)
+        })
+    }
+
+    /// The input the engine must search, or None if the prefilter already
+    /// rejected it.
+    fn prefiltered<'s>(&self, input: Input<'s>) -> Option<Input<'s>> {
+        match &self.prefilter {
+            Some(prefilter) => prefilter.apply(input),
+            None => Some(input),
+        }
+    }
+
+    /// Like `exec`, but the state is checked out first, so that the search
+    /// can be split in phases, see [`ExecTimings`]. Also returns when it
+    /// started, and when its setup and scan ended.
+    fn exec_timed(&self, input: Input<'_>, captures: &mut [Span]) -> (bool, PhaseEnds) {
+        let started = Instant::now();
+        let mut state = self.pool.get(|| self.engine.new_state());
+        let set_up = Instant::now();
+        let found = exec_after_match(&input, captures, |input, captures| {
+            self.prefiltered(input)
+                .is_some_and(|input| self.engine.exec(input, &mut state, captures))
+        });
+        (found, (started, set_up, Instant::now()))
+    }
+
+    /// Like `exec`, with the given state, on an input whose subject is the text
+    /// of the document. With a line anchor, the engine only runs at the
+    /// positions where a match can be, which are tried in order.
+    fn exec_in_document(
+        &self,
+        document: &Document<'_>,
+        input: Input<'_>,
+        state: &mut EngineState,
+        captures: &mut [Span],
+    ) -> bool {
+        let mut input = match &self.prefilter {
+            Some(prefilter) => match prefilter.apply_in_document(input, document) {
+                Some(input) => input,
+                None => return false,
+            },
+            None => input,
+        };
+        let Some(anchor) = self
+            .line_anchor
+            .filter(|_| input.valid() && !input.anchored)
+        else {
+            return self.engine.exec(input, state, captures);
+        };
+        input.anchored = true;
+        let Span { from, to } = input.span;
+        for candidate in document.candidates(anchor, from, to) {
+            input.span.from = candidate;
+            if self.engine.exec(input.clone(), state, captures) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the number of capture groups of this regex, including the
+    /// implicit group 0 (the overall match), which is the length of the
+    /// captures it reports. This is 1 if capture groups are disabled (see
+    /// [`Builder::cg`]).
+    pub fn group_len(&self) -> usize {
+        self.group_info.len()
+    }
+
+    /// Returns the capture groups of this regex, with their names. Like
+    /// [`Regex::group_len`], there is only group 0 if capture groups are
+    /// disabled.
+    pub fn group_info(&self) -> &GroupInfo {
+        &self.group_info
+    }
+
+    /// Returns the name of every capture group of this regex, in order,
+    /// `None` for the groups without names (always group 0).
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(?P<year>\d{4})-(\d{2})").unwrap();
+    /// let names: Vec<_> = re.group_names().collect();
+    /// assert_eq!(names, [None, Some("year"), None]);
+    /// ```
+    pub fn group_names(&self) -> impl ExactSizeIterator<Item = Option<&str>> + '_ {
+        self.group_info.names()
+    }
+
+    /// Captures of a match of this regex, with the names of its groups.
+    fn captures<'s>(&self, subject: &'s str, spans: Box<[Span]>) -> Captures<'s> {
+        Captures::new(subject, spans).with_group_info(self.group_info.clone())
+    }
+
+    /// Whether this regex tracks its capture groups, see [`Builder::cg`]. If
+    /// not, `find_captures` and the other searches reporting captures still
+    /// work, but only report group 0.
+    pub fn captures_enabled(&self) -> bool {
+        self.config.cg
+    }
+
+    /// Returns the maximum length in bytes of a match of this regex, or None
+    /// if it is unbounded (e.g. `a+`).
+    pub fn maximum_len(&self) -> Option<usize> {
+        let engine = match &self.engine {
+            RegexEngine::Prefixed(engine) => &engine.inner,
+            engine => engine,
+        };
+        match engine {
+            RegexEngine::PikeVM(pike_vm) => pike_vm.maximum_len(),
+            RegexEngine::AssertionOnly(engine) => engine.maximum_len(),
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            RegexEngine::JittedRegex(jitted_regex) => jitted_regex.maximum_len(),
+            RegexEngine::LeadingWildcard(_) => None,
+            RegexEngine::LazyJit(engine) => match &engine.vm {
+                RegexEngine::PikeVM(pike_vm) => pike_vm.maximum_len(),
+                _ => unreachable!("the lazy JIT starts on the Pike VM"),
+            },
+            RegexEngine::EndAnchored(engine) => Some(engine.maximum_len),
+            RegexEngine::Prefixed(_) => unreachable!("the prefixes wrap the general engine"),
         }
     }
 
-    pub fn pike_vm(pattern: &str) -> Result<Self, CompileError> {
+    /// Returns how the JIT engine of this regex tracks the capture groups,
+    /// i.e. the strategy picked for [`CgStrategy::Auto`] (see
+    /// [`Builder::jit_cg_strategy`]). None if the regex doesn't run the JIT,
+    /// e.g. for the Pike VM or a specialized engine, or for the lazy JIT until
+    /// it is compiled (see [`Builder::lazy_jit`]).
+    pub fn jit_cg_strategy(&self) -> Option<CgStrategy> {
+        let engine = match &self.engine {
+            RegexEngine::LeadingWildcard(engine) => &engine.full,
+            RegexEngine::EndAnchored(engine) => &engine.inner,
+            engine => engine,
+        };
+        let engine = match engine {
+            RegexEngine::Prefixed(engine) => &engine.inner,
+            engine => engine,
+        };
+        let engine = match engine {
+            RegexEngine::LazyJit(engine) => engine.jit()?,
+            engine => engine,
+        };
+        match engine {
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            RegexEngine::JittedRegex(jitted_regex) => Some(jitted_regex.cg_strategy()),
+            _ => None,
+        }
+    }
+
+    /// Returns an estimate of the work a search costs per char of the
+    /// haystack, with the parts of the pattern which weigh the most, see
+    /// [`crate::complexity`]. This compiles the pattern again.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    /// use gregex::complexity::ComplexityNote;
+    ///
+    /// let simple = Regex::pike_vm(r"[0-9]+").unwrap().complexity();
+    /// assert!(simple.notes.is_empty());
+    /// let slow = Regex::pike_vm(r"(?:\w|-){0,200}x").unwrap().complexity();
+    /// assert!(slow.per_char_cost_estimate > 1000 * simple.per_char_cost_estimate);
+    /// assert_eq!(
+    ///     slow.notes[0],
+    ///     ComplexityNote::UnrolledRepetition { times: 200, instructions: 400 }
+    /// );
+    /// ```
+    pub fn complexity(&self) -> ComplexityEstimate {
+        let hir =
+            Compiler::parse(&self.pattern, &self.config).expect("the pattern of a regex is valid");
+        let bytecode = Compiler::compile(hir.clone(), self.config.clone())
+            .expect("the pattern of a regex is valid");
+        complexity::estimate(&hir, &bytecode, &self.config)
+    }
+
+    /// Returns the bytes such that every match contains at least one of them,
+    /// which `is_match`, `find` and `find_captures` look for before running
+    /// the engine. None if no small set of such bytes is known, or if the
+    /// prefilter is disabled (see [`Builder::prefilter`]).
+    pub fn required_bytes(&self) -> Option<&[u8]> {
+        self.prefilter.as_ref().map(RequiredBytes::bytes)
+    }
+
+    /// Returns the literals one of which starts every match, whose
+    /// occurrences the searches look for (with `memmem`) before running the
+    /// engine from there. None if there is no small set of such literals, or
+    /// if the prefilter is disabled (see [`Builder::prefilter`]).
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"Sherlock [A-Z]\w+|John Watson").unwrap();
+    /// let prefixes = re.prefix_literals().unwrap();
+    /// assert_eq!(prefixes, [&b"Sherlock "[..], b"John Watson"]);
+    /// assert!(Regex::pike_vm(r"\w+").unwrap().prefix_literals().is_none());
+    /// ```
+    pub fn prefix_literals(&self) -> Option<Vec<&[u8]>> {
+        let engine = match &self.engine {
+            RegexEngine::EndAnchored(engine) => &engine.inner,
+            engine => engine,
+        };
+        match engine {
+            RegexEngine::Prefixed(engine) => Some(engine.prefixes.literals().collect()),
+            _ => None,
+        }
+    }
+
+    /// Returns how long building this regex took, by phase, if it was built
+    /// with [`Builder::timings`].
+    pub fn build_timings(&self) -> Option<BuildTimings> {
+        self.build_timings
+    }
+
+    /// Returns the pattern this regex was built from.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the configuration this regex was built with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the engine requested when building this regex. The builder
+    /// may have picked a specialized engine instead (see
+    /// [`Builder::specialize`]), this is still the one reported here.
+    pub fn engine_kind(&self) -> EngineKind {
+        self.kind
+    }
+
+    /// Builds a variant of this regex: the builder passed to `f` holds the
+    /// pattern and the configuration of this regex, and the result is built
+    /// with the same engine kind.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm("abc").unwrap();
+    /// let insensitive = re.rebuild(|builder| builder.case_insensitive(true)).unwrap();
+    /// assert!(!re.is_match("ABC"));
+    /// assert!(insensitive.is_match("ABC"));
+    /// ```
+    pub fn rebuild<'r>(
+        &'r self,
+        f: impl FnOnce(Builder<'r>) -> Builder<'r>,
+    ) -> Result<Regex, crate::Error> {
+        let builder = Builder {
+            pattern: Cow::Borrowed(&self.pattern),
+            config: self.config.clone(),
+        };
+        f(builder).build(self.kind)
+    }
+
+    /// Compiles the pattern with the JIT now, if the regex was built by
+    /// [`Builder::lazy_jit`] and didn't compile it yet, instead of waiting
+    /// for [`Builder::lazy_jit_after`] searches. Returns whether the searches
+    /// of the regex run the JIT, which is always the case for the regexes
+    /// built by [`Builder::pike_jit`], and never for the ones built by
+    /// [`Builder::pike_vm`] or the specialized engines.
+    ///
+    /// ```rust
+    /// use gregex::Builder;
+    ///
+    /// let re = Builder::new(r"(\w+)@(\w+)").lazy_jit_after(None).lazy_jit().unwrap();
+    /// let jit = cfg!(all(feature = "jit", target_arch = "x86_64", any(unix, windows)));
+    /// assert_eq!(re.force_jit(), jit);
+    /// assert_eq!(re.find("me@example").unwrap().as_str(), "me@example");
+    /// ```
+    pub fn force_jit(&self) -> bool {
+        self.engine.force_jit()
+    }
+
+    pub fn pike_vm(pattern: &str) -> Result<Self, crate::Error> {
         Builder::new(pattern).pike_vm()
     }
 
-    pub fn pike_jit(pattern: &str) -> Result<Self, CompileError> {
+    pub fn pike_jit(pattern: &str) -> Result<Self, crate::Error> {
         Builder::new(pattern).pike_jit()
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Config {
     pub unicode: bool,
     pub case_insensitive: bool,
     pub cg: bool,
+    pub specialize: bool,
+    /// Compile every class as outlined, see
+    /// [`crate::thompson::bytecode::MAX_INLINE_CLASS_RANGES`].
+    pub force_class_outlining: bool,
+    /// Compile every class inline, see
+    /// [`crate::thompson::bytecode::MAX_INLINE_CLASS_RANGES`].
+    pub force_class_inlining: bool,
+    pub match_kind: MatchKind,
+    /// Maximum nesting depth of the pattern, see
+    /// [`regex_syntax::ParserBuilder::nest_limit`].
+    pub nest_limit: u32,
+    /// Look for the required bytes and the prefixes of the pattern before
+    /// running the engine, see [`Regex::required_bytes`] and
+    /// [`Regex::prefix_literals`].
+    pub prefilter: bool,
+    /// Parse `*+`, `++`, `?+` and `{n,m}+` as possessive quantifiers, see
+    /// [`Builder::possessive_quantifiers`].
+    pub possessive_quantifiers: bool,
+    /// How the JIT tracks the capture groups, see
+    /// [`Builder::jit_cg_strategy`].
+    pub jit_cg_strategy: CgStrategy,
+    /// Also compile the JIT for the overall match only, see
+    /// [`Builder::match_only_jit`].
+    pub match_only_jit: bool,
+    /// After how many searches the lazy JIT compiles the pattern, see
+    /// [`Builder::lazy_jit_after`].
+    pub lazy_jit_after: Option<usize>,
+    /// The maximum size of the compiled program in bytes, see
+    /// [`Builder::size_limit`].
+    pub size_limit: usize,
+    /// Record how long the phases of the build take, see
+    /// [`Builder::timings`].
+    pub timings: bool,
 }
 
 impl Default for Config {
@@ -145,6 +1439,18 @@ impl Default for Config {
             unicode: true,
             case_insensitive: false,
             cg: true,
+            specialize: true,
+            force_class_outlining: false,
+            force_class_inlining: false,
+            match_kind: MatchKind::LeftmostFirst,
+            nest_limit: 250,
+            prefilter: true,
+            possessive_quantifiers: false,
+            jit_cg_strategy: CgStrategy::Auto,
+            match_only_jit: false,
+            lazy_jit_after: Some(100),
+            size_limit: 10 * (1 << 20),
+            timings: false,
         }
     }
 }
@@ -154,20 +1460,125 @@ impl From<Config> for regex_syntax::Parser {
         regex_syntax::ParserBuilder::new()
             .unicode(value.unicode)
             .case_insensitive(value.case_insensitive)
+            .nest_limit(value.nest_limit)
             .build()
     }
 }
 
-#[derive(Debug, Clone)]
+/// Which match is reported among the ones starting at the leftmost position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MatchKind {
+    /// Perl semantics: the match of the highest priority thread wins, i.e.
+    /// alternatives are tried in order and repetitions are greedy unless
+    /// marked lazy. This is what the `regex` crate does.
+    #[default]
+    LeftmostFirst,
+    /// POSIX semantics: the longest match wins, no matter the order of the
+    /// alternatives or the greediness of repetitions. Among the ways to
+    /// produce that longest match, the capture groups are the ones of the
+    /// highest priority one, as with [`MatchKind::LeftmostFirst`]. This
+    /// differs from the POSIX subexpression rules, which maximize each group
+    /// in turn.
+    ///
+    /// Only supported by the Pike VM.
+    LeftmostLongest,
+}
+
+/// How the JIT engine tracks the capture groups of its threads, see
+/// [`Builder::jit_cg_strategy`]. Every strategy reports the same matches and
+/// captures, they only differ in speed and memory.
+///
+/// | Strategy    | Memory                  | Capture write            | Fork           |
+/// |-------------|-------------------------|--------------------------|----------------|
+/// | `Registers` | none (group 0 only)     | O(1)                     | O(1)           |
+/// | `Tree`      | grows with the haystack | O(1), allocates a node   | O(1)           |
+/// | `Array`     | O(threads × groups)     | O(1)                     | O(groups) copy |
+/// | `CowArray`  | O(threads × groups)     | O(groups) copy if shared | O(1)           |
+///
+/// The time of `find_all_captures` over 100KB of english text, from
+/// `cargo bench -- cg_strategy` (the runs vary by about 10%):
+///
+/// | Pattern                          | `Tree` | `Array` | `CowArray` |
+/// |----------------------------------|--------|---------|------------|
+/// | `(\w+) \d+`                      | 2.16ms | 2.17ms  | 2.10ms     |
+/// | `([A-Z])(\w+) (\w+)(,\|\.)`      | 1.25ms | 1.29ms  | 1.25ms     |
+/// | `(\w)` 15 times                  | 4.75ms | 3.49ms  | 4.05ms     |
+/// | `(\w+) (?:she\|him\|...)(\w*)`   | 2.06ms | 2.91ms  | 2.87ms     |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CgStrategy {
+    /// Pick one from the pattern: [`CgStrategy::Registers`] if it has no
+    /// capture groups (or they are disabled, see [`Builder::cg`]),
+    /// [`CgStrategy::Array`] if it has at least 12 groups and compiles to at
+    /// most 256 instructions, and
+    /// [`CgStrategy::Tree`] otherwise. The strategy picked is reported by
+    /// [`Regex::jit_cg_strategy`].
+    #[default]
+    Auto,
+    /// Only the overall match, kept in two registers. Building fails with
+    /// [`UnsupportedCgStrategy`] if the pattern has capture groups.
+    Registers,
+    /// Each thread points to the last capture it wrote, in a tree of the
+    /// captures shared by all the threads. Every operation is O(1), but the
+    /// tree grows with the haystack.
+    Tree,
+    /// Each thread has its own array of captures, copied when it forks.
+    Array,
+    /// Like [`CgStrategy::Array`], but the threads share their arrays until
+    /// one of them writes a capture.
+    CowArray,
+}
+
+/// The regex engines that can be built by a [`Builder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EngineKind {
+    /// See [`Builder::pike_vm`]
+    PikeVM,
+    /// See [`Builder::pike_jit`]
+    PikeJIT,
+    /// See [`Builder::pike_jit_array`]
+    PikeJITArray,
+    /// See [`Builder::pike_jit_cow_array`]
+    PikeJITCowArray,
+    /// See [`Builder::lazy_jit`]
+    LazyJIT,
+}
+
+impl EngineKind {
+    /// Every engine, including the ones not available on this platform. The
+    /// lazy JIT isn't listed, since it runs the Pike VM and then the JIT.
+    pub const ALL: [EngineKind; 4] = [
+        EngineKind::PikeVM,
+        EngineKind::PikeJIT,
+        EngineKind::PikeJITArray,
+        EngineKind::PikeJITCowArray,
+    ];
+
+    /// The name of the engine, as the [`Builder`] method building it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineKind::PikeVM => "pike_vm",
+            EngineKind::PikeJIT => "pike_jit",
+            EngineKind::PikeJITArray => "pike_jit_array",
+            EngineKind::PikeJITCowArray => "pike_jit_cow_array",
+            EngineKind::LazyJIT => "lazy_jit",
+        }
+    }
+}
+
+/// Builds a [`Regex`] from a pattern and some configuration.
+///
+/// The builder owns its pattern (or borrows it, through a [`Cow`]), and can be
+/// used to build as many regexes as needed, with any engine.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Builder<'s> {
-    pattern: &'s str,
+    pattern: Cow<'s, str>,
     config: Config,
 }
 
 impl<'s> Builder<'s> {
-    pub fn new(pattern: &'s str) -> Self {
+    pub fn new(pattern: impl Into<Cow<'s, str>>) -> Self {
         Self {
-            pattern,
+            pattern: pattern.into(),
             config: Config::default(),
         }
     }
@@ -182,49 +1593,772 @@ impl<'s> Builder<'s> {
         self
     }
 
+    /// Whether the capture groups are tracked (default: true). If not, the
+    /// regex reports only the overall match, as group 0.
     pub fn cg(mut self, value: bool) -> Self {
         self.config.cg = value;
         self
     }
 
-    pub fn pike_vm(self) -> Result<Regex, CompileError> {
-        let pike_vm = PikeVM::new(self.pattern, self.config)?;
-        let capture_count = pike_vm.capture_count();
+    /// Whether the builder may replace the requested engine by a specialized
+    /// one, for patterns where it is known to be faster (default: true).
+    /// Currently, patterns made only of assertions (e.g. `(?m)^$`) are
+    /// answered without running the general engines, and the searches of the
+    /// patterns whose matches end at the end of the haystack, with a bounded
+    /// length (e.g. `\.log$`), start that far from its end.
+    pub fn specialize(mut self, value: bool) -> Self {
+        self.config.specialize = value;
+        self
+    }
 
-        Ok(Regex {
-            engine: RegexEngine::PikeVM(pike_vm),
-            capture_count,
-        })
+    /// Compile every character class as outlined, no matter its size
+    /// (default: false). Only meant to test the engines, since which classes
+    /// are outlined changes the code they run. Building fails if both this and
+    /// [`Builder::force_class_inlining`] are set.
+    pub fn force_class_outlining(mut self, value: bool) -> Self {
+        self.config.force_class_outlining = value;
+        self
     }
 
-    pub fn pike_jit(self) -> Result<Regex, CompileError> {
-        let pike_jit = JittedRegex::new(self.pattern, self.config)?;
-        let capture_count = pike_jit.capture_count();
-        Ok(Regex {
-            engine: RegexEngine::JittedRegex(pike_jit),
-            capture_count,
-        })
+    /// Compile every character class inline, no matter its size (default:
+    /// false). See [`Builder::force_class_outlining`].
+    pub fn force_class_inlining(mut self, value: bool) -> Self {
+        self.config.force_class_inlining = value;
+        self
     }
 
-    pub fn pike_jit_array(self) -> Result<Regex, CompileError> {
-        let pike_jit = JittedRegex::new_array(self.pattern, self.config)?;
-        let capture_count = pike_jit.capture_count();
-        Ok(Regex {
-            engine: RegexEngine::JittedRegex(pike_jit),
-            capture_count,
+    /// Which match to report among the ones starting at the leftmost position
+    /// (default: [`MatchKind::LeftmostFirst`]). Building a JIT engine with
+    /// [`MatchKind::LeftmostLongest`] fails with [`UnsupportedMatchKind`].
+    pub fn match_kind(mut self, value: MatchKind) -> Self {
+        self.config.match_kind = value;
+        self
+    }
+
+    /// Maximum nesting depth of groups and repetitions in the pattern
+    /// (default: 250, like the `regex` crate). Deeper patterns are rejected
+    /// when parsed.
+    pub fn nest_limit(mut self, value: u32) -> Self {
+        self.config.nest_limit = value;
+        self
+    }
+
+    /// Whether the one-shot searches (`is_match`, `find`, `find_captures`)
+    /// first look for the bytes required by any match, and report no match
+    /// without running the engine if there is none (default: true). See
+    /// [`Regex::required_bytes`]. This also disables the search for the
+    /// prefixes of the matches, see [`Regex::prefix_literals`].
+    pub fn prefilter(mut self, value: bool) -> Self {
+        self.config.prefilter = value;
+        self
+    }
+
+    /// Whether a `+` after a repetition makes it possessive (default: false).
+    /// A possessive repetition matches like the greedy one, but once it
+    /// matched, the regex never tries to match it differently: `a*+a` never
+    /// matches, since `a*+` takes all the `a`. This prunes the threads which
+    /// would only try the other ways, e.g. in `\d++x` the threads which
+    /// would retry with fewer digits.
+    ///
+    /// Without it, a `+` after a repetition repeats it (e.g. `a*+` is
+    /// `(?:a*)+`), like in the `regex` crate. Only the Pike VM supports
+    /// possessive repetitions, building a JIT engine for a pattern using them
+    /// fails.
+    ///
+    /// A possessive repetition repeated itself may give back when it is
+    /// matched again before its previous match is settled, e.g.
+    /// `(?:(?:aa)*+a)+` matches all of `aa`.
+    pub fn possessive_quantifiers(mut self, value: bool) -> Self {
+        self.config.possessive_quantifiers = value;
+        self
+    }
+
+    /// How the JIT engine tracks the capture groups (default:
+    /// [`CgStrategy::Auto`]). Only meant for tuning and benchmarks: the
+    /// matches are the same with every strategy. Building a JIT engine with
+    /// [`CgStrategy::Registers`] for a pattern with capture groups fails with
+    /// [`UnsupportedCgStrategy`]. The Pike VM ignores this setting.
+    pub fn jit_cg_strategy(mut self, value: CgStrategy) -> Self {
+        self.config.jit_cg_strategy = value;
+        self
+    }
+
+    /// Whether the JIT also compiles the pattern for the overall match only,
+    /// with [`CgStrategy::Registers`], for the searches which don't need the
+    /// capture groups, such as `is_match` and `find` (default: false).
+    /// Tracking the groups costs the other strategies time on every capture,
+    /// which these searches then skip, at the cost of compiling the pattern
+    /// to machine code twice. Both share the bytecode and the state.
+    ///
+    /// Patterns without capture groups (or with [`Builder::cg`] disabled)
+    /// already use the registers, and the Pike VM always skips the groups
+    /// when they aren't needed, so neither compiles anything more.
+    pub fn match_only_jit(mut self, value: bool) -> Self {
+        self.config.match_only_jit = value;
+        self
+    }
+
+    /// After how many searches a regex built by [`Builder::lazy_jit`] compiles
+    /// its pattern to machine code (default: 100), each match of an iterator
+    /// counting as a search. With None, only [`Regex::force_jit`] compiles it.
+    pub fn lazy_jit_after(mut self, value: Option<usize>) -> Self {
+        self.config.lazy_jit_after = value;
+        self
+    }
+
+    /// The maximum size of the compiled program in bytes (default: 10MB, like
+    /// the `regex` crate): the instructions of the bytecode, and the machine
+    /// code of the JIT. Building a regex whose program is larger fails with
+    /// [`CompileError::CompiledProgramTooLarge`], which the bytecode compiler
+    /// knows before compiling, and the JIT as soon as it emitted that much
+    /// code. E.g. the bytecode of `(?:\w{100}){100}` takes 240KB, and its
+    /// machine code a few MB. This bounds the memory a pattern from an
+    /// untrusted source can use, along with the fixed [`crate::limits`].
+    ///
+    /// [`CompileError::CompiledProgramTooLarge`]: crate::thompson::bytecode::CompileError::CompiledProgramTooLarge
+    pub fn size_limit(mut self, value: usize) -> Self {
+        self.config.size_limit = value;
+        self
+    }
+
+    /// Whether the regexes built record how long each phase of their build
+    /// took, see [`Regex::build_timings`] (default: false). Searches are
+    /// timed separately, see [`Regex::find_timed`].
+    pub fn timings(mut self, value: bool) -> Self {
+        self.config.timings = value;
+        self
+    }
+
+    /// Returns the pattern this builder compiles.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the configuration used when building regexes.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Returns the notes of the compiler about the pattern, such as removed
+    /// duplicate alternation branches, see [`CompileNote`]. They don't change
+    /// the matches, but may point at mistakes in the pattern.
+    pub fn notes(&self) -> Result<Vec<CompileNote>, crate::Error> {
+        let hir = Compiler::parse(&self.pattern, &self.config)?;
+        let (_, notes) = Compiler::compile_with_notes(hir, self.config.clone())?;
+        Ok(notes)
+    }
+
+    /// Build a regex with the given engine. Useful when the engine is selected
+    /// from some configuration data, otherwise prefer the dedicated methods.
+    pub fn build(&self, engine: EngineKind) -> Result<Regex, crate::Error> {
+        match engine {
+            EngineKind::PikeVM => self.pike_vm(),
+            EngineKind::PikeJIT => self.pike_jit(),
+            EngineKind::PikeJITArray => self.pike_jit_array(),
+            EngineKind::PikeJITCowArray => self.pike_jit_cow_array(),
+            EngineKind::LazyJIT => self.lazy_jit(),
+        }
+    }
+
+    pub fn pike_vm(&self) -> Result<Regex, crate::Error> {
+        self.pike_vm_with(None)
+    }
+
+    /// Builds the regex of the pattern with and without case folding, in
+    /// this order, no matter the case sensitivity of the builder, e.g. for a
+    /// search-as-you-type field with a case toggle.
+    ///
+    /// With the Pike VM, both share the storage of their equal outlined
+    /// classes, see [`Builder::build_case_pair_with`]. The JIT compiles its
+    /// classes into its code, so its regexes share nothing.
+    pub fn build_case_pair(&self, engine: EngineKind) -> Result<(Regex, Regex), crate::Error> {
+        self.build_case_pair_with(engine, &ClassCache::new())
+    }
+
+    /// Like [`Builder::build_case_pair`], with the outlined classes of the
+    /// Pike VM interned in the given cache, which can be kept to share them
+    /// with the next pairs too.
+    ///
+    /// Folding only changes the classes with cased letters, so the large
+    /// Unicode classes, such as scripts and digits, are usually stored once.
+    /// The folded classes are separate tables, not views of the others.
+    pub fn build_case_pair_with(
+        &self,
+        engine: EngineKind,
+        cache: &ClassCache,
+    ) -> Result<(Regex, Regex), crate::Error> {
+        let build = |case_insensitive| {
+            let builder = self.clone().case_insensitive(case_insensitive);
+            match engine {
+                EngineKind::PikeVM => builder.pike_vm_with(Some(cache)),
+                _ => builder.build(engine),
+            }
+        };
+        Ok((build(false)?, build(true)?))
+    }
+
+    fn pike_vm_with(&self, cache: Option<&ClassCache>) -> Result<Regex, crate::Error> {
+        self.build_with(EngineKind::PikeVM, cache)
+    }
+
+    /// Build a regex using the JIT engine.
+    ///
+    /// The JIT is only available on x86_64 (unix and windows), with the `jit`
+    /// feature. Otherwise this returns an [`EngineUnavailable`] error, see
+    /// [`crate::error::UnsupportedFeature`].
+    pub fn pike_jit(&self) -> Result<Regex, crate::Error> {
+        self.jit_with(EngineKind::PikeJIT)
+    }
+
+    /// Build a regex using the JIT engine with [`CgStrategy::Array`], no
+    /// matter the strategy of the builder.
+    pub fn pike_jit_array(&self) -> Result<Regex, crate::Error> {
+        self.jit_with(EngineKind::PikeJITArray)
+    }
+
+    /// Build a regex using the JIT engine with [`CgStrategy::CowArray`], no
+    /// matter the strategy of the builder.
+    pub fn pike_jit_cow_array(&self) -> Result<Regex, crate::Error> {
+        self.jit_with(EngineKind::PikeJITCowArray)
+    }
+
+    /// Build a regex which runs the Pike VM, and compiles its bytecode with the
+    /// JIT once it ran [`Builder::lazy_jit_after`] searches, or when
+    /// [`Regex::force_jit`] is called. The searches then run the JIT. This
+    /// saves the cost of the JIT for the regexes which are only searched a
+    /// few times, while the others still get its speed.
+    ///
+    /// If the JIT can't compile the pattern, e.g. because it isn't available
+    /// on this platform or doesn't support the match kind, the regex keeps
+    /// running the Pike VM. The states created before the switch, such as
+    /// [`ScratchState`]s, allocate the state of the JIT on their first search
+    /// after it.
+    ///
+    /// ```rust
+    /// use gregex::Builder;
+    ///
+    /// let re = Builder::new(r"\d+").lazy_jit_after(Some(2)).lazy_jit().unwrap();
+    /// assert_eq!(re.jit_cg_strategy(), None);
+    /// for _ in 0..2 {
+    ///     assert_eq!(re.find("abc123").unwrap().as_str(), "123");
+    /// }
+    /// # if cfg!(all(feature = "jit", target_arch = "x86_64", any(unix, windows))) {
+    /// assert!(re.jit_cg_strategy().is_some());
+    /// # }
+    /// ```
+    pub fn lazy_jit(&self) -> Result<Regex, crate::Error> {
+        self.build_with(EngineKind::LazyJIT, None)
+    }
+
+    #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+    fn jit_with(&self, engine: EngineKind) -> Result<Regex, crate::Error> {
+        self.build_with(engine, None)
+    }
+
+    #[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+    fn jit_with(&self, _engine: EngineKind) -> Result<Regex, crate::Error> {
+        Err(EngineUnavailable.into())
+    }
+
+    /// Builds the regex of the pattern with the engine of the given kind, or
+    /// with a specialized engine, see [`Builder::engine`].
+    fn build_with(
+        &self,
+        kind: EngineKind,
+        cache: Option<&ClassCache>,
+    ) -> Result<Regex, crate::Error> {
+        self.timed(|| {
+            let (hir, parse) = self.parse()?;
+            let engine = self.engine(kind, &hir, cache)?;
+            // The specialized engines only report the total, see
+            // `BuildTimings`.
+            let parse = match engine {
+                RegexEngine::AssertionOnly(_) => Duration::ZERO,
+                _ => parse,
+            };
+            Ok(self.regex(kind, engine, &hir, parse))
         })
     }
 
-    pub fn pike_jit_cow_array(self) -> Result<Regex, CompileError> {
-        let pike_jit = JittedRegex::new_cow(self.pattern, self.config)?;
-        let capture_count = pike_jit.capture_count();
-        Ok(Regex {
-            engine: RegexEngine::JittedRegex(pike_jit),
-            capture_count,
+    /// Runs the build, and records its total duration if the timings are
+    /// enabled, see [`Builder::timings`].
+    fn timed(
+        &self,
+        build: impl FnOnce() -> Result<Regex, crate::Error>,
+    ) -> Result<Regex, crate::Error> {
+        let started = Instant::now();
+        let mut regex = build()?;
+        if let Some(timings) = &mut regex.build_timings {
+            timings.total = started.elapsed();
+        }
+        Ok(regex)
+    }
+
+    /// Parses the pattern, once for the engine and for everything the regex
+    /// derives from the hir, such as its groups and its prefilter. Also
+    /// returns how long it took.
+    fn parse(&self) -> Result<(Hir, Duration), crate::Error> {
+        let started = Instant::now();
+        let hir = Compiler::parse(&self.pattern, &self.config)?;
+        Ok((hir, started.elapsed()))
+    }
+
+    /// Builds the engine of the parsed pattern: a specialized engine if there
+    /// is one and specialization is enabled, and otherwise the engine of the
+    /// given kind, wrapped in the specializations which apply to the pattern.
+    /// The assertion-only engine doesn't scan the haystack, so it needs none.
+    fn engine(
+        &self,
+        kind: EngineKind,
+        hir: &Hir,
+        cache: Option<&ClassCache>,
+    ) -> Result<RegexEngine, crate::Error> {
+        if self.config.specialize
+            && let Some(engine) = AssertionOnly::from_hir(hir, self.config.clone())?
+        {
+            return Ok(RegexEngine::AssertionOnly(engine));
+        }
+        let engine = match kind {
+            EngineKind::PikeVM => {
+                RegexEngine::PikeVM(PikeVM::from_hir(hir.clone(), self.config.clone(), cache)?)
+            }
+            EngineKind::LazyJIT => {
+                let pike_vm = PikeVM::from_hir(hir.clone(), self.config.clone(), None)?;
+                let engine = LazyJit::new(RegexEngine::PikeVM(pike_vm), self.config.clone());
+                RegexEngine::LazyJit(Box::new(engine))
+            }
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            _ => self.jit_engine(kind, hir)?,
+            #[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+            _ => return Err(EngineUnavailable.into()),
+        };
+        // The prefixes are searched within the window of the end-anchored
+        // patterns, so they wrap the engine first.
+        let engine = match self.prefixes(hir) {
+            Some(prefixes) => RegexEngine::Prefixed(Box::new(Prefixed {
+                inner: engine,
+                prefixes,
+            })),
+            None => engine,
+        };
+        Ok(match self.leading_wildcard(kind, hir)? {
+            Some((rest, dot_all)) => RegexEngine::LeadingWildcard(Box::new(LeadingWildcard {
+                full: engine,
+                rest,
+                dot_all,
+            })),
+            None => match self.end_anchored(hir) {
+                Some(maximum_len) => RegexEngine::EndAnchored(Box::new(EndAnchored {
+                    inner: engine,
+                    maximum_len,
+                })),
+                None => engine,
+            },
         })
     }
+
+    /// Compiles the parsed pattern with the JIT, with the CG strategy of the
+    /// kind.
+    #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+    fn jit_engine(&self, kind: EngineKind, hir: &Hir) -> Result<RegexEngine, crate::Error> {
+        if self.config.match_kind != MatchKind::LeftmostFirst {
+            return Err(UnsupportedMatchKind {
+                engine: kind,
+                match_kind: self.config.match_kind,
+            }
+            .into());
+        }
+        let jit_cg_strategy = match kind {
+            EngineKind::PikeJITArray => CgStrategy::Array,
+            EngineKind::PikeJITCowArray => CgStrategy::CowArray,
+            _ => self.config.jit_cg_strategy,
+        };
+        let config = Config {
+            jit_cg_strategy,
+            ..self.config.clone()
+        };
+        let pike_jit = JittedRegex::from_hir(hir.clone(), config, &SystemAllocator)?;
+        Ok(RegexEngine::JittedRegex(pike_jit))
+    }
+
+    /// Builds the engine of the rest of the pattern, if it starts with `.*`,
+    /// see [`crate::leading_wildcard`]. This is a specialization, which only
+    /// applies when the others do, and the rest is built like the pattern,
+    /// without the capture groups and the prefilter.
+    fn leading_wildcard(
+        &self,
+        kind: EngineKind,
+        hir: &Hir,
+    ) -> Result<Option<(RegexEngine, bool)>, crate::Error> {
+        if !self.config.specialize {
+            return Ok(None);
+        }
+        let Some((rest, dot_all)) = leading_wildcard::split(hir) else {
+            return Ok(None);
+        };
+        let config = Config {
+            cg: false,
+            prefilter: false,
+            ..self.config.clone()
+        };
+        let builder = Builder {
+            config,
+            ..self.clone()
+        };
+        let rest = builder.engine(kind, &rest, None)?;
+        Ok(Some((rest, dot_all)))
+    }
+
+    /// Returns the maximum length of the matches, if they all end at the end
+    /// of the haystack, see [`crate::end_anchored`]. This is a
+    /// specialization.
+    fn end_anchored(&self, hir: &Hir) -> Option<usize> {
+        if !self.config.specialize {
+            return None;
+        }
+        end_anchored::maximum_len(hir)
+    }
+
+    /// Returns the literals starting every match, if the prefilter is
+    /// enabled, see [`crate::prefix`].
+    fn prefixes(&self, hir: &Hir) -> Option<Prefixes> {
+        if !self.config.prefilter {
+            return None;
+        }
+        Prefixes::new(hir)
+    }
+
+    /// Wraps the engine built for the requested kind into a [`Regex`], with
+    /// the hir the engine was built from, which took `parse` to parse.
+    fn regex(&self, kind: EngineKind, engine: RegexEngine, hir: &Hir, parse: Duration) -> Regex {
+        let build_timings = self.config.timings.then(|| BuildTimings {
+            parse,
+            ..engine.build_timings()
+        });
+        let group_info = GroupInfo::new(Compiler::group_names(hir, &self.config))
+            .with_static_len(Compiler::static_group_len(hir, &self.config));
+        debug_assert_eq!(group_info.len(), engine.capture_count());
+        let prefilter = if self.config.prefilter {
+            RequiredBytes::new(hir)
+        } else {
+            None
+        };
+        Regex {
+            engine,
+            group_info: Arc::new(group_info),
+            pool: StatePool::new(),
+            id: NEXT_REGEX_ID.fetch_add(1, Ordering::Relaxed),
+            prefilter,
+            line_anchor: LineAnchor::new(hir),
+            pattern: self.pattern.to_string(),
+            config: self.config.clone(),
+            kind,
+            build_timings,
+            longest_prefix_vm: OnceLock::new(),
+        }
+    }
+}
+
+/// An error caused by the pattern, with the part of it at fault when it is
+/// known, e.g. the `\p{Greek}` of `a\p{Greek}` in non-unicode mode. The
+/// syntax errors of regex-syntax are reported this way too.
+///
+/// Displayed as the line of the pattern with the span underlined, followed by
+/// the message, like regex-syntax does. The error it wraps is its
+/// [`Error::source`].
+#[derive(Debug)]
+pub struct PatternError {
+    pattern: String,
+    span: Option<Range<usize>>,
+    message: String,
+    error: Box<dyn Error + Send + Sync + 'static>,
+}
+
+impl PatternError {
+    pub(crate) fn new(
+        pattern: &str,
+        span: Option<Range<usize>>,
+        error: impl Into<Box<dyn Error + Send + Sync + 'static>>,
+    ) -> Self {
+        let error = error.into();
+        Self {
+            pattern: pattern.to_string(),
+            span,
+            message: error.to_string(),
+            error,
+        }
+    }
+
+    /// Wraps an error of regex-syntax, whose message is the one of its kind,
+    /// without its own rendering of the pattern.
+    pub(crate) fn syntax(pattern: &str, error: regex_syntax::Error) -> Self {
+        let (span, message) = match &error {
+            regex_syntax::Error::Parse(err) => (err.span(), err.kind().to_string()),
+            regex_syntax::Error::Translate(err) => (err.span(), err.kind().to_string()),
+            _ => return Self::new(pattern, None, error),
+        };
+        Self {
+            pattern: pattern.to_string(),
+            span: Some(span.start.offset..span.end.offset),
+            message,
+            error: error.into(),
+        }
+    }
+
+    /// The byte range of the pattern at fault, if known.
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.span.clone()
+    }
+
+    /// The pattern, if the error has a span in it.
+    pub fn pattern(&self) -> Option<&str> {
+        self.span.as_ref().map(|_| self.pattern.as_str())
+    }
+
+    /// The wrapped error, e.g. a [`regex_syntax::Error`] or a
+    /// [`crate::thompson::bytecode::CompileError`].
+    pub fn inner(&self) -> &(dyn Error + Send + Sync + 'static) {
+        &*self.error
+    }
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(span) = &self.span else {
+            return write!(f, "{}", self.message);
+        };
+        // The line of the start of the span, the underline stops at its end.
+        let line_start = self.pattern[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.pattern[span.start..]
+            .find('\n')
+            .map_or(self.pattern.len(), |i| span.start + i);
+        let line = &self.pattern[line_start..line_end];
+        let offset = self.pattern[line_start..span.start].chars().count();
+        let width = self.pattern[span.start..span.end.min(line_end)]
+            .chars()
+            .count()
+            .max(1);
+        writeln!(f, "regex parse error:")?;
+        let prefix = if self.pattern.contains('\n') {
+            let number = self.pattern[..line_start].matches('\n').count() + 1;
+            format!("{number}: ")
+        } else {
+            String::new()
+        };
+        writeln!(f, "    {prefix}{line}")?;
+        let padding = " ".repeat(prefix.len() + offset);
+        writeln!(f, "    {padding}{}", "^".repeat(width))?;
+        write!(f, "error: {}", self.message)
+    }
+}
+
+impl Error for PatternError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&*self.error)
+    }
+}
+
+/// Error returned when building a regex with an engine that is not available
+/// on the current platform, or in builds without the `jit` feature.
+#[derive(Debug)]
+pub struct EngineUnavailable;
+
+impl fmt::Display for EngineUnavailable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The JIT engine is only available on x86_64 (unix or windows), with the `jit` feature"
+        )
+    }
 }
 
+impl Error for EngineUnavailable {}
+
+/// Error returned when building a regex with an engine that does not
+/// implement the requested [`MatchKind`].
+#[derive(Debug)]
+pub struct UnsupportedMatchKind {
+    pub engine: EngineKind,
+    pub match_kind: MatchKind,
+}
+
+impl fmt::Display for UnsupportedMatchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The {} engine does not support {:?} matching",
+            self.engine.name(),
+            self.match_kind
+        )
+    }
+}
+
+impl Error for UnsupportedMatchKind {}
+
+/// What [`Regex::find_at_positions`] does with the positions which are not
+/// char boundaries of the haystack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InvalidPositions {
+    /// Ignore them, and search at the other positions.
+    Skip,
+    /// Fail with an [`InvalidPosition`] error at the first of them.
+    Reject,
+}
+
+/// Error returned by [`Regex::find_at_positions`] for a position which is
+/// not a char boundary of the haystack, see [`InvalidPositions::Reject`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidPosition {
+    /// The index of the position in the given positions.
+    pub index: usize,
+    pub position: usize,
+}
+
+impl fmt::Display for InvalidPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Position {} (at index {}) is not a char boundary of the haystack",
+            self.position, self.index
+        )
+    }
+}
+
+impl Error for InvalidPosition {}
+
+/// Error returned when building a JIT engine with
+/// [`CgStrategy::Registers`] for a pattern with capture groups.
+#[derive(Debug)]
+pub struct UnsupportedCgStrategy {
+    pub strategy: CgStrategy,
+    /// The number of groups of the pattern, including group 0.
+    pub group_len: usize,
+}
+
+impl fmt::Display for UnsupportedCgStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The {:?} CG strategy can't track the {} capture groups of the pattern",
+            self.strategy,
+            self.group_len - 1
+        )
+    }
+}
+
+impl Error for UnsupportedCgStrategy {}
+
+/// Replaces the first `limit` matches of the haystack, with the replacement
+/// `append` writes, see [`Regex::replacen`].
+fn replace_matches<'s, T>(
+    haystack: &'s str,
+    limit: usize,
+    matches: impl Iterator<Item = T>,
+    span: impl Fn(&T) -> Span,
+    mut append: impl FnMut(&T, &mut String),
+) -> Cow<'s, str> {
+    let mut replaced = String::new();
+    // The end of the previous match.
+    let mut last = None;
+    for m in matches.take(limit) {
+        let span = span(&m);
+        replaced.push_str(&haystack[last.unwrap_or(0)..span.from]);
+        append(&m, &mut replaced);
+        last = Some(span.to);
+    }
+    let Some(last) = last else {
+        return Cow::Borrowed(haystack);
+    };
+    replaced.push_str(&haystack[last..]);
+    Cow::Owned(replaced)
+}
+
+/// The step shared by all the searches over the successive matches of an
+/// input: runs `exec` on the input (see [`exec_after_match`]), and if it finds
+/// a match, moves the start of the input to where the next search starts.
+/// That is the end of the match, or one char further for an empty match, so
+/// that it isn't found again, see [`Input::advance_past`], which also gives
+/// the next search the char before its start. Returns false without running
+/// `exec` once the start went past the end of the input.
+fn search_next(
+    input: &mut Input<'_>,
+    spans: &mut [Span],
+    exec: impl FnMut(Input<'_>, &mut [Span]) -> bool,
+) -> bool {
+    if !input.span.valid() || !exec_after_match(input, spans, exec) {
+        return false;
+    }
+    input.advance_past(&Match::new(input.subject, spans[0]));
+    true
+}
+
+/// Runs `exec` on the input, but skips an empty match right at the end of the
+/// match the input was advanced past, like the regex crate does (e.g. the one
+/// at the end of `aaa` for `a*`): the search then runs again from the next
+/// char. Every search of an input applies this rule here, or with
+/// [`skip_empty_after_match`], so that the searches over the successive
+/// matches all see the same ones.
+fn exec_after_match(
+    input: &Input<'_>,
+    spans: &mut [Span],
+    mut exec: impl FnMut(Input<'_>, &mut [Span]) -> bool,
+) -> bool {
+    if input.previous_match_end != Some(input.span.from) {
+        return exec(input.clone(), spans);
+    }
+    // The overall match tells whether it is the empty one, even when the
+    // caller doesn't want it, e.g. for `is_match`.
+    let mut group0 = [Span::invalid()];
+    let spans = if spans.is_empty() {
+        &mut group0[..]
+    } else {
+        spans
+    };
+    if !exec(input.clone(), spans) {
+        return false;
+    }
+    let found = spans[0];
+    if !is_empty_after_match(input, found) {
+        return true;
+    }
+    let mut next = input.clone();
+    next.advance_past(&Match::new(input.subject, found));
+    next.span.valid() && exec(next, spans)
+}
+
+/// Returns the input from which the search must run to skip the empty match
+/// right after the previous match, see [`exec_after_match`]: the input itself,
+/// unless `exec` finds that empty match. This is for the searches which
+/// can't run twice on the way to their match, e.g. because they report its
+/// progress. Returns None if the start then goes past the end of the input.
+fn skip_empty_after_match<'s>(
+    input: Input<'s>,
+    exec: impl FnOnce(Input<'_>, &mut [Span]) -> bool,
+) -> Option<Input<'s>> {
+    if input.previous_match_end != Some(input.span.from) {
+        return Some(input);
+    }
+    let mut found = [Span::invalid()];
+    if !exec(input.clone(), &mut found) || !is_empty_after_match(&input, found[0]) {
+        return Some(input);
+    }
+    let mut next = input.clone();
+    next.advance_past(&Match::new(input.subject, found[0]));
+    next.span.valid().then_some(next)
+}
+
+/// Whether the match found on the input is the empty one right at the end of
+/// the match the input was advanced past, which is skipped.
+fn is_empty_after_match(input: &Input<'_>, found: Span) -> bool {
+    input.previous_match_end == Some(input.span.from)
+        && found.empty()
+        && found.from == input.span.from
+}
+
+/// When a timed search started, and when its setup and its scan ended, see
+/// [`ExecTimings`].
+type PhaseEnds = (Instant, Instant, Instant);
+
 /// Iterator over all match in a regex.
 pub struct AllMatch<'r, 's> {
     input: Input<'s>,
@@ -232,64 +2366,299 @@ pub struct AllMatch<'r, 's> {
     imp: EngineWithState<'r>,
 }
 
+impl AllMatch<'_, '_> {
+    /// Makes the iterator stop once the flag is set, e.g. when the consumer
+    /// of the matches is gone: `next` then returns `None`, even in the middle
+    /// of a search, after reading at most a few thousand more chars. That
+    /// `None` looks like the end of the matches, see
+    /// [`AllMatch::was_interrupted`].
+    ///
+    /// ```rust
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"\d+").unwrap();
+    /// let stop = Arc::new(AtomicBool::new(false));
+    /// let mut matches = re.find_all("1 22 333");
+    /// matches.set_interrupt(stop.clone());
+    /// assert_eq!(matches.next().unwrap().as_str(), "1");
+    /// stop.store(true, Ordering::Relaxed);
+    /// assert!(matches.next().is_none());
+    /// assert!(matches.was_interrupted());
+    /// ```
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.imp.interrupt = Some(flag);
+    }
+
+    /// Whether the iterator stopped because its interrupt flag was set,
+    /// rather than because there are no more matches.
+    pub fn was_interrupted(&self) -> bool {
+        self.imp.interrupted
+    }
+
+    /// The memory held by the state of the iterator, in bytes, see
+    /// [`Regex::state_memory_usage`]. The same state is used by all the
+    /// searches of the iterator, so this is the memory of the largest one.
+    pub fn state_memory_usage(&self) -> usize {
+        self.imp.state.memory_usage()
+    }
+}
+
 impl<'r, 's> Iterator for AllMatch<'r, 's> {
     type Item = Match<'s>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.input.span.valid() {
+        if !self.imp.exec_next(&mut self.input, &mut self.spans) {
             return None;
         }
-        let result = match &mut self.imp {
-            EngineWithState::PikeVM(pike_vm, state) => {
-                // Add soft reset
-                pike_vm.reset_state(state);
-                pike_vm.exec(self.input.clone(), state, &mut self.spans)
+        Some(Match::new(self.input.subject, self.spans[0]))
+    }
+}
+
+/// Iterator over the regions between the matches, see [`Regex::find_gaps`].
+pub struct Gaps<'r, 's> {
+    matches: AllMatch<'r, 's>,
+    /// Where the next gap starts, or None once the last one was returned.
+    last: Option<usize>,
+    /// The end of the input span, where the last gap ends.
+    end: usize,
+}
+
+impl<'r, 's> Iterator for Gaps<'r, 's> {
+    type Item = Match<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last = self.last?;
+        let subject = self.matches.input.subject;
+        match self.matches.next() {
+            Some(found) => {
+                self.last = Some(found.end());
+                Some(Match::new(subject, last..found.start()))
             }
-            EngineWithState::JittedRegex(jitted_regex, state) => {
-                jitted_regex.reset_state(state);
-                jitted_regex.exec(self.input.clone(), state, &mut self.spans)
+            None => {
+                self.last = None;
+                Some(Match::new(subject, last..self.end))
             }
-        };
-        if !result {
+        }
+    }
+}
+
+/// Iterator over the pieces of a haystack between the matches, see
+/// [`Regex::split`].
+pub struct Split<'r, 's> {
+    matches: AllMatch<'r, 's>,
+    /// Where the next piece starts, or None once the last one was returned.
+    last: Option<usize>,
+}
+
+impl<'r, 's> Iterator for Split<'r, 's> {
+    type Item = &'s str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let last = self.last?;
+        let subject = self.matches.input.subject;
+        match self.matches.next() {
+            Some(found) => {
+                self.last = Some(found.end());
+                Some(&subject[last..found.start()])
+            }
+            None => {
+                self.last = None;
+                Some(&subject[last..])
+            }
+        }
+    }
+}
+
+/// Iterator over at most a given number of pieces of a haystack, see
+/// [`Regex::splitn`].
+pub struct SplitN<'r, 's> {
+    split: Split<'r, 's>,
+    /// The number of pieces left.
+    limit: usize,
+}
+
+impl<'r, 's> Iterator for SplitN<'r, 's> {
+    type Item = &'s str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.limit = self.limit.checked_sub(1)?;
+        if self.limit > 0 {
+            return self.split.next();
+        }
+        // The rest of the haystack, without searching it.
+        let last = self.split.last.take()?;
+        Some(&self.split.matches.input.subject[last..])
+    }
+}
+
+/// Iterator over all match in a [`Document`].
+pub struct DocumentMatches<'r, 'd, 's> {
+    regex: &'r Regex,
+    document: &'d Document<'s>,
+    input: Input<'s>,
+    spans: [Span; 1],
+    state: EngineState,
+}
+
+impl<'r, 'd, 's> Iterator for DocumentMatches<'r, 'd, 's> {
+    type Item = Match<'s>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (regex, document, state) = (self.regex, self.document, &mut self.state);
+        if !search_next(&mut self.input, &mut self.spans, |input, spans| {
+            regex.exec_in_document(document, input, state, spans)
+        }) {
             return None;
         }
-        let result = Match::new(self.input.subject, self.spans[0]);
-        self.input.span.from = result.next_match_start();
-        Some(result)
+        Some(Match::new(self.input.subject, self.spans[0]))
     }
 }
 
 /// Iterator over all match and their capture groups.
 pub struct AllCaptures<'r, 's> {
     input: Input<'s>,
-    spans: Box<[Span]>,
+    group_info: Arc<GroupInfo>,
+    /// The buffer of the next match, see [`next_spans`].
+    spans: Option<Box<[Span]>>,
     imp: EngineWithState<'r>,
 }
 
+/// Returns the buffer the next match of a captures iterator is written to.
+/// Each match owns its buffer, so a new one is allocated once a match took
+/// the previous one. A search which finds nothing leaves it for the next.
+fn next_spans(spans: &mut Option<Box<[Span]>>, group_len: usize) -> Box<[Span]> {
+    spans
+        .take()
+        .unwrap_or_else(|| vec![Span::invalid(); group_len].into_boxed_slice())
+}
+
+impl<'s> AllCaptures<'_, 's> {
+    /// Like `next`, but writes the groups of the next match to `locs`, like
+    /// [`Regex::captures_read`], instead of allocating them, and only returns
+    /// the overall match. Both can be mixed, they advance the same iterator.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(\d+)").unwrap();
+    /// let mut locs = re.capture_locations();
+    /// let mut matches = re.find_all_captures("1 22 333");
+    /// let mut total = 0;
+    /// while matches.read_next(&mut locs).is_some() {
+    ///     let (start, end) = locs.get(1).unwrap();
+    ///     total += end - start;
+    /// }
+    /// assert_eq!(total, 6);
+    /// ```
+    pub fn read_next(&mut self, locs: &mut CaptureLocations) -> Option<Match<'s>> {
+        let spans = locs.clear();
+        if !self.imp.exec_next(&mut self.input, spans) {
+            return None;
+        }
+        Some(Match::new(self.input.subject, spans[0]))
+    }
+
+    /// Makes the iterator stop once the flag is set, see
+    /// [`AllMatch::set_interrupt`].
+    pub fn set_interrupt(&mut self, flag: Arc<AtomicBool>) {
+        self.imp.interrupt = Some(flag);
+    }
+
+    /// Whether the iterator stopped because its interrupt flag was set,
+    /// rather than because there are no more matches.
+    pub fn was_interrupted(&self) -> bool {
+        self.imp.interrupted
+    }
+
+    /// The memory held by the state of the iterator, in bytes, see
+    /// [`AllMatch::state_memory_usage`].
+    pub fn state_memory_usage(&self) -> usize {
+        self.imp.state.memory_usage()
+    }
+}
+
 impl<'r, 's> Iterator for AllCaptures<'r, 's> {
     type Item = Captures<'s>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.input.span.valid() {
+        let mut spans = next_spans(&mut self.spans, self.group_info.len());
+        if !self.imp.exec_next(&mut self.input, &mut spans) {
+            self.spans = Some(spans);
             return None;
         }
-        let result = match &mut self.imp {
-            EngineWithState::PikeVM(pike_vm, state) => {
-                pike_vm.reset_state(state);
-                pike_vm.exec(self.input.clone(), state, &mut self.spans)
-            }
-            EngineWithState::JittedRegex(jitted_regex, state) => {
-                jitted_regex.reset_state(state);
-                jitted_regex.exec(self.input.clone(), state, &mut self.spans)
-            }
-        };
-        if !result {
+        let captures = Captures::new(self.input.subject, spans);
+        Some(captures.with_group_info(self.group_info.clone()))
+    }
+}
+
+/// Iterator over all match in a regex, owning its input.
+pub struct AllMatchOwned<'r> {
+    input: OwnedInput,
+    /// The char before the start of the input, from the previous match, see
+    /// [`Input::with_look_behind`].
+    look_behind: Option<(usize, Char)>,
+    /// The end of the previous match, see [`Input::advance_past`].
+    previous_match_end: Option<usize>,
+    spans: [Span; 1],
+    imp: EngineWithState<'r>,
+}
+
+impl<'r> Iterator for AllMatchOwned<'r> {
+    type Item = OwnedMatch;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let imp = &mut self.imp;
+        let mut input = self.input.as_input();
+        input.look_behind = self.look_behind;
+        input.previous_match_end = self.previous_match_end;
+        if !search_next(&mut input, &mut self.spans, |input, spans| {
+            imp.exec(input, spans)
+        }) {
             return None;
         }
-        // TODO: Don't clone the spans and instead reuse them
-        let result = Captures::new(self.input.subject, self.spans.clone());
-        self.input.span.from = result.group0().next_match_start();
-        Some(result)
+        self.look_behind = input.look_behind;
+        self.previous_match_end = input.previous_match_end;
+        self.input.span.from = input.span.from;
+        Some(OwnedMatch::new(self.input.subject.clone(), self.spans[0]))
+    }
+}
+
+/// Iterator over all match and their capture groups, owning its input.
+pub struct AllCapturesOwned<'r> {
+    input: OwnedInput,
+    /// The char before the start of the input, from the previous match, see
+    /// [`Input::with_look_behind`].
+    look_behind: Option<(usize, Char)>,
+    /// The end of the previous match, see [`Input::advance_past`].
+    previous_match_end: Option<usize>,
+    group_info: Arc<GroupInfo>,
+    /// The buffer of the next match, see [`next_spans`].
+    spans: Option<Box<[Span]>>,
+    imp: EngineWithState<'r>,
+}
+
+impl<'r> Iterator for AllCapturesOwned<'r> {
+    type Item = OwnedCaptures;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut spans = next_spans(&mut self.spans, self.group_info.len());
+        let imp = &mut self.imp;
+        let mut input = self.input.as_input();
+        input.look_behind = self.look_behind;
+        input.previous_match_end = self.previous_match_end;
+        if !search_next(&mut input, &mut spans, |input, spans| {
+            imp.exec(input, spans)
+        }) {
+            self.spans = Some(spans);
+            return None;
+        }
+        self.look_behind = input.look_behind;
+        self.previous_match_end = input.previous_match_end;
+        self.input.span.from = input.span.from;
+        let captures = OwnedCaptures::new(self.input.subject.clone(), spans);
+        Some(captures.with_group_info(self.group_info.clone()))
     }
 }
 
@@ -297,14 +2666,354 @@ impl<'r, 's> Iterator for AllCaptures<'r, 's> {
 /// the right version at runtime.
 pub(crate) enum RegexEngine {
     PikeVM(PikeVM),
+    AssertionOnly(AssertionOnly),
+    #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
     JittedRegex(JittedRegex),
+    /// Wraps the engine of a pattern starting with `.*`, see
+    /// [`crate::leading_wildcard`].
+    LeadingWildcard(Box<LeadingWildcard>),
+    /// Runs the Pike VM until the JIT is compiled, see [`crate::lazy_jit`].
+    LazyJit(Box<LazyJit>),
+    /// Wraps the engine of a pattern whose matches end at the end of the
+    /// haystack, see [`crate::end_anchored`]. It has the state of its engine.
+    EndAnchored(Box<EndAnchored>),
+    /// Wraps the engine of a pattern whose matches start with one of a few
+    /// literals, see [`crate::prefix`]. It has the state of its engine.
+    Prefixed(Box<Prefixed>),
+}
+
+/// The state of a [`RegexEngine`].
+// The states of the VM and of the JIT are about the same size, only the one of
+// the VM is left without the JIT, and boxing it would cost the one-shot
+// searches an allocation.
+#[cfg_attr(
+    not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))),
+    allow(clippy::large_enum_variant)
+)]
+pub(crate) enum EngineState {
+    PikeVM(<PikeVM as RegexImpl>::State),
+    AssertionOnly(<AssertionOnly as RegexImpl>::State),
+    #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+    JittedRegex(<JittedRegex as RegexImpl>::State),
+    LeadingWildcard(Box<LeadingWildcardState>),
+    LazyJit(Box<LazyJitState>),
 }
 
-/// A regex implementation, with it's respective state.
+impl EngineState {
+    /// The memory held by the state, in bytes, see
+    /// [`Regex::state_memory_usage`].
+    pub(crate) fn memory_usage(&self) -> usize {
+        match self {
+            EngineState::PikeVM(state) => state.memory_usage(),
+            EngineState::AssertionOnly(state) => state.memory_usage(),
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            EngineState::JittedRegex(state) => state.memory_usage(),
+            EngineState::LeadingWildcard(state) => {
+                state.full.memory_usage() + state.rest.memory_usage()
+            }
+            EngineState::LazyJit(state) => LazyJit::memory_usage(state),
+        }
+    }
+}
+
+impl RegexEngine {
+    pub(crate) fn new_state(&self) -> EngineState {
+        match self {
+            RegexEngine::PikeVM(pike_vm) => EngineState::PikeVM(pike_vm.new_state()),
+            RegexEngine::AssertionOnly(engine) => EngineState::AssertionOnly(engine.new_state()),
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            RegexEngine::JittedRegex(jitted_regex) => {
+                EngineState::JittedRegex(jitted_regex.new_state())
+            }
+            RegexEngine::LeadingWildcard(engine) => {
+                EngineState::LeadingWildcard(Box::new(engine.new_state()))
+            }
+            RegexEngine::LazyJit(engine) => EngineState::LazyJit(Box::new(engine.new_state())),
+            RegexEngine::EndAnchored(engine) => engine.inner.new_state(),
+            RegexEngine::Prefixed(engine) => engine.inner.new_state(),
+        }
+    }
+
+    /// The durations of the phases of the build which the engine recorded,
+    /// see [`BuildTimings`].
+    fn build_timings(&self) -> BuildTimings {
+        match self.general() {
+            RegexEngine::PikeVM(pike_vm) => pike_vm.build_timings(),
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            RegexEngine::JittedRegex(jitted_regex) => jitted_regex.build_timings(),
+            _ => BuildTimings::default(),
+        }
+    }
+
+    /// The number of capture groups the engine reports, including group 0.
+    fn capture_count(&self) -> usize {
+        match self.general() {
+            RegexEngine::PikeVM(pike_vm) => pike_vm.capture_count(),
+            RegexEngine::AssertionOnly(engine) => engine.capture_count(),
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            RegexEngine::JittedRegex(jitted_regex) => jitted_regex.capture_count(),
+            RegexEngine::LeadingWildcard(_)
+            | RegexEngine::LazyJit(_)
+            | RegexEngine::EndAnchored(_)
+            | RegexEngine::Prefixed(_) => unreachable!("not the general engine"),
+        }
+    }
+
+    /// The engine running the pattern as it is written, for the features
+    /// which depend on the engine (e.g. [`Regex::find_explain`]).
+    fn general(&self) -> &RegexEngine {
+        match self {
+            RegexEngine::LeadingWildcard(engine) => engine.full.general(),
+            RegexEngine::LazyJit(engine) => &engine.vm,
+            RegexEngine::EndAnchored(engine) => engine.inner.general(),
+            RegexEngine::Prefixed(engine) => engine.inner.general(),
+            _ => self,
+        }
+    }
+
+    /// Compiles the lazy JITs of the engine, and returns whether its
+    /// searches run the JIT, see [`Regex::force_jit`].
+    fn force_jit(&self) -> bool {
+        match self {
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            RegexEngine::JittedRegex(_) => true,
+            RegexEngine::LazyJit(engine) => engine.force(),
+            RegexEngine::EndAnchored(engine) => engine.inner.force_jit(),
+            RegexEngine::Prefixed(engine) => engine.inner.force_jit(),
+            // Both are compiled, even if one of them fails.
+            RegexEngine::LeadingWildcard(engine) => {
+                engine.full.force_jit() & engine.rest.force_jit()
+            }
+            _ => false,
+        }
+    }
+
+    /// Reset the state and execute the engine on the input. The state must
+    /// have been created by this engine.
+    pub(crate) fn exec(
+        &self,
+        input: Input<'_>,
+        state: &mut EngineState,
+        captures: &mut [Span],
+    ) -> bool {
+        match (self, state) {
+            (RegexEngine::PikeVM(pike_vm), EngineState::PikeVM(state)) => {
+                pike_vm.reset_state(state);
+                pike_vm.exec(input, state, captures)
+            }
+            (RegexEngine::AssertionOnly(engine), EngineState::AssertionOnly(state)) => {
+                engine.reset_state(state);
+                engine.exec(input, state, captures)
+            }
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            (RegexEngine::JittedRegex(jitted_regex), EngineState::JittedRegex(state)) => {
+                jitted_regex.reset_state(state);
+                jitted_regex.exec(input, state, captures)
+            }
+            (RegexEngine::LeadingWildcard(engine), EngineState::LeadingWildcard(state)) => {
+                engine.exec(input, state, captures)
+            }
+            (RegexEngine::LazyJit(engine), EngineState::LazyJit(state)) => {
+                let (engine, state) = engine.engine(state);
+                engine.exec(input, state, captures)
+            }
+            (RegexEngine::EndAnchored(engine), state) => engine.exec(input, state, captures),
+            (RegexEngine::Prefixed(engine), state) => engine.exec(input, state, captures),
+            _ => unreachable!("The state belongs to another engine"),
+        }
+    }
+
+    /// Reset the state and execute the engine on the input, calling
+    /// `interrupt` every `interval` chars, see [`Regex::find_interruptible`].
+    fn exec_interruptible(
+        &self,
+        input: Input<'_>,
+        state: &mut EngineState,
+        captures: &mut [Span],
+        interval: usize,
+        interrupt: &dyn Fn() -> bool,
+    ) -> Result<bool, MatchError> {
+        match (self, state) {
+            (RegexEngine::PikeVM(pike_vm), EngineState::PikeVM(state)) => {
+                pike_vm.reset_state(state);
+                pike_vm.exec_interruptible(input, state, captures, interval, interrupt)
+            }
+            // This engine only reads the chars around the assertions.
+            (RegexEngine::AssertionOnly(engine), EngineState::AssertionOnly(state)) => {
+                engine.reset_state(state);
+                Ok(engine.exec(input, state, captures))
+            }
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            (RegexEngine::JittedRegex(jitted_regex), EngineState::JittedRegex(state)) => {
+                jitted_regex.reset_state(state);
+                jitted_regex.exec_interruptible(input, state, captures, interval, interrupt)
+            }
+            (RegexEngine::LeadingWildcard(engine), EngineState::LeadingWildcard(state)) => engine
+                .full
+                .exec_interruptible(input, &mut state.full, captures, interval, interrupt),
+            (RegexEngine::LazyJit(engine), EngineState::LazyJit(state)) => {
+                let (engine, state) = engine.engine(state);
+                engine.exec_interruptible(input, state, captures, interval, interrupt)
+            }
+            (RegexEngine::EndAnchored(engine), state) => engine.inner.exec_interruptible(
+                end_anchored::window(input, engine.maximum_len),
+                state,
+                captures,
+                interval,
+                interrupt,
+            ),
+            // The engine runs from the start of the span, so that it reads
+            // and counts every char.
+            (RegexEngine::Prefixed(engine), state) => engine
+                .inner
+                .exec_interruptible(input, state, captures, interval, interrupt),
+            _ => unreachable!("The state belongs to another engine"),
+        }
+    }
+
+    /// Reset the state and search bytes which may not be valid UTF-8, see
+    /// [`Regex::find_validating`].
+    fn exec_validating(
+        &self,
+        haystack: &[u8],
+        state: &mut EngineState,
+        captures: &mut [Span],
+    ) -> Result<bool, Utf8ErrorAt> {
+        match (self, state) {
+            (RegexEngine::PikeVM(pike_vm), EngineState::PikeVM(state)) => {
+                pike_vm.reset_state(state);
+                pike_vm.exec_validating(haystack, state, captures)
+            }
+            // This engine doesn't read most of the haystack, so it is checked
+            // beforehand.
+            (RegexEngine::AssertionOnly(engine), EngineState::AssertionOnly(state)) => {
+                let subject = std::str::from_utf8(haystack).map_err(|err| Utf8ErrorAt {
+                    offset: err.valid_up_to(),
+                })?;
+                engine.reset_state(state);
+                Ok(engine.exec(Input::new(subject), state, captures))
+            }
+            #[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+            (RegexEngine::JittedRegex(jitted_regex), EngineState::JittedRegex(state)) => {
+                jitted_regex.reset_state(state);
+                jitted_regex.exec_validating(haystack, state, captures)
+            }
+            (RegexEngine::LeadingWildcard(engine), EngineState::LeadingWildcard(state)) => engine
+                .full
+                .exec_validating(haystack, &mut state.full, captures),
+            (RegexEngine::LazyJit(engine), EngineState::LazyJit(state)) => {
+                let (engine, state) = engine.engine(state);
+                engine.exec_validating(haystack, state, captures)
+            }
+            // The whole haystack is validated, so it is searched as a whole.
+            (RegexEngine::EndAnchored(engine), state) => {
+                engine.inner.exec_validating(haystack, state, captures)
+            }
+            (RegexEngine::Prefixed(engine), state) => {
+                engine.inner.exec_validating(haystack, state, captures)
+            }
+            _ => unreachable!("The state belongs to another engine"),
+        }
+    }
+}
+
+/// How many chars the searches of an iterator read between two checks of its
+/// interrupt flag, see [`AllMatch::set_interrupt`].
+const ITERATOR_INTERRUPT_INTERVAL: usize = 4096;
+
+/// The source of the ids of the regexes, which tie them to their
+/// [`ScratchState`]s.
+static NEXT_REGEX_ID: AtomicU64 = AtomicU64::new(0);
+
+/// The state of the searches of a regex, owned by the caller, see
+/// [`Regex::scratch_state`].
+///
+/// The one-shot searches such as [`Regex::find`] already reuse the states of a
+/// pool shared by the threads using the regex. A scratch state gives full
+/// control instead: no lock is taken, and the state is never dropped or
+/// handed to another search. Each thread needs its own, since the searches
+/// borrow it mutably. It can only be used with the regex which created it,
+/// which the searches check.
+///
+/// ```rust
+/// use gregex::Regex;
+///
+/// let re = Regex::pike_vm(r"\d+").unwrap();
+/// let mut scratch = re.scratch_state();
+/// let found: Vec<_> = ["a 1", "no digits", "22 b"]
+///     .into_iter()
+///     .filter_map(|line| re.find_with(&mut scratch, line))
+///     .map(|m| m.as_str())
+///     .collect();
+/// assert_eq!(found, ["1", "22"]);
+/// ```
+pub struct ScratchState {
+    state: EngineState,
+    regex_id: u64,
+}
+
+impl ScratchState {
+    /// The memory held by the state, in bytes, see
+    /// [`Regex::state_memory_usage`].
+    pub fn memory_usage(&self) -> usize {
+        self.state.memory_usage()
+    }
+}
+
+impl fmt::Debug for ScratchState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScratchState")
+            .field("regex_id", &self.regex_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A regex implementation, with it's own state.
 /// Used when looking for all match.
-pub(crate) enum EngineWithState<'r> {
-    PikeVM(&'r PikeVM, <PikeVM as RegexImpl>::State),
-    JittedRegex(&'r JittedRegex, <JittedRegex as RegexImpl>::State),
+pub(crate) struct EngineWithState<'r> {
+    engine: &'r RegexEngine,
+    state: EngineState,
+    /// Stops the searches once set, see [`AllMatch::set_interrupt`].
+    interrupt: Option<Arc<AtomicBool>>,
+    /// Whether a search stopped because of the flag.
+    interrupted: bool,
+}
+
+impl EngineWithState<'_> {
+    /// Reset the state and execute the engine on the input. Once the
+    /// interrupt flag is set, the searches fail.
+    pub(crate) fn exec(&mut self, input: Input<'_>, captures: &mut [Span]) -> bool {
+        let Some(interrupt) = &self.interrupt else {
+            return self.engine.exec(input, &mut self.state, captures);
+        };
+        let check = || interrupt.load(Ordering::Relaxed);
+        let result = if check() {
+            Err(MatchError::Interrupted)
+        } else {
+            self.engine.exec_interruptible(
+                input,
+                &mut self.state,
+                captures,
+                ITERATOR_INTERRUPT_INTERVAL,
+                &check,
+            )
+        };
+        match result {
+            Ok(found) => found,
+            Err(err) => {
+                self.interrupted |= err == MatchError::Interrupted;
+                false
+            }
+        }
+    }
+
+    /// Runs the next search over the successive matches of the input, and
+    /// moves its start past the match found, see [`search_next`].
+    pub(crate) fn exec_next(&mut self, input: &mut Input<'_>, captures: &mut [Span]) -> bool {
+        search_next(input, captures, |input, captures| {
+            self.exec(input, captures)
+        })
+    }
 }
 
 /// The Regex impl trait
@@ -314,7 +3023,7 @@ pub(crate) enum EngineWithState<'r> {
 /// used in practice, since we use static dispatch, but it's there just to make
 /// sure all engines maintain the same API, and in case we want to swtich to
 /// dynamic dispatch at some point.
-pub(crate) trait RegexImpl {
+pub trait RegexImpl {
     /// State used by this engine. Every methods take a &mut State,
     /// in order to avoid repeated allocations when matching in a loop.
     type State;
@@ -327,8 +3036,8 @@ pub(crate) trait RegexImpl {
 
     /// Finds the next match, if any, and fill the provided capture group array.
     /// If the given array is of size n, then only the n-first capture groups will be written.
-    /// And if n is greater than the number of capture groups, then the remaining slots are not
-    /// overwritten.
+    /// And if n is greater than the number of capture groups (e.g. 1 if they are disabled, see
+    /// [`Builder::cg`]), then the remaining slots are set to [`Span::invalid()`] on a match.
     /// This method is enough to write all higher-level functionalities of [`crate::Regex`].
     fn exec<'s>(&self, input: Input<'s>, state: &mut Self::State, captures: &mut [Span]) -> bool;
 }
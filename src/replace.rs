@@ -0,0 +1,104 @@
+//! Replacements of the matches of a regex, see [`crate::Regex::replace_all`].
+//!
+//! A replacement is a [`Replacer`]: a string, whose references to groups
+//! (`$1`, `${1}`, `$name` or `${name}`) are expanded with the captures of each
+//! match (see [`Captures::expand`]), a closure computing the replacement from
+//! the captures, or a [`NoExpand`] string, used as is.
+//!
+//! ```rust
+//! use gregex::Regex;
+//! use gregex::replace::NoExpand;
+//! use gregex::util::Captures;
+//!
+//! let re = Regex::pike_vm(r"(?P<last>\w+),\s+(?P<first>\w+)").unwrap();
+//! assert_eq!(re.replace("Springsteen, Bruce", "$first $last"), "Bruce Springsteen");
+//! assert_eq!(re.replace("Springsteen, Bruce", NoExpand("$first")), "$first");
+//! let initials = re.replace_all("Springsteen, Bruce; Smith, Patti", |caps: &Captures| {
+//!     format!("{}.", &caps.name("first").unwrap().as_str()[..1])
+//! });
+//! assert_eq!(initials, "B.; P.");
+//! ```
+use std::borrow::Cow;
+
+use crate::util::Captures;
+
+/// The replacement of the matches of a regex.
+pub trait Replacer {
+    /// Appends the replacement of the match to `dst`.
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String);
+
+    /// The replacement, if it is the same for every match. The searches then
+    /// skip the capture groups.
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        None
+    }
+}
+
+/// Strings without `$` are used as is.
+pub(crate) fn no_expansion_str(replacement: &str) -> Option<Cow<'_, str>> {
+    (!replacement.contains('$')).then_some(Cow::Borrowed(replacement))
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl Replacer for &String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl Replacer for Cow<'_, str> {
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        caps.expand(self, dst);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        no_expansion_str(self)
+    }
+}
+
+impl<F, T> Replacer for F
+where
+    F: FnMut(&Captures<'_>) -> T,
+    T: AsRef<str>,
+{
+    fn replace_append(&mut self, caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str((*self)(caps).as_ref());
+    }
+}
+
+/// A replacement used as is, without expanding the references to groups.
+#[derive(Debug, Clone)]
+pub struct NoExpand<'s>(pub &'s str);
+
+impl Replacer for NoExpand<'_> {
+    fn replace_append(&mut self, _caps: &Captures<'_>, dst: &mut String) {
+        dst.push_str(self.0);
+    }
+
+    fn no_expansion(&mut self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self.0))
+    }
+}
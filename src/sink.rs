@@ -0,0 +1,192 @@
+//! Destinations of the matches of a bulk scan, see
+//! [`crate::Regex::scan_into`].
+//!
+//! A scan reports each match to its [`MatchSink`] as soon as it is found,
+//! from the search loop itself: there is no iterator, and no allocation per
+//! match. [`SpanVecSink`] collects the spans, and [`WriterSink`] writes them
+//! as text lines to an [`io::Write`].
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use crate::util::Span;
+
+/// Receives the matches of a scan, in order.
+pub trait MatchSink {
+    /// Called with the span of each match, if the sink doesn't want the
+    /// captures. Returning [`ControlFlow::Break`] stops the scan.
+    fn on_match(&mut self, span: Span) -> ControlFlow<()>;
+
+    /// Whether the scan reports the captures of the matches, with
+    /// [`MatchSink::on_captures`] instead of [`MatchSink::on_match`]. This
+    /// makes the search slower, see [`crate::Builder::cg`].
+    fn wants_captures(&self) -> bool {
+        false
+    }
+
+    /// Called with the spans of the groups of each match (the group 0 first)
+    /// if the sink wants the captures. The groups which didn't participate
+    /// in the match have an invalid span, see [`Span::valid`].
+    fn on_captures(&mut self, spans: &[Span]) -> ControlFlow<()> {
+        self.on_match(spans[0])
+    }
+}
+
+/// What a scan did, see [`crate::Regex::scan_into`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanSummary {
+    /// The number of matches reported to the sink.
+    pub matches: usize,
+    /// Where the next search of the scan would start: the end of the input
+    /// span if the scan went through it, or right after the last match
+    /// reported if the sink stopped it (one char further for an empty
+    /// match, see [`crate::util::Match::next_match_start`]), but never past
+    /// the end of the input span.
+    pub position: usize,
+    /// Whether the sink stopped the scan.
+    pub stopped: bool,
+}
+
+/// Error returned by a search which couldn't run, or didn't finish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MatchError {
+    /// The span of the input is reversed, or its bounds are not char
+    /// boundaries of the haystack, see [`crate::util::Input::valid`].
+    InvalidSpan { from: usize, to: usize },
+    /// The interrupt check of the search asked to stop, see
+    /// [`crate::Regex::find_interruptible`].
+    Interrupted,
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::InvalidSpan { from, to } => {
+                write!(f, "The span {from}..{to} is not valid in the haystack")
+            }
+            MatchError::Interrupted => write!(f, "The search was interrupted"),
+        }
+    }
+}
+
+impl Error for MatchError {}
+
+/// Collects the spans of the matches.
+#[derive(Debug, Clone, Default)]
+pub struct SpanVecSink {
+    pub spans: Vec<Span>,
+}
+
+impl SpanVecSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl MatchSink for SpanVecSink {
+    fn on_match(&mut self, span: Span) -> ControlFlow<()> {
+        self.spans.push(span);
+        ControlFlow::Continue(())
+    }
+}
+
+/// Size of the buffer of a [`WriterSink`], unless given.
+const DEFAULT_WRITER_CAPACITY: usize = 64 * 1024;
+
+/// Writes the span of each match as a `start\tend\n` line to a writer, in
+/// decimal. The lines are buffered, and written in large chunks.
+///
+/// A write error stops the scan, and is returned by [`WriterSink::finish`],
+/// which also writes the buffered lines. Dropping the sink writes them too,
+/// but ignores the errors.
+pub struct WriterSink<W: Write> {
+    /// Only None once finished.
+    writer: Option<W>,
+    buffer: Vec<u8>,
+    /// The buffer is written once it holds this many bytes.
+    capacity: usize,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_capacity(writer, DEFAULT_WRITER_CAPACITY)
+    }
+
+    /// A sink which writes its lines once they fill `capacity` bytes.
+    pub fn with_capacity(writer: W, capacity: usize) -> Self {
+        Self {
+            writer: Some(writer),
+            // With room for the line which fills it.
+            buffer: Vec::with_capacity(capacity + MAX_LINE_LEN),
+            capacity,
+            error: None,
+        }
+    }
+
+    /// Writes the buffered lines, and returns the writer, or the first error.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.write_buffer()?;
+        let mut writer = self.writer.take().unwrap();
+        writer.flush()?;
+        Ok(writer)
+    }
+
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if let Some(writer) = &mut self.writer {
+            writer.write_all(&self.buffer)?;
+        }
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> MatchSink for WriterSink<W> {
+    fn on_match(&mut self, span: Span) -> ControlFlow<()> {
+        if self.error.is_some() {
+            return ControlFlow::Break(());
+        }
+        push_decimal(&mut self.buffer, span.from);
+        self.buffer.push(b'\t');
+        push_decimal(&mut self.buffer, span.to);
+        self.buffer.push(b'\n');
+        if self.buffer.len() >= self.capacity
+            && let Err(err) = self.write_buffer()
+        {
+            self.error = Some(err);
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+impl<W: Write> Drop for WriterSink<W> {
+    fn drop(&mut self) {
+        if self.error.is_none() && self.write_buffer().is_ok() {
+            let _ = self.writer.as_mut().map(Write::flush);
+        }
+    }
+}
+
+/// Two numbers of up to 20 digits, a tab and a newline.
+const MAX_LINE_LEN: usize = 42;
+
+/// Appends the number in decimal, without the formatting machinery.
+fn push_decimal(buffer: &mut Vec<u8>, mut value: usize) {
+    let mut digits = [0u8; 20];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    buffer.extend_from_slice(&digits[start..]);
+}
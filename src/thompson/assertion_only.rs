@@ -0,0 +1,307 @@
+//! An engine for patterns made only of assertions.
+//!
+//! Patterns such as `^`, `$`, `\A\z` or `(?m)^$` never consume any character,
+//! therefore every match they produce is empty. Instead of simulating threads
+//! over the whole input, this engine evaluates the bytecode directly at a few
+//! candidate positions, which are derived from the assertions every match must
+//! go through (e.g. only the positions following a `\n` for `(?m)^`).
+
+use std::mem;
+
+use regex_syntax::hir::Hir;
+
+use crate::{
+    regex::{Config, RegexImpl},
+    thompson::bytecode::{Bytecode, Compiler, Instruction::*, LookKind},
+    util::{Char, Input, Span, find_prev_char, look_matches},
+};
+
+/// The engine used for patterns that contain no consuming instructions.
+pub struct AssertionOnly {
+    bytecode: Bytecode,
+    capture_count: usize,
+    candidates: Candidates,
+}
+
+/// The positions at which a match could possibly occur.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Candidates {
+    /// Every char boundary
+    All,
+    /// Only the start of the input
+    Start,
+    /// Only the end of the input
+    End,
+    /// The start of the input or after a `\n`
+    StartLF,
+    /// The end of the input or before a `\n`
+    EndLF,
+    /// The start of the input or after a `\n` or `\r`
+    StartCRLF,
+    /// The end of the input or before a `\n` or `\r`
+    EndCRLF,
+}
+
+/// A step in the depth-first exploration of the bytecode.
+#[derive(Debug)]
+enum Frame {
+    Explore(usize),
+    /// Restore the given register to the given value when backtracking.
+    Restore(usize, bool),
+}
+
+pub struct State {
+    stack: Vec<Frame>,
+    visited: Box<[bool]>,
+    registers: Box<[bool]>,
+}
+
+impl State {
+    /// The memory of the buffers, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.stack.capacity() * mem::size_of::<Frame>()
+            + mem::size_of_val(&*self.visited)
+            + mem::size_of_val(&*self.registers)
+    }
+}
+
+/// The bit of the look in the bitsets, by its order in [`LookKind`].
+fn look_bit(look: LookKind) -> u32 {
+    1 << look as u32
+}
+
+impl AssertionOnly {
+    /// Try to build this engine for the given pattern. Returns `None` if the
+    /// pattern can consume characters.
+    pub fn new(pattern: &str, config: Config) -> Result<Option<Self>, crate::Error> {
+        let hir = Compiler::parse(pattern, &config)?;
+        Self::from_hir(&hir, config)
+    }
+
+    /// Like `new`, for a pattern already parsed with the config, see
+    /// [`Compiler::parse`]. The hir is only copied if it can't consume
+    /// characters.
+    pub fn from_hir(hir: &Hir, config: Config) -> Result<Option<Self>, crate::Error> {
+        if hir.properties().maximum_len() != Some(0) {
+            return Ok(None);
+        }
+        let capture_count = Compiler::capture_count(hir, &config);
+        let bytecode = Compiler::compile(hir.clone(), config)?;
+        Ok(Self::from_bytecode(bytecode, capture_count))
+    }
+
+    pub fn from_bytecode(bytecode: Bytecode, capture_count: usize) -> Option<Self> {
+        for instruction in &bytecode.instructions {
+            match instruction {
+                // Cut regions prune threads, which this engine doesn't
+                // have.
+                Consume(_) | ConsumeClass(_) | ConsumeOutlined(_) | CutBegin(_) | CutEnd(_) => {
+                    return None;
+                }
+                _ => (),
+            }
+        }
+        let candidates = Self::candidates(&bytecode);
+        Some(Self {
+            bytecode,
+            capture_count,
+            candidates,
+        })
+    }
+
+    pub fn capture_count(&self) -> usize {
+        self.capture_count
+    }
+
+    /// Matches of assertions are always empty.
+    pub fn maximum_len(&self) -> Option<usize> {
+        Some(0)
+    }
+
+    /// Computes the set of looks that every path from the start to an accept
+    /// goes through, and picks the most selective candidate positions from it.
+    fn candidates(bytecode: &Bytecode) -> Candidates {
+        let instructions = &bytecode.instructions;
+        // Greatest fixpoint, since empty loops can exist (e.g. `(?:^)*`)
+        let mut required = vec![u32::MAX; instructions.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for pc in (0..instructions.len()).rev() {
+                let value = match &instructions[pc] {
+                    Accept(_) => 0,
+                    Assertion(look) => look_bit(*look) | required[pc + 1],
+                    WriteReg(_) => required[pc + 1],
+                    Jmp(target) => required[*target],
+                    Fork2(a, b) => required[*a] & required[*b],
+                    ForkN(targets) => targets.iter().fold(u32::MAX, |acc, t| acc & required[*t]),
+                    Consume(_) | ConsumeClass(_) | ConsumeOutlined(_) | CutBegin(_) | CutEnd(_) => {
+                        unreachable!()
+                    }
+                };
+                if value != required[pc] {
+                    required[pc] = value;
+                    changed = true;
+                }
+            }
+        }
+        let required = required[0];
+        let has = |look| required & look_bit(look) != 0;
+        if has(LookKind::Start) {
+            Candidates::Start
+        } else if has(LookKind::End) {
+            Candidates::End
+        } else if has(LookKind::StartLF) {
+            Candidates::StartLF
+        } else if has(LookKind::EndLF) {
+            Candidates::EndLF
+        } else if has(LookKind::StartCRLF) {
+            Candidates::StartCRLF
+        } else if has(LookKind::EndCRLF) {
+            Candidates::EndCRLF
+        } else {
+            Candidates::All
+        }
+    }
+
+    /// Returns the first candidate position greater or equal to `pos`.
+    /// `pos` must be a char boundary.
+    fn next_candidate(&self, subject: &str, pos: usize) -> Option<usize> {
+        let bytes = subject.as_bytes();
+        match self.candidates {
+            Candidates::All => Some(pos),
+            Candidates::Start => (pos == 0).then_some(0),
+            Candidates::End => Some(subject.len()),
+            Candidates::StartLF | Candidates::StartCRLF if pos == 0 => Some(0),
+            Candidates::StartLF => memchr::memchr(b'\n', &bytes[(pos - 1)..]).map(|i| pos + i),
+            Candidates::StartCRLF => {
+                memchr::memchr2(b'\n', b'\r', &bytes[(pos - 1)..]).map(|i| pos + i)
+            }
+            Candidates::EndLF => {
+                Some(memchr::memchr(b'\n', &bytes[pos..]).map_or(subject.len(), |i| pos + i))
+            }
+            Candidates::EndCRLF => Some(
+                memchr::memchr2(b'\n', b'\r', &bytes[pos..]).map_or(subject.len(), |i| pos + i),
+            ),
+        }
+    }
+
+    /// Explore the bytecode in priority order at the given position, and
+    /// returns true if an accept is reached. In that case the registers of
+    /// the state hold the ones written by the winning path.
+    fn eval_at(&self, state: &mut State, prev: Char, c: Char) -> bool {
+        let instructions = &self.bytecode.instructions;
+        state.stack.clear();
+        state.visited.fill(false);
+        state.registers.fill(false);
+        state.stack.push(Frame::Explore(0));
+        while let Some(frame) = state.stack.pop() {
+            let mut pc = match frame {
+                Frame::Explore(pc) => pc,
+                Frame::Restore(reg, value) => {
+                    state.registers[reg] = value;
+                    continue;
+                }
+            };
+            loop {
+                if state.visited[pc] {
+                    break;
+                }
+                state.visited[pc] = true;
+                match &instructions[pc] {
+                    Accept(_) => return true,
+                    Assertion(look) if look_matches(*look, prev, c) => pc += 1,
+                    Assertion(_) => break,
+                    Jmp(target) => pc = *target,
+                    Fork2(a, b) => {
+                        state.stack.push(Frame::Explore(*b));
+                        pc = *a;
+                    }
+                    ForkN(targets) => {
+                        for target in targets.iter().skip(1).rev() {
+                            state.stack.push(Frame::Explore(*target));
+                        }
+                        pc = targets[0];
+                    }
+                    WriteReg(reg) => {
+                        let reg = *reg as usize;
+                        if reg < state.registers.len() {
+                            state.stack.push(Frame::Restore(reg, state.registers[reg]));
+                            state.registers[reg] = true;
+                        }
+                        pc += 1;
+                    }
+                    Consume(_) | ConsumeClass(_) | ConsumeOutlined(_) | CutBegin(_) | CutEnd(_) => {
+                        unreachable!()
+                    }
+                }
+            }
+        }
+        false
+    }
+}
+
+impl RegexImpl for AssertionOnly {
+    type State = State;
+
+    fn new_state(&self) -> Self::State {
+        State {
+            stack: Vec::new(),
+            visited: vec![false; self.bytecode.instructions.len()].into_boxed_slice(),
+            registers: vec![false; self.capture_count * 2].into_boxed_slice(),
+        }
+    }
+
+    fn reset_state(&self, _state: &mut Self::State) {
+        // Nothing to do, the state is reset before every evaluation
+    }
+
+    fn exec<'s>(&self, input: Input<'s>, state: &mut Self::State, captures: &mut [Span]) -> bool {
+        if !input.valid() {
+            return false;
+        }
+        let Input {
+            subject,
+            span: Span { from, to },
+            anchored,
+            ..
+        } = input;
+
+        // The start is always tried, since the char before it may not be the
+        // one of the subject, see `Input::with_look_behind`.
+        let mut next = Some(from);
+        while let Some(candidate) = next {
+            if candidate > to || (anchored && candidate != from) {
+                return false;
+            }
+            let prev = if candidate == from {
+                input.prev_char()
+            } else {
+                find_prev_char(subject, candidate)
+            };
+            let c = subject[candidate..]
+                .chars()
+                .next()
+                .map_or(Char::INPUT_BOUND, Char::from);
+            if self.eval_at(state, prev, c) {
+                for (i, span) in captures.iter_mut().enumerate() {
+                    *span = if i == 0
+                        || (i < self.capture_count
+                            && state.registers[2 * i]
+                            && state.registers[2 * i + 1])
+                    {
+                        (candidate..candidate).into()
+                    } else {
+                        Span::invalid()
+                    };
+                }
+                return true;
+            }
+            match subject[candidate..].chars().next() {
+                Some(c) => next = self.next_candidate(subject, candidate + c.len_utf8()),
+                None => return false,
+            }
+        }
+        false
+    }
+}
@@ -7,12 +7,24 @@
 //! pattern. Furthermore compiling a pattern to this representation
 //! take linear time. The compiler is also provided by this module,
 //! see [`Compiler`].
-use std::{collections::HashMap, error::Error, fmt};
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fmt, mem,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
 
-use crate::{regex::Config, util::Char};
+use crate::{
+    limits,
+    regex::{Config, PatternError},
+    util::{Char, Interval, IntervalSet, stable_hash},
+};
+
+pub mod encoding;
 
 /// Bytecode
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Instruction {
     Consume(Char),
     ConsumeClass(Box<[(Char, Char)]>),
@@ -25,20 +37,181 @@ pub enum Instruction {
     ForkN(Box<[usize]>),
     Jmp(usize),
     WriteReg(u32),
-    Assertion(Look),
-    Accept,
+    Assertion(LookKind),
+    /// Enters the cut region with the given id, see [`Instruction::CutEnd`].
+    CutBegin(u32),
+    /// Leaves the cut region with the given id. The other threads which
+    /// entered it at the same position as this one, and have a lower priority,
+    /// are killed: only the first way (by priority) to match the region is
+    /// kept. This implements possessive quantifiers, see
+    /// [`Config::possessive_quantifiers`].
+    ///
+    /// A thread only remembers its last entry in each region: if it enters a
+    /// region again while a higher priority thread is still inside the region
+    /// from its previous entry, it can't be killed for that entry anymore.
+    CutEnd(u32),
+    /// Reports a match of the pattern with the given id, which is always 0
+    /// unless several patterns are compiled together, see
+    /// [`Compiler::compile_many`].
+    Accept(u32),
+}
+
+/// The assertions of the bytecode, one for each [`regex_syntax::hir::Look`].
+/// The bytecode uses its own type, so that its format doesn't depend on the
+/// one of regex-syntax.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookKind {
+    /// `\A`, or `^` without multi-line mode.
+    Start,
+    /// `\z`, or `$` without multi-line mode.
+    End,
+    /// `(?m)^`
+    StartLF,
+    /// `(?m)$`
+    EndLF,
+    /// `(?mR)^`
+    StartCRLF,
+    /// `(?mR)$`
+    EndCRLF,
+    /// `\b`: a word char (`\w`) on exactly one side, the bounds of the input
+    /// being non-word chars.
+    WordUnicode,
+    /// `\B`: word chars on both sides, or on none.
+    WordUnicodeNegate,
+    /// `(?-u:\b)`, like `\b` with only the ASCII word chars.
+    WordAscii,
+    /// `(?-u:\B)`
+    WordAsciiNegate,
+    /// `\<` or `\b{start}`: no word char before, and one after.
+    WordStartUnicode,
+    /// `\>` or `\b{end}`: a word char before, and none after.
+    WordEndUnicode,
+    /// `(?-u:\<)`
+    WordStartAscii,
+    /// `(?-u:\>)`
+    WordEndAscii,
+    /// `\b{start-half}`: no word char before, whatever comes after.
+    WordStartHalfUnicode,
+    /// `\b{end-half}`: no word char after, whatever comes before.
+    WordEndHalfUnicode,
+    /// `(?-u:\b{start-half})`
+    WordStartHalfAscii,
+    /// `(?-u:\b{end-half})`
+    WordEndHalfAscii,
+}
+
+impl LookKind {
+    /// Whether this is a word assertion with the Unicode word chars, which
+    /// need the Unicode tables.
+    pub fn is_unicode_word(self) -> bool {
+        matches!(
+            self,
+            LookKind::WordUnicode
+                | LookKind::WordUnicodeNegate
+                | LookKind::WordStartUnicode
+                | LookKind::WordEndUnicode
+                | LookKind::WordStartHalfUnicode
+                | LookKind::WordEndHalfUnicode
+        )
+    }
+}
+
+impl From<LookKind> for Look {
+    fn from(look: LookKind) -> Self {
+        match look {
+            LookKind::Start => Look::Start,
+            LookKind::End => Look::End,
+            LookKind::StartLF => Look::StartLF,
+            LookKind::EndLF => Look::EndLF,
+            LookKind::StartCRLF => Look::StartCRLF,
+            LookKind::EndCRLF => Look::EndCRLF,
+            LookKind::WordUnicode => Look::WordUnicode,
+            LookKind::WordUnicodeNegate => Look::WordUnicodeNegate,
+            LookKind::WordAscii => Look::WordAscii,
+            LookKind::WordAsciiNegate => Look::WordAsciiNegate,
+            LookKind::WordStartUnicode => Look::WordStartUnicode,
+            LookKind::WordEndUnicode => Look::WordEndUnicode,
+            LookKind::WordStartAscii => Look::WordStartAscii,
+            LookKind::WordEndAscii => Look::WordEndAscii,
+            LookKind::WordStartHalfUnicode => Look::WordStartHalfUnicode,
+            LookKind::WordEndHalfUnicode => Look::WordEndHalfUnicode,
+            LookKind::WordStartHalfAscii => Look::WordStartHalfAscii,
+            LookKind::WordEndHalfAscii => Look::WordEndHalfAscii,
+        }
+    }
+}
+
+impl From<Look> for LookKind {
+    fn from(look: Look) -> Self {
+        match look {
+            Look::Start => LookKind::Start,
+            Look::End => LookKind::End,
+            Look::StartLF => LookKind::StartLF,
+            Look::EndLF => LookKind::EndLF,
+            Look::StartCRLF => LookKind::StartCRLF,
+            Look::EndCRLF => LookKind::EndCRLF,
+            Look::WordUnicode => LookKind::WordUnicode,
+            Look::WordUnicodeNegate => LookKind::WordUnicodeNegate,
+            Look::WordAscii => LookKind::WordAscii,
+            Look::WordAsciiNegate => LookKind::WordAsciiNegate,
+            Look::WordStartUnicode => LookKind::WordStartUnicode,
+            Look::WordEndUnicode => LookKind::WordEndUnicode,
+            Look::WordStartAscii => LookKind::WordStartAscii,
+            Look::WordEndAscii => LookKind::WordEndAscii,
+            Look::WordStartHalfUnicode => LookKind::WordStartHalfUnicode,
+            Look::WordEndHalfUnicode => LookKind::WordEndHalfUnicode,
+            Look::WordStartHalfAscii => LookKind::WordStartHalfAscii,
+            Look::WordEndHalfAscii => LookKind::WordEndHalfAscii,
+        }
+    }
 }
 
 use Instruction::*;
-use regex_syntax::hir::{Capture, Class, Hir, HirKind, Literal, Look, Repetition};
+use regex_syntax::ast::{self, Ast, GroupKind, RepetitionKind};
+use regex_syntax::hir::translate::TranslatorBuilder;
+use regex_syntax::hir::{Capture, Class, ErrorKind, Hir, HirKind, Literal, Look, Repetition};
 
 /// Compilation error
 /// TODO: Explain why each of these senario can occure
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq)]
 pub enum CompileError {
     InvalidUtf8,
     ContainsLookAround,
-    ContainsNamedCaptureGroup,
+    /// A unicode property class (e.g. `\p{Greek}`) was used while unicode
+    /// mode is disabled.
+    UnsupportedInNonUnicodeMode {
+        property: String,
+    },
+    /// Both `force_class_outlining` and `force_class_inlining` were set.
+    ConflictingClassCompilation,
+    /// See [`limits::MAX_INSTRUCTIONS`].
+    TooManyInstructions {
+        instructions: usize,
+        max: usize,
+    },
+    /// See [`limits::MAX_UNROLLED_REPETITION_PRODUCT`].
+    RepetitionTooLarge {
+        product: usize,
+        max: usize,
+    },
+    /// See [`limits::MAX_FORK_BRANCHES`].
+    TooManyForkBranches {
+        branches: usize,
+        max: usize,
+    },
+    /// See [`limits::MAX_OUTLINED_CLASSES`].
+    TooManyOutlinedClasses {
+        classes: usize,
+        max: usize,
+    },
+    /// The compiled program, in bytes, is over the size limit of the
+    /// configuration, see [`Config::size_limit`]. The size of the bytecode is
+    /// known before compiling it, the JIT stops at the first instruction
+    /// whose code crosses the limit, so its size is the one emitted so far.
+    CompiledProgramTooLarge {
+        size: usize,
+        limit: usize,
+    },
 }
 
 impl fmt::Display for CompileError {
@@ -46,215 +219,1148 @@ impl fmt::Display for CompileError {
         match self {
             CompileError::InvalidUtf8 => write!(f, "Pattern contains non-unicode sequence"),
             CompileError::ContainsLookAround => write!(f, "Pattern contains look-around"),
-            CompileError::ContainsNamedCaptureGroup => {
-                write!(f, "Pattern contains named capture groups")
+            CompileError::UnsupportedInNonUnicodeMode { property } => write!(
+                f,
+                "Property class {property} can't be used when unicode mode is disabled"
+            ),
+            CompileError::ConflictingClassCompilation => {
+                write!(f, "Classes can't be forced to be both outlined and inlined")
             }
+            CompileError::TooManyInstructions { instructions, max } => write!(
+                f,
+                "The pattern compiles to {instructions} instructions, over the limit of {max}"
+            ),
+            CompileError::RepetitionTooLarge { product, max } => write!(
+                f,
+                "The repetitions unroll {product} copies of a sub-pattern, over the limit of {max}"
+            ),
+            CompileError::TooManyForkBranches { branches, max } => write!(
+                f,
+                "The pattern forks into {branches} branches, over the limit of {max}"
+            ),
+            CompileError::TooManyOutlinedClasses { classes, max } => write!(
+                f,
+                "The pattern has {classes} outlined classes, over the limit of {max}"
+            ),
+            CompileError::CompiledProgramTooLarge { size, limit } => write!(
+                f,
+                "The compiled program takes {size} bytes, over the size limit of {limit}"
+            ),
         }
     }
 }
 
 impl Error for CompileError {}
 
-#[derive(Debug, Default)]
+/// A change made by the compiler to the pattern, which doesn't change its
+/// matches, but may point at a mistake in it. See [`Compiler::compile_with_notes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompileNote {
+    /// A branch of an alternation was identical to the previous one, and was
+    /// removed, e.g. the second `foo` of `foo|foo|bar`.
+    DuplicateBranch { branch: String },
+}
+
+impl fmt::Display for CompileNote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileNote::DuplicateBranch { branch } => {
+                write!(f, "Duplicate alternation branch {branch:?} was removed")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
 pub struct Bytecode {
     // TODO: Make these fields private, and only alow reading them most likely
     pub instructions: Vec<Instruction>,
     pub barriers: Vec<bool>,
-    pub outlined_classes: Vec<Box<[(Char, Char)]>>,
+    /// The classes of [`Instruction::ConsumeOutlined`], which may be shared
+    /// with other bytecodes, see [`ClassCache`].
+    pub outlined_classes: Vec<Arc<[(Char, Char)]>>,
     pub write_reg_count: usize,
+    /// For each instruction, the number of consume instructions in a row
+    /// from it, the ones after the first without a barrier, e.g. 3 for the
+    /// first char of `abc`, and 0 for the instructions which don't consume.
+    /// Filled with the barriers, see [`Bytecode::consume_run`].
+    pub consume_runs: Vec<u32>,
+}
+
+impl Bytecode {
+    /// A hash of the bytecode, computed over its [`encoding`]. The compilation
+    /// is deterministic, so it only depends on the pattern, the [`Config`]
+    /// and the version of the crate, and can be used as the key of a cache of
+    /// compiled artifacts.
+    pub fn content_hash(&self) -> u64 {
+        let words = encoding::encode(self);
+        stable_hash(words.iter().flat_map(|word| word.to_le_bytes()))
+    }
+
+    /// The number of consume instructions in a row from `pc`, see
+    /// [`Bytecode::consume_runs`]. 0 for the bytecode built by hand without
+    /// them, which then runs without the optimizations relying on them.
+    pub fn consume_run(&self, pc: usize) -> usize {
+        self.consume_runs.get(pc).map_or(0, |run| *run as usize)
+    }
+
+    /// Fills [`Bytecode::consume_runs`], once the barriers are placed.
+    fn place_consume_runs(&mut self) {
+        let mut runs = vec![0u32; self.instructions.len()];
+        for pc in (0..self.instructions.len()).rev() {
+            if let Consume(_) | ConsumeClass(_) | ConsumeOutlined(_) = self.instructions[pc] {
+                let next = match runs.get(pc + 1) {
+                    Some(next) if !self.barriers[pc + 1] => *next,
+                    _ => 0,
+                };
+                runs[pc] = next + 1;
+            }
+        }
+        self.consume_runs = runs;
+    }
+
+    /// The classes of the [`Instruction::ConsumeClass`] instructions, then
+    /// the outlined classes.
+    pub fn classes(&self) -> impl Iterator<Item = &[(Char, Char)]> {
+        let inline = self
+            .instructions
+            .iter()
+            .filter_map(|instruction| match instruction {
+                ConsumeClass(class) => Some(&**class),
+                _ => None,
+            });
+        inline.chain(self.outlined_classes.iter().map(|class| &**class))
+    }
+
+    /// Returns true if every class is normalized (see
+    /// [`IntervalSet::is_normalized`]), as the engines expect. This is always
+    /// the case for the bytecode of the [`Compiler`] and of
+    /// [`encoding::decode`].
+    pub fn classes_are_normalized(&self) -> bool {
+        self.classes().all(IntervalSet::is_normalized)
+    }
+
+    /// Normalizes every class, for bytecode which was not built by the
+    /// [`Compiler`], see [`IntervalSet::from_ranges`]. The classes keep their
+    /// ids, even if some of them become equal.
+    pub fn normalize_classes(&mut self) {
+        let normalize = |class: &[(Char, Char)]| IntervalSet::from_ranges(class.iter().copied());
+        for instruction in &mut self.instructions {
+            if let ConsumeClass(class) = instruction {
+                *class = normalize(class).to_ranges();
+            }
+        }
+        for class in &mut self.outlined_classes {
+            *class = normalize(class).to_ranges().into();
+        }
+    }
+
+    /// Replaces the outlined classes by the equal ones of the cache, and adds
+    /// the others to it, see [`ClassCache`].
+    pub fn intern_classes(&mut self, cache: &ClassCache) {
+        for class in &mut self.outlined_classes {
+            *class = cache.intern(class);
+        }
+    }
+}
+
+/// Outlined classes shared by several bytecodes, e.g. the ones of a pattern
+/// compiled with and without case folding (see
+/// [`crate::Builder::build_case_pair`]): the bytecodes interning their
+/// classes in the same cache share the storage of the equal ones. Large
+/// Unicode classes, such as `\p{Han}`, often stay the same when folded.
+///
+/// The cache keeps its classes alive until it is dropped, the bytecodes
+/// keep the ones they use.
+#[derive(Debug, Default)]
+pub struct ClassCache {
+    classes: Mutex<SharedClasses>,
+}
+
+type SharedClasses = HashSet<Arc<[(Char, Char)]>>;
+
+impl ClassCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the class of the cache equal to the given one, adding it if
+    /// there is none.
+    pub fn intern(&self, class: &Arc<[(Char, Char)]>) -> Arc<[(Char, Char)]> {
+        let mut classes = self.lock();
+        match classes.get(class) {
+            Some(interned) => interned.clone(),
+            None => {
+                classes.insert(class.clone());
+                class.clone()
+            }
+        }
+    }
+
+    /// The number of distinct classes in the cache.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// The size in bytes of the ranges of the classes in the cache.
+    pub fn bytes(&self) -> usize {
+        self.lock()
+            .iter()
+            .map(|class| mem::size_of_val(&**class))
+            .sum()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SharedClasses> {
+        // Interning never leaves the set inconsistent.
+        self.classes.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
+/// Classes with more ranges than this are outlined (see
+/// [`Instruction::ConsumeOutlined`]), smaller ones are inlined in their
+/// [`Instruction::ConsumeClass`]. The JIT checks the ranges of an inline
+/// class one after the other, while outlined ones are shared and compiled
+/// once. This can be overriden with `force_class_outlining` and
+/// `force_class_inlining` in [`Config`], to test both code paths.
+pub const MAX_INLINE_CLASS_RANGES: usize = 4;
+
 /// A compiler from [`regex_syntax::hir::Hir`] to
 /// this bytecode representation.
 #[derive(Debug, Default)]
 pub struct Compiler {
     bytecode: Bytecode,
+    /// The ids of the outlined classes, only used for lookups: the ids are
+    /// given in the order the classes are found, so that the compilation is
+    /// deterministic.
     outlined_classes: HashMap<Box<[(Char, Char)]>, usize>,
     config: Config,
+    notes: Vec<CompileNote>,
+    /// Number of cut regions compiled so far, see [`Instruction::CutEnd`].
+    cut_count: u32,
+    /// Added to the group indices of the pattern being compiled, so that the
+    /// patterns of [`Compiler::compile_many`] write their own registers.
+    group_offset: u32,
+}
+
+/// Name of the groups standing for possessive repetitions in the hir (see
+/// [`mark_possessive`]). It is not a valid group name, so it can't clash with
+/// the ones of the pattern.
+const POSSESSIVE_GROUP_NAME: &str = "<possessive>";
+
+fn is_possessive_group(capture: &Capture) -> bool {
+    capture.name.as_deref() == Some(POSSESSIVE_GROUP_NAME)
 }
 
 impl Compiler {
+    /// Parse the pattern to [`regex_syntax::hir::Hir`] with the given config.
+    ///
+    /// The errors have the span of the part of the pattern at fault. Unicode property classes used in non-unicode mode
+    /// are reported as [`CompileError::UnsupportedInNonUnicodeMode`], instead
+    /// of the generic error from regex-syntax.
+    pub fn parse(pattern: &str, config: &Config) -> Result<Hir, PatternError> {
+        // Same as `Parser::from(config)`, with the possessive repetitions
+        // marked in the ast before it is translated.
+        let mut ast = ast::parse::ParserBuilder::new()
+            .nest_limit(config.nest_limit)
+            .build()
+            .parse(pattern)
+            .map_err(|err| PatternError::syntax(pattern, err.into()))?;
+        if config.possessive_quantifiers {
+            mark_possessive(&mut ast);
+        }
+        let hir = TranslatorBuilder::new()
+            .unicode(config.unicode)
+            .case_insensitive(config.case_insensitive)
+            .build()
+            .translate(pattern, &ast)
+            .map_err(|err| {
+                let span = err.span().start.offset..err.span().end.offset;
+                let property = &pattern[span.clone()];
+                if *err.kind() == ErrorKind::UnicodeNotAllowed
+                    && (property.starts_with("\\p") || property.starts_with("\\P"))
+                {
+                    let property = property.to_string();
+                    let err = CompileError::UnsupportedInNonUnicodeMode { property };
+                    return PatternError::new(pattern, Some(span), err);
+                }
+                PatternError::syntax(pattern, err.into())
+            })?;
+        Ok(hir)
+    }
+
     /// Try to compile a regex in [`regex_syntax::hir::Hir`] form to
     /// this bytecode.
     pub fn compile(hir: Hir, config: Config) -> Result<Bytecode, CompileError> {
-        if !hir.properties().is_utf8() {
-            return Err(CompileError::InvalidUtf8);
+        Self::compile_with_notes(hir, config).map(|(bytecode, _)| bytecode)
+    }
+
+    /// Like [`Compiler::compile`], but also returns the notes about what the
+    /// compiler changed in the pattern.
+    pub fn compile_with_notes(
+        hir: Hir,
+        config: Config,
+    ) -> Result<(Bytecode, Vec<CompileNote>), CompileError> {
+        Self::check(&hir, &config)?;
+        Self::check_repetitions(&hir)?;
+        let instructions = Self::instruction_count(&hir, &config);
+        Self::check_instruction_count(instructions)?;
+        Self::check_size_limit(instructions, &config)?;
+        let mut compiler = Compiler {
+            config,
+            ..Default::default()
+        };
+        compiler.compile_internal(hir);
+        compiler.push(Accept(0));
+        compiler.check_bytecode_limits()?;
+        compiler.place_barriers();
+        compiler.assert_consumes_chars();
+        Ok((compiler.bytecode, compiler.notes))
+    }
+
+    /// Compiles several patterns into a single bytecode, which matches like
+    /// their alternation, by priority order, but whose `Accept` reports which
+    /// pattern matched (its index in `hirs`). With no pattern, the bytecode
+    /// never matches.
+    ///
+    /// Each pattern has its own range of groups: group 0 is shared, and the
+    /// other groups of a pattern follow the ones of the previous patterns,
+    /// e.g. the group 1 of the second pattern is the group 3 of the bytecode
+    /// if the first pattern has 2 groups (besides group 0). The number of
+    /// groups of each pattern is the one of [`Compiler::capture_count`].
+    pub fn compile_many(hirs: Vec<Hir>, config: Config) -> Result<Bytecode, CompileError> {
+        // The fork between the patterns, and their accepts
+        let mut instructions = usize::from(hirs.len() > 1);
+        for hir in &hirs {
+            Self::check(hir, &config)?;
+            Self::check_repetitions(hir)?;
+            instructions = instructions.saturating_add(Self::instruction_count(hir, &config));
         }
+        Self::check_instruction_count(instructions)?;
+        Self::check_size_limit(instructions, &config)?;
         let mut compiler = Compiler {
             config,
             ..Default::default()
         };
-        compiler.compile_internal(hir, false);
-        compiler.push(Accept, false);
+        if hirs.is_empty() {
+            // An empty class, which never matches.
+            compiler.push(ConsumeClass(Box::default()));
+            compiler.place_barriers();
+            return Ok(compiler.bytecode);
+        }
+        let length = hirs.len();
+        let fork_pc = compiler.current_pc();
+        if length > 1 {
+            // Patched below, once the start of each pattern is known
+            compiler.push(Jmp(0));
+        }
+        let mut starts = Vec::with_capacity(length);
+        for (id, hir) in hirs.into_iter().enumerate() {
+            starts.push(compiler.current_pc());
+            let group_count = Self::capture_count(&hir, &compiler.config);
+            compiler.compile_internal(hir);
+            compiler.push(Accept(id as u32));
+            compiler.group_offset += group_count as u32 - 1;
+        }
+        if length > 1 {
+            compiler.bytecode.instructions[fork_pc] = ForkN(starts.into_boxed_slice());
+        }
+        compiler.check_bytecode_limits()?;
+        compiler.place_barriers();
+        compiler.assert_consumes_chars();
         Ok(compiler.bytecode)
     }
 
+    /// Rejects the patterns the compiler doesn't support.
+    pub(crate) fn check(hir: &Hir, config: &Config) -> Result<(), CompileError> {
+        if config.force_class_outlining && config.force_class_inlining {
+            return Err(CompileError::ConflictingClassCompilation);
+        }
+        if !hir.properties().is_utf8() {
+            return Err(CompileError::InvalidUtf8);
+        }
+        Ok(())
+    }
+
+    /// Rejects the patterns whose counted repetitions unroll too many copies,
+    /// see [`limits::MAX_UNROLLED_REPETITION_PRODUCT`].
+    pub(crate) fn check_repetitions(hir: &Hir) -> Result<(), CompileError> {
+        let product = Self::repetition_product(hir);
+        let max = limits::MAX_UNROLLED_REPETITION_PRODUCT;
+        if product > max {
+            return Err(CompileError::RepetitionTooLarge { product, max });
+        }
+        Ok(())
+    }
+
+    /// The largest product of the counts of nested counted repetitions,
+    /// saturating at `usize::MAX`.
+    fn repetition_product(hir: &Hir) -> usize {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => 1,
+            HirKind::Repetition(repetition) => {
+                let count = repetition.max.unwrap_or(repetition.min).max(1) as usize;
+                count.saturating_mul(Self::repetition_product(&repetition.sub))
+            }
+            HirKind::Capture(capture) => Self::repetition_product(&capture.sub),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => {
+                hirs.iter().map(Self::repetition_product).max().unwrap_or(1)
+            }
+        }
+    }
+
+    /// Rejects the bytecodes with too many instructions, see
+    /// [`limits::MAX_INSTRUCTIONS`].
+    pub(crate) fn check_instruction_count(instructions: usize) -> Result<(), CompileError> {
+        let max = limits::MAX_INSTRUCTIONS;
+        if instructions > max {
+            return Err(CompileError::TooManyInstructions { instructions, max });
+        }
+        Ok(())
+    }
+
+    /// Rejects the bytecodes whose instructions take more bytes than
+    /// [`Config::size_limit`]. The outlined classes are left out, they are
+    /// bounded by [`limits::MAX_OUTLINED_CLASSES`] and shared by their uses.
+    pub(crate) fn check_size_limit(
+        instructions: usize,
+        config: &Config,
+    ) -> Result<(), CompileError> {
+        let size = instructions.saturating_mul(mem::size_of::<Instruction>());
+        let limit = config.size_limit;
+        if size > limit {
+            return Err(CompileError::CompiledProgramTooLarge { size, limit });
+        }
+        Ok(())
+    }
+
+    /// Checks the limits only known once the bytecode is compiled, see
+    /// [`limits::MAX_FORK_BRANCHES`] and [`limits::MAX_OUTLINED_CLASSES`].
+    /// The compilation is bounded by the limits checked before.
+    fn check_bytecode_limits(&self) -> Result<(), CompileError> {
+        let branches = self
+            .bytecode
+            .instructions
+            .iter()
+            .map(|instruction| match instruction {
+                ForkN(targets) => targets.len(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0);
+        let max = limits::MAX_FORK_BRANCHES;
+        if branches > max {
+            return Err(CompileError::TooManyForkBranches { branches, max });
+        }
+        let classes = self.bytecode.outlined_classes.len();
+        let max = limits::MAX_OUTLINED_CLASSES;
+        if classes > max {
+            return Err(CompileError::TooManyOutlinedClasses { classes, max });
+        }
+        Ok(())
+    }
+
+    /// Returns the number of capture groups of the pattern, including the
+    /// implicit group 0, or 1 if they are disabled (see [`Config::cg`]).
+    ///
+    /// Like in the `regex` crate, this is one more than the largest group
+    /// index in the hir, which is not always the number of groups it
+    /// contains: regex-syntax drops the groups which can never match (e.g.
+    /// the first one of `(a){0}(b)`), but the following ones keep their index.
+    pub fn capture_count(hir: &Hir, config: &Config) -> usize {
+        if !config.cg {
+            return 1;
+        }
+        let mut max_index = 0;
+        visit_captures(hir, |capture| {
+            if !is_possessive_group(capture) {
+                max_index = max_index.max(capture.index as usize);
+            }
+        });
+        max_index + 1
+    }
+
+    /// Returns the name of each group of the hir, by index, group 0 first,
+    /// `None` for the groups without names. Like [`Compiler::capture_count`],
+    /// there is only group 0 if the groups are disabled.
+    pub fn group_names(hir: &Hir, config: &Config) -> Vec<Option<Box<str>>> {
+        let mut names = vec![None; Self::capture_count(hir, config)];
+        if config.cg {
+            visit_captures(hir, |capture| {
+                if !is_possessive_group(capture) {
+                    names[capture.index as usize] = capture.name.clone();
+                }
+            });
+        }
+        names
+    }
+
+    /// Returns the number of groups (including group 0) which participate in
+    /// every match of the hir, if it is the same for all of them, like the
+    /// `static_captures_len` of the `regex` crate, e.g. 2 for `(a)|(b)`, but
+    /// none for `(a)?`. There is only group 0 if the groups are disabled.
+    pub fn static_group_len(hir: &Hir, config: &Config) -> Option<usize> {
+        if !config.cg {
+            return Some(1);
+        }
+        Some(static_explicit_group_len(hir)? + 1)
+    }
+
+    /// Returns the number of instructions of the bytecode of the hir, the
+    /// length of [`Bytecode::instructions`] after [`Compiler::compile`],
+    /// without compiling it. The count saturates at `usize::MAX`.
+    ///
+    /// The sub-pattern of a counted repetition is counted once, and
+    /// multiplied by the number of copies the compiler unrolls, so this takes
+    /// linear time in the size of the hir (for a bounded nesting depth), even
+    /// when the bytecode is huge, e.g. for `(?:a{1000}){1000}`.
+    ///
+    /// This mirrors the compilation of the hir into instructions, one
+    /// construct at a time, and must be kept in sync with it.
+    pub fn instruction_count(hir: &Hir, config: &Config) -> usize {
+        // And the final Accept
+        Self::count_internal(hir, config).saturating_add(1)
+    }
+
     fn current_pc(&self) -> usize {
         self.bytecode.instructions.len()
     }
 
-    fn push(&mut self, instruction: Instruction, barrier: bool) {
+    fn push(&mut self, instruction: Instruction) {
         self.bytecode.instructions.push(instruction);
-        self.bytecode.barriers.push(barrier);
     }
 
-    fn fork2(a: usize, b: usize, greedy: bool) -> Instruction {
-        if greedy { Fork2(a, b) } else { Fork2(b, a) }
+    /// Checks that no instruction consumes [`Char::INPUT_BOUND`]: it stands
+    /// for the edges of the haystack, which such an instruction would match
+    /// as a char. Any other value is a char, e.g. `\0`. regex-syntax never
+    /// produces it, so this would be a bug of the parser or of the compiler.
+    fn assert_consumes_chars(&self) {
+        // The ranges are ordered, and nothing is above the bound.
+        let bounded = |class: &[(Char, Char)]| class.iter().all(|(_, to)| *to != Char::INPUT_BOUND);
+        let chars = self
+            .bytecode
+            .instructions
+            .iter()
+            .all(|instruction| match instruction {
+                Consume(c) => *c != Char::INPUT_BOUND,
+                ConsumeClass(class) => bounded(class),
+                _ => true,
+            });
+        assert!(
+            chars
+                && self
+                    .bytecode
+                    .outlined_classes
+                    .iter()
+                    .all(|class| bounded(class)),
+            "An instruction consumes Char::INPUT_BOUND"
+        );
     }
 
-    /// Compiles the given hir to Bytecode.
-    /// Takes as parameter whenever a barrier should be added for the first instruction
-    /// in the compiled code, and returns whenever whatever comes after should have a barrier.
-    fn compile_internal(&mut self, hir: Hir, mut barrier: bool) -> bool {
-        match hir.into_kind() {
-            HirKind::Empty => barrier,
-            HirKind::Literal(Literal(bytes)) => {
-                // Ok because we check for Hir::is_utf8() before
-                let string = str::from_utf8(&bytes).unwrap();
-                // We could also directly decode the chars from the bytes
-                // without creating the &str.
-                for c in string.chars() {
-                    self.push(Consume(c.into()), barrier);
-                    if barrier {
-                        // Only the first consume need a barrier (if it was required in the first place)
-                        barrier = false;
-                    }
-                }
-                barrier
+    /// Places the barriers, and the consume runs which depend on them, once
+    /// the whole bytecode is compiled.
+    ///
+    /// A barrier goes on every join: every instruction which can be reached
+    /// from two others, or from another one and the start of the search for
+    /// the first instruction, e.g. the end of an alternation or the head of a
+    /// loop. Only the first thread (by priority) going through a barrier at a
+    /// given position survives, which keeps the number of threads bounded by
+    /// the size of the bytecode.
+    ///
+    /// This also breaks the loops which don't consume anything (e.g. the
+    /// empty branch of `(?:a|)*`): a loop is entered through its first
+    /// instruction reached from the start, which is a join, since it has a
+    /// predecessor in the loop and one outside of it. So a thread can't go
+    /// around a loop twice at the same position.
+    ///
+    /// The barriers never go on a WriteReg or a CutEnd, but on the first
+    /// instruction after them, which they always fall through to. This way the
+    /// barriers (and so which threads are pruned) don't depend on
+    /// [`Config::cg`], and a thread is never killed right before leaving a cut
+    /// region, or the threads it should kill would survive. An Accept needs
+    /// none, the threads reaching it stop there anyway.
+    fn place_barriers(&mut self) {
+        let instructions = &self.bytecode.instructions;
+        let mut predecessors = vec![0u32; instructions.len()];
+        if let Some(first) = predecessors.first_mut() {
+            *first += 1;
+        }
+        let mut add = |pc: usize| {
+            if let Some(count) = predecessors.get_mut(pc) {
+                *count += 1;
             }
-            HirKind::Class(class) => {
-                let class = match class {
-                    Class::Unicode(class_unicode) => class_unicode
-                        .iter()
-                        .map(|c| (c.start().into(), c.end().into()))
-                        .collect::<Box<[_]>>(),
-                    Class::Bytes(class_byte) => class_byte
-                        .iter()
-                        .map(|c| (c.start().into(), c.end().into()))
-                        .collect::<Box<[_]>>(),
-                };
-                // TODO: Parametrized this
-                if class.len() > 4 {
-                    let id = match self.outlined_classes.get(&class) {
-                        Some(id) => *id,
-                        None => {
-                            let id = self.bytecode.outlined_classes.len();
-                            // TODO: Find a way to avoid this cloning
-                            self.outlined_classes.insert(class.clone(), id);
-                            self.bytecode.outlined_classes.push(class);
-                            id
-                        }
-                    };
-                    self.push(ConsumeOutlined(id), barrier);
-                } else {
-                    self.push(ConsumeClass(class), barrier);
+        };
+        for (pc, instruction) in instructions.iter().enumerate() {
+            match instruction {
+                Fork2(a, b) => {
+                    add(*a);
+                    add(*b);
                 }
-                false
+                ForkN(targets) => targets.iter().for_each(|target| add(*target)),
+                Jmp(target) => add(*target),
+                Accept(_) => {}
+                _ => add(pc + 1),
             }
-            HirKind::Look(look) => {
-                self.push(Assertion(look), barrier);
-                false
+        }
+        let mut barriers = vec![false; instructions.len()];
+        for (pc, count) in predecessors.into_iter().enumerate() {
+            if count > 1 {
+                let pc = (pc..instructions.len())
+                    .find(|pc| !matches!(instructions[*pc], WriteReg(_) | CutEnd(_)))
+                    .expect("a WriteReg or a CutEnd is always followed by another instruction");
+                barriers[pc] = !matches!(instructions[pc], Accept(_));
             }
-            HirKind::Repetition(Repetition {
-                min,
-                max,
-                greedy,
-                sub,
-            }) => {
-                let mut last_iter_start = None;
-                for i in 0..min {
-                    if i == min - 1 {
-                        last_iter_start = Some(self.current_pc());
-                    }
-                    // Same as Literal and Concat, only the begining may require a barrier
-                    barrier = self.compile_internal(*sub.clone(), barrier);
-                }
-                match max {
-                    Some(max) => {
-                        let diff = (max - min) as usize;
-                        let mut forks_pc = Vec::with_capacity(diff);
-                        for _ in min..max {
-                            forks_pc.push(self.current_pc());
-                            self.push(Fork2(0, 0), barrier);
-                            barrier = self.compile_internal(*sub.clone(), false);
-                        }
-                        let end_pc = self.current_pc();
-                        for fork_pc in forks_pc {
-                            self.bytecode.instructions[fork_pc] =
-                                Self::fork2(fork_pc + 1, end_pc, greedy);
-                        }
-                        // TODO: There are some rare cases where this is not necessary
-                        true
-                    }
-                    None => match last_iter_start {
-                        Some(last_iter_start) => {
-                            self.push(
-                                Self::fork2(last_iter_start, self.current_pc() + 1, greedy),
-                                barrier,
-                            );
-                            self.bytecode.barriers[last_iter_start] = true;
-                            false
-                        }
-                        None => {
-                            let fork_pc = self.current_pc();
-                            self.push(Fork2(0, 0), true);
-                            barrier = self.compile_internal(*sub, false);
-                            // Technnically we could pass false here, since it will immediatly jump to
-                            // an instruction (the first fork) with a barrier
-                            self.push(Jmp(fork_pc), barrier);
-                            self.bytecode.instructions[fork_pc] =
-                                Self::fork2(fork_pc + 1, self.current_pc(), greedy);
-                            false
-                        }
-                    },
-                }
+        }
+        self.bytecode.barriers = barriers;
+        self.bytecode.place_consume_runs();
+    }
+
+    fn fork2(a: usize, b: usize, greedy: bool) -> Instruction {
+        if greedy { Fork2(a, b) } else { Fork2(b, a) }
+    }
+
+    /// Compiles the given hir to Bytecode.
+    ///
+    /// This recurses once per nesting level of the pattern, so only the
+    /// variants nesting directly (captures and concatenations) are compiled
+    /// here, the other ones have their own method. This keeps the frame small,
+    /// and deeply nested patterns (see [`Config::nest_limit`]) from
+    /// overflowing the stack.
+    ///
+    /// The instructions emitted here are counted by
+    /// [`Compiler::instruction_count`], which must be updated along with it.
+    fn compile_internal(&mut self, hir: Hir) {
+        match hir.into_kind() {
+            HirKind::Empty => {}
+            HirKind::Literal(Literal(bytes)) => self.compile_literal(&bytes),
+            HirKind::Class(class) => self.compile_class(&class),
+            HirKind::Look(look) => self.push(Assertion(look.into())),
+            HirKind::Repetition(repetition) => self.compile_repetition(repetition),
+            HirKind::Capture(capture) if is_possessive_group(&capture) => {
+                self.compile_cut(*capture.sub)
             }
-            HirKind::Capture(Capture { index, name, sub }) => {
+            HirKind::Capture(Capture { index, sub, .. }) => {
                 if self.config.cg {
-                    // TODO: Add support for this
-                    assert!(name.is_none());
-                    self.push(WriteReg(index * 2), barrier);
-                    let barrier = self.compile_internal(*sub, false);
-                    self.push(WriteReg(index * 2 + 1), barrier);
+                    let index = self.group_offset + index;
+                    self.push(WriteReg(index * 2));
+                    self.compile_internal(*sub);
+                    self.push(WriteReg(index * 2 + 1));
                     self.bytecode.write_reg_count += 2;
-                    false
                 } else {
-                    self.compile_internal(*sub, barrier)
+                    self.compile_internal(*sub)
                 }
             }
             HirKind::Concat(hirs) => {
                 for hir in hirs {
-                    barrier = self.compile_internal(hir, barrier);
+                    self.compile_internal(hir);
                 }
-                barrier
             }
             // Quick annoying fun fact for anyone who would read this:
             // In regex-syntax (rust regex) Alternation means a regex of the form e1|e2|e3,
             // and concatenation is e1e2e3. In V8 (and I guess JS in general) alternation
             // means e1e2e3 and disjuction means e1|e2|e3.
-            HirKind::Alternation(hirs) => {
-                let length = hirs.len();
-                let mut fork_targets = Vec::with_capacity(length);
-                let mut jmps = Vec::with_capacity(length - 1);
-                let current_pc = self.current_pc();
-                // Just to allocate some space
-                self.push(Jmp(0), barrier);
-                for (i, hir) in hirs.into_iter().enumerate() {
-                    fork_targets.push(self.current_pc());
-                    let barrier = self.compile_internal(hir, false);
-                    if i < length - 1 {
-                        jmps.push(self.current_pc());
-                        // Patched just below
-                        self.push(Jmp(0), barrier);
-                    }
+            HirKind::Alternation(hirs) => self.compile_alternation(hirs),
+        }
+    }
+
+    /// Compiles a possessive repetition, in a cut region of its own.
+    fn compile_cut(&mut self, hir: Hir) {
+        let id = self.cut_count;
+        self.cut_count += 1;
+        self.push(CutBegin(id));
+        self.compile_internal(hir);
+        self.push(CutEnd(id));
+    }
+
+    fn compile_literal(&mut self, bytes: &[u8]) {
+        // Ok because we check for Hir::is_utf8() before
+        let string = str::from_utf8(bytes).unwrap();
+        // We could also directly decode the chars from the bytes
+        // without creating the &str.
+        for c in string.chars() {
+            self.push(Consume(c.into()));
+        }
+    }
+
+    fn compile_class(&mut self, class: &Class) {
+        self.compile_set(&class_set(class));
+    }
+
+    fn compile_set(&mut self, set: &IntervalSet) {
+        let class = set.to_ranges();
+        let outline = if self.config.force_class_outlining {
+            true
+        } else if self.config.force_class_inlining {
+            false
+        } else {
+            class.len() > MAX_INLINE_CLASS_RANGES
+        };
+        if outline {
+            let id = match self.outlined_classes.get(&class) {
+                Some(id) => *id,
+                None => {
+                    let id = self.bytecode.outlined_classes.len();
+                    // TODO: Find a way to avoid this cloning
+                    self.outlined_classes.insert(class.clone(), id);
+                    self.bytecode.outlined_classes.push(class.into());
+                    id
                 }
-                self.bytecode.instructions[current_pc] = ForkN(fork_targets.into_boxed_slice());
-                // Path jumps to point to the end of the alternation
-                for pc in jmps {
-                    self.bytecode.instructions[pc] = Jmp(self.current_pc())
+            };
+            self.push(ConsumeOutlined(id));
+        } else {
+            self.push(ConsumeClass(class));
+        }
+    }
+
+    fn compile_repetition(&mut self, repetition: Repetition) {
+        let Repetition {
+            min,
+            max,
+            greedy,
+            sub,
+        } = repetition;
+        let mut last_iter_start = None;
+        for i in 0..min {
+            if i == min - 1 {
+                last_iter_start = Some(self.current_pc());
+            }
+            self.compile_internal(*sub.clone());
+        }
+        match max {
+            // The optional copies are unrolled, lazy or not. A thread in a
+            // copy can't be merged with the threads of another copy at the
+            // same position: they started elsewhere and have a different
+            // number of iterations left, e.g. for `.{0,2}?X` on "abcX" the
+            // thread started at 0 runs out of iterations on `c`, and only the
+            // one started at 1 matches.
+            //
+            // Sharing a single copy of the body between the iterations isn't
+            // possible either, even for bodies without groups: a thread in the
+            // shared body doesn't know how many iterations it went through,
+            // so it can't tell which fork follows, and `(?:ab){1,2}` would
+            // match all of "ababab". Bounding the copies needs a counter,
+            // which the threads don't have.
+            Some(max) => {
+                let diff = (max - min) as usize;
+                let mut forks_pc = Vec::with_capacity(diff);
+                for _ in min..max {
+                    forks_pc.push(self.current_pc());
+                    self.push(Fork2(0, 0));
+                    self.compile_internal(*sub.clone());
+                }
+                let end_pc = self.current_pc();
+                for fork_pc in forks_pc {
+                    self.bytecode.instructions[fork_pc] = Self::fork2(fork_pc + 1, end_pc, greedy);
+                }
+            }
+            None => match last_iter_start {
+                Some(last_iter_start) => {
+                    self.push(Self::fork2(last_iter_start, self.current_pc() + 1, greedy))
                 }
-                true
+                // Like the `regex` crate, `e*` is compiled as `(?:e+)?` when
+                // `e` can match the empty string: the thread going around the
+                // loop once without consuming anything is then the one
+                // leaving it, with the groups written by that iteration (e.g.
+                // the group 1 of `(?:(a|)|b)*` is empty, not unset, on "").
+                // The loop below would kill it at its fork, in favor of the
+                // thread which never entered it.
+                None if sub.properties().minimum_len() == Some(0) => {
+                    let fork_pc = self.current_pc();
+                    self.push(Fork2(0, 0));
+                    self.compile_internal(*sub);
+                    let end_pc = self.current_pc() + 1;
+                    self.push(Self::fork2(fork_pc + 1, end_pc, greedy));
+                    self.bytecode.instructions[fork_pc] = Self::fork2(fork_pc + 1, end_pc, greedy);
+                }
+                None => {
+                    let fork_pc = self.current_pc();
+                    self.push(Fork2(0, 0));
+                    self.compile_internal(*sub);
+                    self.push(Jmp(fork_pc));
+                    self.bytecode.instructions[fork_pc] =
+                        Self::fork2(fork_pc + 1, self.current_pc(), greedy);
+                }
+            },
+        }
+    }
+
+    fn compile_alternation(&mut self, mut hirs: Vec<Hir>) {
+        // A branch identical to the previous one can't match anything the
+        // previous one doesn't, with a lower priority, so it is dead code.
+        // Groups have different indices, so they are never identical.
+        let notes = &mut self.notes;
+        hirs.dedup_by(|branch, previous| {
+            let duplicate = branch == previous;
+            if duplicate {
+                notes.push(CompileNote::DuplicateBranch {
+                    branch: branch.to_string(),
+                });
+            }
+            duplicate
+        });
+        let mut branches = merge_single_char_branches(hirs);
+        if branches.len() == 1 {
+            return self.compile_branch(branches.pop().unwrap());
+        }
+        let length = branches.len();
+        let mut fork_targets = Vec::with_capacity(length);
+        let mut jmps = Vec::with_capacity(length - 1);
+        let current_pc = self.current_pc();
+        // Just to allocate some space
+        self.push(Jmp(0));
+        for (i, branch) in branches.into_iter().enumerate() {
+            fork_targets.push(self.current_pc());
+            self.compile_branch(branch);
+            if i < length - 1 {
+                jmps.push(self.current_pc());
+                // Patched just below
+                self.push(Jmp(0));
             }
         }
+        // Path jumps to point to the end of the alternation
+        for pc in jmps {
+            self.bytecode.instructions[pc] = Jmp(self.current_pc())
+        }
+        // Empty branches are just a jump to the end, which the fork can take
+        // directly. Several of them then have the same target, only the first
+        // one (of highest priority) is kept.
+        let mut targets: Vec<usize> = Vec::with_capacity(length);
+        let mut seen = HashSet::with_capacity(length);
+        for pc in fork_targets {
+            // The last branch may target the end, which is not compiled yet.
+            let target = match self.bytecode.instructions.get(pc) {
+                Some(Jmp(target)) => *target,
+                _ => pc,
+            };
+            if seen.insert(target) {
+                targets.push(target);
+            }
+        }
+        self.bytecode.instructions[current_pc] = ForkN(targets.into_boxed_slice());
+    }
+
+    fn compile_branch(&mut self, branch: Branch) {
+        match branch {
+            Branch::Hir(hir) => self.compile_internal(hir),
+            Branch::Class(set) => self.compile_set(&set),
+        }
+    }
+
+    fn count_internal(hir: &Hir, config: &Config) -> usize {
+        match hir.kind() {
+            HirKind::Empty => 0,
+            HirKind::Literal(Literal(bytes)) => {
+                str::from_utf8(bytes).map_or(0, |string| string.chars().count())
+            }
+            HirKind::Class(_) | HirKind::Look(_) => 1,
+            HirKind::Repetition(repetition) => Self::count_repetition(repetition, config),
+            // The CutBegin and CutEnd
+            HirKind::Capture(capture) if is_possessive_group(capture) => {
+                Self::count_internal(&capture.sub, config).saturating_add(2)
+            }
+            HirKind::Capture(capture) => {
+                let sub = Self::count_internal(&capture.sub, config);
+                if config.cg {
+                    sub.saturating_add(2)
+                } else {
+                    sub
+                }
+            }
+            HirKind::Concat(hirs) => hirs.iter().fold(0usize, |count, hir| {
+                count.saturating_add(Self::count_internal(hir, config))
+            }),
+            HirKind::Alternation(hirs) => Self::count_alternation(hirs, config),
+        }
+    }
+
+    fn count_repetition(repetition: &Repetition, config: &Config) -> usize {
+        let sub = Self::count_internal(&repetition.sub, config);
+        let required = (repetition.min as usize).saturating_mul(sub);
+        let optional = match repetition.max {
+            // A fork before each optional copy
+            Some(max) => ((max - repetition.min) as usize).saturating_mul(sub.saturating_add(1)),
+            // A fork after the last copy
+            None if repetition.min > 0 => 1,
+            // A fork before the single copy, and a fork or a jump after it
+            None => sub.saturating_add(2),
+        };
+        required.saturating_add(optional)
+    }
+
+    fn count_alternation(hirs: &[Hir], config: &Config) -> usize {
+        let mut hirs: Vec<&Hir> = hirs.iter().collect();
+        hirs.dedup();
+        let branches = merge_single_char_branches(hirs);
+        let count = branches.iter().fold(0usize, |count, branch| {
+            count.saturating_add(match branch {
+                Branch::Hir(hir) => Self::count_internal(hir, config),
+                Branch::Class(_) => 1,
+            })
+        });
+        match branches.len() {
+            1 => count,
+            // The fork, and a jump after each branch but the last
+            length => count.saturating_add(length),
+        }
+    }
+}
+
+/// The chars of the class. Any further class math (e.g. folding) should be
+/// done on this set, rather than on the raw ranges. The ranges of
+/// regex-syntax are already sorted, but every class goes through the same
+/// normalization whatever its origin.
+fn class_set(class: &Class) -> IntervalSet {
+    match class {
+        Class::Unicode(class) => {
+            IntervalSet::from_ranges(class.iter().map(|c| (c.start().into(), c.end().into())))
+        }
+        Class::Bytes(class) => {
+            IntervalSet::from_ranges(class.iter().map(|c| (c.start().into(), c.end().into())))
+        }
+    }
+}
+
+/// A branch of an alternation, or the class merging several of them, see
+/// [`merge_single_char_branches`].
+enum Branch<H = Hir> {
+    Hir(H),
+    Class(IntervalSet),
+}
+
+/// The class being built by [`merge_single_char_branches`]: its index in the
+/// branches, the ranges of its chars, and how many branches it merges so far.
+/// The ranges are only normalized once the class is complete, so that a long
+/// alternation doesn't copy them at every branch.
+type OpenClass = (usize, Vec<(Char, Char)>, usize);
+
+/// Merges the branches of an alternation which match a single char (a
+/// literal char or a class) into classes, which cost a single comparison
+/// instead of a thread per branch, e.g. `x|y|\d` is compiled as `[xy\d]`.
+/// regex-syntax only does it when every branch is a literal char.
+///
+/// A class goes where its first branch was, and the branches merged into it
+/// keep no priority among themselves: each matches a single char, so the
+/// one which matches leads to the same span. A later branch only joins it if
+/// the branches in between can't start with the same chars, so that they
+/// never match where it does. Otherwise, e.g. for the last `a` of `x|ab|a`,
+/// it starts a new class, after them. Groups are never merged.
+///
+/// The branches are borrowed when the compiler only counts the instructions,
+/// see [`Compiler::instruction_count`].
+fn merge_single_char_branches<H: Borrow<Hir>>(hirs: Vec<H>) -> Vec<Branch<H>> {
+    let single_chars = hirs
+        .iter()
+        .filter(|hir| single_char((*hir).borrow()).is_some());
+    if single_chars.take(2).count() < 2 {
+        return hirs.into_iter().map(Branch::Hir).collect();
+    }
+    let mut branches = Vec::with_capacity(hirs.len());
+    let mut class: Option<OpenClass> = None;
+    // The chars the branches since the start of the class can start with,
+    // None if they can start with anything (or match the empty string).
+    let mut blocked = Some(CharUnion::default());
+    let close = |branches: &mut Vec<Branch<H>>, class: Option<OpenClass>| {
+        if let Some((index, ranges, merged)) = class
+            && merged > 1
+        {
+            branches[index] = Branch::Class(IntervalSet::from_ranges(ranges));
+        }
+    };
+    for hir in hirs {
+        match single_char(hir.borrow()) {
+            Some(set) => match &mut class {
+                Some((_, ranges, merged))
+                    if blocked.as_ref().is_some_and(|b| b.is_disjoint(&set)) =>
+                {
+                    ranges.extend(set.intervals().iter().map(Interval::bounds));
+                    *merged += 1;
+                }
+                _ => {
+                    close(&mut branches, class.take());
+                    class = Some((branches.len(), set.to_ranges().into_vec(), 1));
+                    blocked = Some(CharUnion::default());
+                    branches.push(Branch::Hir(hir));
+                }
+            },
+            None => {
+                if class.is_some()
+                    && let Some(chars) = &mut blocked
+                    && !add_first_chars(hir.borrow(), chars)
+                {
+                    blocked = None;
+                }
+                branches.push(Branch::Hir(hir));
+            }
+        }
+    }
+    close(&mut branches, class);
+    branches
+}
+
+/// The chars of the hir, if it matches exactly one.
+fn single_char(hir: &Hir) -> Option<IntervalSet> {
+    match hir.kind() {
+        HirKind::Class(class) => Some(class_set(class)),
+        HirKind::Literal(Literal(bytes)) => {
+            let mut chars = str::from_utf8(bytes).ok()?.chars();
+            let c = chars.next()?;
+            chars
+                .next()
+                .is_none()
+                .then(|| IntervalSet::from_ranges([(c.into(), c.into())]))
+        }
+        _ => None,
+    }
+}
+
+/// Adds to the union some chars which include all those the matches of the
+/// hir can start with. Returns false if they may not start with a char, i.e.
+/// if it can match the empty string. Conservative: false for the hirs too
+/// complex to tell.
+fn add_first_chars(hir: &Hir, chars: &mut CharUnion) -> bool {
+    if hir.properties().minimum_len().is_none_or(|len| len == 0) {
+        return false;
+    }
+    match hir.kind() {
+        HirKind::Literal(Literal(bytes)) => {
+            match str::from_utf8(bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => {
+                    chars.insert((c.into(), c.into()));
+                    true
+                }
+                None => false,
+            }
+        }
+        HirKind::Class(class) => {
+            for interval in class_set(class).intervals() {
+                chars.insert(interval.bounds());
+            }
+            true
+        }
+        HirKind::Capture(capture) => add_first_chars(&capture.sub, chars),
+        HirKind::Repetition(repetition) => add_first_chars(&repetition.sub, chars),
+        HirKind::Concat(hirs) => hirs.first().is_some_and(|hir| add_first_chars(hir, chars)),
+        HirKind::Alternation(hirs) => hirs.iter().all(|hir| add_first_chars(hir, chars)),
+        HirKind::Empty | HirKind::Look(_) => false,
+    }
+}
+
+/// A union of char ranges, which grows one range at a time. Unlike
+/// [`IntervalSet::union`], adding a range doesn't copy the ones already in
+/// the union, so it takes logarithmic time (amortized).
+#[derive(Debug, Default)]
+struct CharUnion(BTreeMap<Char, Char>);
+
+impl CharUnion {
+    /// Adds the inclusive range, merged with the ones it overlaps.
+    fn insert(&mut self, (mut from, mut to): (Char, Char)) {
+        if from > to {
+            return;
+        }
+        if let Some((&start, &end)) = self.0.range(..=from).next_back()
+            && end >= from
+        {
+            from = start;
+            to = to.max(end);
+        }
+        while let Some((&start, &end)) = self.0.range(from..=to).next() {
+            to = to.max(end);
+            self.0.remove(&start);
+        }
+        self.0.insert(from, to);
+    }
+
+    /// Returns true if no char of the set is in the union.
+    fn is_disjoint(&self, set: &IntervalSet) -> bool {
+        // The ranges don't overlap, so the last one starting before the end
+        // of an interval is the only one which can overlap it.
+        set.intervals().iter().all(|interval| {
+            let (from, to) = interval.bounds();
+            self.0
+                .range(..=to)
+                .next_back()
+                .is_none_or(|(_, &end)| end < from)
+        })
+    }
+}
+
+/// Calls `f` on every capture group of the hir. Not recursive, since the hir
+/// can be deeply nested.
+fn visit_captures(hir: &Hir, mut f: impl FnMut(&Capture)) {
+    let mut stack = vec![hir];
+    while let Some(hir) = stack.pop() {
+        match hir.kind() {
+            HirKind::Capture(capture) => {
+                f(capture);
+                stack.push(&capture.sub);
+            }
+            HirKind::Repetition(repetition) => stack.push(&repetition.sub),
+            HirKind::Concat(hirs) | HirKind::Alternation(hirs) => stack.extend(hirs),
+            HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => {}
+        }
+    }
+}
+
+/// The number of groups participating in every match of the hir, without
+/// group 0 and the possessive groups, see [`Compiler::static_group_len`].
+fn static_explicit_group_len(hir: &Hir) -> Option<usize> {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Literal(_) | HirKind::Class(_) | HirKind::Look(_) => Some(0),
+        HirKind::Capture(capture) => {
+            let own = usize::from(!is_possessive_group(capture));
+            Some(static_explicit_group_len(&capture.sub)? + own)
+        }
+        HirKind::Repetition(repetition) => match static_explicit_group_len(&repetition.sub)? {
+            len if repetition.min > 0 || len == 0 => Some(len),
+            _ => None,
+        },
+        HirKind::Concat(hirs) => hirs.iter().map(static_explicit_group_len).sum(),
+        HirKind::Alternation(hirs) => {
+            let mut lens = hirs.iter().map(static_explicit_group_len);
+            let first = lens.next()??;
+            lens.all(|len| len == Some(first)).then_some(first)
+        }
+    }
+}
+
+/// Replaces each possessive repetition of the ast (e.g. `a*+`, `a?+` or
+/// `a{2,}+`) by a group named [`POSSESSIVE_GROUP_NAME`] around the repetition
+/// without its trailing `+`. regex-syntax parses them as a `+` repetition of
+/// a repetition, like the `regex` crate does, so the pattern is left to it.
+fn mark_possessive(ast: &mut Ast) {
+    let mut stack = vec![ast];
+    while let Some(ast) = stack.pop() {
+        if let Ast::Repetition(outer) = ast
+            && let Ast::Repetition(inner) = &*outer.ast
+            && outer.greedy
+            && outer.op.kind == RepetitionKind::OneOrMore
+            // Not `a* +` in verbose mode
+            && outer.op.span.start == inner.op.span.end
+        {
+            let span = outer.span;
+            let inner = mem::replace(&mut outer.ast, Box::new(Ast::empty(span)));
+            *ast = Ast::group(ast::Group {
+                span,
+                kind: GroupKind::CaptureName {
+                    starts_with_p: false,
+                    name: ast::CaptureName {
+                        span,
+                        name: POSSESSIVE_GROUP_NAME.to_string(),
+                        index: 0,
+                    },
+                },
+                ast: inner,
+            });
+        }
+        match ast {
+            Ast::Repetition(repetition) => stack.push(&mut repetition.ast),
+            Ast::Group(group) => stack.push(&mut group.ast),
+            Ast::Alternation(alternation) => stack.extend(&mut alternation.asts),
+            Ast::Concat(concat) => stack.extend(&mut concat.asts),
+            _ => {}
+        }
     }
 }
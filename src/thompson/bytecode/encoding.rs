@@ -0,0 +1,481 @@
+//! A stable, compact binary encoding of [`Bytecode`].
+//!
+//! The in-memory [`Bytecode`] stays the format used for execution. This
+//! module is a projection of it into a stream of `u32` words, whose layout
+//! is versioned and documented here so that external tools (debuggers,
+//! visualizers) can read it without depending on the layout of
+//! [`Instruction`].
+//!
+//! # Layout
+//!
+//! Every value is a single `u32` word. A stream is made of a header followed
+//! by three sections:
+//!
+//! | Word          | Content                                                  |
+//! |---------------|----------------------------------------------------------|
+//! | 0             | Bits 0..8: [`VERSION`], bits 8..32: [`MAGIC`]            |
+//! | 1             | Number of registers written (`write_reg_count`)          |
+//! | 2             | Number of instructions                                   |
+//! | 3             | Length in words of the code section                      |
+//! | 4             | Number of outlined classes                               |
+//! | code section  | The instructions, see below                              |
+//! | class table   | For each outlined class, its offset in the constant pool |
+//! | constant pool | Class tables, until the end of the stream                |
+//!
+//! Each instruction starts with a word holding its [`Opcode`] in bits 0..8,
+//! and in bit 8 whether the instruction has a barrier. The remaining bits are
+//! reserved and must be zero. It is followed by its operands, one word each:
+//!
+//! | Opcode          | Operands                                                   |
+//! |-----------------|------------------------------------------------------------|
+//! | Consume         | The code point                                             |
+//! | ConsumeClass    | Offset of the class in the constant pool                   |
+//! | ConsumeOutlined | Index of the outlined class                                |
+//! | Fork2           | The two targets, by priority                               |
+//! | ForkN           | The number of targets `n`, followed by the `n` targets     |
+//! | Jmp             | The target                                                 |
+//! | WriteReg        | The register                                               |
+//! | Assertion       | The assertion, as numbered in [`LOOKS`]                    |
+//! | CutBegin        | The id of the region                                       |
+//! | CutEnd          | The id of the region                                       |
+//! | Accept          | The id of the pattern                                      |
+//!
+//! Targets are instruction indices (not word offsets), exactly like in
+//! [`Instruction`]. A class in the constant pool is its number of ranges `n`
+//! followed by the `n` ranges, each being its start and end (inclusive)
+//! code points. Offsets into the constant pool are relative to its start.
+//! The ranges of a class are normalized (see
+//! [`IntervalSet::is_normalized`](crate::util::IntervalSet::is_normalized)),
+//! classes which are not are rejected by [`decode`].
+//!
+//! Any change to this layout, or to the numbering of opcodes and assertions,
+//! must bump [`VERSION`].
+use std::{error::Error, fmt, ops::RangeInclusive};
+
+use super::{Bytecode, Instruction, LookKind};
+use crate::util::{Char, IntervalSet};
+
+/// Version of the encoding, stored in the low byte of the first word.
+pub const VERSION: u8 = 2;
+
+/// Magic number stored in the upper 24 bits of the first word (`"GRX"`).
+pub const MAGIC: u32 = 0x47_52_58;
+
+/// Length in words of the header.
+pub const HEADER_LEN: usize = 5;
+
+/// Opcodes reserved for future instructions of the engine.
+pub const RESERVED_OPCODES: RangeInclusive<u8> = 12..=127;
+
+/// Opcodes reserved for external tools (e.g. breakpoints or annotations in a
+/// debugger). They are never produced by [`encode`], and rejected by the
+/// decoder.
+pub const TOOLING_OPCODES: RangeInclusive<u8> = 128..=255;
+
+/// Assertions, numbered by their index in this table.
+pub const LOOKS: [LookKind; 18] = [
+    LookKind::Start,
+    LookKind::End,
+    LookKind::StartLF,
+    LookKind::EndLF,
+    LookKind::StartCRLF,
+    LookKind::EndCRLF,
+    LookKind::WordUnicode,
+    LookKind::WordUnicodeNegate,
+    LookKind::WordAscii,
+    LookKind::WordAsciiNegate,
+    LookKind::WordStartUnicode,
+    LookKind::WordEndUnicode,
+    LookKind::WordStartAscii,
+    LookKind::WordEndAscii,
+    LookKind::WordStartHalfUnicode,
+    LookKind::WordEndHalfUnicode,
+    LookKind::WordStartHalfAscii,
+    LookKind::WordEndHalfAscii,
+];
+
+const OPCODE_MASK: u32 = 0xff;
+const BARRIER_BIT: u32 = 1 << 8;
+
+/// The numeric opcode of each [`Instruction`].
+///
+/// Opcode 0 is never valid, so that a zeroed stream is rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum Opcode {
+    Consume = 1,
+    ConsumeClass = 2,
+    ConsumeOutlined = 3,
+    Fork2 = 4,
+    ForkN = 5,
+    Jmp = 6,
+    WriteReg = 7,
+    Assertion = 8,
+    Accept = 9,
+    CutBegin = 10,
+    CutEnd = 11,
+}
+
+impl Opcode {
+    /// Returns the opcode of the given instruction.
+    pub fn of(instruction: &Instruction) -> Self {
+        match instruction {
+            Instruction::Consume(_) => Opcode::Consume,
+            Instruction::ConsumeClass(_) => Opcode::ConsumeClass,
+            Instruction::ConsumeOutlined(_) => Opcode::ConsumeOutlined,
+            Instruction::Fork2(_, _) => Opcode::Fork2,
+            Instruction::ForkN(_) => Opcode::ForkN,
+            Instruction::Jmp(_) => Opcode::Jmp,
+            Instruction::WriteReg(_) => Opcode::WriteReg,
+            Instruction::Assertion(_) => Opcode::Assertion,
+            Instruction::Accept(_) => Opcode::Accept,
+            Instruction::CutBegin(_) => Opcode::CutBegin,
+            Instruction::CutEnd(_) => Opcode::CutEnd,
+        }
+    }
+
+    /// Returns the opcode with the given number, if any.
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            1 => Opcode::Consume,
+            2 => Opcode::ConsumeClass,
+            3 => Opcode::ConsumeOutlined,
+            4 => Opcode::Fork2,
+            5 => Opcode::ForkN,
+            6 => Opcode::Jmp,
+            7 => Opcode::WriteReg,
+            8 => Opcode::Assertion,
+            9 => Opcode::Accept,
+            10 => Opcode::CutBegin,
+            11 => Opcode::CutEnd,
+            _ => return None,
+        })
+    }
+}
+
+/// Error returned when decoding an invalid stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The first word does not start with [`MAGIC`].
+    BadMagic,
+    /// The stream was produced by another version of the encoding.
+    UnsupportedVersion(u8),
+    /// The header describes sections that don't fit in the stream, or the
+    /// code section doesn't contain the announced number of instructions.
+    InvalidHeader,
+    /// The stream ends in the middle of the item starting at this offset.
+    Truncated { offset: usize },
+    /// The word at this offset is not a valid instruction word.
+    InvalidOpcode { offset: usize, word: u32 },
+    /// An operand of the instruction at this offset is out of range.
+    InvalidOperand { offset: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "Stream is not encoded bytecode"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported encoding version {version}")
+            }
+            DecodeError::InvalidHeader => write!(f, "Invalid header"),
+            DecodeError::Truncated { offset } => {
+                write!(f, "Stream is truncated at offset {offset}")
+            }
+            DecodeError::InvalidOpcode { offset, word } => {
+                write!(f, "Invalid instruction word {word:#x} at offset {offset}")
+            }
+            DecodeError::InvalidOperand { offset } => {
+                write!(f, "Invalid operand for the instruction at offset {offset}")
+            }
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Encodes the bytecode, see the [module documentation](self) for the layout.
+///
+/// # Panics
+///
+/// Panics if a value (e.g. the number of instructions) doesn't fit in 32 bits.
+pub fn encode(bytecode: &Bytecode) -> Vec<u32> {
+    let mut code = Vec::with_capacity(bytecode.instructions.len() * 2);
+    let mut pool = Vec::new();
+    let class_table = bytecode
+        .outlined_classes
+        .iter()
+        .map(|class| push_class(&mut pool, class))
+        .collect::<Vec<_>>();
+
+    for (instruction, barrier) in bytecode.instructions.iter().zip(&bytecode.barriers) {
+        let barrier = if *barrier { BARRIER_BIT } else { 0 };
+        code.push(Opcode::of(instruction) as u32 | barrier);
+        match instruction {
+            Instruction::Consume(c) => code.push((*c).into()),
+            Instruction::ConsumeClass(class) => code.push(push_class(&mut pool, class)),
+            Instruction::ConsumeOutlined(id) => code.push(word(*id)),
+            Instruction::Fork2(a, b) => code.extend([word(*a), word(*b)]),
+            Instruction::ForkN(targets) => {
+                code.push(word(targets.len()));
+                code.extend(targets.iter().map(|target| word(*target)));
+            }
+            Instruction::Jmp(target) => code.push(word(*target)),
+            Instruction::WriteReg(reg) => code.push(*reg),
+            Instruction::Assertion(look) => {
+                let id = LOOKS.iter().position(|l| l == look).unwrap();
+                code.push(word(id));
+            }
+            Instruction::CutBegin(id) | Instruction::CutEnd(id) | Instruction::Accept(id) => {
+                code.push(*id)
+            }
+        }
+    }
+
+    let mut words = Vec::with_capacity(HEADER_LEN + code.len() + class_table.len() + pool.len());
+    words.extend([
+        (MAGIC << 8) | VERSION as u32,
+        word(bytecode.write_reg_count),
+        word(bytecode.instructions.len()),
+        word(code.len()),
+        word(class_table.len()),
+    ]);
+    words.extend(code);
+    words.extend(class_table);
+    words.extend(pool);
+    words
+}
+
+/// Decodes a stream produced by [`encode`] back to [`Bytecode`].
+pub fn decode(words: &[u32]) -> Result<Bytecode, DecodeError> {
+    let cursor = InstructionCursor::new(words)?;
+    let outlined_classes = (0..cursor.outlined_count)
+        .map(|id| cursor.outlined_class(id).map(Into::into))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut bytecode = Bytecode {
+        write_reg_count: words[1] as usize,
+        outlined_classes,
+        ..Default::default()
+    };
+    for decoded in cursor {
+        let decoded = decoded?;
+        bytecode.instructions.push(decoded.instruction);
+        bytecode.barriers.push(decoded.barrier);
+    }
+    bytecode.place_consume_runs();
+    Ok(bytecode)
+}
+
+/// An instruction decoded by [`InstructionCursor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedInstruction {
+    /// Offset in words of the instruction in the stream.
+    pub offset: usize,
+    /// Index of the instruction, as used by jump targets.
+    pub pc: usize,
+    pub barrier: bool,
+    pub instruction: Instruction,
+}
+
+/// Iterates over the instructions of an encoded stream, in order.
+///
+/// The header and the operands are validated as they are read. Once an
+/// error is returned the iteration stops.
+#[derive(Debug, Clone)]
+pub struct InstructionCursor<'a> {
+    words: &'a [u32],
+    offset: usize,
+    code_end: usize,
+    pc: usize,
+    instruction_count: usize,
+    outlined_count: usize,
+    class_table: &'a [u32],
+    pool: &'a [u32],
+    failed: bool,
+}
+
+impl<'a> InstructionCursor<'a> {
+    /// Creates a cursor over the given stream, after validating its header.
+    pub fn new(words: &'a [u32]) -> Result<Self, DecodeError> {
+        let Some(header) = words.get(..HEADER_LEN) else {
+            return Err(DecodeError::Truncated { offset: 0 });
+        };
+        if header[0] >> 8 != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let version = (header[0] & OPCODE_MASK) as u8;
+        if version != VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let instruction_count = header[2] as usize;
+        let code_end = HEADER_LEN
+            .checked_add(header[3] as usize)
+            .ok_or(DecodeError::InvalidHeader)?;
+        let outlined_count = header[4] as usize;
+        let table_end = code_end
+            .checked_add(outlined_count)
+            .filter(|end| *end <= words.len())
+            .ok_or(DecodeError::InvalidHeader)?;
+        Ok(InstructionCursor {
+            words,
+            offset: HEADER_LEN,
+            code_end,
+            pc: 0,
+            instruction_count,
+            outlined_count,
+            class_table: &words[code_end..table_end],
+            pool: &words[table_end..],
+            failed: false,
+        })
+    }
+
+    /// Offset in words of the next instruction.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Number of instructions in the stream.
+    pub fn instruction_count(&self) -> usize {
+        self.instruction_count
+    }
+
+    /// Returns the outlined class with the given index.
+    pub fn outlined_class(&self, id: usize) -> Result<Box<[(Char, Char)]>, DecodeError> {
+        let offset = self.code_end + id;
+        let pool_offset = *self
+            .class_table
+            .get(id)
+            .ok_or(DecodeError::InvalidOperand { offset })?;
+        self.class(pool_offset, offset)
+    }
+
+    /// Reads the class at the given offset of the constant pool, reporting
+    /// errors at `offset`, including when it is not normalized.
+    fn class(&self, pool_offset: u32, offset: usize) -> Result<Box<[(Char, Char)]>, DecodeError> {
+        let start = pool_offset as usize;
+        let len = *self
+            .pool
+            .get(start)
+            .ok_or(DecodeError::InvalidOperand { offset })? as usize;
+        let ranges = len
+            .checked_mul(2)
+            .and_then(|len| self.pool.get(start + 1..start + 1 + len))
+            .ok_or(DecodeError::InvalidOperand { offset })?;
+        let class = ranges
+            .chunks_exact(2)
+            .map(|range| Ok((to_char(range[0], offset)?, to_char(range[1], offset)?)))
+            .collect::<Result<Box<[_]>, _>>()?;
+        if !IntervalSet::is_normalized(&class) {
+            return Err(DecodeError::InvalidOperand { offset });
+        }
+        Ok(class)
+    }
+
+    fn decode_next(&mut self) -> Result<DecodedInstruction, DecodeError> {
+        let offset = self.offset;
+        let code = &self.words[..self.code_end];
+        let head = code[offset];
+        let opcode = Opcode::from_u8((head & OPCODE_MASK) as u8)
+            .filter(|_| head & !(OPCODE_MASK | BARRIER_BIT) == 0)
+            .ok_or(DecodeError::InvalidOpcode { offset, word: head })?;
+        let operand_count = match opcode {
+            Opcode::Fork2 => 2,
+            Opcode::ForkN => {
+                1 + *code
+                    .get(offset + 1)
+                    .ok_or(DecodeError::Truncated { offset })? as usize
+            }
+            _ => 1,
+        };
+        let operands = code
+            .get(offset + 1..offset + 1 + operand_count)
+            .ok_or(DecodeError::Truncated { offset })?;
+        let target = |word: u32| {
+            let target = word as usize;
+            if target < self.instruction_count {
+                Ok(target)
+            } else {
+                Err(DecodeError::InvalidOperand { offset })
+            }
+        };
+        let instruction = match opcode {
+            Opcode::Consume => Instruction::Consume(to_char(operands[0], offset)?),
+            Opcode::ConsumeClass => Instruction::ConsumeClass(self.class(operands[0], offset)?),
+            Opcode::ConsumeOutlined => {
+                let id = operands[0] as usize;
+                if id >= self.outlined_count {
+                    return Err(DecodeError::InvalidOperand { offset });
+                }
+                Instruction::ConsumeOutlined(id)
+            }
+            Opcode::Fork2 => Instruction::Fork2(target(operands[0])?, target(operands[1])?),
+            Opcode::ForkN => Instruction::ForkN(
+                operands[1..]
+                    .iter()
+                    .map(|word| target(*word))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Opcode::Jmp => Instruction::Jmp(target(operands[0])?),
+            Opcode::WriteReg => Instruction::WriteReg(operands[0]),
+            Opcode::Assertion => Instruction::Assertion(
+                *LOOKS
+                    .get(operands[0] as usize)
+                    .ok_or(DecodeError::InvalidOperand { offset })?,
+            ),
+            Opcode::Accept => Instruction::Accept(operands[0]),
+            Opcode::CutBegin => Instruction::CutBegin(operands[0]),
+            Opcode::CutEnd => Instruction::CutEnd(operands[0]),
+        };
+        let pc = self.pc;
+        self.pc += 1;
+        self.offset += 1 + operand_count;
+        Ok(DecodedInstruction {
+            offset,
+            pc,
+            barrier: head & BARRIER_BIT != 0,
+            instruction,
+        })
+    }
+}
+
+impl Iterator for InstructionCursor<'_> {
+    type Item = Result<DecodedInstruction, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let result = if self.offset < self.code_end && self.pc < self.instruction_count {
+            self.decode_next()
+        } else if self.offset == self.code_end && self.pc == self.instruction_count {
+            return None;
+        } else {
+            Err(DecodeError::InvalidHeader)
+        };
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Appends the class to the constant pool and returns its offset.
+fn push_class(pool: &mut Vec<u32>, class: &[(Char, Char)]) -> u32 {
+    let offset = word(pool.len());
+    pool.push(word(class.len()));
+    for (start, end) in class {
+        pool.extend([u32::from(*start), u32::from(*end)]);
+    }
+    offset
+}
+
+fn word(value: usize) -> u32 {
+    u32::try_from(value).expect("bytecode is too large to be encoded")
+}
+
+fn to_char(word: u32, offset: usize) -> Result<Char, DecodeError> {
+    if word == u32::from(Char::INPUT_BOUND) {
+        return Ok(Char::INPUT_BOUND);
+    }
+    char::from_u32(word)
+        .map(Char::from)
+        .ok_or(DecodeError::InvalidOperand { offset })
+}
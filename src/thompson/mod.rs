@@ -1,3 +1,5 @@
+pub mod assertion_only;
 pub mod bytecode;
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
 pub mod pike_jit;
 pub mod pike_vm;
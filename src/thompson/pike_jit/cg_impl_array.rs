@@ -1,6 +1,7 @@
 use dynasmrt::{DynasmApi, DynasmLabelApi, dynasm};
 
-use super::{PikeJIT, cg_implementation::CGImpl};
+use super::PikeJIT;
+use super::cg_implementation::{CGImpl, Clobbers};
 
 pub struct CGImplArray;
 
@@ -13,6 +14,9 @@ impl CGImplArray {
         jit.register_count
     }
 
+    /// Frees the threads of the active queue, down to the sentinel. Besides
+    /// the registers of `free_curr_thread`, this changes `reg1`,
+    /// `curr_thd_data` and `curr_top`, see [`PikeJIT::pop_active`].
     fn free_all_threads_in_active(jit: &mut PikeJIT) {
         __!(jit.ops,
           loop_:
@@ -36,6 +40,21 @@ cst!(
 );
 
 impl CGImpl for CGImplArray {
+    const WRITE_REG_CLOBBERS: Clobbers = Clobbers::NONE;
+    const ACCEPT_CLOBBERS: Clobbers = Clobbers::REG1
+        .union(Clobbers::REG2)
+        .union(Clobbers::CURR_THD_DATA)
+        .union(Clobbers::CG_REG)
+        .union(Clobbers::CURR_TOP);
+    const ALLOC_THREAD_CLOBBERS: Clobbers = Clobbers::REG1
+        .union(Clobbers::CURR_THD_DATA)
+        .union(Clobbers::CG_REG);
+    const FREE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::CG_REG;
+    const CLONE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::REG1
+        .union(Clobbers::REG2)
+        .union(Clobbers::CURR_THD_DATA)
+        .union(Clobbers::CG_REG);
+
     fn write_reg(jit: &mut PikeJIT, reg: u32) {
         // TODO, again make sure we avoid having overflow on indices
         __!(jit.ops,
@@ -45,16 +64,19 @@ impl CGImpl for CGImplArray {
 
     fn accept_curr_thread(jit: &mut PikeJIT) {
         Self::write_reg(jit, 1);
+        // The previous match is kept in reg2, which is left alone by the
+        // threads being freed, see `free_all_threads_in_active`.
+        const {
+            assert!(!Self::FREE_CURR_THREAD_CLOBBERS.contains(Clobbers::REG2));
+        }
         __!(jit.ops,
-          mov reg1, [rbp + current_match_offset!()]
-        ; push reg1
+          mov reg2, [rbp + current_match_offset!()]
         ; mov [rbp + current_match_offset!()], curr_thd_data
         ;; Self::free_all_threads_in_active(jit)
-        ; pop reg1
-        ; test reg1, reg1
+        ; test reg2, reg2
         ; jz >next
         // TODO Find a way to pass a register to free_curr_thread
-        ; mov curr_thd_data, reg1
+        ; mov curr_thd_data, reg2
         ;; Self::free_curr_thread(jit)
         ; next:
         );
@@ -133,8 +155,10 @@ impl CGImpl for CGImplArray {
         ;; {
         for i in 0..jit.register_count {
             let offset = i * ptr_size!();
+            // The spans of the groups which never matched are
+            // `Span::invalid()`, returned as they are.
             if i % 2 == 0 {
-                __!(jit.ops, mov QWORD [mem + curr_thd_data + offset as i32], 1);
+                __!(jit.ops, mov QWORD [mem + curr_thd_data + offset as i32], -1);
             } else {
                 __!(jit.ops, mov QWORD [mem + curr_thd_data + offset as i32], 0);
             }
@@ -1,6 +1,7 @@
 use dynasmrt::{DynasmApi, DynasmLabelApi, dynasm};
 
-use super::{PikeJIT, cg_implementation::CGImpl};
+use super::cg_implementation::{CGImpl, Clobbers};
+use super::{PikeJIT, State};
 
 pub struct CGImplCowArray;
 
@@ -14,6 +15,14 @@ impl CGImplCowArray {
         jit.register_count + 1
     }
 
+    /// The offset of the first array, after the free list.
+    fn arrays_start(jit: &PikeJIT) -> usize {
+        jit.cg_mem_start() + Self::free_list_size(jit) * ptr_size!()
+    }
+
+    /// Frees the threads of the active queue, down to the sentinel. Besides
+    /// the registers of `free_curr_thread`, this changes `reg1`,
+    /// `curr_thd_data` and `curr_top`, see [`PikeJIT::pop_active`].
     fn free_all_threads_in_active(jit: &mut PikeJIT) {
         __!(jit.ops,
           loop_:
@@ -37,6 +46,18 @@ cst!(
 );
 
 impl CGImpl for CGImplCowArray {
+    const WRITE_REG_CLOBBERS: Clobbers = Clobbers::REG1
+        .union(Clobbers::REG2)
+        .union(Clobbers::CURR_THD_DATA)
+        .union(Clobbers::CG_REG);
+    const ACCEPT_CLOBBERS: Clobbers = Self::WRITE_REG_CLOBBERS.union(Clobbers::CURR_TOP);
+    const ALLOC_THREAD_CLOBBERS: Clobbers = Clobbers::REG1
+        .union(Clobbers::CURR_THD_DATA)
+        .union(Clobbers::CG_REG);
+    const FREE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::CG_REG;
+    // Only the ref-count changes, in memory.
+    const CLONE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::NONE;
+
     fn write_reg(jit: &mut PikeJIT, reg: u32) {
         let offset = (jit.register_count * ptr_size!()) as i32;
         __!(jit.ops,
@@ -55,23 +76,44 @@ impl CGImpl for CGImplCowArray {
     }
 
     fn accept_curr_thread(jit: &mut PikeJIT) {
+        // After the write, the array belongs to this thread alone (its
+        // ref-count is 1), and the thread stops here without being freed, so
+        // its reference is moved to the current match as it is. The threads
+        // still sharing the array it had before the write keep their own
+        // references to it. This is checked by `check_ref_counts`.
         Self::write_reg(jit, 1);
+        // The previous match is kept in reg2, which is left alone by the
+        // threads being freed, see `free_all_threads_in_active`.
+        const {
+            assert!(!Self::FREE_CURR_THREAD_CLOBBERS.contains(Clobbers::REG2));
+        }
         __!(jit.ops,
-          mov reg1, [rbp + current_match_offset!()]
-        ; push reg1
+          mov reg2, [rbp + current_match_offset!()]
         ; mov [rbp + current_match_offset!()], curr_thd_data
         ;; Self::free_all_threads_in_active(jit)
-        ; pop reg1
-        ; test reg1, reg1
+        ; test reg2, reg2
         ; jz >end
         // TODO Find a way to pass a register to free_curr_thread
-        ; mov curr_thd_data, reg1
+        ; mov curr_thd_data, reg2
         ;; Self::free_curr_thread(jit)
         ; end:
         );
     }
 
     fn return_result(jit: &mut PikeJIT) {
+        if cfg!(debug_assertions) {
+            let offset = State::COW_ARRAYS_OFFSET;
+            __!(jit.ops,
+              mov reg1, [rbp + state_ptr_offset!()]
+            ; mov reg2, cg_reg
+            ; sub reg2, mem
+            ; mov [reg1 + offset], reg2
+            ; mov reg2, [rbp + current_match_offset!()]
+            ; mov [reg1 + offset + ptr_size!()], reg2
+            ; mov QWORD [reg1 + offset + 2*ptr_size!()], Self::arrays_start(jit) as i32
+            ; mov [reg1 + offset + 3*ptr_size!()], next_tail
+            );
+        }
         __!(jit.ops,
           mov rsi, [rbp + current_match_offset!()]
         ; test rsi, rsi
@@ -101,8 +143,7 @@ impl CGImpl for CGImplCowArray {
     }
 
     fn initialize_cg_region(jit: &mut PikeJIT) {
-        let free_list_start = jit.cg_mem_start();
-        let array_start = (free_list_start + (Self::free_list_size(jit) * ptr_size!())) as u32;
+        let array_start = Self::arrays_start(jit) as u32;
         __!(jit.ops,
           mov QWORD [rbp + current_match_offset!()], 0
         ; lea rsp, [rbp + current_match_offset!()]
@@ -115,13 +156,13 @@ impl CGImpl for CGImplCowArray {
         __!(jit.ops, call ->alloc_empty_array);
     }
 
-    // Must not use end as a local label
+    // Must not use end as a local label. The thread accepted last is not
+    // freed: its array is moved to the current match, see
+    // `accept_curr_thread`.
     fn free_curr_thread(jit: &mut PikeJIT) {
         let offset = (jit.register_count * ptr_size!()) as i32;
         __!(jit.ops,
           dec QWORD [mem + curr_thd_data + offset]
-        ; mov reg1, [mem + curr_thd_data + offset]
-        ; test reg1, reg1
         ; jnz >next
         ; add cg_reg, ptr_size!()
         ; mov QWORD [cg_reg], curr_thd_data
@@ -152,8 +193,10 @@ impl CGImpl for CGImplCowArray {
         ;; {
         for i in 0..jit.register_count {
             let offset = i * ptr_size!();
+            // The spans of the groups which never matched are
+            // `Span::invalid()`, returned as they are.
             if i % 2 == 0 {
-                __!(jit.ops, mov QWORD [mem + curr_thd_data + offset as i32], 1);
+                __!(jit.ops, mov QWORD [mem + curr_thd_data + offset as i32], -1);
             } else {
                 __!(jit.ops, mov QWORD [mem + curr_thd_data + offset as i32], 0);
             }
@@ -195,3 +238,97 @@ impl CGImpl for CGImplCowArray {
         // Nothing to do
     }
 }
+
+/// Where the arrays were when a search returned, written by the jitted code
+/// in debug builds. The fields are offsets in the memory, in bytes, except
+/// for `next_tail`.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct ArraysAtReturn {
+    /// The top of the free list, which starts at the word after the next
+    /// array to allocate.
+    free_top: u64,
+    /// The array of the current match, or 0 without a match.
+    current_match: u64,
+    /// The first array, or 0 if the search didn't return a result (e.g. it
+    /// was interrupted).
+    arrays_start: u64,
+    /// The address of the last thread pushed on the next queue.
+    next_tail: u64,
+}
+
+/// Checks the ref-counts of the arrays once a search returned. The active
+/// queue is empty by then, so the count of each array must be the number of
+/// threads of the next queue pointing to it (e.g. the ones which consumed
+/// the char after the span), plus one for the current match. The arrays
+/// with a count of 0 must be in the free list, once.
+///
+/// Panics if one of them doesn't hold. Only called in debug builds, after
+/// each search with [`crate::CgStrategy::CowArray`].
+pub(super) fn check_ref_counts(state: &mut State, cg_mem_start: usize, register_count: usize) {
+    let arrays = std::mem::take(&mut state.cow_arrays);
+    if arrays.arrays_start == 0 {
+        return;
+    }
+    let word = |offset: u64| offset as usize / ptr_size!();
+    let array_size = register_count + 1;
+    let arrays_start = word(arrays.arrays_start);
+    let mem = state.as_slice();
+    let allocated = (word(mem[cg_mem_start / ptr_size!()]) - arrays_start) / array_size;
+    let index = |array: u64| {
+        let array = word(array);
+        assert!(
+            array >= arrays_start
+                && (array - arrays_start) % array_size == 0
+                && (array - arrays_start) / array_size < allocated,
+            "{array} is not an allocated array"
+        );
+        (array - arrays_start) / array_size
+    };
+
+    let mut references = vec![0; allocated];
+    if arrays.current_match != 0 {
+        references[index(arrays.current_match)] += 1;
+    }
+    // The next queue grows downward from the middle of its region, see
+    // `PikeJIT::swap_queues`.
+    let region = State::queue_region_size(state.queue_capacity);
+    let queues = state.queues as u64;
+    let mid = [
+        queues + region as u64 / 2,
+        queues + (region + region / 2) as u64,
+    ]
+    .into_iter()
+    .find(|mid| arrays.next_tail <= *mid)
+    .expect("the next queue is in one of the regions");
+    for thread in (arrays.next_tail..mid).step_by(PikeJIT::THREAD_SIZE_BYTE as usize) {
+        // SAFETY: The threads between the tail and the middle were pushed
+        // by the search, and the queues are not freed until the state is.
+        let data = unsafe { *(thread as *const u64) };
+        // The sentinels have no array.
+        if data != 0 {
+            references[index(data)] += 1;
+        }
+    }
+
+    let free_list = &mem[cg_mem_start / ptr_size!() + 1..=word(arrays.free_top)];
+    let mut free = vec![false; allocated];
+    for &array in free_list {
+        let i = index(array);
+        assert!(!free[i], "the free list has {} twice", word(array));
+        free[i] = true;
+    }
+    for (i, (references, free)) in references.into_iter().zip(free).enumerate() {
+        let array = arrays_start + i * array_size;
+        let count = mem[array + register_count];
+        assert_eq!(
+            count, references,
+            "the array {array} has {references} references"
+        );
+        assert_eq!(
+            free,
+            count == 0,
+            "the array {array} (count {count}) is free: {free}"
+        );
+    }
+}
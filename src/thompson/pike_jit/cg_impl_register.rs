@@ -1,7 +1,7 @@
 use crate::thompson::pike_jit::PikeJIT;
 use dynasmrt::{DynasmApi, DynasmLabelApi, dynasm};
 
-use super::cg_implementation::CGImpl;
+use super::cg_implementation::{CGImpl, Clobbers};
 
 /// CG implementation when no capture groups are present.
 pub struct CGImplReg;
@@ -14,6 +14,12 @@ cst!(
 );
 
 impl CGImpl for CGImplReg {
+    const WRITE_REG_CLOBBERS: Clobbers = Clobbers::CURR_THD_DATA;
+    const ACCEPT_CLOBBERS: Clobbers = Clobbers::NONE;
+    const ALLOC_THREAD_CLOBBERS: Clobbers = Clobbers::NONE;
+    const FREE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::NONE;
+    const CLONE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::NONE;
+
     fn init_mem_size(_: &PikeJIT) -> usize {
         0
     }
@@ -24,9 +30,14 @@ impl CGImpl for CGImplReg {
         ; mov cg_reg, [rbp + current_match_offset!() + ptr_size!()]
         ; cmp cg_reg, rax
         ; jb >no_match
+        // Nothing to write if the result is empty (e.g. for is_match)
+        ; mov rcx, [rbp + result_len_offset!()]
+        ; test rcx, rcx
+        ; jz >matched
         ; mov rcx, [rbp + (result_offset!())]
         ; mov QWORD [rcx], rax
         ; mov QWORD [rcx+ ptr_size!()], cg_reg
+        ; matched:
         ; mov rax, 1
         ;; jit.epilogue()
         ; ret
@@ -2,7 +2,8 @@ use dynasmrt::{DynasmApi, DynasmLabelApi, dynasm};
 
 use crate::util::Span;
 
-use super::{PikeJIT, cg_implementation::CGImpl};
+use super::cg_implementation::{CGImpl, Clobbers};
+use super::{PikeJIT, State};
 
 /// CG implementation using trees. All operations are O(1), and threads don't
 /// need to be freed. However this consumes O(|haystack|) memory
@@ -23,13 +24,19 @@ cst!(
     last_saved_value_offset!() - 3 * ptr_size!()
 );
 
-extern "sysv64" fn write_results(
+/// The signature of [`write_results`], see [`super::State::write_tree_results`].
+pub(super) type WriteResults = extern "sysv64" fn(*mut Span, usize, usize, *const u64, usize);
+
+pub(super) extern "sysv64" fn write_results(
     spans: *mut Span,
     reg_count: usize,
     mut offset: usize,
     mem: *const u64,
     cg0_to: usize,
 ) {
+    if reg_count == 0 {
+        return;
+    }
     unsafe {
         // Reset span array
         // CG0 is always set
@@ -37,6 +44,7 @@ extern "sysv64" fn write_results(
             (*spans.add(i)) = Span::invalid();
         }
         loop {
+            // The start of group 0, see `CGImplTree::write_reg`.
             if (offset as isize) <= 0 {
                 break;
             }
@@ -47,7 +55,7 @@ extern "sysv64" fn write_results(
             if reg < reg_count {
                 let span = spans.add(span_idx);
                 if (*span).from == usize::MAX {
-                    if reg % 2 == 0 {
+                    if reg.is_multiple_of(2) {
                         (*span).from = pos;
                     } else {
                         (*span).to = pos;
@@ -64,10 +72,24 @@ extern "sysv64" fn write_results(
 }
 
 impl CGImpl for CGImplTree {
+    // The memory grows when a node doesn't fit, see `grow_cg_region`.
+    const WRITE_REG_CLOBBERS: Clobbers = Clobbers::REG1
+        .union(Clobbers::CURR_THD_DATA)
+        .union(Clobbers::CG_REG)
+        .union(Clobbers::MEM);
+    const ACCEPT_CLOBBERS: Clobbers = Clobbers::NONE;
+    const ALLOC_THREAD_CLOBBERS: Clobbers = Clobbers::CURR_THD_DATA;
+    const FREE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::NONE;
+    const CLONE_CURR_THREAD_CLOBBERS: Clobbers = Clobbers::NONE;
+
     fn write_reg(jit: &mut PikeJIT, reg: u32) {
         if reg == 0 {
-            // Since curr_thd_data is init to 0, we set it to -inpu_pos, that way it is flagged as being cg_0
-            // and we avoid using memory
+            // Since curr_thd_data is init to 0, we set it to -input_pos, that
+            // way it is flagged as being cg_0 and we avoid using memory. This
+            // is 0 for a match starting at position 0, which is no node
+            // either: the nodes are after the visited set, so their offsets
+            // are positive (see `initialize_cg_region`). Whether there is a
+            // match at all is recorded apart, see `accept_curr_thread`.
             __!(jit.ops,
              sub curr_thd_data, input_pos
             );
@@ -76,11 +98,27 @@ impl CGImpl for CGImplTree {
             unreachable!()
         }
         __!(jit.ops,
-          mov [mem + cg_reg], curr_thd_data
+        // Registers beyond the requested result length are never reported,
+        // so don't waste a node on them
+          cmp QWORD [rbp + result_len_offset!()], reg as i32
+        ; jbe >skip
+        // Any number of threads may write a group during a step, so the room
+        // for the node is checked by every write
+        ; mov reg1, [rbp + state_ptr_offset!()]
+        ; mov reg1, [reg1 + ptr_size!()]
+        // We want the size in byte, not in word, hence the shift
+        ; shl reg1, 3
+        ; sub reg1, cg_reg
+        ; cmp reg1, size_of::<Node>() as i32
+        ; jae >enough_space
+        ; call ->grow_cg_region
+        ; enough_space:
+        ; mov [mem + cg_reg], curr_thd_data
         ; mov [mem + cg_reg + 8], input_pos
         ; mov QWORD [mem + cg_reg + 16], reg as i32
         ; mov curr_thd_data, cg_reg
         ; add cg_reg, size_of::<Node>() as i32
+        ; skip:
         )
     }
 
@@ -94,7 +132,6 @@ impl CGImpl for CGImplTree {
         )
     }
 
-    #[allow(clippy::fn_to_numeric_cast)]
     fn return_result(jit: &mut PikeJIT) {
         __!(jit.ops,
           mov rdx, [rbp + current_match_offset!() + 2*ptr_size!()]
@@ -105,9 +142,9 @@ impl CGImpl for CGImplTree {
         ; mov rsi, [rbp + result_len_offset!()]
         ; mov rcx, mem
         ; mov r8, [rbp + current_match_offset!() + ptr_size!()]
-        ; mov rax, QWORD write_results as _
+        ; mov rax, [rbp + state_ptr_offset!()]
         // TODO: Check the alignment but normally it should be good
-        ; call rax
+        ; call QWORD [rax + State::WRITE_TREE_RESULTS_OFFSET]
         ; mov rax, 1
         ;; jit.epilogue()
         ; ret
@@ -125,6 +162,9 @@ impl CGImpl for CGImplTree {
     }
 
     fn initialize_cg_region(jit: &mut PikeJIT) {
+        // The offsets of the nodes must not be confused with the start of
+        // group 0, see `write_reg`.
+        assert!(jit.cg_mem_start() > 0, "the visited set is never empty");
         __!(jit.ops,
           mov cg_reg, QWORD jit.cg_mem_start() as _
         // Mark that no match were found yet
@@ -141,19 +181,9 @@ impl CGImpl for CGImplTree {
 
     fn clone_curr_thread(_jit: &mut PikeJIT) {}
 
-    fn at_code_end(_jit: &mut PikeJIT) {}
-
-    fn at_fetch_next_char(jit: &mut PikeJIT) {
-        let requested_space = (jit.write_reg_count * size_of::<Node>()) as i32;
+    fn at_code_end(jit: &mut PikeJIT) {
         __!(jit.ops,
-        // This incurr a load from the stack at every iter which would be great to not have
-          mov reg1, [rbp + state_ptr_offset!()]
-        ; mov reg2, [reg1 + ptr_size!()]
-        // We want the size in byte, not in word, hence the shift
-        ; shl reg2, 3
-        ; sub reg2, cg_reg
-        ; cmp reg2, requested_space
-        ; jae >enough_space
+          ->grow_cg_region:
         // Prepare calling external function, push saved registers
         ; push rax
         ; push rcx
@@ -163,11 +193,13 @@ impl CGImpl for CGImplTree {
         ; push r11
         ; push r9
         ; push r10
-        ; mov rdi, reg1
-        // TODO: Again, make sure rsp is 16byte aligned, but nomrally it should
+        // Realign the stack, after the return address pushed by the call
+        ; sub rsp, 8
+        ; mov rdi, [rbp + state_ptr_offset!()]
         ;; jit.grow_memory()
         // Reload mem
         ; mov mem, [rax]
+        ; add rsp, 8
         // Pop saved registers
         ; pop r10
         ; pop r9
@@ -177,7 +209,11 @@ impl CGImpl for CGImplTree {
         ; pop rdx
         ; pop rcx
         ; pop rax
-        ; enough_space:
+        ; ret
         )
     }
+
+    fn at_fetch_next_char(_: &mut PikeJIT) {
+        // Nothing to do, see `CGImplTree::write_reg`
+    }
 }
@@ -1,6 +1,75 @@
+//! The interface between the JIT and the ways it tracks the capture groups,
+//! see [`crate::CgStrategy`].
+//!
+//! # Registers
+//!
+//! The jitted code keeps its state in registers (see the aliases of the `__!`
+//! macro). The code emitted by the hooks of [`CGImpl`], and the helpers the
+//! jitted code calls (the outlined classes, and the ones added by
+//! [`CGImpl::at_code_end`]), preserve all of them, except:
+//!
+//! - the ones in their [`Clobbers`], e.g. [`CGImpl::WRITE_REG_CLOBBERS`] or
+//!   [`super::OUTLINED_CLASS_CLOBBERS`]. Their callers can't expect these to
+//!   survive, and in debug builds the scratch registers among them are
+//!   overwritten after each hook, so that the tests catch any code which
+//!   does.
+//! - the flags, which are never preserved.
+//!
+//! The hooks and helpers may push on the machine stack, as long as they pop
+//! what they pushed. Only the helpers of an implementation may rely on the
+//! alignment of the stack pointer it sets in [`CGImpl::initialize_cg_region`],
+//! the others align it themselves before calling into Rust.
 use crate::thompson::pike_jit::PikeJIT;
 
+/// A set of registers which some emitted code changes, see the
+/// [module documentation](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clobbers(u8);
+
+impl Clobbers {
+    pub const NONE: Clobbers = Clobbers(0);
+    /// `reg1` (rdi), a scratch register.
+    pub const REG1: Clobbers = Clobbers(1);
+    /// `reg2` (rcx), a scratch register.
+    pub const REG2: Clobbers = Clobbers(1 << 1);
+    /// `curr_thd_data` (rsi), replaced by the data of a new thread, or of a
+    /// copy of the current one.
+    pub const CURR_THD_DATA: Clobbers = Clobbers(1 << 2);
+    /// `cg_reg` (r11), which belongs to the CG implementation.
+    pub const CG_REG: Clobbers = Clobbers(1 << 3);
+    /// `mem` (r8), reloaded when the memory grows.
+    pub const MEM: Clobbers = Clobbers(1 << 4);
+    /// `curr_top` (r10), when the threads of the active queue are popped.
+    pub const CURR_TOP: Clobbers = Clobbers(1 << 5);
+
+    pub const fn union(self, other: Clobbers) -> Clobbers {
+        Clobbers(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Clobbers) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
 pub trait CGImpl {
+    /// The registers changed by [`CGImpl::write_reg`].
+    const WRITE_REG_CLOBBERS: Clobbers;
+    /// The registers changed by [`CGImpl::accept_curr_thread`].
+    const ACCEPT_CLOBBERS: Clobbers;
+    /// The registers changed by [`CGImpl::alloc_thread`], which sets
+    /// `curr_thd_data` to the new thread.
+    const ALLOC_THREAD_CLOBBERS: Clobbers;
+    /// The registers changed by [`CGImpl::free_curr_thread`], after which
+    /// `curr_thd_data` is dead.
+    const FREE_CURR_THREAD_CLOBBERS: Clobbers;
+    /// The registers changed by [`CGImpl::clone_curr_thread`].
+    const CLONE_CURR_THREAD_CLOBBERS: Clobbers;
+
+    /// Generates the code for a WriteReg instruction. The written value must
+    /// go to a register or to the state memory, never to the machine stack:
+    /// a thread can write any number of groups without consuming a char (one
+    /// per nesting level of the pattern), so the stack usage of the jitted
+    /// code would otherwise depend on the pattern.
     fn write_reg(jit: &mut PikeJIT, reg: u32);
     /// Called when generating the code for the accept instruction.
     /// Since the capture groups are what encode the result
@@ -26,6 +95,6 @@ pub trait CGImpl {
     fn at_code_end(jit: &mut PikeJIT);
     /// Called before generating the code for fetching the next character.
     /// This is mostly usefull for implementations that may require the memory
-    /// to grow at runtime.
+    /// to grow at runtime. The code must not change any register.
     fn at_fetch_next_char(jit: &mut PikeJIT);
 }
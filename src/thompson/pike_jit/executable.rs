@@ -0,0 +1,210 @@
+//! The executable memory holding the jitted code.
+//!
+//! The code is assembled in a regular buffer, then copied to memory mapped
+//! writable, which is only made executable (and read-only) once written. The
+//! memory is therefore never writable and executable at the same time (W^X),
+//! which hardened platforms enforce: e.g. SELinux with `deny_execmem`, PaX
+//! `MPROTECT`, or OpenBSD. Some of them deny executable memory altogether, in
+//! which case the compilation fails with
+//! [`CompileError::ExecutableMemoryDenied`](super::CompileError::ExecutableMemoryDenied).
+//!
+//! On macOS, the hardened runtime only allows executable memory mapped with
+//! `MAP_JIT` (and the `com.apple.security.cs.allow-jit` entitlement). Such
+//! memory is writable and executable, but only writable by the thread which
+//! disabled its write protection (with `pthread_jit_write_protect_np`, where
+//! supported), so that is what [`SystemAllocator`] uses there, falling back
+//! to the regular path if `MAP_JIT` is rejected.
+//!
+//! The memory is provided by an [`ExecutableAllocator`], which can be
+//! replaced (see [`super::JittedRegex::with_allocator`]), e.g. to test the
+//! handling of the failures.
+use std::{fmt, io};
+
+use dynasmrt::AssemblyOffset;
+use memmap2::{Mmap, MmapMut};
+
+/// Provides the memory of the jitted code, see the [module documentation](self).
+///
+/// # Safety
+///
+/// Once [`ExecutableRegion::make_executable`] succeeded, the regions must
+/// hold the bytes written before, be executable, and stay so (and at the same
+/// address) until they are dropped.
+pub unsafe trait ExecutableAllocator {
+    /// Returns a writable region of `len` bytes.
+    fn allocate(&self, len: usize) -> io::Result<Box<dyn ExecutableRegion>>;
+}
+
+/// Memory for jitted code, provided by an [`ExecutableAllocator`]. It is
+/// written once, then made executable. The memory is released on drop.
+pub trait ExecutableRegion: Send + Sync {
+    /// The memory to write the code to. Only called before the region is
+    /// made executable.
+    fn writable(&mut self) -> &mut [u8];
+
+    /// Makes the memory executable, and no longer writable.
+    fn make_executable(&mut self) -> io::Result<()>;
+
+    /// The start of the memory. Only called once the region is executable.
+    fn as_ptr(&self) -> *const u8;
+}
+
+/// The allocator used by default, mapping the memory from the OS, see the
+/// [module documentation](self).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemAllocator;
+
+// SAFETY: The regions are mapped by the OS, and only unmapped on drop.
+unsafe impl ExecutableAllocator for SystemAllocator {
+    fn allocate(&self, len: usize) -> io::Result<Box<dyn ExecutableRegion>> {
+        #[cfg(target_os = "macos")]
+        if let Ok(region) = macos::MapJitRegion::new(len) {
+            return Ok(Box::new(region));
+        }
+        Ok(Box::new(MappedRegion::Writable(MmapMut::map_anon(len)?)))
+    }
+}
+
+/// A region of [`SystemAllocator`], mapped writable, then remapped
+/// executable with `mprotect` (or `VirtualProtect` on Windows).
+enum MappedRegion {
+    Writable(MmapMut),
+    Executable(Mmap),
+    /// After a failure to make the region executable, which unmapped it.
+    Unmapped,
+}
+
+impl ExecutableRegion for MappedRegion {
+    fn writable(&mut self) -> &mut [u8] {
+        match self {
+            MappedRegion::Writable(map) => map,
+            _ => panic!("The region is not writable anymore"),
+        }
+    }
+
+    fn make_executable(&mut self) -> io::Result<()> {
+        let MappedRegion::Writable(map) = std::mem::replace(self, MappedRegion::Unmapped) else {
+            panic!("The region is not writable anymore");
+        };
+        *self = MappedRegion::Executable(map.make_exec()?);
+        Ok(())
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        match self {
+            MappedRegion::Executable(map) => map.as_ptr(),
+            _ => panic!("The region is not executable"),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::{io, ptr, slice};
+
+    use super::ExecutableRegion;
+
+    /// A region mapped with `MAP_JIT`, see the [module documentation](super).
+    pub(super) struct MapJitRegion {
+        ptr: *mut u8,
+        len: usize,
+    }
+
+    // SAFETY: The region is only written through `&mut self`.
+    unsafe impl Send for MapJitRegion {}
+    unsafe impl Sync for MapJitRegion {}
+
+    /// Enables or disables the write protection of the `MAP_JIT` regions for
+    /// the current thread, where it is supported (Apple silicon). Elsewhere,
+    /// they are always writable.
+    fn write_protect(enabled: bool) {
+        // SAFETY: These functions have no preconditions.
+        unsafe {
+            if libc::pthread_jit_write_protect_supported_np() != 0 {
+                libc::pthread_jit_write_protect_np(enabled as libc::c_int);
+            }
+        }
+    }
+
+    impl MapJitRegion {
+        pub(super) fn new(len: usize) -> io::Result<Self> {
+            // SAFETY: An anonymous mapping, at an address chosen by the OS.
+            let ptr = unsafe {
+                libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+                    libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_JIT,
+                    -1,
+                    0,
+                )
+            };
+            if ptr == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+            write_protect(false);
+            Ok(MapJitRegion {
+                ptr: ptr.cast(),
+                len,
+            })
+        }
+    }
+
+    impl ExecutableRegion for MapJitRegion {
+        fn writable(&mut self) -> &mut [u8] {
+            // SAFETY: The mapping is `len` bytes long, and the write
+            // protection is disabled until the region is made executable.
+            unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+
+        fn make_executable(&mut self) -> io::Result<()> {
+            write_protect(true);
+            Ok(())
+        }
+
+        fn as_ptr(&self) -> *const u8 {
+            self.ptr
+        }
+    }
+
+    impl Drop for MapJitRegion {
+        fn drop(&mut self) {
+            // SAFETY: The region was mapped by `new`, with this length.
+            unsafe { libc::munmap(self.ptr.cast(), self.len) };
+        }
+    }
+}
+
+/// The jitted code, in an executable region.
+pub(super) struct ExecutableCode {
+    region: Box<dyn ExecutableRegion>,
+    len: usize,
+}
+
+impl ExecutableCode {
+    /// Copies the code to a region of the allocator, and makes it executable.
+    pub(super) fn new(code: &[u8], allocator: &dyn ExecutableAllocator) -> io::Result<Self> {
+        let mut region = allocator.allocate(code.len())?;
+        region.writable()[..code.len()].copy_from_slice(code);
+        region.make_executable()?;
+        Ok(ExecutableCode {
+            region,
+            len: code.len(),
+        })
+    }
+
+    /// The address of the code at the given offset.
+    pub(super) fn ptr(&self, offset: AssemblyOffset) -> *const u8 {
+        assert!(offset.0 < self.len);
+        self.region.as_ptr().wrapping_add(offset.0)
+    }
+}
+
+impl fmt::Debug for ExecutableCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExecutableCode")
+            .field("ptr", &self.region.as_ptr())
+            .field("len", &self.len)
+            .finish()
+    }
+}
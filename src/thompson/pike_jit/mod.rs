@@ -1,25 +1,30 @@
 use std::alloc::{self, Layout};
+use std::any::Any;
+use std::borrow::Cow;
 use std::error::Error;
 use std::fmt::Display;
-use std::{fmt, mem};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Instant;
+use std::{fmt, io, mem};
 
 use cg_impl_array::CGImplArray;
 use cg_impl_cow_array::CGImplCowArray;
 use cg_impl_register::CGImplReg;
 use cg_impl_tree::CGImplTree;
-use cg_implementation::CGImpl;
+use cg_implementation::{CGImpl, Clobbers};
 use dynasmrt::{
-    AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi, ExecutableBuffer, dynasm,
-    x64::Assembler,
+    AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi, VecAssembler, dynasm,
+    x64::X64Relocation,
 };
-use regex_syntax::Parser;
-use regex_syntax::hir::Look;
+use executable::{ExecutableAllocator, ExecutableCode, SystemAllocator};
+use regex_syntax::hir::Hir;
 
-use crate::regex::{Config, RegexImpl};
+use crate::regex::{CgStrategy, Config, RegexImpl, UnsupportedCgStrategy};
+use crate::sink::MatchError;
 use crate::thompson::bytecode::Instruction;
-use crate::util::{Char, Input, Span, find_prev_char};
+use crate::util::{BuildTimings, Char, Input, Span, Utf8ErrorAt, stable_hash};
 
-use super::bytecode::{Bytecode, Compiler};
+use super::bytecode::{Bytecode, Compiler, LookKind};
 
 /// Defines the platform and register aliases
 macro_rules! __ {
@@ -67,6 +72,7 @@ cst!(return_addr_offset, frame_ptr_offset!() + ptr_size!());
 cst!(span_end_offset, return_addr_offset!() + ptr_size!());
 cst!(return_on_accept, span_end_offset!() + ptr_size!());
 cst!(prev_char_offset, return_on_accept!() + ptr_size!());
+cst!(validate_utf8_offset, prev_char_offset!() + ptr_size!());
 cst!(result_offset, frame_ptr_offset!() - ptr_size!());
 cst!(result_len_offset, result_offset!() - ptr_size!());
 cst!(saved_rbx_offset, result_len_offset!() - ptr_size!());
@@ -75,65 +81,181 @@ cst!(saved_r13_offset, saved_r12_offset!() - ptr_size!());
 cst!(saved_r14_offset, saved_r13_offset!() - ptr_size!());
 cst!(saved_r15_offset, saved_r14_offset!() - ptr_size!());
 cst!(state_ptr_offset, saved_r15_offset!() - ptr_size!());
-cst!(last_saved_value_offset, state_ptr_offset!());
+// The geometry of the thread queues, see `PikeJIT::swap_queues`
+cst!(queue_mid1_offset, state_ptr_offset!() - ptr_size!());
+cst!(queue_mid2_offset, queue_mid1_offset!() - ptr_size!());
+cst!(queue_mask_offset, queue_mid2_offset!() - ptr_size!());
+cst!(queue_limit_offset, queue_mask_offset!() - ptr_size!());
+cst!(active_peak_offset, queue_limit_offset!() - ptr_size!());
+cst!(next_low_offset, active_peak_offset!() - ptr_size!());
+// The chars left until the next interrupt check, see
+// `State::interrupt_interval`
+cst!(interrupt_countdown_offset, next_low_offset!() - ptr_size!());
+cst!(last_saved_value_offset, interrupt_countdown_offset!());
 
 pub mod cg_impl_array;
 pub mod cg_impl_cow_array;
 pub mod cg_impl_register;
 pub mod cg_impl_tree;
 pub mod cg_implementation;
+pub mod executable;
+
+/// What the jitted code returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    NotFound,
+    Found,
+    /// Only when validating, the offset is in [`State::invalid_utf8_at`].
+    InvalidUtf8,
+    /// The interrupt check asked to stop, see
+    /// [`JittedRegex::exec_interruptible`].
+    Interrupted,
+}
 
+/// A regex compiled to machine code.
+///
+/// The code is never written once it is executable, see [`executable`]. If
+/// the process revokes the execution permission of its memory afterwards
+/// (e.g. with `mprotect`), running the code faults, which terminates the
+/// process: there is nothing the regex can do about it, but it never runs
+/// memory it didn't write.
 #[derive(Debug)]
 pub struct JittedRegex {
-    code: ExecutableBuffer,
+    code: ExecutableCode,
     start: AssemblyOffset,
     start_anchored: AssemblyOffset,
     register_count: usize,
     initial_mem_size: usize,
     visited_set_size: usize,
+    /// The queue capacity of the new states, see [`PikeJIT::queue_capacity`].
+    queue_capacity: usize,
+    maximum_len: Option<usize>,
+    cg_strategy: CgStrategy,
+    /// The same bytecode compiled with [`CgStrategy::Registers`], for the
+    /// searches which only need the overall match, see
+    /// [`crate::Builder::match_only_jit`].
+    match_only: Option<Box<JittedRegex>>,
+    /// See [`JittedRegex::code_hash`].
+    code_hash: u64,
+    /// See [`JittedRegex::build_timings`].
+    build_timings: BuildTimings,
 }
 
 /// State used by the jitted code for execution.
-/// It is basically a Vec<u8>, but since it is shared between the jitted
+/// It is basically a `Vec<u64>`, but since it is shared between the jitted
 /// code and the rust code we need something lower level, and repr(C)
+///
+/// Every size of the state is in words (u64), including `mem_len`, which the
+/// jitted code reads to know how much memory is available. Only the offsets
+/// used by the jitted code to address the memory are in bytes.
+///
+/// The thread queues are in a separate allocation, so that they can be
+/// resized without moving the rest of the memory, whose layout is fixed by
+/// the jitted code. See [`State::queue_capacity`].
 #[derive(Debug)]
 #[repr(C)]
 pub struct State {
     /// We use u64 to make sure things are aligned
     mem: *mut u64,
     mem_len: usize,
+    /// Where the validating searches found an invalid UTF-8 sequence, written
+    /// by the jitted code, see [`PikeJIT::compile_cold_paths`].
+    invalid_utf8_at: usize,
+    /// The memory of the two queues, see [`PikeJIT::swap_queues`]. Null
+    /// until the first search, and then allocated with
+    /// `State::queue_layout(queue_capacity)`.
+    queues: *mut u64,
+    queue_capacity: usize,
+    /// The offsets in their region of the highest thread pushed in the
+    /// active queue, and of the lowest top of the next queue checked by
+    /// [`PikeJIT::reserve_next`], written by the jitted code when it returns.
+    active_peak: usize,
+    next_low: usize,
+    queue_high_water: usize,
+    /// The deepest queue of all the searches since the tuning was enabled,
+    /// or 0 before the first one, see [`State::tune_queues`].
+    observed_queue_depth: usize,
+    queue_safety_factor: Option<usize>,
+    /// The number of chars between two calls of the interrupt check, loaded
+    /// by the jitted code when it starts. It is `usize::MAX` when there is
+    /// no check, which is never reached.
+    interrupt_interval: usize,
+    /// The `&dyn Fn() -> bool` checked during the search, or null. It only
+    /// points to the stack of [`JittedRegex::exec_interruptible`], for the
+    /// duration of the search.
+    interrupt: *const (),
+    /// The panic of the interrupt check, caught by [`check_interrupt`] since
+    /// it can't unwind through the jitted code, and resumed once it returns.
+    interrupt_panic: Option<Box<dyn Any + Send>>,
+    /// The Rust helpers called by the jitted code. It loads them from here
+    /// rather than embedding their address, which depends on where the crate
+    /// is loaded, so that the same pattern always compiles to the same code,
+    /// see [`JittedRegex::code_hash`].
+    double_mem_size: extern "sysv64" fn(*mut State) -> *mut State,
+    write_tree_results: cg_impl_tree::WriteResults,
+    check_interrupt: extern "sysv64" fn(*mut State) -> usize,
+    /// The arrays of [`CgStrategy::CowArray`] when the search returned,
+    /// written by the jitted code in debug builds, see
+    /// [`cg_impl_cow_array::check_ref_counts`].
+    cow_arrays: cg_impl_cow_array::ArraysAtReturn,
+    /// Last, so that the offsets of the other fields, and therefore the code
+    /// of the patterns without `\b`, stayed the same when it was added.
+    is_word_char: extern "sysv64" fn(u32) -> u32,
 }
 
 impl Drop for State {
     fn drop(&mut self) {
-        // SAFETY: The pointer is owned.
+        // SAFETY: The pointer is owned, and was allocated with this layout.
         unsafe {
-            alloc::dealloc(
-                self.mem as *mut u8,
-                Layout::array::<u64>(self.mem_len).unwrap(),
-            );
+            alloc::dealloc(self.mem as *mut u8, Self::layout(self.mem_len));
         }
+        self.free_queues();
     }
 }
 
 impl Clone for State {
     fn clone(&self) -> Self {
-        let layout = Layout::array::<u64>(self.mem_len).unwrap();
+        let layout = Self::layout(self.mem_len);
         // SAFETY:
-        // The allocation has nothing in particular.
+        // The layout is never zero-sized (see `State::layout`).
         // The memcopy works because both pointers are from two different
         // allocations (therefore non overlapping). And they were both allocated
         // with the same length.
         let mem = unsafe {
             let mem = alloc::alloc(layout) as *mut u64;
+            if mem.is_null() {
+                alloc::handle_alloc_error(layout);
+            }
             std::ptr::copy_nonoverlapping(self.mem, mem, self.mem_len);
             mem
         };
 
-        Self {
+        // The content of the queues does not outlive a search, only their
+        // capacity is kept.
+        let mut clone = Self {
             mem,
             mem_len: self.mem_len,
+            invalid_utf8_at: self.invalid_utf8_at,
+            queues: std::ptr::null_mut(),
+            queue_capacity: 0,
+            active_peak: 0,
+            next_low: 0,
+            queue_high_water: self.queue_high_water,
+            observed_queue_depth: self.observed_queue_depth,
+            queue_safety_factor: self.queue_safety_factor,
+            interrupt_interval: usize::MAX,
+            interrupt: std::ptr::null(),
+            interrupt_panic: None,
+            double_mem_size,
+            write_tree_results: cg_impl_tree::write_results,
+            check_interrupt,
+            cow_arrays: cg_impl_cow_array::ArraysAtReturn::default(),
+            is_word_char,
+        };
+        if self.queue_capacity > 0 {
+            clone.resize_queues(self.queue_capacity);
         }
+        clone
     }
 }
 
@@ -142,67 +264,270 @@ impl Clone for State {
 unsafe impl Send for State {}
 
 impl State {
-    /// Allocate a new State of the given size in bytes.
+    /// Allocate a new zeroed State of the given size in words. At least one
+    /// word is allocated. The queues are allocated by the first search.
     pub fn new(mem_len: usize) -> Self {
-        let layout = Layout::array::<u64>(mem_len).unwrap();
-        // SAFETY: That's just an allocation.
+        let mem_len = mem_len.max(1);
+        let layout = Self::layout(mem_len);
+        // SAFETY: The layout is not zero-sized.
         let mem = unsafe { alloc::alloc_zeroed(layout) as *mut u64 };
         if mem.is_null() {
-            panic!()
+            alloc::handle_alloc_error(layout);
+        }
+        Self {
+            mem,
+            mem_len,
+            invalid_utf8_at: 0,
+            queues: std::ptr::null_mut(),
+            queue_capacity: 0,
+            active_peak: 0,
+            next_low: 0,
+            queue_high_water: 0,
+            observed_queue_depth: 0,
+            queue_safety_factor: None,
+            interrupt_interval: usize::MAX,
+            interrupt: std::ptr::null(),
+            interrupt_panic: None,
+            double_mem_size,
+            write_tree_results: cg_impl_tree::write_results,
+            check_interrupt,
+            cow_arrays: cg_impl_cow_array::ArraysAtReturn::default(),
+            is_word_char,
         }
-        Self { mem, mem_len }
     }
 
-    /// Ensure the given state can hold the given number of bytes,
-    /// by reallocating if it is too small.
-    pub fn ensure_capacity(&mut self, mem_len: usize) {
-        if mem_len > self.mem_len {
-            let layout = Layout::array::<u64>(self.mem_len).unwrap();
-            let new_mem =
-                unsafe { alloc::realloc(self.mem as *mut u8, layout, mem_len * size_of::<u64>()) };
+    /// Offsets of the helpers, as addressed by the jitted code.
+    const DOUBLE_MEM_SIZE_OFFSET: i32 = mem::offset_of!(State, double_mem_size) as i32;
+    const WRITE_TREE_RESULTS_OFFSET: i32 = mem::offset_of!(State, write_tree_results) as i32;
+    const CHECK_INTERRUPT_OFFSET: i32 = mem::offset_of!(State, check_interrupt) as i32;
+    const INTERRUPT_INTERVAL_OFFSET: i32 = mem::offset_of!(State, interrupt_interval) as i32;
+    const COW_ARRAYS_OFFSET: i32 = mem::offset_of!(State, cow_arrays) as i32;
+    const IS_WORD_CHAR_OFFSET: i32 = mem::offset_of!(State, is_word_char) as i32;
+
+    /// The layout of an allocation of the given size in words. Panics if it
+    /// overflows, like a Vec would.
+    fn layout(mem_len: usize) -> Layout {
+        debug_assert!(mem_len > 0);
+        Layout::array::<u64>(mem_len).expect("JIT state too large")
+    }
+
+    /// The size of the state, in words.
+    pub fn mem_len(&self) -> usize {
+        self.mem_len
+    }
+
+    /// The memory of the state and of its queues, in bytes. The state only
+    /// grows, doubling when the captures need more room, see
+    /// [`State::double_size`], and the queues only shrink when tuned, see
+    /// [`State::tune_queues`].
+    pub fn memory_usage(&self) -> usize {
+        let queues = match self.queue_capacity {
+            0 => 0,
+            capacity => Self::queue_layout(capacity).size(),
+        };
+        Self::layout(self.mem_len).size() + queues
+    }
+
+    /// The memory of the state. Every word is initialized: memory is zeroed
+    /// when allocated, and then written by the jitted code.
+    pub fn as_slice(&self) -> &[u64] {
+        // SAFETY: The allocation holds mem_len initialized words.
+        unsafe { std::slice::from_raw_parts(self.mem, self.mem_len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [u64] {
+        // SAFETY: The allocation holds mem_len initialized words, and is
+        // borrowed mutably.
+        unsafe { std::slice::from_raw_parts_mut(self.mem, self.mem_len) }
+    }
 
+    /// Ensure the given state can hold the given number of words, by
+    /// reallocating if it is too small. The content is preserved, and the
+    /// new words are zeroed.
+    pub fn ensure_capacity(&mut self, mem_len: usize) {
+        if mem_len <= self.mem_len {
+            return;
+        }
+        let old_layout = Self::layout(self.mem_len);
+        let new_layout = Self::layout(mem_len);
+        // SAFETY: The pointer was allocated with old_layout, and the new size
+        // is not zero and does not overflow (checked by `State::layout`).
+        // The words past the old length are zeroed before being exposed.
+        unsafe {
+            let new_mem = alloc::realloc(self.mem as *mut u8, old_layout, new_layout.size());
             if new_mem.is_null() {
-                panic!()
+                alloc::handle_alloc_error(new_layout);
             }
-
-            self.mem = new_mem as *mut u64;
-            self.mem_len = mem_len;
+            let new_mem = new_mem as *mut u64;
+            new_mem
+                .add(self.mem_len)
+                .write_bytes(0, mem_len - self.mem_len);
+            self.mem = new_mem;
         }
+        self.mem_len = mem_len;
     }
 
+    /// Double the size of the state, see [`State::ensure_capacity`].
     pub fn double_size(&mut self) {
-        self.ensure_capacity(2 * self.mem_len);
+        let mem_len = self.mem_len.checked_mul(2).expect("JIT state too large");
+        self.ensure_capacity(mem_len);
+    }
+
+    /// The number of threads a queue can grow by during a step of the
+    /// search, or 0 before the first search. It is always one less than a
+    /// power of two. A search needing more stops, and is restarted with
+    /// queues twice as large.
+    pub fn queue_capacity(&self) -> usize {
+        self.queue_capacity
+    }
+
+    /// The deepest a queue got during the last search, in threads, which is
+    /// never more than the capacity.
+    pub fn queue_high_water(&self) -> usize {
+        self.queue_high_water
+    }
+
+    /// Reallocate the queues to the given capacity, rounded up to one less
+    /// than a power of two (and at least 1). Their content is lost, so this
+    /// must not be called during a search.
+    pub fn resize_queues(&mut self, capacity: usize) {
+        let capacity = capacity
+            .checked_add(1)
+            .and_then(usize::checked_next_power_of_two)
+            .expect("JIT state too large")
+            .max(2)
+            - 1;
+        self.free_queues();
+        let layout = Self::queue_layout(capacity);
+        // SAFETY: The layout is not zero-sized.
+        let queues = unsafe { alloc::alloc_zeroed(layout) as *mut u64 };
+        if queues.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        self.queues = queues;
+        self.queue_capacity = capacity;
+    }
+
+    /// Make the resets shrink the queues to the deepest queue of all the
+    /// searches from now on, times the safety factor (at least 1). This
+    /// saves memory for states reused by many searches, since the default
+    /// capacity is an upper bound most patterns never get close to. The
+    /// queues grow back when a search needs more.
+    pub fn tune_queues(&mut self, safety_factor: usize) {
+        self.queue_safety_factor = Some(safety_factor.max(1));
+        self.observed_queue_depth = 0;
+    }
+
+    /// The size of a region of the queues of the given capacity, in bytes.
+    /// Each queue starts in the middle of its region, and can grow by the
+    /// capacity in both directions. The first and last threads of the region
+    /// are never used, see [`PikeJIT::reserve_active`].
+    fn queue_region_size(capacity: usize) -> usize {
+        (capacity + 1) * 2 * PikeJIT::THREAD_SIZE_BYTE as usize
+    }
+
+    /// The layout of the queues of the given capacity: two regions, aligned
+    /// to their size, so that the offset of an address in its region is
+    /// given by its low bits.
+    fn queue_layout(capacity: usize) -> Layout {
+        let region = Self::queue_region_size(capacity);
+        Layout::from_size_align(region * 2, region).expect("JIT state too large")
+    }
+
+    fn free_queues(&mut self) {
+        if self.queues.is_null() {
+            return;
+        }
+        // SAFETY: The pointer is owned, and was allocated with this layout.
+        unsafe {
+            alloc::dealloc(
+                self.queues as *mut u8,
+                Self::queue_layout(self.queue_capacity),
+            );
+        }
+        self.queues = std::ptr::null_mut();
+        self.queue_capacity = 0;
+    }
+
+    /// Computes the high-water mark of the search which just returned, from
+    /// the bounds written by the jitted code.
+    fn record_queue_usage(&mut self) {
+        let thread = PikeJIT::THREAD_SIZE_BYTE as usize;
+        let start = Self::queue_region_size(self.queue_capacity) / 2;
+        let active = self.active_peak + thread - start;
+        let reserve = PikeJIT::NEXT_STEP_RESERVE as usize * thread;
+        let next = start + thread + reserve - self.next_low;
+        self.queue_high_water = active.max(next) / thread;
+        self.observed_queue_depth = self.observed_queue_depth.max(self.queue_high_water);
+    }
+
+    /// Called when a search overflowed the queues, before restarting it.
+    fn grow_queues(&mut self, pikejit: &JittedRegex) {
+        let capacity = self.queue_capacity;
+        // The search needed more than the capacity, the tuning must not
+        // shrink the queues back to it.
+        self.observed_queue_depth = self.observed_queue_depth.max(capacity + 1);
+        self.resize_queues(capacity.checked_mul(2).expect("JIT state too large"));
+        self.as_mut_slice()[..pikejit.visited_set_size].fill(0);
     }
 
     /// Reset the state for the given regex.
     /// Called before executing.
     pub fn reset(&mut self, pikejit: &JittedRegex) {
         self.ensure_capacity(pikejit.initial_mem_size);
-
-        // SAFETY: TODO
-        unsafe {
-            std::slice::from_raw_parts_mut(self.mem, pikejit.visited_set_size).fill(0);
+        let safety_factor = self
+            .queue_safety_factor
+            .filter(|_| self.observed_queue_depth > 0);
+        if let Some(safety_factor) = safety_factor {
+            let capacity = self.observed_queue_depth.saturating_mul(safety_factor);
+            if capacity < self.queue_capacity {
+                self.resize_queues(capacity);
+            }
+        }
+        if self.queue_capacity == 0 {
+            self.resize_queues(pikejit.queue_capacity);
         }
+        // The visited set is at the start of the memory, and is smaller than
+        // the initial size.
+        self.as_mut_slice()[..pikejit.visited_set_size].fill(0);
     }
 }
 
+/// Called by the jitted code when it runs out of memory.
 extern "sysv64" fn double_mem_size(state: *mut State) -> *mut State {
-    // SAFETY: TODO
-    unsafe {
-        let mem_len = (*state).mem_len;
-        let mem = (*state).mem;
-        let new_len = 2 * mem_len;
-        let layout = Layout::array::<u64>(mem_len).unwrap();
-        let new_mem = alloc::realloc(mem as *mut u8, layout, new_len * size_of::<u64>());
+    // SAFETY: The jitted code passes the state it was called with, which is
+    // borrowed mutably for the whole execution.
+    unsafe { (*state).double_size() };
+    state
+}
 
-        if new_mem.is_null() {
-            panic!()
+/// Called by the jitted code every [`State::interrupt_interval`] chars.
+/// Returns the number of chars until the next call, or 0 to stop the search,
+/// also when the check panics.
+extern "sysv64" fn check_interrupt(state: *mut State) -> usize {
+    // SAFETY: The jitted code passes the state it was called with, which is
+    // borrowed mutably for the whole execution.
+    let state = unsafe { &mut *state };
+    if state.interrupt.is_null() {
+        return state.interrupt_interval;
+    }
+    // SAFETY: A non-null pointer is set by `JittedRegex::exec_interruptible`
+    // to a reference which outlives the search.
+    let interrupt = unsafe { *(state.interrupt as *const &dyn Fn() -> bool) };
+    match panic::catch_unwind(AssertUnwindSafe(interrupt)) {
+        Ok(false) => state.interrupt_interval,
+        Ok(true) => 0,
+        Err(payload) => {
+            state.interrupt_panic = Some(payload);
+            0
         }
-
-        (*state).mem = new_mem as *mut u64;
-        (*state).mem_len = new_len;
     }
-    state
+}
+
+/// Called by the jitted code for the non-ASCII chars around `\b` and `\B`:
+/// 1 if the char is a word char, and 0 otherwise (also for the input bound).
+extern "sysv64" fn is_word_char(c: u32) -> u32 {
+    char::from_u32(c).is_some_and(regex_syntax::is_word_character) as u32
 }
 
 impl RegexImpl for JittedRegex {
@@ -222,74 +547,322 @@ impl RegexImpl for JittedRegex {
 }
 
 impl JittedRegex {
-    fn new_internal<CG: CGImpl>(
+    /// Builds the regex with the CG implementation of
+    /// `config.jit_cg_strategy`, see [`CgStrategy`] and
+    /// [`JittedRegex::auto_cg_strategy`] for [`CgStrategy::Auto`].
+    fn new_internal(
         pattern: &str,
         config: Config,
-    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
-        let hir = Parser::from(config.clone()).parse(pattern)?;
-        let capture_count = if config.cg {
-            hir.properties().explicit_captures_len() + 1
-        } else {
-            1
-        };
-        let bytecode = Compiler::compile(hir, config)?;
-        let s = if capture_count == 1 {
-            PikeJIT::compile::<CGImplReg>(&bytecode, capture_count)?
-        } else {
-            PikeJIT::compile::<CG>(&bytecode, capture_count)?
-        };
+        allocator: &dyn ExecutableAllocator,
+    ) -> Result<Self, crate::Error> {
+        let started = Instant::now();
+        let hir = Compiler::parse(pattern, &config)?;
+        let parse = started.elapsed();
+        let mut s = Self::from_hir(hir, config, allocator)?;
+        s.build_timings.parse = parse;
         Ok(s)
     }
 
-    pub fn new(
-        pattern: &str,
+    /// Compiles a pattern already parsed with the config, see
+    /// [`Compiler::parse`]. Like `new`, but the parse isn't timed.
+    pub(crate) fn from_hir(
+        hir: Hir,
         config: Config,
-    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
-        Self::new_internal::<CGImplTree>(pattern, config)
+        allocator: &dyn ExecutableAllocator,
+    ) -> Result<Self, crate::Error> {
+        let started = Instant::now();
+        let capture_count = Compiler::capture_count(&hir, &config);
+        let maximum_len = hir.properties().maximum_len();
+        let bytecode = Compiler::compile(hir, config.clone())?;
+        let compiled = Instant::now();
+        let mut s = Self::from_bytecode(&bytecode, capture_count, maximum_len, &config, allocator)?;
+        s.build_timings = BuildTimings {
+            compile: compiled - started,
+            assemble: compiled.elapsed(),
+            ..BuildTimings::default()
+        };
+        Ok(s)
     }
 
-    pub fn new_array(
-        pattern: &str,
-        config: Config,
-    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
-        let hir = Parser::from(config.clone()).parse(pattern)?;
-        let capture_count = if config.cg {
-            hir.properties().explicit_captures_len() + 1
-        } else {
-            1
+    /// Compiles the bytecode of a pattern like [`JittedRegex::new`], with the
+    /// CG strategy and the match-only code of the config. The lazy JIT
+    /// compiles the bytecode of its Pike VM this way, see
+    /// [`crate::Builder::lazy_jit`].
+    pub(crate) fn from_bytecode(
+        bytecode: &Bytecode,
+        capture_count: usize,
+        maximum_len: Option<usize>,
+        config: &Config,
+        allocator: &dyn ExecutableAllocator,
+    ) -> Result<Self, crate::Error> {
+        let strategy = match config.jit_cg_strategy {
+            CgStrategy::Auto => Self::auto_cg_strategy(bytecode, capture_count),
+            strategy => strategy,
+        };
+        let limit = config.size_limit;
+        let mut s = match strategy {
+            CgStrategy::Registers if capture_count > 1 => {
+                return Err(UnsupportedCgStrategy {
+                    strategy,
+                    group_len: capture_count,
+                }
+                .into());
+            }
+            CgStrategy::Registers => PikeJIT::compile_with_size_limit::<CGImplReg>(
+                bytecode,
+                capture_count,
+                limit,
+                allocator,
+            )?,
+            CgStrategy::Tree => PikeJIT::compile_with_size_limit::<CGImplTree>(
+                bytecode,
+                capture_count,
+                limit,
+                allocator,
+            )?,
+            CgStrategy::Array => PikeJIT::compile_with_size_limit::<CGImplArray>(
+                bytecode,
+                capture_count,
+                limit,
+                allocator,
+            )?,
+            CgStrategy::CowArray => PikeJIT::compile_with_size_limit::<CGImplCowArray>(
+                bytecode,
+                capture_count,
+                limit,
+                allocator,
+            )?,
+            CgStrategy::Auto => unreachable!("auto is resolved above"),
         };
-        let bytecode = Compiler::compile(hir, config)?;
-        // Force array for benchmark purposes
-        let s = PikeJIT::compile::<CGImplArray>(&bytecode, capture_count)?;
+        if config.match_only_jit && strategy != CgStrategy::Registers {
+            let match_only =
+                PikeJIT::compile_with_size_limit::<CGImplReg>(bytecode, 1, limit, allocator)?;
+            debug_assert_eq!(match_only.visited_set_size, s.visited_set_size);
+            s.match_only = Some(Box::new(match_only));
+        }
+        s.maximum_len = maximum_len;
+        s.cg_strategy = strategy;
         Ok(s)
     }
 
-    pub fn new_cow(
+    /// Above this many groups (including group 0), [`CgStrategy::Auto`] may
+    /// pick the arrays, see [`JittedRegex::auto_cg_strategy`].
+    const AUTO_ARRAY_MIN_GROUPS: usize = 13;
+
+    /// Above this many instructions, [`CgStrategy::Auto`] keeps the tree, see
+    /// [`JittedRegex::auto_cg_strategy`].
+    const AUTO_ARRAY_MAX_INSTRUCTIONS: usize = 256;
+
+    /// The CG implementation picked for [`CgStrategy::Auto`]:
+    ///
+    /// - [`CgStrategy::Registers`] without capture groups (only group 0),
+    ///   since the match then fits in two registers.
+    /// - [`CgStrategy::Array`] with many groups in a small pattern. The tree
+    ///   allocates a node for every capture written, and walks them back to
+    ///   report the captures, which is slower than copying the arrays once
+    ///   there are enough groups. A small pattern has few threads alive at
+    ///   once, so the copies stay cheap.
+    /// - [`CgStrategy::Tree`] otherwise, whose operations don't depend on
+    ///   the number of groups nor on the number of threads.
+    ///
+    /// [`CgStrategy::CowArray`] is never picked: it is slower than the
+    /// arrays in all the benchmarks with many groups, even when the threads
+    /// fork many times without writing any group (`groups_then_forks`),
+    /// which is where sharing the arrays should pay off.
+    ///
+    /// The thresholds come from the `cg_strategy` benchmarks, see the table of
+    /// [`CgStrategy`].
+    fn auto_cg_strategy(bytecode: &Bytecode, capture_count: usize) -> CgStrategy {
+        if capture_count == 1 {
+            CgStrategy::Registers
+        } else if capture_count >= Self::AUTO_ARRAY_MIN_GROUPS
+            && bytecode.instructions.len() <= Self::AUTO_ARRAY_MAX_INSTRUCTIONS
+        {
+            CgStrategy::Array
+        } else {
+            CgStrategy::Tree
+        }
+    }
+
+    pub fn new(pattern: &str, config: Config) -> Result<Self, crate::Error> {
+        Self::new_internal(pattern, config, &SystemAllocator)
+    }
+
+    /// Like `new`, with the executable memory provided by the given
+    /// allocator instead of the OS, see [`executable`].
+    pub fn with_allocator(
         pattern: &str,
         config: Config,
-    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
-        Self::new_internal::<CGImplCowArray>(pattern, config)
+        allocator: &dyn ExecutableAllocator,
+    ) -> Result<Self, crate::Error> {
+        Self::new_internal(pattern, config, allocator)
+    }
+
+    /// A hash of the jitted code, computed like [`Bytecode::content_hash`].
+    /// The code doesn't embed any address, so it only depends on the
+    /// pattern, the [`Config`], and the versions of the crate and of dynasm.
+    pub fn code_hash(&self) -> u64 {
+        self.code_hash
+    }
+
+    /// The CG implementation this regex was compiled with, never
+    /// [`CgStrategy::Auto`].
+    pub fn cg_strategy(&self) -> CgStrategy {
+        self.cg_strategy
+    }
+
+    /// How long parsing, compiling and assembling the pattern took. Only the
+    /// constructors from a pattern time the phases, [`PikeJIT::compile`]
+    /// leaves them at zero.
+    pub fn build_timings(&self) -> BuildTimings {
+        self.build_timings
     }
 
     fn exec_internal<'s>(&self, input: &Input<'s>, state: &mut State, result: &mut [Span]) -> bool {
         if !input.valid() {
             return false;
         }
-
-        // This assumption is used in the jitted code
-        assert!(result.len() <= self.capture_count());
-
-        state.ensure_capacity(self.initial_mem_size);
-
         let Input {
             subject,
             span,
             first_match,
             anchored,
+            ..
         } = input;
+        let prev_char = input.prev_char();
+        let outcome = self.call(
+            subject.as_bytes(),
+            *span,
+            *anchored,
+            *first_match,
+            prev_char,
+            false,
+            state,
+            result,
+        );
+        outcome == Outcome::Found
+    }
+
+    /// Searches bytes which may not be valid UTF-8, checking the chars as
+    /// they are decoded. The search stops at the first invalid sequence it
+    /// reads, see [`crate::Regex::find_validating`]. The state must have been
+    /// reset since its last use.
+    pub fn exec_validating(
+        &self,
+        haystack: &[u8],
+        state: &mut State,
+        captures: &mut [Span],
+    ) -> Result<bool, Utf8ErrorAt> {
+        let span = (0..haystack.len()).into();
+        let outcome = self.call(
+            haystack,
+            span,
+            false,
+            false,
+            Char::INPUT_BOUND,
+            true,
+            state,
+            captures,
+        );
+        match outcome {
+            Outcome::Found => Ok(true),
+            Outcome::NotFound => Ok(false),
+            Outcome::InvalidUtf8 => Err(Utf8ErrorAt {
+                offset: state.invalid_utf8_at,
+            }),
+            Outcome::Interrupted => unreachable!("The search has no interrupt check"),
+        }
+    }
+
+    /// Like a regular execution, but calls `interrupt` every `interval`
+    /// chars, and stops the search once it returns true, see
+    /// [`crate::Regex::find_interruptible`]. The state must have been reset
+    /// since its last use.
+    pub fn exec_interruptible(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+        interval: usize,
+        interrupt: &dyn Fn() -> bool,
+    ) -> Result<bool, MatchError> {
+        if !input.valid() {
+            return Ok(false);
+        }
+        let prev_char = input.prev_char();
+        state.interrupt_interval = interval.max(1);
+        state.interrupt = &interrupt as *const &dyn Fn() -> bool as *const ();
+        let outcome = self.call(
+            input.subject.as_bytes(),
+            input.span,
+            input.anchored,
+            input.first_match,
+            prev_char,
+            false,
+            state,
+            captures,
+        );
+        state.interrupt_interval = usize::MAX;
+        state.interrupt = std::ptr::null();
+        if let Some(payload) = state.interrupt_panic.take() {
+            panic::resume_unwind(payload);
+        }
+        match outcome {
+            Outcome::Found => Ok(true),
+            Outcome::NotFound => Ok(false),
+            Outcome::Interrupted => Err(MatchError::Interrupted),
+            Outcome::InvalidUtf8 => unreachable!("The subject is valid UTF-8"),
+        }
+    }
 
-        let prev_char = find_prev_char(subject, span.from);
+    /// Runs the jitted code. Unless `validate_utf8` is set, the subject must
+    /// be valid UTF-8, since it is decoded without any check.
+    #[allow(clippy::too_many_arguments)]
+    fn call(
+        &self,
+        subject: &[u8],
+        span: Span,
+        anchored: bool,
+        first_match: bool,
+        prev_char: Char,
+        validate_utf8: bool,
+        state: &mut State,
+        result: &mut [Span],
+    ) -> Outcome {
+        // The code for the overall match only runs with the same state: both
+        // were compiled from the same bytecode, so the visited set reset for
+        // this one is the one it uses, and the rest of the memory is
+        // initialized by each search.
+        if result.len() <= 1
+            && let Some(match_only) = &self.match_only
+        {
+            return match_only.call(
+                subject,
+                span,
+                anchored,
+                first_match,
+                prev_char,
+                validate_utf8,
+                state,
+                result,
+            );
+        }
+        // The jitted code writes all the groups it is asked for, so it must
+        // not be asked for more than it tracks. The others are cleared below.
+        let (result, rest) = result.split_at_mut(result.len().min(self.capture_count()));
+
+        state.ensure_capacity(self.initial_mem_size);
+        if state.queue_capacity == 0 {
+            state.resize_queues(self.queue_capacity);
+        }
 
+        // The jitted code always follows the System V calling convention,
+        // including on Windows. Rust supports `extern "sysv64"` on every
+        // x86_64 target, so the compiler emits the translation from the
+        // Microsoft x64 convention at this call site, and saves rsi/rdi
+        // (which are callee-saved on Windows, but not in System V) itself.
+        // The same holds for the Rust helpers called from the jitted code.
+        //
         // API:
         // subject: *const u8 -> rdi
         // subject_len: u64 -> rsi
@@ -300,6 +873,7 @@ impl JittedRegex {
         // to: u64 -> rbp+8
         // first_match: u64 -> rbp+16
         // prev_char: u32 -> rbp+24
+        // validate_utf8: u64 -> rbp+32
         type ExecSig = extern "sysv64" fn(
             *const u8,
             u64,
@@ -310,6 +884,7 @@ impl JittedRegex {
             u64,
             u64,
             Char,
+            u64,
         ) -> u8;
 
         let f: ExecSig = unsafe {
@@ -321,29 +896,60 @@ impl JittedRegex {
         };
         let result_len = (result.len() * 2) as u64;
 
-        f(
-            subject.as_ptr(),
-            subject.len() as u64,
-            // TODO: This works because of repr(C) but needs something nicer I think
-            result.as_mut_ptr(),
-            // TODO: This is the length in usize (yeah maybe we should use the array length instead)
-            result_len,
-            state as *mut State,
-            span.from as u64,
-            span.to as u64,
-            // TODO: Pass this as a bool instead
-            *first_match as u64,
-            prev_char,
-        ) > 0
+        loop {
+            let outcome = f(
+                subject.as_ptr(),
+                subject.len() as u64,
+                // TODO: This works because of repr(C) but needs something nicer I think
+                result.as_mut_ptr(),
+                // TODO: This is the length in usize (yeah maybe we should use the array length instead)
+                result_len,
+                state as *mut State,
+                span.from as u64,
+                span.to as u64,
+                // TODO: Pass this as a bool instead
+                first_match as u64,
+                prev_char,
+                validate_utf8 as u64,
+            );
+            if cfg!(debug_assertions) && self.cg_strategy == CgStrategy::CowArray {
+                cg_impl_cow_array::check_ref_counts(
+                    state,
+                    self.visited_set_size * ptr_size!(),
+                    self.register_count,
+                );
+            }
+            let outcome = match outcome {
+                0 => Outcome::NotFound,
+                1 => {
+                    rest.fill(Span::invalid());
+                    Outcome::Found
+                }
+                2 => Outcome::InvalidUtf8,
+                4 => Outcome::Interrupted,
+                // A queue was full, the search stopped without writing
+                // anything, and is restarted with larger queues.
+                _ => {
+                    state.grow_queues(self);
+                    continue;
+                }
+            };
+            state.record_queue_usage();
+            return outcome;
+        }
     }
 
     pub(crate) fn capture_count(&self) -> usize {
         self.register_count / 2
     }
+
+    pub(crate) fn maximum_len(&self) -> Option<usize> {
+        self.maximum_len
+    }
 }
 
 pub struct PikeJIT {
-    ops: Assembler,
+    ops: VecAssembler<X64Relocation>,
     instr_labels: Vec<DynamicLabel>,
     outlined_class_labels: Vec<DynamicLabel>,
     register_count: usize,
@@ -351,13 +957,67 @@ pub struct PikeJIT {
     next_iter: DynamicLabel,
     next_iter_with_search: DynamicLabel,
     fetch_next_char: DynamicLabel,
+    /// Out of line path freeing the current thread and stepping to the next
+    /// one, taken whenever a thread dies. See [`PikeJIT::compile_cold_paths`].
+    kill_thread: DynamicLabel,
+    /// Out of line path checking the multi-byte chars of the validating
+    /// searches, see [`PikeJIT::compile_cold_paths`].
+    validate_utf8: DynamicLabel,
+    /// Where the decoding of a multi-byte char starts, once it is checked.
+    decode_multibyte: DynamicLabel,
+    /// Out of line path stopping the search when a queue is full, see
+    /// [`PikeJIT::reserve_active`].
+    queue_overflow: DynamicLabel,
+    /// Out of line path calling the interrupt check, see
+    /// [`PikeJIT::compile_cold_paths`], and where it resumes the search.
+    check_interrupt: DynamicLabel,
+    interrupt_checked: DynamicLabel,
+    /// Out of line routine classifying the non-ASCII chars for `\b` and
+    /// `\B`, only compiled when the pattern uses them, see
+    /// [`PikeJIT::compile_is_word_char`].
+    unicode_word_char: Option<DynamicLabel>,
     write_reg_count: usize,
+    /// For every instruction starting a run of fused consume, the number of
+    /// instructions in that run. 0 for every other instructions.
+    literal_runs: Vec<usize>,
+    /// Same as `literal_runs`, for the runs of class consume, see
+    /// [`PikeJIT::class_runs`].
+    class_runs: Vec<usize>,
+    /// Entry points of the consume instructions inside a run, which are used
+    /// by threads for which the run was already checked.
+    unchecked_labels: Vec<Option<DynamicLabel>>,
+    /// The forks which choose their thread from the current char, see
+    /// [`PikeJIT::class_forks`].
+    class_forks: Vec<Option<ClassFork>>,
+}
+
+/// A fork between a single consume instruction (of a char or a class) and
+/// an exit which can't consume the same chars, see [`PikeJIT::class_forks`].
+#[derive(Debug, Clone, Copy)]
+struct ClassFork {
+    consume: usize,
+    exit: usize,
 }
 
 #[derive(Debug)]
 pub enum CompileError {
-    FailedToCreateAssembler,
     FailedToFinalizeOps,
+    /// The platform didn't provide executable memory for the code, see
+    /// [`executable`].
+    ExecutableMemoryDenied(io::Error),
+    /// The memory needed by the pattern can't be addressed with 32-bit
+    /// displacements, i.e. it is over `i32::MAX` bytes.
+    PatternTooLarge,
+    /// The cut regions of possessive repetitions (see
+    /// [`Instruction::CutEnd`]) are only implemented by the Pike VM.
+    ContainsPossessiveRepetition,
+    /// The code emitted reached `size` bytes, over the size limit of the
+    /// configuration, see [`crate::Builder::size_limit`]. Reported as a
+    /// [`crate::Error::CompileLimitExceeded`] by the builds of the regexes.
+    CompiledProgramTooLarge {
+        size: usize,
+        limit: usize,
+    },
 }
 
 impl Error for CompileError {}
@@ -365,25 +1025,86 @@ impl Error for CompileError {}
 impl Display for CompileError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CompileError::FailedToCreateAssembler => {
-                write!(f, "Failed to create assembler for the current platform")
-            }
             CompileError::FailedToFinalizeOps => write!(f, "Failed to finalize ops"),
+            CompileError::ExecutableMemoryDenied(err) => write!(
+                f,
+                "The platform denied executable memory for the JIT ({err}). Hardened \
+                 systems (e.g. SELinux with deny_execmem, PaX MPROTECT, or the macOS \
+                 hardened runtime without the com.apple.security.cs.allow-jit \
+                 entitlement) must allow JIT code for this program, otherwise use the \
+                 Pike VM"
+            ),
+            CompileError::PatternTooLarge => {
+                write!(f, "Pattern is too large to be compiled by the JIT")
+            }
+            CompileError::ContainsPossessiveRepetition => {
+                write!(f, "Possessive repetitions are not supported by the JIT")
+            }
+            CompileError::CompiledProgramTooLarge { size, limit } => write!(
+                f,
+                "The jitted code takes {size} bytes, over the size limit of {limit}"
+            ),
         }
     }
 }
 
+/// The registers changed by the helper the JIT emits for an outlined class:
+/// it returns 0 in `reg1` if the current char is in the class.
+pub const OUTLINED_CLASS_CLOBBERS: Clobbers = Clobbers::REG1;
+
+/// The value of the scratch registers after a hook which may change them, in
+/// debug builds, see [`PikeJIT::cg_hook`].
+const CLOBBERED: i64 = 0x0bad_c0de_0bad_c0de;
+
 impl PikeJIT {
     /// Each threads are 2 words long, one word for the pc, and one for
     /// the cg-data (often a pointer).
     const THREAD_SIZE: i32 = 2;
-    const THREAD_SIZE_BYTE: i32 = 16;
+    const THREAD_SIZE_BYTE: i32 = Self::THREAD_SIZE * ptr_size!();
+
+    /// The number of threads pushed on the next queue at the end of every
+    /// step, see [`PikeJIT::reserve_next`].
+    const NEXT_STEP_RESERVE: i32 = 2;
+
+    /// The generated code addresses the visited set, the queues and the
+    /// initial CG region with 32-bit displacements, so their size is bounded.
+    /// Note that this only depends on the pattern: input positions and
+    /// lengths are always kept in 64-bit registers, and the CG memory that
+    /// grows with the input (see `CGImplTree`) is addressed through them.
+    const MAX_MEM_SIZE_BYTES: usize = i32::MAX as usize;
 
+    /// Compiles the bytecode, and places the code in memory provided by the
+    /// allocator, see [`executable`].
     pub fn compile<CG: CGImpl>(
         bytecode: &Bytecode,
         capture_count: usize,
+        allocator: &dyn ExecutableAllocator,
+    ) -> Result<JittedRegex, CompileError> {
+        Self::compile_with_size_limit::<CG>(bytecode, capture_count, usize::MAX, allocator)
+    }
+
+    /// Like [`PikeJIT::compile`], but stops with
+    /// [`CompileError::CompiledProgramTooLarge`] as soon as the code takes
+    /// more than `size_limit` bytes, checked after each instruction.
+    pub fn compile_with_size_limit<CG: CGImpl>(
+        bytecode: &Bytecode,
+        capture_count: usize,
+        size_limit: usize,
+        allocator: &dyn ExecutableAllocator,
     ) -> Result<JittedRegex, CompileError> {
-        let mut ops = Assembler::new().map_err(|_| CompileError::FailedToCreateAssembler)?;
+        let has_cut = |instruction: &Instruction| {
+            matches!(
+                instruction,
+                Instruction::CutBegin(_) | Instruction::CutEnd(_)
+            )
+        };
+        if bytecode.instructions.iter().any(has_cut) {
+            return Err(CompileError::ContainsPossessiveRepetition);
+        }
+        // The range checks jump to the failure on the first range past the
+        // char.
+        debug_assert!(bytecode.classes_are_normalized(), "unnormalized class");
+        let mut ops = VecAssembler::new(0);
         let instr_labels = Vec::from_iter(
             bytecode
                 .instructions
@@ -400,6 +1121,28 @@ impl PikeJIT {
         let next_iter = ops.new_dynamic_label();
         let next_iter_with_search = ops.new_dynamic_label();
         let fetch_next_char = ops.new_dynamic_label();
+        let kill_thread = ops.new_dynamic_label();
+        let validate_utf8 = ops.new_dynamic_label();
+        let decode_multibyte = ops.new_dynamic_label();
+        let queue_overflow = ops.new_dynamic_label();
+        let check_interrupt = ops.new_dynamic_label();
+        let interrupt_checked = ops.new_dynamic_label();
+        let unicode_word_char = bytecode
+            .instructions
+            .iter()
+            .any(|instruction| {
+                matches!(instruction, Instruction::Assertion(look) if look.is_unicode_word())
+            })
+            .then(|| ops.new_dynamic_label());
+        let literal_runs = Self::literal_runs(bytecode);
+        let class_runs = Self::class_runs(bytecode);
+        let runs = literal_runs.iter().zip(&class_runs).map(|(a, b)| a.max(b));
+        let mut unchecked_labels = vec![None; bytecode.instructions.len()];
+        for (i, len) in runs.enumerate().filter(|(_, len)| **len > 1) {
+            for label in &mut unchecked_labels[(i + 1)..(i + len)] {
+                *label = Some(ops.new_dynamic_label());
+            }
+        }
         let mut compiler = Self {
             ops,
             instr_labels,
@@ -408,17 +1151,236 @@ impl PikeJIT {
             next_iter,
             next_iter_with_search,
             fetch_next_char,
+            kill_thread,
+            validate_utf8,
+            decode_multibyte,
+            queue_overflow,
+            check_interrupt,
+            interrupt_checked,
+            unicode_word_char,
             outlined_class_labels,
             write_reg_count: bytecode.write_reg_count,
+            literal_runs,
+            class_runs,
+            unchecked_labels,
+            class_forks: Self::class_forks(bytecode),
         };
+        if compiler.initial_mem_size::<CG>() * ptr_size!() > Self::MAX_MEM_SIZE_BYTES {
+            return Err(CompileError::PatternTooLarge);
+        }
         for (i, class) in bytecode.outlined_classes.iter().enumerate() {
             compiler.compile_outlined_class(i, class);
         }
-        for (i, instr) in bytecode.instructions.iter().enumerate() {
-            let barrier = bytecode.barriers[i];
-            compiler.compile_instruction::<CG>(i, instr, barrier);
+        for i in 0..bytecode.instructions.len() {
+            compiler.compile_instruction::<CG>(bytecode, i);
+            compiler.check_size_limit(size_limit)?;
+        }
+        compiler.compile_cold_paths::<CG>();
+        compiler.check_size_limit(size_limit)?;
+        compiler.assemble::<CG>(allocator)
+    }
+
+    /// See [`PikeJIT::compile_with_size_limit`].
+    fn check_size_limit(&self, limit: usize) -> Result<(), CompileError> {
+        let size = self.ops.offset().0;
+        if size > limit {
+            return Err(CompileError::CompiledProgramTooLarge { size, limit });
+        }
+        Ok(())
+    }
+
+    /// Maximum number of bytes checked at once by a fused run of consume.
+    /// Longer runs are split, this bounds the size of the generated code.
+    const MAX_LITERAL_RUN_BYTES: usize = 64;
+
+    /// Find the runs of consume instructions which can be fused together.
+    ///
+    /// A literal compiles to a sequence of consume, each of them going through
+    /// the thread queues. Since there is no branching within such sequence, a
+    /// thread entering it can check all the following characters at once, and
+    /// die immediately if one of them does not match. The thread then goes
+    /// through the rest of the run without re-checking the characters. The
+    /// lockstep of the threads is preserved, which is required for the
+    /// priorities to be correct. Only instructions without barriers can be
+    /// part of a run (except the first one), since their visited-set check
+    /// can't be skipped, which the [`Bytecode::consume_runs`] of the compiler
+    /// already account for.
+    fn literal_runs(bytecode: &Bytecode) -> Vec<usize> {
+        let instructions = &bytecode.instructions;
+        let mut runs = vec![0; instructions.len()];
+        let mut i = 0;
+        while i < instructions.len() {
+            let Instruction::Consume(c) = instructions[i] else {
+                i += 1;
+                continue;
+            };
+            let consumes = bytecode.consume_run(i);
+            let mut len = 1;
+            let mut bytes = c.len_utf8();
+            while len < consumes
+                && let Instruction::Consume(next) = instructions[i + len]
+            {
+                if bytes + next.len_utf8() > Self::MAX_LITERAL_RUN_BYTES {
+                    break;
+                }
+                bytes += next.len_utf8();
+                len += 1;
+            }
+            if len > 1 {
+                runs[i] = len;
+            }
+            i += len;
+        }
+        runs
+    }
+
+    /// Find the runs of class consume which can be checked at once, like the
+    /// runs of [`PikeJIT::literal_runs`]. They come from the repetitions of a
+    /// class, e.g. the first eight of `[a-z]{8,64}`.
+    ///
+    /// Only the runs of an ASCII class are checked at once: their chars are
+    /// single bytes, so the thread entering the run scans the following bytes
+    /// in a loop, without decoding them, and dies if the run is cut short.
+    fn class_runs(bytecode: &Bytecode) -> Vec<usize> {
+        let instructions = &bytecode.instructions;
+        let mut runs = vec![0; instructions.len()];
+        let mut i = 0;
+        while i < instructions.len() {
+            let is_ascii = Self::consumed_class(bytecode, i)
+                .is_some_and(|class| class.iter().all(|(_, to)| u32::from(*to) < 0x80));
+            if !is_ascii || matches!(instructions[i], Instruction::Consume(_)) {
+                i += 1;
+                continue;
+            }
+            let consumes = bytecode.consume_run(i);
+            let mut len = 1;
+            while len < consumes && instructions[i + len] == instructions[i] {
+                len += 1;
+            }
+            if len > 1 {
+                runs[i] = len;
+            }
+            i += len;
+        }
+        runs
+    }
+
+    /// The maximum number of instructions explored from the exit of a fork
+    /// by [`PikeJIT::class_forks`].
+    const MAX_CLASS_FORK_EXIT: usize = 64;
+
+    /// Find the forks between a single consume and an exit which can only
+    /// consume other chars, such as the ones of `[a-z]{0,8}:` or `[a-z]*:`
+    /// (greedy or lazy), which choose their thread from the current char
+    /// instead of forking.
+    ///
+    /// If the current char is in the class, the thread taking the exit would
+    /// die on it, so the fork directly takes the consume. Otherwise, the
+    /// thread consuming it would die, so the fork directly takes the exit.
+    /// Either way, a single thread goes on, without going through the queue:
+    /// in a run of the class, only the thread consuming it is left, which
+    /// takes the exit at the end of the run. Since the dropped thread dies at
+    /// the current char anyway, whatever its priority, the matches are the
+    /// same.
+    ///
+    /// An exit which may accept or check an assertion first is always kept,
+    /// e.g. the one of `[a-z]{0,8}` at the end of a pattern: there, the fork
+    /// has to push both threads.
+    fn class_forks(bytecode: &Bytecode) -> Vec<Option<ClassFork>> {
+        let is_consume = |pc: usize| Self::consumed_class(bytecode, pc).is_some();
+        let class_fork = |consume: usize, exit: usize| {
+            let class = Self::consumed_class(bytecode, consume)?;
+            let exit_dies = Self::exit_chars(bytecode, exit)
+                .is_some_and(|chars| !Self::intersects(&class, &chars));
+            exit_dies.then_some(ClassFork { consume, exit })
+        };
+        bytecode
+            .instructions
+            .iter()
+            .map(|instruction| match *instruction {
+                Instruction::Fork2(a, b) if a != b && is_consume(a) => class_fork(a, b),
+                Instruction::Fork2(a, b) if a != b && is_consume(b) => class_fork(b, a),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The chars consumed by the instruction, if it consumes one.
+    fn consumed_class(bytecode: &Bytecode, pc: usize) -> Option<Cow<'_, [(Char, Char)]>> {
+        match &bytecode.instructions[pc] {
+            Instruction::Consume(c) => Some(Cow::Owned(vec![(*c, *c)])),
+            Instruction::ConsumeClass(class) => Some(Cow::Borrowed(class)),
+            Instruction::ConsumeOutlined(id) => {
+                Some(Cow::Borrowed(&*bytecode.outlined_classes[*id]))
+            }
+            _ => None,
+        }
+    }
+
+    /// The chars which a thread at `pc` may consume first, sorted. `None` if
+    /// it may do anything else first, such as accepting or checking an
+    /// assertion, or if that is too long to find out.
+    fn exit_chars(bytecode: &Bytecode, pc: usize) -> Option<Vec<(Char, Char)>> {
+        let mut chars = Vec::new();
+        let mut visited = Vec::new();
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            if visited.contains(&pc) {
+                continue;
+            }
+            if visited.len() == Self::MAX_CLASS_FORK_EXIT {
+                return None;
+            }
+            visited.push(pc);
+            match &bytecode.instructions[pc] {
+                Instruction::Fork2(a, b) => stack.extend([*a, *b]),
+                Instruction::ForkN(targets) => stack.extend(targets.iter()),
+                Instruction::Jmp(target) => stack.push(*target),
+                Instruction::WriteReg(_) => stack.push(pc + 1),
+                Instruction::Consume(_)
+                | Instruction::ConsumeClass(_)
+                | Instruction::ConsumeOutlined(_) => {
+                    chars.extend_from_slice(&Self::consumed_class(bytecode, pc)?);
+                }
+                Instruction::Accept(_)
+                | Instruction::Assertion(_)
+                | Instruction::CutBegin(_)
+                | Instruction::CutEnd(_) => return None,
+            }
+        }
+        chars.sort_unstable();
+        Some(chars)
+    }
+
+    /// Whether two sorted lists of ranges have a char in common.
+    fn intersects(a: &[(Char, Char)], b: &[(Char, Char)]) -> bool {
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i].1 < b[j].0 {
+                i += 1;
+            } else if b[j].1 < a[i].0 {
+                j += 1;
+            } else {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Emits the code of a hook of the CG implementation, which changes the
+    /// given registers, see [`cg_implementation`]. In debug builds, the
+    /// scratch registers among them are then overwritten, so that the code
+    /// relying on them surviving the hook fails the tests.
+    fn cg_hook(&mut self, clobbers: Clobbers, hook: impl FnOnce(&mut Self)) {
+        hook(self);
+        if cfg!(debug_assertions) {
+            if clobbers.contains(Clobbers::REG1) {
+                __!(self.ops, mov reg1, QWORD CLOBBERED)
+            }
+            if clobbers.contains(Clobbers::REG2) {
+                __!(self.ops, mov reg2, QWORD CLOBBERED)
+            }
         }
-        compiler.assemble::<CG>()
     }
 
     fn set_and_align_sp(&mut self, value: i32) {
@@ -431,25 +1393,33 @@ impl PikeJIT {
         }
     }
 
-    fn assemble<CG: CGImpl>(mut self) -> Result<JittedRegex, CompileError> {
+    fn assemble<CG: CGImpl>(
+        mut self,
+        allocator: &dyn ExecutableAllocator,
+    ) -> Result<JittedRegex, CompileError> {
         let label0 = self.instr_labels[0];
         let start;
         let start_anchored;
         __!(self.ops,
          ; start_anchored = self.ops.offset()
          ;; self.prologue::<CG>()
+         ;; self.reserve_active(2)
          ;; self.push_active_sentinel(self.next_iter)
-         ;; CG::alloc_thread(&mut self)
-         ;; CG::write_reg(&mut self, 0)
+         ;; self.cg_hook(CG::ALLOC_THREAD_CLOBBERS, CG::alloc_thread)
+         ;; self.cg_hook(CG::WRITE_REG_CLOBBERS, |jit| CG::write_reg(jit, 0))
          ;; self.push_active(label0)
          ; jmp =>self.fetch_next_char
          ;; start = self.ops.offset()
          ;; self.prologue::<CG>()
+         ;; self.reserve_active(2)
          ;; self.push_active_sentinel(self.next_iter_with_search)
-         ;; CG::alloc_thread(&mut self)
-         ;; CG::write_reg(&mut self, 0)
+         ;; self.cg_hook(CG::ALLOC_THREAD_CLOBBERS, CG::alloc_thread)
+         ;; self.cg_hook(CG::WRITE_REG_CLOBBERS, |jit| CG::write_reg(jit, 0))
          ;; self.push_active(label0)
          ; =>self.fetch_next_char
+         ; sub QWORD [rbp + interrupt_countdown_offset!()], 1
+         ; jz =>self.check_interrupt
+         ; =>self.interrupt_checked
          ;; CG::at_fetch_next_char(&mut self)
          ; mov prev_char, curr_char
          ; cmp input_len, input_pos
@@ -476,17 +1446,11 @@ impl PikeJIT {
          ; cmp input_pos, span_end
          ; je >return_result
          ; add input_pos, input_inc
-         ;; CG::alloc_thread(&mut self)
-         ;; CG::write_reg(&mut self, 0)
+         ;; self.cg_hook(CG::ALLOC_THREAD_CLOBBERS, CG::alloc_thread)
+         ;; self.cg_hook(CG::WRITE_REG_CLOBBERS, |jit| CG::write_reg(jit, 0))
          ;; self.push_next(label0)
          ;; self.push_next_sentinel(self.next_iter_with_search)
-         ; mov reg1, QWORD ((self.queue_start() + ((3*ptr_size!()*self.queue_size())/2)) as i64)
-         ; mov reg2, QWORD ((self.queue_start() + ((ptr_size!()*self.queue_size())/2)) as i64)
-         ; cmp curr_top, next_tail
-         ; mov next_tail, reg2
-         ; mov curr_top, reg1
-         ; cmovg next_tail, reg1
-         ; cmovg curr_top, reg2
+         ;; self.swap_queues()
          ; jmp =>self.fetch_next_char
          // Same as above, but does not spawn a new thread. Used when doing
          // anchored searches, or when an accpeting state has already been
@@ -495,20 +1459,12 @@ impl PikeJIT {
          ; cmp input_pos, span_end
          ; je >return_result
          ; add input_pos, input_inc
-         ; mov reg1, QWORD ((self.queue_start() + ((3*ptr_size!()*self.queue_size())/2)) as i64)
-         ; mov reg2, QWORD ((self.queue_start() + ((ptr_size!()*self.queue_size())/2)) as i64)
-         ; cmp next_tail, reg1
+         ; cmp next_tail, [rbp + queue_mid1_offset!()]
          ; je >return_result
-         ; cmp next_tail, reg2
+         ; cmp next_tail, [rbp + queue_mid2_offset!()]
          ; je >return_result
          ;; self.push_next_sentinel(self.next_iter)
-         ; mov reg1, QWORD ((self.queue_start() + ((3*ptr_size!()*self.queue_size())/2)) as i64)
-         ; mov reg2, QWORD ((self.queue_start() + ((ptr_size!()*self.queue_size())/2)) as i64)
-         ; cmp curr_top, next_tail
-         ; mov next_tail, reg2
-         ; mov curr_top, reg1
-         ; cmovg next_tail, reg1
-         ; cmovg curr_top, reg2
+         ;; self.swap_queues()
          ; jmp =>self.fetch_next_char
          ; return_result:
          ;; CG::return_result(&mut self)
@@ -517,7 +1473,14 @@ impl PikeJIT {
 
         let visited_set_size = self.visited_set_size();
         let initial_mem_size = self.initial_mem_size::<CG>();
-        let code = self.ops.finalize().unwrap();
+        let queue_capacity = self.queue_capacity();
+        let code = self
+            .ops
+            .finalize()
+            .map_err(|_| CompileError::FailedToFinalizeOps)?;
+        let code_hash = stable_hash(code.iter().copied());
+        let code =
+            ExecutableCode::new(&code, allocator).map_err(CompileError::ExecutableMemoryDenied)?;
 
         Ok(JittedRegex {
             code,
@@ -526,22 +1489,67 @@ impl PikeJIT {
             register_count: self.register_count,
             visited_set_size,
             initial_mem_size,
+            queue_capacity,
+            maximum_len: None,
+            cg_strategy: CgStrategy::Auto,
+            match_only: None,
+            code_hash,
+            build_timings: BuildTimings::default(),
         })
     }
 
     fn pop_active(&mut self) {
         __!(self.ops,
           sub curr_top, Self::THREAD_SIZE_BYTE
-        ; mov curr_thd_data, QWORD [mem + curr_top]
-        ; mov reg1, QWORD [mem + curr_top + 8]
+        ; mov curr_thd_data, QWORD [curr_top]
+        ; mov reg1, QWORD [curr_top + 8]
+        )
+    }
+
+    /// Check that the given number of threads can be pushed on the active
+    /// queue, or stop the search. Reaching higher in the region than ever
+    /// before records the new high-water mark. The last thread of a region
+    /// is never used, so that the top of the active queue, which is right
+    /// past its last thread, is still in the region.
+    fn reserve_active(&mut self, count: i32) {
+        __!(self.ops,
+          mov reg1, curr_top
+        ; and reg1, [rbp + queue_mask_offset!()]
+        ; add reg1, (count - 1) * Self::THREAD_SIZE_BYTE
+        ; cmp reg1, [rbp + active_peak_offset!()]
+        ; jbe >fits
+        ; mov [rbp + active_peak_offset!()], reg1
+        ; cmp reg1, [rbp + queue_limit_offset!()]
+        ; ja =>self.queue_overflow
+        ; fits:
+        )
+    }
+
+    /// Check that a thread can be pushed on the next queue, see
+    /// [`PikeJIT::reserve_active`]. Room is also left for the threads pushed
+    /// at the end of every step (the sentinel, and the thread starting the
+    /// next search), which therefore need no check. Like the last one, the
+    /// first thread of a region is never used.
+    fn reserve_next(&mut self) {
+        __!(self.ops,
+          mov reg1, next_tail
+        ; and reg1, [rbp + queue_mask_offset!()]
+        ; cmp reg1, [rbp + next_low_offset!()]
+        ; jae >fits
+        ; mov [rbp + next_low_offset!()], reg1
+        ; cmp reg1, (Self::NEXT_STEP_RESERVE + 1) * Self::THREAD_SIZE_BYTE
+        ; jbe =>self.queue_overflow
+        ; fits:
         )
     }
 
+    /// Push a thread on the active queue, which must have been reserved, see
+    /// [`PikeJIT::reserve_active`].
     fn push_active(&mut self, label: DynamicLabel) {
         __!(self.ops,
-          mov QWORD [mem + curr_top], curr_thd_data
+          mov QWORD [curr_top], curr_thd_data
         ; lea reg1, [=>label]
-        ; mov QWORD [mem + curr_top + 8], reg1
+        ; mov QWORD [curr_top + 8], reg1
         ; add curr_top, Self::THREAD_SIZE_BYTE
         )
     }
@@ -560,17 +1568,46 @@ impl PikeJIT {
         self.push_next(label);
     }
 
+    /// Push a thread on the next queue, which must have been reserved, see
+    /// [`PikeJIT::reserve_next`].
     fn push_next(&mut self, label: DynamicLabel) {
         __!(self.ops,
           sub next_tail, Self::THREAD_SIZE_BYTE
-        ; mov QWORD [mem + next_tail], curr_thd_data
+        ; mov QWORD [next_tail], curr_thd_data
         ; lea reg1, [=>label]
-        ; mov QWORD [mem + next_tail + 8], reg1
+        ; mov QWORD [next_tail + 8], reg1
+        )
+    }
+
+    /* The queues are in their own allocation (see `State::queues`), made of
+     * two regions of 2 * (capacity + 1) threads:
+     * |---------region_1---------|---------region_2---------|
+     *              ^mid1                      ^mid2
+     * Each queue starts at the middle of its region. The next queue grows
+     * downward from there, and then becomes the active queue, which is
+     * popped downward, while the forks push upward again. Since the pops
+     * free slots, the active queue only needs the capacity above the middle.
+     *
+     * The regions are aligned to their size, so the bounds of the queues are
+     * checked on the offsets in the regions, which are the same for both.
+     */
+
+    /// Make the next queue the active one, and the old active one (which is
+    /// empty) the new next queue.
+    fn swap_queues(&mut self) {
+        __!(self.ops,
+          mov reg1, [rbp + queue_mid2_offset!()]
+        ; mov reg2, [rbp + queue_mid1_offset!()]
+        ; cmp curr_top, next_tail
+        ; mov next_tail, reg2
+        ; mov curr_top, reg1
+        ; cmovg next_tail, reg1
+        ; cmovg curr_top, reg2
         )
     }
 
     /* The overall shape of the memory is the following:
-     * |---------visited_set--------|-------queue_1------|-----queue2------|--------cg_space--------|
+     * |---------visited_set--------|--------cg_space--------|
      */
 
     /// Returns the size in words (okok in x64 words are 16bit, but here we mean 64bit)
@@ -582,25 +1619,20 @@ impl PikeJIT {
         self.instr_labels.len()
     }
 
-    /// Starting offset of the queues region (in *bytes*)
-    fn queue_start(&self) -> usize {
-        self.visited_set_size() * ptr_size!()
-    }
-
-    fn queue_size(&self) -> usize {
-        (self.instr_labels.len() * 2 + 1) * Self::THREAD_SIZE as usize
-    }
-
-    fn total_queue_size(&self) -> usize {
-        self.queue_size() * 2
+    /// The default capacity of the queues, in threads. A step pushes at
+    /// most one thread per consume instruction on the next queue, plus the
+    /// sentinel and the thread of the next search. The forks pushing on the
+    /// active queue rarely get further. When they do, the queues grow.
+    fn queue_capacity(&self) -> usize {
+        self.instr_labels.len() + 2
     }
 
     fn initial_mem_size<CG: CGImpl>(&self) -> usize {
-        self.visited_set_size() + self.total_queue_size() + CG::init_mem_size(self)
+        self.visited_set_size() + CG::init_mem_size(self)
     }
 
     fn cg_mem_start(&self) -> usize {
-        (self.visited_set_size() + self.total_queue_size()) * ptr_size!()
+        self.visited_set_size() * ptr_size!()
     }
 
     fn max_concurrent_threads(&self) -> usize {
@@ -608,11 +1640,10 @@ impl PikeJIT {
         3 * self.instr_labels.len()
     }
 
-    #[allow(clippy::fn_to_numeric_cast)]
     fn grow_memory(&mut self) {
         __!(self.ops,
-          mov rax, QWORD double_mem_size as i64
-        ; call rax
+          mov rax, [rbp + state_ptr_offset!()]
+        ; call QWORD [rax + State::DOUBLE_MEM_SIZE_OFFSET]
         )
     }
 
@@ -630,28 +1661,53 @@ impl PikeJIT {
         ; push r13
         ; push r14
         ; push r15
+        // Okay so push immediate does not support 64bits value with this
+        // library. Therefore we do it manually. This must happen before
+        // writing the state pointer and the bounds of the queues: the
+        // Microsoft x64 ABI has no red zone, so nothing may be stored below
+        // rsp.
+        ; sub rsp, (saved_r15_offset!() - last_saved_value_offset!())
         // Initialize mem, input_pos, input_end, state_ptr and prev_char
         ; mov [rbp + state_ptr_offset!()], r8
-        // State is { mem: *mut u64, size: usize }, and is repr(c)
+        // State is { mem: *mut u64, size: usize, .. }, and is repr(c)
         ; mov input, rdi
         ; mov input_len, rsi
+        ; mov reg1, [r8 + State::INTERRUPT_INTERVAL_OFFSET]
+        ; mov [rbp + interrupt_countdown_offset!()], reg1
         ; mov mem, [r8]
         ; mov input_pos, r9
         ; mov span_end, [rbp + span_end_offset!()]
         // We set curr_char, because the first thing we do after the prologue is to
         // swap curr_char with prev_char, and fetch the next char in curr_char
         ; mov curr_char, [rbp + prev_char_offset!()]
-        // Okay so push immediate does not support 64bits value with this
-        // library. Therefore we do it manually.
-        ; sub rsp, (4*ptr_size!())
-        // Initialize curr_top, next_tail and saved them on the stack for easier
-        ; mov curr_top, QWORD ((self.queue_start() + ((self.queue_size() * ptr_size!())/2)) as i64)
-        ; mov next_tail, QWORD ((self.queue_start() + ((3*ptr_size!()*self.queue_size())/2)) as i64)
+        // Initialize curr_top and next_tail to the middle of the regions of
+        // the queues, and the bounds of the queues, see `PikeJIT::swap_queues`
+        ; mov reg1, [rbp + state_ptr_offset!()]
+        // The size of a region is 32 * (capacity + 1), see
+        // `State::queue_region_size`
+        ; mov reg2, [reg1 + 4*ptr_size!()]
+        ; inc reg2
+        ; shl reg2, 5
+        ; mov reg1, [reg1 + 3*ptr_size!()]
+        ; lea next_tail, [reg1 + reg2]
+        ; shr reg2, 1
+        ; lea curr_top, [reg1 + reg2]
+        ; add next_tail, reg2
+        ; mov [rbp + queue_mid1_offset!()], curr_top
+        ; mov [rbp + queue_mid2_offset!()], next_tail
+        ; lea reg1, [reg2 + Self::THREAD_SIZE_BYTE]
+        ; mov [rbp + next_low_offset!()], reg1
+        ; lea reg1, [reg2 - Self::THREAD_SIZE_BYTE]
+        ; mov [rbp + active_peak_offset!()], reg1
+        ; lea reg1, [reg2*2 - 1]
+        ; mov [rbp + queue_mask_offset!()], reg1
+        ; lea reg1, [reg2*2 - 2*Self::THREAD_SIZE_BYTE]
+        ; mov [rbp + queue_limit_offset!()], reg1
         ;; CG::initialize_cg_region(self)
         )
     }
 
-    fn check_has_visited<CG: CGImpl>(&mut self, instr_index: usize) {
+    fn check_has_visited(&mut self, instr_index: usize) {
         // This limit the size of the input string,
         // The better way would be to load with an immediate
         // offset if instr_index fits in 31 bits, otherwise
@@ -663,18 +1719,23 @@ impl PikeJIT {
         // The idea is that when writing in visited, we write input_pos + 1
         // That way 0 (which is what the memory is initialized to) can always
         // be crossed.
-        ; jbe >success
-        ;; CG::free_curr_thread(self)
-        ; jmp =>self.step_next_active
-        ; success:
+        ; ja =>self.kill_thread
         ; lea reg1, [input_pos + 1]
         ; mov [mem + byte_offset], reg1
         )
     }
 
+    /// Return from the jitted code, with the return value already in rax.
+    /// The bounds reached by the queues are written in the state, see
+    /// [`State::queue_high_water`].
     fn epilogue(&mut self) {
         __!(self.ops,
-          mov rbx, [rbp + saved_rbx_offset!()]
+          mov reg1, [rbp + state_ptr_offset!()]
+        ; mov reg2, [rbp + active_peak_offset!()]
+        ; mov [reg1 + 5*ptr_size!()], reg2
+        ; mov reg2, [rbp + next_low_offset!()]
+        ; mov [reg1 + 6*ptr_size!()], reg2
+        ; mov rbx, [rbp + saved_rbx_offset!()]
         ; mov r12, [rbp + saved_r12_offset!()]
         ; mov r13, [rbp + saved_r13_offset!()]
         ; mov r14, [rbp + saved_r14_offset!()]
@@ -684,45 +1745,55 @@ impl PikeJIT {
         )
     }
 
-    fn compile_instruction<CG: CGImpl>(
-        &mut self,
-        i: usize,
-        instruction: &Instruction,
-        barrier: bool,
-    ) {
+    fn compile_instruction<CG: CGImpl>(&mut self, bytecode: &Bytecode, i: usize) {
         self.bind_label(i);
-        if barrier {
-            self.check_has_visited::<CG>(i);
+        if bytecode.barriers[i] {
+            self.check_has_visited(i);
         }
-        match instruction {
-            Instruction::Consume(c) => self.compile_consume::<CG>(i, *c),
-            Instruction::ConsumeClass(class) => self.compile_consume_class::<CG>(i, class),
-            Instruction::Fork2(a, b) => self.compile_fork::<CG>(&[*a, *b]),
+        match &bytecode.instructions[i] {
+            Instruction::Consume(_) => {
+                let run = bytecode.instructions[i..(i + self.literal_runs[i].max(1))]
+                    .iter()
+                    .map(|instr| match instr {
+                        Instruction::Consume(c) => *c,
+                        _ => unreachable!(),
+                    })
+                    .collect::<Vec<_>>();
+                self.compile_consume(i, &run)
+            }
+            Instruction::ConsumeClass(class) => self.compile_consume_class(i, class),
+            Instruction::Fork2(a, b) => match self.class_forks[i] {
+                Some(fork) => self.compile_class_fork(bytecode, fork),
+                None => self.compile_fork::<CG>(&[*a, *b]),
+            },
             Instruction::ForkN(items) => self.compile_fork::<CG>(items),
             Instruction::Jmp(target) => self.compile_jump(*target),
             Instruction::WriteReg(reg) => self.compile_write_reg::<CG>(i, *reg),
-            Instruction::Accept => self.compile_accept::<CG>(),
+            // Only the Pike VM reports the pattern, see `Compiler::compile_many`.
+            Instruction::Accept(_) => self.compile_accept::<CG>(),
             Instruction::ConsumeOutlined(class_id) => {
-                self.compile_consume_outlined::<CG>(i, *class_id)
+                self.compile_consume_outlined(i, *class_id, &bytecode.outlined_classes[*class_id])
+            }
+            Instruction::Assertion(look) => self.compile_assertion(i, *look),
+            Instruction::CutBegin(_) | Instruction::CutEnd(_) => {
+                unreachable!("rejected by PikeJIT::compile")
             }
-            Instruction::Assertion(look) => self.compile_assertion::<CG>(i, *look),
         }
     }
 
-    fn compile_consume_outlined<CG: CGImpl>(&mut self, i: usize, class_id: usize) {
+    fn compile_consume_outlined(&mut self, i: usize, class_id: usize, class: &[(Char, Char)]) {
         let class_label = self.outlined_class_labels[class_id];
         __!(self.ops,
           call =>class_label
         ; test reg1, reg1
-        ; jnz >fail
-        ;; self.push_next(self.instr_labels[i+1])
-        ; jmp =>self.step_next_active
-        ; fail:
-        ;; CG::free_curr_thread(self)
-        ; jmp =>self.step_next_active
-        )
+        ; jnz =>self.kill_thread
+        );
+        self.step_after_class(i, class);
     }
 
+    /// The helper checking whether the current char is in the class, called
+    /// by the instructions consuming it. It only changes the registers of
+    /// [`OUTLINED_CLASS_CLOBBERS`].
     fn compile_outlined_class(&mut self, i: usize, class: &[(Char, Char)]) {
         let label = self.outlined_class_labels[i];
 
@@ -732,23 +1803,25 @@ impl PikeJIT {
         // we compare this sentinel value agains every interval even though we
         // know it will never match.
         ; cmp curr_char, Char::INPUT_BOUND.into()
-        ; jne >next
+        ; jne >check_ranges
         ; mov reg1, 1
         ; ret
-        ; next:
+        ; check_ranges:
         );
+        // Like `compile_consume_range`, returning 0 in the range, 1 below it,
+        // and checking the next range above it.
         for (from, to) in class {
             __!(self.ops,
                 cmp curr_char, (u32::from(*from)).cast_signed()
-            ; jb >fail
+            ; jb >below_range
             ; cmp curr_char, (u32::from(*to)).cast_signed()
-            ; ja >next
+            ; ja >next_range
             ; mov reg1, 0
             ; ret
-            ; fail:
+            ; below_range:
             ; mov reg1, 1
             ; ret
-            ; next:
+            ; next_range:
             )
         }
         __!(self.ops,
@@ -757,43 +1830,168 @@ impl PikeJIT {
         )
     }
 
+    /// Move the current thread, which consumed the current char, to the
+    /// given instruction in the next step, and run the next active thread.
+    fn step_to(&mut self, label: DynamicLabel) {
+        self.reserve_next();
+        self.push_next(label);
+        __!(self.ops, jmp =>self.step_next_active);
+    }
+
     fn bind_label(&mut self, i: usize) {
         let label = self.instr_labels[i];
         __!(self.ops, =>label)
     }
 
-    fn compile_consume<CG: CGImpl>(&mut self, i: usize, c: Char) {
-        let next_label = self.instr_labels[i + 1];
+    fn compile_consume(&mut self, i: usize, run: &[Char]) {
+        let next_label = self.unchecked_labels[i + 1].unwrap_or(self.instr_labels[i + 1]);
         __!(self.ops,
-          cmp curr_char, ((u32::from(c)).cast_signed())
-        ; jne >fail
-        ;; self.push_next(next_label)
-        ; jmp =>self.step_next_active
-        ; fail:
-        ;; CG::free_curr_thread(self)
-        ; jmp =>self.step_next_active
-        )
+          cmp curr_char, ((u32::from(run[0])).cast_signed())
+        ; jne =>self.kill_thread
+        );
+        if run.len() > 1 {
+            self.compile_literal_run_check(run);
+        }
+        self.step_to(next_label);
+        // Entry point used by threads for which this consume was already
+        // checked by the first instruction of the run.
+        if let Some(unchecked) = self.unchecked_labels[i] {
+            __!(self.ops,
+              =>unchecked
+            ;; self.step_to(next_label)
+            );
+        }
+    }
+
+    /// Check that the characters following the current one are the ones of
+    /// the given run, or kill the current thread. The first character of
+    /// the run must already be checked, it is the current one.
+    fn compile_literal_run_check(&mut self, run: &[Char]) {
+        let mut bytes = Vec::new();
+        for c in &run[1..] {
+            let mut buf = [0; 4];
+            let c = char::from_u32(u32::from(*c)).unwrap();
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        let first_len = run[0].len_utf8() as i32;
+        // If the run does not fit before the end of the span, then the thread
+        // can't match it in any cases.
+        __!(self.ops,
+          lea reg1, [input_pos + first_len + bytes.len() as i32]
+        ; cmp reg1, span_end
+        ; ja =>self.kill_thread
+        );
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let disp = first_len + offset as i32;
+            let rest = &bytes[offset..];
+            if rest.len() >= 8 {
+                let value = u64::from_le_bytes(rest[..8].try_into().unwrap());
+                __!(self.ops,
+                  mov reg1, QWORD value.cast_signed()
+                ; cmp QWORD [input + input_pos + disp], reg1
+                ; jne =>self.kill_thread
+                );
+                offset += 8;
+            } else if rest.len() >= 4 {
+                let value = u32::from_le_bytes(rest[..4].try_into().unwrap());
+                __!(self.ops,
+                  cmp DWORD [input + input_pos + disp], value.cast_signed()
+                ; jne =>self.kill_thread
+                );
+                offset += 4;
+            } else if rest.len() >= 2 {
+                let value = u16::from_le_bytes(rest[..2].try_into().unwrap());
+                __!(self.ops,
+                  cmp WORD [input + input_pos + disp], value.cast_signed()
+                ; jne =>self.kill_thread
+                );
+                offset += 2;
+            } else {
+                __!(self.ops,
+                  cmp BYTE [input + input_pos + disp], rest[0].cast_signed()
+                ; jne =>self.kill_thread
+                );
+                offset += 1;
+            }
+        }
     }
 
-    fn compile_consume_class<CG: CGImpl>(&mut self, i: usize, class: &[(Char, Char)]) {
-        let fail = self.ops.new_dynamic_label();
-        let next = self.ops.new_dynamic_label();
+    fn compile_consume_class(&mut self, i: usize, class: &[(Char, Char)]) {
+        let in_class = self.ops.new_dynamic_label();
         for (from, to) in class {
-            self.compile_consume_range(next, fail, *from, *to);
+            self.compile_consume_range(in_class, self.kill_thread, *from, *to);
         }
         __!(self.ops,
-          =>fail
-        ;; CG::free_curr_thread(self)
-        ; jmp =>self.step_next_active
-        ; =>next
-        ;; self.push_next(self.instr_labels[i+1])
-        ; jmp =>self.step_next_active
-        )
+          jmp =>self.kill_thread
+        ; =>in_class
+        );
+        self.step_after_class(i, class);
     }
 
+    /// Move the current thread, which consumed the current char of the class
+    /// of instruction `i`, to the next instruction. If the instruction starts
+    /// a run (see [`PikeJIT::class_runs`]), the rest of the run is checked
+    /// first.
+    fn step_after_class(&mut self, i: usize, class: &[(Char, Char)]) {
+        let next_label = self.unchecked_labels[i + 1].unwrap_or(self.instr_labels[i + 1]);
+        if self.class_runs[i] > 1 {
+            self.compile_class_run_check(class, self.class_runs[i]);
+        }
+        self.step_to(next_label);
+        // Entry point used by threads for which this consume was already
+        // checked by the first instruction of the run.
+        if let Some(unchecked) = self.unchecked_labels[i] {
+            __!(self.ops,
+              =>unchecked
+            ;; self.step_to(next_label)
+            );
+        }
+    }
+
+    /// Check that the `len - 1` bytes following the current char are in the
+    /// given ASCII class, or kill the current thread. The current char must
+    /// already be checked, it is a single byte.
+    fn compile_class_run_check(&mut self, class: &[(Char, Char)], len: usize) {
+        let len = len as i32;
+        let scan = self.ops.new_dynamic_label();
+        let in_class = self.ops.new_dynamic_label();
+        // If the run does not fit before the end of the span, then the thread
+        // can't match it in any cases.
+        __!(self.ops,
+          lea reg2, [input_pos + len]
+        ; cmp reg2, span_end
+        ; ja =>self.kill_thread
+        ; lea reg2, [input_pos + 1]
+        ; =>scan
+        ; movzx reg1d, BYTE [input + reg2]
+        );
+        for (from, to) in class {
+            __!(self.ops,
+              cmp reg1d, (u32::from(*from)).cast_signed()
+            ; jb =>self.kill_thread
+            ; cmp reg1d, (u32::from(*to)).cast_signed()
+            ; jbe =>in_class
+            );
+        }
+        __!(self.ops,
+          jmp =>self.kill_thread
+        ; =>in_class
+        ; inc reg2
+        ; lea reg1, [input_pos + len]
+        ; cmp reg2, reg1
+        ; jb =>scan
+        );
+    }
+
+    /// Check the current char against the range `from..=to` of a class:
+    /// jump to `in_class_label` if it is in the range, and to `fail_label` if
+    /// it is below. Above the range, this falls through to the check of the
+    /// next range. Since the ranges of a class are sorted, a char below a
+    /// range is below all the following ones, and can't be in the class.
     fn compile_consume_range(
         &mut self,
-        next_label: DynamicLabel,
+        in_class_label: DynamicLabel,
         fail_label: DynamicLabel,
         from: Char,
         to: Char,
@@ -802,18 +2000,51 @@ impl PikeJIT {
           cmp curr_char, (u32::from(from)).cast_signed()
         ; jb =>fail_label
         ; cmp curr_char, (u32::from(to)).cast_signed()
-        ; ja >next
-        ; jmp =>next_label
-        ; next:
+        ; jbe =>in_class_label
         )
     }
 
+    /// A fork choosing its thread from the current char when it can, see
+    /// [`PikeJIT::class_forks`].
+    fn compile_class_fork(&mut self, bytecode: &Bytecode, fork: ClassFork) {
+        let in_class = self.ops.new_dynamic_label();
+        let exit = self.instr_labels[fork.exit];
+        match &bytecode.instructions[fork.consume] {
+            Instruction::Consume(c) => {
+                __!(self.ops,
+                  cmp curr_char, ((u32::from(*c)).cast_signed())
+                ; jne =>exit
+                )
+            }
+            Instruction::ConsumeClass(class) => {
+                for (from, to) in class {
+                    self.compile_consume_range(in_class, exit, *from, *to);
+                }
+                __!(self.ops, jmp =>exit)
+            }
+            Instruction::ConsumeOutlined(class_id) => {
+                let class_label = self.outlined_class_labels[*class_id];
+                __!(self.ops,
+                  call =>class_label
+                ; test reg1, reg1
+                ; jnz =>exit
+                )
+            }
+            _ => unreachable!("see PikeJIT::class_forks"),
+        }
+        __!(self.ops,
+          =>in_class
+        ; jmp =>self.instr_labels[fork.consume]
+        );
+    }
+
     fn compile_fork<CG: CGImpl>(&mut self, branches: &[usize]) {
         let len = branches.len();
+        self.reserve_active(len as i32 - 1);
         for i in (1..len).rev() {
             let instr_i = branches[i];
             self.push_active(self.instr_labels[instr_i]);
-            CG::clone_curr_thread(self);
+            self.cg_hook(CG::CLONE_CURR_THREAD_CLOBBERS, CG::clone_curr_thread);
         }
         __!(self.ops, jmp => self.instr_labels[branches[0]])
     }
@@ -824,13 +2055,17 @@ impl PikeJIT {
     }
 
     fn compile_write_reg<CG: CGImpl>(&mut self, i: usize, reg: u32) {
-        CG::write_reg(self, reg);
+        // The groups past the ones tracked are skipped, which lets a bytecode
+        // with groups be compiled for the overall match only.
+        if (reg as usize) < self.register_count {
+            self.cg_hook(CG::WRITE_REG_CLOBBERS, |jit| CG::write_reg(jit, reg));
+        }
         let next_label = self.instr_labels[i + 1];
         __!(self.ops, jmp =>next_label)
     }
 
     fn compile_accept<CG: CGImpl>(&mut self) {
-        CG::accept_curr_thread(self);
+        self.cg_hook(CG::ACCEPT_CLOBBERS, CG::accept_curr_thread);
         __!(self.ops,
           mov reg1, [rbp + return_on_accept!()]
         ; test reg1, reg1
@@ -846,12 +2081,16 @@ impl PikeJIT {
     /// number of bytes consumed in input_inc.
     /// ## Note
     /// It assumes the input is valid utf-8 (which is always true in rust)
-    /// and it does not perform any bound check.
+    /// and it does not perform any bound check, unless the search validates
+    /// the input, in which case the multi-byte chars are checked first.
     fn decode_next_utf_8(&mut self) {
         __!(self.ops,
           movzx curr_char, BYTE [input + input_pos]      // Load first byte
         ; cmp curr_char, 0x80
         ; jb >ascii
+        ; cmp QWORD [rbp + validate_utf8_offset!()], 0
+        ; jne =>self.validate_utf8
+        ; =>self.decode_multibyte
         ; cmp curr_char, 0xE0
         ; jb >twobyte
         ; cmp curr_char, 0xF0
@@ -907,74 +2146,318 @@ impl PikeJIT {
         )
     }
 
-    fn compile_assertion<CG: CGImpl>(&mut self, i: usize, look: Look) {
+    /// The code of the next instruction directly follows, so when the
+    /// assertion holds, the thread simply falls through to it.
+    fn compile_assertion(&mut self, i: usize, look: LookKind) {
+        let next = self.instr_labels[i + 1];
         match look {
-            Look::Start => {
+            LookKind::Start => {
                 __!(self.ops,
                   cmp prev_char,  Char::INPUT_BOUND.into()
-                ; je =>self.instr_labels[i+1]
-                ;; CG::free_curr_thread(self)
-                ; jmp =>self.step_next_active
+                ; jne =>self.kill_thread
                 )
             }
-            Look::End => {
+            LookKind::End => {
                 __!(self.ops,
                   cmp curr_char, Char::INPUT_BOUND.into()
-                ; je =>self.instr_labels[i+1]
-                ;; CG::free_curr_thread(self)
-                ; jmp =>self.step_next_active
+                ; jne =>self.kill_thread
                 )
             }
-            Look::StartLF => {
+            LookKind::StartLF => {
                 __!(self.ops,
                   cmp prev_char,  Char::INPUT_BOUND.into()
-                ; je =>self.instr_labels[i+1]
+                ; je =>next
                 ; cmp prev_char,  ('\n' as u32).cast_signed()
-                ; je =>self.instr_labels[i+1]
-                ;; CG::free_curr_thread(self)
-                ; jmp =>self.step_next_active
+                ; jne =>self.kill_thread
                 )
             }
-            Look::EndLF => {
+            LookKind::EndLF => {
                 __!(self.ops,
                   cmp curr_char,  Char::INPUT_BOUND.into()
-                ; je =>self.instr_labels[i+1]
+                ; je =>next
                 ; cmp curr_char,  ('\n' as u32).cast_signed()
-                ; je =>self.instr_labels[i+1]
-                ;; CG::free_curr_thread(self)
-                ; jmp =>self.step_next_active
+                ; jne =>self.kill_thread
                 )
             }
-            Look::StartCRLF => {
+            LookKind::StartCRLF => {
                 __!(self.ops,
                   cmp prev_char,  Char::INPUT_BOUND.into()
-                ; je =>self.instr_labels[i+1]
+                ; je =>next
                 ; cmp prev_char,  ('\n' as u32).cast_signed()
-                ; je =>self.instr_labels[i+1]
+                ; je =>next
                 ; cmp prev_char,  ('\r' as u32).cast_signed()
-                ; jne >fail
+                ; jne =>self.kill_thread
                 ; cmp curr_char,  ('\n' as u32).cast_signed()
-                ; jne =>self.instr_labels[i+1]
-                ; fail:
-                ;; CG::free_curr_thread(self)
-                ; jmp =>self.step_next_active
+                ; je =>self.kill_thread
                 )
             }
-            Look::EndCRLF => {
+            LookKind::EndCRLF => {
                 __!(self.ops,
                   cmp curr_char,  Char::INPUT_BOUND.into()
-                ; je =>self.instr_labels[i+1]
+                ; je =>next
                 ; cmp curr_char,  ('\r' as u32).cast_signed()
-                ; je =>self.instr_labels[i+1]
+                ; je =>next
                 ; cmp curr_char,  ('\n' as u32).cast_signed()
-                ; jne >fail
+                ; jne =>self.kill_thread
                 ; cmp prev_char,  ('\r' as u32).cast_signed()
-                ; jne =>self.instr_labels[i+1]
-                ; fail:
-                ;; CG::free_curr_thread(self)
-                ; jmp =>self.step_next_active)
+                ; je =>self.kill_thread
+                )
+            }
+            LookKind::WordStartHalfUnicode | LookKind::WordStartHalfAscii => {
+                __!(self.ops,
+                  mov reg1d, prev_char
+                ;; self.compile_is_word_char(look.is_unicode_word())
+                ; test reg1d, reg1d
+                ; jnz =>self.kill_thread
+                )
+            }
+            LookKind::WordEndHalfUnicode | LookKind::WordEndHalfAscii => {
+                __!(self.ops,
+                  mov reg1d, curr_char
+                ;; self.compile_is_word_char(look.is_unicode_word())
+                ; test reg1d, reg1d
+                ; jnz =>self.kill_thread
+                )
+            }
+            LookKind::WordUnicode
+            | LookKind::WordUnicodeNegate
+            | LookKind::WordAscii
+            | LookKind::WordAsciiNegate
+            | LookKind::WordStartUnicode
+            | LookKind::WordEndUnicode
+            | LookKind::WordStartAscii
+            | LookKind::WordEndAscii => {
+                let unicode = look.is_unicode_word();
+                // Only two scratch registers are free, so the class of the
+                // previous char waits on the stack.
+                __!(self.ops,
+                  mov reg1d, prev_char
+                ;; self.compile_is_word_char(unicode)
+                ; push reg1
+                ; mov reg1d, curr_char
+                ;; self.compile_is_word_char(unicode)
+                ; pop reg2
+                // The class after the position, minus the one before.
+                ; cmp reg1d, reg2d
+                );
+                match look {
+                    LookKind::WordUnicode | LookKind::WordAscii => {
+                        __!(self.ops, je =>self.kill_thread)
+                    }
+                    LookKind::WordUnicodeNegate | LookKind::WordAsciiNegate => {
+                        __!(self.ops, jne =>self.kill_thread)
+                    }
+                    // Holds only for 1 after and 0 before.
+                    LookKind::WordStartUnicode | LookKind::WordStartAscii => {
+                        __!(self.ops, jbe =>self.kill_thread)
+                    }
+                    _ => __!(self.ops, jae =>self.kill_thread),
+                }
             }
-            _ => todo!(),
         }
     }
+
+    /// Replaces the char in reg1d by 1 if it is a word char, and 0 otherwise,
+    /// clobbering reg2. The ASCII chars are looked up in two 64 bits masks.
+    /// The others (including the input bound) are never ASCII word chars, and
+    /// go through [`PikeJIT::compile_unicode_word_char`] otherwise.
+    fn compile_is_word_char(&mut self, unicode: bool) {
+        const DIGITS: i64 = 0x03FF_0000_0000_0000;
+        const LETTERS_AND_UNDERSCORE: i64 = 0x07FF_FFFE_87FF_FFFE;
+        __!(self.ops,
+          cmp reg1d, 0x80
+        ; jae >non_ascii
+        ; mov reg2, QWORD DIGITS
+        ; cmp reg1d, 64
+        ; jb >low
+        ; mov reg2, QWORD LETTERS_AND_UNDERSCORE
+        ; low:
+        // Only the low 6 bits of reg1 select the bit.
+        ; bt reg2, reg1
+        ; sbb reg1d, reg1d
+        ; neg reg1d
+        ; jmp >done
+        ; non_ascii:
+        );
+        match self.unicode_word_char.filter(|_| unicode) {
+            Some(label) => __!(self.ops, call =>label),
+            None => __!(self.ops, xor reg1d, reg1d),
+        }
+        __!(self.ops, done:)
+    }
+
+    /// The routine called by [`PikeJIT::compile_is_word_char`] for the
+    /// non-ASCII chars, which calls [`is_word_char`]. Like
+    /// [`PikeJIT::compile_check_interrupt`], it saves every caller-saved
+    /// register, except the one holding its result.
+    fn compile_unicode_word_char(&mut self) {
+        let Some(label) = self.unicode_word_char else {
+            return;
+        };
+        __!(self.ops,
+          =>label
+        ; push rax
+        ; push rcx
+        ; push rdx
+        ; push rsi
+        ; push r8
+        ; push r9
+        ; push r10
+        ; push r11
+        ; mov rcx, rsp
+        ; and rsp, -16
+        ; push rcx
+        ; push rcx
+        ; mov rax, [rbp + state_ptr_offset!()]
+        // The char is already in edi, the first argument.
+        ; call QWORD [rax + State::IS_WORD_CHAR_OFFSET]
+        ; mov rsp, [rsp]
+        ; mov reg1d, eax
+        ; pop r11
+        ; pop r10
+        ; pop r9
+        ; pop r8
+        ; pop rsi
+        ; pop rdx
+        ; pop rcx
+        ; pop rax
+        ; ret
+        )
+    }
+
+    /// Emit the paths taken when a thread dies, after all the code of the
+    /// instructions. Since they are the same for every instruction, they are
+    /// shared, and the instructions only jump there on failure. This keeps
+    /// them out of the hot code, which falls through on success. The same
+    /// goes for the overflow of the queues, which stops the search.
+    fn compile_cold_paths<CG: CGImpl>(&mut self) {
+        __!(self.ops,
+          =>self.kill_thread
+        ;; self.cg_hook(CG::FREE_CURR_THREAD_CLOBBERS, CG::free_curr_thread)
+        ; jmp =>self.step_next_active
+        ; =>self.queue_overflow
+        ; mov rax, 3
+        ;; self.epilogue()
+        ; ret
+        );
+        self.compile_check_interrupt();
+        self.compile_validate_utf8();
+        self.compile_unicode_word_char();
+    }
+
+    /// Calls the interrupt check once the countdown of chars reaches 0, and
+    /// either stops the search, or resets the countdown to what the check
+    /// returned. See [`check_interrupt`].
+    fn compile_check_interrupt(&mut self) {
+        __!(self.ops,
+          =>self.check_interrupt
+        // Every caller-saved register may be live
+        ; push rax
+        ; push rcx
+        ; push rdx
+        ; push rsi
+        ; push rdi
+        ; push r8
+        ; push r9
+        ; push r10
+        ; push r11
+        // The alignment of the stack depends on the CG implementation, so it
+        // is aligned here, and restored from the saved pointer.
+        ; mov rcx, rsp
+        ; and rsp, -16
+        ; push rcx
+        ; push rcx
+        ; mov rdi, [rbp + state_ptr_offset!()]
+        ; call QWORD [rdi + State::CHECK_INTERRUPT_OFFSET]
+        ; mov rsp, [rsp]
+        ; mov [rbp + interrupt_countdown_offset!()], rax
+        ; test rax, rax
+        // Pop leaves the flags as they are
+        ; pop r11
+        ; pop r10
+        ; pop r9
+        ; pop r8
+        ; pop rdi
+        ; pop rsi
+        ; pop rdx
+        ; pop rcx
+        ; pop rax
+        ; jnz =>self.interrupt_checked
+        ; mov rax, 4
+        ;; self.epilogue()
+        ; ret
+        )
+    }
+
+    /// Checks the multi-byte char starting with the byte in curr_char, and
+    /// decodes it if it is valid. Otherwise, the search stops, and reports the
+    /// position of the char in [`State::invalid_utf8_at`]. The checks are the
+    /// ones of the UTF-8 grammar (RFC 3629), which rules out overlong
+    /// encodings, surrogates, and chars above U+10FFFF.
+    fn compile_validate_utf8(&mut self) {
+        // Checks that the byte at the given offset is in the given range, with
+        // reg2 holding the number of bytes left in the input.
+        let check_byte = |jit: &mut Self, offset: i32, from: u8, to: u8| {
+            __!(jit.ops,
+              cmp reg2, offset
+            ; jbe >invalid
+            ; movzx reg1d, BYTE [input + input_pos + offset]
+            ; sub reg1d, i32::from(from)
+            ; cmp reg1d, i32::from(to - from)
+            ; ja >invalid
+            )
+        };
+        __!(self.ops,
+          =>self.validate_utf8
+        ; mov reg2, input_len
+        ; sub reg2, input_pos
+        ; cmp curr_char, 0xC2
+        ; jb >invalid
+        ; cmp curr_char, 0xE0
+        ; jb >two_bytes
+        ; cmp curr_char, 0xF0
+        ; jb >three_bytes
+        ; cmp curr_char, 0xF5
+        ; jae >invalid
+        ; cmp curr_char, 0xF0
+        ; je >f0
+        ; cmp curr_char, 0xF4
+        ; je >f4
+        ;; check_byte(self, 1, 0x80, 0xBF)
+        ; jmp >four_bytes
+        ; f0:
+        ;; check_byte(self, 1, 0x90, 0xBF)
+        ; jmp >four_bytes
+        ; f4:
+        ;; check_byte(self, 1, 0x80, 0x8F)
+        ; four_bytes:
+        ;; check_byte(self, 2, 0x80, 0xBF)
+        ;; check_byte(self, 3, 0x80, 0xBF)
+        ; jmp =>self.decode_multibyte
+        ; three_bytes:
+        ; cmp curr_char, 0xE0
+        ; je >e0
+        ; cmp curr_char, 0xED
+        ; je >ed
+        ;; check_byte(self, 1, 0x80, 0xBF)
+        ; jmp >last_byte
+        ; e0:
+        ;; check_byte(self, 1, 0xA0, 0xBF)
+        ; jmp >last_byte
+        ; ed:
+        ;; check_byte(self, 1, 0x80, 0x9F)
+        ; last_byte:
+        ;; check_byte(self, 2, 0x80, 0xBF)
+        ; jmp =>self.decode_multibyte
+        ; two_bytes:
+        ;; check_byte(self, 1, 0x80, 0xBF)
+        ; jmp =>self.decode_multibyte
+        ; invalid:
+        ; mov reg1, [rbp + state_ptr_offset!()]
+        ; mov [reg1 + 2*ptr_size!()], input_pos
+        ; mov rax, 2
+        ;; self.epilogue()
+        ; ret
+        )
+    }
 }
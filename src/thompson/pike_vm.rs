@@ -1,13 +1,16 @@
 //! An interpreter for [`crate::thompson::bytecode`].
 
-use std::{cmp::min, collections::VecDeque, error::Error, mem};
+use std::{cmp::min, collections::VecDeque, mem, time::Instant};
 
-use regex_syntax::{Parser, hir::Look};
+use regex_syntax::hir::Hir;
 
 use crate::{
-    regex::{Config, RegexImpl},
-    thompson::bytecode::{Bytecode, Compiler, Instruction::*},
-    util::{Char, Input, Span, find_prev_char},
+    regex::{Config, MatchKind, RegexImpl},
+    sink::MatchError,
+    thompson::bytecode::{Bytecode, ClassCache, Compiler, Instruction::*},
+    util::{
+        BuildTimings, Char, CheckedChars, ExplainTrace, Input, Span, Utf8ErrorAt, look_matches,
+    },
 };
 
 /// A so-called PikeVM.
@@ -16,6 +19,100 @@ use crate::{
 pub struct PikeVM {
     bytecode: Bytecode,
     capture_count: usize,
+    /// The pc of the `CutEnd` of each cut region, see
+    /// [`crate::thompson::bytecode::Instruction::CutEnd`].
+    cut_ends: Vec<usize>,
+    maximum_len: Option<usize>,
+    match_kind: MatchKind,
+    /// See [`PikeVM::build_timings`].
+    build_timings: BuildTimings,
+}
+
+/// A capture event reported by [`PikeVM::exec_with_observer`].
+///
+/// Every thread of the VM is a candidate for the final match, and reports the
+/// group bounds it writes as they happen. These events are speculative: each
+/// candidate eventually receives either a `Commit` (it is the final match, and
+/// its groups are the ones returned) or an `Abort` (its events must be
+/// discarded). A candidate created by a fork first receives the bounds it
+/// inherits from its parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupEvent {
+    /// The candidate set the start of a group.
+    Open {
+        candidate: usize,
+        group: usize,
+        position: usize,
+    },
+    /// The candidate set the end of a group.
+    Close {
+        candidate: usize,
+        group: usize,
+        position: usize,
+    },
+    /// The candidate is the final match.
+    Commit { candidate: usize },
+    /// The candidate died without being the final match.
+    Abort { candidate: usize },
+}
+
+/// Receives the capture events of an execution. When not `ENABLED`, the
+/// VM does not track candidates at all, so regular executions don't pay for
+/// it.
+///
+/// The other hooks are called unconditionally, but do nothing by default and
+/// are therefore compiled out.
+trait Observer {
+    const ENABLED: bool;
+
+    fn event(&mut self, event: GroupEvent);
+
+    /// The thread consumed the current character.
+    fn consumed(&mut self, _thread: &Thread, _state: &State) {}
+
+    /// The thread reached `Accept`, with the given overall match.
+    fn accepted(&mut self, _span: Span) {}
+}
+
+/// The observer used by regular executions.
+struct NoObserver;
+
+impl Observer for NoObserver {
+    const ENABLED: bool = false;
+
+    fn event(&mut self, _event: GroupEvent) {}
+}
+
+impl<F: FnMut(GroupEvent)> Observer for F {
+    const ENABLED: bool = true;
+
+    fn event(&mut self, event: GroupEvent) {
+        self(event)
+    }
+}
+
+/// Used by [`PikeVM::exec_explain`], which requires at least one capture so
+/// that the start of each thread is known.
+impl Observer for ExplainTrace {
+    const ENABLED: bool = false;
+
+    fn event(&mut self, _event: GroupEvent) {}
+
+    fn consumed(&mut self, thread: &Thread, state: &State) {
+        let start = state.cg_arrays[thread.capture_offset].from;
+        if self
+            .earliest_surviving_start
+            .is_none_or(|earliest| start < earliest)
+        {
+            self.earliest_surviving_start = Some(start);
+        }
+    }
+
+    fn accepted(&mut self, span: Span) {
+        self.accepts += 1;
+        self.first_accept.get_or_insert(span);
+        self.last_accept = Some(span);
+    }
 }
 
 /// A thread currently alive in the bytecode.
@@ -23,18 +120,48 @@ pub struct PikeVM {
 struct Thread {
     pc: usize,
     capture_offset: usize,
+    /// Identifies the thread for the observer, only set when there is one
+    candidate: usize,
+    /// Whether the thread reached `Accept` while it could still be killed by
+    /// a cut, see [`State::undecided`]. It then stays at `Accept`, and its
+    /// match ends where it was parked.
+    parked: bool,
+    /// The last barrier the thread went through at the current position, or
+    /// [`NO_BARRIER`].
+    barrier: usize,
 }
 
 impl Thread {
-    fn write_reg(&self, reg: usize, value: usize, state: &mut State) {
+    fn write_reg<O: Observer>(
+        &self,
+        reg: usize,
+        value: usize,
+        state: &mut State,
+        observer: &mut O,
+    ) {
         let offset = self.capture_offset + reg / 2;
         if offset >= self.capture_offset + state.result_len {
             return;
         }
-        if reg % 2 == 0 {
+        let (candidate, group, position) = (self.candidate, reg / 2, value);
+        if reg.is_multiple_of(2) {
             state.cg_arrays[offset].from = value;
+            if O::ENABLED {
+                observer.event(GroupEvent::Open {
+                    candidate,
+                    group,
+                    position,
+                });
+            }
         } else {
             state.cg_arrays[offset].to = value;
+            if O::ENABLED {
+                observer.event(GroupEvent::Close {
+                    candidate,
+                    group,
+                    position,
+                });
+            }
         }
     }
 
@@ -48,74 +175,497 @@ impl Thread {
         self
     }
 
-    fn free(self, state: &mut State) {
+    fn free<O: Observer>(self, state: &mut State, observer: &mut O) {
         state.cg_free.push(self.capture_offset);
+        if O::ENABLED {
+            observer.event(GroupEvent::Abort {
+                candidate: self.candidate,
+            });
+        }
     }
 
-    fn dup(&self, state: &mut State) -> Self {
+    fn dup<O: Observer>(&self, state: &mut State, observer: &mut O) -> Self {
         let pc = self.pc;
         let capture_offset = state.alloc_array();
         state.cg_arrays.copy_within(
-            self.capture_offset..(self.capture_offset + state.result_len),
+            self.capture_offset..(self.capture_offset + state.thread_len()),
             capture_offset,
         );
-        Thread { pc, capture_offset }
+        let candidate = state.new_candidate::<O>();
+        if O::ENABLED {
+            let spans = &state.cg_arrays[capture_offset..(capture_offset + state.result_len)];
+            for (group, span) in spans.iter().enumerate() {
+                // Unset bounds are the ones of Span::invalid()
+                if span.from != usize::MAX {
+                    observer.event(GroupEvent::Open {
+                        candidate,
+                        group,
+                        position: span.from,
+                    });
+                }
+                if span.to != 0 {
+                    observer.event(GroupEvent::Close {
+                        candidate,
+                        group,
+                        position: span.to,
+                    });
+                }
+            }
+        }
+        Thread {
+            pc,
+            capture_offset,
+            candidate,
+            parked: false,
+            barrier: self.barrier,
+        }
     }
 }
 
+/// What is known of the thread which went through a barrier, for a cut
+/// region, see [`State::pass_barrier`].
+#[derive(Clone, Copy)]
+struct Claim {
+    /// The slot of the thread for the region.
+    slot: Span,
+    /// Whether a higher priority thread may kill it, see
+    /// [`State::threatened`].
+    threatened: bool,
+    /// Whether the thread, or one of its forks, left the region since.
+    left: bool,
+}
+
+const NO_BARRIER: usize = usize::MAX;
+
+/// The slot of a thread for a cut region is a span, starting at the position
+/// where the thread entered the region, and ending with one of these.
+const INSIDE: usize = 0;
+const LEFT: usize = 1;
+
 pub struct State {
     active: VecDeque<Thread>,
     next: VecDeque<Thread>,
     input_pos: usize,
     visited: Box<[usize]>,
+    /// For each barrier and cut region, the claim of the thread which went
+    /// through the barrier at the current position.
+    claims: Box<[Claim]>,
+    /// For each barrier, the barrier that thread went through before, at the
+    /// same position, or [`NO_BARRIER`], and whether it replaced a thread
+    /// threatened as much.
+    claim_parents: Box<[usize]>,
+    replaced_claims: Box<[bool]>,
     cg_free: Vec<usize>,
     cg_arrays: Box<[Span]>,
     best_match: Option<Thread>,
+    /// The pattern of the last match, see [`PikeVM::exec_pattern`].
+    pattern: u32,
     capture_count: usize,
+    cut_count: usize,
+    state_count: usize,
     result_len: usize,
+    next_candidate: usize,
 }
 
 impl State {
-    fn new(capture_count: usize, state_count: usize, input_pos: usize) -> Self {
+    /// The buffers are only allocated on the first execution which needs
+    /// them (see [`State::ensure_allocated`]), so that executions taking the
+    /// allocation-free path (see [`PikeVM::exec_small`]) never allocate.
+    fn new(capture_count: usize, cut_count: usize, state_count: usize, input_pos: usize) -> Self {
         Self {
-            active: VecDeque::with_capacity(2 * state_count),
-            next: VecDeque::with_capacity(2 * state_count),
+            active: VecDeque::new(),
+            next: VecDeque::new(),
             input_pos,
-            visited: vec![0; state_count].into_boxed_slice(),
+            visited: Box::default(),
+            claims: Box::default(),
+            claim_parents: Box::default(),
+            replaced_claims: Box::default(),
             best_match: None,
-            cg_free: vec![0],
-            cg_arrays: vec![Span::invalid(); (state_count * 3 + 1) * capture_count]
-                .into_boxed_slice(),
+            pattern: 0,
+            cg_free: Vec::new(),
+            cg_arrays: Box::default(),
             capture_count,
+            cut_count,
+            state_count,
             result_len: 0,
+            next_candidate: 0,
+        }
+    }
+
+    /// The memory of the buffers, in bytes. None of them depends on the
+    /// haystack: they are sized from the bytecode when first allocated, and
+    /// the queues never hold more than two threads per instruction.
+    pub fn memory_usage(&self) -> usize {
+        (self.active.capacity() + self.next.capacity()) * mem::size_of::<Thread>()
+            + mem::size_of_val(&*self.visited)
+            + mem::size_of_val(&*self.claims)
+            + mem::size_of_val(&*self.claim_parents)
+            + mem::size_of_val(&*self.replaced_claims)
+            + self.cg_free.capacity() * mem::size_of::<usize>()
+            + mem::size_of_val(&*self.cg_arrays)
+    }
+
+    fn ensure_allocated(&mut self) {
+        if self.visited.len() == self.state_count {
+            return;
         }
+        let state_count = self.state_count;
+        self.active.reserve(2 * state_count);
+        self.next.reserve(2 * state_count);
+        self.visited = vec![0; state_count].into_boxed_slice();
+        if self.cut_count > 0 {
+            let claim = Claim {
+                slot: Span::invalid(),
+                threatened: false,
+                left: false,
+            };
+            self.claims = vec![claim; state_count * self.cut_count].into_boxed_slice();
+            self.claim_parents = vec![NO_BARRIER; state_count].into_boxed_slice();
+            self.replaced_claims = vec![false; state_count].into_boxed_slice();
+        }
+        self.cg_arrays =
+            vec![Span::invalid(); (state_count * 3 + 1) * (self.capture_count + self.cut_count)]
+                .into_boxed_slice();
+    }
+
+    /// Number of spans of each thread in the arena: the requested capture
+    /// groups, followed by the slots of the cut regions.
+    fn thread_len(&self) -> usize {
+        self.result_len + self.cut_count
     }
 
-    fn new_thread(&mut self, pc: usize) -> Thread {
+    fn new_thread<O: Observer>(&mut self, pc: usize) -> Thread {
         let capture_offset = self.alloc_array();
-        self.cg_arrays[capture_offset..(capture_offset + self.result_len)].fill(Span::invalid());
-        Thread { pc, capture_offset }
+        let thread_len = self.thread_len();
+        self.cg_arrays[capture_offset..(capture_offset + thread_len)].fill(Span::invalid());
+        let candidate = self.new_candidate::<O>();
+        Thread {
+            pc,
+            capture_offset,
+            candidate,
+            parked: false,
+            barrier: NO_BARRIER,
+        }
+    }
+
+    fn new_candidate<O: Observer>(&mut self) -> usize {
+        if !O::ENABLED {
+            return 0;
+        }
+        self.next_candidate += 1;
+        self.next_candidate - 1
     }
 
     fn alloc_array(&mut self) -> usize {
         if self.cg_free.len() == 1 {
             let capture_offset = self.cg_free[0];
-            self.cg_free[0] = capture_offset + self.result_len;
+            self.cg_free[0] = capture_offset + self.thread_len();
+            // Without cut regions, there are at most 3 threads per state.
+            // Parked threads (see `Thread::parked`) come on top of these.
+            if self.cg_free[0] > self.cg_arrays.len() {
+                let mut cg_arrays = mem::take(&mut self.cg_arrays).into_vec();
+                cg_arrays.resize(2 * self.cg_free[0], Span::invalid());
+                self.cg_arrays = cg_arrays.into_boxed_slice();
+            }
             capture_offset
         } else {
             self.cg_free.pop().unwrap()
         }
     }
 
-    fn accept(&mut self, thread: Thread) {
-        thread.write_reg(1, self.input_pos, self);
+    fn accept<O: Observer>(&mut self, thread: Thread, observer: &mut O) {
+        if !thread.parked {
+            thread.write_reg(1, self.input_pos, self, observer);
+        }
+        if self.result_len > 0 {
+            observer.accepted(self.cg_arrays[thread.capture_offset]);
+        }
         if let Some(prev) = self.best_match.replace(thread) {
-            prev.free(self);
+            prev.free(self, observer);
+        }
+        // Taken to free the threads, and put back to keep its buffer.
+        let mut active = mem::take(&mut self.active);
+        for thread in active.drain(..) {
+            thread.free(self, observer);
+        }
+        self.active = active;
+    }
+
+    /// Accept in [`MatchKind::LeftmostLongest`] mode. The thread replaces the
+    /// best match if it starts earlier, or at the same position but ends
+    /// later. On ties the first thread to accept, i.e. the highest priority
+    /// one, is kept. Lower priority threads keep running, since they may
+    /// still produce a longer match: only the ones starting after the best
+    /// match are killed, see [`State::outlived`].
+    fn accept_longest<O: Observer>(&mut self, thread: Thread, observer: &mut O) {
+        if !thread.parked {
+            thread.write_reg(1, self.input_pos, self, observer);
+        }
+        let span = self.cg_arrays[thread.capture_offset];
+        observer.accepted(span);
+        let better = match &self.best_match {
+            Some(best) => {
+                let best = self.cg_arrays[best.capture_offset];
+                span.from < best.from || (span.from == best.from && span.to > best.to)
+            }
+            None => true,
+        };
+        if !better {
+            thread.free(self, observer);
+        } else if let Some(prev) = self.best_match.replace(thread) {
+            prev.free(self, observer);
+        }
+    }
+
+    /// Whether a thread can't produce a better match than the best one in
+    /// [`MatchKind::LeftmostLongest`] mode, because it starts after it.
+    fn outlived(&self, thread: &Thread) -> bool {
+        self.best_match.as_ref().is_some_and(|best| {
+            self.cg_arrays[thread.capture_offset].from > self.cg_arrays[best.capture_offset].from
+        })
+    }
+
+    /// Whether the thread goes through the barrier at its pc: only the first
+    /// thread reaching it at a position does, since the others share its
+    /// future, with a lower priority.
+    ///
+    /// With cut regions, the thread which went through may be killed later
+    /// (see [`State::threatened`]), so a thread which may survive it goes
+    /// through too, and replaces it: one which is threatened in fewer
+    /// regions, or the first one which is threatened by other threads.
+    ///
+    /// A thread which is dropped inside a cut region may also have entered it
+    /// elsewhere. The thread which went through leaves it where the dropped
+    /// one would have, so it must also kill the threads the dropped one would
+    /// have killed: the threads of both entries are merged, and if it (or one
+    /// of its forks) left the region since it went through, the kills are
+    /// done right away.
+    fn pass_barrier<O: Observer>(&mut self, thread: &mut Thread, observer: &mut O) -> bool {
+        let first = self.visited[thread.pc] <= self.input_pos;
+        self.visited[thread.pc] = self.input_pos + 1;
+        if self.cut_count == 0 {
+            return first;
+        }
+        let slots = thread.capture_offset + self.result_len;
+        let claims = thread.pc * self.cut_count;
+        if first {
+            self.replaced_claims[thread.pc] = false;
+        } else {
+            let (mut threats, mut claim_threats, mut escapes) = (0, 0, false);
+            for id in 0..self.cut_count {
+                let (slot, claim) = (self.cg_arrays[slots + id], self.claims[claims + id]);
+                threats += usize::from(self.threatened(id, slot));
+                claim_threats += usize::from(claim.threatened);
+                escapes |= claim.threatened && slot.from != claim.slot.from;
+            }
+            // Only one replacement doesn't lower the number of threats, so
+            // that threads can't go through the same barrier forever.
+            let fewer = threats < claim_threats;
+            let replaced = !fewer && escapes && !self.replaced_claims[thread.pc];
+            if !fewer && !replaced {
+                self.drop_at_barrier(thread, observer);
+                return false;
+            }
+            self.replaced_claims[thread.pc] |= replaced;
+        }
+        for id in 0..self.cut_count {
+            let slot = self.cg_arrays[slots + id];
+            self.claims[claims + id] = Claim {
+                slot,
+                threatened: self.threatened(id, slot),
+                left: false,
+            };
+        }
+        self.claim_parents[thread.pc] = thread.barrier;
+        thread.barrier = thread.pc;
+        true
+    }
+
+    /// Merges the cut regions of a thread dropped at a barrier with the ones
+    /// of the thread which went through, see [`State::pass_barrier`].
+    fn drop_at_barrier<O: Observer>(&mut self, thread: &Thread, observer: &mut O) {
+        let slots = thread.capture_offset + self.result_len;
+        let claims = thread.pc * self.cut_count;
+        for id in 0..self.cut_count {
+            let (dropped, claim) = (self.cg_arrays[slots + id], self.claims[claims + id]);
+            let inside = |slot: Span| slot.from != usize::MAX && slot.to == INSIDE;
+            if inside(dropped) && inside(claim.slot) && dropped.from != claim.slot.from {
+                if claim.left {
+                    self.kill_entered(id, dropped.from, &[], observer);
+                }
+                if self.inside_from(thread.pc, id, claim.slot.from) {
+                    self.merge_cuts(id, dropped.from, claim.slot.from);
+                }
+            }
+        }
+    }
+
+    /// Whether a thread which went through the barrier `pc` at the current
+    /// position, or one of its forks, is still inside the cut region `id`,
+    /// which it entered at `entry`.
+    fn inside_from(&self, pc: usize, id: usize, entry: usize) -> bool {
+        self.next.iter().any(|thread| {
+            let slot = self.cg_arrays[thread.capture_offset + self.result_len + id];
+            let mut barrier = thread.barrier;
+            // Replaced claims may form cycles.
+            for _ in 0..self.state_count {
+                if barrier == pc || barrier == NO_BARRIER {
+                    break;
+                }
+                barrier = self.claim_parents[barrier];
+            }
+            barrier == pc && slot.from == entry && slot.to == INSIDE
+        })
+    }
+
+    /// Moves the threads which entered the cut region `id` at `from` to the
+    /// ones which entered it at `to`.
+    fn merge_cuts(&mut self, id: usize, from: usize, to: usize) {
+        let State {
+            active,
+            next,
+            cg_arrays,
+            result_len,
+            ..
+        } = self;
+        for thread in active.iter().chain(next.iter()) {
+            let slot = &mut cg_arrays[thread.capture_offset + *result_len + id];
+            if slot.from == from {
+                slot.from = to;
+            }
         }
-        let active = mem::take(&mut self.active);
-        for thread in active {
-            thread.free(self);
+    }
+
+    fn cut_slot(&mut self, thread: &Thread, id: u32) -> &mut Span {
+        &mut self.cg_arrays[thread.capture_offset + self.result_len + id as usize]
+    }
+
+    /// Called when the thread leaves the cut region `id`: the lower priority
+    /// threads which entered it at the same position are killed, whether they
+    /// left it already or not. These are the ones of the active queue, which
+    /// were not stepped yet.
+    ///
+    /// A thread which may still be killed itself (see [`State::threatened`])
+    /// only kills the threads which would be killed along with it, i.e. the
+    /// ones which entered the same regions at the same positions.
+    fn leave_cut<O: Observer>(&mut self, thread: &Thread, id: u32, observer: &mut O) {
+        let slots = thread.capture_offset + self.result_len;
+        let id = id as usize;
+        let entry = self.cg_arrays[slots + id].from;
+        let pending: Vec<_> = (0..self.cut_count)
+            .filter(|other_id| {
+                let slot = self.cg_arrays[slots + other_id];
+                *other_id != id && slot.to == LEFT && self.inside(*other_id, slot.from)
+            })
+            .map(|other_id| (other_id, self.cg_arrays[slots + other_id].from))
+            .collect();
+        self.kill_entered(id, entry, &pending, observer);
+        let mut barrier = thread.barrier;
+        while barrier != NO_BARRIER && !self.claims[barrier * self.cut_count + id].left {
+            self.claims[barrier * self.cut_count + id].left = true;
+            barrier = self.claim_parents[barrier];
+        }
+        // Unless a higher priority thread may still kill it, the thread is
+        // done with the region, and its slot is reset so that it merges at
+        // barriers with the threads which never entered it.
+        self.cg_arrays[slots + id] = if self.inside(id, entry) {
+            Span {
+                from: entry,
+                to: LEFT,
+            }
+        } else {
+            Span::invalid()
+        };
+    }
+
+    /// Kills the threads of the active queue which entered the cut region
+    /// `id` at `entry`, and the other regions of `pending` at the given
+    /// positions.
+    fn kill_entered<O: Observer>(
+        &mut self,
+        id: usize,
+        entry: usize,
+        pending: &[(usize, usize)],
+        observer: &mut O,
+    ) {
+        let State {
+            active,
+            cg_arrays,
+            cg_free,
+            result_len,
+            ..
+        } = self;
+        active.retain(|other| {
+            let other_slots = &cg_arrays[other.capture_offset + *result_len..];
+            let killed = other_slots[id].from == entry
+                && pending
+                    .iter()
+                    .all(|(other_id, entry)| other_slots[*other_id].from == *entry);
+            if killed {
+                cg_free.push(other.capture_offset);
+                if O::ENABLED {
+                    observer.event(GroupEvent::Abort {
+                        candidate: other.candidate,
+                    });
+                }
+            }
+            !killed
+        });
+    }
+
+    /// Whether a higher priority thread (i.e. one of the next queue) is
+    /// inside the cut region `id`, which it entered at `entry`.
+    fn inside(&self, id: usize, entry: usize) -> bool {
+        self.next.iter().any(|other| {
+            let other = self.cg_arrays[other.capture_offset + self.result_len + id];
+            other.from == entry && other.to == INSIDE
+        })
+    }
+
+    /// Whether a thread with this slot for the cut region `id` may still be
+    /// killed, because a higher priority thread is inside the region, which
+    /// it entered at the same position. When that thread leaves the region,
+    /// it kills this one.
+    fn threatened(&self, id: usize, slot: Span) -> bool {
+        slot.from != usize::MAX && self.inside(id, slot.from)
+    }
+
+    /// Whether a thread reaching `Accept` may still be killed, see
+    /// [`State::threatened`].
+    fn undecided(&self, thread: &Thread) -> bool {
+        let slots = &self.cg_arrays[thread.capture_offset + self.result_len..][..self.cut_count];
+        slots
+            .iter()
+            .enumerate()
+            .any(|(id, slot)| self.threatened(id, *slot))
+    }
+
+    /// Keeps an undecided thread (see [`State::undecided`]) in the next
+    /// queue, at its priority, until it is known whether it is killed.
+    fn park<O: Observer>(&mut self, mut thread: Thread, observer: &mut O) {
+        if !thread.parked {
+            thread.write_reg(1, self.input_pos, self, observer);
+            thread.parked = true;
+        }
+        self.push_next(thread);
+    }
+
+    /// Called after the last step, once no thread can leave a cut region
+    /// anymore: the parked threads are accepted, by priority.
+    fn accept_parked<O: Observer>(&mut self, longest: bool, observer: &mut O) {
+        let mut next = mem::take(&mut self.next);
+        let mut accepted = false;
+        for thread in next.drain(..) {
+            if !thread.parked || (accepted && !longest) {
+                thread.free(self, observer);
+            } else if longest {
+                self.accept_longest(thread, observer);
+            } else {
+                self.accept(thread, observer);
+                accepted = true;
+            }
         }
+        self.next = next;
     }
 
     fn push_active(&mut self, thread: Thread) {
@@ -133,6 +683,11 @@ impl State {
     fn swap_and_advance_by(&mut self, step: usize) {
         self.input_pos += step;
         std::mem::swap(&mut self.active, &mut self.next);
+        if self.cut_count > 0 {
+            for thread in &mut self.active {
+                thread.barrier = NO_BARRIER;
+            }
+        }
     }
 
     fn reset(&mut self) {
@@ -140,9 +695,29 @@ impl State {
         self.visited.fill(0);
         self.active.clear();
         self.next.clear();
+        // The arena is set up by each execution (see `exec_internal`), so
+        // that resetting a state which was never used doesn't allocate.
         self.cg_free.clear();
-        self.cg_free.push(0);
         self.result_len = 0;
+        self.next_candidate = 0;
+    }
+
+    /// Called when the execution stops, to report the outcome of the
+    /// remaining candidates.
+    fn finish<O: Observer>(&mut self, observer: &mut O) {
+        if !O::ENABLED {
+            return;
+        }
+        if let Some(thread) = &self.best_match {
+            observer.event(GroupEvent::Commit {
+                candidate: thread.candidate,
+            });
+        }
+        for thread in self.active.drain(..).chain(self.next.drain(..)) {
+            observer.event(GroupEvent::Abort {
+                candidate: thread.candidate,
+            });
+        }
     }
 
     fn write_best_match(&mut self, result: &mut [Span]) {
@@ -150,54 +725,168 @@ impl State {
         let result_len = min(self.capture_count, result.len());
         let bounds = winning_thread.capture_offset..(winning_thread.capture_offset + result_len);
         result[0..result_len].copy_from_slice(&self.cg_arrays[bounds]);
+        result[result_len..].fill(Span::invalid());
     }
 }
 
 impl PikeVM {
+    /// Builds the VM of bytecode compiled elsewhere, whose classes must be
+    /// normalized, see [`Bytecode::normalize_classes`].
     pub fn from_bytecode(bytecode: Bytecode, capture_count: usize) -> Self {
+        // `class_contains` stops at the first range past the char.
+        debug_assert!(bytecode.classes_are_normalized(), "unnormalized class");
         Self {
+            cut_ends: Self::cut_ends(&bytecode),
             bytecode,
             capture_count,
+            maximum_len: None,
+            match_kind: MatchKind::LeftmostFirst,
+            build_timings: BuildTimings::default(),
         }
     }
 
-    pub fn new(
+    pub fn new(pattern: &str, config: Config) -> Result<Self, crate::Error> {
+        Self::with_class_cache(pattern, config, None)
+    }
+
+    /// Builds the VM of the pattern, whose outlined classes are interned in
+    /// the cache if there is one, see [`ClassCache`].
+    pub fn with_class_cache(
         pattern: &str,
         config: Config,
-    ) -> Result<Self, Box<dyn Error + Send + Sync + 'static>> {
-        let hir = Parser::from(config.clone()).parse(pattern)?;
-        let capture_count = if config.cg {
-            hir.properties().explicit_captures_len() + 1
-        } else {
-            1
+        cache: Option<&ClassCache>,
+    ) -> Result<Self, crate::Error> {
+        let started = Instant::now();
+        let hir = Compiler::parse(pattern, &config)?;
+        let parse = started.elapsed();
+        let mut vm = Self::from_hir(hir, config, cache)?;
+        vm.build_timings.parse = parse;
+        Ok(vm)
+    }
+
+    /// Builds the VM of a pattern already parsed with the config, see
+    /// [`Compiler::parse`]. Like [`PikeVM::with_class_cache`], but only the
+    /// compilation is timed.
+    pub fn from_hir(
+        hir: Hir,
+        config: Config,
+        cache: Option<&ClassCache>,
+    ) -> Result<Self, crate::Error> {
+        let started = Instant::now();
+        let capture_count = Compiler::capture_count(&hir, &config);
+        let maximum_len = hir.properties().maximum_len();
+        let match_kind = config.match_kind;
+        let mut bytecode = Compiler::compile(hir, config)?;
+        if let Some(cache) = cache {
+            bytecode.intern_classes(cache);
+        }
+        let build_timings = BuildTimings {
+            compile: started.elapsed(),
+            ..BuildTimings::default()
+        };
+
+        Ok(Self {
+            cut_ends: Self::cut_ends(&bytecode),
+            bytecode,
+            capture_count,
+            maximum_len,
+            match_kind,
+            build_timings,
+        })
+    }
+
+    /// Builds the VM of several patterns, which reports which pattern
+    /// matched, see [`Compiler::compile_many`] and [`PikeVM::exec_pattern`].
+    /// The groups of the patterns are reported together, as laid out by the
+    /// compiler.
+    pub fn new_many(hirs: Vec<Hir>, config: Config) -> Result<Self, crate::Error> {
+        let capture_count = 1 + hirs
+            .iter()
+            .map(|hir| Compiler::capture_count(hir, &config) - 1)
+            .sum::<usize>();
+        let maximum_len = hirs
+            .iter()
+            .map(|hir| hir.properties().maximum_len())
+            .try_fold(0, |max, len| Some(max.max(len?)));
+        let match_kind = config.match_kind;
+        let started = Instant::now();
+        let bytecode = Compiler::compile_many(hirs, config)?;
+        let build_timings = BuildTimings {
+            compile: started.elapsed(),
+            ..BuildTimings::default()
         };
-        let bytecode = Compiler::compile(hir, config)?;
 
         Ok(Self {
+            cut_ends: Self::cut_ends(&bytecode),
             bytecode,
             capture_count,
+            maximum_len,
+            match_kind,
+            build_timings,
         })
     }
 
+    /// The maximum length in bytes of a match, if it is bounded and known.
+    pub fn maximum_len(&self) -> Option<usize> {
+        self.maximum_len
+    }
+
+    /// The bytecode the VM interprets.
+    pub fn bytecode(&self) -> &Bytecode {
+        &self.bytecode
+    }
+
+    /// How long parsing and compiling the pattern took in [`PikeVM::new`].
+    /// The patterns given to [`PikeVM::from_hir`] and [`PikeVM::new_many`]
+    /// are already parsed, and [`PikeVM::from_bytecode`] leaves every
+    /// duration at zero.
+    pub fn build_timings(&self) -> BuildTimings {
+        self.build_timings
+    }
+
+    fn cut_ends(bytecode: &Bytecode) -> Vec<usize> {
+        let mut cut_ends = Vec::new();
+        for (pc, instruction) in bytecode.instructions.iter().enumerate() {
+            if let CutEnd(id) = instruction {
+                let id = *id as usize;
+                if cut_ends.len() <= id {
+                    cut_ends.resize(id + 1, 0);
+                }
+                cut_ends[id] = pc;
+            }
+        }
+        cut_ends
+    }
+
     /// Do one step of simulation, meaning stepping through all threads in the
     /// active queue and simulating them until they either die, or successfully consumed
     /// a character.
-    fn step(&self, state: &mut State, prev: Char, c: Char) {
+    fn step<O: Observer>(
+        &self,
+        state: &mut State,
+        prev: Char,
+        c: Char,
+        longest: bool,
+        observer: &mut O,
+    ) {
         let bytecode = self.bytecode.instructions.as_slice();
         'next_active: while let Some(mut thread) = state.pop_active() {
+            if longest && state.outlived(&thread) {
+                thread.free(state, observer);
+                continue;
+            }
             loop {
-                // Check barrier if needed
-                if self.bytecode.barriers[thread.pc] {
-                    let value = &mut state.visited[thread.pc];
-                    if *value <= state.input_pos {
-                        *value = state.input_pos + 1;
-                    } else {
-                        thread.free(state);
-                        break;
-                    }
+                // Check barrier if needed. Parked threads already passed it.
+                if self.bytecode.barriers[thread.pc]
+                    && !thread.parked
+                    && !state.pass_barrier(&mut thread, observer)
+                {
+                    thread.free(state, observer);
+                    break;
                 }
                 match &bytecode[thread.pc] {
                     Consume(c2) if *c2 == c => {
+                        observer.consumed(&thread, state);
                         state.push_next(thread.inc_pc());
                         break;
                     }
@@ -208,10 +897,11 @@ impl PikeVM {
                             } else if c > *end {
                                 continue;
                             }
+                            observer.consumed(&thread, state);
                             state.push_next(thread.inc_pc());
                             continue 'next_active;
                         }
-                        thread.free(state);
+                        thread.free(state, observer);
                         break;
                     }
                     ConsumeOutlined(id) => {
@@ -223,21 +913,22 @@ impl PikeVM {
                             } else if c > *end {
                                 continue;
                             }
+                            observer.consumed(&thread, state);
                             state.push_next(thread.inc_pc());
                             continue 'next_active;
                         }
-                        thread.free(state);
+                        thread.free(state, observer);
                         break;
                     }
                     Fork2(a, b) => {
-                        let new_thread = thread.dup(state).with_pc(*b);
+                        let new_thread = thread.dup(state, observer).with_pc(*b);
                         state.push_active(new_thread);
                         thread.pc = *a;
                     }
                     ForkN(branches) => {
                         let len = branches.len();
                         for pc in branches.iter().rev().take(len - 1) {
-                            let new_thread = thread.dup(state).with_pc(*pc);
+                            let new_thread = thread.dup(state, observer).with_pc(*pc);
                             state.push_active(new_thread);
                         }
                         thread.pc = branches[0];
@@ -246,72 +937,52 @@ impl PikeVM {
                         thread.pc = *target;
                     }
                     WriteReg(r) => {
-                        thread.write_reg(*r as usize, state.input_pos, state);
+                        thread.write_reg(*r as usize, state.input_pos, state, observer);
+                        thread.pc += 1;
+                    }
+                    CutBegin(id) => {
+                        let input_pos = state.input_pos;
+                        let slot = state.cut_slot(&thread, *id);
+                        if slot.from == input_pos && slot.to == LEFT {
+                            // Entering the region again where the thread
+                            // entered it last would make the same choices,
+                            // so the thread leaves it right away. It may
+                            // still be killed, by the threads of the first
+                            // entry only.
+                            thread.pc = self.cut_ends[*id as usize] + 1;
+                        } else {
+                            *slot = Span {
+                                from: input_pos,
+                                to: INSIDE,
+                            };
+                            thread.pc += 1;
+                        }
+                    }
+                    CutEnd(id) => {
+                        state.leave_cut(&thread, *id, observer);
                         thread.pc += 1;
                     }
-                    Accept => {
-                        state.accept(thread);
+                    Accept(_) if !self.cut_ends.is_empty() && state.undecided(&thread) => {
+                        state.park(thread, observer);
+                        break;
+                    }
+                    Accept(_) if longest => {
+                        state.accept_longest(thread, observer);
+                        break;
+                    }
+                    Accept(_) => {
+                        state.accept(thread, observer);
+                        break;
+                    }
+                    Assertion(look) if look_matches(*look, prev, c) => {
+                        thread.pc += 1;
+                    }
+                    Assertion(_) => {
+                        thread.free(state, observer);
                         break;
                     }
-                    Assertion(look) => match look {
-                        Look::Start => {
-                            if prev == Char::INPUT_BOUND {
-                                thread.pc += 1;
-                            } else {
-                                thread.free(state);
-                                break;
-                            }
-                        }
-                        Look::End => {
-                            if c == Char::INPUT_BOUND {
-                                thread.pc += 1;
-                            } else {
-                                thread.free(state);
-                                break;
-                            }
-                        }
-                        Look::StartLF => {
-                            if prev == Char::INPUT_BOUND || prev == '\n'.into() {
-                                thread.pc += 1;
-                            } else {
-                                thread.free(state);
-                                break;
-                            }
-                        }
-                        Look::EndLF => {
-                            if c == Char::INPUT_BOUND || c == '\n'.into() {
-                                thread.pc += 1;
-                            } else {
-                                thread.free(state);
-                                break;
-                            }
-                        }
-                        Look::StartCRLF => {
-                            if prev == Char::INPUT_BOUND
-                                || prev == '\n'.into()
-                                || (prev == '\r'.into() && c != '\n'.into())
-                            {
-                                thread.pc += 1;
-                            } else {
-                                thread.free(state);
-                                break;
-                            }
-                        }
-                        Look::EndCRLF => {
-                            if c == Char::INPUT_BOUND
-                                || c == '\r'.into()
-                                || (c == '\n'.into() && prev != '\r'.into())
-                            {
-                                thread.pc += 1;
-                            } else {
-                                thread.free(state);
-                                break;
-                            }
-                        }
-                        _ => todo!(),
-                    },
                     _ => {
-                        thread.free(state);
+                        thread.free(state, observer);
                         break;
                     }
                 }
@@ -322,75 +993,599 @@ impl PikeVM {
     pub fn capture_count(&self) -> usize {
         self.capture_count
     }
-}
 
-impl RegexImpl for PikeVM {
-    type State = State;
+    /// Like a regular execution, but reports the capture events to the
+    /// observer as they happen, see [`GroupEvent`]. The state must have been
+    /// reset since its last use.
+    pub fn exec_with_observer(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+        mut observer: impl FnMut(GroupEvent),
+    ) -> bool {
+        self.exec_internal(input, state, captures, &mut observer)
+    }
 
-    fn new_state(&self) -> Self::State {
-        State::new(self.capture_count, self.bytecode.instructions.len(), 0)
+    /// Like a regular execution, but also records how the final match was
+    /// chosen. `captures` must not be empty.
+    pub fn exec_explain(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+    ) -> (bool, ExplainTrace) {
+        assert!(!captures.is_empty());
+        let mut trace = ExplainTrace::default();
+        let found = self.exec_internal(input, state, captures, &mut trace);
+        (found, trace)
     }
 
-    fn reset_state(&self, state: &mut Self::State) {
-        state.reset();
+    /// Like a regular execution, but returns which pattern matched, for a VM
+    /// of several patterns (see [`PikeVM::new_many`]), or `None` if there is
+    /// no match. `captures` must not be empty. The state must have been reset
+    /// since its last use.
+    pub fn exec_pattern(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+    ) -> Option<usize> {
+        assert!(!captures.is_empty());
+        self.exec_internal(input, state, captures, &mut NoObserver)
+            .then_some(state.pattern as usize)
     }
 
-    fn exec<'s>(&self, input: Input<'s>, state: &mut Self::State, captures: &mut [Span]) -> bool {
+    /// Searches the longest match starting at the start of the span of the
+    /// input, whatever the match kind of the VM: the threads run until they
+    /// all die or the input ends, and the accept ending last is kept, ties
+    /// going to the highest priority thread. The anchoring and `first_match`
+    /// of the input are ignored. `captures` must not be empty. The state must
+    /// have been reset since its last use.
+    pub fn exec_longest_prefix(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+    ) -> bool {
+        assert!(!captures.is_empty());
+        if !input.valid() {
+            return false;
+        }
+        let Span { from, to } = input.span;
+        let haystack = Haystack {
+            anchored: true,
+            first_match: false,
+            longest: true,
+            ..Haystack::new(&input, input.subject[from..to].chars())
+        };
+        self.exec_chars(haystack, state, captures, &mut NoObserver)
+    }
+
+    /// Searches the concatenation of the slices, with the separator (if any)
+    /// between each of them, as if it was a single subject. The reported
+    /// positions are offsets in that concatenation. The state must have been
+    /// reset since its last use.
+    pub fn exec_slices(
+        &self,
+        slices: &[&str],
+        separator: Option<char>,
+        state: &mut State,
+        captures: &mut [Span],
+    ) -> bool {
+        let last = slices.len().saturating_sub(1);
+        let chars = slices.iter().enumerate().flat_map(|(i, slice)| {
+            let separator = if i < last { separator } else { None };
+            slice.chars().chain(separator)
+        });
+        let haystack = Haystack {
+            chars,
+            from: 0,
+            prev: Char::INPUT_BOUND,
+            next: Char::INPUT_BOUND,
+            anchored: false,
+            first_match: false,
+            longest: false,
+        };
+        self.exec_chars(haystack, state, captures, &mut NoObserver)
+    }
+
+    /// Searches bytes which may not be valid UTF-8, checking the chars as
+    /// they are decoded. The search stops at the first invalid sequence it
+    /// reads, see [`crate::Regex::find_validating`]. The state must have been
+    /// reset since its last use.
+    pub fn exec_validating(
+        &self,
+        haystack: &[u8],
+        state: &mut State,
+        captures: &mut [Span],
+    ) -> Result<bool, Utf8ErrorAt> {
+        let mut chars = CheckedChars::new(haystack);
+        let haystack = Haystack {
+            chars: &mut chars,
+            from: 0,
+            prev: Char::INPUT_BOUND,
+            next: Char::INPUT_BOUND,
+            anchored: false,
+            first_match: false,
+            longest: false,
+        };
+        let found = self.exec_chars(haystack, state, captures, &mut NoObserver);
+        match chars.invalid_at {
+            Some(offset) => Err(Utf8ErrorAt { offset }),
+            None => Ok(found),
+        }
+    }
+
+    /// Like a regular execution, but calls `interrupt` every `interval`
+    /// chars, and stops the search once it returns true, see
+    /// [`crate::Regex::find_interruptible`]. The state must have been reset
+    /// since its last use.
+    pub fn exec_interruptible(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+        interval: usize,
+        interrupt: &dyn Fn() -> bool,
+    ) -> Result<bool, MatchError> {
+        if !input.valid() {
+            return Ok(false);
+        }
+        let Span { from, to } = input.span;
+        let mut chars = InterruptibleChars {
+            chars: input.subject[from..to].chars(),
+            interval: interval.max(1),
+            countdown: interval.max(1),
+            interrupt,
+            interrupted: false,
+        };
+        let found = if self.takes_small_path(captures) {
+            self.exec_small_chars(&input, &mut chars, captures)
+        } else {
+            let haystack = Haystack::new(&input, &mut chars);
+            self.exec_chars(haystack, state, captures, &mut NoObserver)
+        };
+        if chars.interrupted {
+            return Err(MatchError::Interrupted);
+        }
+        Ok(found)
+    }
+
+    fn exec_internal<O: Observer>(
+        &self,
+        input: Input<'_>,
+        state: &mut State,
+        captures: &mut [Span],
+        observer: &mut O,
+    ) -> bool {
         if !input.valid() {
             return false;
         }
+        let Span { from, to } = input.span;
+        let haystack = Haystack::new(&input, input.subject[from..to].chars());
+        self.exec_chars(haystack, state, captures, observer)
+    }
 
+    fn exec_chars<O: Observer>(
+        &self,
+        haystack: Haystack<impl Iterator<Item = char>>,
+        state: &mut State,
+        captures: &mut [Span],
+        observer: &mut O,
+    ) -> bool {
+        state.ensure_allocated();
+        // The arena is split in slots of result_len spans, so slots from a
+        // previous execution can't be reused if that length changed. No thread
+        // is alive between two executions, therefore we can simply drop them.
         state.result_len = captures.len();
+        state.cg_free.clear();
+        state.cg_free.push(0);
 
-        let Input {
-            subject,
-            span: Span { from, to },
+        let Haystack {
+            chars,
+            from,
+            prev: mut prev_char,
+            next,
             anchored,
             first_match,
-        } = input;
-
-        let mut prev_char = find_prev_char(subject, from);
+            longest,
+        } = haystack;
+        // Without any span to report, every match is as good as another.
+        let longest =
+            (longest || self.match_kind == MatchKind::LeftmostLongest) && state.result_len > 0;
 
         state.input_pos = from;
-        let first_thread = state.new_thread(0);
-        first_thread.write_reg(0, from, state);
+        let first_thread = state.new_thread::<O>(0);
+        first_thread.write_reg(0, from, state, observer);
         state.push_active(first_thread);
-        for c in subject[from..to].chars() {
-            self.step(state, prev_char, c.into());
+        for c in chars {
+            self.step(state, prev_char, c.into(), longest, observer);
             prev_char = c.into();
             match &state.best_match {
                 Some(_) if first_match || state.next.is_empty() => {
-                    state.write_best_match(captures);
+                    state.finish(observer);
+                    self.write_best_match(state, captures);
                     return true;
                 }
                 Some(_) => {
                     state.swap_and_advance_by(c.len_utf8());
                 }
                 None if !anchored => {
-                    let thread = state.new_thread(0);
-                    thread.write_reg(0, state.input_pos + c.len_utf8(), state);
+                    let thread = state.new_thread::<O>(0);
+                    thread.write_reg(0, state.input_pos + c.len_utf8(), state, observer);
                     state.push_next(thread);
                     state.swap_and_advance_by(c.len_utf8());
                 }
-                None => {
+                None if state.next.is_empty() => {
+                    state.finish(observer);
                     return false;
                 }
+                None => {
+                    state.swap_and_advance_by(c.len_utf8());
+                }
             }
         }
 
-        if to == subject.len() {
-            self.step(state, prev_char, Char::INPUT_BOUND);
-        } else {
-            // TODO: Find a nicer way to do this
-            let c = subject[to..subject.len()].chars().next().unwrap().into();
-            self.step(state, prev_char, c);
+        self.step(state, prev_char, next, longest, observer);
+        if !self.cut_ends.is_empty() {
+            state.accept_parked(longest, observer);
         }
 
+        state.finish(observer);
         if state.best_match.is_some() {
-            state.write_best_match(captures);
+            self.write_best_match(state, captures);
             true
         } else {
             false
         }
     }
+
+    /// Writes the groups of the best match, and records its pattern.
+    fn write_best_match(&self, state: &mut State, captures: &mut [Span]) {
+        let pc = state.best_match.as_ref().unwrap().pc;
+        let Accept(pattern) = self.bytecode.instructions[pc] else {
+            unreachable!("The best match stays at its Accept");
+        };
+        state.pattern = pattern;
+        state.write_best_match(captures);
+    }
+}
+
+/// The chars searched by [`PikeVM::exec_chars`], and their context.
+struct Haystack<I> {
+    chars: I,
+    /// The position of the first char.
+    from: usize,
+    /// The chars just before and just after the searched ones, or
+    /// [`Char::INPUT_BOUND`], for the assertions.
+    prev: Char,
+    next: Char,
+    anchored: bool,
+    first_match: bool,
+    /// Whether the longest match is kept, as in
+    /// [`MatchKind::LeftmostLongest`], whatever the match kind of the VM.
+    longest: bool,
+}
+
+impl<I> Haystack<I> {
+    /// The haystack of a valid input, whose chars are the ones of its span.
+    fn new(input: &Input<'_>, chars: I) -> Self {
+        let Input {
+            subject,
+            span: Span { from, to },
+            anchored,
+            first_match,
+            ..
+        } = *input;
+        Haystack {
+            chars,
+            from,
+            prev: input.prev_char(),
+            next: subject[to..]
+                .chars()
+                .next()
+                .map_or(Char::INPUT_BOUND, Char::from),
+            anchored,
+            first_match,
+            longest: false,
+        }
+    }
+}
+
+/// The chars of an interruptible search, see
+/// [`PikeVM::exec_interruptible`]. The iteration stops early once the check
+/// returns true, which is recorded.
+struct InterruptibleChars<'c, I> {
+    chars: I,
+    interval: usize,
+    /// The chars left until the next check.
+    countdown: usize,
+    interrupt: &'c dyn Fn() -> bool,
+    interrupted: bool,
+}
+
+impl<I: Iterator<Item = char>> Iterator for InterruptibleChars<'_, I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.countdown = self.interval;
+            if (self.interrupt)() {
+                self.interrupted = true;
+                return None;
+            }
+        }
+        self.chars.next()
+    }
+}
+
+impl RegexImpl for PikeVM {
+    type State = State;
+
+    fn new_state(&self) -> Self::State {
+        State::new(
+            self.capture_count,
+            self.cut_ends.len(),
+            self.bytecode.instructions.len(),
+            0,
+        )
+    }
+
+    fn reset_state(&self, state: &mut Self::State) {
+        state.reset();
+    }
+
+    fn exec<'s>(&self, input: Input<'s>, state: &mut Self::State, captures: &mut [Span]) -> bool {
+        if self.takes_small_path(captures) {
+            return self.exec_small(input, captures);
+        }
+        self.exec_internal(input, state, captures, &mut NoObserver)
+    }
+}
+
+/// Patterns with more instructions than this always take the general path,
+/// so that the visited set of [`PikeVM::exec_small`] fits on the stack.
+const SMALL_MAX_INSTRUCTIONS: usize = 512;
+
+/// Number of threads a queue of [`PikeVM::exec_small`] holds without
+/// allocating.
+const SMALL_QUEUE_CAP: usize = 32;
+
+/// A thread of [`PikeVM::exec_small`]: its pc, and where its match starts.
+type SmallThread = (usize, usize);
+
+/// A stack of threads stored inline, spilling to the heap when full.
+struct InlineStack {
+    inline: [SmallThread; SMALL_QUEUE_CAP],
+    len: usize,
+    spill: Vec<SmallThread>,
+}
+
+impl InlineStack {
+    fn new() -> Self {
+        Self {
+            inline: [(0, 0); SMALL_QUEUE_CAP],
+            len: 0,
+            spill: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, thread: SmallThread) {
+        if self.len < SMALL_QUEUE_CAP {
+            self.inline[self.len] = thread;
+            self.len += 1;
+        } else {
+            self.spill.push(thread);
+        }
+    }
+
+    fn pop(&mut self) -> Option<SmallThread> {
+        if let Some(thread) = self.spill.pop() {
+            Some(thread)
+        } else if self.len > 0 {
+            self.len -= 1;
+            Some(self.inline[self.len])
+        } else {
+            None
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn clear(&mut self) {
+        self.len = 0;
+        self.spill.clear();
+    }
+}
+
+/// The queues of [`PikeVM::exec_small`]. Both are stacks: `active` is popped
+/// in priority order, and `next` is reversed into it when advancing.
+struct SmallState {
+    active: InlineStack,
+    next: InlineStack,
+    visited: [u64; SMALL_MAX_INSTRUCTIONS / 64],
+    best_match: Option<Span>,
+    input_pos: usize,
+}
+
+impl SmallState {
+    fn swap_and_advance_by(&mut self, step: usize) {
+        self.input_pos += step;
+        while let Some(thread) = self.next.pop() {
+            self.active.push(thread);
+        }
+    }
+}
+
+fn class_contains(class: &[(Char, Char)], c: Char) -> bool {
+    for (start, end) in class.iter() {
+        if c < *start {
+            return false;
+        } else if c <= *end {
+            return true;
+        }
+    }
+    false
+}
+
+impl PikeVM {
+    /// Execution used when at most the overall match is requested. Threads
+    /// then only need their pc and their start, so the whole state lives on
+    /// the stack and the execution does not allocate (unless a queue
+    /// overflows). It follows [`PikeVM::exec_internal`] exactly, and must
+    /// return the same results.
+    fn exec_small(&self, input: Input<'_>, captures: &mut [Span]) -> bool {
+        if !input.valid() {
+            return false;
+        }
+        let Span { from, to } = input.span;
+        self.exec_small_chars(&input, input.subject[from..to].chars(), captures)
+    }
+
+    /// Whether the search takes the path of [`PikeVM::exec_small`].
+    fn takes_small_path(&self, captures: &[Span]) -> bool {
+        captures.len() <= 1
+            && self.bytecode.instructions.len() <= SMALL_MAX_INSTRUCTIONS
+            && self.cut_ends.is_empty()
+    }
+
+    /// The search of [`PikeVM::exec_small`] of a valid input, whose chars are
+    /// the ones of its span.
+    fn exec_small_chars(
+        &self,
+        input: &Input<'_>,
+        chars: impl Iterator<Item = char>,
+        captures: &mut [Span],
+    ) -> bool {
+        let Input {
+            subject,
+            span: Span { from, to },
+            anchored,
+            first_match,
+            ..
+        } = *input;
+
+        let mut prev_char = input.prev_char();
+
+        let mut state = SmallState {
+            active: InlineStack::new(),
+            next: InlineStack::new(),
+            visited: [0; SMALL_MAX_INSTRUCTIONS / 64],
+            best_match: None,
+            input_pos: from,
+        };
+        state.active.push((0, from));
+        let found = 'search: {
+            for c in chars {
+                self.step_small(&mut state, prev_char, c.into());
+                prev_char = c.into();
+                match state.best_match {
+                    Some(_) if first_match || state.next.is_empty() => break 'search true,
+                    Some(_) => state.swap_and_advance_by(c.len_utf8()),
+                    None if !anchored => {
+                        // The new thread has the lowest priority, so it is
+                        // the last one of the next queue.
+                        let start = state.input_pos + c.len_utf8();
+                        state.next.push((0, start));
+                        state.swap_and_advance_by(c.len_utf8());
+                    }
+                    None if state.next.is_empty() => break 'search false,
+                    None => state.swap_and_advance_by(c.len_utf8()),
+                }
+            }
+
+            let c = match subject[to..].chars().next() {
+                Some(c) => c.into(),
+                None => Char::INPUT_BOUND,
+            };
+            self.step_small(&mut state, prev_char, c);
+            state.best_match.is_some()
+        };
+        if found && let Some((result, rest)) = captures.split_first_mut() {
+            *result = state.best_match.unwrap();
+            rest.fill(Span::invalid());
+        }
+        found
+    }
+
+    /// Same as [`PikeVM::step`], for [`PikeVM::exec_small`].
+    fn step_small(&self, state: &mut SmallState, prev: Char, c: Char) {
+        let bytecode = self.bytecode.instructions.as_slice();
+        let longest = self.match_kind == MatchKind::LeftmostLongest;
+        state.visited[..bytecode.len().div_ceil(64)].fill(0);
+        while let Some((mut pc, start)) = state.active.pop() {
+            if longest && state.best_match.is_some_and(|best| start > best.from) {
+                continue;
+            }
+            loop {
+                if self.bytecode.barriers[pc] {
+                    let (word, bit) = (pc / 64, 1 << (pc % 64));
+                    if state.visited[word] & bit != 0 {
+                        break;
+                    }
+                    state.visited[word] |= bit;
+                }
+                match &bytecode[pc] {
+                    Consume(c2) => {
+                        if *c2 == c {
+                            state.next.push((pc + 1, start));
+                        }
+                        break;
+                    }
+                    ConsumeClass(class) => {
+                        if class_contains(class, c) {
+                            state.next.push((pc + 1, start));
+                        }
+                        break;
+                    }
+                    ConsumeOutlined(id) => {
+                        if class_contains(&self.bytecode.outlined_classes[*id], c) {
+                            state.next.push((pc + 1, start));
+                        }
+                        break;
+                    }
+                    Fork2(a, b) => {
+                        state.active.push((*b, start));
+                        pc = *a;
+                    }
+                    ForkN(branches) => {
+                        for target in branches[1..].iter().rev() {
+                            state.active.push((*target, start));
+                        }
+                        pc = branches[0];
+                    }
+                    Jmp(target) => pc = *target,
+                    WriteReg(_) => pc += 1,
+                    Accept(_) if longest => {
+                        let better = state.best_match.is_none_or(|best| {
+                            start < best.from || (start == best.from && state.input_pos > best.to)
+                        });
+                        if better {
+                            state.best_match = Some(Span {
+                                from: start,
+                                to: state.input_pos,
+                            });
+                        }
+                        break;
+                    }
+                    Accept(_) => {
+                        state.best_match = Some(Span {
+                            from: start,
+                            to: state.input_pos,
+                        });
+                        // Lower priority threads can't win anymore
+                        state.active.clear();
+                        break;
+                    }
+                    Assertion(look) if look_matches(*look, prev, c) => pc += 1,
+                    Assertion(_) => break,
+                    CutBegin(_) | CutEnd(_) => unreachable!("not taken by patterns with cuts"),
+                }
+            }
+        }
+    }
 }
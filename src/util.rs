@@ -5,10 +5,18 @@
 
 use std::{
     cmp::{max, min},
+    collections::HashMap,
     fmt,
-    ops::Range,
+    ops::{Index, Range},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+#[cfg(feature = "unicode-segmentation")]
+use unicode_segmentation::GraphemeCursor;
+
+use crate::thompson::bytecode::LookKind;
+
 /// Defines the input parameter to most matching methods on a [`crate::Regex`].
 ///
 /// # Fields
@@ -17,6 +25,13 @@ use std::{
 /// - `anchored`: If true, only matches starting at the beginning of `span` are considered (default: false).
 /// - `first_match`: If true, returns the first match found, not necessarily the leftmost (default: false).
 ///
+/// With `first_match`, the search stops at the first position where a match
+/// ends, and reports the match of highest priority among the ones ending
+/// there, with its captures: a greedy repetition ends as early as it can,
+/// e.g. `a+` matches a single `a`. This also holds for anchored searches,
+/// which then read no further than that end. Every engine reports the same
+/// match, and stops reading the input at its end.
+///
 /// Usually, you can just pass a `&str` to matching methods, but `Input` allows more control for advanced use cases.
 #[derive(Clone)]
 pub struct Input<'s> {
@@ -24,6 +39,12 @@ pub struct Input<'s> {
     pub span: Span,
     pub anchored: bool,
     pub first_match: bool,
+    /// The char before the span, known from the position where the span
+    /// started, see [`Input::with_look_behind`].
+    pub(crate) look_behind: Option<(usize, Char)>,
+    /// The end of the match the input was advanced past, see
+    /// [`Input::advance_past`]. The next search skips an empty match there.
+    pub(crate) previous_match_end: Option<usize>,
 }
 
 impl<'s> Input<'s> {
@@ -33,6 +54,8 @@ impl<'s> Input<'s> {
             span: (0..subject.len()).into(),
             anchored: false,
             first_match: false,
+            look_behind: None,
+            previous_match_end: None,
         }
     }
 
@@ -48,11 +71,139 @@ impl<'s> Input<'s> {
         self
     }
 
+    /// Sets the span to search. A span which is not a valid range of char
+    /// boundaries of the subject never matches, see [`Input::try_span`] to
+    /// reject it instead.
     pub fn span(mut self, value: Span) -> Self {
         self.span = value;
         self
     }
 
+    /// Like [`Input::span`], but fails if the span is not a range of char
+    /// boundaries of the subject, instead of searching it for no match.
+    ///
+    /// ```rust
+    /// use gregex::util::{Input, InvalidSpan};
+    ///
+    /// assert!(Input::new("héllo").try_span((3..6).into()).is_ok());
+    /// let err = Input::new("héllo").try_span((2..6).into()).err();
+    /// assert_eq!(err, Some(InvalidSpan { from: 2, to: 6 }));
+    /// ```
+    pub fn try_span(self, value: Span) -> Result<Self, InvalidSpan> {
+        let input = self.span(value);
+        if !input.valid() {
+            return Err(InvalidSpan {
+                from: value.from,
+                to: value.to,
+            });
+        }
+        Ok(input)
+    }
+
+    /// Sets the char just before the span, which the assertions at its start
+    /// look at (e.g. `\b`, or `^` in multi-line mode), so that the searches
+    /// don't read it in the subject. The caller is responsible for the char
+    /// being right, a wrong one changes the matches of these assertions.
+    ///
+    /// This is meant for the loops which already know it, e.g. from the end
+    /// of the previous match. It only applies to the current start of the
+    /// span, and is ignored once the start moves: set the span first.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    /// use gregex::util::Input;
+    ///
+    /// let re = Regex::pike_vm(r"(?m)^b").unwrap();
+    /// let input = Input::new("ab").span((1..2).into());
+    /// assert!(re.find(input.clone()).is_none());
+    /// assert!(re.find(input.with_look_behind('\n')).is_some());
+    /// ```
+    pub fn with_look_behind(mut self, c: char) -> Self {
+        self.look_behind = Some((self.span.from, c.into()));
+        self
+    }
+
+    /// Like [`Input::with_look_behind`], but with nothing before the span,
+    /// as if it started the haystack, e.g. for `^` or `\A`.
+    pub fn at_haystack_start(mut self) -> Self {
+        self.look_behind = Some((self.span.from, Char::INPUT_BOUND));
+        self
+    }
+
+    /// Looks for the char before the span once, so that the searches on the
+    /// clones of this input don't each look for it again. The matches are
+    /// the same as without it.
+    pub fn cache_look_behind(mut self) -> Self {
+        self.look_behind = Some((self.span.from, self.prev_char()));
+        self
+    }
+
+    /// The char before the span, for the assertions at its start, or
+    /// [`Char::INPUT_BOUND`] if there is none.
+    pub(crate) fn prev_char(&self) -> Char {
+        match self.look_behind {
+            Some((from, c)) if from == self.span.from => c,
+            _ => find_prev_char(self.subject, self.span.from),
+        }
+    }
+
+    /// Moves the start of the span to where the next non-overlapping match
+    /// can start after the given one (see [`Match::next_match_start`]), like
+    /// [`crate::Regex::find_all`] does between its searches: to the end of
+    /// the match, or one char further after an empty match. After an empty
+    /// match at the end of the span, the span is no longer valid, and the
+    /// searches report no match.
+    ///
+    /// Like in the regex crate, the next search then skips an empty match
+    /// right at the end of the given one (e.g. the one at the end of `aaa`
+    /// for `a*`), and looks for a match from the next char instead. So the
+    /// searches of `find_all`, `split`, `replace_all` and the other iterators
+    /// all see the same matches.
+    ///
+    /// This is how to go through the matches one at a time, where `find_all`
+    /// doesn't fit, e.g. to change the input between the searches:
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    /// use gregex::util::Input;
+    ///
+    /// let re = Regex::pike_vm(r"\d*").unwrap();
+    /// let mut input = Input::new("1é23");
+    /// let mut found = Vec::new();
+    /// while let Some(m) = re.find(input.clone()) {
+    ///     found.push(m.as_str());
+    ///     input.advance_past(&m);
+    /// }
+    /// assert_eq!(found, re.find_all("1é23").map(|m| m.as_str()).collect::<Vec<_>>());
+    /// ```
+    ///
+    /// Setting the start of the span by hand is more error prone: after an
+    /// empty match, the next search must start one char further, which may
+    /// be more than one byte, and a span which doesn't start on a char
+    /// boundary never matches.
+    ///
+    /// The char before the new start is taken from the match, see
+    /// [`Input::with_look_behind`].
+    pub fn advance_past(&mut self, m: &Match<'_>) {
+        debug_assert!(
+            std::ptr::eq(self.subject, m.subject),
+            "the match is not in the subject of the input"
+        );
+        debug_assert!(
+            m.span.valid() && self.span.from <= m.span.from,
+            "the match is not in the span of the input"
+        );
+        self.span.from = m.next_match_start();
+        // The last char of the match, or the one skipped after an empty one.
+        let skipped = if m.span.empty() {
+            m.subject.get(m.span.from..).and_then(|s| s.chars().next())
+        } else {
+            m.as_str().chars().next_back()
+        };
+        self.look_behind = skipped.map(|c| (self.span.from, c.into()));
+        self.previous_match_end = Some(m.span.to);
+    }
+
     /// Returns true if the span is valid and the boundaries are valid UTF-8 boundaries in the subject.
     pub fn valid(&self) -> bool {
         self.span.valid()
@@ -72,7 +223,7 @@ impl<'s> From<&'s str> for Input<'s> {
 /// to share it with the jitted code.
 ///
 /// `from` is the start byte offset (inclusive), `to` is the end byte offset (exclusive).
-#[derive(Copy, Debug, Clone)]
+#[derive(Copy, Clone)]
 #[repr(C)]
 pub struct Span {
     pub from: usize,
@@ -100,6 +251,65 @@ impl Span {
     }
 }
 
+/// Formatted like a range, e.g. `3..6`.
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.from, self.to)
+    }
+}
+
+/// How many chars of a matched text the `Debug` formats show, see
+/// [`DebugText`].
+const DEBUG_TEXT_MAX_CHARS: usize = 64;
+
+/// A matched text in the `Debug` format of a match: quoted and escaped like a
+/// `str`, and cut after [`DEBUG_TEXT_MAX_CHARS`] chars, followed by `...` when
+/// it is. The subject itself is never shown, it may be huge.
+struct DebugText<'a>(&'a str);
+
+impl fmt::Debug for DebugText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.char_indices().nth(DEBUG_TEXT_MAX_CHARS) {
+            Some((end, _)) => write!(f, "{:?}...", &self.0[..end]),
+            None => write!(f, "{:?}", self.0),
+        }
+    }
+}
+
+/// Formats a match, e.g. `Match { start: 3, end: 6, string: "foo" }`, with
+/// the name of its type.
+fn debug_match(f: &mut fmt::Formatter<'_>, name: &str, subject: &str, span: Span) -> fmt::Result {
+    f.debug_struct(name)
+        .field("start", &span.from)
+        .field("end", &span.to)
+        .field("string", &DebugText(&subject[span.from..span.to]))
+        .finish()
+}
+
+/// Formats the groups of a match, e.g. `Captures({0: Some("ab"), 1: None})`,
+/// with the name of its type.
+fn debug_captures(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    subject: &str,
+    spans: &[Span],
+) -> fmt::Result {
+    struct Groups<'a>(&'a str, &'a [Span]);
+
+    impl fmt::Debug for Groups<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let Groups(subject, spans) = *self;
+            let groups = spans.iter().map(|span| {
+                span.valid()
+                    .then(|| DebugText(&subject[span.from..span.to]))
+            });
+            f.debug_map().entries(groups.enumerate()).finish()
+        }
+    }
+
+    f.debug_tuple(name).field(&Groups(subject, spans)).finish()
+}
+
 impl From<Range<usize>> for Span {
     fn from(value: Range<usize>) -> Self {
         Self {
@@ -117,12 +327,22 @@ impl From<Span> for Range<usize> {
 
 /// Represents a successful non-capturing match. Contains only the bounds of the
 /// overall match within the subject string. The span is guaranteed to be valid.
-#[derive(Copy, Debug, Clone)]
+///
+/// Its `Debug` format shows the bounds and the matched text, e.g. `Match {
+/// start: 3, end: 6, string: "foo" }`, without the subject. Long texts are cut
+/// after 64 chars, followed by `...`.
+#[derive(Copy, Clone)]
 pub struct Match<'s> {
     pub subject: &'s str,
     pub span: Span,
 }
 
+impl fmt::Debug for Match<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_match(f, "Match", self.subject, self.span)
+    }
+}
+
 impl<'s> Match<'s> {
     pub fn new(subject: &'s str, span: impl Into<Span>) -> Self {
         let span = span.into();
@@ -144,41 +364,335 @@ impl<'s> Match<'s> {
         self.span.to
     }
 
+    /// Returns the span of this match extended outward to the nearest
+    /// grapheme cluster boundaries, so that it doesn't split a user-perceived
+    /// character (e.g. a letter and its combining accent). Meant for display,
+    /// the span of the match itself is unchanged.
+    #[cfg(feature = "unicode-segmentation")]
+    pub fn snap_to_graphemes(&self) -> Span {
+        Span {
+            from: grapheme_floor(self.subject, self.span.from),
+            to: grapheme_ceil(self.subject, self.span.to),
+        }
+    }
+
     /// Returns the byte-index where the next non-overlapping match could start.
     /// This takes into account empty matches and advances at least one codepoint
-    /// to avoid infinite loops.
+    /// to avoid infinite loops. After an empty match at the end of the subject,
+    /// this is past the end, so that no span starting there is valid.
     pub fn next_match_start(&self) -> usize {
-        if self.span.empty() {
-            if self.span.from == self.subject.len() {
-                self.span.from + 1
+        if !self.span.empty() {
+            self.span.to
+        } else if self.span.from >= self.subject.len() {
+            self.span.from + 1
+        } else {
+            // Must advance to next codepoint otherwise we would always return
+            // the same empty match forever. The span may not start on a char
+            // boundary if it wasn't found by a search.
+            self.subject.ceil_char_boundary(self.span.from + 1)
+        }
+    }
+}
+
+/// Statistics of the matches of [`crate::Regex::find_all`], see
+/// [`crate::Regex::match_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    /// The lengths of the matches, in bytes. Their count is the number of
+    /// matches.
+    pub lengths: LengthStats,
+    /// The lengths of the gaps between the end of a match and the start of
+    /// the next one, in bytes. There is one less gap than matches.
+    pub gaps: LengthStats,
+}
+
+/// The distribution of some lengths, see [`MatchStats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LengthStats {
+    pub count: u64,
+    pub total: u64,
+    /// The smallest length, or 0 if there is none.
+    pub min: usize,
+    /// The largest length, or 0 if there is none.
+    pub max: usize,
+    /// The number of lengths in each bucket, see [`LengthStats::bucket`].
+    pub histogram: [u64; LengthStats::BUCKETS],
+}
+
+impl LengthStats {
+    /// The number of buckets of the histogram.
+    pub const BUCKETS: usize = 16;
+
+    /// The bucket of the histogram counting the given length: 0 for the empty
+    /// ones, then `i` for the lengths from `2^(i-1)` to `2^i - 1`, except for
+    /// the last one, which also counts all the longer ones.
+    pub fn bucket(len: usize) -> usize {
+        match len.checked_ilog2() {
+            Some(log) => (log as usize + 1).min(Self::BUCKETS - 1),
+            None => 0,
+        }
+    }
+
+    /// Adds a length to the distribution.
+    pub fn add(&mut self, len: usize) {
+        if self.count == 0 || len < self.min {
+            self.min = len;
+        }
+        self.max = self.max.max(len);
+        self.count += 1;
+        self.total += len as u64;
+        self.histogram[Self::bucket(len)] += 1;
+    }
+}
+
+/// How long building a regex took, by phase, see [`crate::Builder::timings`].
+/// The phases an engine doesn't run are zero, e.g. the assembly for the Pike
+/// VM, or every phase for the specialized engines (see
+/// [`crate::Builder::specialize`]), whose build is only in the total.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BuildTimings {
+    /// Parsing the pattern to its [`regex_syntax::hir::Hir`].
+    pub parse: Duration,
+    /// Compiling the hir to bytecode.
+    pub compile: Duration,
+    /// Generating and assembling the machine code of the JIT.
+    pub assemble: Duration,
+    /// The whole build, which also includes the work outside of the phases,
+    /// such as finding the prefilter.
+    pub total: Duration,
+}
+
+/// How long searches took, by phase, see [`crate::Regex::find_timed`]. The
+/// durations of successive searches add up, so a single value can time a
+/// whole loop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecTimings {
+    /// The number of searches timed.
+    pub searches: u64,
+    /// Getting a state for the search, which allocates it the first time.
+    pub setup: Duration,
+    /// Running the prefilter and the engine.
+    pub scan: Duration,
+    /// Building the match or the captures from the spans found.
+    pub extract: Duration,
+    /// The whole searches, the sum of the phases.
+    pub total: Duration,
+}
+
+impl ExecTimings {
+    /// Adds a search whose phases ended at the given instants.
+    pub(crate) fn add(&mut self, started: Instant, set_up: Instant, scanned: Instant) {
+        let extracted = Instant::now();
+        self.searches += 1;
+        self.setup += set_up - started;
+        self.scan += scanned - set_up;
+        self.extract += extracted - scanned;
+        self.total += extracted - started;
+    }
+}
+
+/// A match whose span was extended to grapheme cluster boundaries, see
+/// [`crate::Regex::find_graphemes`].
+#[cfg(feature = "unicode-segmentation")]
+#[derive(Copy, Debug, Clone)]
+pub struct GraphemeMatch<'s> {
+    /// The match as found by the regex.
+    pub raw: Match<'s>,
+    /// The span of the match, snapped to grapheme boundaries.
+    pub span: Span,
+}
+
+#[cfg(feature = "unicode-segmentation")]
+impl<'s> GraphemeMatch<'s> {
+    /// Returns the snapped substring.
+    pub fn as_str(&self) -> &'s str {
+        &self.raw.subject[self.span.from..self.span.to]
+    }
+
+    /// Start in byte of the snapped span
+    pub fn start(&self) -> usize {
+        self.span.from
+    }
+
+    /// End in byte of the snapped span
+    pub fn end(&self) -> usize {
+        self.span.to
+    }
+}
+
+/// A match in several slices, searched as if they were concatenated with a
+/// separator between each of them, see [`crate::Regex::find_in_slices`].
+#[derive(Copy, Debug, Clone)]
+pub struct MultiSliceMatch<'s> {
+    pub slices: &'s [&'s str],
+    pub separator: Option<char>,
+    /// The bounds of the match in the concatenation of the slices (and
+    /// separators).
+    pub span: Span,
+}
+
+impl MultiSliceMatch<'_> {
+    /// Returns the slice index and the byte offset in that slice where the
+    /// match starts. A match starting with a separator starts at the end of
+    /// the slice before it.
+    pub fn start(&self) -> (usize, usize) {
+        // The last slice starting at or before the position.
+        let mut slice_start = 0;
+        let mut start = (0, 0);
+        for (i, slice) in self.slices.iter().enumerate() {
+            if slice_start > self.span.from {
+                break;
+            }
+            start = (i, self.span.from - slice_start);
+            slice_start += slice.len() + self.separator_len();
+        }
+        start
+    }
+
+    /// Returns the slice index and the byte offset in that slice where the
+    /// match ends. A match ending with a separator ends at the start of the
+    /// slice after it.
+    pub fn end(&self) -> (usize, usize) {
+        // The first slice ending at or after the position.
+        let mut slice_start = 0;
+        for (i, slice) in self.slices.iter().enumerate() {
+            if self.span.to <= slice_start + slice.len() {
+                return (i, self.span.to - slice_start);
+            }
+            slice_start += slice.len() + self.separator_len();
+        }
+        (0, 0)
+    }
+
+    /// Returns the matched text, including the separators it spans.
+    pub fn extract_to_string(&self) -> String {
+        let (start_slice, start_offset) = self.start();
+        let (end_slice, end_offset) = self.end();
+        let mut text = String::with_capacity(self.span.to - self.span.from);
+        let spanned = self.slices.iter().enumerate();
+        for (i, slice) in spanned.take(end_slice + 1).skip(start_slice) {
+            let from = if i == start_slice { start_offset } else { 0 };
+            let to = if i == end_slice {
+                end_offset
             } else {
-                // Must advance to next codepoint otherwise we would always return
-                // the same empty match forever.
-                // TODO: Find a less manual way to do this
-                let b = self.subject.as_bytes()[self.span.from];
-                if b < 0x80 {
-                    self.span.from + 1
-                } else if b < 0xE0 {
-                    self.span.from + 2
-                } else if b < 0xF0 {
-                    self.span.from + 3
-                } else {
-                    self.span.from + 4
-                }
+                slice.len()
+            };
+            text.push_str(&slice[from..to]);
+            if i < end_slice {
+                text.extend(self.separator);
             }
-        } else {
-            self.span.to
         }
+        text
+    }
+
+    fn separator_len(&self) -> usize {
+        self.separator.map_or(0, char::len_utf8)
+    }
+}
+
+/// The capture groups of a pattern: their number (including group 0) and
+/// their names. Built once with the regex, and shared by the captures of its
+/// matches.
+///
+/// ```rust
+/// use gregex::Regex;
+///
+/// let re = Regex::pike_vm(r"(?P<year>\d{4})-(\d{2})").unwrap();
+/// let info = re.group_info();
+/// assert_eq!(info.len(), 3);
+/// assert_eq!(info.index("year"), Some(1));
+/// assert_eq!(info.names().collect::<Vec<_>>(), [None, Some("year"), None]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupInfo {
+    names: Box<[Option<Box<str>>]>,
+    indices: HashMap<Box<str>, usize>,
+    /// See [`GroupInfo::static_len`].
+    static_len: Option<usize>,
+}
+
+impl GroupInfo {
+    /// The groups with these names, by index, group 0 first. The names must be
+    /// unique, which regex-syntax checks when parsing.
+    pub fn new(names: impl IntoIterator<Item = Option<Box<str>>>) -> Self {
+        let names: Box<[_]> = names.into_iter().collect();
+        let indices = names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| Some((name.clone()?, index)))
+            .collect();
+        Self {
+            names,
+            indices,
+            static_len: None,
+        }
+    }
+
+    /// Sets the number of groups participating in every match, see
+    /// [`GroupInfo::static_len`].
+    pub fn with_static_len(mut self, static_len: Option<usize>) -> Self {
+        self.static_len = static_len;
+        self
+    }
+
+    /// Returns the number of groups (including group 0) which participate in
+    /// every match, if it is the same for all of them and known, see
+    /// [`Captures::extract`]. Groups in different branches of an alternation
+    /// may be counted together, e.g. it is 2 for `(a)|(b)`.
+    pub fn static_len(&self) -> Option<usize> {
+        self.static_len
+    }
+
+    /// `len` groups without names.
+    pub fn unnamed(len: usize) -> Self {
+        Self::new(std::iter::repeat_n(None, len))
+    }
+
+    /// Returns the number of groups, including group 0.
+    // Never empty, group 0 is always there.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns the name of the group, or `None` if it has no name or is out
+    /// of range.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index)?.as_deref()
+    }
+
+    /// Returns the index of the group with this name.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    /// Returns the name of every group, in order, `None` for the groups
+    /// without names (always group 0).
+    pub fn names(&self) -> impl ExactSizeIterator<Item = Option<&str>> + '_ {
+        self.names.iter().map(|name| name.as_deref())
     }
 }
 
 /// Represents a successful capturing match. Contains the bounds (if any) of all
 /// capture groups defined in the pattern, including the implicit group 0 (the
 /// overall match).
-#[derive(Debug, Clone)]
+///
+/// Its `Debug` format shows the text of each group, or `None` for the ones
+/// which didn't participate, e.g. `Captures({0: Some("ab"), 1: None})`. Long
+/// texts are cut like the ones of [`Match`].
+#[derive(Clone)]
 pub struct Captures<'s> {
     subject: &'s str,
     spans: Box<[Span]>,
+    /// The names of the groups, if they are known, see
+    /// [`Captures::with_group_info`].
+    group_info: Option<Arc<GroupInfo>>,
+}
+
+impl fmt::Debug for Captures<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_captures(f, "Captures", self.subject, &self.spans)
+    }
 }
 
 impl<'s> Captures<'s> {
@@ -196,14 +710,484 @@ impl<'s> Captures<'s> {
         })
     }
 
+    /// Returns true if the given capture group participated in the match.
+    /// Returns false for groups in a branch that was not taken (e.g. group 1
+    /// of `(a)|(b)` matching "b"), and for out of range indices.
+    pub fn participated(&self, group_index: usize) -> bool {
+        self.spans.get(group_index).is_some_and(Span::valid)
+    }
+
+    /// Returns an iterator over the participation of every capture group,
+    /// see [`Captures::participated`].
+    pub fn participation(&self) -> impl Iterator<Item = bool> + '_ {
+        self.spans.iter().map(Span::valid)
+    }
+
+    /// Returns the match of every capture group, in order, `None` for the
+    /// groups which did not participate in the match.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(a)|(b)").unwrap();
+    /// let caps = re.find_captures("b").unwrap();
+    /// let groups: Vec<_> = caps.iter().map(|m| m.map(|m| m.as_str())).collect();
+    /// assert_eq!(groups, [Some("b"), None, Some("b")]);
+    /// ```
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Option<Match<'s>>> + '_ {
+        (0..self.group_len()).map(|i| self.get(i))
+    }
+
+    /// Returns the text of group 0 and of the `N` groups participating in the
+    /// match, like the method of the same name of the regex crate. This is
+    /// for the patterns whose matches all have the same number of groups, see
+    /// [`GroupInfo::static_len`], e.g. to destructure the groups:
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(\w+)@(\w+)\.com|<(\w+)@(\w+)>").unwrap();
+    /// let caps = re.find_captures("write to <me@example>").unwrap();
+    /// let (whole, [user, host]) = caps.extract();
+    /// assert_eq!((whole, user, host), ("<me@example>", "me", "example"));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If the number of groups varies between the matches (e.g. `(a)?`), or
+    /// isn't known (e.g. for captures built with [`Captures::new`]), or if it
+    /// is not `N` plus group 0. Also if fewer groups are reported, e.g. with
+    /// [`crate::Regex::find_captures_n`].
+    pub fn extract<const N: usize>(&self) -> (&'s str, [&'s str; N]) {
+        let len = self
+            .group_info
+            .as_ref()
+            .and_then(|info| info.static_len())
+            .expect("number of capture groups can vary in a match")
+            - 1;
+        assert_eq!(N, len, "asked for {N} groups, but must ask for {len}");
+        let mut matched = self.iter().flatten();
+        let whole = matched.next().expect("a match").as_str();
+        let groups = [(); N].map(|_| matched.next().expect("too few matching groups").as_str());
+        (whole, groups)
+    }
+
     /// Returns the overall match (group 0).
     pub fn group0(&self) -> Match<'s> {
         // Must always be set
         self.get(0).unwrap()
     }
 
+    /// Captures with the given spans, whose groups have no names, see
+    /// [`Captures::with_group_info`].
     pub fn new(subject: &'s str, spans: Box<[Span]>) -> Self {
-        Self { subject, spans }
+        Self {
+            subject,
+            spans,
+            group_info: None,
+        }
+    }
+
+    /// Sets the groups the spans are the ones of, for their names. There may
+    /// be fewer spans than groups, e.g. with [`crate::Regex::find_captures_n`].
+    pub fn with_group_info(mut self, group_info: Arc<GroupInfo>) -> Self {
+        self.group_info = Some(group_info);
+        self
+    }
+
+    /// Returns the match of the group with the given name, or `None` if it
+    /// did not participate in the match, or if there is no such group.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(?P<year>\d{4})-(?P<month>\d{2})").unwrap();
+    /// let caps = re.find_captures("released in 2024-03").unwrap();
+    /// assert_eq!(caps.name("year").unwrap().as_str(), "2024");
+    /// assert_eq!(caps.name("month").unwrap().as_str(), "03");
+    /// assert!(caps.name("day").is_none());
+    /// ```
+    pub fn name(&self, name: &str) -> Option<Match<'s>> {
+        self.get(self.group_info.as_ref()?.index(name)?)
+    }
+
+    /// Returns the name of every group of the captures, in order, `None` for
+    /// the groups without names (always group 0).
+    pub fn group_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        (0..self.group_len()).map(|i| self.group_info.as_ref()?.name(i))
+    }
+
+    /// Returns the number of capture groups (including group 0).
+    pub fn group_len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Returns the match of every capture group, in order, `None` for the
+    /// groups which did not participate in the match.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(\d{4})-(\d{2})-(\d{2})").unwrap();
+    /// let caps = re.find_captures("released on 2024-03-17").unwrap();
+    /// let groups: Vec<_> = caps.all_groups().iter().map(|m| m.unwrap().as_str()).collect();
+    /// assert_eq!(groups, ["2024-03-17", "2024", "03", "17"]);
+    /// ```
+    pub fn all_groups(&self) -> Vec<Option<Match<'s>>> {
+        self.groups_iter().map(|(_, _, group)| group).collect()
+    }
+
+    /// Returns an iterator over every capture group, yielding its index, its
+    /// name, and its match (if it participated).
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(\d{4})-(?P<month>\d{2})(?:-(\d{2}))?").unwrap();
+    /// let caps = re.find_captures("month: 2024-03").unwrap();
+    /// for (index, name, group) in caps.groups_iter() {
+    ///     assert_eq!(name, (index == 2).then_some("month"));
+    ///     match index {
+    ///         0 | 1 | 2 => assert!(group.is_some()),
+    ///         _ => assert!(group.is_none()),
+    ///     }
+    /// }
+    /// ```
+    pub fn groups_iter(
+        &self,
+    ) -> impl Iterator<Item = (usize, Option<&str>, Option<Match<'s>>)> + '_ {
+        self.group_names()
+            .enumerate()
+            .map(|(i, name)| (i, name, self.get(i)))
+    }
+
+    /// Appends the replacement to `dst`, with every reference to a group
+    /// replaced by its match, see [`crate::Regex::replace_all`]. `$$` is
+    /// replaced by `$`.
+    ///
+    /// Like in the regex crate, a reference is either `$` followed by the
+    /// longest run of letters, digits and underscores, or `${...}` with
+    /// anything but `}` inside, referring to a group by number if it is one,
+    /// and by name otherwise. References to groups which don't exist, or did
+    /// not participate, are replaced by nothing. A `$` which doesn't start a
+    /// reference is kept.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(?P<key>\w+)=(\w+)?").unwrap();
+    /// let caps = re.find_captures("color=").unwrap();
+    /// let mut dst = String::new();
+    /// caps.expand("${key}_1: [$2], $$5, $", &mut dst);
+    /// assert_eq!(dst, "color_1: [], $5, $");
+    /// ```
+    pub fn expand(&self, replacement: &str, dst: &mut String) {
+        let mut rest = replacement;
+        while let Some(dollar) = rest.find('$') {
+            dst.push_str(&rest[..dollar]);
+            rest = &rest[dollar + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                dst.push('$');
+                rest = after;
+                continue;
+            }
+            let (name, after) = match rest.strip_prefix('{') {
+                Some(braced) => match braced.find('}') {
+                    Some(end) => (&braced[..end], &braced[end + 1..]),
+                    None => ("", rest),
+                },
+                None => {
+                    let end = rest
+                        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                        .unwrap_or(rest.len());
+                    (&rest[..end], &rest[end..])
+                }
+            };
+            if name.is_empty() {
+                // Not a reference, the dollar is kept.
+                dst.push('$');
+                continue;
+            }
+            let group = match name.parse() {
+                Ok(i) => self.get(i),
+                Err(_) => self.name(name),
+            };
+            if let Some(m) = group {
+                dst.push_str(m.as_str());
+            }
+            rest = after;
+        }
+        dst.push_str(rest);
+    }
+
+    /// Returns the match of every named capture group, by name.
+    ///
+    /// ```rust
+    /// use gregex::Regex;
+    ///
+    /// let re = Regex::pike_vm(r"(?P<y>\d{4})-(?P<m>\d{2})(?:-(?P<d>\d{2}))?").unwrap();
+    /// let caps = re.find_captures("2024-03").unwrap();
+    /// let map = caps.to_name_map();
+    /// assert_eq!(map.len(), 3);
+    /// assert_eq!(map["y"].unwrap().as_str(), "2024");
+    /// assert!(map["d"].is_none());
+    /// ```
+    pub fn to_name_map(&self) -> HashMap<&str, Option<Match<'s>>> {
+        self.groups_iter()
+            .filter_map(|(_, name, group)| Some((name?, group)))
+            .collect()
+    }
+}
+
+/// Returns the text of the group, like the regex crate: panics if it did not
+/// participate in the match, or if there is no such group.
+impl Index<usize> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, i: usize) -> &str {
+        self.get(i)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group at index '{i}'"))
+    }
+}
+
+/// Returns the text of the group with this name, like the regex crate: panics
+/// if it did not participate in the match, or if there is no such group.
+impl Index<&str> for Captures<'_> {
+    type Output = str;
+
+    fn index(&self, name: &str) -> &str {
+        self.name(name)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group named '{name}'"))
+    }
+}
+
+/// Like [`Input`], but owns its subject through an [`Arc<str>`], which is then
+/// shared with every match found. Used by the owning iterators, such as
+/// [`crate::Regex::find_all_owned`].
+#[derive(Debug, Clone)]
+pub struct OwnedInput {
+    pub subject: Arc<str>,
+    pub span: Span,
+    pub anchored: bool,
+    pub first_match: bool,
+}
+
+impl OwnedInput {
+    pub fn new(subject: impl Into<Arc<str>>) -> Self {
+        let subject = subject.into();
+        Self {
+            span: (0..subject.len()).into(),
+            subject,
+            anchored: false,
+            first_match: false,
+        }
+    }
+
+    /// Sets whether to return the first match found.
+    pub fn first_match(mut self, value: bool) -> Self {
+        self.first_match = value;
+        self
+    }
+
+    /// Sets whenever to do an anchored match.
+    pub fn anchored(mut self, value: bool) -> Self {
+        self.anchored = value;
+        self
+    }
+
+    pub fn span(mut self, value: Span) -> Self {
+        self.span = value;
+        self
+    }
+
+    /// Returns a borrowed [`Input`] with the same parameters.
+    pub fn as_input(&self) -> Input<'_> {
+        Input {
+            subject: &self.subject,
+            span: self.span,
+            anchored: self.anchored,
+            first_match: self.first_match,
+            look_behind: None,
+            previous_match_end: None,
+        }
+    }
+}
+
+impl From<Arc<str>> for OwnedInput {
+    fn from(subject: Arc<str>) -> Self {
+        Self::new(subject)
+    }
+}
+
+impl From<String> for OwnedInput {
+    fn from(subject: String) -> Self {
+        Self::new(subject)
+    }
+}
+
+impl From<&str> for OwnedInput {
+    fn from(subject: &str) -> Self {
+        Self::new(subject)
+    }
+}
+
+/// Like [`Match`], but shares ownership of its subject.
+#[derive(Clone)]
+pub struct OwnedMatch {
+    pub subject: Arc<str>,
+    pub span: Span,
+}
+
+impl fmt::Debug for OwnedMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_match(f, "OwnedMatch", &self.subject, self.span)
+    }
+}
+
+impl OwnedMatch {
+    pub fn new(subject: Arc<str>, span: impl Into<Span>) -> Self {
+        let span = span.into();
+        Self { subject, span }
+    }
+
+    /// Returns a borrowed [`Match`] with the same bounds.
+    pub fn as_match(&self) -> Match<'_> {
+        Match::new(&self.subject, self.span)
+    }
+
+    /// Returns the matched substring.
+    pub fn as_str(&self) -> &str {
+        &self.subject[self.span.from..self.span.to]
+    }
+
+    /// Start in byte of this match
+    pub fn start(&self) -> usize {
+        self.span.from
+    }
+
+    /// End in byte of this match
+    pub fn end(&self) -> usize {
+        self.span.to
+    }
+
+    /// See [`Match::next_match_start`].
+    pub fn next_match_start(&self) -> usize {
+        self.as_match().next_match_start()
+    }
+}
+
+/// A reusable buffer for the capture groups of a match, filled by
+/// [`crate::Regex::captures_read`]. Unlike [`Captures`], it doesn't borrow the
+/// haystack, so the same buffer can be used for every search of a loop,
+/// without allocating.
+///
+/// ```rust
+/// use gregex::Regex;
+///
+/// let re = Regex::pike_vm(r"(\w+)=(\d+)?").unwrap();
+/// let mut locs = re.capture_locations();
+/// for (line, value) in [("a=1", Some((2, 3))), ("b=", None)] {
+///     assert!(re.captures_read(&mut locs, line).is_some());
+///     assert_eq!(locs.get(2), value);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaptureLocations {
+    spans: Box<[Span]>,
+}
+
+impl CaptureLocations {
+    /// A buffer for `len` groups, including group 0, which is always there.
+    /// See [`crate::Regex::capture_locations`] for the buffer of a regex.
+    pub fn new(len: usize) -> Self {
+        Self {
+            spans: vec![Span::invalid(); len.max(1)].into_boxed_slice(),
+        }
+    }
+
+    /// Returns the start and end of the given group, or `None` if it did not
+    /// participate in the last match, or is out of range.
+    pub fn get(&self, group_index: usize) -> Option<(usize, usize)> {
+        let span = self.spans.get(group_index).filter(|span| span.valid())?;
+        Some((span.from, span.to))
+    }
+
+    /// Returns the number of groups of the buffer, including group 0.
+    // Never empty, group 0 is always there.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    /// Returns the groups of the last match as [`Captures`] of its subject,
+    /// which copies them.
+    pub fn to_captures<'s>(&self, subject: &'s str) -> Captures<'s> {
+        Captures::new(subject, self.spans.clone())
+    }
+
+    /// Marks every group as not participating, and returns the buffer for the
+    /// next search to write to.
+    pub(crate) fn clear(&mut self) -> &mut [Span] {
+        self.spans.fill(Span::invalid());
+        &mut self.spans
+    }
+}
+
+/// Like [`Captures`], but shares ownership of its subject.
+#[derive(Clone)]
+pub struct OwnedCaptures {
+    subject: Arc<str>,
+    spans: Box<[Span]>,
+    group_info: Option<Arc<GroupInfo>>,
+}
+
+impl fmt::Debug for OwnedCaptures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        debug_captures(f, "OwnedCaptures", &self.subject, &self.spans)
+    }
+}
+
+impl OwnedCaptures {
+    pub fn new(subject: Arc<str>, spans: Box<[Span]>) -> Self {
+        Self {
+            subject,
+            spans,
+            group_info: None,
+        }
+    }
+
+    /// See [`Captures::with_group_info`].
+    pub fn with_group_info(mut self, group_info: Arc<GroupInfo>) -> Self {
+        self.group_info = Some(group_info);
+        self
+    }
+
+    /// See [`Captures::name`].
+    pub fn name(&self, name: &str) -> Option<OwnedMatch> {
+        self.get(self.group_info.as_ref()?.index(name)?)
+    }
+
+    /// Returns the match for the given capture group index, or `None` if the
+    /// group did not participate in the match.
+    pub fn get(&self, group_index: usize) -> Option<OwnedMatch> {
+        let span = *self.spans.get(group_index)?;
+        if !span.valid() {
+            return None;
+        }
+        Some(OwnedMatch::new(self.subject.clone(), span))
+    }
+
+    /// See [`Captures::participated`].
+    pub fn participated(&self, group_index: usize) -> bool {
+        self.spans.get(group_index).is_some_and(Span::valid)
+    }
+
+    /// Returns the overall match (group 0).
+    pub fn group0(&self) -> OwnedMatch {
+        // Must always be set
+        self.get(0).unwrap()
     }
 
     /// Returns the number of capture groups (including group 0).
@@ -211,8 +1195,71 @@ impl<'s> Captures<'s> {
         self.spans.len()
     }
 
-    // TODO: Add an iterator over groups
-    // and one over all matched groups maybe?
+    /// Returns borrowed [`Captures`] with the same groups.
+    pub fn as_captures(&self) -> Captures<'_> {
+        Captures {
+            subject: &self.subject,
+            spans: self.spans.clone(),
+            group_info: self.group_info.clone(),
+        }
+    }
+}
+
+/// Explains how the final match of a search was chosen, see
+/// [`crate::Regex::find_explain`].
+#[derive(Debug, Clone)]
+pub enum Explanation {
+    /// Recorded by the Pike VM.
+    PikeVM(ExplainTrace),
+    /// The engine which ran the search can't record explanations.
+    Unavailable,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Explanation::PikeVM(trace) => write!(f, "{trace}"),
+            Explanation::Unavailable => write!(f, "No explanation available for this engine"),
+        }
+    }
+}
+
+/// What the Pike VM observed during a search.
+#[derive(Debug, Clone, Default)]
+pub struct ExplainTrace {
+    /// The earliest start position at which a thread consumed at least one
+    /// character. When it is before the start of the match, this is where an
+    /// earlier match was attempted but failed later on.
+    pub earliest_surviving_start: Option<usize>,
+    /// Number of start positions skipped by prefilters. Always 0 for now,
+    /// since the VM does not have any.
+    pub pruned_starts: usize,
+    /// Number of times a thread reached the end of the pattern. Every accept
+    /// replaces the previous one, so the last accept is the final match.
+    pub accepts: usize,
+    /// The first accepted match.
+    pub first_accept: Option<Span>,
+    /// The last accepted match.
+    pub last_accept: Option<Span>,
+}
+
+impl fmt::Display for ExplainTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.earliest_surviving_start {
+            Some(start) => writeln!(f, "earliest start consuming input: {start}")?,
+            None => writeln!(f, "no start consumed any input")?,
+        }
+        writeln!(f, "start positions pruned: {}", self.pruned_starts)?;
+        write!(f, "accepts considered: {}", self.accepts)?;
+        if let (Some(first), Some(last)) = (self.first_accept, self.last_accept) {
+            write!(
+                f,
+                " (first {}..{}, last {}..{})",
+                first.from, first.to, last.from, last.to
+            )?;
+        }
+        Ok(())
+    }
 }
 
 /// Represents a single Unicode code-point, or a special sentinel value used when
@@ -234,6 +1281,26 @@ impl Char {
     pub fn all_valid() -> (Char, Char) {
         (char::MIN.into(), char::MAX.into())
     }
+
+    /// Whether this is a word char (`\w`), which the input bound is not.
+    pub fn is_word_char(&self) -> bool {
+        char::from_u32(self.0).is_some_and(regex_syntax::is_word_character)
+    }
+
+    /// Whether this is an ASCII word char (`(?-u:\w)`).
+    pub fn is_ascii_word_char(&self) -> bool {
+        u8::try_from(self.0).is_ok_and(regex_syntax::is_word_byte)
+    }
+
+    /// Returns the number of bytes needed to encode this code point in UTF-8.
+    pub fn len_utf8(&self) -> usize {
+        match self.0 {
+            0..0x80 => 1,
+            0x80..0x800 => 2,
+            0x800..0x10000 => 3,
+            _ => 4,
+        }
+    }
 }
 
 impl fmt::Debug for Char {
@@ -276,13 +1343,29 @@ impl From<Char> for i32 {
     }
 }
 
+/// A 64-bit FNV-1a hash of the bytes. Unlike the hashers of the standard
+/// library, its value is specified, and the same on every platform, run and
+/// Rust version, see [`crate::thompson::bytecode::Bytecode::content_hash`].
+pub(crate) fn stable_hash(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
 /// Given a (valid) position in a str, returns the previous character.
 ///
 /// Since assertions require knowing which Char appeared before the current
 /// one, and that at the beginning of the matching process we don't know
 /// what is the first previous-char (except if from == 0) we must look for
 /// it.
+///
+/// This is the previous code point, not the previous user-perceived
+/// character: after a combining accent, this is the accent, as the regex
+/// semantics require. See [`Match::snap_to_graphemes`] for display purposes.
 pub(crate) fn find_prev_char(s: &str, to: usize) -> Char {
+    debug_assert!(s.is_char_boundary(to), "{to} is not a char boundary");
     if to == 0 {
         return Char::INPUT_BOUND;
     }
@@ -293,9 +1376,158 @@ pub(crate) fn find_prev_char(s: &str, to: usize) -> Char {
     s[from..to].chars().next().unwrap().into()
 }
 
+/// Error of [`Input::try_span`]: the span is reversed, past the end of the
+/// subject, or one of its bounds is inside a char.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidSpan {
+    pub from: usize,
+    pub to: usize,
+}
+
+impl fmt::Display for InvalidSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The span {}..{} is not a range of char boundaries of the subject",
+            self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for InvalidSpan {}
+
+/// Error of the validating searches (see [`crate::Regex::find_validating`]):
+/// the haystack has an invalid UTF-8 sequence at `offset`, and is valid UTF-8
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Utf8ErrorAt {
+    pub offset: usize,
+}
+
+impl fmt::Display for Utf8ErrorAt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid UTF-8 sequence at byte offset {}", self.offset)
+    }
+}
+
+impl std::error::Error for Utf8ErrorAt {}
+
+/// The chars of bytes which may not be valid UTF-8. The iteration stops at
+/// the first invalid sequence, whose offset is then recorded.
+pub(crate) struct CheckedChars<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+    pub(crate) invalid_at: Option<usize>,
+}
+
+impl<'b> CheckedChars<'b> {
+    pub(crate) fn new(bytes: &'b [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            invalid_at: None,
+        }
+    }
+}
+
+impl Iterator for CheckedChars<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let rest = &self.bytes[self.pos..];
+        let first = *rest.first()?;
+        if first < 0x80 {
+            self.pos += 1;
+            return Some(first.into());
+        }
+        // A char is at most 4 bytes long, the valid prefix of a chunk holds it
+        // if it is valid.
+        let chunk = rest[..rest.len().min(4)].utf8_chunks().next()?;
+        match chunk.valid().chars().next() {
+            Some(c) => {
+                self.pos += c.len_utf8();
+                Some(c)
+            }
+            None => {
+                self.invalid_at = Some(self.pos);
+                None
+            }
+        }
+    }
+}
+
+/// Returns true if `pos` is on an (extended) grapheme cluster boundary of the
+/// haystack, see [UAX #29](https://www.unicode.org/reports/tr29/). Both ends
+/// of the haystack are boundaries, positions which are not on a char boundary
+/// (or out of the haystack) are not.
+#[cfg(feature = "unicode-segmentation")]
+pub fn is_grapheme_boundary(haystack: &str, pos: usize) -> bool {
+    if !haystack.is_char_boundary(pos) {
+        return false;
+    }
+    // The cursor only fails when given a part of the haystack.
+    GraphemeCursor::new(pos, haystack.len(), true)
+        .is_boundary(haystack, 0)
+        .unwrap()
+}
+
+/// The last grapheme boundary at or before `pos`, a char boundary.
+#[cfg(feature = "unicode-segmentation")]
+fn grapheme_floor(haystack: &str, pos: usize) -> usize {
+    if is_grapheme_boundary(haystack, pos) {
+        return pos;
+    }
+    let mut cursor = GraphemeCursor::new(pos, haystack.len(), true);
+    cursor.prev_boundary(haystack, 0).unwrap().unwrap_or(0)
+}
+
+/// The first grapheme boundary at or after `pos`, a char boundary.
+#[cfg(feature = "unicode-segmentation")]
+fn grapheme_ceil(haystack: &str, pos: usize) -> usize {
+    if is_grapheme_boundary(haystack, pos) {
+        return pos;
+    }
+    let mut cursor = GraphemeCursor::new(pos, haystack.len(), true);
+    cursor
+        .next_boundary(haystack, 0)
+        .unwrap()
+        .unwrap_or(haystack.len())
+}
+
+/// Returns true if the given look-around assertion holds between `prev` and
+/// `c`, the characters before and after the current position.
+pub(crate) fn look_matches(look: LookKind, prev: Char, c: Char) -> bool {
+    match look {
+        LookKind::Start => prev == Char::INPUT_BOUND,
+        LookKind::End => c == Char::INPUT_BOUND,
+        LookKind::StartLF => prev == Char::INPUT_BOUND || prev == '\n'.into(),
+        LookKind::EndLF => c == Char::INPUT_BOUND || c == '\n'.into(),
+        LookKind::StartCRLF => {
+            prev == Char::INPUT_BOUND
+                || prev == '\n'.into()
+                || (prev == '\r'.into() && c != '\n'.into())
+        }
+        LookKind::EndCRLF => {
+            c == Char::INPUT_BOUND || c == '\r'.into() || (c == '\n'.into() && prev != '\r'.into())
+        }
+        LookKind::WordUnicode => prev.is_word_char() != c.is_word_char(),
+        LookKind::WordUnicodeNegate => prev.is_word_char() == c.is_word_char(),
+        LookKind::WordAscii => prev.is_ascii_word_char() != c.is_ascii_word_char(),
+        LookKind::WordAsciiNegate => prev.is_ascii_word_char() == c.is_ascii_word_char(),
+        LookKind::WordStartUnicode => !prev.is_word_char() && c.is_word_char(),
+        LookKind::WordEndUnicode => prev.is_word_char() && !c.is_word_char(),
+        LookKind::WordStartAscii => !prev.is_ascii_word_char() && c.is_ascii_word_char(),
+        LookKind::WordEndAscii => prev.is_ascii_word_char() && !c.is_ascii_word_char(),
+        LookKind::WordStartHalfUnicode => !prev.is_word_char(),
+        LookKind::WordEndHalfUnicode => !c.is_word_char(),
+        LookKind::WordStartHalfAscii => !prev.is_ascii_word_char(),
+        LookKind::WordEndHalfAscii => !c.is_ascii_word_char(),
+    }
+}
+
 /// A character interval where both bounds are inclusive. If the lower bound is
 /// greater than the upper bound, then the interval is considered empty.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Interval(Char, Char);
 
 impl Interval {
@@ -314,6 +1546,11 @@ impl Interval {
         self.0 > self.1
     }
 
+    /// Returns the inclusive bounds of the interval.
+    pub fn bounds(&self) -> (Char, Char) {
+        (self.0, self.1)
+    }
+
     /// Return the intersection between self and other.
     pub fn intersect(&self, other: &Interval) -> Interval {
         let start = max(self.0, other.0);
@@ -322,21 +1559,25 @@ impl Interval {
     }
 
     /// Return the substraction between self and other. Since other can be
-    /// strictly within self, this can result in two intervals.
+    /// strictly within self, this can result in two intervals, the part of
+    /// self before other and the part after it.
     pub fn substract(&self, other: &Interval) -> (Interval, Interval) {
+        if self.is_empty() {
+            return (Interval::EMPTY, Interval::EMPTY);
+        }
         // If no overlap, return self and empty
-        if other.1 < self.0 || other.0 > self.1 {
+        if other.is_empty() || other.1 < self.0 || other.0 > self.1 {
             return (*self, Interval::EMPTY);
         }
         // Left part: from self.0 to one before other's start
         let left = if other.0 > self.0 {
-            Interval(self.0, Char(u32::from(other.0) - 1))
+            Interval(self.0, Char(other.0.0 - 1))
         } else {
             Interval::EMPTY
         };
         // Right part: from one after other's end to self.1
         let right = if other.1 < self.1 {
-            Interval(Char(u32::from(other.1) + 1), self.1)
+            Interval(Char(other.1.0 + 1), self.1)
         } else {
             Interval::EMPTY
         };
@@ -344,82 +1585,161 @@ impl Interval {
     }
 }
 
-/// A sorted set of non-overlapping intervals, in increasing order.
-#[derive(Debug, Clone)]
+/// A sorted set of non-overlapping and non-adjacent intervals, in increasing
+/// order.
+///
+/// This is the single place where class math (negation, union, intersection
+/// and difference) is implemented.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IntervalSet(Vec<Interval>);
 
 impl IntervalSet {
+    /// Creates a set from intervals which are already sorted, non-overlapping
+    /// and non-adjacent.
     pub fn new(intervals: Vec<Interval>) -> Self {
         Self(intervals)
     }
 
+    /// Creates a set from inclusive ranges sorted by their start. Empty
+    /// ranges are ignored, and overlapping or adjacent ranges are merged.
+    pub fn from_sorted_ranges(ranges: &[(Char, Char)]) -> Self {
+        let mut intervals: Vec<Interval> = Vec::with_capacity(ranges.len());
+        for &(from, to) in ranges {
+            let interval = Interval(from, to);
+            if interval.is_empty() {
+                continue;
+            }
+            match intervals.last_mut() {
+                Some(last) if from.0 <= last.1.0.saturating_add(1) => last.1 = max(last.1, to),
+                _ => intervals.push(interval),
+            }
+        }
+        Self(intervals)
+    }
+
+    /// Creates a set from inclusive ranges in any order: they are sorted by
+    /// their start, then normalized like [`IntervalSet::from_sorted_ranges`].
+    pub fn from_ranges(ranges: impl IntoIterator<Item = (Char, Char)>) -> Self {
+        let mut ranges: Vec<_> = ranges.into_iter().collect();
+        ranges.sort_unstable();
+        Self::from_sorted_ranges(&ranges)
+    }
+
+    /// Returns true if the ranges are sorted, non-empty, and neither overlap
+    /// nor touch, i.e. if [`IntervalSet::from_ranges`] would keep them as
+    /// they are. The engines rely on this to stop at the first range past the
+    /// char they check.
+    pub fn is_normalized(ranges: &[(Char, Char)]) -> bool {
+        ranges.iter().all(|(from, to)| from <= to)
+            && ranges
+                .windows(2)
+                .all(|pair| pair[0].1.0.saturating_add(1) < pair[1].0.0)
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
-}
 
-impl IntervalSet {
+    pub fn intervals(&self) -> &[Interval] {
+        &self.0
+    }
+
+    /// Returns the set as inclusive ranges, the form used by the bytecode.
+    pub fn to_ranges(&self) -> Box<[(Char, Char)]> {
+        self.0.iter().map(Interval::bounds).collect()
+    }
+
+    /// Returns all the code points of `universe` which are not in this set.
+    pub fn negate(&self, universe: Interval) -> IntervalSet {
+        let universe = IntervalSet::from_sorted_ranges(&[universe.bounds()]);
+        let (_, _, negation) = self.clone().intersect_and_substract(universe);
+        negation
+    }
+
+    /// Returns the code points which are in self, other, or both.
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        let mut ranges = Vec::with_capacity(self.0.len() + other.0.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.0.len() || j < other.0.len() {
+            if j == other.0.len() || (i < self.0.len() && self.0[i].0 <= other.0[j].0) {
+                ranges.push(self.0[i].bounds());
+                i += 1;
+            } else {
+                ranges.push(other.0[j].bounds());
+                j += 1;
+            }
+        }
+        IntervalSet::from_sorted_ranges(&ranges)
+    }
+
+    /// Returns true if no code point is in both self and other.
+    pub fn is_disjoint(&self, other: &IntervalSet) -> bool {
+        let (mut i, mut j) = (0, 0);
+        while i < self.0.len() && j < other.0.len() {
+            let (a, b) = (self.0[i], other.0[j]);
+            if a.1 < b.0 {
+                i += 1;
+            } else if b.1 < a.0 {
+                j += 1;
+            } else {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Splits self and other into three sets: what only belongs to self, what
+    /// belongs to both, and what only belongs to other.
     pub fn intersect_and_substract(
-        mut self,
-        mut other: IntervalSet,
+        self,
+        other: IntervalSet,
     ) -> (IntervalSet, IntervalSet, IntervalSet) {
-        let mut i = 0;
-        let mut j = 0;
         let mut intersection = Vec::new();
         let mut self_only = Vec::new();
         let mut other_only = Vec::new();
 
-        let self_intervals = &mut self.0;
-        let other_intervals = &mut other.0;
+        let mut self_intervals = self.0.into_iter();
+        let mut other_intervals = other.0.into_iter();
+        let mut a = self_intervals.next();
+        let mut b = other_intervals.next();
 
-        while i < self_intervals.len() && j < other_intervals.len() {
-            let a = self_intervals[i];
-            let b = other_intervals[j];
-
-            let inter = a.intersect(&b);
-            if !inter.is_empty() {
-                intersection.push(inter);
-
-                // Subtract intersection from a and b
-                let (a_left, a_right) = a.substract(&b);
+        while let (Some(cur_a), Some(cur_b)) = (a, b) {
+            if cur_a.1 < cur_b.0 {
+                self_only.push(cur_a);
+                a = self_intervals.next();
+            } else if cur_b.1 < cur_a.0 {
+                other_only.push(cur_b);
+                b = other_intervals.next();
+            } else {
+                intersection.push(cur_a.intersect(&cur_b));
+                // The parts before the intersection can't overlap anything
+                // else, and at most one of the parts after it is non-empty.
+                // That one is kept, since it might overlap with the next
+                // interval of the other set.
+                let (a_left, a_right) = cur_a.substract(&cur_b);
+                let (b_left, b_right) = cur_b.substract(&cur_a);
                 if !a_left.is_empty() {
                     self_only.push(a_left);
                 }
-                if !a_right.is_empty() {
-                    // Don't push yet, might overlap with next b
-                    self_intervals[i] = a_right;
-                    continue;
-                }
-
-                let (b_left, b_right) = b.substract(&a);
                 if !b_left.is_empty() {
                     other_only.push(b_left);
                 }
-                if !b_right.is_empty() {
-                    other_intervals[j] = b_right;
-                    continue;
-                }
-
-                i += 1;
-                j += 1;
-            } else if a.1 < b.0 {
-                self_only.push(a);
-                i += 1;
-            } else {
-                other_only.push(b);
-                j += 1;
+                a = if a_right.is_empty() {
+                    self_intervals.next()
+                } else {
+                    Some(a_right)
+                };
+                b = if b_right.is_empty() {
+                    other_intervals.next()
+                } else {
+                    Some(b_right)
+                };
             }
         }
 
         // Remaining intervals
-        while i < self_intervals.len() {
-            self_only.push(self_intervals[i]);
-            i += 1;
-        }
-        while j < other_intervals.len() {
-            other_only.push(other_intervals[j]);
-            j += 1;
-        }
+        self_only.extend(a.into_iter().chain(self_intervals));
+        other_only.extend(b.into_iter().chain(other_intervals));
 
         (
             IntervalSet(self_only),
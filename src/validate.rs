@@ -0,0 +1,151 @@
+//! Checks of user-supplied patterns, without compiling them.
+//!
+//! Compiling a pattern unrolls its counted repetitions, so a short pattern
+//! can take a long time and a lot of memory to build, e.g. the bytecode of
+//! `(?:\w{1000}){1000}` has a million instructions, and the JIT emits code
+//! for each of them. [`validate`] parses the pattern and runs the checks of
+//! the compiler, then counts the instructions of the bytecode without
+//! emitting it, in time linear in the pattern (for a bounded nesting depth,
+//! see [`Config::nest_limit`]). The patterns over the [`ValidateLimits`] are
+//! rejected, and the others come with the facts needed to decide when and
+//! how to compile them, see [`PatternInfo`].
+use std::{error::Error, fmt};
+
+use regex_syntax::hir::Look;
+
+use crate::regex::Config;
+use crate::thompson::bytecode::Compiler;
+
+/// The limits of [`validate`], over which a pattern is rejected with a
+/// [`LimitExceeded`] error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidateLimits {
+    /// The maximum length of the pattern in bytes, checked before parsing it.
+    pub max_pattern_len: usize,
+    /// The maximum number of instructions of the bytecode of the pattern, see
+    /// [`PatternInfo::estimated_instructions`].
+    pub max_instructions: usize,
+}
+
+impl Default for ValidateLimits {
+    fn default() -> Self {
+        Self {
+            max_pattern_len: 10_000,
+            max_instructions: 100_000,
+        }
+    }
+}
+
+/// The facts about a pattern found by [`validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternInfo {
+    /// The number of capture groups, including the implicit group 0, like
+    /// [`crate::Regex::group_len`].
+    pub capture_count: usize,
+    /// The number of instructions of the bytecode of the pattern, which the
+    /// engines compile. This is the exact count, see
+    /// [`Compiler::instruction_count`].
+    pub estimated_instructions: usize,
+    /// The minimum length in bytes of a match, or None if the pattern never
+    /// matches (e.g. `[^\s\S]`).
+    pub min_len: Option<usize>,
+    /// The maximum length in bytes of a match, or None if it is unbounded
+    /// (e.g. `a+`).
+    pub max_len: Option<usize>,
+    /// Whether the pattern contains assertions, such as `^` or `$`.
+    pub has_assertions: bool,
+    /// Whether every match starts at the start of the haystack, i.e. the
+    /// pattern starts with `^` (without multi-line mode) or `\A`.
+    pub anchored_start: bool,
+}
+
+/// A limit of [`validate`] which the pattern exceeds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    /// See [`ValidateLimits::max_pattern_len`].
+    PatternTooLong { len: usize, max: usize },
+    /// See [`ValidateLimits::max_instructions`].
+    TooManyInstructions { instructions: usize, max: usize },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::PatternTooLong { len, max } => {
+                write!(
+                    f,
+                    "The pattern is {len} bytes long, over the limit of {max}"
+                )
+            }
+            LimitExceeded::TooManyInstructions { instructions, max } => write!(
+                f,
+                "The pattern compiles to {instructions} instructions, over the limit of {max}"
+            ),
+        }
+    }
+}
+
+impl Error for LimitExceeded {}
+
+/// Checks that a regex can be built from the pattern with the config, within
+/// the limits, without compiling it, and returns facts about it.
+///
+/// The pattern is rejected with the same errors as the regex constructors
+/// (e.g. a [`crate::regex::PatternError`] for a syntax error, a pattern
+/// nested deeper than [`Config::nest_limit`], or over one of the
+/// [`crate::limits`] known before compiling), or with a [`LimitExceeded`].
+/// The checks specific to an engine are left to the constructors: the JIT
+/// also rejects the match kinds other than [`crate::MatchKind::LeftmostFirst`],
+/// the possessive repetitions, and the patterns whose state doesn't fit its
+/// addressing.
+///
+/// ```rust
+/// use gregex::regex::Config;
+/// use gregex::validate::{LimitExceeded, ValidateLimits};
+///
+/// let limits = ValidateLimits::default();
+/// let info = gregex::validate(r"^([0-9]{4})-([0-9]{2})", &Config::default(), &limits).unwrap();
+/// assert_eq!(info.capture_count, 3);
+/// assert_eq!((info.min_len, info.max_len), (Some(7), Some(7)));
+/// assert!(info.anchored_start);
+///
+/// let err = gregex::validate(r"(?:\w{1000}){1000}", &Config::default(), &limits).unwrap_err();
+/// assert_eq!(
+///     err.downcast_ref::<LimitExceeded>(),
+///     Some(&LimitExceeded::TooManyInstructions { instructions: 1_000_001, max: 100_000 })
+/// );
+/// ```
+pub fn validate(
+    pattern: &str,
+    config: &Config,
+    limits: &ValidateLimits,
+) -> Result<PatternInfo, Box<dyn Error + Send + Sync + 'static>> {
+    if pattern.len() > limits.max_pattern_len {
+        return Err(Box::new(LimitExceeded::PatternTooLong {
+            len: pattern.len(),
+            max: limits.max_pattern_len,
+        }));
+    }
+    let hir = Compiler::parse(pattern, config)?;
+    Compiler::check(&hir, config)?;
+    let instructions = Compiler::instruction_count(&hir, config);
+    if instructions > limits.max_instructions {
+        return Err(Box::new(LimitExceeded::TooManyInstructions {
+            instructions,
+            max: limits.max_instructions,
+        }));
+    }
+    // The limits of the compiler, which the ones above may exceed
+    Compiler::check_repetitions(&hir)?;
+    Compiler::check_instruction_count(instructions)?;
+    Compiler::check_size_limit(instructions, config)?;
+    let properties = hir.properties();
+    Ok(PatternInfo {
+        capture_count: Compiler::capture_count(&hir, config),
+        estimated_instructions: instructions,
+        min_len: properties.minimum_len(),
+        max_len: properties.maximum_len(),
+        has_assertions: !properties.look_set().is_empty(),
+        anchored_start: properties.look_set_prefix().contains(Look::Start),
+    })
+}
@@ -0,0 +1,191 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use gregex::{Builder, EngineKind};
+
+/// Counts the allocations made by the current thread, since tests run
+/// concurrently.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+#[test]
+fn test_pike_vm_is_match_and_find_do_not_allocate() {
+    const CASES: &[(&str, &str, Option<&str>)] = &[
+        (r"\d+", "abc 12345 def", Some("12345")),
+        (r"\d+", "no digits here", None),
+        ("foo|bar", "a bar and a foo", Some("bar")),
+        ("foo|bar", "nothing", None),
+    ];
+    for (pattern, haystack, expected) in CASES {
+        let re = Builder::new(*pattern).pike_vm().unwrap();
+        let (is_match, count) = allocations(|| re.is_match(*haystack));
+        assert_eq!(is_match, expected.is_some());
+        assert_eq!(
+            count, 0,
+            "is_match of {pattern:?} on {haystack:?} allocated"
+        );
+        let (found, count) = allocations(|| re.find(*haystack).map(|m| m.as_str()));
+        assert_eq!(found, *expected);
+        assert_eq!(count, 0, "find of {pattern:?} on {haystack:?} allocated");
+    }
+}
+
+#[test]
+fn test_pike_vm_captures_still_work_after_find() {
+    // The state buffers are allocated lazily, by the first execution which
+    // needs them.
+    let re = Builder::new(r"(\w+)-(\d+)").pike_vm().unwrap();
+    assert_eq!(re.find("x test-42").unwrap().as_str(), "test-42");
+    let caps = re.find_captures("x test-42").unwrap();
+    assert_eq!(caps.get(1).unwrap().as_str(), "test");
+    assert_eq!(caps.get(2).unwrap().as_str(), "42");
+    assert_eq!(re.find_all("a-1 b-2").count(), 2);
+}
+
+#[test]
+fn test_find_captures_allocates_the_spans_once() {
+    let re = Builder::new(r"(\w+)-(\d+)").pike_vm().unwrap();
+    // Warm up the state pool.
+    re.find_captures("x test-42").unwrap();
+    let (caps, count) = allocations(|| re.find_captures("x test-42"));
+    let caps = caps.unwrap();
+    assert_eq!(caps.get(1).unwrap().as_str(), "test");
+    assert_eq!(caps.get(2).unwrap().as_str(), "42");
+    assert_eq!(count, 1);
+    let (caps, count) = allocations(|| re.find_captures("no match"));
+    assert!(caps.is_none());
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn test_captures_iterators_allocate_one_buffer_per_match() {
+    let re = Builder::new(r"(\w)-(\d)").pike_vm().unwrap();
+    let haystack = "a-1 b-2 c-3 d-4";
+    // The buffers are allocated by the searches, not upfront.
+    let (mut iter, count) = allocations(|| re.find_all_captures(haystack));
+    assert_eq!(count, 0);
+    // The first search also sets up the state.
+    assert_eq!(iter.next().unwrap().get(1).unwrap().as_str(), "a");
+    for expected in ["b", "c", "d"] {
+        let (caps, count) = allocations(|| iter.next());
+        assert_eq!(caps.unwrap().get(1).unwrap().as_str(), expected);
+        assert_eq!(count, 1);
+    }
+    let (caps, count) = allocations(|| iter.next());
+    assert!(caps.is_none());
+    assert_eq!(count, 1);
+    // The buffer of the failed search is reused.
+    let (caps, count) = allocations(|| iter.next());
+    assert!(caps.is_none());
+    assert_eq!(count, 0);
+
+    let mut iter = re.find_all_captures_owned(haystack);
+    assert_eq!(iter.next().unwrap().get(1).unwrap().as_str(), "a");
+    for expected in ["b", "c", "d"] {
+        let (caps, count) = allocations(|| iter.next());
+        assert_eq!(caps.unwrap().get(1).unwrap().as_str(), expected);
+        assert_eq!(count, 1);
+    }
+}
+
+#[test]
+fn test_captures_read_does_not_allocate() {
+    let re = Builder::new(r"(\w+)-(\d+)").pike_vm().unwrap();
+    let mut locs = re.capture_locations();
+    // Warm up the state pool.
+    re.captures_read(&mut locs, "x test-42").unwrap();
+    for (haystack, expected) in [("x test-42", Some((2, 6))), ("no match", None)] {
+        let (found, count) = allocations(|| re.captures_read(&mut locs, haystack));
+        assert_eq!(found.is_some(), expected.is_some());
+        assert_eq!(locs.get(1), expected);
+        assert_eq!(count, 0, "captures_read on {haystack:?} allocated");
+    }
+
+    let mut iter = re.find_all_captures("a-1 b-2 c-3");
+    assert!(iter.read_next(&mut locs).is_some());
+    for expected in [(4, 5), (8, 9)] {
+        let (found, count) = allocations(|| iter.read_next(&mut locs));
+        assert!(found.is_some());
+        assert_eq!(locs.get(1), Some(expected));
+        assert_eq!(count, 0);
+    }
+    let (found, count) = allocations(|| iter.read_next(&mut locs));
+    assert!(found.is_none());
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn test_repeated_searches_reuse_the_state() {
+    let builder = Builder::new(r"(\w+)@(\w+)");
+    for engine in EngineKind::ALL {
+        // The debug checks of the copy-on-write arrays allocate.
+        if cfg!(debug_assertions) && engine == EngineKind::PikeJITCowArray {
+            continue;
+        }
+        let Ok(re) = builder.build(engine) else {
+            continue;
+        };
+        // The first search creates the pooled state, if the build didn't.
+        re.find("me@host");
+        for haystack in ["me@host", "nobody", "you@there"] {
+            let (_, count) = allocations(|| re.find(haystack));
+            assert_eq!(count, 0, "{} on {haystack:?}", engine.name());
+            let (_, count) = allocations(|| re.is_match(haystack));
+            assert_eq!(count, 0, "{} on {haystack:?}", engine.name());
+        }
+    }
+}
+
+#[test]
+fn test_scratch_state_searches_do_not_allocate() {
+    let builder = Builder::new(r"(\w+)@(\w+)");
+    for engine in EngineKind::ALL {
+        // The debug checks of the copy-on-write arrays allocate.
+        if cfg!(debug_assertions) && engine == EngineKind::PikeJITCowArray {
+            continue;
+        }
+        let Ok(re) = builder.build(engine) else {
+            continue;
+        };
+        let mut scratch = re.scratch_state();
+        let mut locs = re.capture_locations();
+        // The first search sizes the buffers of the state.
+        re.captures_read_with(&mut scratch, &mut locs, "me@host");
+        for haystack in ["me@host", "nobody", "you@there"] {
+            let (found, count) = allocations(|| re.find_with(&mut scratch, haystack));
+            assert_eq!(found.is_some(), haystack.contains('@'));
+            assert_eq!(count, 0, "{} on {haystack:?}", engine.name());
+            let (_, count) =
+                allocations(|| re.captures_read_with(&mut scratch, &mut locs, haystack));
+            assert_eq!(count, 0, "{} on {haystack:?}", engine.name());
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! The branches of alternations which match a single char are compiled as
+//! classes, without forking a thread per branch.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use gregex::thompson::bytecode::{Bytecode, Compiler, Instruction};
+
+const HAYSTACKS: &[&str] = &[
+    "xyzw abc 123 ab",
+    "wxyz yzx zzz a1b2",
+    "(x)[y]{z} ab xa",
+    "éèê ÉÈ ê5 ab",
+    "",
+];
+
+fn compile(pattern: &str) -> Bytecode {
+    let builder = Builder::new(pattern);
+    let hir = Compiler::parse(pattern, builder.config()).unwrap();
+    Compiler::compile(hir, builder.config().clone()).unwrap()
+}
+
+/// The number of branches of each fork of the bytecode.
+fn forks(bytecode: &Bytecode) -> Vec<usize> {
+    bytecode
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Fork2(..) => Some(2),
+            Instruction::ForkN(targets) => Some(targets.len()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_merged_classes() {
+    for (pattern, expected_forks) in [
+        // Fully merged
+        (r"x|y|\d", vec![]),
+        ("(?:x|y|[a-c])z", vec![]),
+        ("(?i)a|b|é", vec![]),
+        // The single chars around `yz`, which can't start with them.
+        ("x|yz|w", vec![2]),
+        ("x|y|zz|w|v", vec![2]),
+        ("x|(y)z|w", vec![2]),
+        ("x|[yz]a|w", vec![2]),
+        // The last `a` can't move before `ab`, which starts with it.
+        ("x|ab|a", vec![3]),
+        ("x|ab|a|b", vec![3]),
+        // Nor before a branch which may not start with a char.
+        ("x|a?b|y", vec![3, 2]),
+        ("x|$|y", vec![3]),
+        // Groups are never merged.
+        ("(x)|y|(z)", vec![3]),
+    ] {
+        let bytecode = compile(pattern);
+        assert_eq!(
+            forks(&bytecode),
+            expected_forks,
+            "{pattern:?}: {bytecode:?}"
+        );
+    }
+}
+
+#[test]
+fn test_same_as_class() {
+    // The spans and captures of the alternations are the ones of the classes.
+    for (alternation, class) in [
+        ("a|b", "[ab]"),
+        (r"(a|b|\d)(x|y)", r"([ab\d])([xy])"),
+        ("(?:x|y|z)+", "[xyz]+"),
+        ("(?i:é|a)b", "(?i:[éa])b"),
+        ("x|yz|w", "[xw]|yz"),
+    ] {
+        let alternations = utils::compile_all(alternation).unwrap();
+        let classes = utils::compile_all(class).unwrap();
+        for (re, class_re) in alternations.iter().zip(&classes) {
+            for haystack in HAYSTACKS {
+                let spans = |re: &gregex::Regex| -> Vec<_> {
+                    re.find_all_captures(*haystack)
+                        .map(|caps| {
+                            caps.groups_iter()
+                                .map(|(_, _, group)| group.map(|g| (g.start(), g.end())))
+                                .collect::<Vec<_>>()
+                        })
+                        .collect()
+                };
+                assert_eq!(
+                    spans(re),
+                    spans(class_re),
+                    "{alternation:?} on {haystack:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_priority_kept() {
+    for pattern in [
+        "x|ab|a",
+        "x|ab|a|b",
+        "(?:x|ab|a)b",
+        "a|ab|b|c",
+        "x|a?b|b|y",
+        r"x|(a)b|a|(b)",
+        "y|[a-c]d|a|e",
+        r"(?:x|\wa|a)+",
+        "x|yz|w",
+        "(?:x|y|zz|w|v)+",
+        "é|àb|à|ù",
+    ] {
+        for haystack in HAYSTACKS
+            .iter()
+            .chain(&["ab", "xab", "abab", "àbà", "cdae"])
+        {
+            utils::check_all_engines(pattern, haystack);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! The searches of a regex agree with each other: `is_match` is true iff
+//! `find`, `find_captures` and `find_all` find something, and `find` is the
+//! first match of `find_all`, on every engine, for the patterns matching the
+//! empty string at many positions too. `bench::compare` checks the same.
+//! `find_all` also skips the empty matches right after a match, like the
+//! `find_iter` of the regex crate.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::util::Input;
+
+/// Patterns which can match the empty string, at every position or only at
+/// some of them.
+const EMPTY_CAPABLE: &[&str] = &[
+    "", "(?:)", "a*", "a*?", "(a*)", "(a)*", "a?", "(a|)", "(|a)", "(?:a|b)*", "^", "$", "^$",
+    "(?m)^", "(?m)$", "(?m)^$", r"\A", r"\z", "a{0}", "(a){0}", "(?:a*)*", "(?:a|)+", "b*a*",
+    "(a*)(b*)", "a*$", "^a*", "x?y?z?",
+];
+
+const HAYSTACKS: &[&str] = &[
+    "", "a", "b", "aa", "ab", "ba", "bab", "aab", "abab", "\n", "a\n", "\na\n", "é", "aé", "éa",
+    "xyz", "zyx",
+];
+
+fn assert_consistent(re: &Regex, input: Input<'_>, context: &str) {
+    let is_match = re.is_match(input.clone());
+    let found = re.find(input.clone()).map(|m| (m.start(), m.end()));
+    let captures = re
+        .find_captures(input.clone())
+        .map(|caps| (caps.group0().start(), caps.group0().end()));
+    let first = re
+        .find_all(input.clone())
+        .next()
+        .map(|m| (m.start(), m.end()));
+    let count = re.find_all(input).count();
+    assert_eq!(found.is_some(), is_match, "find vs is_match, {context}");
+    assert_eq!(captures, found, "find_captures vs find, {context}");
+    assert_eq!(first, found, "find_all vs find, {context}");
+    assert_eq!(count > 0, is_match, "find_all vs is_match, {context}");
+}
+
+fn check(pattern: &str, haystack: &str) {
+    let Some(engines) = utils::compile_all_labeled(pattern) else {
+        return;
+    };
+    // Every start of the short haystacks.
+    let starts: Vec<usize> = (0..=haystack.len())
+        .filter(|&from| haystack.is_char_boundary(from))
+        .take_while(|&from| from == 0 || haystack.len() <= 64)
+        .collect();
+    for (label, re) in &engines {
+        for &from in &starts {
+            for anchored in [false, true] {
+                let input = Input::new(haystack)
+                    .span((from..haystack.len()).into())
+                    .anchored(anchored);
+                let context = format!(
+                    "{pattern:?} on {haystack:?} from {from} (anchored: {anchored}) with {label}"
+                );
+                assert_consistent(re, input, &context);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_empty_capable_patterns() {
+    for pattern in EMPTY_CAPABLE {
+        for haystack in HAYSTACKS {
+            check(pattern, haystack);
+        }
+    }
+}
+
+#[test]
+fn test_corpus() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        check(pattern, haystack);
+        for haystack in HAYSTACKS {
+            check(pattern, haystack);
+        }
+    }
+}
+
+#[test]
+fn test_compare_checks_the_searches() {
+    for pattern in EMPTY_CAPABLE {
+        for haystack in ["", "bab", "a\n"] {
+            let report = gregex::bench::compare(pattern, haystack, 1);
+            assert!(report.is_consistent(), "{report}");
+        }
+    }
+}
+
+#[test]
+fn test_find_all_skips_the_empty_match_after_a_match() {
+    for (label, re) in utils::compile_all_labeled("a*").unwrap() {
+        let spans: Vec<_> = re.find_all("aaa").map(|m| (m.start(), m.end())).collect();
+        assert_eq!(spans, [(0, 3)], "{label}");
+    }
+    for pattern in EMPTY_CAPABLE {
+        let Some(engines) = utils::compile_all_labeled(pattern) else {
+            continue;
+        };
+        let rust = regex::Regex::new(pattern).unwrap();
+        for haystack in HAYSTACKS.iter().chain(&["aaa", "baaab"]) {
+            let expected: Vec<_> = rust
+                .find_iter(haystack)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            for (label, re) in &engines {
+                let spans: Vec<_> = re
+                    .find_all(*haystack)
+                    .map(|m| (m.start(), m.end()))
+                    .collect();
+                assert_eq!(spans, expected, "{pattern:?} on {haystack:?} with {label}");
+            }
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! The patterns made only of assertions, which are answered without running
+//! the general engines.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::Input;
+
+#[test]
+fn test_assertion_only() {
+    const PATTERNS: &[&str] = &[
+        r"^",
+        r"$",
+        r"^$",
+        r"\A",
+        r"\z",
+        r"\A\z",
+        r"(?m)^",
+        r"(?m)$",
+        r"(?m)^$",
+        r"(?mR)^",
+        r"(?mR)$",
+        r"(?mR)^$",
+        r"(?m)^|$",
+        r"(?m)$|\A",
+        r"(?m)(^)|($)",
+        r"(?m)(?:^)*$",
+        r"(?m)(^)?$",
+        r"(?mR)(^|$)(\z)?",
+        r"",
+        r"()",
+        r"(?:)|(?m)^",
+    ];
+    const INPUTS: &[&str] = &[
+        "",
+        "\n",
+        "\n\n",
+        "abc",
+        "abc\n",
+        "\nabc",
+        "a\n\nb\n\n\nc",
+        "a\r\nb\r\n\r\nc\r",
+        "\r\n\r\n",
+        "\r\r\n\n",
+        "héllo\nwörld\n\n日本",
+    ];
+    for pattern in PATTERNS {
+        for input in INPUTS {
+            println!("Testing: {pattern:?} on {input:?}");
+            utils::check_all_engines(pattern, input);
+        }
+    }
+}
+
+#[test]
+fn test_assertion_only_spans() {
+    let subject = "a\n\nb\n\nc";
+    for engine in utils::compile_all(r"(?m)^$").unwrap() {
+        let input = Input::new(subject).span((3..6).into());
+        let all: Vec<_> = engine.find_all(input).map(|m| m.span.from).collect();
+        assert_eq!(all, vec![5]);
+        let input = Input::new(subject).span((3..6).into()).anchored(true);
+        assert!(engine.find(input).is_none());
+        let input = Input::new(subject).span((5..6).into()).anchored(true);
+        assert_eq!(engine.find(input).unwrap().span.from, 5);
+    }
+}
@@ -0,0 +1,189 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::thompson::bytecode::{Bytecode, Compiler, Instruction};
+use gregex::{Builder, EngineKind};
+
+const PATTERNS: &[&str] = &[
+    "(a)*b",
+    "(a|b)c",
+    "(a|b)*c",
+    "((a)|b)+c",
+    "(a)+",
+    "(a?)+?b",
+    "(())*a",
+    "(a*)*",
+    "(?:x|y)(a)",
+    "(a){2,4}b",
+    "x(?:(a|b)|c)d",
+    "(?:a|b|)*c",
+    "(?:(a)|(b)|)*c",
+    "(?:(a|)|(b|))*?c",
+    "((?:a|)*)*c",
+    "(?:)*a",
+    "(?:(?:a|)(?:b|)){2,}c",
+];
+
+fn bytecode(builder: &Builder<'_>) -> Option<Bytecode> {
+    let hir = Compiler::parse(builder.pattern(), builder.config()).ok()?;
+    Compiler::compile(hir, builder.config().clone()).ok()
+}
+
+/// The instructions a thread goes to from `pc` without consuming a char.
+fn epsilon_successors(instructions: &[Instruction], pc: usize) -> Vec<usize> {
+    match &instructions[pc] {
+        Instruction::Fork2(a, b) => vec![*a, *b],
+        Instruction::ForkN(targets) => targets.to_vec(),
+        Instruction::Jmp(target) => vec![*target],
+        Instruction::WriteReg(_)
+        | Instruction::Assertion(_)
+        | Instruction::CutBegin(_)
+        | Instruction::CutEnd(_) => vec![pc + 1],
+        _ => Vec::new(),
+    }
+}
+
+fn patterns() -> impl Iterator<Item = &'static str> {
+    PATTERNS
+        .iter()
+        .copied()
+        .chain(utils::corpus::CASES.iter().map(|(pattern, _)| *pattern))
+}
+
+#[test]
+fn test_barriers_do_not_depend_on_cg() {
+    for pattern in patterns() {
+        let Some(with_cg) = bytecode(&Builder::new(pattern)) else {
+            continue;
+        };
+        let without_cg = bytecode(&Builder::new(pattern).cg(false)).unwrap();
+        // The same program, without the WriteReg.
+        let stripped: Vec<_> = with_cg
+            .instructions
+            .iter()
+            .zip(&with_cg.barriers)
+            .filter(|(instruction, barrier)| {
+                let is_write = matches!(instruction, Instruction::WriteReg(_));
+                assert!(
+                    !(is_write && **barrier),
+                    "barrier on a WriteReg in {pattern:?}"
+                );
+                !is_write
+            })
+            .map(|(_, barrier)| *barrier)
+            .collect();
+        assert_eq!(stripped.len(), without_cg.instructions.len(), "{pattern:?}");
+        assert_eq!(stripped, without_cg.barriers, "{pattern:?}");
+    }
+}
+
+#[test]
+fn test_consume_runs() {
+    let runs = |pattern| bytecode(&Builder::new(pattern)).unwrap().consume_runs;
+    assert_eq!(runs("abc"), [3, 2, 1, 0]);
+    assert_eq!(runs("(?:[a-z]|_)b"), [2, 1, 0]);
+    // The barrier at the head of the loop ends the runs.
+    assert_eq!(runs("a(?:b)*c"), [1, 0, 1, 0, 1, 0]);
+    for pattern in patterns() {
+        let Some(bytecode) = bytecode(&Builder::new(pattern)) else {
+            continue;
+        };
+        let instructions = &bytecode.instructions;
+        assert_eq!(bytecode.consume_runs.len(), instructions.len());
+        for (pc, run) in bytecode.consume_runs.iter().enumerate() {
+            let consumes = matches!(
+                instructions[pc],
+                Instruction::Consume(_)
+                    | Instruction::ConsumeClass(_)
+                    | Instruction::ConsumeOutlined(_)
+            );
+            let continued = pc + 1 < instructions.len() && !bytecode.barriers[pc + 1];
+            let expected = match consumes {
+                false => 0,
+                true if continued => 1 + bytecode.consume_runs[pc + 1],
+                true => 1,
+            };
+            assert_eq!(*run, expected, "{pattern:?} at {pc}");
+        }
+    }
+}
+
+#[test]
+fn test_same_matches_with_and_without_cg() {
+    let mut cases = vec![
+        ("(a)*b", "aaab ab b"),
+        ("(a|b)*c", "abacbbc"),
+        ("((a)|b)+c", "bac abc c"),
+        ("(a?)+?b", "aab b"),
+        ("(())*a", "aa"),
+        ("(a*)*", "aab"),
+    ];
+    cases.extend_from_slice(utils::corpus::CASES);
+    for (pattern, input) in cases {
+        for engine in EngineKind::ALL {
+            let Ok(with_cg) = Builder::new(pattern).build(engine) else {
+                continue;
+            };
+            let without_cg = Builder::new(pattern).cg(false).build(engine).unwrap();
+            let spans = |re: &gregex::Regex| {
+                re.find_all(input)
+                    .map(|m| (m.start(), m.end()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(
+                spans(&with_cg),
+                spans(&without_cg),
+                "{} on {pattern:?} with {input:?}",
+                engine.name()
+            );
+            let group0 = with_cg
+                .find_all_captures(input)
+                .map(|caps| (caps.group0().start(), caps.group0().end()))
+                .collect::<Vec<_>>();
+            assert_eq!(group0, spans(&without_cg), "{}", engine.name());
+        }
+    }
+}
+
+#[test]
+fn test_empty_loops_have_a_barrier() {
+    for pattern in patterns() {
+        let Some(bytecode) = bytecode(&Builder::new(pattern)) else {
+            continue;
+        };
+        let instructions = &bytecode.instructions;
+        // Follows the epsilon transitions from each instruction without a
+        // barrier, stopping at the barriers: coming back to the start would
+        // be a loop a thread could go around forever at the same position.
+        for start in (0..instructions.len()).filter(|pc| !bytecode.barriers[*pc]) {
+            let mut stack = vec![start];
+            let mut visited = vec![false; instructions.len()];
+            while let Some(pc) = stack.pop() {
+                for next in epsilon_successors(instructions, pc) {
+                    assert_ne!(next, start, "loop without barrier in {pattern:?}");
+                    if !bytecode.barriers[next] && !visited[next] {
+                        visited[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_empty_iterations_captures() {
+    let cases = [
+        ("(?:(a)|(b)|)*c", "abc"),
+        ("(?:(a|)|(b|))*c", "c"),
+        ("(?:(a|)|(b|))*c", "bac"),
+        ("(?:(a|)|(b|))*?c", "c"),
+        ("((?:a|)*)*c", "c"),
+        ("(?:(a)|b|(?:c|))*d", "abcd"),
+        ("(?:(a|)|(b|)){0,3}c", "c"),
+        ("(a|)+b", "b"),
+    ];
+    for (pattern, input) in cases {
+        utils::check_all_engines(pattern, input);
+    }
+}
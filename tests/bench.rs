@@ -0,0 +1,64 @@
+use gregex::EngineKind;
+use gregex::bench::compare;
+
+#[test]
+fn test_compare_engines_agree() {
+    let report = compare("a+", "aa b aaa", 3);
+    assert_eq!(report.iterations, 3);
+    assert_eq!(report.haystack_len, 8);
+    // The JIT engines are unavailable on the other platforms, and without
+    // the `jit` feature.
+    let unavailable = if cfg!(all(
+        feature = "jit",
+        target_arch = "x86_64",
+        any(unix, windows)
+    )) {
+        0
+    } else {
+        EngineKind::ALL.len() - 1
+    };
+    assert_eq!(report.unavailable.len(), unavailable);
+    assert_eq!(report.engines.len(), EngineKind::ALL.len() - unavailable);
+    assert_eq!(report.engines[0].engine, EngineKind::PikeVM);
+    for engine in &report.engines {
+        assert_eq!(engine.match_count, 2, "{}", engine.engine.name());
+        assert!(engine.per_match.is_some());
+    }
+    assert!(report.is_consistent());
+}
+
+#[test]
+fn test_compare_without_match() {
+    let report = compare("z", "aa b aaa", 1);
+    for engine in &report.engines {
+        assert_eq!(engine.match_count, 0);
+        assert_eq!(engine.per_match, None);
+    }
+    assert!(report.is_consistent());
+    // Zero iterations still run the search once.
+    assert_eq!(compare("a", "a", 0).iterations, 1);
+}
+
+#[test]
+fn test_compare_invalid_pattern() {
+    let report = compare("(", "a", 1);
+    assert!(report.engines.is_empty());
+    assert_eq!(report.unavailable.len(), EngineKind::ALL.len());
+    assert!(report.to_string().contains("unavailable"));
+}
+
+#[test]
+fn test_report_formats() {
+    let report = compare("ab", "abab", 2);
+    let tsv = report.to_tsv();
+    let lines: Vec<_> = tsv.lines().collect();
+    assert_eq!(lines.len(), report.engines.len() + 1);
+    assert!(lines[0].starts_with("engine\ttotal_ns"));
+    assert!(lines[0].contains("\tbuild_ns\t"));
+    assert!(lines[1].starts_with("pike_vm\t"));
+    assert!(lines[1].ends_with("\t2\ttrue"));
+
+    let table = report.to_string();
+    assert!(table.contains("engine"));
+    assert!(table.contains("pike_vm"));
+}
@@ -0,0 +1,463 @@
+//! Searches over haystacks of a few megabytes, for the bugs which only show at
+//! scale (e.g. a state growing with the haystack, or a quadratic restart of
+//! `find_all`). They are slow in debug builds, so they only run when
+//! `GREGEX_BIG_TESTS=1` is set:
+//!
+//! ```sh
+//! GREGEX_BIG_TESTS=1 cargo test --release --test big_haystacks
+//! ```
+//!
+//! The haystacks are pseudo-random text, which never matches the patterns by
+//! itself, with matches written at known positions: each pattern comes with
+//! a generator of its matches, which gives the spans of their groups. Every
+//! engine and CG strategy must find exactly these matches, within the memory
+//! ceilings documented by `Regex::state_memory_usage`.
+use std::time::{Duration, Instant};
+
+use gregex::error::UnsupportedFeature;
+use gregex::util::{Input, Span};
+use gregex::{Builder, CgStrategy, Error, Regex};
+
+const ENABLE_VAR: &str = "GREGEX_BIG_TESTS";
+
+/// The length of the haystacks, in bytes, which may be a little longer to
+/// hold all their matches.
+const HAYSTACK_LEN: usize = 4 << 20;
+
+/// The length of the prefix of the haystacks searched first, whose memory
+/// usage the one of the whole haystack is compared to.
+const PREFIX_LEN: usize = 64 << 10;
+
+/// The memory of a state which doesn't depend on the haystack, for the small
+/// patterns below, in bytes.
+const FIXED_MEMORY_CEILING: usize = 1 << 20;
+
+/// The memory of the tree of captures of [`CgStrategy::Tree`], per byte of
+/// the haystack searched, on top of [`FIXED_MEMORY_CEILING`].
+const TREE_MEMORY_PER_BYTE: usize = 64;
+
+/// The minimum speed of a search, in bytes per second, low enough for the
+/// unoptimized builds on a loaded machine.
+const MIN_THROUGHPUT: usize = 100 << 10;
+
+type Groups = Vec<Option<(usize, usize)>>;
+
+/// A xorshift generator, so that the haystacks are the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    fn string(&mut self, chars: &[char], len: usize) -> String {
+        (0..len).map(|_| self.pick(chars)).collect()
+    }
+}
+
+/// The chars of the text between the matches: letters and spaces, with a
+/// newline now and then, and never a digit, an uppercase letter or any of
+/// the punctuation of the patterns.
+#[derive(Debug, Clone, Copy)]
+enum Alphabet {
+    Ascii,
+    /// Mostly chars of 2 to 4 bytes.
+    MultiByte,
+}
+
+impl Alphabet {
+    fn letters(self) -> Vec<char> {
+        match self {
+            Alphabet::Ascii => ('a'..='z').collect(),
+            Alphabet::MultiByte => ('α'..='ω')
+                .chain('а'..='я')
+                .chain(['é', 'ß', 'ø', 'a', 'e', '中', '文', '字', '😀', '🦀'])
+                .collect(),
+        }
+    }
+}
+
+/// A match to write in the haystack: its text, and the spans of its groups
+/// relative to its start, group 0 first. The text may have context around
+/// the match, such as the newlines of a line anchored pattern.
+struct Written {
+    text: String,
+    groups: Groups,
+}
+
+/// A pattern, and how to write its matches.
+struct Case {
+    pattern: &'static str,
+    /// The number of matches in a haystack.
+    count: usize,
+    write: fn(&mut Rng) -> Written,
+}
+
+fn digits(rng: &mut Rng, len: usize) -> String {
+    rng.string(&['0', '1', '2', '3', '4', '5', '6', '7', '8', '9'], len)
+}
+
+fn upper(rng: &mut Rng, len: usize) -> String {
+    let letters: Vec<char> = ('A'..='Z').collect();
+    rng.string(&letters, len)
+}
+
+const CASES: &[Case] = &[
+    Case {
+        pattern: r"<<id:(\d+)>>",
+        count: 2_000,
+        write: |rng| {
+            let len = 1 + rng.below(8);
+            Written {
+                text: format!("<<id:{}>>", digits(rng, len)),
+                groups: vec![Some((0, len + 7)), Some((5, len + 5))],
+            }
+        },
+    },
+    // Dense, for the restarts of `find_all`.
+    Case {
+        pattern: r"([A-Z]{3})-(\d{4})",
+        count: 100_000,
+        write: |rng| Written {
+            text: format!("{}-{}", upper(rng, 3), digits(rng, 4)),
+            groups: vec![Some((0, 8)), Some((0, 3)), Some((4, 8))],
+        },
+    },
+    Case {
+        pattern: r"(ERROR|WARN|FATAL)\[(\d+)\]",
+        count: 5_000,
+        write: |rng| {
+            let level = rng.pick(&["ERROR", "WARN", "FATAL"]);
+            let len = 1 + rng.below(5);
+            let text = format!("{level}[{}]", digits(rng, len));
+            let end = text.len();
+            Written {
+                text,
+                groups: vec![
+                    Some((0, end)),
+                    Some((0, level.len())),
+                    Some((level.len() + 1, end - 1)),
+                ],
+            }
+        },
+    },
+    Case {
+        pattern: r"(?m)^#([A-Z]+)$",
+        count: 2_000,
+        write: |rng| {
+            let len = 1 + rng.below(10);
+            Written {
+                text: format!("\n#{}\n", upper(rng, len)),
+                groups: vec![Some((1, len + 2)), Some((2, len + 2))],
+            }
+        },
+    },
+    // Optional groups, some of which don't participate.
+    Case {
+        pattern: r"Q(X+)?(Y+)?Z",
+        count: 5_000,
+        write: |rng| {
+            let (x, y) = (rng.below(3), rng.below(3));
+            let text = format!("Q{}{}Z", "X".repeat(x), "Y".repeat(y));
+            let group = |from: usize, len: usize| (len > 0).then_some((from, from + len));
+            Written {
+                groups: vec![Some((0, text.len())), group(1, x), group(1 + x, y)],
+                text,
+            }
+        },
+    },
+    Case {
+        pattern: r"(?i)needle(\d)",
+        count: 2_000,
+        write: |rng| {
+            let needle: String = "needle"
+                .chars()
+                .map(|c| match rng.below(2) {
+                    0 => c,
+                    _ => c.to_ascii_uppercase(),
+                })
+                .collect();
+            Written {
+                text: format!("{needle}{}", digits(rng, 1)),
+                groups: vec![Some((0, 7)), Some((6, 7))],
+            }
+        },
+    },
+    Case {
+        pattern: r"«(\w+)»",
+        count: 2_000,
+        write: |rng| {
+            let mut letters = Alphabet::MultiByte.letters();
+            letters.retain(|c| c.is_alphabetic());
+            let len = 1 + rng.below(6);
+            let word = rng.string(&letters, len);
+            let text = format!("«{word}»");
+            Written {
+                groups: vec![Some((0, text.len())), Some((2, 2 + word.len()))],
+                text,
+            }
+        },
+    },
+    // Never matches, the whole haystack is scanned.
+    Case {
+        pattern: r"(\d{3})z",
+        count: 0,
+        write: |_| unreachable!(),
+    },
+];
+
+/// The haystack of a case, and the groups of its matches in order.
+fn haystack(case: &Case, alphabet: Alphabet, seed: u64) -> (String, Vec<Groups>) {
+    let mut rng = Rng(seed);
+    let letters = alphabet.letters();
+    let mut haystack = String::with_capacity(HAYSTACK_LEN + 64);
+    let mut expected = Vec::with_capacity(case.count);
+    // The text between two matches is at most twice as long as the average.
+    let gap = HAYSTACK_LEN / (case.count + 1);
+    let mut next_match = rng.below(2 * gap + 1);
+    while haystack.len() < HAYSTACK_LEN || expected.len() < case.count {
+        if expected.len() < case.count && haystack.len() >= next_match {
+            // Separated from the words, so that they can't be part of the
+            // match, e.g. with `(\w+)`.
+            haystack.push(' ');
+            let written = (case.write)(&mut rng);
+            let start = haystack.len();
+            let at =
+                |span: Option<(usize, usize)>| span.map(|(from, to)| (start + from, start + to));
+            expected.push(written.groups.into_iter().map(at).collect());
+            haystack.push_str(&written.text);
+            haystack.push(' ');
+            next_match = haystack.len() + rng.below(2 * gap + 1);
+        }
+        let word_len = 1 + rng.below(10);
+        haystack.push_str(&rng.string(&letters, word_len));
+        haystack.push(if rng.below(16) == 0 { '\n' } else { ' ' });
+    }
+    assert_eq!(expected.len(), case.count, "{:?}", case.pattern);
+    (haystack, expected)
+}
+
+/// An engine, with its CG strategy for the JIT.
+struct Engine {
+    label: String,
+    builder: fn(&str) -> Builder<'_>,
+    jit: Option<CgStrategy>,
+}
+
+fn engines() -> Vec<Engine> {
+    let mut engines = vec![
+        Engine {
+            label: "PikeVM".to_string(),
+            builder: |pattern| Builder::new(pattern),
+            jit: None,
+        },
+        Engine {
+            label: "PikeVM without groups".to_string(),
+            builder: |pattern| Builder::new(pattern).cg(false),
+            jit: None,
+        },
+    ];
+    for strategy in [CgStrategy::Tree, CgStrategy::Array, CgStrategy::CowArray] {
+        engines.push(Engine {
+            label: format!("PikeJIT {strategy:?}"),
+            builder: |pattern| Builder::new(pattern),
+            jit: Some(strategy),
+        });
+    }
+    engines.push(Engine {
+        label: "PikeJIT Registers".to_string(),
+        builder: |pattern| Builder::new(pattern).cg(false),
+        jit: Some(CgStrategy::Registers),
+    });
+    engines
+}
+
+impl Engine {
+    /// The regex of the pattern, or None if the JIT is not available.
+    fn build(&self, pattern: &str) -> Option<Regex> {
+        let builder = (self.builder)(pattern);
+        let built = match self.jit {
+            Some(strategy) => builder.jit_cg_strategy(strategy).pike_jit(),
+            None => builder.pike_vm(),
+        };
+        match built {
+            Err(Error::Unsupported(UnsupportedFeature::EngineUnavailable(_))) => None,
+            built => Some(built.unwrap()),
+        }
+    }
+
+    /// The memory a state may hold after searching `len` bytes.
+    fn memory_ceiling(&self, len: usize) -> usize {
+        match self.jit {
+            Some(CgStrategy::Tree) => FIXED_MEMORY_CEILING + TREE_MEMORY_PER_BYTE * len,
+            _ => FIXED_MEMORY_CEILING,
+        }
+    }
+}
+
+fn groups_of(re: &Regex, input: Input<'_>) -> Vec<Groups> {
+    re.find_all_captures(input)
+        .map(|caps| {
+            (0..caps.group_len())
+                .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                .collect()
+        })
+        .collect()
+}
+
+/// The groups reported by a regex, which may have its groups disabled.
+fn visible(expected: &[Groups], re: &Regex) -> Vec<Groups> {
+    expected
+        .iter()
+        .map(|groups| groups[..re.group_len()].to_vec())
+        .collect()
+}
+
+/// Runs a search, and checks that it took a sane time for its length.
+fn timed<T>(context: &str, len: usize, search: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = search();
+    let elapsed = started.elapsed();
+    let bound =
+        Duration::from_secs_f64(len as f64 / MIN_THROUGHPUT as f64).max(Duration::from_secs(1));
+    assert!(elapsed <= bound, "{context}: {elapsed:?} for {len} bytes");
+    result
+}
+
+/// The largest char boundary at most `at`.
+fn floor_boundary(haystack: &str, mut at: usize) -> usize {
+    while !haystack.is_char_boundary(at) {
+        at -= 1;
+    }
+    at
+}
+
+fn check(case: &Case, alphabet: Alphabet, engine: &Engine) {
+    let Some(re) = engine.build(case.pattern) else {
+        return;
+    };
+    let (haystack, expected) = haystack(case, alphabet, 0x9e37_79b9_7f4a_7c15);
+    let expected = visible(&expected, &re);
+    let context = format!("{} for {:?} on {alphabet:?}", engine.label, case.pattern);
+    let len = haystack.len();
+
+    // The state must not grow with the haystack, except for the tree of
+    // captures.
+    let prefix = floor_boundary(&haystack, PREFIX_LEN);
+    let prefix_usage = {
+        let mut matches = re.find_all_captures(&haystack[..prefix]);
+        matches.by_ref().for_each(drop);
+        matches.state_memory_usage()
+    };
+
+    let mut matches = re.find_all_captures(haystack.as_str());
+    let found: Vec<Groups> = timed(&context, len, || {
+        matches
+            .by_ref()
+            .map(|caps| {
+                (0..caps.group_len())
+                    .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                    .collect()
+            })
+            .collect()
+    });
+    let usage = matches.state_memory_usage();
+    assert_eq!(found.len(), expected.len(), "{context} (count)");
+    if let Some(i) = (0..found.len()).find(|i| found[*i] != expected[*i]) {
+        panic!(
+            "{context}: match {i} is {:?}, expected {:?}",
+            found[i], expected[i]
+        );
+    }
+    assert!(
+        usage <= engine.memory_ceiling(len),
+        "{context}: {usage} bytes"
+    );
+    if engine.jit != Some(CgStrategy::Tree) {
+        assert!(
+            usage <= 2 * prefix_usage.max(1 << 10),
+            "{context}: {usage} bytes, {prefix_usage} for the first {prefix} bytes"
+        );
+    }
+
+    let count = timed(&context, len, || re.find_all(haystack.as_str()).count());
+    assert_eq!(count, expected.len(), "{context} (find_all)");
+
+    // The one-shot searches, through the pool. The last match is the one
+    // found after scanning the most.
+    let first = timed(&context, len, || re.find_captures(haystack.as_str()));
+    let first = first.map(|caps| caps.get(0).map(|m| (m.start(), m.end())));
+    assert_eq!(
+        first,
+        expected.first().map(|groups| groups[0]),
+        "{context} (first)"
+    );
+    assert_eq!(
+        re.is_match(haystack.as_str()),
+        !expected.is_empty(),
+        "{context}"
+    );
+    if let Some(last) = expected.last() {
+        let (from, _) = last[0].unwrap();
+        let input = Input::new(&haystack).span(Span { from, to: len });
+        let found = timed(&context, len, || re.find(input)).map(|m| (m.start(), m.end()));
+        assert_eq!(found, last[0], "{context} (last)");
+    }
+    let usage = re.state_memory_usage();
+    assert!(
+        usage <= engine.memory_ceiling(len),
+        "{context}: {usage} bytes in the pool"
+    );
+
+    // A window in the middle, which only has the matches entirely in it.
+    let from = floor_boundary(&haystack, len / 3);
+    let to = floor_boundary(&haystack, 2 * len / 3);
+    let in_window: Vec<Groups> = expected
+        .iter()
+        .filter(|groups| groups[0].is_some_and(|(start, end)| from <= start && end <= to))
+        .cloned()
+        .collect();
+    let input = Input::new(&haystack).span(Span { from, to });
+    let found = timed(&context, to - from, || groups_of(&re, input));
+    assert_eq!(found.len(), in_window.len(), "{context} (window count)");
+    assert!(found == in_window, "{context} (window)");
+}
+
+fn enabled() -> bool {
+    std::env::var_os(ENABLE_VAR).is_some_and(|value| value == "1")
+}
+
+#[test]
+fn test_ascii_haystacks() {
+    if !enabled() {
+        return;
+    }
+    for case in CASES {
+        for engine in engines() {
+            check(case, Alphabet::Ascii, &engine);
+        }
+    }
+}
+
+#[test]
+fn test_multi_byte_haystacks() {
+    if !enabled() {
+        return;
+    }
+    for case in CASES {
+        for engine in engines() {
+            check(case, Alphabet::MultiByte, &engine);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+#[allow(dead_code)]
+mod utils;
+
+use std::borrow::Cow;
+
+use gregex::thompson::bytecode::{Compiler, Instruction};
+use gregex::{Builder, EngineKind, MatchKind};
+
+const ENGINES: &[EngineKind] = &[
+    EngineKind::PikeVM,
+    EngineKind::PikeJIT,
+    EngineKind::PikeJITArray,
+    EngineKind::PikeJITCowArray,
+];
+
+#[test]
+fn test_builder_from_string_str_and_cow() {
+    let owned = Builder::new(format!(r"(\d+)-{}", "x"));
+    let borrowed = Builder::new(r"(\d+)-x");
+    let cow = Builder::new(Cow::Borrowed(r"(\d+)-x"));
+    assert_eq!(owned, borrowed);
+    assert_eq!(borrowed, cow);
+    for builder in [owned, borrowed, cow] {
+        let re = builder.pike_vm().unwrap();
+        assert_eq!(re.find("ab 12-x").unwrap().as_str(), "12-x");
+    }
+}
+
+#[test]
+fn test_builder_stored_in_struct() {
+    struct Settings {
+        name: &'static str,
+        builder: Builder<'static>,
+    }
+
+    fn make_settings(word: &str) -> Settings {
+        Settings {
+            name: "words",
+            builder: Builder::new(format!("(?:{word})+")).case_insensitive(true),
+        }
+    }
+
+    let settings = make_settings("ab");
+    assert_eq!(settings.name, "words");
+    assert!(settings.builder.config().case_insensitive);
+    assert_eq!(settings.builder.pattern(), "(?:ab)+");
+    let re = settings.builder.pike_vm().unwrap();
+    assert_eq!(re.find("xxABab").unwrap().as_str(), "ABab");
+}
+
+#[test]
+fn test_builder_builds_multiple_engines() {
+    let builder = Builder::new(r"([a-z]+)@([a-z]+)\.com");
+    for engine in ENGINES {
+        let re = match builder.build(*engine) {
+            Ok(re) => re,
+            Err(_) if !utils::jit_available() && *engine != EngineKind::PikeVM => continue,
+            Err(e) => panic!("Failed to build {engine:?}: {e}"),
+        };
+        let caps = re.find_captures("mail foo@bar.com").unwrap();
+        assert_eq!(caps.get(1).unwrap().as_str(), "foo");
+        assert_eq!(caps.get(2).unwrap().as_str(), "bar");
+    }
+    // Building twice from the same builder works
+    assert!(builder.pike_vm().is_ok());
+    assert!(builder.pike_vm().is_ok());
+}
+
+#[test]
+fn test_builder_force_class_compilation() {
+    let builder = Builder::new(r"\w+").force_class_outlining(true);
+    assert!(builder.config().force_class_outlining);
+    let conflicting = builder.force_class_inlining(true);
+    for engine in ENGINES {
+        let err = conflicting.build(*engine).err().unwrap();
+        if utils::jit_available() || *engine == EngineKind::PikeVM {
+            assert!(
+                err.to_string().contains("both outlined and inlined"),
+                "{err}"
+            );
+        }
+    }
+
+    let is_outlined = |builder: Builder<'_>| {
+        let hir = Compiler::parse(builder.pattern(), builder.config()).unwrap();
+        let bytecode = Compiler::compile(hir, builder.config().clone()).unwrap();
+        match &bytecode.instructions[0] {
+            Instruction::ConsumeOutlined(_) => true,
+            Instruction::ConsumeClass(_) => false,
+            other => panic!("Unexpected instruction {other:?}"),
+        }
+    };
+    // \w has many more ranges than MAX_INLINE_CLASS_RANGES, [a-c] only one
+    assert!(is_outlined(Builder::new(r"\w")));
+    assert!(!is_outlined(Builder::new("[a-c]")));
+    assert!(is_outlined(
+        Builder::new("[a-c]").force_class_outlining(true)
+    ));
+    assert!(!is_outlined(Builder::new(r"\w").force_class_inlining(true)));
+}
+
+#[test]
+fn test_regex_config_round_trip() {
+    let base = Builder::new("[a-z]+");
+    let builders = [
+        base.clone(),
+        base.clone().unicode(false),
+        base.clone().case_insensitive(true),
+        base.clone().cg(false),
+        base.clone().specialize(false),
+        base.clone().force_class_outlining(true),
+        base.clone().force_class_inlining(true),
+        base.clone().match_kind(MatchKind::LeftmostLongest),
+    ];
+    for builder in &builders {
+        for engine in ENGINES {
+            let Ok(re) = builder.build(*engine) else {
+                // The JIT is unavailable, or refuses leftmost-longest
+                assert_ne!(*engine, EngineKind::PikeVM);
+                continue;
+            };
+            assert_eq!(re.config(), builder.config(), "{engine:?}");
+            assert_eq!(re.pattern(), "[a-z]+");
+            assert_eq!(re.engine_kind(), *engine);
+        }
+    }
+}
+
+#[test]
+fn test_rebuild_with_modified_flags() {
+    for engine in ENGINES {
+        let Ok(re) = Builder::new("(hello) world").build(*engine) else {
+            assert!(!utils::jit_available());
+            continue;
+        };
+        assert!(!re.is_match("HELLO World"));
+        let insensitive = re
+            .rebuild(|builder| builder.case_insensitive(true))
+            .unwrap();
+        assert_eq!(insensitive.engine_kind(), *engine);
+        assert!(insensitive.config().case_insensitive);
+        let caps = insensitive.find_captures("say HELLO World").unwrap();
+        assert_eq!(caps.get(1).unwrap().as_str(), "HELLO");
+
+        // Only the modified flag changes, and it can be changed back.
+        let without_groups = insensitive.rebuild(|builder| builder.cg(false)).unwrap();
+        assert!(without_groups.config().case_insensitive);
+        assert_eq!(
+            without_groups
+                .find_captures("HELLO World")
+                .unwrap()
+                .group_len(),
+            1
+        );
+        let sensitive = without_groups
+            .rebuild(|builder| builder.case_insensitive(false).cg(true))
+            .unwrap();
+        assert_eq!(sensitive.config(), re.config());
+        assert!(!sensitive.is_match("HELLO World"));
+        assert_eq!(sensitive.engine_kind(), *engine);
+    }
+}
+
+#[test]
+fn test_rebuild_specialized_and_failing() {
+    // The requested engine is kept, even when a specialized one was built.
+    let re = Builder::new("(?m)^$").pike_vm().unwrap();
+    let rebuilt = re.rebuild(|builder| builder.specialize(false)).unwrap();
+    assert_eq!(rebuilt.engine_kind(), EngineKind::PikeVM);
+    assert_eq!(rebuilt.find_all("a\n\nb").count(), 1);
+
+    let re = Builder::new("a|ab").pike_vm().unwrap();
+    assert!(
+        re.rebuild(|builder| builder.force_class_inlining(true))
+            .is_ok()
+    );
+    let longest = re
+        .rebuild(|builder| builder.match_kind(MatchKind::LeftmostLongest))
+        .unwrap();
+    assert_eq!(longest.find("ab").unwrap().as_str(), "ab");
+    if utils::jit_available() {
+        let jit = Builder::new("a|ab").pike_jit().unwrap();
+        assert!(
+            jit.rebuild(|builder| builder.match_kind(MatchKind::LeftmostLongest))
+                .is_err()
+        );
+    }
+}
+
+#[test]
+fn test_captures_group_views() {
+    let re = Builder::new(r"(\d{4})-(\d{2})(?:-(\d{2}))?")
+        .pike_vm()
+        .unwrap();
+    let caps = re.find_captures("on 2024-03, or 2024-04-01").unwrap();
+    let groups: Vec<_> = caps
+        .all_groups()
+        .into_iter()
+        .map(|g| g.map(|g| (g.start(), g.end())))
+        .collect();
+    assert_eq!(
+        groups,
+        vec![Some((3, 10)), Some((3, 7)), Some((8, 10)), None]
+    );
+
+    let indexed: Vec<_> = caps
+        .groups_iter()
+        .map(|(i, name, g)| (i, name, g.map(|g| g.as_str())))
+        .collect();
+    assert_eq!(
+        indexed,
+        vec![
+            (0, None, Some("2024-03")),
+            (1, None, Some("2024")),
+            (2, None, Some("03")),
+            (3, None, None),
+        ]
+    );
+    // Named groups are not supported yet.
+    assert!(caps.to_name_map().is_empty());
+}
@@ -0,0 +1,105 @@
+//! Searches writing their groups to a caller-provided `CaptureLocations`,
+//! compared with the ones allocating `Captures`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::CaptureLocations;
+use gregex::{Builder, EngineKind};
+
+const CASES: &[(&str, &[&str])] = &[
+    (
+        r"(\w+)@(\w+)(?:\.(com|org))?",
+        &["me@host.com", "x you@there y", "none", "a@b.org c@d"],
+    ),
+    (r"(a)|(b)", &["b", "ab", "", "c"]),
+    (r"(?P<k>\w+)=(?P<v>\d*)", &["k=", "key=12 a=3", "=="]),
+    (r"x*", &["", "axx"]),
+];
+
+fn groups(locs: &CaptureLocations) -> Vec<Option<(usize, usize)>> {
+    (0..locs.len()).map(|i| locs.get(i)).collect()
+}
+
+#[test]
+fn test_same_as_find_captures() {
+    for &(pattern, haystacks) in CASES {
+        for engine in EngineKind::ALL {
+            let Ok(re) = Builder::new(pattern).build(engine) else {
+                continue;
+            };
+            let ctx = format!("{} for {pattern:?}", engine.name());
+            // A single buffer for every search, so that stale groups would
+            // show.
+            let mut locs = re.capture_locations();
+            assert_eq!(locs.len(), re.group_len(), "{ctx}");
+            for haystack in haystacks {
+                let expected = re.find_captures(*haystack);
+                let found = re.captures_read(&mut locs, *haystack);
+                assert_eq!(
+                    found.map(|m| (m.start(), m.end())),
+                    expected.as_ref().map(|caps| {
+                        let m = caps.group0();
+                        (m.start(), m.end())
+                    }),
+                    "{ctx} on {haystack:?}"
+                );
+                match expected {
+                    Some(caps) => assert_eq!(
+                        groups(&locs),
+                        utils::group_spans(&caps),
+                        "{ctx} on {haystack:?}"
+                    ),
+                    None => assert!(groups(&locs).iter().all(Option::is_none), "{ctx}"),
+                }
+
+                // The iterator, mixing both ways of advancing it.
+                let expected: Vec<_> = re
+                    .find_all_captures(*haystack)
+                    .map(|c| utils::group_spans(&c))
+                    .collect();
+                let mut iter = re.find_all_captures(*haystack);
+                let mut found = Vec::new();
+                loop {
+                    if found.len() % 2 == 0 {
+                        if iter.read_next(&mut locs).is_none() {
+                            break;
+                        }
+                        found.push(groups(&locs));
+                    } else {
+                        let Some(caps) = iter.next() else {
+                            break;
+                        };
+                        found.push(utils::group_spans(&caps));
+                    }
+                }
+                assert_eq!(found, expected, "{ctx} on {haystack:?} (find_all_captures)");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_other_lengths() {
+    let re = Builder::new(r"(\w)(\d)(\w)").pike_vm().unwrap();
+    // Fewer groups, like find_captures_n.
+    let mut locs = CaptureLocations::new(2);
+    assert!(re.captures_read(&mut locs, "-a1b").is_some());
+    assert_eq!(groups(&locs), [Some((1, 4)), Some((1, 2))]);
+    // More groups, which never participate.
+    let mut locs = CaptureLocations::new(6);
+    assert!(re.captures_read(&mut locs, "-a1b").is_some());
+    assert_eq!(locs.get(3), Some((3, 4)));
+    assert_eq!(locs.get(4), None);
+    // Group 0 is always there.
+    assert_eq!(CaptureLocations::new(0).len(), 1);
+}
+
+#[test]
+fn test_to_captures() {
+    let re = Builder::new(r"(\w+)@(\w+)").pike_vm().unwrap();
+    let mut locs = re.capture_locations();
+    let haystack = "to: you@there";
+    re.captures_read(&mut locs, haystack).unwrap();
+    let caps = locs.to_captures(haystack);
+    assert_eq!(caps.get(2).unwrap().as_str(), "there");
+}
@@ -0,0 +1,111 @@
+use gregex::Builder;
+use gregex::regex::{Config, RegexImpl};
+use gregex::thompson::assertion_only::AssertionOnly;
+use gregex::thompson::pike_vm::PikeVM;
+use gregex::util::{Input, Span};
+
+/// What the slots hold before a search, to tell stale data apart.
+const STALE: Span = Span { from: 7, to: 7 };
+
+fn config(cg: bool) -> Config {
+    Config {
+        cg,
+        ..Config::default()
+    }
+}
+
+/// The bounds of a span, if it is valid. Engines may encode invalid spans
+/// differently.
+fn bounds(span: Span) -> Option<(usize, usize)> {
+    span.valid().then_some((span.from, span.to))
+}
+
+/// Runs the engine with `len` slots, and checks that the first ones hold the
+/// expected groups and the others are cleared.
+fn check<E: RegexImpl>(engine: &E, input: &str, expected: &[Span], len: usize, name: &str) {
+    let mut state = engine.new_state();
+    let mut captures = vec![STALE; len];
+    assert!(engine.exec(Input::new(input), &mut state, &mut captures));
+    for (i, span) in captures.iter().enumerate() {
+        let want = expected.get(i).copied().unwrap_or(Span::invalid());
+        assert_eq!(bounds(*span), bounds(want), "{name}: slot {i} of {len}");
+    }
+}
+
+/// The slice lengths to try for a regex tracking `groups` groups.
+fn lengths(groups: usize) -> [usize; 5] {
+    [0, 1, groups, groups + 1, groups + 3]
+}
+
+#[test]
+fn test_pike_vm_slots() {
+    let spans = [(1..3).into(), (1..2).into(), Span::invalid()];
+    for cg in [true, false] {
+        let expected = if cg { &spans[..] } else { &spans[..1] };
+        let vm = PikeVM::new("(a)(b)?c", config(cg)).unwrap();
+        assert_eq!(vm.capture_count(), expected.len());
+        for len in lengths(expected.len()) {
+            check(&vm, "xac", expected, len, &format!("cg {cg}"));
+        }
+    }
+}
+
+#[test]
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+fn test_pike_jit_slots() {
+    use gregex::CgStrategy;
+    use gregex::thompson::pike_jit::JittedRegex;
+
+    let spans = [(1..3).into(), (1..2).into(), Span::invalid()];
+    for cg in [true, false] {
+        let expected = if cg { &spans[..] } else { &spans[..1] };
+        let strategies = [
+            CgStrategy::Registers,
+            CgStrategy::Tree,
+            CgStrategy::Array,
+            CgStrategy::CowArray,
+        ];
+        let engines = strategies.into_iter().filter_map(|jit_cg_strategy| {
+            let config = Config {
+                jit_cg_strategy,
+                ..config(cg)
+            };
+            // The registers only hold group 0.
+            let jit = JittedRegex::new("(a)(b)?c", config);
+            assert_eq!(jit.is_ok(), !cg || jit_cg_strategy != CgStrategy::Registers);
+            Some((jit_cg_strategy, jit.ok()?))
+        });
+        for (name, jit) in engines {
+            for len in lengths(expected.len()) {
+                check(&jit, "xac", expected, len, &format!("{name:?} cg {cg}"));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_assertion_only_slots() {
+    let spans = [(0..0).into(), (0..0).into(), Span::invalid()];
+    for cg in [true, false] {
+        let expected = if cg { &spans[..] } else { &spans[..1] };
+        let engine = AssertionOnly::new("(^)($)?", config(cg)).unwrap().unwrap();
+        for len in lengths(expected.len()) {
+            check(&engine, "x", expected, len, &format!("cg {cg}"));
+        }
+    }
+}
+
+#[test]
+fn test_captures_disabled() {
+    let re = Builder::new("(a)(b)?c").cg(false).pike_vm().unwrap();
+    assert!(!re.captures_enabled());
+    assert_eq!(re.group_len(), 1);
+    let caps = re.find_captures("xac").unwrap();
+    assert_eq!(caps.group_len(), 1);
+    assert_eq!((caps.group0().start(), caps.group0().end()), (1, 3));
+    assert!(caps.get(1).is_none());
+
+    let re = Builder::new("(a)(b)?c").pike_vm().unwrap();
+    assert!(re.captures_enabled());
+    assert_eq!(re.find_captures("xac").unwrap().group_len(), 3);
+}
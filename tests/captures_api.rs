@@ -0,0 +1,109 @@
+//! The API of the captures mirroring the regex crate: `iter`, the `Index`
+//! impls and `extract`, including their panics.
+#[allow(dead_code)]
+mod utils;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use gregex::util::{Captures, Span};
+use gregex::{Builder, Regex};
+use regex as rust_regex;
+use utils::compile_all_labeled;
+
+/// The result of `f`, or the message of its panic.
+fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    panic::catch_unwind(AssertUnwindSafe(f)).map_err(|payload| match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload.downcast::<&str>().unwrap().to_string(),
+    })
+}
+
+#[test]
+fn test_iter_and_index() {
+    let cases = [
+        (r"(a)|(b)", "xb"),
+        (r"(?P<y>\d{4})-(?P<m>\d{2})(?:-(?P<d>\d{2}))?", "2024-03"),
+        (r"(a)(b)?(c)*", "ac"),
+        (r"(a){0}(b)", "b"),
+        (r"(é+)(\w)?", "éé!"),
+        ("", "x"),
+    ];
+    for (pattern, haystack) in cases {
+        let rust = rust_regex::Regex::new(pattern).unwrap();
+        let rust_caps = rust.captures(haystack).unwrap();
+        for (label, ours) in compile_all_labeled(pattern).unwrap() {
+            let ctx = format!("{label} for {pattern:?} on {haystack:?}");
+            let caps = ours.find_captures(haystack).unwrap();
+            let groups: Vec<_> = caps.iter().map(|m| m.map(|m| m.as_str())).collect();
+            let rust_groups: Vec<_> = rust_caps.iter().map(|m| m.map(|m| m.as_str())).collect();
+            assert_eq!(groups, rust_groups, "{ctx}");
+            assert_eq!(caps.iter().len(), rust_caps.len(), "{ctx}");
+            for i in 0..=rust_caps.len() {
+                assert_eq!(catch(|| &caps[i]), catch(|| &rust_caps[i]), "{ctx} at {i}");
+            }
+            for name in ["y", "d", "nope"] {
+                assert_eq!(
+                    catch(|| &caps[name]),
+                    catch(|| &rust_caps[name]),
+                    "{ctx} for {name:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_extract() {
+    fn check<const N: usize>(pattern: &str, haystack: &str) {
+        let rust = rust_regex::Regex::new(pattern).unwrap();
+        let rust_caps = rust.captures(haystack).unwrap();
+        let expected = catch(|| rust_caps.extract::<N>());
+        for (label, ours) in compile_all_labeled(pattern).unwrap() {
+            let caps = ours.find_captures(haystack).unwrap();
+            let found = catch(|| caps.extract::<N>());
+            assert_eq!(found, expected, "{label} for {pattern:?} on {haystack:?}");
+        }
+    }
+
+    check::<2>(r"(\w+)@(\w+)", "me@example");
+    check::<1>(r"(a)|(b)", "b");
+    check::<1>(r"(a){0}(b)", "b");
+    check::<1>(r"(?:(a)b)+", "abab");
+    check::<0>(r"\d+", "a12");
+    check::<1>(r"(?P<x>é)(?:x|y)", "éy");
+    // The arity mismatches.
+    check::<1>(r"(\w+)@(\w+)", "me@example");
+    check::<3>(r"(\w+)@(\w+)", "me@example");
+    // The groups which may not participate, even if they do.
+    check::<1>(r"(a)?", "a");
+    check::<1>(r"(a)|b", "a");
+    check::<2>(r"(a)*(b)", "ab");
+}
+
+#[test]
+fn test_extract_without_groups() {
+    let re = Builder::new(r"(\w+)@(\w+)").cg(false).pike_vm().unwrap();
+    let (whole, []) = re.find_captures("at me@example").unwrap().extract();
+    assert_eq!(whole, "me@example");
+
+    let re = Builder::new(r"(a)b*+(c)")
+        .possessive_quantifiers(true)
+        .pike_vm()
+        .unwrap();
+    let (_, [a, c]) = re.find_captures("abbc").unwrap().extract();
+    assert_eq!((a, c), ("a", "c"));
+}
+
+#[test]
+#[should_panic(expected = "number of capture groups can vary in a match")]
+fn test_extract_unknown_groups() {
+    let caps = Captures::new("ab", vec![Span::from(0..2), Span::from(1..2)].into());
+    caps.extract::<1>();
+}
+
+#[test]
+#[should_panic(expected = "too few matching groups")]
+fn test_extract_fewer_groups_reported() {
+    let re = Regex::pike_vm(r"(a)(b)").unwrap();
+    re.find_captures_n("ab", 2).unwrap().extract::<2>();
+}
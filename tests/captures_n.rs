@@ -0,0 +1,24 @@
+//! `Regex::find_captures_n`, which only reports the first groups.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::Input;
+
+#[test]
+fn test_captures_n() {
+    let pattern = r"(a)(b)?(c)(?:(d)|(e))(f)(g)(h)(i)(j)(k)(l)(m)(n)(o)";
+    let subject = "xx abcdfghijklmno acefghijklmno";
+    for engine in utils::compile_all(pattern).unwrap() {
+        assert!(engine.is_match(subject));
+        let caps = engine.find_captures_n(subject, 2).unwrap();
+        assert_eq!(caps.group_len(), 2);
+        assert_eq!(caps.group0().as_str(), "abcdfghijklmno");
+        assert_eq!(caps.get(1).unwrap().as_str(), "a");
+        let all: Vec<_> = engine
+            .find_all(subject)
+            .map(|m| engine.find_captures_n(Input::new(subject).span(m.span), 5))
+            .map(|caps| caps.unwrap().get(4).map(|g| g.as_str()))
+            .collect();
+        assert_eq!(all, vec![Some("d"), None]);
+    }
+}
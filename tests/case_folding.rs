@@ -0,0 +1,85 @@
+//! The case-insensitive matches of the letters with unusual folds. Like
+//! rust-regex, the folding is simple: each char folds to single chars, never
+//! to a sequence (`ß` doesn't match "ss", nor `ﬁ` "fi"). A letter and its folds
+//! are then a class, which the engines consume like any other.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::regex::Config;
+use gregex::thompson::bytecode::{Compiler, Instruction};
+use regex_syntax::hir::HirKind;
+
+/// The letters whose folds include chars of other blocks, or whose full
+/// folding would be several chars.
+const LETTERS: &[char] = &[
+    'ß', 'ẞ', 's', 'S', 'ſ', 'k', 'K', 'K', 'i', 'I', 'İ', 'ı', 'Ǆ', 'ǅ', 'ǆ', 'ﬁ', 'ﬀ', 'ŉ',
+];
+
+fn haystack() -> String {
+    let mut haystack: String = LETTERS.iter().flat_map(|c| [*c, ' ']).collect();
+    haystack.push_str("ss SS fi ff ʼn dž DŽ i̇ ");
+    haystack
+}
+
+#[test]
+fn test_single_letters() {
+    let haystack = haystack();
+    for c in LETTERS {
+        for pattern in [
+            format!("(?i){c}"),
+            format!("(?i){c}+"),
+            format!("(?i)[{c}x]"),
+            format!("(?i)[^{c}]+"),
+            format!("(?i)({c}) "),
+        ] {
+            utils::check_all_engines(&pattern, &haystack);
+        }
+    }
+}
+
+#[test]
+fn test_sequences() {
+    let haystack = haystack();
+    for pattern in [
+        "(?i)ss",
+        "(?i)ß|ss",
+        "(?i)fi",
+        "(?i)dž",
+        "(?i)i̇",
+        "(?i)[ß-ẞ]+",
+        "(?i)[a-z]+",
+        "(?i)[^a-z ]+",
+        r"(?i)\w+",
+        "(?i:k)K",
+    ] {
+        utils::check_all_engines(pattern, &haystack);
+    }
+}
+
+#[test]
+fn test_folds_are_single_char_classes() {
+    // A fold into several chars would be a concatenation or an alternation
+    // in the HIR, and would need a new look at how it compiles.
+    let config = Config::default();
+    for c in LETTERS {
+        let hir = Compiler::parse(&format!("(?i){c}"), &config).unwrap();
+        match hir.kind() {
+            HirKind::Class(_) | HirKind::Literal(_) => {}
+            kind => panic!("{c:?} folds to {kind:?}"),
+        }
+        // Consumed by a single instruction, with its ranges inline.
+        let bytecode = Compiler::compile(hir, config.clone()).unwrap();
+        let consumes = bytecode
+            .instructions
+            .iter()
+            .filter(|instruction| {
+                matches!(
+                    instruction,
+                    Instruction::Consume(_) | Instruction::ConsumeClass(_)
+                )
+            })
+            .count();
+        assert_eq!(consumes, 1, "{c:?}");
+        assert!(bytecode.outlined_classes.is_empty(), "{c:?}");
+    }
+}
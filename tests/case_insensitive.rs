@@ -0,0 +1,120 @@
+//! Case-insensitive matching, from the inline flags and from
+//! `Builder::case_insensitive`, compared with rust-regex. The folded classes
+//! are often large (e.g. `(?i)\p{Lu}`), so they also go through the outlined
+//! classes of the engines.
+#[allow(dead_code)]
+mod utils;
+
+use utils::{Flags, check_all_engines_with};
+
+const HAYSTACKS: &[&str] = &[
+    "Straße STRASSE strasse STRAẞE",
+    "ΣΊΣΥΦΟΣ σίσυφος Σίσυφος ς",
+    "Kelvin KELVIN \u{212a}elvin ſ S s",
+    "ǅungla ǆ Ǆ ĳs Ĳ İi ıI",
+    "ÉTÉ été Été aBc ABC abc xX 123_",
+];
+
+/// Patterns setting the flag themselves, searched with the default flags.
+const INLINE: &[&str] = &[
+    "(?i)straße",
+    "(?i)STRASSE",
+    "(?i)stra[ßs]+e",
+    "(?i)Σ",
+    "(?i)σ+",
+    "(?i)ς",
+    "(?i)[α-ω]+",
+    "(?i)σ(?-i)ς",
+    "(?i)ΣΊΣΥΦΟΣ",
+    "(?i:a)b(?i:c)",
+    "a(?i)b(?-i)c",
+    "(?i)[^a-z ]+",
+    r"(?i)\p{Greek}+",
+    r"(?i)\p{Lu}+",
+    r"(?i)[^\p{Ll}\s]+",
+    "(?i)[[:upper:]]+",
+    r"(?i)\bk\w*",
+    "(?i)(?-i:K)elvin",
+    "(?i)kelvin",
+    "(?i)ǅ",
+    "(?i)[ǅĳ]+",
+    "(?i)(é|É)+",
+    "(?i)[a-zé-ö]+",
+    "(?-i)(?i)x",
+    "(?ix) s t r a ß e",
+    "(?i)(s)(?-i:s)",
+    "(?i)i",
+    "(?i)[İı]",
+    r"(?i)(?<up>[A-Z]+)\s(?-i:[a-z]+)",
+];
+
+/// Patterns searched with `Builder::case_insensitive(true)`, whose inline
+/// flags may turn it off.
+const BUILDER: &[&str] = &[
+    "straße",
+    "Σ",
+    "[α-ω]+",
+    "(?-i)Σ|σ",
+    "(?-i:a)BC",
+    "a(?-i)bc",
+    r"\w+(?-i)X",
+    "[^a-z ]+",
+    r"\p{Lu}+",
+    "kelvin",
+    "(?-i)(?i:k)elvin",
+    // The rest of the pattern is built again from its hir, with the flags.
+    ".*σ(ς)",
+    r"(?s).*\bss",
+];
+
+/// Patterns searched with `Builder::case_insensitive(true)` and
+/// `Builder::unicode(false)`, which only fold the ASCII letters. The classes
+/// which may match invalid UTF-8 fail to build on both sides.
+const ASCII: &[&str] = &[
+    "abc",
+    "[a-z]+",
+    r"\w+",
+    "kelvin",
+    "s",
+    "(?u)s",
+    "(?-i)x|X",
+    "[^a-z]+",
+    "straße",
+    "(?-u:[a-c])+",
+    ".*K",
+];
+
+#[test]
+fn test_inline_flags() {
+    for pattern in INLINE {
+        for haystack in HAYSTACKS {
+            check_all_engines_with(pattern, haystack, Flags::default());
+        }
+    }
+}
+
+#[test]
+fn test_builder_flag() {
+    let flags = Flags {
+        case_insensitive: true,
+        ..Flags::default()
+    };
+    for pattern in BUILDER.iter().chain(INLINE) {
+        for haystack in HAYSTACKS {
+            check_all_engines_with(pattern, haystack, flags);
+        }
+    }
+}
+
+#[test]
+fn test_builder_flag_without_unicode() {
+    let flags = Flags {
+        case_insensitive: true,
+        unicode: false,
+    };
+    for pattern in ASCII {
+        for haystack in HAYSTACKS {
+            check_all_engines_with(pattern, haystack, flags);
+        }
+    }
+}
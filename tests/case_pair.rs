@@ -0,0 +1,127 @@
+//! The case-sensitive and case-insensitive regexes of a pattern built
+//! together, see `Builder::build_case_pair`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::thompson::bytecode::{ClassCache, Compiler};
+use gregex::{Builder, EngineKind, Regex};
+
+/// Scripts and digits, which folding leaves as they are, and Latin letters,
+/// which it changes.
+const MULTILINGUAL: &str =
+    r"[\p{Han}\p{Hiragana}\p{Katakana}]+|\p{Greek}+\d+|[\p{Cyrillic}\p{Nd}]+|[a-z\u{e0}-\u{ff}]+";
+
+fn assert_same(pair: &Regex, alone: &Regex, haystack: &str, context: &str) {
+    assert_eq!(
+        utils::match_spans(pair.find_all(haystack)),
+        utils::match_spans(alone.find_all(haystack)),
+        "{context}"
+    );
+    assert_eq!(
+        utils::find_groups(pair, haystack),
+        utils::find_groups(alone, haystack),
+        "{context}"
+    );
+}
+
+/// The size of the outlined classes of the pattern when compiled alone.
+fn class_bytes(builder: &Builder) -> usize {
+    let hir = Compiler::parse(builder.pattern(), builder.config()).unwrap();
+    let bytecode = Compiler::compile(hir, builder.config().clone()).unwrap();
+    let cache = ClassCache::new();
+    for class in &bytecode.outlined_classes {
+        cache.intern(class);
+    }
+    cache.bytes()
+}
+
+#[test]
+fn test_same_as_independent_builds() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        for engine in EngineKind::ALL {
+            let builder = Builder::new(*pattern);
+            let Ok((sensitive, insensitive)) = builder.build_case_pair(engine) else {
+                continue;
+            };
+            let alone = [false, true].map(|case_insensitive| {
+                builder
+                    .clone()
+                    .case_insensitive(case_insensitive)
+                    .build(engine)
+                    .unwrap()
+            });
+            let context = format!("{pattern:?} on {haystack:?} with {}", engine.name());
+            assert_same(&sensitive, &alone[0], haystack, &context);
+            assert_same(&insensitive, &alone[1], haystack, &context);
+        }
+    }
+}
+
+#[test]
+fn test_pair_order() {
+    let builder = Builder::new("straße").case_insensitive(true);
+    for engine in EngineKind::ALL {
+        let Ok((sensitive, insensitive)) = builder.build_case_pair(engine) else {
+            continue;
+        };
+        assert!(!sensitive.is_match("STRASSE Straße"));
+        assert_eq!(
+            utils::match_spans(sensitive.find_all("Straße straße")),
+            [(8, 15)]
+        );
+        assert_eq!(
+            utils::match_spans(insensitive.find_all("Straße straße")),
+            [(0, 7), (8, 15)]
+        );
+    }
+}
+
+#[test]
+fn test_shared_classes() {
+    let builder = Builder::new(MULTILINGUAL);
+    let cache = ClassCache::new();
+    let (sensitive, insensitive) = builder
+        .build_case_pair_with(EngineKind::PikeVM, &cache)
+        .unwrap();
+    // Only the Latin class differs, the other ones are stored once.
+    let shared = cache.bytes();
+    let sensitive_bytes = class_bytes(&builder);
+    let insensitive_bytes = class_bytes(&builder.clone().case_insensitive(true));
+    assert!(shared < sensitive_bytes + insensitive_bytes);
+    assert!(shared > insensitive_bytes.max(sensitive_bytes));
+
+    // Building the pair again adds nothing to the cache.
+    let (classes, bytes) = (cache.len(), cache.bytes());
+    builder
+        .build_case_pair_with(EngineKind::PikeVM, &cache)
+        .unwrap();
+    assert_eq!((cache.len(), cache.bytes()), (classes, bytes));
+
+    let haystack = "漢字かなカナ αβγ42 Привет2024 ÉCOLE école";
+    assert_eq!(
+        utils::match_spans(sensitive.find_all(haystack)),
+        [(0, 18), (19, 27), (28, 44), (52, 58)]
+    );
+    assert_eq!(
+        utils::match_spans(insensitive.find_all(haystack)),
+        [(0, 18), (19, 27), (28, 44), (45, 51), (52, 58)]
+    );
+}
+
+#[test]
+fn test_cache_is_optional() {
+    // The JIT keeps no classes, and a pattern without outlined classes adds
+    // nothing to the cache.
+    let cache = ClassCache::new();
+    Builder::new("[a-c]x|y")
+        .build_case_pair_with(EngineKind::PikeVM, &cache)
+        .unwrap();
+    assert!(cache.is_empty());
+    if let Ok((sensitive, insensitive)) =
+        Builder::new(MULTILINGUAL).build_case_pair_with(EngineKind::PikeJIT, &cache)
+    {
+        assert!(cache.is_empty());
+        assert!(!sensitive.is_match("ÉCOLE"));
+        assert!(insensitive.is_match("ÉCOLE"));
+    }
+}
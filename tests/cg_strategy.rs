@@ -0,0 +1,185 @@
+//! The CG strategies of the JIT, see `Builder::jit_cg_strategy`. That they
+//! all report the same captures is checked by the harness of `utils`, which
+//! builds the JIT with each of them.
+#![cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+
+#[allow(dead_code)]
+mod utils;
+
+use gregex::error::UnsupportedFeature;
+use gregex::util::Input;
+use gregex::{Builder, CgStrategy, Error, Regex};
+
+const STRATEGIES: [CgStrategy; 4] = [
+    CgStrategy::Registers,
+    CgStrategy::Tree,
+    CgStrategy::Array,
+    CgStrategy::CowArray,
+];
+
+#[test]
+fn test_auto_strategy() {
+    let builder = Builder::new(r"\d+").specialize(false);
+    let re = builder.pike_jit().unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::Registers));
+    // Disabled groups only need the registers.
+    let re = Builder::new(r"(\d+)-(\d+)").cg(false).pike_jit().unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::Registers));
+
+    let re = Regex::pike_jit(r"(\d+)-(\d+)").unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::Tree));
+    let many = r"(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)";
+    let re = Regex::pike_jit(many).unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::Array));
+}
+
+#[test]
+fn test_explicit_strategy() {
+    for pattern in [r"\d+", r"(\d+)-(\d+)?"] {
+        for strategy in STRATEGIES {
+            let builder = Builder::new(pattern).jit_cg_strategy(strategy);
+            let Ok(re) = builder.pike_jit() else {
+                assert_eq!(strategy, CgStrategy::Registers);
+                continue;
+            };
+            assert_eq!(re.jit_cg_strategy(), Some(strategy), "{pattern:?}");
+            let expected = utils::find_groups(&builder.pike_vm().unwrap(), "ab 12- 3");
+            assert_eq!(utils::find_groups(&re, "ab 12- 3"), expected, "{pattern:?}");
+            // The strategy is part of the config, and kept when rebuilding.
+            let rebuilt = re.rebuild(|builder| builder.cg(true)).unwrap();
+            assert_eq!(rebuilt.jit_cg_strategy(), Some(strategy));
+        }
+    }
+}
+
+#[test]
+fn test_registers_with_groups() {
+    let err = match Builder::new(r"(a)(b)?")
+        .jit_cg_strategy(CgStrategy::Registers)
+        .pike_jit()
+    {
+        Ok(_) => panic!("the registers only hold group 0"),
+        Err(err) => err,
+    };
+    let Error::Unsupported(UnsupportedFeature::CgStrategy(err)) = err else {
+        panic!("unexpected error {err:?}");
+    };
+    assert_eq!(err.strategy, CgStrategy::Registers);
+    assert_eq!(err.group_len, 3);
+    assert!(err.to_string().contains("2 capture groups"));
+}
+
+#[test]
+fn test_shortcuts_and_other_engines() {
+    // The array engines override the strategy of the builder.
+    let builder = Builder::new("(a)b").jit_cg_strategy(CgStrategy::Tree);
+    let re = builder.pike_jit_array().unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::Array));
+    let re = builder.pike_jit_cow_array().unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::CowArray));
+
+    // The Pike VM and the specialized engines don't run the JIT.
+    let re = builder.pike_vm().unwrap();
+    assert_eq!(re.jit_cg_strategy(), None);
+    let re = Regex::pike_jit("(?m)^$").unwrap();
+    assert_eq!(re.jit_cg_strategy(), None);
+    // A leading wildcard still runs the JIT on the whole pattern.
+    let re = Regex::pike_jit(".*(a)(b)").unwrap();
+    assert_eq!(re.jit_cg_strategy(), Some(CgStrategy::Tree));
+}
+
+#[test]
+fn test_cow_array_superseded_matches() {
+    // A match found first is replaced by a later one of higher priority,
+    // while the threads still alive share arrays with the accepted thread.
+    // In debug builds, the ref-counts of the arrays are also checked after
+    // every search.
+    let patterns = [
+        r"([a-z])+x|([a-z])+y",
+        r"([a-z])+x|([a-z])+",
+        r"(a+)x|(a)",
+        r"(a)+",
+        r"((a)|(b))+c?",
+        r"(a|ab)(c|bcd)?(d*)",
+    ];
+    let haystacks = [
+        "", "abx", "aby", "abcz", "aaax", "abxaby", "aaa aax", "abcd ab", "abab",
+    ];
+    for pattern in patterns {
+        let vm = Builder::new(pattern).pike_vm().unwrap();
+        let re = Builder::new(pattern)
+            .specialize(false)
+            .jit_cg_strategy(CgStrategy::CowArray)
+            .pike_jit()
+            .unwrap();
+        for haystack in haystacks {
+            let all = |re: &Regex| {
+                re.find_all_captures(haystack)
+                    .map(|caps| utils::group_spans(&caps))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(all(&re), all(&vm), "{pattern:?} on {haystack:?}");
+            for first_match in [false, true] {
+                let input = Input::new(haystack).first_match(first_match);
+                assert_eq!(
+                    utils::find_groups(&re, input.clone()),
+                    utils::find_groups(&vm, input),
+                    "{pattern:?} on {haystack:?} (first_match: {first_match})"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_match_only_jit() {
+    let haystack = "x abcde 12- 34-5 abcd";
+    for pattern in [r"a(b)(c)(d)(e)", r"(\d+)-(\d+)?", r"\d+"] {
+        for strategy in [CgStrategy::Auto, CgStrategy::Tree, CgStrategy::Array] {
+            let builder = Builder::new(pattern)
+                .specialize(false)
+                .jit_cg_strategy(strategy);
+            let Ok(expected) = builder.pike_jit() else {
+                continue;
+            };
+            let re = builder.match_only_jit(true).pike_jit().unwrap();
+            let ctx = format!("{pattern:?} with {strategy:?}");
+            // The regex still reports the strategy of its groups.
+            assert_eq!(re.jit_cg_strategy(), expected.jit_cg_strategy(), "{ctx}");
+            let spans = |re: &Regex| {
+                let found = re.find(haystack).map(|m| (m.start(), m.end()));
+                let validated = re.find_validating(haystack.as_bytes()).unwrap();
+                let interruptible = re.find_interruptible(haystack, 4, &|| false).unwrap();
+                let (all, more) = re.find_n(haystack, 2);
+                let all: Vec<_> = all.iter().map(|m| m.as_str()).collect();
+                (
+                    re.is_match(haystack),
+                    found,
+                    validated.map(|m| (m.start(), m.end())),
+                    interruptible.map(|m| (m.start(), m.end())),
+                    all,
+                    more,
+                )
+            };
+            assert_eq!(spans(&re), spans(&expected), "{ctx}");
+            // The groups are still tracked when needed, with the same state
+            // as the searches for the overall match in between.
+            for _ in 0..2 {
+                assert_eq!(
+                    utils::find_groups(&re, haystack),
+                    utils::find_groups(&expected, haystack),
+                    "{ctx}"
+                );
+                assert!(re.is_match(haystack), "{ctx}");
+            }
+            let captures = |re: &Regex| {
+                let (caps, more) = re.captures_n(haystack, 1);
+                let caps: Vec<_> = (0..caps[0].group_len())
+                    .map(|i| caps[0].get(i).map(|m| m.as_str()))
+                    .collect();
+                (caps, more)
+            };
+            assert_eq!(captures(&re), captures(&expected), "{ctx}");
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! The classes run by the engines are normalized: sorted, without empty,
+//! overlapping or adjacent ranges, see `IntervalSet::is_normalized`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use gregex::regex::RegexImpl;
+use gregex::thompson::bytecode::{Bytecode, Compiler, Instruction};
+use gregex::thompson::pike_vm::PikeVM;
+use gregex::util::{Char, Input, IntervalSet, Span};
+
+fn compile(builder: &Builder) -> Option<Bytecode> {
+    let hir = Compiler::parse(builder.pattern(), builder.config()).ok()?;
+    Compiler::compile(hir, builder.config().clone()).ok()
+}
+
+fn ranges(pairs: &[(char, char)]) -> Box<[(Char, Char)]> {
+    pairs
+        .iter()
+        .map(|(from, to)| ((*from).into(), (*to).into()))
+        .collect()
+}
+
+/// A bytecode matching a single char of the class, inline or outlined.
+fn single_class(class: Box<[(Char, Char)]>, outlined: bool) -> Bytecode {
+    let (consume, outlined_classes) = if outlined {
+        (Instruction::ConsumeOutlined(0), vec![class.into()])
+    } else {
+        (Instruction::ConsumeClass(class), Vec::new())
+    };
+    Bytecode {
+        instructions: vec![consume, Instruction::Accept(0)],
+        barriers: vec![false; 2],
+        outlined_classes,
+        ..Bytecode::default()
+    }
+}
+
+#[test]
+fn test_compiled_classes() {
+    let patterns = [
+        "(?i)[a-z]",
+        "(?i)[k-m]",
+        "(?i)[a-fA-F0-9]",
+        "(?i)[^a-z]",
+        "(?i-u)[a-z]",
+        "(?i)[\u{17f}s]",
+        "(?-u)[\\x00-\\x7f\\x80-\\xff]",
+    ];
+    let corpus = utils::corpus::CASES.iter().map(|(pattern, _)| *pattern);
+    for pattern in patterns.into_iter().chain(corpus) {
+        for case_insensitive in [false, true] {
+            let builders = [
+                Builder::new(pattern).case_insensitive(case_insensitive),
+                Builder::new(pattern)
+                    .case_insensitive(case_insensitive)
+                    .force_class_outlining(true),
+            ];
+            for builder in &builders {
+                let Some(bytecode) = compile(builder) else {
+                    continue;
+                };
+                assert!(bytecode.classes_are_normalized(), "{pattern:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_case_insensitive_ascii_class() {
+    // The folding of `k` and `s` adds the Kelvin sign and the long s past the
+    // ASCII ranges.
+    let bytecode = compile(&Builder::new("(?i)[a-z]")).unwrap();
+    let classes: Vec<_> = bytecode.classes().collect();
+    assert_eq!(
+        classes,
+        [&*ranges(&[
+            ('A', 'Z'),
+            ('a', 'z'),
+            ('\u{17f}', '\u{17f}'),
+            ('\u{212a}', '\u{212a}'),
+        ])]
+    );
+}
+
+#[test]
+fn test_normalize_hand_built_classes() {
+    let class = ranges(&[('x', 'z'), ('a', 'c'), ('b', 'e'), ('f', 'f'), ('q', 'p')]);
+    for outlined in [false, true] {
+        let mut bytecode = single_class(class.clone(), outlined);
+        assert!(!bytecode.classes_are_normalized());
+        bytecode.normalize_classes();
+        assert!(bytecode.classes_are_normalized());
+        let classes: Vec<_> = bytecode.classes().collect();
+        assert_eq!(classes, [&*ranges(&[('a', 'f'), ('x', 'z')])]);
+
+        // With the first range past `d` checked first, the VM would miss it.
+        let vm = PikeVM::from_bytecode(bytecode, 1);
+        let mut state = vm.new_state();
+        for (haystack, expected) in [("d", true), ("f", true), ("y", true), ("p", false)] {
+            let mut captures = [Span::invalid()];
+            let found = vm.exec(Input::new(haystack), &mut state, &mut captures);
+            assert_eq!(found, expected, "{haystack:?} outlined: {outlined}");
+        }
+    }
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "unnormalized class")]
+fn test_vm_rejects_unnormalized_class() {
+    PikeVM::from_bytecode(single_class(ranges(&[('x', 'z'), ('a', 'c')]), false), 1);
+}
+
+#[test]
+#[cfg(all(
+    debug_assertions,
+    feature = "jit",
+    target_arch = "x86_64",
+    any(unix, windows)
+))]
+#[should_panic(expected = "unnormalized class")]
+fn test_jit_rejects_unnormalized_class() {
+    use gregex::thompson::pike_jit::PikeJIT;
+    use gregex::thompson::pike_jit::cg_impl_register::CGImplReg;
+    use gregex::thompson::pike_jit::executable::SystemAllocator;
+
+    let bytecode = single_class(ranges(&[('a', 'c'), ('b', 'd')]), true);
+    let _ = PikeJIT::compile::<CGImplReg>(&bytecode, 1, &SystemAllocator);
+}
+
+#[test]
+fn test_is_normalized_matches_from_ranges() {
+    // `IntervalSet::from_ranges` keeps exactly the normalized ranges as is.
+    let samples = [
+        &[('a', 'c'), ('e', 'g')][..],
+        &[('a', 'c'), ('d', 'g')],
+        &[('e', 'g'), ('a', 'c')],
+        &[('a', 'a')],
+        &[('b', 'a')],
+        &[],
+    ];
+    for sample in samples {
+        let class = ranges(sample);
+        let normalized = IntervalSet::from_ranges(class.iter().copied()).to_ranges();
+        assert_eq!(
+            IntervalSet::is_normalized(&class),
+            normalized == class,
+            "{sample:?}"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! The repetitions of a class, whose runs are checked at once by the JIT, and
+//! whose forks choose their thread from the current char.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::Input;
+
+const PATTERNS: &[&str] = &[
+    r"[a-z]{8,64}",
+    r"[a-z]{3}",
+    r"[a-z]{3,5}?",
+    r"[a-z]{2,16}\(",
+    r"[a-z]{2,16}?\(",
+    r"[a-z]{0,4}[0-9]",
+    r"[a-z]{0,4}?[0-9]",
+    r"[A-Za-z_][A-Za-z0-9_]{0,63}",
+    r"[a-z]+ [a-z]+",
+    r"[a-z]+? [a-z]+?",
+    r"[a-z]*:",
+    r"[a-z]*?:",
+    r"a{0,3}b",
+    r"a*?b",
+    // The follow set overlaps the class, the exit must be kept.
+    r"[a-z]{0,5}c",
+    r"[a-z]*ing",
+    r"[a-z]{1,3}[c-f]",
+    r"[a-z]*?[a-c]x",
+    r"[a-z]{2,4}(?:x|:)",
+    // The exit checks an assertion or accepts first.
+    r"[a-z]{1,3}$",
+    r"(?m)[a-z]+$",
+    r"[a-z]{1,3}",
+    r"[a-z]{1,3}?",
+    // Captures around the repetition.
+    r"([a-z]{2,4})(\()",
+    r"([a-z]{2,4}?)(:)?",
+    r"(?:([a-z]{3})[0-9])+",
+    r"([a-z]*)([0-9]+)",
+    r"(x?)([a-z]{2,3})(;|:)",
+    // Runs of larger classes, and of repeated groups.
+    r"[a-df-hj-lm-pr-tv-z]{4,6}!",
+    r"\w{3,5}!",
+    r"[0-9]{4}-[0-9]{2}",
+    r"(?:[a-z][0-9]){2,3}",
+    r"(?:[a-z]{2}){1,3}:",
+    // Every iteration has its own copy of the body, each bounding the count.
+    r"(?:[a-z]{2,5}[0-9]){1,10}",
+    r"(?:[a-z]{2,5}?[0-9]){2,3}?",
+    r"(?:ab){1,2}",
+    r"[^ ]{3}",
+];
+
+const INPUTS: &[&str] = &[
+    "",
+    "abc",
+    "hello world",
+    "call foo(x) and barbaz(y)",
+    "key: value, other_key:",
+    "abcdefghijklmnopqrstuvwxyz0123",
+    "ab1 abcd2 abcde3 abcdef4",
+    "éclair à la crème: café(1)",
+    "2024-01-31 and 1999-12",
+    "a1b2c3d4 x9",
+    "aaab ab b",
+    "running jumping sing",
+    "word\nanother line\nend",
+    "naïve: façade(x)",
+];
+
+#[test]
+fn test_class_repetitions_match_rust_regex() {
+    for pattern in PATTERNS {
+        for input in INPUTS {
+            utils::check_all_engines(pattern, input);
+        }
+    }
+}
+
+#[test]
+fn test_class_run_span_end() {
+    let subject = "xx abcdefgh yy";
+    for engine in utils::compile_all("[a-z]{8}").unwrap() {
+        let input = Input::new(subject).span((0..10).into());
+        assert!(engine.find(input).is_none());
+        let input = Input::new(subject).span((0..11).into());
+        assert_eq!(engine.find(input).unwrap().span.from, 3);
+        let input = Input::new(subject).span((4..14).into());
+        assert!(engine.find(input).is_none());
+    }
+}
+
+#[test]
+fn test_long_class_runs() {
+    let subject = format!("{} {}!", "a".repeat(999), "b".repeat(1000));
+    for engine in utils::compile_all("[a-z]{1000}").unwrap() {
+        let found = engine.find(subject.as_str()).unwrap();
+        assert_eq!((found.span.from, found.span.to), (1000, 2000));
+    }
+    for engine in utils::compile_all(r"([a-z]*)(!)").unwrap() {
+        let caps = engine.find_captures(subject.as_str()).unwrap();
+        assert_eq!(caps.get(1).unwrap().span.from, 1000);
+    }
+}
@@ -0,0 +1,138 @@
+use gregex::compare::{DiffLimits, PatternDiff, diff_patterns};
+use gregex::{Builder, EngineKind, Regex};
+
+/// Both regexes, with every available engine.
+fn pairs(old: &str, new: &str) -> Vec<(Regex, Regex)> {
+    EngineKind::ALL
+        .into_iter()
+        .filter_map(|engine| {
+            let old = Builder::new(old).build(engine).ok()?;
+            let new = Builder::new(new).build(engine).ok()?;
+            Some((old, new))
+        })
+        .collect()
+}
+
+type Bounds = Option<(usize, usize)>;
+
+/// The (haystack index, position, old span, new span) of the divergences.
+fn divergences(diff: &PatternDiff) -> Vec<(usize, usize, Bounds, Bounds)> {
+    let bounds = |span: Option<gregex::util::Span>| span.map(|span| (span.from, span.to));
+    diff.divergences
+        .iter()
+        .map(|d| (d.haystack_index, d.position, bounds(d.old), bounds(d.new)))
+        .collect()
+}
+
+/// Lines with addresses, where every 7th line also has a `.org` one.
+fn address_corpus() -> Vec<String> {
+    (0..50)
+        .map(|i| {
+            let mut line = format!("line {i}: mail bob@example.com today");
+            if i % 7 == 3 {
+                line.push_str(" or alice@example.org");
+            }
+            line
+        })
+        .collect()
+}
+
+#[test]
+fn test_extra_matches() {
+    let corpus = address_corpus();
+    for (old, new) in pairs(r"[a-z]+@[a-z]+\.com", r"[a-z]+@[a-z]+\.(?:com|org)") {
+        let diff = diff_patterns(
+            &old,
+            &new,
+            corpus.iter().map(String::as_str),
+            DiffLimits::default(),
+        );
+        let expected: Vec<_> = (0..50)
+            .filter(|i| i % 7 == 3)
+            .map(|i: usize| {
+                let from = corpus[i].find("alice").unwrap();
+                (i, from, None, Some((from, corpus[i].len())))
+            })
+            .collect();
+        assert_eq!(divergences(&diff), expected, "{:?}", old.engine_kind());
+        assert_eq!(diff.haystacks, 50);
+        assert_eq!(diff.different, expected.len());
+        assert_eq!(diff.count_differs, expected.len());
+        assert_eq!(diff.identical, 50 - expected.len());
+        assert!(diff.divergences.iter().all(|d| d.match_index == 1));
+    }
+}
+
+#[test]
+fn test_different_spans() {
+    let corpus = [
+        "call 555-1234 now",
+        "ext 555-12345 later",
+        "none here",
+        "555-0000 and 555-99999",
+    ];
+    for (old, new) in pairs(r"\d{3}-\d{4}", r"\d{3}-\d{4,5}") {
+        let diff = diff_patterns(&old, &new, corpus, DiffLimits::default());
+        assert_eq!(
+            divergences(&diff),
+            [
+                (1, 4, Some((4, 12)), Some((4, 13))),
+                (3, 13, Some((13, 21)), Some((13, 22))),
+            ]
+        );
+        assert_eq!(diff.divergences[1].match_index, 1);
+        assert_eq!(
+            (diff.identical, diff.different, diff.count_differs),
+            (2, 2, 0)
+        );
+        assert!(!diff.is_identical());
+    }
+}
+
+#[test]
+fn test_identical() {
+    let corpus = address_corpus();
+    for (old, new) in pairs(r"[a-z]+@[a-z]+\.(?:com|org)", r"[a-z]+@[a-z]+\.(?:org|com)") {
+        let diff = diff_patterns(
+            &old,
+            &new,
+            corpus.iter().map(String::as_str),
+            DiffLimits::default(),
+        );
+        assert!(diff.is_identical());
+        assert_eq!(diff.identical, 50);
+        assert!(diff.divergences.is_empty());
+    }
+}
+
+#[test]
+fn test_limits_and_report() {
+    let corpus = address_corpus();
+    let old = Regex::pike_vm(r"[a-z]+@[a-z]+\.com").unwrap();
+    let new = Regex::pike_vm(r"[a-z]+@[a-z]+\.(?:com|org)").unwrap();
+    let limits = DiffLimits {
+        max_haystacks: Some(20),
+        max_divergences: 2,
+    };
+    let diff = diff_patterns(&old, &new, corpus.iter().map(String::as_str), limits);
+    assert_eq!(diff.haystacks, 20);
+    // The lines 3, 10 and 17 differ, the last one is only counted.
+    assert_eq!(diff.different, 3);
+    assert_eq!(
+        divergences(&diff).iter().map(|d| d.0).collect::<Vec<_>>(),
+        [3, 10]
+    );
+    let report = diff.to_string();
+    let lines: Vec<_> = report.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            r#"old pattern "[a-z]+@[a-z]+\\.com""#,
+            r#"new pattern "[a-z]+@[a-z]+\\.(?:com|org)""#,
+            "20 haystacks: 17 identical, 3 different (3 with a different number of matches)",
+            "haystack 3 at 38: match 1 is no match (old) vs 38..55 (new)",
+            "haystack 10 at 39: match 1 is no match (old) vs 39..56 (new)",
+            "... and 1 more different haystacks",
+        ]
+    );
+}
@@ -0,0 +1,230 @@
+//! The examples of the documentation of the regex crate, run against the
+//! compatibility facade, and comparisons of both crates.
+use gregex::compat as regex;
+use regex::{Captures, NoExpand, Regex};
+
+#[test]
+fn test_regex_crate_examples() {
+    let re = Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap();
+    assert!(re.is_match("2010-03-14"));
+
+    let re = Regex::new(r"[a-z]+").unwrap();
+    let hay = "123 foo 456";
+    let m = re.find(hay).unwrap();
+    assert_eq!(4..7, m.range());
+    assert_eq!("foo", m.as_str());
+    assert_eq!((m.start(), m.end(), m.len()), (4, 7, 3));
+
+    let re = Regex::new(r"[a-z]{13}").unwrap();
+    let hay = "Retroactively relinquishing remunerations is reprehensible.";
+    let matches: Vec<_> = re.find_iter(hay).map(|m| m.as_str()).collect();
+    assert_eq!(
+        matches,
+        vec!["relinquishing", "remunerations", "reprehensible"]
+    );
+
+    let re = Regex::new(r"'([^']+)'\s+\((\d{4})\)").unwrap();
+    let hay = "Not my favorite movie: 'Citizen Kane' (1941).";
+    let caps = re.captures(hay).unwrap();
+    assert_eq!(caps.get(0).unwrap().as_str(), "'Citizen Kane' (1941)");
+    assert_eq!(caps.get(1).unwrap().as_str(), "Citizen Kane");
+    assert_eq!(caps.get(2).unwrap().as_str(), "1941");
+    // You can also access the groups by index using the Index notation.
+    // Note that this will panic on an invalid index. In this case, these
+    // accesses are always correct because the overall regex will only
+    // match when these capture groups match.
+    assert_eq!(&caps[0], "'Citizen Kane' (1941)");
+    assert_eq!(&caps[1], "Citizen Kane");
+    assert_eq!(&caps[2], "1941");
+
+    let re = Regex::new(r"'([^']+)'\s+\(([0-9]{4})\)").unwrap();
+    let hay = "'Citizen Kane' (1941), 'The Wizard of Oz' (1939), 'M' (1931).";
+    let mut movies = vec![];
+    for caps in re.captures_iter(hay) {
+        let title = caps.get(1).unwrap().as_str();
+        let year = caps.get(2).unwrap().as_str();
+        movies.push((title, year.parse::<i64>().unwrap()));
+    }
+    assert_eq!(
+        movies,
+        vec![
+            ("Citizen Kane", 1941),
+            ("The Wizard of Oz", 1939),
+            ("M", 1931),
+        ]
+    );
+
+    let re = Regex::new(r"(\w)(\d)?").unwrap();
+    assert_eq!(re.captures_len(), 3);
+
+    let re = Regex::new(r"[ \t]+").unwrap();
+    let hay = "a b \t  c\td    e";
+    let fields: Vec<&str> = re.split(hay).collect();
+    assert_eq!(fields, vec!["a", "b", "c", "d", "e"]);
+    let re = Regex::new(r"X").unwrap();
+    let got: Vec<&str> = re.split("lionXXtigerXleopard").collect();
+    assert_eq!(got, vec!["lion", "", "tiger", "leopard"]);
+    let re = Regex::new(r"::").unwrap();
+    let got: Vec<&str> = re.split("lion::tiger::leopard").collect();
+    assert_eq!(got, vec!["lion", "tiger", "leopard"]);
+    let re = Regex::new(r"").unwrap();
+    let got: Vec<&str> = re.split("rust").collect();
+    assert_eq!(got, vec!["", "r", "u", "s", "t", ""]);
+    let re = Regex::new(r"X").unwrap();
+    let got: Vec<&str> = re.split("").collect();
+    assert_eq!(got, vec![""]);
+    let got: Vec<&str> = re.split("XXXXaXXbXc").collect();
+    assert_eq!(got, vec!["", "", "", "", "a", "", "b", "c"]);
+
+    let re = Regex::new(r"[^01]+").unwrap();
+    assert_eq!(re.replace("1078910", ""), "1010");
+
+    let re = Regex::new(r"([^,\s]+),\s+(\S+)").unwrap();
+    let result = re.replace("Springsteen, Bruce", |caps: &Captures| {
+        format!("{} {}", &caps[2], &caps[1])
+    });
+    assert_eq!(result, "Bruce Springsteen");
+    let result = re.replace("Springsteen, Bruce", "$2 $1");
+    assert_eq!(result, "Bruce Springsteen");
+    let result = re.replace("Springsteen, Bruce", NoExpand("$2 $1"));
+    assert_eq!(result, "$2 $1");
+
+    let re = Regex::new(r"(\w+)\s+(\w+)").unwrap();
+    let result = re.replace("deep fried", "${1}_$2");
+    assert_eq!(result, "deep_fried");
+    // $1_ is a reference to a group named "1_".
+    let result = re.replace("deep fried", "$1_$2");
+    assert_eq!(result, "fried");
+
+    let re = Regex::new(r"(?m)^(\S+)[\s--\r\n]+(\S+)$").unwrap();
+    let hay = "
+Greetings  1973
+Wild\t1973
+BornToRun\t\t\t\t1975
+Darkness                    1978
+TheRiver 1980
+";
+    let new = re.replace_all(hay, "$2 $1");
+    assert_eq!(
+        new,
+        "
+1973 Greetings
+1973 Wild
+1975 BornToRun
+1978 Darkness
+1980 TheRiver
+"
+    );
+
+    assert_eq!(regex::escape(r"a.b*c"), r"a\.b\*c");
+}
+
+#[test]
+fn test_borrowed_when_nothing_replaced() {
+    let re = Regex::new("x").unwrap();
+    assert!(matches!(
+        re.replace_all("abc", "y"),
+        std::borrow::Cow::Borrowed("abc")
+    ));
+    assert!(matches!(
+        re.replace_all("axc", "y"),
+        std::borrow::Cow::Owned(_)
+    ));
+}
+
+#[test]
+fn test_unsupported_patterns() {
+    for pattern in [r"(?P<x>a)(?P<x>b)", "a("] {
+        let err = Regex::new(pattern).unwrap_err();
+        assert!(matches!(err, regex::Error::Syntax(_)), "{pattern:?}");
+    }
+    assert_eq!("a+".parse::<Regex>().unwrap().as_str(), "a+");
+    assert_eq!(Regex::new("a+").unwrap().to_string(), "a+");
+}
+
+#[test]
+fn test_same_as_regex_crate() {
+    let cases = [
+        ("a*", "baaab"),
+        ("", "héllo"),
+        (r"\d*", "a12b3"),
+        ("(a)|(b)", "xaybz"),
+        (r"(\w+)@(\w+)\.com", "me@example.com, you@test.com"),
+        ("x*|y", "yxxy"),
+        (r"(?m)^\s*$", "a\n\n  \nb\n"),
+        ("é|", "aéb"),
+        (r"\b\w|\B", "née à l'été"),
+        (
+            r"(?P<user>\w+)@(?<host>\w+)\.com",
+            "me@example.com, you@test.com",
+        ),
+    ];
+    let replacements = [
+        "",
+        "<$0>",
+        "$2-$1",
+        "${1}x",
+        "$1x",
+        "$$",
+        "$",
+        "${",
+        "$10",
+        "[$9]",
+        "$user",
+        "${host}.$user",
+        "$nope",
+    ];
+    for (pattern, haystack) in cases {
+        let ours = Regex::new(pattern).unwrap();
+        let rust = ::regex::Regex::new(pattern).unwrap();
+        let ctx = format!("{pattern:?} on {haystack:?}");
+        assert_eq!(ours.captures_len(), rust.captures_len(), "{ctx}");
+        assert!(ours.capture_names().eq(rust.capture_names()), "{ctx}");
+        assert_eq!(
+            ours.find_iter(haystack)
+                .map(|m| m.range())
+                .collect::<Vec<_>>(),
+            rust.find_iter(haystack)
+                .map(|m| m.range())
+                .collect::<Vec<_>>(),
+            "{ctx}"
+        );
+        let ours_groups: Vec<Vec<_>> = ours
+            .captures_iter(haystack)
+            .map(|caps| {
+                (0..caps.len())
+                    .map(|i| caps.get(i).map(|m| m.range()))
+                    .collect()
+            })
+            .collect();
+        let rust_groups: Vec<Vec<_>> = rust
+            .captures_iter(haystack)
+            .map(|caps| caps.iter().map(|m| m.map(|m| m.range())).collect())
+            .collect();
+        assert_eq!(ours_groups, rust_groups, "{ctx}");
+        assert_eq!(
+            ours.split(haystack).collect::<Vec<_>>(),
+            rust.split(haystack).collect::<Vec<_>>(),
+            "{ctx}"
+        );
+        for replacement in replacements {
+            assert_eq!(
+                ours.replace_all(haystack, replacement),
+                rust.replace_all(haystack, replacement),
+                "{ctx} with {replacement:?}"
+            );
+            assert_eq!(
+                ours.replace(haystack, replacement),
+                rust.replace(haystack, replacement),
+                "{ctx} with {replacement:?}"
+            );
+            for limit in 0..3 {
+                assert_eq!(
+                    ours.replacen(haystack, limit, replacement),
+                    rust.replacen(haystack, limit, replacement),
+                    "{ctx} with {replacement:?}, at most {limit}"
+                );
+            }
+        }
+    }
+}
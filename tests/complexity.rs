@@ -0,0 +1,189 @@
+//! The complexity estimates of the patterns, see `Regex::complexity`: their
+//! values for known shapes of patterns, and the ranking they give, which is
+//! what they are meant for.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::complexity::{ComplexityEstimate, ComplexityNote};
+use gregex::lint::LintKind;
+use gregex::regex::Config;
+
+fn complexity(pattern: &str) -> ComplexityEstimate {
+    Regex::pike_vm(pattern).unwrap().complexity()
+}
+
+/// The bytecode length, the thread bound and the per-char cost.
+fn estimates(pattern: &str) -> (usize, usize, usize) {
+    let estimate = complexity(pattern);
+    (
+        estimate.bytecode_len,
+        estimate.max_threads_bound,
+        estimate.per_char_cost_estimate,
+    )
+}
+
+#[test]
+fn test_known_shapes() {
+    // A thread per char of the literal, one at the Accept, and the one
+    // starting at the next char.
+    assert_eq!(estimates("abc"), (4, 5, 3));
+    assert_eq!(estimates("(?i)abc"), (4, 5, 6));
+    assert_eq!(estimates("colou?r"), (8, 8, 6));
+    assert_eq!(estimates("[0-9]+"), (3, 3, 1));
+    // A comparison per range of Unicode `\d`.
+    assert_eq!(estimates(r"\d+"), (3, 3, 71));
+    assert_eq!(estimates("^$"), (3, 2, 0));
+    // The threads never go past an empty class.
+    assert_eq!(estimates(r"[^\s\S]a").2, 1);
+    assert_eq!(estimates(r"(?s:.){0,300}x"), (602, 303, 301));
+    for pattern in ["abc", "[0-9]+", r"\d+", "(?:foo|bar)+"] {
+        assert_eq!(complexity(pattern).notes, [], "{pattern:?}");
+    }
+}
+
+#[test]
+fn test_notes() {
+    let estimate = complexity(r"\w{150}");
+    assert_eq!(
+        estimate.notes,
+        [
+            ComplexityNote::UnrolledRepetition {
+                times: 150,
+                instructions: 150
+            },
+            ComplexityNote::LargeClass {
+                ranges: 796,
+                copies: 150
+            }
+        ]
+    );
+    assert_eq!(
+        estimate.notes[0].to_string(),
+        "Bounded repetition unrolled 150x, into 150 instructions"
+    );
+    assert_eq!(
+        estimate.notes[1].to_string(),
+        "Class with 796 ranges consulted on every char, 150 times"
+    );
+    assert_eq!(
+        complexity(r"\p{L}+").notes,
+        [ComplexityNote::LargeClass {
+            ranges: 677,
+            copies: 1
+        }]
+    );
+    // Only the alternations looping back on themselves.
+    let branches = "(?:foo|bar|baz|qux|quux|corge|grault|garply)";
+    assert_eq!(
+        complexity(&format!("{branches}+")).notes,
+        [ComplexityNote::RepeatedAlternation { branches: 8 }]
+    );
+    assert_eq!(complexity(branches).notes, []);
+    assert_eq!(complexity(&format!("{branches}{{2}}")).notes, []);
+    // The repetitions in the order of the pattern, the nested ones included.
+    assert_eq!(
+        complexity("(?:a{100}b){100}|c{200,}").notes,
+        [
+            ComplexityNote::UnrolledRepetition {
+                times: 100,
+                instructions: 10100
+            },
+            ComplexityNote::UnrolledRepetition {
+                times: 100,
+                instructions: 100
+            },
+            ComplexityNote::UnrolledRepetition {
+                times: 200,
+                instructions: 201
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_pathological_patterns_score_higher() {
+    let in_corpus = |pattern: &'static str| {
+        assert!(
+            utils::corpus::CASES.iter().any(|(p, _)| *p == pattern),
+            "{pattern:?}"
+        );
+        pattern
+    };
+    let simple = ["foo", "colou?r", "[a-z]{3}", "ab{2,4}c", "(?i)abc", "^$"].map(in_corpus);
+    let pathological = [
+        r"\p{L}+",
+        r"(\s*)((?:# [Nn][Oo][Qq][Aa])(?::\s?(([A-Z]+[0-9]+(?:[,\s]+)?)+))?)",
+        r"Sherlock Holmes|Shrelock Holm|John Watson|Irene Adler|Inspector Lestrade|Professor Moriarty",
+    ]
+    .map(in_corpus);
+    let max_simple = simple
+        .iter()
+        .map(|pattern| complexity(pattern).per_char_cost_estimate)
+        .max()
+        .unwrap();
+    for pattern in pathological {
+        let cost = complexity(pattern).per_char_cost_estimate;
+        assert!(cost >= 5 * max_simple, "{pattern:?}: {cost}");
+    }
+    // The shapes which are slow under a Pike VM: many threads each seeing a
+    // large class, or a large alternation under a repetition.
+    for pattern in [r"(?:\w|-){0,200}x", r"[\w\p{Greek}]{50}", r".{0,1000}x"] {
+        let estimate = complexity(pattern);
+        assert!(
+            estimate.per_char_cost_estimate >= 100 * max_simple,
+            "{pattern:?}: {estimate:?}"
+        );
+        assert!(estimate.likely_slow(), "{pattern:?}");
+    }
+    let repeated = complexity("(?:foo|bar|baz|qux|quux|corge|grault|garply)+");
+    assert!(repeated.max_threads_bound >= 4 * complexity("foo|bar").max_threads_bound);
+}
+
+#[test]
+fn test_same_estimate_for_every_engine() {
+    for (pattern, _) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        let expected = complexity(pattern);
+        for re in engines {
+            assert_eq!(re.complexity(), expected, "{pattern:?}");
+        }
+    }
+}
+
+#[test]
+fn test_lint() {
+    let config = Config::default();
+    let slow = gregex::lint(r"\w{150}", &config);
+    let messages: Vec<_> = slow
+        .iter()
+        .filter(|warning| warning.kind == LintKind::SlowPattern)
+        .map(|warning| (warning.message.as_str(), warning.span.from, warning.span.to))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "Bounded repetition unrolled 150x, into 150 instructions",
+                0,
+                7
+            ),
+            (
+                "Class with 796 ranges consulted on every char, 150 times",
+                0,
+                7
+            ),
+        ]
+    );
+    // A large class alone is not slow enough.
+    assert_eq!(gregex::lint(r"\w+", &config).len(), 0);
+    // Too large to be compiled.
+    let huge = gregex::lint(r"(?:\w{1000}){1000}", &config);
+    assert_eq!(huge.len(), 1);
+    assert_eq!(
+        huge[0].message,
+        "The pattern compiles to 1000001 instructions, over the limit of 100000"
+    );
+}
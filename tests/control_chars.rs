@@ -0,0 +1,105 @@
+//! The ASCII control chars (`\x00` to `\x1f`, and `\x7f`) in the patterns and
+//! in the haystacks, which every engine must match like rust-regex. They are
+//! regular chars for the engines: the edges of the haystack are
+//! `Char::INPUT_BOUND`, past every char, and the zeros of the JIT are thread
+//! data, never chars.
+#[allow(dead_code)]
+mod utils;
+
+fn controls() -> impl Iterator<Item = char> {
+    ('\x00'..='\x1f').chain(['\x7f'])
+}
+
+/// Every control char between letters, with a NUL at each edge.
+fn all_controls() -> String {
+    let mut haystack = String::from("\0");
+    for c in controls() {
+        haystack.push('a');
+        haystack.push(c);
+    }
+    haystack.push('\0');
+    haystack
+}
+
+#[test]
+fn test_each_control_char() {
+    let all = all_controls();
+    for c in controls() {
+        let code = c as u32;
+        let patterns = [
+            format!(r"\x{code:02x}"),
+            format!(r"(\x{code:02x}+)a"),
+            format!(r"[\x{code:02x}b]"),
+            format!(r"[^\x{code:02x}]+"),
+            // The char itself, not escaped
+            format!("a{c}"),
+        ];
+        let haystacks = [
+            all.clone(),
+            c.to_string(),
+            format!("{c}{c}a{c}"),
+            format!("b{c}"),
+        ];
+        for pattern in &patterns {
+            for haystack in &haystacks {
+                utils::check_all_engines(pattern, haystack);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_control_classes() {
+    let patterns = [
+        r"[\x00-\x1f]+",
+        r"[^\x00-\x1f]+",
+        r"[\x00\x7f]",
+        r"[\x01-\x1e]+",
+        r"[[:cntrl:]]+",
+        r"\p{Cc}+",
+        r"(?-u:[\x00-\x7f])+",
+        r"(?i)[\x00-\x1f]",
+        r"\x00*a",
+        r"\x00|\x00\x00",
+        r"(\x00)(\x01)?|(\x7f)",
+        r"(?s).\x00",
+        r"[^a]\x00[^a]",
+        r"\s+",
+        r"\W+",
+    ];
+    let haystacks = [
+        all_controls(),
+        String::new(),
+        "\0".to_string(),
+        "\0\0\0".to_string(),
+        "a\0b\x7f".to_string(),
+        "\x7f\0\x1f\n\r\0".to_string(),
+        "\0\x01\0\x01\x7f".to_string(),
+    ];
+    for pattern in patterns {
+        for haystack in &haystacks {
+            utils::check_all_engines(pattern, haystack);
+        }
+    }
+}
+
+#[test]
+fn test_at_the_edges() {
+    // The assertions look at the char before and after the position, which
+    // is a NUL here rather than the edge of the haystack.
+    let patterns = [
+        r"^\x00",
+        r"\x00$",
+        r"\A\x00|\x00\z",
+        r"(?m)^\x00$",
+        r"(?mR)^\x00$",
+        r"^$",
+        r"(?m)^",
+    ];
+    let haystacks = ["\0", "\0\0", "a\0", "\0a", "\0\n\0", "\r\0\n", "\x7f"];
+    for pattern in patterns {
+        for haystack in haystacks {
+            utils::check_all_engines(pattern, haystack);
+        }
+    }
+}
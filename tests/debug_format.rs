@@ -0,0 +1,121 @@
+//! The `Debug` formats of the matches, which show the matched texts but never
+//! the subject, and cut the long ones.
+use std::sync::Arc;
+
+use gregex::Regex;
+use gregex::util::{Captures, Match, OwnedMatch, Span};
+
+#[test]
+fn test_span() {
+    assert_eq!(format!("{:?}", Span { from: 3, to: 6 }), "3..6");
+    assert_eq!(format!("{:?}", Span { from: 2, to: 1 }), "2..1");
+    assert_eq!(
+        format!("{:?}", [Span::from(0..0), Span::from(1..4)]),
+        "[0..0, 1..4]"
+    );
+}
+
+#[test]
+fn test_match() {
+    let subject = "xx foo yy";
+    let m = Match::new(subject, 3..6);
+    assert_eq!(
+        format!("{m:?}"),
+        r#"Match { start: 3, end: 6, string: "foo" }"#
+    );
+    assert_eq!(
+        format!("{m:#?}"),
+        "Match {\n    start: 3,\n    end: 6,\n    string: \"foo\",\n}"
+    );
+    let empty = Match::new(subject, 9..9);
+    assert_eq!(
+        format!("{empty:?}"),
+        r#"Match { start: 9, end: 9, string: "" }"#
+    );
+    let owned = OwnedMatch::new(Arc::from(subject), 3..6);
+    assert_eq!(
+        format!("{owned:?}"),
+        r#"OwnedMatch { start: 3, end: 6, string: "foo" }"#
+    );
+}
+
+#[test]
+fn test_escaped_text() {
+    let subject = "a\tb\n\"c\\\u{0}\u{7f}é";
+    let m = Match::new(subject, 0..subject.len());
+    assert_eq!(
+        format!("{m:?}"),
+        r#"Match { start: 0, end: 11, string: "a\tb\n\"c\\\0\u{7f}é" }"#
+    );
+}
+
+#[test]
+fn test_long_text() {
+    // The subject is never shown, however long.
+    let subject = format!("{}foo{}", "x".repeat(1 << 20), "y".repeat(1 << 20));
+    let re = Regex::pike_vm("foo").unwrap();
+    let m = re.find(&*subject).unwrap();
+    assert_eq!(
+        format!("{m:?}"),
+        r#"Match { start: 1048576, end: 1048579, string: "foo" }"#
+    );
+
+    // Cut after 64 chars.
+    let m = Match::new(&subject, 0..(1 << 20));
+    let expected = format!(
+        r#"Match {{ start: 0, end: 1048576, string: "{}"... }}"#,
+        "x".repeat(64)
+    );
+    assert_eq!(format!("{m:?}"), expected);
+    let exactly = Match::new(&subject, 0..64);
+    assert!(!format!("{exactly:?}").contains("..."));
+    let one_more = Match::new(&subject, 0..65);
+    assert!(format!("{one_more:?}").ends_with(r#""... }"#));
+
+    // The cut never splits a char, and counts the chars, not the bytes.
+    let subject = "日本".repeat(40);
+    let m = Match::new(&subject, 0..subject.len());
+    let expected = format!(
+        r#"Match {{ start: 0, end: 240, string: "{}"... }}"#,
+        "日本".repeat(32)
+    );
+    assert_eq!(format!("{m:?}"), expected);
+
+    // An escaped char counts as one.
+    let subject = "\n".repeat(100);
+    let m = Match::new(&subject, 0..subject.len());
+    let expected = format!(
+        r#"Match {{ start: 0, end: 100, string: "{}"... }}"#,
+        r"\n".repeat(64)
+    );
+    assert_eq!(format!("{m:?}"), expected);
+}
+
+#[test]
+fn test_captures() {
+    let re = Regex::pike_vm(r"(a)|(b)(c)?").unwrap();
+    let caps = re.find_captures("xb").unwrap();
+    assert_eq!(
+        format!("{caps:?}"),
+        r#"Captures({0: Some("b"), 1: None, 2: Some("b"), 3: None})"#
+    );
+    let owned = re.find_all_captures_owned("xb").next().unwrap();
+    assert_eq!(
+        format!("{owned:?}"),
+        r#"OwnedCaptures({0: Some("b"), 1: None, 2: Some("b"), 3: None})"#
+    );
+    let empty = Captures::new("", vec![Span::from(0..0)].into_boxed_slice());
+    assert_eq!(format!("{empty:?}"), r#"Captures({0: Some("")})"#);
+
+    let subject = format!("<{}>", "é".repeat(100));
+    let re = Regex::pike_vm(r"<(\w+)>").unwrap();
+    let caps = re.find_captures(&*subject).unwrap();
+    let long = format!(r#""{}"..."#, "é".repeat(64));
+    assert_eq!(
+        format!("{caps:?}"),
+        format!(
+            r#"Captures({{0: Some("<{}"...), 1: Some({long})}})"#,
+            "é".repeat(63)
+        )
+    );
+}
@@ -0,0 +1,39 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::{Builder, EngineKind};
+
+const DEPTH: usize = 1000;
+
+fn nested(depth: usize) -> String {
+    "(".repeat(depth) + "a" + &")".repeat(depth)
+}
+
+#[test]
+fn test_default_nest_limit_rejects_deep_patterns() {
+    assert!(Builder::new(nested(DEPTH)).pike_vm().is_err());
+    assert!(Builder::new(nested(200)).pike_vm().is_ok());
+    assert!(Builder::new(nested(20)).nest_limit(10).pike_vm().is_err());
+}
+
+#[test]
+fn test_deeply_nested_groups() {
+    // Every group opens before the 'a' and closes after it, so each thread
+    // writes all of them without consuming anything. This must neither
+    // overflow the stack when compiling, nor in the jitted code.
+    let builder = Builder::new(nested(DEPTH)).nest_limit(2 * DEPTH as u32);
+    for engine in EngineKind::ALL {
+        let Ok(re) = builder.build(engine) else {
+            assert!(!utils::jit_available(), "{}", engine.name());
+            continue;
+        };
+        let caps = re.find_captures("xxaxx").unwrap();
+        assert_eq!(caps.group_len(), DEPTH + 1, "{}", engine.name());
+        for i in 0..=DEPTH {
+            let group = caps.get(i).unwrap();
+            assert_eq!((group.start(), group.end()), (2, 3), "{}", engine.name());
+        }
+        assert_eq!(re.find_all_captures("aaa").count(), 3);
+        assert!(re.find_captures("xxx").is_none());
+    }
+}
@@ -0,0 +1,114 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::{Builder, Document, EngineKind, MatchKind, Regex};
+
+/// Checks that the searches in the document of the text report the same
+/// matches as the plain ones.
+fn check(re: &Regex, text: &str, what: &str) {
+    let document = Document::new(text);
+    let found = re.find(text).map(|m| (m.start(), m.end()));
+    let in_document = re.find_in_document(&document).map(|m| (m.start(), m.end()));
+    assert_eq!(in_document, found, "find {what} on {text:?}");
+    assert_eq!(
+        utils::match_spans(re.find_all_in_document(&document)),
+        utils::match_spans(re.find_all(text)),
+        "find_all {what} on {text:?}"
+    );
+}
+
+#[test]
+fn test_same_as_plain_searches_on_corpus() {
+    for (pattern, input) in utils::corpus::CASES {
+        for kind in EngineKind::ALL {
+            for prefilter in [true, false] {
+                let Ok(re) = Builder::new(*pattern).prefilter(prefilter).build(kind) else {
+                    continue;
+                };
+                check(&re, input, &format!("{pattern:?} ({kind:?})"));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_same_as_plain_searches_with_lines() {
+    let patterns = [
+        r"(?m)^",
+        r"(?m)$",
+        r"(?m)^$",
+        r"(?m)$^",
+        r"(?m)^\w+",
+        r"(?m)^(?:a|b\n)*",
+        r"(?m)^a|^b",
+        r"(?m)^a|b",
+        r"^a",
+        r"\Aa|\Ab",
+        r"$",
+        r"(?m)a$",
+        r"(?m)(?:^|,)\w",
+        r"(?m)^é+",
+        r"(?m)x*$",
+    ];
+    let texts = [
+        "",
+        "\n",
+        "\n\n",
+        "a\nb\n",
+        "ab\nba\n\nbb",
+        "b\nab,a\né\néé",
+        "\naa\n",
+    ];
+    for pattern in patterns {
+        for match_kind in [MatchKind::LeftmostFirst, MatchKind::LeftmostLongest] {
+            let builder = Builder::new(pattern).match_kind(match_kind);
+            for kind in EngineKind::ALL {
+                for specialize in [true, false] {
+                    let Ok(re) = builder.clone().specialize(specialize).build(kind) else {
+                        continue;
+                    };
+                    for text in texts {
+                        check(&re, text, &format!("{pattern:?} ({kind:?} {match_kind:?})"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_preprocessing() {
+    let document = Document::new("ab\nc\n\né");
+    assert_eq!(document.newlines(), &[2, 4, 5]);
+    assert_eq!(document.line_count(), 4);
+    assert_eq!(document.byte_count(b'\n'), 3);
+    assert_eq!(document.byte_count(b'z'), 0);
+    assert_eq!(document.byte_count("é".as_bytes()[0]), 1);
+    assert_eq!(Document::new("").line_count(), 1);
+
+    // None of the required bytes is in the document.
+    let re = Regex::pike_vm(r"\d+=\d+").unwrap();
+    assert!(re.find_in_document(&document).is_none());
+}
+
+#[test]
+fn test_shared_between_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Document<'_>>();
+
+    let text = "key: value\nother: thing\n".repeat(10);
+    let document = Document::new(&text);
+    let patterns = [r"(?m)^\w+", r"\w+$", ":"];
+    std::thread::scope(|scope| {
+        for pattern in patterns {
+            let document = &document;
+            scope.spawn(move || {
+                let re = Regex::pike_vm(pattern).unwrap();
+                assert_eq!(
+                    utils::match_spans(re.find_all_in_document(document)),
+                    utils::match_spans(re.find_all(document.text()))
+                );
+            });
+        }
+    });
+}
@@ -0,0 +1,96 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use gregex::thompson::bytecode::{CompileNote, Compiler, Instruction};
+
+/// The targets of every ForkN of the pattern.
+fn fork_targets(pattern: &str) -> Vec<usize> {
+    let builder = Builder::new(pattern);
+    let hir = Compiler::parse(builder.pattern(), builder.config()).unwrap();
+    let bytecode = Compiler::compile(hir, builder.config().clone()).unwrap();
+    bytecode
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::ForkN(targets) => Some(targets.len()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_identical_groups_report_the_first_one() {
+    let expected = regex::Regex::new("(x)|(x)").unwrap();
+    let expected = expected.captures("x").unwrap();
+    assert!(expected.get(1).is_some() && expected.get(2).is_none());
+    for (label, re) in utils::compile_all_labeled("(x)|(x)").unwrap() {
+        let caps = re.find_captures("x").unwrap();
+        assert_eq!(caps.group_len(), 3, "{label}");
+        assert!(caps.participated(1), "{label}");
+        assert!(!caps.participated(2), "{label}");
+    }
+    // Groups are never identical, so nothing is removed.
+    assert_eq!(Builder::new("(x)|(x)").notes().unwrap(), vec![]);
+}
+
+#[test]
+fn test_duplicate_branches_are_noted() {
+    let duplicate = |branch: &str| CompileNote::DuplicateBranch {
+        branch: branch.to_string(),
+    };
+    assert_eq!(
+        Builder::new("foo|foo|bar").notes().unwrap(),
+        vec![duplicate("(?:foo)")]
+    );
+    assert_eq!(
+        Builder::new("(?:a+|a+|a+)b|c").notes().unwrap(),
+        vec![duplicate("a+"), duplicate("a+")]
+    );
+    // Only adjacent duplicates are removed.
+    assert_eq!(Builder::new("foo|bar|foo").notes().unwrap(), vec![]);
+    assert_eq!(Builder::new("a|||b").notes().unwrap().len(), 1);
+    assert!(Builder::new("a(").notes().is_err());
+    assert_eq!(
+        duplicate("(?:foo)").to_string(),
+        "Duplicate alternation branch \"(?:foo)\" was removed"
+    );
+
+    assert_eq!(fork_targets("foo|foo|bar"), vec![2]);
+    assert_eq!(fork_targets("foo|foo"), vec![]);
+}
+
+#[test]
+fn test_empty_branches_share_their_target() {
+    // Both empty branches jump to the end of the alternation.
+    assert_eq!(fork_targets("x(?:|a|)"), vec![2]);
+    assert_eq!(fork_targets("x(?:a||b)"), vec![3]);
+}
+
+#[test]
+fn test_same_matches_as_regex_crate() {
+    let cases = [
+        ("foo|foo|bar", ["xbar foo", "foo", "fo"]),
+        ("a|||b", ["ba", "b", "a"]),
+        ("x(?:|a|)y", ["xay", "xy", "xby"]),
+        ("(?:a+|a+|b)c", ["bc", "aac", "c"]),
+        ("(a)|(a)|a", ["a", "ba", ""]),
+        ("(?:|a)+b", ["aab", "b", "a"]),
+        ("(?:(a)|(a)|())+", ["aa", "", "b"]),
+        ("(?:a||b)*c", ["abc", "bc", "c"]),
+    ];
+    for (pattern, inputs) in cases {
+        let rust = regex::Regex::new(pattern).unwrap();
+        for input in inputs {
+            let expected = rust.captures(input).map(|caps| {
+                caps.iter()
+                    .map(|g| g.map(|g| (g.start(), g.end())))
+                    .collect::<Vec<_>>()
+            });
+            for (label, re) in utils::compile_all_labeled(pattern).unwrap() {
+                let found = utils::find_groups(&re, input);
+                assert_eq!(found, expected, "{pattern:?} on {input:?} with {label}");
+            }
+        }
+    }
+}
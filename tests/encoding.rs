@@ -0,0 +1,239 @@
+#[allow(dead_code)]
+mod utils;
+
+use std::fmt::Write;
+
+use gregex::Builder;
+use gregex::thompson::bytecode::encoding::{
+    self, DecodeError, HEADER_LEN, InstructionCursor, Opcode,
+};
+use gregex::thompson::bytecode::{Bytecode, Compiler, Instruction, LookKind};
+use gregex::util::Char;
+use regex_syntax::hir::{Look, LookSet};
+
+/// Set this variable to rewrite the golden file after an intended change of
+/// the encoding (which must also bump `encoding::VERSION`).
+const BLESS_VAR: &str = "GREGEX_BLESS";
+const GOLDEN_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/encoding.txt");
+
+const GOLDEN_PATTERNS: &[&str] = &[
+    "a",
+    "a|bc",
+    "(a+)?b",
+    "(?m)^[a-ce-gi-km-oq]*$",
+    "(?-u)x{2,3}?[0-9a-f]",
+];
+
+fn compile(pattern: &str) -> Option<Bytecode> {
+    let builder = Builder::new(pattern);
+    let hir = Compiler::parse(pattern, builder.config()).ok()?;
+    Compiler::compile(hir, builder.config().clone()).ok()
+}
+
+#[test]
+fn test_encoding_round_trip() {
+    for (pattern, _) in utils::corpus::CASES {
+        let Some(bytecode) = compile(pattern) else {
+            continue;
+        };
+        let words = encoding::encode(&bytecode);
+        assert_eq!(
+            encoding::decode(&words).as_ref(),
+            Ok(&bytecode),
+            "Round trip failed for {pattern:?}"
+        );
+    }
+}
+
+#[test]
+fn test_cursor_offsets() {
+    for (pattern, _) in utils::corpus::CASES {
+        let Some(bytecode) = compile(pattern) else {
+            continue;
+        };
+        let words = encoding::encode(&bytecode);
+        let cursor = InstructionCursor::new(&words).unwrap();
+        assert_eq!(cursor.instruction_count(), bytecode.instructions.len());
+        let mut expected_offset = HEADER_LEN;
+        for (pc, decoded) in cursor.enumerate() {
+            let decoded = decoded.unwrap();
+            assert_eq!(decoded.pc, pc);
+            assert_eq!(decoded.offset, expected_offset);
+            assert_eq!(decoded.instruction, bytecode.instructions[pc]);
+            assert_eq!(decoded.barrier, bytecode.barriers[pc]);
+            assert_eq!(
+                words[decoded.offset] & 0xff,
+                Opcode::of(&decoded.instruction) as u32
+            );
+            expected_offset = match decoded.instruction {
+                Instruction::Fork2(_, _) => expected_offset + 3,
+                Instruction::ForkN(ref targets) => expected_offset + 2 + targets.len(),
+                _ => expected_offset + 2,
+            };
+        }
+    }
+}
+
+#[test]
+fn test_decode_rejects_invalid_streams() {
+    let words = encoding::encode(&compile("a|bc").unwrap());
+
+    assert_eq!(
+        encoding::decode(&words[..3]).unwrap_err(),
+        DecodeError::Truncated { offset: 0 }
+    );
+
+    let mut bad_magic = words.clone();
+    bad_magic[0] ^= 1 << 31;
+    assert_eq!(
+        encoding::decode(&bad_magic).unwrap_err(),
+        DecodeError::BadMagic
+    );
+
+    let mut bad_version = words.clone();
+    bad_version[0] += 1;
+    assert_eq!(
+        encoding::decode(&bad_version).unwrap_err(),
+        DecodeError::UnsupportedVersion(encoding::VERSION + 1)
+    );
+
+    let mut bad_opcode = words.clone();
+    bad_opcode[HEADER_LEN] = 200;
+    assert_eq!(
+        encoding::decode(&bad_opcode).unwrap_err(),
+        DecodeError::InvalidOpcode {
+            offset: HEADER_LEN,
+            word: 200
+        }
+    );
+
+    // The first instruction is the ForkN of the alternation, make its first
+    // target point past the end.
+    let mut bad_target = words.clone();
+    bad_target[HEADER_LEN + 2] = 1000;
+    assert_eq!(
+        encoding::decode(&bad_target).unwrap_err(),
+        DecodeError::InvalidOperand { offset: HEADER_LEN }
+    );
+
+    let mut bad_count = words.clone();
+    bad_count[2] += 1;
+    assert_eq!(
+        encoding::decode(&bad_count).unwrap_err(),
+        DecodeError::InvalidHeader
+    );
+}
+
+#[test]
+fn test_looks() {
+    // Every supported assertion has a number, and converts back and forth.
+    for (id, look) in encoding::LOOKS.iter().enumerate() {
+        assert_eq!(LookKind::from(Look::from(*look)), *look);
+        let words = encoding::encode(&compile(&format!("{}a", look_pattern(*look))).unwrap());
+        let assertion = encoding::decode(&words).unwrap().instructions[0].clone();
+        assert_eq!(assertion, Instruction::Assertion(*look));
+        assert_eq!(words[HEADER_LEN + 1], id as u32);
+    }
+    for look in LookSet::full().iter() {
+        assert!(encoding::LOOKS.contains(&LookKind::from(look)), "{look:?}");
+    }
+
+    // The numbers past the table are rejected.
+    let mut words = encoding::encode(&compile("^a").unwrap());
+    words[HEADER_LEN + 1] = encoding::LOOKS.len() as u32;
+    assert_eq!(
+        encoding::decode(&words).unwrap_err(),
+        DecodeError::InvalidOperand { offset: HEADER_LEN }
+    );
+}
+
+fn look_pattern(look: LookKind) -> &'static str {
+    match look {
+        LookKind::Start => r"\A",
+        LookKind::End => r"\z",
+        LookKind::StartLF => "(?m)^",
+        LookKind::EndLF => "(?m)$",
+        LookKind::StartCRLF => "(?mR)^",
+        LookKind::EndCRLF => "(?mR)$",
+        LookKind::WordUnicode => r"\b",
+        LookKind::WordUnicodeNegate => r"\B",
+        LookKind::WordAscii => r"(?-u:\b)",
+        LookKind::WordAsciiNegate => r"(?-u:\B)",
+        LookKind::WordStartUnicode => r"\<",
+        LookKind::WordEndUnicode => r"\>",
+        LookKind::WordStartAscii => r"(?-u:\<)",
+        LookKind::WordEndAscii => r"(?-u:\>)",
+        LookKind::WordStartHalfUnicode => r"\b{start-half}",
+        LookKind::WordEndHalfUnicode => r"\b{end-half}",
+        LookKind::WordStartHalfAscii => r"(?-u:\b{start-half})",
+        LookKind::WordEndHalfAscii => r"(?-u:\b{end-half})",
+    }
+}
+
+#[test]
+fn test_decode_rejects_unnormalized_classes() {
+    // The encoder writes the classes as they are, only the decoder checks them.
+    let c = |c: char| Char::from(c);
+    for class in [
+        vec![(c('x'), c('z')), (c('a'), c('c'))],
+        vec![(c('a'), c('m')), (c('k'), c('z'))],
+        vec![(c('a'), c('c')), (c('d'), c('f'))],
+        vec![(c('c'), c('a'))],
+    ] {
+        let bytecode = Bytecode {
+            instructions: vec![
+                Instruction::ConsumeClass(class.clone().into()),
+                Instruction::Accept(0),
+            ],
+            barriers: vec![false; 2],
+            ..Bytecode::default()
+        };
+        let words = encoding::encode(&bytecode);
+        assert_eq!(
+            encoding::decode(&words).unwrap_err(),
+            DecodeError::InvalidOperand { offset: HEADER_LEN },
+            "{class:?}"
+        );
+
+        let outlined = Bytecode {
+            instructions: vec![Instruction::ConsumeOutlined(0), Instruction::Accept(0)],
+            barriers: vec![false; 2],
+            outlined_classes: vec![class.into()],
+            ..Bytecode::default()
+        };
+        let words = encoding::encode(&outlined);
+        assert!(encoding::decode(&words).is_err());
+    }
+}
+
+fn golden() -> String {
+    let mut out = String::new();
+    for pattern in GOLDEN_PATTERNS {
+        let words = encoding::encode(&compile(pattern).unwrap());
+        writeln!(out, "pattern: {pattern}").unwrap();
+        for line in words.chunks(8) {
+            let line = line
+                .iter()
+                .map(|word| format!("{word:08x}"))
+                .collect::<Vec<_>>();
+            writeln!(out, "{}", line.join(" ")).unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    out
+}
+
+#[test]
+fn test_encoding_golden() {
+    let actual = golden();
+    if std::env::var_os(BLESS_VAR).is_some() {
+        std::fs::write(GOLDEN_PATH, &actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(GOLDEN_PATH).unwrap();
+    assert!(
+        actual == expected,
+        "The encoding changed, if this is intended bump encoding::VERSION and \
+         rerun with {BLESS_VAR}=1 to update {GOLDEN_PATH}.\nNew encoding:\n{actual}"
+    );
+}
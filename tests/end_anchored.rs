@@ -0,0 +1,92 @@
+//! The patterns whose matches end at the end of the haystack, with a bounded
+//! length, whose searches only start near the end of the span.
+#[allow(dead_code)]
+mod utils;
+
+use std::cell::Cell;
+
+use gregex::util::Input;
+use gregex::{Builder, EngineKind};
+
+const CASES: &[(&str, &str)] = &[
+    (r"\.log$", "app.log.1 app.log"),
+    (r"\.log$", "app.log.1"),
+    (r"(\w+)\.log\z", "a.log b.log"),
+    (r"a?$", "bab"),
+    (r"a?$", "b"),
+    ("$", "abc"),
+    ("$", ""),
+    ("a$", ""),
+    ("é$", "aéé"),
+    ("(?:é|ab)$", "éab"),
+    (r"\bfoo$", "afoo"),
+    (r"\bfoo$", "a foo"),
+    (r"\Bfoo$", "afoo"),
+    ("(a|ab)(c|bcd)?$", "xabcd"),
+    ("^abc$", "abc"),
+    ("^abc$", "xabc"),
+    ("(?:a$)|(?:b$)", "ab"),
+    ("a$|b", "ba"),
+    ("(?m)a$", "a\nba"),
+    ("(?R)a$", "ba\r\n"),
+    (r"[^\n]{1,3}$", "a\nbcdef"),
+    ("x+$", "abxx"),
+];
+
+#[test]
+fn test_same_matches_as_the_full_search() {
+    for (pattern, haystack) in CASES {
+        utils::check_all_engines(pattern, haystack);
+    }
+}
+
+/// The number of chars the engine read, counted by the checks of an
+/// interruptible search.
+fn chars_read(builder: &Builder, kind: EngineKind, input: Input<'_>) -> usize {
+    let Ok(re) = builder.build(kind) else {
+        return 0;
+    };
+    let calls = Cell::new(0);
+    let interrupt = || {
+        calls.set(calls.get() + 1);
+        false
+    };
+    let found = re.find_interruptible(input, 1, &interrupt).unwrap();
+    assert!(found.is_some(), "{}", kind.name());
+    calls.get()
+}
+
+#[test]
+fn test_search_starts_near_the_end() {
+    let haystack = format!("{}.log", "x".repeat(10_000));
+    for kind in EngineKind::ALL {
+        let builder = Builder::new(r"\.log$").prefilter(false);
+        let windowed = chars_read(&builder, kind, Input::new(&haystack));
+        assert!(windowed < 20, "{} read {windowed} chars", kind.name());
+        // Unless specialization is disabled.
+        let full = chars_read(
+            &builder.clone().specialize(false),
+            kind,
+            Input::new(&haystack),
+        );
+        assert!(
+            full == 0 || full > 10_000,
+            "{} read {full} chars",
+            kind.name()
+        );
+        // Within a span, from its end.
+        let input = Input::new(&haystack).span((0..haystack.len()).into());
+        assert!(chars_read(&builder, kind, input) < 20, "{}", kind.name());
+    }
+}
+
+#[test]
+fn test_window_of_multibyte_chars() {
+    // The window starts 4 bytes before the end, inside the `é`, and is moved
+    // back to its start.
+    let re = Builder::new("(?:é|ab)c$").pike_vm().unwrap();
+    assert_eq!(re.find("éééc").unwrap().start(), 4);
+    assert_eq!(re.maximum_len(), Some(3));
+    let re = Builder::new(r"\w{2}$").pike_vm().unwrap();
+    assert_eq!(re.find("aé日").unwrap().as_str(), "é日");
+}
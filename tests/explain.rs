@@ -0,0 +1,94 @@
+use gregex::Builder;
+use gregex::util::{ExplainTrace, Explanation};
+
+fn explain(pattern: &str, input: &str) -> (Option<(usize, usize)>, ExplainTrace) {
+    let re = Builder::new(pattern).specialize(false).pike_vm().unwrap();
+    match re.find_explain(input) {
+        Some((found, Explanation::PikeVM(trace))) => (Some((found.start(), found.end())), trace),
+        Some((_, Explanation::Unavailable)) => panic!("The VM must explain its matches"),
+        None => (None, ExplainTrace::default()),
+    }
+}
+
+fn span(span: Option<gregex::util::Span>) -> Option<(usize, usize)> {
+    span.map(|span| (span.from, span.to))
+}
+
+#[test]
+fn test_explain_leftmost_first_alternation() {
+    // The first alternative wins, even though the second one is longer. Its
+    // accept kills the thread of the second one before it can accept.
+    let (found, trace) = explain("a|ab", "xab");
+    assert_eq!(found, Some((1, 2)));
+    assert_eq!(trace.earliest_surviving_start, Some(1));
+    assert_eq!(trace.accepts, 1);
+    assert_eq!(span(trace.first_accept), Some((1, 2)));
+    assert_eq!(span(trace.last_accept), Some((1, 2)));
+    assert_eq!(trace.pruned_starts, 0);
+
+    // With the alternatives swapped, the longer one has priority.
+    let (found, trace) = explain("ab|a", "xab");
+    assert_eq!(found, Some((1, 3)));
+    assert_eq!(trace.accepts, 2);
+    assert_eq!(span(trace.first_accept), Some((1, 2)));
+    assert_eq!(span(trace.last_accept), Some((1, 3)));
+}
+
+#[test]
+fn test_explain_greedy_repetition() {
+    let (found, trace) = explain("a+", "baaa");
+    assert_eq!(found, Some((1, 4)));
+    assert_eq!(trace.accepts, 3);
+    assert_eq!(span(trace.first_accept), Some((1, 2)));
+    assert_eq!(span(trace.last_accept), Some((1, 4)));
+
+    let (found, trace) = explain("a+?", "baaa");
+    assert_eq!(found, Some((1, 2)));
+    assert_eq!(trace.accepts, 1);
+}
+
+#[test]
+fn test_explain_earlier_start_failed() {
+    // A match starting at 0 was attempted, but died on the 'd'.
+    let (found, trace) = explain("abc|b", "abd");
+    assert_eq!(found, Some((1, 2)));
+    assert_eq!(trace.earliest_surviving_start, Some(0));
+    assert_eq!(trace.accepts, 1);
+
+    let (found, trace) = explain("^$", "");
+    assert_eq!(found, Some((0, 0)));
+    assert_eq!(trace.earliest_surviving_start, None);
+
+    let (found, _) = explain("z", "abd");
+    assert_eq!(found, None);
+}
+
+#[test]
+fn test_explain_display() {
+    let (_, trace) = explain("abc|b", "abd");
+    assert_eq!(
+        trace.to_string(),
+        "earliest start consuming input: 0\n\
+         start positions pruned: 0\n\
+         accepts considered: 1 (first 1..2, last 1..2)"
+    );
+}
+
+#[test]
+fn test_explain_unavailable_for_jit() {
+    if !cfg!(all(
+        feature = "jit",
+        target_arch = "x86_64",
+        any(unix, windows)
+    )) {
+        return;
+    }
+    let re = Builder::new("a|ab").pike_jit().unwrap();
+    let (found, explanation) = re.find_explain("xab").unwrap();
+    assert_eq!(found.as_str(), "a");
+    assert!(matches!(explanation, Explanation::Unavailable));
+    assert_eq!(
+        explanation.to_string(),
+        "No explanation available for this engine"
+    );
+}
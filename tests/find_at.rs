@@ -0,0 +1,98 @@
+//! The searches from an offset of the haystack, `Regex::find_at` and
+//! `Regex::captures_at`, where the assertions see the chars before the offset.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::util::{Input, InvalidSpan};
+use regex as rust_regex;
+use utils::compile_all_labeled;
+
+#[test]
+fn test_same_as_regex_crate() {
+    let patterns = [
+        "^",
+        "^é",
+        r"\A\w",
+        "(?m)^",
+        r"(?m)^\w+",
+        "(?mR)^.",
+        r"\b",
+        r"\B\w",
+        r"\b\w+",
+        r"(\w)\b",
+        r"\<(\w+)",
+        r"\b{end}",
+        r"\b{start-half}(\w)",
+        r"(?-u:\b)\w",
+        "(é)|x",
+    ];
+    let haystacks = ["éa", "aé b", "日\n本é", "\r\néé\n", "x é日x", "ß"];
+    for pattern in patterns {
+        let rust = rust_regex::Regex::new(pattern).unwrap();
+        for (label, ours) in compile_all_labeled(pattern).unwrap() {
+            for haystack in haystacks {
+                let starts = (0..=haystack.len()).filter(|i| haystack.is_char_boundary(*i));
+                for start in starts {
+                    let ctx = format!("{label} for {pattern:?} on {haystack:?} at {start}");
+                    assert_eq!(
+                        ours.find_at(haystack, start).map(|m| (m.start(), m.end())),
+                        rust.find_at(haystack, start).map(|m| (m.start(), m.end())),
+                        "{ctx}"
+                    );
+                    let our_groups = ours.captures_at(haystack, start).map(|caps| {
+                        (0..caps.group_len())
+                            .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                            .collect::<Vec<_>>()
+                    });
+                    let rust_groups = rust.captures_at(haystack, start).map(|caps| {
+                        caps.iter()
+                            .map(|m| m.map(|m| (m.start(), m.end())))
+                            .collect::<Vec<_>>()
+                    });
+                    assert_eq!(our_groups, rust_groups, "{ctx}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_unlike_searching_the_rest() {
+    let re = Regex::pike_vm(r"^\w").unwrap();
+    assert!(re.find_at("éa", 2).is_none());
+    assert_eq!(re.find(&"éa"[2..]).unwrap().as_str(), "a");
+    let re = Regex::pike_vm(r"(?m)^(\w)").unwrap();
+    let caps = re.captures_at("é\nb", 3).unwrap();
+    assert_eq!(caps.get(1).unwrap().start(), 3);
+    assert!(re.find_at("é\nb", 4).is_none());
+    // At the end of the haystack.
+    assert!(Regex::pike_vm(r"\z").unwrap().find_at("é", 2).is_some());
+}
+
+#[test]
+#[should_panic(expected = "Invalid start 1")]
+fn test_start_inside_a_char() {
+    Regex::pike_vm("a").unwrap().find_at("éa", 1);
+}
+
+#[test]
+#[should_panic(expected = "Invalid start 4")]
+fn test_start_past_the_end() {
+    Regex::pike_vm("a").unwrap().captures_at("éa", 4);
+}
+
+#[test]
+fn test_try_span() {
+    let input = |span: std::ops::Range<usize>| Input::new("aé日").try_span(span.into());
+    assert!(input(0..6).is_ok());
+    assert!(input(3..3).is_ok());
+    for (from, to) in [(2, 6), (0, 4), (3, 1), (0, 7)] {
+        assert_eq!(input(from..to).err(), Some(InvalidSpan { from, to }));
+    }
+    let err = input(2..6).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "The span 2..6 is not a range of char boundaries of the subject"
+    );
+}
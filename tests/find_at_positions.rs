@@ -0,0 +1,122 @@
+//! The anchored searches at candidate positions, see `Regex::find_at_positions`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::regex::{InvalidPosition, InvalidPositions};
+use gregex::util::Input;
+
+type Found = Option<(usize, (usize, usize))>;
+
+/// The loop of anchored searches replaced by `find_at_positions`.
+fn naive(re: &Regex, haystack: &str, positions: &[usize]) -> Found {
+    positions.iter().enumerate().find_map(|(index, &position)| {
+        if !haystack.is_char_boundary(position) {
+            return None;
+        }
+        let input = Input::new(haystack)
+            .span((position..haystack.len()).into())
+            .anchored(true);
+        re.find(input).map(|m| (index, (m.start(), m.end())))
+    })
+}
+
+fn find(re: &Regex, haystack: &str, positions: &[usize]) -> Found {
+    re.find_at_positions(haystack, positions, InvalidPositions::Skip)
+        .unwrap()
+        .map(|(index, m)| (index, (m.start(), m.end())))
+}
+
+#[test]
+fn test_same_as_naive_loop() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        // Every offset, including the ones inside chars, in several orders.
+        let forward: Vec<_> = (0..=haystack.len() + 1).collect();
+        let backward: Vec<_> = forward.iter().rev().copied().collect();
+        let odd_first: Vec<_> = forward
+            .iter()
+            .filter(|p| *p % 2 == 1)
+            .chain(forward.iter().filter(|p| *p % 2 == 0))
+            .copied()
+            .collect();
+        for re in &engines {
+            for positions in [&forward, &backward, &odd_first] {
+                assert_eq!(
+                    find(re, haystack, positions),
+                    naive(re, haystack, positions),
+                    "{pattern:?} on {haystack:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_first_position_in_order_wins() {
+    for re in utils::compile_all(r"\{(\w+)\}").unwrap() {
+        let haystack = "{a} x {bc} {d}";
+        // The positions are tried in the given order, not from left to right.
+        assert_eq!(find(&re, haystack, &[11, 0, 6]), Some((0, (11, 14))));
+        assert_eq!(find(&re, haystack, &[1, 6, 0]), Some((1, (6, 10))));
+        assert_eq!(find(&re, haystack, &[1, 2, 3]), None);
+        assert_eq!(find(&re, haystack, &[]), None);
+        // A repeated position is found at its first index.
+        assert_eq!(find(&re, haystack, &[6, 6]), Some((0, (6, 10))));
+    }
+}
+
+#[test]
+fn test_empty_match_at_end() {
+    for re in utils::compile_all("x*").unwrap() {
+        assert_eq!(find(&re, "ab", &[2]), Some((0, (2, 2))));
+        assert_eq!(find(&re, "ab", &[3, 1]), Some((1, (1, 1))));
+    }
+}
+
+#[test]
+fn test_invalid_positions() {
+    let re = Regex::pike_vm("é+").unwrap();
+    let haystack = "aéé";
+    // Offset 2 is inside the first `é`, 6 is past the end.
+    let positions = [2, 6, 3];
+    let found = re
+        .find_at_positions(haystack, &positions, InvalidPositions::Skip)
+        .unwrap()
+        .unwrap();
+    assert_eq!((found.0, found.1.as_str()), (2, "é"));
+    let err = re
+        .find_at_positions(haystack, &positions, InvalidPositions::Reject)
+        .unwrap_err();
+    assert_eq!(
+        err,
+        InvalidPosition {
+            index: 0,
+            position: 2
+        }
+    );
+    assert!(err.to_string().contains("not a char boundary"));
+
+    // The positions are checked before searching.
+    let err = re
+        .find_at_positions(haystack, &[1, 7], InvalidPositions::Reject)
+        .unwrap_err();
+    assert_eq!(err.index, 1);
+    assert!(
+        re.find_at_positions(haystack, &[1, 3], InvalidPositions::Reject)
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_prefilter() {
+    // `=` is required, and only occurs before the positions.
+    for re in utils::compile_all(r"\d+=\d+").unwrap() {
+        let haystack = "1=2 and 3 4";
+        assert_eq!(find(&re, haystack, &[8, 10]), None);
+        assert_eq!(find(&re, haystack, &[8, 0]), Some((1, (0, 3))));
+    }
+}
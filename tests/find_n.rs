@@ -0,0 +1,164 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::sink::SpanVecSink;
+use gregex::util::Input;
+use gregex::{Document, Regex};
+
+fn spans(re: &Regex, input: &str, n: usize) -> (Vec<(usize, usize)>, bool) {
+    let (matches, more) = re.find_n(input, n);
+    let spans = matches.iter().map(|m| (m.start(), m.end())).collect();
+    (spans, more)
+}
+
+#[test]
+fn test_find_n_is_find_all_take() {
+    for (pattern, input) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        for re in &engines {
+            let all: Vec<_> = re.find_all(*input).map(|m| (m.start(), m.end())).collect();
+            // Around the number of matches, which is the interesting part.
+            let mut ns = vec![0, 1, all.len().saturating_sub(1), all.len(), all.len() + 1];
+            ns.sort();
+            ns.dedup();
+            for n in ns {
+                let expected = &all[..n.min(all.len())];
+                let (found, more) = spans(re, input, n);
+                assert_eq!(found, expected, "{pattern:?} on {input:?}, n = {n}");
+                assert_eq!(more, all.len() > n, "{pattern:?} on {input:?}, n = {n}");
+
+                let (captures, more) = re.captures_n(*input, n);
+                let expected: Vec<_> = re.find_all_captures(*input).take(n).collect();
+                assert_eq!(captures.len(), expected.len());
+                for (caps, expected) in captures.iter().zip(&expected) {
+                    assert_eq!(
+                        utils::group_spans(caps),
+                        utils::group_spans(expected),
+                        "{pattern:?} on {input:?}"
+                    );
+                }
+                assert_eq!(more, all.len() > n, "{pattern:?} on {input:?}, n = {n}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_more_flag_at_the_boundaries() {
+    for re in utils::compile_all(r"\d+").unwrap() {
+        // Exactly n matches.
+        assert_eq!(
+            spans(&re, "1 22 333", 3),
+            (vec![(0, 1), (2, 4), (5, 8)], false)
+        );
+        // n + 1 matches.
+        assert_eq!(
+            spans(&re, "1 22 333 4", 3),
+            (vec![(0, 1), (2, 4), (5, 8)], true)
+        );
+        // None.
+        assert_eq!(spans(&re, "none", 3), (vec![], false));
+        assert_eq!(spans(&re, "none", 0), (vec![], false));
+        assert_eq!(spans(&re, "1", 0), (vec![], true));
+    }
+    // Empty matches, up to the one at the end of the input.
+    for re in utils::compile_all(r"a*").unwrap() {
+        assert_eq!(spans(&re, "b", 2), (vec![(0, 0), (1, 1)], false));
+        assert_eq!(spans(&re, "b", 1), (vec![(0, 0)], true));
+        let (captures, more) = re.captures_n("b", 3);
+        assert_eq!(captures.len(), 2);
+        assert_eq!(captures[1].get(0).unwrap().start(), 1);
+        assert!(!more);
+    }
+}
+
+/// Every search over the successive matches skips the empty match right after
+/// the previous match, like the regex crate does.
+#[test]
+fn test_iteration_apis_agree_on_empty_matches() {
+    let cases: [(&str, &[(usize, usize)]); 2] =
+        [("aaa", &[(0, 3)]), ("baaab", &[(0, 0), (1, 4), (5, 5)])];
+    let rust = regex::Regex::new("a*").unwrap();
+    let compat = gregex::compat::Regex::new("a*").unwrap();
+    let engines = utils::compile_all("a*").unwrap();
+    for (haystack, expected) in cases {
+        let rust: Vec<_> = rust
+            .find_iter(haystack)
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        assert_eq!(rust, expected);
+        let bounds: Vec<_> = compat
+            .find_iter(haystack)
+            .map(|m| (m.start(), m.end()))
+            .collect();
+        assert_eq!(bounds, expected, "compat find_iter");
+        let bounds: Vec<_> = compat
+            .captures_iter(haystack)
+            .map(|caps| caps.get(0).map(|m| (m.start(), m.end())).unwrap())
+            .collect();
+        assert_eq!(bounds, expected, "compat captures_iter");
+
+        // The pieces between the matches.
+        let mut pieces = Vec::new();
+        let mut last = 0;
+        for (start, end) in expected {
+            pieces.push(&haystack[last..*start]);
+            last = *end;
+        }
+        pieces.push(&haystack[last..]);
+        let replaced: String = pieces.join("-");
+
+        for re in &engines {
+            let label = format!("{haystack:?} with {:?}", re.engine_kind());
+            let bounds = |m: gregex::util::Match<'_>| (m.start(), m.end());
+            let all: Vec<_> = re.find_all(haystack).map(bounds).collect();
+            assert_eq!(all, expected, "find_all on {label}");
+            let all: Vec<_> = re
+                .find_all_captures(haystack)
+                .map(|caps| bounds(caps.group0()))
+                .collect();
+            assert_eq!(all, expected, "find_all_captures on {label}");
+            let all: Vec<_> = re
+                .find_all_owned(haystack)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            assert_eq!(all, expected, "find_all_owned on {label}");
+            let all: Vec<_> = re
+                .find_all_captures_owned(haystack)
+                .map(|caps| caps.get(0).map(|m| (m.start(), m.end())).unwrap())
+                .collect();
+            assert_eq!(all, expected, "find_all_captures_owned on {label}");
+            let mut all = Vec::new();
+            re.for_each_match(haystack, |m| all.push(bounds(m)));
+            assert_eq!(all, expected, "for_each_match on {label}");
+            let mut sink = SpanVecSink::new();
+            re.scan_into(haystack, &mut sink).unwrap();
+            let all: Vec<_> = sink.spans.iter().map(|s| (s.from, s.to)).collect();
+            assert_eq!(all, expected, "scan_into on {label}");
+            assert_eq!(spans(re, haystack, 10), (expected.to_vec(), false));
+            let (captures, more) = re.captures_n(haystack, 10);
+            let all: Vec<_> = captures.iter().map(|caps| bounds(caps.group0())).collect();
+            assert_eq!((all, more), (expected.to_vec(), false), "captures_n");
+            let stats = re.match_stats(haystack);
+            assert_eq!(stats.lengths.count, expected.len() as u64, "match_stats");
+            let document = Document::new(haystack);
+            let all: Vec<_> = re.find_all_in_document(&document).map(bounds).collect();
+            assert_eq!(all, expected, "find_all_in_document on {label}");
+            let mut all = Vec::new();
+            let mut input = Input::new(haystack);
+            while let Some(m) = re.find(input.clone()) {
+                all.push(bounds(m));
+                input.advance_past(&m);
+            }
+            assert_eq!(all, expected, "advance_past on {label}");
+
+            let gaps: Vec<_> = re.find_gaps(haystack).map(|m| m.as_str()).collect();
+            assert_eq!(gaps, pieces, "find_gaps on {label}");
+            let split: Vec<_> = re.split(haystack).collect();
+            assert_eq!(split, pieces, "split on {label}");
+            assert_eq!(re.replace_all(haystack, "-"), replaced, "replace_all");
+        }
+    }
+}
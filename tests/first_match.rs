@@ -0,0 +1,101 @@
+//! The searches returning the first match found, see `Input::first_match`:
+//! every engine reports the same match and captures, and stops reading the
+//! input at its end, anchored or not.
+#[allow(dead_code)]
+mod utils;
+
+use std::cell::Cell;
+
+use gregex::Regex;
+use gregex::util::Input;
+
+/// The number of chars the search reads, counted by the interrupt check.
+fn chars_read(re: &Regex, input: Input<'_>) -> usize {
+    let calls = Cell::new(0);
+    let check = || {
+        calls.set(calls.get() + 1);
+        false
+    };
+    re.find_interruptible(input, 1, &check).unwrap();
+    calls.get()
+}
+
+const PATTERNS: &[&str] = &[
+    "^a+",
+    "a+",
+    "(a+)(a*)",
+    "^(a+)b?",
+    "(a*)(b|ab)",
+    "(a|ab)(c|bcd)",
+    "(?:ab|a)(b*)",
+    "(a)|b|(ab)",
+    "((a)|(ab))+c?",
+    "abc|b",
+    "a+?b",
+    "x*",
+];
+
+#[test]
+fn test_same_match_on_every_engine() {
+    let long = "a".repeat(10_000);
+    let haystacks = ["aaa", "abcd", "ab", "abab", "xyz", "ba", &long];
+    for pattern in PATTERNS {
+        let engines = utils::compile_all_labeled(pattern).unwrap();
+        for haystack in haystacks {
+            for anchored in [true, false] {
+                let input = Input::new(haystack).anchored(anchored).first_match(true);
+                let (first_label, first) = &engines[0];
+                let expected = utils::find_groups(first, input.clone());
+                let expected_read = chars_read(first, input.clone());
+                for (label, re) in &engines[1..] {
+                    let context = format!(
+                        "{pattern:?} on {:.10?} (anchored: {anchored}) with {label} and {first_label}",
+                        haystack
+                    );
+                    assert_eq!(utils::find_groups(re, input.clone()), expected, "{context}");
+                    // Without a match, the JIT may see earlier that every
+                    // thread died, when it checks runs of chars at once.
+                    if expected.is_some() {
+                        assert_eq!(chars_read(re, input.clone()), expected_read, "{context}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_greedy_tail_stops_early() {
+    let long = "a".repeat(100_000);
+    for (pattern, expected) in [
+        ("^a+", vec![Some((0, 1))]),
+        ("(a+)(a*)", vec![Some((0, 1)), Some((0, 1)), Some((1, 1))]),
+        ("^(a+)b?", vec![Some((0, 1)), Some((0, 1))]),
+    ] {
+        for (label, re) in utils::compile_all_labeled(pattern).unwrap() {
+            for anchored in [true, false] {
+                let input = Input::new(&long).anchored(anchored).first_match(true);
+                assert_eq!(
+                    utils::find_groups(&re, input.clone()),
+                    Some(expected.clone()),
+                    "{label}"
+                );
+                assert!(chars_read(&re, input.clone()) <= 3, "{label}");
+                assert!(re.is_match(input));
+            }
+            // Without `first_match`, the repetition takes the whole run.
+            let m = re.find(Input::new(&long).anchored(true)).unwrap();
+            assert_eq!(m.end(), long.len(), "{label}");
+        }
+    }
+}
+
+#[test]
+fn test_not_the_leftmost() {
+    for re in utils::compile_all("abc|b").unwrap() {
+        let input = Input::new("abc").first_match(true);
+        assert_eq!(utils::find_groups(&re, input), Some(vec![Some((1, 2))]));
+        let anchored = Input::new("abc").first_match(true).anchored(true);
+        assert_eq!(utils::find_groups(&re, anchored), Some(vec![Some((0, 3))]));
+    }
+}
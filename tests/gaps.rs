@@ -0,0 +1,126 @@
+//! The regions between the matches, see `Regex::find_gaps`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::util::{Input, Span};
+
+fn gaps<'s>(re: &Regex, input: impl Into<Input<'s>>) -> Vec<(usize, usize)> {
+    re.find_gaps(input)
+        .map(|gap| (gap.start(), gap.end()))
+        .collect()
+}
+
+/// Checks that the gaps and the matches, interleaved, tile the span of the
+/// input.
+fn check_tiling(re: &Regex, haystack: &str, from: usize, to: usize) {
+    let input = || Input::new(haystack).span((from..to).into());
+    let matches: Vec<_> = re.find_all(input()).collect();
+    let gaps: Vec<_> = re.find_gaps(input()).collect();
+    assert_eq!(gaps.len(), matches.len() + 1, "{:?}", re.pattern());
+    let mut rebuilt = String::new();
+    let mut position = from;
+    for (i, gap) in gaps.iter().enumerate() {
+        assert_eq!(gap.start(), position, "{:?} on {haystack:?}", re.pattern());
+        rebuilt.push_str(gap.as_str());
+        position = gap.end();
+        if let Some(m) = matches.get(i) {
+            assert_eq!(m.start(), position, "{:?} on {haystack:?}", re.pattern());
+            rebuilt.push_str(m.as_str());
+            position = m.end();
+        }
+    }
+    assert_eq!(position, to);
+    assert_eq!(rebuilt, haystack[from..to]);
+}
+
+#[test]
+fn test_corpus_tiling() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        let bounds: Vec<_> = (0..=haystack.len())
+            .filter(|i| haystack.is_char_boundary(*i))
+            .collect();
+        let (first, last) = (bounds[bounds.len() / 3], bounds[2 * bounds.len() / 3]);
+        for re in &engines {
+            check_tiling(re, haystack, 0, haystack.len());
+            check_tiling(re, haystack, first, haystack.len());
+            check_tiling(re, haystack, first, last);
+        }
+    }
+}
+
+#[test]
+fn test_edges() {
+    for re in utils::compile_all(r"\d+").unwrap() {
+        assert_eq!(gaps(&re, "a1b22"), [(0, 1), (2, 3), (5, 5)]);
+        // Empty gaps before a leading match and between adjacent ones.
+        assert_eq!(gaps(&re, "12x"), [(0, 0), (2, 3)]);
+        // No match, one gap over everything, even when empty.
+        assert_eq!(gaps(&re, "none"), [(0, 4)]);
+        assert_eq!(gaps(&re, ""), [(0, 0)]);
+        // A match over everything, two empty gaps.
+        assert_eq!(gaps(&re, "123"), [(0, 0), (3, 3)]);
+        // The gaps stay in the span of the input.
+        let input = Input::new("1a2b3").span((1..4).into());
+        assert_eq!(gaps(&re, input), [(1, 2), (3, 4)]);
+    }
+}
+
+#[test]
+fn test_adjacent_and_empty_matches() {
+    for re in utils::compile_all("a").unwrap() {
+        assert_eq!(gaps(&re, "aab"), [(0, 0), (1, 1), (2, 3)]);
+    }
+    for re in utils::compile_all("x?").unwrap() {
+        // An empty match at each position without an `x`.
+        assert_eq!(gaps(&re, "ab"), [(0, 0), (0, 1), (1, 2), (2, 2)]);
+        check_tiling(&re, "axb", 0, 3);
+    }
+}
+
+#[test]
+fn test_invalid_input() {
+    let re = Regex::pike_vm("a").unwrap();
+    let input = Input::new("abc").span(Span { from: 2, to: 1 });
+    assert_eq!(re.find_gaps(input).count(), 0);
+}
+
+#[test]
+fn test_same_as_split() {
+    let cases = [
+        (r"\s*,\s*", "a , b,c ,, d"),
+        (r"\d+", "12ab3c456"),
+        ("x", "xax"),
+        ("[a-z]+", "ABC"),
+    ];
+    for (pattern, haystack) in cases {
+        let re = Regex::pike_vm(pattern).unwrap();
+        let gaps: Vec<_> = re.find_gaps(haystack).map(|gap| gap.as_str()).collect();
+        let rust = regex::Regex::new(pattern).unwrap();
+        let pieces: Vec<_> = rust.split(haystack).collect();
+        assert_eq!(gaps, pieces, "{pattern:?}");
+    }
+}
+
+#[test]
+fn test_texts_are_the_pieces_of_split() {
+    let empty = [
+        ("a*", "baaab"),
+        ("a*", "aaa"),
+        ("(?:)", "aé"),
+        (r"\b", "ab cd"),
+    ];
+    for (pattern, haystack) in utils::corpus::CASES.iter().chain(&empty) {
+        let Some(engines) = utils::compile_all_labeled(pattern) else {
+            continue;
+        };
+        for (label, re) in &engines {
+            let gaps: Vec<_> = re.find_gaps(*haystack).map(|gap| gap.as_str()).collect();
+            let pieces: Vec<_> = re.split(haystack).collect();
+            assert_eq!(gaps, pieces, "{pattern:?} on {haystack:?} ({label})");
+        }
+    }
+}
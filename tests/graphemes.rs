@@ -0,0 +1,126 @@
+#![cfg(feature = "unicode-segmentation")]
+
+use gregex::util::{Input, Span, is_grapheme_boundary};
+use gregex::{Builder, Regex};
+
+/// e followed by a combining acute accent, then a plain e.
+const ACCENT: &str = "cafe\u{301} e";
+/// Man, woman and girl joined by zero width joiners, a single grapheme.
+const FAMILY: &str = "a \u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467} b";
+/// The syllable 각 spelled with conjoining jamo (choseong, jungseong,
+/// jongseong), a single grapheme.
+const JAMO: &str = "x\u{1100}\u{1161}\u{11A8}y";
+
+fn pair(span: Span) -> (usize, usize) {
+    (span.from, span.to)
+}
+
+fn regex(pattern: &str) -> Regex {
+    Builder::new(pattern).pike_vm().unwrap()
+}
+
+/// Checks the invariants of snapping on every match of the pattern, and
+/// returns the raw and snapped spans.
+fn snapped(pattern: &str, haystack: &str) -> Vec<((usize, usize), (usize, usize))> {
+    let re = regex(pattern);
+    let mut spans = Vec::new();
+    for m in re.find_all(haystack) {
+        let snapped = m.snap_to_graphemes();
+        assert!(is_grapheme_boundary(haystack, snapped.from));
+        assert!(is_grapheme_boundary(haystack, snapped.to));
+        assert!(snapped.from <= m.start() && m.end() <= snapped.to);
+        spans.push(((m.start(), m.end()), (snapped.from, snapped.to)));
+    }
+    spans
+}
+
+#[test]
+fn test_grapheme_boundaries() {
+    let boundaries: Vec<_> = (0..=ACCENT.len())
+        .filter(|pos| is_grapheme_boundary(ACCENT, *pos))
+        .collect();
+    // No boundary between the e and its accent (at 4), nor inside the accent.
+    assert_eq!(boundaries, vec![0, 1, 2, 3, 6, 7, 8]);
+    assert!(!is_grapheme_boundary(ACCENT, ACCENT.len() + 1));
+
+    let boundaries: Vec<_> = (0..=JAMO.len())
+        .filter(|pos| is_grapheme_boundary(JAMO, *pos))
+        .collect();
+    assert_eq!(boundaries, vec![0, 1, 10, 11]);
+
+    assert!(is_grapheme_boundary("", 0));
+}
+
+#[test]
+fn test_snap_combining_accent() {
+    // The accent alone, and the base letter alone, are both extended to the
+    // whole "é". The last e has no accent and is left unchanged.
+    assert_eq!(snapped("\u{301}", ACCENT), vec![((4, 6), (3, 6))]);
+    assert_eq!(
+        snapped("e", ACCENT),
+        vec![((3, 4), (3, 6)), ((7, 8), (7, 8))]
+    );
+    assert_eq!(snapped("caf", ACCENT), vec![((0, 3), (0, 3))]);
+    // An empty match inside the "é" is extended to it.
+    let empty = regex("")
+        .find(Input::new(ACCENT).span((4..4).into()))
+        .unwrap();
+    assert_eq!(pair(empty.snap_to_graphemes()), (3, 6));
+}
+
+#[test]
+fn test_snap_zwj_sequence() {
+    let start = 2;
+    let end = FAMILY.len() - 2;
+    let woman = "\u{1F469}";
+    assert_eq!(
+        snapped(woman, FAMILY),
+        vec![((start + 7, start + 11), (start, end))]
+    );
+    assert_eq!(
+        snapped("\u{200D}", FAMILY),
+        vec![
+            ((start + 4, start + 7), (start, end)),
+            ((start + 11, start + 14), (start, end))
+        ]
+    );
+    assert_eq!(
+        snapped("b", FAMILY),
+        vec![((end + 1, end + 2), (end + 1, end + 2))]
+    );
+}
+
+#[test]
+fn test_snap_hangul_jamo() {
+    assert_eq!(snapped("\u{1161}", JAMO), vec![((4, 7), (1, 10))]);
+    assert_eq!(snapped("\u{1100}", JAMO), vec![((1, 4), (1, 10))]);
+    assert_eq!(snapped("\u{11A8}y", JAMO), vec![((7, 11), (1, 11))]);
+}
+
+#[test]
+fn test_find_graphemes() {
+    for haystack in [ACCENT, FAMILY, JAMO] {
+        for pattern in [r"\p{M}", r"\p{L}", "\u{200D}", r"\s", "z", ""] {
+            let re = regex(pattern);
+            let raw = re.find(haystack);
+            let found = re.find_graphemes(haystack);
+            // Snapping never changes whether there is a match.
+            assert_eq!(
+                raw.is_none(),
+                found.is_none(),
+                "{pattern:?} on {haystack:?}"
+            );
+            let (Some(raw), Some(found)) = (raw, found) else {
+                continue;
+            };
+            assert_eq!(pair(found.raw.span), pair(raw.span));
+            assert_eq!(pair(found.span), pair(raw.snap_to_graphemes()));
+            assert_eq!(found.as_str(), &haystack[found.start()..found.end()]);
+        }
+    }
+    let re = regex("\u{301}");
+    let found = re.find_graphemes(ACCENT).unwrap();
+    assert_eq!(found.as_str(), "e\u{301}");
+    assert_eq!(found.raw.as_str(), "\u{301}");
+    assert_eq!(pair(found.span), (3, 6));
+}
@@ -0,0 +1,164 @@
+//! Runs the `gregex-grep` example (examples/grep.rs) end to end.
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// The example binary, which `cargo test` builds next to the test binaries.
+fn grep_binary() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let path = exe
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .join("examples")
+        .join(format!("gregex-grep{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "{} is missing, build it with `cargo build --example gregex-grep`",
+        path.display()
+    );
+    path
+}
+
+/// A file in the temp dir, removed on drop.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, content: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("gregex-grep-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        TempFile(path)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn grep(args: &[&str], files: &[&TempFile]) -> Output {
+    Command::new(grep_binary())
+        .args(args)
+        .args(files.iter().map(|file| &file.0))
+        .output()
+        .unwrap()
+}
+
+/// Checks the exit code and the output, on every engine.
+fn check(args: &[&str], files: &[&TempFile], code: i32, expected: &str) {
+    for engine in ["vm", "jit", "auto"] {
+        if engine == "jit"
+            && !cfg!(all(
+                feature = "jit",
+                target_arch = "x86_64",
+                any(unix, windows)
+            ))
+        {
+            continue;
+        }
+        let output = grep(&[&["-E", engine], args].concat(), files);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            expected,
+            "{args:?} with {engine}"
+        );
+        assert_eq!(output.status.code(), Some(code), "{args:?} with {engine}");
+    }
+}
+
+const LOG: &str = "\
+GET /index.html 200
+POST /login 302
+get /favicon.ico 404
+GET /missing 404
+";
+
+#[test]
+fn test_lines() {
+    let log = TempFile::new("lines", LOG);
+    check(
+        &["404"],
+        &[&log],
+        0,
+        "get /favicon.ico 404\nGET /missing 404\n",
+    );
+    check(
+        &["-n", "^GET"],
+        &[&log],
+        0,
+        "1:GET /index.html 200\n4:GET /missing 404\n",
+    );
+    check(
+        &["-in", "^get"],
+        &[&log],
+        0,
+        "1:GET /index.html 200\n3:get /favicon.ico 404\n4:GET /missing 404\n",
+    );
+    check(&["DELETE"], &[&log], 1, "");
+}
+
+#[test]
+fn test_only_matching_and_count() {
+    let log = TempFile::new("only", LOG);
+    check(&["-o", r"\d+"], &[&log], 0, "200\n302\n404\n404\n");
+    check(
+        &["-on", "/[a-z]+"],
+        &[&log],
+        0,
+        "1:/index\n2:/login\n3:/favicon\n4:/missing\n",
+    );
+    check(&["-c", "4"], &[&log], 0, "2\n");
+    check(&["-c", "DELETE"], &[&log], 1, "0\n");
+}
+
+#[test]
+fn test_captures() {
+    let log = TempFile::new("captures", LOG);
+    check(
+        &["--captures", r"^(\w+) \S+ (3)?(\d+)"],
+        &[&log],
+        0,
+        "GET\t\t200\nPOST\t3\t02\nget\t\t404\nGET\t\t404\n",
+    );
+    // Without groups, the whole match is printed.
+    check(
+        &["--captures", "-n", "[0-9]{3}$"],
+        &[&log],
+        0,
+        "1:200\n2:302\n3:404\n4:404\n",
+    );
+}
+
+#[test]
+fn test_several_files() {
+    let first = TempFile::new("first", "alpha\nbeta\n");
+    let second = TempFile::new("second", "gamma\nalphabet");
+    let expected = format!(
+        "{0}:1:alpha\n{1}:2:alphabet\n",
+        first.0.display(),
+        second.0.display()
+    );
+    check(&["-n", "alpha"], &[&first, &second], 0, &expected);
+    let expected = format!("{}:1\n{}:0\n", first.0.display(), second.0.display());
+    check(&["-c", "beta"], &[&first, &second], 0, &expected);
+}
+
+#[test]
+fn test_errors() {
+    let log = TempFile::new("errors", LOG);
+    let output = grep(&["a(b"], &[&log]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid pattern"));
+
+    let output = grep(&["-E", "dfa", "a"], &[&log]);
+    assert_eq!(output.status.code(), Some(2));
+
+    // The matches of the readable files are still printed.
+    let missing = TempFile(std::env::temp_dir().join("gregex-grep-does-not-exist"));
+    let output = grep(&["POST"], &[&log, &missing]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{}:POST /login 302\n", log.0.display())
+    );
+}
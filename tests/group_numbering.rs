@@ -0,0 +1,71 @@
+#[allow(dead_code)]
+mod utils;
+
+/// Patterns with groups in every position affecting their numbering.
+const TORTURE: &[(&str, &str)] = &[
+    ("(?:(a)|(b))(c)", "bc"),
+    ("((a)|(b))+(c)", "abac"),
+    ("(a(b(c)))(d)", "abcd"),
+    ("((((a))))", "a"),
+    ("(a)|(b)|(c)|(d)|(e)", "d"),
+    ("(?:(a)(b)|(c)(d))+", "abcdab"),
+    ("(a){3}", "aaa"),
+    ("(a){2,4}(b)?", "aaab"),
+    ("(a(b)){0,2}c", "ababc"),
+    ("((a)|(b)){2}", "ba"),
+    ("(a)?(b)*(c)+", "c"),
+    ("()", ""),
+    ("()()()", "x"),
+    ("(a*)(b*)", "bbb"),
+    ("(^)(a)($)", "a"),
+    ("(?m)(^)|($)", "\n"),
+    ("(?:()|())+x", "x"),
+    ("(?:(?:(a)|(b))|(?:(c)|(d)))e", "ce"),
+    ("(?i)(a)(?-i:(B))", "Ab"),
+    ("(a)|b", "b"),
+    ("(x)|(x)", "x"),
+    ("((a)|(b)|(c)){0,3}?d", "abcd"),
+    ("(?:(a)|(b)|(c)){1,3}d", "cbd"),
+    // Groups which are never compiled, but still numbered.
+    ("(a){0}b", "ab"),
+    ("(a){0}(b)", "ab"),
+    ("(a){0}(b)(c)(d)", "bcd"),
+    ("(?:(a)(b)){0}(c)", "abc"),
+    ("((a){0}(b))(c)", "bc"),
+    ("(b)(a){0}", "b"),
+];
+
+#[test]
+fn test_group_numbering_like_regex_crate() {
+    let mut compared = 0;
+    for (pattern, input) in TORTURE.iter().chain(utils::corpus::CASES) {
+        let Ok(expected) = regex::Regex::new(pattern) else {
+            continue;
+        };
+        for (label, re) in utils::compile_all_labeled(pattern).into_iter().flatten() {
+            let caps = re.find_captures(*input);
+            assert_eq!(
+                caps.as_ref().map(|caps| caps.group_len()),
+                expected.captures(input).map(|_| expected.captures_len()),
+                "group_len of {pattern:?} with {label}"
+            );
+            let groups = caps.map(|caps| {
+                caps.all_groups()
+                    .into_iter()
+                    .map(|g| g.map(|g| (g.start(), g.end())))
+                    .collect::<Vec<_>>()
+            });
+            let expected_groups = expected.captures(input).map(|caps| {
+                caps.iter()
+                    .map(|g| g.map(|g| (g.start(), g.end())))
+                    .collect::<Vec<_>>()
+            });
+            assert_eq!(
+                groups, expected_groups,
+                "{pattern:?} on {input:?} with {label}"
+            );
+            compared += 1;
+        }
+    }
+    assert!(compared > 100);
+}
@@ -0,0 +1,45 @@
+//! The participation of the groups in a match, see `Captures::participated`.
+#[allow(dead_code)]
+mod utils;
+
+#[test]
+fn test_group_participation_with_reuse() {
+    let pattern = r"(a)|(b)|((c)(d)?)";
+    let haystacks = ["b", "a", "cd", "c", "ba", "cdab"];
+    for engine in utils::compile_all(pattern).unwrap() {
+        // Alternate haystacks and the number of requested groups, so that
+        // stale data from a previous match would be visible
+        for round in 0..3 {
+            for (i, haystack) in haystacks.iter().enumerate() {
+                let n = (i + round) % 7;
+                let caps = engine.find_captures_n(*haystack, n).unwrap();
+                let expected = regex::Regex::new(pattern)
+                    .unwrap()
+                    .captures(haystack)
+                    .unwrap();
+                for group in 0..caps.group_len() {
+                    assert_eq!(
+                        caps.participated(group),
+                        expected.get(group).is_some(),
+                        "group {group} of {haystack:?} (n={n})"
+                    );
+                }
+            }
+            let all: Vec<Vec<bool>> = engine
+                .find_all_captures("bacdcab")
+                .map(|caps| caps.participation().collect())
+                .collect();
+            assert_eq!(
+                all,
+                vec![
+                    vec![true, false, true, false, false, false],
+                    vec![true, true, false, false, false, false],
+                    vec![true, false, false, true, true, true],
+                    vec![true, false, false, true, true, false],
+                    vec![true, true, false, false, false, false],
+                    vec![true, false, true, false, false, false],
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Haystacks larger than 4GB, past the 32-bit displacements of the JIT. They
+//! take several GB of memory, so they only run with:
+//!
+//! ```sh
+//! cargo test --release --features huge_tests --test huge_haystacks -- --ignored
+//! ```
+#![cfg(feature = "huge_tests")]
+
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::Input;
+
+#[test]
+#[ignore]
+fn test_haystack_larger_than_4gb() {
+    let pattern = r"needle\((\d+)\)";
+    let padding = (1 << 32) + (1 << 20);
+    // Avoid doubling the capacity when pushing the needle
+    let mut haystack = String::with_capacity(padding + 16);
+    haystack.extend(std::iter::repeat_n('a', padding));
+    haystack.push_str("needle(42)");
+    let len = haystack.len();
+
+    // A full scan is slow, only do it once per kind of engine
+    let mut engines = vec![gregex::Regex::pike_vm(pattern).unwrap()];
+    engines.extend(gregex::Regex::pike_jit(pattern));
+    for engine in &engines {
+        let caps = engine.find_captures(haystack.as_str()).unwrap();
+        assert_eq!(caps.group0().span.from, padding);
+        assert_eq!(caps.group0().span.to, len);
+        assert_eq!(caps.get(1).unwrap().span.from, padding + 7);
+    }
+
+    for engine in utils::compile_all(pattern).unwrap() {
+        let input = Input::new(&haystack).span((padding - 16..len).into());
+        let caps = engine.find_captures(input.clone()).unwrap();
+        assert_eq!(caps.get(1).unwrap().as_str(), "42");
+        let m = engine.find(input.span((padding..len).into()).anchored(true));
+        assert_eq!(m.unwrap().span.to, len);
+        let input = Input::new(&haystack).span((padding + 1..len).into());
+        assert!(!engine.is_match(input));
+    }
+}
@@ -0,0 +1,105 @@
+//! The small classes compiled inline by the JIT, which checks their ranges
+//! one after the other. Every char in and around the bounds of each range is
+//! checked, on the JIT only since the Pike VM doesn't inline classes.
+#![cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+
+use gregex::{Builder, CgStrategy, Regex};
+
+/// Classes, with their ranges as sorted by the parser.
+const CLASSES: &[(&str, &[(char, char)])] = &[
+    ("[ace]", &[('a', 'a'), ('c', 'c'), ('e', 'e')]),
+    ("[a-cx-z]", &[('a', 'c'), ('x', 'z')]),
+    ("[a-c]", &[('a', 'c')]),
+    ("[0-9A-Fa-f]", &[('0', '9'), ('A', 'F'), ('a', 'f')]),
+    ("[b-cf-gj-k]", &[('b', 'c'), ('f', 'g'), ('j', 'k')]),
+    ("[a-bd]", &[('a', 'b'), ('d', 'd')]),
+    ("[\0-\x02~-\x7f]", &[('\0', '\x02'), ('~', '\x7f')]),
+    ("[é-ëα-γ日]", &[('é', 'ë'), ('α', 'γ'), ('日', '日')]),
+    (
+        "[a\u{ffff}-\u{10001}]",
+        &[('a', 'a'), ('\u{ffff}', '\u{10001}')],
+    ),
+];
+
+/// The chars in and around the bounds of the ranges.
+fn boundary_chars(ranges: &[(char, char)]) -> Vec<char> {
+    let mut chars = Vec::new();
+    for (from, to) in ranges {
+        for bound in [*from as u32, *to as u32] {
+            let around = bound.saturating_sub(2)..=bound.saturating_add(2);
+            chars.extend(around.filter_map(char::from_u32));
+        }
+    }
+    chars.sort_unstable();
+    chars.dedup();
+    chars
+}
+
+fn in_class(ranges: &[(char, char)], c: char) -> bool {
+    ranges.iter().any(|(from, to)| (*from..=*to).contains(&c))
+}
+
+/// The JIT with every CG strategy, with the classes inlined.
+fn jits(pattern: &str) -> Vec<(CgStrategy, Regex)> {
+    [CgStrategy::Auto, CgStrategy::Tree, CgStrategy::Array]
+        .into_iter()
+        .map(|strategy| {
+            let re = Builder::new(pattern)
+                .force_class_inlining(true)
+                .jit_cg_strategy(strategy)
+                .pike_jit()
+                .unwrap();
+            (strategy, re)
+        })
+        .collect()
+}
+
+#[test]
+fn test_single_char() {
+    for (class, ranges) in CLASSES {
+        for (strategy, re) in jits(&format!("^{class}$")) {
+            for c in boundary_chars(ranges) {
+                let haystack = c.to_string();
+                assert_eq!(
+                    re.is_match(haystack.as_str()),
+                    in_class(ranges, c),
+                    "{class} on {c:?} with {strategy:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_in_context() {
+    // The class in a literal, in a repetition checked at once (for the ASCII
+    // classes), and in a fork choosing its thread from the current char.
+    for (class, ranges) in CLASSES {
+        let patterns = [
+            format!("x{class}y"),
+            format!("({class}{{3}})"),
+            format!("x({class}*)y"),
+            format!("x{class}?y"),
+        ];
+        for pattern in &patterns {
+            let jits = jits(pattern);
+            for c in boundary_chars(ranges) {
+                for haystack in [
+                    format!("-x{c}y-"),
+                    format!("{c}{c}{c}"),
+                    format!("x{c}{c}{c}y"),
+                ] {
+                    let rust = regex::Regex::new(pattern).unwrap();
+                    let expected = rust.find(&haystack).map(|m| (m.start(), m.end()));
+                    for (strategy, re) in &jits {
+                        let found = re.find(haystack.as_str()).map(|m| (m.start(), m.end()));
+                        assert_eq!(
+                            found, expected,
+                            "{pattern} on {haystack:?} with {strategy:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
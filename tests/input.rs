@@ -0,0 +1,243 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::thompson::pike_vm::GroupEvent;
+use gregex::util::{Input, Match};
+
+/// The matches found one at a time, with `Input::advance_past`.
+fn find_one_by_one<'s>(re: &gregex::Regex, mut input: Input<'s>) -> Vec<(usize, usize)> {
+    let mut found = Vec::new();
+    while let Some(m) = re.find(input.clone()) {
+        found.push((m.start(), m.end()));
+        input.advance_past(&m);
+    }
+    found
+}
+
+#[test]
+fn test_advance_past_like_find_all() {
+    let cases = [
+        ("", "é日a"),
+        ("", ""),
+        ("x*", "é日x"),
+        ("x*", "xé日"),
+        (r"\d*", "1é23"),
+        ("(?m)$", "日\né\n"),
+        ("é|", "aéé日"),
+        (r"\w+", "naïve façade 日本"),
+    ];
+    for (pattern, subject) in cases {
+        for re in utils::compile_all(pattern).unwrap() {
+            let expected: Vec<_> = re.find_all(subject).map(|m| (m.start(), m.end())).collect();
+            assert_eq!(
+                find_one_by_one(&re, Input::new(subject)),
+                expected,
+                "{pattern:?} on {subject:?} with {:?}",
+                re.engine_kind()
+            );
+            let span = (1.min(subject.len())..subject.len()).into();
+            let input = Input::new(subject).span(span);
+            if input.valid() {
+                let expected: Vec<_> = re
+                    .find_all(input.clone())
+                    .map(|m| (m.start(), m.end()))
+                    .collect();
+                assert_eq!(find_one_by_one(&re, input), expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_advance_past_empty_matches() {
+    let subject = "é日a";
+    let mut input = Input::new(subject);
+    // At the start, before a 2 bytes char.
+    input.advance_past(&Match::new(subject, 0..0));
+    assert_eq!(input.span.from, 2);
+    assert!(input.valid());
+    // In the middle, before a 3 bytes char.
+    input.advance_past(&Match::new(subject, 2..2));
+    assert_eq!(input.span.from, 5);
+    input.advance_past(&Match::new(subject, 5..5));
+    assert_eq!(input.span.from, 6);
+    // At the end, the span is left invalid.
+    input.advance_past(&Match::new(subject, 6..6));
+    assert_eq!(input.span.from, 7);
+    assert!(!input.valid());
+    assert!(gregex::Regex::pike_vm("").unwrap().find(input).is_none());
+}
+
+#[test]
+fn test_advance_past_non_empty_match() {
+    let subject = "日本語";
+    let mut input = Input::new(subject);
+    input.advance_past(&Match::new(subject, 0..3));
+    assert_eq!(input.span.from, 3);
+    input.advance_past(&Match::new(subject, 3..9));
+    assert_eq!(input.span.from, 9);
+    assert!(input.valid());
+}
+
+#[test]
+fn test_next_match_start_inside_a_char() {
+    // Such spans are never found by the searches, but can be made by hand.
+    let subject = "a日b";
+    assert_eq!(Match::new(subject, 2..2).next_match_start(), 4);
+    assert_eq!(Match::new(subject, 3..3).next_match_start(), 4);
+    assert_eq!(Match::new(subject, 4..4).next_match_start(), 5);
+    assert_eq!(Match::new(subject, 5..5).next_match_start(), 6);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "not in the subject")]
+fn test_advance_past_match_of_another_subject() {
+    let other = String::from("abc");
+    let mut input = Input::new("abc");
+    input.advance_past(&Match::new(&other, 0..1));
+}
+
+#[test]
+fn test_look_behind() {
+    let subject = "ab";
+    let input = Input::new(subject).span((1..2).into());
+    for (pattern, hint) in [("(?m)^b", '\n'), ("(?m)^", '\n'), (r"\Ab", 'x'), ("^", 'x')] {
+        for re in utils::compile_all(pattern).unwrap() {
+            assert!(re.find(input.clone()).is_none(), "{pattern:?}");
+            // The hint is trusted, although 'a' is before the span.
+            let m = re.find(input.clone().with_look_behind(hint));
+            assert_eq!(
+                m.map(|m| m.start()),
+                (hint == '\n').then_some(1),
+                "{pattern:?}"
+            );
+            let m = re.find(input.clone().at_haystack_start()).unwrap();
+            assert_eq!(m.start(), 1, "{pattern:?}");
+        }
+    }
+    // And the other way around: a wrong hint hides the real char.
+    let input = Input::new("a\nb").span((2..3).into());
+    for re in utils::compile_all("(?m)^b").unwrap() {
+        assert!(re.find(input.clone()).is_some());
+        assert!(re.find(input.clone().with_look_behind('a')).is_none());
+        assert!(re.find(input.clone().at_haystack_start()).is_some());
+    }
+}
+
+#[test]
+fn test_look_behind_ignored_once_the_span_moves() {
+    let re = gregex::Regex::pike_vm("(?m)^b").unwrap();
+    // Given for the start of the subject, not of the span.
+    let input = Input::new("abb").with_look_behind('\n');
+    assert!(re.find(input.span((1..3).into())).is_none());
+    let mut input = Input::new("abb").span((1..3).into()).with_look_behind('\n');
+    assert!(re.find(input.clone()).is_some());
+    input.span.from = 2;
+    assert!(re.find(input).is_none());
+}
+
+#[test]
+fn test_advance_past_look_behind() {
+    // The hints from the matches give the same matches as reading the
+    // subject, on every engine.
+    let patterns = utils::corpus::CASES
+        .iter()
+        .map(|(pattern, _)| *pattern)
+        .chain([
+            "(?m)^",
+            "(?m)$",
+            "(?m)^\\w*$",
+            r"\A|x",
+            "(?m)^é*",
+            r"(?m)\d*$",
+        ]);
+    let subjects = ["", "a\nb", "\n\nx\r\n", "é\n日\né", "1x2\n34", "xx\nxé"];
+    for pattern in patterns {
+        let Some(regexes) = utils::compile_all(pattern) else {
+            continue;
+        };
+        for subject in utils::corpus::CASES.iter().map(|(_, s)| *s).chain(subjects) {
+            for re in &regexes {
+                // Without hints, only moving the span, and skipping the empty
+                // matches right after the previous one.
+                let mut expected = Vec::new();
+                let mut input = Input::new(subject);
+                let mut previous_end = None;
+                while input.valid()
+                    && let Some(m) = re.find(input.clone())
+                {
+                    if !(m.start() == m.end() && previous_end == Some(m.start())) {
+                        expected.push((m.start(), m.end()));
+                        previous_end = Some(m.end());
+                    }
+                    input.span.from = m.next_match_start();
+                }
+                assert_eq!(
+                    find_one_by_one(re, Input::new(subject)),
+                    expected,
+                    "{pattern:?} on {subject:?} with {:?}",
+                    re.engine_kind()
+                );
+                let found: Vec<_> = re.find_all(subject).map(|m| (m.start(), m.end())).collect();
+                assert_eq!(found, expected, "{pattern:?} on {subject:?}");
+                let found: Vec<_> = re
+                    .find_all_owned(subject)
+                    .map(|m| (m.start(), m.end()))
+                    .collect();
+                assert_eq!(found, expected, "{pattern:?} on {subject:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_cache_look_behind() {
+    let subject = "a\né";
+    for pattern in ["(?m)^", r"\A", "(?m)^é", "é"] {
+        for re in utils::compile_all(pattern).unwrap() {
+            for from in [0, 1, 2, 4] {
+                let input = Input::new(subject).span((from..subject.len()).into());
+                let cached = input.clone().cache_look_behind();
+                assert_eq!(
+                    re.find(cached.clone()).map(|m| m.start()),
+                    re.find(input.clone()).map(|m| m.start()),
+                    "{pattern:?} from {from}"
+                );
+                // Ignored like the hints once the span moves.
+                let mut moved = cached;
+                moved.span.from = 0;
+                assert_eq!(
+                    re.find(moved).map(|m| m.start()),
+                    re.find(subject).map(|m| m.start()),
+                    "{pattern:?} from {from}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_advance_past_skips_the_empty_match_in_every_search() {
+    let span = |m: Match<'_>| (m.start(), m.end());
+    for (label, re) in utils::compile_all_labeled("a*").unwrap() {
+        // After the match of the leading `a`s, the empty match right at its
+        // end is skipped.
+        for (subject, expected) in [("ab", Some((2, 2))), ("aéb", Some((3, 3))), ("aa", None)] {
+            let mut input = Input::new(subject);
+            input.advance_past(&re.find(input.clone()).unwrap());
+            let context = format!("{subject:?} with {label}");
+            assert_eq!(re.find(input.clone()).map(span), expected, "{context}");
+            let interruptible = re.find_interruptible(input.clone(), 1, &|| false);
+            assert_eq!(interruptible.unwrap().map(span), expected, "{context}");
+            let explained = re.find_explain(input.clone()).map(|(m, _)| span(m));
+            assert_eq!(explained, expected, "{context}");
+            let mut commits = 0;
+            let streamed = re.find_captures_streaming(input.clone(), |event| {
+                commits += matches!(event, GroupEvent::Commit { .. }) as usize;
+            });
+            assert_eq!(streamed.map(|caps| span(caps.group0())), expected);
+            assert_eq!(commits, expected.is_some() as usize, "{context}");
+        }
+    }
+}
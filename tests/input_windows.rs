@@ -0,0 +1,183 @@
+//! Every span of small haystacks, anchored or not, with `first_match` or not:
+//! the engines must agree with each other, and with rust-regex where it has
+//! the same search. Spans which are not valid (see `Input::valid`) never
+//! match. rust-regex is not the reference of `find_all`, since it skips the
+//! empty matches right after a match, which we report.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::util::{Input, Span};
+use regex_automata::{Anchored, meta};
+use utils::Check;
+
+type Groups = Vec<Option<(usize, usize)>>;
+
+const PATTERNS: &[&str] = &[
+    "",
+    "a",
+    "a*",
+    "a+?",
+    "a|b",
+    "ab|a",
+    "(a|ab)(c|bcd)?",
+    "(a*)(b?)",
+    "[ab]*c",
+    "é+",
+    "(?s).",
+    "^",
+    "$",
+    "^a",
+    "a$",
+    "x*$",
+    r"\A|b",
+    r"(?m)^",
+    r"(?m)$",
+    r"(?m)^\w*$",
+    r"(?m)^é|a$",
+];
+
+const HAYSTACKS: &[&str] = &[
+    "", "a", "ab", "ba", "aab", "abc", "é", "aéa", "a\nb", "\na\n", "bcd",
+];
+
+/// A search, named for the failure messages.
+struct Config<'s> {
+    haystack: &'s str,
+    span: Span,
+    anchored: bool,
+    first_match: bool,
+}
+
+impl Config<'_> {
+    fn input(&self) -> Input<'_> {
+        Input::new(self.haystack)
+            .span(self.span)
+            .anchored(self.anchored)
+            .first_match(self.first_match)
+    }
+
+    fn describe(&self, pattern: &str, label: &str) -> String {
+        let Config {
+            haystack,
+            span,
+            anchored,
+            first_match,
+        } = self;
+        format!(
+            "{label} for {pattern:?} on {haystack:?}, span {}..{}, anchored: {anchored}, first_match: {first_match}",
+            span.from, span.to
+        )
+    }
+}
+
+/// Every pair of offsets up to one past the end, whether they are char
+/// boundaries or not, and in order or not.
+fn all_spans(haystack: &str) -> impl Iterator<Item = Span> {
+    let len = haystack.len();
+    (0..=len + 1).flat_map(move |from| (0..=len + 1).map(move |to| Span { from, to }))
+}
+
+/// The groups found by rust-regex, which has no `first_match`. Like us, it
+/// looks outside of the span for the assertions.
+fn rust_groups(re: &meta::Regex, config: &Config<'_>) -> Option<Groups> {
+    let input = regex_automata::Input::new(config.haystack)
+        .range(config.span.from..config.span.to)
+        .anchored(if config.anchored {
+            Anchored::Yes
+        } else {
+            Anchored::No
+        });
+    let mut caps = re.create_captures();
+    re.captures(input, &mut caps);
+    caps.is_match().then(|| {
+        (0..caps.group_len())
+            .map(|i| caps.get_group(i).map(|m| (m.start, m.end)))
+            .collect()
+    })
+}
+
+fn find_all(re: &Regex, config: &Config<'_>) -> Vec<(usize, usize)> {
+    re.find_all(config.input())
+        .map(|m| (m.start(), m.end()))
+        .collect()
+}
+
+/// Checks one search on every engine, and returns its groups.
+fn check(pattern: &str, engines: &[(String, Regex)], config: &Config<'_>) -> Option<Groups> {
+    let (first_label, first) = &engines[0];
+    let expected = utils::find_groups(first, config.input());
+    let expected_all = find_all(first, config);
+    for (label, re) in engines {
+        let context = config.describe(pattern, label);
+        let found = utils::find_groups(re, config.input());
+        assert_eq!(found, expected, "{context}, compared to {first_label}");
+        let m = re.find(config.input()).map(|m| (m.start(), m.end()));
+        assert_eq!(m, found.as_ref().and_then(|g| g[0]), "{context} (find)");
+        assert_eq!(
+            re.is_match(config.input()),
+            m.is_some(),
+            "{context} (is_match)"
+        );
+        let all = find_all(re, config);
+        assert_eq!(
+            all, expected_all,
+            "{context} (find_all), compared to {first_label}"
+        );
+        assert_eq!(all.first().copied(), m, "{context} (find_all)");
+        let Some((start, end)) = m else {
+            continue;
+        };
+        let Span { from, to } = config.span;
+        assert!(from <= start && end <= to, "{context}: {start}..{end}");
+        if config.anchored {
+            assert_eq!(start, from, "{context} (anchored)");
+        }
+    }
+    expected
+}
+
+#[test]
+fn test_every_span_on_every_engine() {
+    for pattern in PATTERNS {
+        let engines = utils::compile_all_labeled(pattern).unwrap();
+        let rust_re = meta::Regex::new(pattern).unwrap();
+        for haystack in HAYSTACKS {
+            for span in all_spans(haystack) {
+                let valid = Input::new(haystack).span(span).valid();
+                for anchored in [false, true] {
+                    let config = |first_match| Config {
+                        haystack,
+                        span,
+                        anchored,
+                        first_match,
+                    };
+                    let leftmost = config(false);
+                    let found = check(pattern, &engines, &leftmost);
+                    if !valid {
+                        assert_eq!(found, None, "{}", leftmost.describe(pattern, "all"));
+                    } else if !utils::is_expected_divergence(pattern, Check::SubSpan) {
+                        assert_eq!(
+                            found,
+                            rust_groups(&rust_re, &leftmost),
+                            "{}",
+                            leftmost.describe(pattern, "rust-regex")
+                        );
+                    }
+
+                    // The first match ends the earliest, so it exists iff
+                    // the leftmost one does, and doesn't end after it.
+                    let first = config(true);
+                    let first_found = check(pattern, &engines, &first);
+                    if utils::is_expected_divergence(pattern, Check::FirstMatch) {
+                        continue;
+                    }
+                    let context = first.describe(pattern, "all");
+                    let end = |groups: &Option<Groups>| groups.as_ref().map(|g| g[0].unwrap().1);
+                    assert_eq!(first_found.is_some(), found.is_some(), "{context}");
+                    assert!(end(&first_found) <= end(&found), "{context}");
+                }
+            }
+        }
+    }
+}
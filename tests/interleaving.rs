@@ -0,0 +1,67 @@
+#[allow(dead_code)]
+mod utils;
+
+use std::thread;
+
+const PATTERN: &str = r"(\w+)-(\d+)";
+const HAYSTACK: &str = "foo-1 bar- baz-42 qux-7 end";
+
+fn expected() -> Vec<(usize, usize)> {
+    vec![(0, 5), (11, 17), (18, 23)]
+}
+
+#[test]
+fn test_find_all_interleaved_with_one_shot_searches() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let mut found = Vec::new();
+        for m in re.find_all(HAYSTACK) {
+            // Each of these checks out a state while the iterator is paused.
+            assert!(re.is_match("other-0"));
+            assert!(!re.is_match("nothing here"));
+            assert_eq!(re.find("x y-9").unwrap().as_str(), "y-9");
+            let caps = re.find_captures("ab-12").unwrap();
+            assert_eq!(caps.get(2).unwrap().as_str(), "12");
+            found.push((m.start(), m.end()));
+        }
+        assert_eq!(found, expected());
+    }
+}
+
+#[test]
+fn test_nested_iterators_on_the_same_regex() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let mut outer = Vec::new();
+        for caps in re.find_all_captures(HAYSTACK) {
+            let inner: Vec<_> = re
+                .find_all(HAYSTACK)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            assert_eq!(inner, expected());
+            // The inner iterator didn't disturb the groups of the outer one.
+            let group0 = caps.group0();
+            let number = caps.get(2).unwrap();
+            assert_eq!(number.end(), group0.end());
+            outer.push((group0.start(), group0.end()));
+        }
+        assert_eq!(outer, expected());
+    }
+}
+
+#[test]
+fn test_one_shot_searches_from_many_threads() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let re = &re;
+        thread::scope(|scope| {
+            for i in 0..16 {
+                scope.spawn(move || {
+                    let haystack = format!("{} x-{i}", "y ".repeat(i));
+                    for _ in 0..100 {
+                        let found = re.find(haystack.as_str()).unwrap();
+                        assert_eq!(found.as_str(), format!("x-{i}"));
+                        assert!(!re.is_match("no match"));
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,211 @@
+//! The searches which can be interrupted, see `Regex::find_interruptible`.
+#[allow(dead_code)]
+mod utils;
+
+use std::cell::Cell;
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use gregex::sink::MatchError;
+use gregex::util::{Input, Span};
+
+#[test]
+fn test_same_as_find() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        for re in &engines {
+            for interval in [1, 3, 1000] {
+                let found = re.find_interruptible(*haystack, interval, &|| false);
+                let found = found.unwrap().map(|m| (m.start(), m.end()));
+                let expected = re.find(*haystack).map(|m| (m.start(), m.end()));
+                assert_eq!(found, expected, "{pattern:?} on {haystack:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_check_interval() {
+    // No literal for the prefilter, and no match: every char is read.
+    let haystack = "ab-".repeat(1000);
+    for re in utils::compile_all(r"(\w)(\d+)\s").unwrap() {
+        for interval in [1, 7, 100] {
+            let calls = Cell::new(0usize);
+            let check = || {
+                calls.set(calls.get() + 1);
+                false
+            };
+            assert!(
+                re.find_interruptible(&*haystack, interval, &check)
+                    .unwrap()
+                    .is_none()
+            );
+            let expected = haystack.len() / interval;
+            assert!(calls.get().abs_diff(expected) <= 1, "{}", calls.get());
+        }
+    }
+}
+
+#[test]
+fn test_stops_at_first_true() {
+    let haystack = "ab-".repeat(1000);
+    for re in utils::compile_all(r"(\w)(\d+)\s").unwrap() {
+        // The search stops at the call which returns true, the tenth.
+        let calls = Cell::new(0);
+        let check = || {
+            calls.set(calls.get() + 1);
+            calls.get() == 10
+        };
+        let result = re.find_interruptible(&*haystack, 5, &check);
+        assert_eq!(result.unwrap_err(), MatchError::Interrupted);
+        assert_eq!(calls.get(), 10);
+
+        // A match found before the check returns true is reported.
+        let calls = Cell::new(0);
+        let check = || {
+            calls.set(calls.get() + 1);
+            calls.get() == 10
+        };
+        let m = re
+            .find_interruptible("ab12 ab-", 5, &check)
+            .unwrap()
+            .unwrap();
+        assert_eq!((m.start(), m.end()), (1, 5));
+        assert!(calls.get() < 10);
+    }
+}
+
+#[test]
+fn test_flag_from_another_thread() {
+    // Many threads alive at every char, for a long time, and no literal for
+    // the prefilter.
+    let haystack = "a".repeat(1 << 22);
+    let interval = 1000;
+    for re in utils::compile_all(r"(?:a|aa)*\d|\w{1,50}\s").unwrap() {
+        let stop = AtomicBool::new(false);
+        let calls = AtomicUsize::new(0);
+        let check = || {
+            calls.fetch_add(1, Ordering::Relaxed);
+            stop.load(Ordering::Relaxed)
+        };
+        let result = std::thread::scope(|scope| {
+            // The flag is set once the search is running.
+            scope.spawn(|| {
+                while calls.load(Ordering::Relaxed) == 0 {
+                    std::hint::spin_loop();
+                }
+                stop.store(true, Ordering::Relaxed);
+            });
+            let result = re.find_interruptible(&*haystack, interval, &check);
+            // Releases the other thread, if the check was never called.
+            calls.fetch_add(1, Ordering::Relaxed);
+            result
+        });
+        assert_eq!(result.unwrap_err(), MatchError::Interrupted);
+        // The search stopped long before reading the whole haystack.
+        let read = calls.load(Ordering::Relaxed) * interval;
+        assert!(read < haystack.len() / 2, "{read}");
+        // The regex is still usable.
+        assert_eq!(re.find("aa1").unwrap().as_str(), "aa1");
+    }
+}
+
+#[test]
+fn test_panic_in_check() {
+    let haystack = "ab-".repeat(100);
+    for re in utils::compile_all(r"(\w)(\d+)\s").unwrap() {
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            re.find_interruptible(&*haystack, 10, &|| panic!("check"))
+        }));
+        assert!(result.is_err());
+        let m = re
+            .find_interruptible("a1 ", 10, &|| false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(m.as_str(), "a1 ");
+        assert_eq!(re.find("b22 ").unwrap().as_str(), "b22 ");
+    }
+}
+
+#[test]
+fn test_invalid_span() {
+    for re in utils::compile_all("a").unwrap() {
+        let reversed = Input::new("aaa").span(Span { from: 2, to: 1 });
+        assert_eq!(
+            re.find_interruptible(reversed, 1, &|| false).unwrap_err(),
+            MatchError::InvalidSpan { from: 2, to: 1 }
+        );
+    }
+}
+
+#[test]
+fn test_iterators_without_interruption() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        for re in &engines {
+            let mut matches = re.find_all(*haystack);
+            matches.set_interrupt(Arc::new(AtomicBool::new(false)));
+            let found: Vec<_> = matches.by_ref().map(|m| (m.start(), m.end())).collect();
+            let expected: Vec<_> = re
+                .find_all(*haystack)
+                .map(|m| (m.start(), m.end()))
+                .collect();
+            assert_eq!(found, expected, "{pattern:?} on {haystack:?}");
+            assert!(!matches.was_interrupted());
+        }
+    }
+}
+
+#[test]
+fn test_iterators_flagged_between_matches() {
+    for re in utils::compile_all(r"(\w)(\d)").unwrap() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut matches = re.find_all_captures("a1 b2 c3");
+        matches.set_interrupt(stop.clone());
+        assert_eq!(matches.next().unwrap().group0().as_str(), "a1");
+        stop.store(true, Ordering::Relaxed);
+        assert!(matches.next().is_none());
+        assert!(matches.was_interrupted());
+    }
+}
+
+#[test]
+fn test_iterators_flagged_during_a_search() {
+    // A first match, then a long haystack without any, with many threads
+    // alive at every char, and no literal for the prefilter.
+    let haystack = format!("a1 {}", "a".repeat(1 << 20));
+    for re in utils::compile_all(r"(?:a|aa)*\d|\w{1,50}\s").unwrap() {
+        let started = Instant::now();
+        assert_eq!(re.find_all(&*haystack).count(), 1);
+        let full_scan = started.elapsed();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut matches = re.find_all(&*haystack);
+        matches.set_interrupt(stop.clone());
+        assert_eq!(matches.next().unwrap().as_str(), "a1");
+        let (next, elapsed) = std::thread::scope(|scope| {
+            // The flag is set early in the search for the next match.
+            scope.spawn(|| {
+                std::thread::sleep(full_scan / 20);
+                stop.store(true, Ordering::Relaxed);
+            });
+            let started = Instant::now();
+            let next = matches.next().map(|m| m.as_str());
+            (next, started.elapsed())
+        });
+        assert_eq!(next, None);
+        assert!(matches.was_interrupted());
+        // The search stopped long before the end of the haystack, with a lot
+        // of slack for a noisy machine.
+        assert!(
+            elapsed < full_scan / 2 + Duration::from_millis(5),
+            "{elapsed:?} of {full_scan:?}"
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use gregex::util::{Char, Interval, IntervalSet};
+
+/// Universe of the exhaustive tests, small enough to enumerate every pair of
+/// sets.
+const SMALL: u32 = 8;
+/// Universe of the randomized tests, a set is then a `u64` bitset.
+const LARGE: u32 = 64;
+
+fn c(value: u32) -> Char {
+    char::from_u32(value).unwrap().into()
+}
+
+fn interval(from: u32, to: u32) -> Interval {
+    Interval::new(c(from), c(to))
+}
+
+/// The canonical set of the code points in the bitset.
+fn from_bits(bits: u64, universe: u32) -> IntervalSet {
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < universe {
+        if bits & (1 << i) != 0 {
+            let start = i;
+            while i + 1 < universe && bits & (1 << (i + 1)) != 0 {
+                i += 1;
+            }
+            ranges.push((c(start), c(i)));
+        }
+        i += 1;
+    }
+    IntervalSet::from_sorted_ranges(&ranges)
+}
+
+/// Checks that the set is canonical, and returns it as a bitset.
+fn to_bits(set: &IntervalSet) -> u64 {
+    let mut bits = 0;
+    let mut previous_end: Option<u32> = None;
+    for interval in set.intervals() {
+        assert!(!interval.is_empty(), "Empty interval in {set:?}");
+        let (from, to) = interval.bounds();
+        let (from, to) = (u32::from(from), u32::from(to));
+        if let Some(end) = previous_end {
+            assert!(from > end + 1, "Unsorted or adjacent intervals in {set:?}");
+        }
+        previous_end = Some(to);
+        for i in from..=to {
+            bits |= 1 << i;
+        }
+    }
+    bits
+}
+
+fn check_pair(a: u64, b: u64, universe: u32) {
+    let set_a = from_bits(a, universe);
+    let set_b = from_bits(b, universe);
+    let (a_only, both, b_only) = set_a.clone().intersect_and_substract(set_b.clone());
+    assert_eq!(to_bits(&a_only), a & !b, "{set_a:?} - {set_b:?}");
+    assert_eq!(to_bits(&both), a & b, "{set_a:?} & {set_b:?}");
+    assert_eq!(to_bits(&b_only), b & !a, "{set_b:?} - {set_a:?}");
+    assert_eq!(
+        to_bits(&set_a.union(&set_b)),
+        a | b,
+        "{set_a:?} | {set_b:?}"
+    );
+}
+
+#[test]
+fn test_interval_set_exhaustive() {
+    let mask = (1u64 << SMALL) - 1;
+    for a in 0..=mask {
+        for b in 0..=mask {
+            check_pair(a, b, SMALL);
+        }
+        let set = from_bits(a, SMALL);
+        assert_eq!(to_bits(&set.negate(interval(0, SMALL - 1))), !a & mask);
+        // Negating in a smaller universe ignores what is outside of it.
+        assert_eq!(to_bits(&set.negate(interval(2, 5))), !a & 0b111100);
+        assert!(set.negate(Interval::EMPTY).is_empty());
+    }
+}
+
+/// A xorshift generator, good enough to pick small interval sets.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u32) -> u32 {
+        (self.next() % n as u64) as u32
+    }
+}
+
+#[test]
+fn test_interval_set_randomized() {
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    for _ in 0..20_000 {
+        // Random sorted ranges, possibly overlapping, adjacent or empty, to
+        // also exercise the normalization of from_sorted_ranges.
+        let mut pick = || {
+            let mut starts = (0..rng.below(6))
+                .map(|_| rng.below(LARGE))
+                .collect::<Vec<_>>();
+            starts.sort();
+            let mut bits = 0u64;
+            let ranges = starts
+                .into_iter()
+                .map(|start| {
+                    let end = (start + rng.below(12)).min(LARGE - 1);
+                    // Roughly one range in eight is empty.
+                    let end = if rng.below(8) == 0 && start > 0 {
+                        start - 1
+                    } else {
+                        end
+                    };
+                    for i in start..=end {
+                        bits |= 1 << i;
+                    }
+                    (c(start), c(end))
+                })
+                .collect::<Vec<_>>();
+            let set = IntervalSet::from_sorted_ranges(&ranges);
+            assert_eq!(to_bits(&set), bits, "{ranges:?}");
+            bits
+        };
+        let (a, b) = (pick(), pick());
+        check_pair(a, b, LARGE);
+        assert_eq!(
+            to_bits(&from_bits(a, LARGE).negate(interval(0, LARGE - 1))),
+            !a
+        );
+    }
+}
+
+#[test]
+fn test_unsorted_ranges_randomized() {
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for _ in 0..20_000 {
+        // Random ranges in any order, possibly overlapping, adjacent, empty
+        // or repeated.
+        let mut bits = 0u64;
+        let ranges = (0..rng.below(8))
+            .map(|_| {
+                let start = rng.below(LARGE);
+                let end = (start + rng.below(12)).min(LARGE - 1);
+                let end = if rng.below(8) == 0 && start > 0 {
+                    start - 1
+                } else {
+                    end
+                };
+                for i in start..=end {
+                    bits |= 1 << i;
+                }
+                (c(start), c(end))
+            })
+            .collect::<Vec<_>>();
+        let set = IntervalSet::from_ranges(ranges.iter().copied());
+        assert_eq!(to_bits(&set), bits, "{ranges:?}");
+        assert_eq!(set, from_bits(bits, LARGE), "{ranges:?}");
+        assert!(IntervalSet::is_normalized(&set.to_ranges()));
+        // Normalized ranges are kept as they are.
+        let normalized = IntervalSet::from_ranges(set.to_ranges().iter().copied());
+        assert_eq!(normalized, set);
+    }
+}
+
+#[test]
+fn test_is_normalized() {
+    let ranges = |pairs: &[(u32, u32)]| {
+        pairs
+            .iter()
+            .map(|(from, to)| (c(*from), c(*to)))
+            .collect::<Vec<_>>()
+    };
+    assert!(IntervalSet::is_normalized(&[]));
+    assert!(IntervalSet::is_normalized(&ranges(&[
+        (1, 1),
+        (3, 5),
+        (7, 9)
+    ])));
+    // Unsorted, overlapping, adjacent and empty ranges.
+    assert!(!IntervalSet::is_normalized(&ranges(&[(7, 9), (3, 5)])));
+    assert!(!IntervalSet::is_normalized(&ranges(&[(3, 7), (5, 9)])));
+    assert!(!IntervalSet::is_normalized(&ranges(&[(3, 4), (5, 9)])));
+    assert!(!IntervalSet::is_normalized(&ranges(&[(3, 2)])));
+    // The last code point doesn't overflow.
+    let top = [(Char::from(0u8), Char::from(char::MAX))];
+    assert!(IntervalSet::is_normalized(&top));
+}
+
+#[test]
+fn test_interval_substract() {
+    let (left, right) = interval(3, 9).substract(&interval(5, 6));
+    assert_eq!((left, right), (interval(3, 4), interval(7, 9)));
+    let (left, right) = interval(3, 9).substract(&interval(0, 20));
+    assert!(left.is_empty() && right.is_empty());
+    let (left, right) = interval(3, 9).substract(&Interval::EMPTY);
+    assert_eq!((left, right.is_empty()), (interval(3, 9), true));
+    let (left, right) = Interval::EMPTY.substract(&interval(0, 20));
+    assert!(left.is_empty() && right.is_empty());
+}
+
+#[test]
+fn test_interval_set_bounds() {
+    // The extremities of the code points must not overflow.
+    let all = IntervalSet::new(vec![Interval::ALL]);
+    assert!(all.negate(Interval::ALL).is_empty());
+    let valid = IntervalSet::new(vec![Interval::ALL_VALID]);
+    let (left, right) = Interval::ALL.substract(&Interval::ALL_VALID);
+    assert!(left.is_empty());
+    assert_eq!(valid.negate(Interval::ALL), IntervalSet::new(vec![right]));
+    assert_eq!(valid.union(&all), all);
+    let (_, both, _) = all.intersect_and_substract(valid.clone());
+    assert_eq!(both, valid);
+}
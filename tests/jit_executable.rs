@@ -0,0 +1,109 @@
+//! The executable memory of the JIT, see `gregex::thompson::pike_jit::executable`.
+#![cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+
+use std::cell::Cell;
+use std::io;
+
+use gregex::regex::{Config, RegexImpl};
+use gregex::thompson::pike_jit::executable::{
+    ExecutableAllocator, ExecutableRegion, SystemAllocator,
+};
+use gregex::thompson::pike_jit::{CompileError, JittedRegex};
+use gregex::util::{Input, Span};
+
+fn find(jit: &JittedRegex, haystack: &str) -> Option<(usize, usize)> {
+    let mut state = jit.new_state();
+    let mut spans = [Span::invalid()];
+    jit.exec(Input::new(haystack), &mut state, &mut spans)
+        .then(|| (spans[0].from, spans[0].to))
+}
+
+/// Delegates to the system allocator, counting the allocations.
+#[derive(Default)]
+struct CountingAllocator {
+    allocations: Cell<usize>,
+}
+
+// SAFETY: The regions are the ones of the system allocator.
+unsafe impl ExecutableAllocator for CountingAllocator {
+    fn allocate(&self, len: usize) -> io::Result<Box<dyn ExecutableRegion>> {
+        self.allocations.set(self.allocations.get() + 1);
+        SystemAllocator.allocate(len)
+    }
+}
+
+#[test]
+fn test_code_runs_from_the_allocated_memory() {
+    let allocator = CountingAllocator::default();
+    let jit = JittedRegex::with_allocator(r"(\d+)-(\d+)", Config::default(), &allocator).unwrap();
+    assert_eq!(allocator.allocations.get(), 1);
+    assert_eq!(find(&jit, "from 10-20"), Some((5, 10)));
+    assert_eq!(find(&jit, "none"), None);
+}
+
+#[test]
+fn test_system_allocator_regions() {
+    let mut region = SystemAllocator.allocate(16).unwrap();
+    // `ret`
+    region.writable()[0] = 0xc3;
+    region.make_executable().unwrap();
+    assert!(!region.as_ptr().is_null());
+}
+
+/// Never provides memory.
+struct NoMemory;
+
+// SAFETY: No region is ever returned.
+unsafe impl ExecutableAllocator for NoMemory {
+    fn allocate(&self, _len: usize) -> io::Result<Box<dyn ExecutableRegion>> {
+        Err(io::Error::from(io::ErrorKind::OutOfMemory))
+    }
+}
+
+/// Provides writable memory, which can't be made executable, like on a
+/// platform denying executable memory.
+struct DeniedExecution;
+
+struct DeniedRegion(Vec<u8>);
+
+impl ExecutableRegion for DeniedRegion {
+    fn writable(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+
+    fn make_executable(&mut self) -> io::Result<()> {
+        Err(io::Error::from(io::ErrorKind::PermissionDenied))
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        unreachable!("the region is never executable")
+    }
+}
+
+// SAFETY: The regions are never made executable.
+unsafe impl ExecutableAllocator for DeniedExecution {
+    fn allocate(&self, len: usize) -> io::Result<Box<dyn ExecutableRegion>> {
+        Ok(Box::new(DeniedRegion(vec![0; len])))
+    }
+}
+
+fn denied_kind(allocator: &dyn ExecutableAllocator) -> io::ErrorKind {
+    let err = JittedRegex::with_allocator("a+b", Config::default(), allocator).unwrap_err();
+    assert!(
+        err.to_string().contains("denied executable memory"),
+        "{err}"
+    );
+    match &err {
+        gregex::Error::Jit(CompileError::ExecutableMemoryDenied(err)) => err.kind(),
+        _ => panic!("unexpected error {err:?}"),
+    }
+}
+
+#[test]
+fn test_denied_memory_is_reported() {
+    assert_eq!(denied_kind(&NoMemory), io::ErrorKind::OutOfMemory);
+    assert_eq!(
+        denied_kind(&DeniedExecution),
+        io::ErrorKind::PermissionDenied
+    );
+}
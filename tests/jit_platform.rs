@@ -0,0 +1,23 @@
+//! The platforms of the JIT: it runs on x86_64 unix and windows, and is
+//! unavailable elsewhere, where the Pike VM still works.
+#[test]
+#[cfg(all(windows, feature = "jit"))]
+fn test_jit_windows_smoke() {
+    let re = gregex::Regex::pike_jit(r"(\w+)-(\d+)").unwrap();
+    let caps = re.find_captures("test-42").unwrap();
+    assert_eq!(caps.group0().as_str(), "test-42");
+    assert_eq!(caps.get(1).unwrap().as_str(), "test");
+    assert_eq!(caps.get(2).unwrap().as_str(), "42");
+    assert_eq!(re.find_all("a-1 b-2 c-3").count(), 3);
+}
+
+#[test]
+#[cfg(not(all(feature = "jit", target_arch = "x86_64", any(unix, windows))))]
+fn test_jit_unavailable() {
+    let err = gregex::Regex::pike_jit("a").err().unwrap();
+    assert!(matches!(
+        err,
+        gregex::Error::Unsupported(gregex::error::UnsupportedFeature::EngineUnavailable(_))
+    ));
+    assert!(gregex::Regex::pike_vm("a").is_ok());
+}
@@ -0,0 +1,232 @@
+//! The memory management of the JIT state. The tests which don't run the
+//! jitted code are also meant to be run under Miri:
+//! `cargo +nightly miri test --test jit_state state_`
+#![cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+
+use gregex::regex::{Config, RegexImpl};
+use gregex::thompson::pike_jit::{JittedRegex, State};
+use gregex::util::{Input, Span};
+use gregex::{Builder, CgStrategy};
+
+/// A value which depends on the index and on a seed, so that the content of
+/// two regions written with different seeds differs everywhere.
+fn canary(seed: u64, i: usize) -> u64 {
+    0xC0FF_EE00_0000_0000 ^ (seed << 32) ^ i as u64
+}
+
+fn write_canaries(state: &mut State, seed: u64) {
+    for (i, word) in state.as_mut_slice().iter_mut().enumerate() {
+        *word = canary(seed, i);
+    }
+}
+
+fn check_canaries(state: &State, seed: u64, len: usize) {
+    let words = state.as_slice();
+    for (i, word) in words[..len].iter().enumerate() {
+        assert_eq!(*word, canary(seed, i), "word {i} of {}", words.len());
+    }
+}
+
+#[test]
+fn state_new_is_zeroed() {
+    for len in [1, 2, 7, 64, 1000] {
+        let state = State::new(len);
+        assert_eq!(state.mem_len(), len);
+        assert!(state.as_slice().iter().all(|word| *word == 0));
+    }
+    // Never zero-sized.
+    let mut state = State::new(0);
+    assert_eq!(state.mem_len(), 1);
+    write_canaries(&mut state, 1);
+    check_canaries(&state, 1, 1);
+}
+
+#[test]
+fn state_grow_preserves_content() {
+    let mut state = State::new(5);
+    write_canaries(&mut state, 1);
+
+    // Shrinking is a no-op.
+    state.ensure_capacity(3);
+    assert_eq!(state.mem_len(), 5);
+    check_canaries(&state, 1, 5);
+
+    state.ensure_capacity(13);
+    assert_eq!(state.mem_len(), 13);
+    check_canaries(&state, 1, 5);
+    assert!(state.as_slice()[5..].iter().all(|word| *word == 0));
+
+    // The whole new region is usable, up to its last word.
+    write_canaries(&mut state, 2);
+    state.double_size();
+    assert_eq!(state.mem_len(), 26);
+    check_canaries(&state, 2, 13);
+    assert!(state.as_slice()[13..].iter().all(|word| *word == 0));
+    write_canaries(&mut state, 3);
+    check_canaries(&state, 3, 26);
+}
+
+#[test]
+fn state_clone_is_deep() {
+    let mut state = State::new(4);
+    for _ in 0..4 {
+        state.double_size();
+    }
+    assert_eq!(state.mem_len(), 64);
+    write_canaries(&mut state, 1);
+
+    let mut clone = state.clone();
+    assert_eq!(clone.mem_len(), 64);
+    check_canaries(&clone, 1, 64);
+
+    // Both copies are independent, including when one of them grows.
+    write_canaries(&mut clone, 2);
+    clone.double_size();
+    check_canaries(&state, 1, 64);
+    check_canaries(&clone, 2, 64);
+    drop(state);
+    write_canaries(&mut clone, 3);
+    check_canaries(&clone, 3, 128);
+
+    // Clone of a clone, after the original was dropped.
+    let again = clone.clone();
+    drop(clone);
+    check_canaries(&again, 3, 128);
+}
+
+#[test]
+fn state_resize_queues() {
+    let mut state = State::new(4);
+    assert_eq!(state.queue_capacity(), 0);
+    for (capacity, rounded) in [(0, 1), (1, 1), (2, 3), (5, 7), (8, 15), (100, 127)] {
+        state.resize_queues(capacity);
+        assert_eq!(state.queue_capacity(), rounded);
+    }
+    // The queues are not part of the memory.
+    assert_eq!(state.mem_len(), 4);
+    let clone = state.clone();
+    assert_eq!(clone.queue_capacity(), 127);
+}
+
+/// Runs the jitted regex on the haystack, and returns the bounds of the
+/// first two groups.
+fn groups(jit: &JittedRegex, state: &mut State, haystack: &str) -> Vec<Option<(usize, usize)>> {
+    let mut spans = vec![Span::invalid(); 2];
+    jit.reset_state(state);
+    if !jit.exec(Input::new(haystack), state, &mut spans) {
+        return Vec::new();
+    }
+    spans
+        .into_iter()
+        .map(|span| span.valid().then_some((span.from, span.to)))
+        .collect()
+}
+
+fn jits(pattern: &str) -> Vec<JittedRegex> {
+    [CgStrategy::Array, CgStrategy::CowArray]
+        .into_iter()
+        .map(|jit_cg_strategy| {
+            let config = Config {
+                jit_cg_strategy,
+                ..Config::default()
+            };
+            JittedRegex::new(pattern, config).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_queue_high_water() {
+    for jit in jits("ab|cd|ef|gh|ij|kl|mn|op") {
+        let mut state = jit.new_state();
+        assert_eq!(groups(&jit, &mut state, "xxop"), [Some((2, 4)), None]);
+        let capacity = state.queue_capacity();
+        let high_water = state.queue_high_water();
+        // Every alternative is pushed on the active queue at each position.
+        assert!(high_water >= 8, "{high_water}");
+        assert!(high_water <= capacity, "{high_water} > {capacity}");
+    }
+}
+
+#[test]
+fn test_queue_overflow_grows() {
+    // Every thread of the counted repetition is alive at the same time.
+    let haystack = "a".repeat(40) + "c";
+    for jit in jits("(a{1,30})c") {
+        let expected = [Some((10, 41)), Some((10, 40))];
+        let mut state = jit.new_state();
+        assert_eq!(groups(&jit, &mut state, &haystack), expected);
+        let needed = state.queue_high_water();
+        assert!(needed >= 30, "{needed}");
+
+        // Too small queues are detected, and grown until the search fits.
+        state.resize_queues(1);
+        assert_eq!(groups(&jit, &mut state, &haystack), expected);
+        assert!(state.queue_capacity() >= needed);
+        assert_eq!(state.queue_high_water(), needed);
+    }
+}
+
+#[test]
+fn test_queue_tuning_shrinks_and_regrows() {
+    let long = "a".repeat(40) + "c";
+    for jit in jits("(a{1,30})c") {
+        let mut state = jit.new_state();
+        state.tune_queues(2);
+        assert_eq!(groups(&jit, &mut state, "xyz"), []);
+        let default = state.queue_capacity();
+        let small = state.queue_high_water();
+        assert!(small < 4, "{small}");
+
+        // The queues shrink to the observed depth, times the safety factor.
+        assert_eq!(
+            groups(&jit, &mut state, "xac"),
+            [Some((1, 3)), Some((1, 2))]
+        );
+        let shrunk = state.queue_capacity();
+        assert!(shrunk < default, "{shrunk} >= {default}");
+        assert!(shrunk >= 2 * small);
+
+        // They grow back when needed, and the tuning keeps what was needed.
+        assert_eq!(
+            groups(&jit, &mut state, &long),
+            [Some((10, 41)), Some((10, 40))]
+        );
+        let needed = state.queue_high_water();
+        assert!(state.queue_capacity() >= needed);
+        for _ in 0..3 {
+            assert_eq!(groups(&jit, &mut state, "xyz"), []);
+            assert_eq!(state.queue_high_water(), small);
+            assert!(state.queue_capacity() >= needed);
+        }
+    }
+}
+
+#[test]
+fn test_captures_survive_growth_mid_search() {
+    // The tree implementation of the capture groups starts with a small
+    // memory, and grows it when many threads write groups. The groups must
+    // survive the reallocation.
+    let pattern = r"((a)|(b)|(ab)|(ba))*(c)";
+    let haystack = "ab".repeat(2000) + "c" + &"ba".repeat(500) + "c";
+    let vm = Builder::new(pattern).pike_vm().unwrap();
+    let jit = Builder::new(pattern).pike_jit().unwrap();
+    let groups = |re: &gregex::Regex| {
+        re.find_all_captures(haystack.as_str())
+            .map(|caps| {
+                (0..caps.group_len())
+                    .map(|i| caps.get(i).map(|g| (g.start(), g.end())))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    let expected = groups(&vm);
+    assert_eq!(expected.len(), 2);
+    assert_eq!(groups(&jit), expected);
+    // The state is reused by the iterator, and by the pool.
+    for _ in 0..3 {
+        assert_eq!(groups(&jit), expected);
+        let caps = jit.find_captures(haystack.as_str()).unwrap();
+        assert_eq!(caps.get(6).unwrap().start(), 4000);
+    }
+}
@@ -0,0 +1,111 @@
+//! The lazy JIT, which runs the Pike VM until it compiled the pattern with
+//! the JIT, see `Builder::lazy_jit`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::{Builder, CgStrategy, EngineKind, MatchKind, Regex};
+
+fn spans(re: &Regex, haystack: &str) -> Vec<Vec<Option<(usize, usize)>>> {
+    re.find_all_captures(haystack)
+        .map(|caps| utils::group_spans(&caps))
+        .collect()
+}
+
+#[test]
+fn test_switches_after_the_threshold() {
+    let re = Builder::new(r"(\w+)@(\w+)")
+        .lazy_jit_after(Some(3))
+        .lazy_jit()
+        .unwrap();
+    assert_eq!(re.engine_kind(), EngineKind::LazyJIT);
+    for _ in 0..2 {
+        assert!(re.is_match("me@example"));
+        assert_eq!(re.jit_cg_strategy(), None);
+    }
+    assert!(re.is_match("me@example"));
+    assert_eq!(re.jit_cg_strategy().is_some(), utils::jit_available());
+    assert_eq!(re.force_jit(), utils::jit_available());
+}
+
+#[test]
+fn test_same_matches_before_and_after_the_switch() {
+    let cases = [
+        (r"(\w+)@(\w+)", "a@b, me@example and x@"),
+        (r"(a)|(b)|", "xaby"),
+        (r"(?m)^(\d+)$", "12\nab\n345\n"),
+        (r"\b(é+)\b", "é éé xé"),
+    ];
+    for (pattern, haystack) in cases {
+        let vm = Regex::pike_vm(pattern).unwrap();
+        let expected = spans(&vm, haystack);
+        let re = Builder::new(pattern)
+            .lazy_jit_after(None)
+            .lazy_jit()
+            .unwrap();
+        assert_eq!(spans(&re, haystack), expected, "{pattern:?} before");
+        assert_eq!(re.jit_cg_strategy(), None);
+        re.force_jit();
+        assert_eq!(spans(&re, haystack), expected, "{pattern:?} after");
+    }
+}
+
+#[test]
+fn test_states_created_before_the_switch() {
+    let re = Builder::new(r"(\d)(\d)?")
+        .lazy_jit_after(Some(2))
+        .lazy_jit()
+        .unwrap();
+    let mut scratch = re.scratch_state();
+    // The iterator's own state is created on the VM, and the JIT is compiled
+    // by its second search.
+    let found: Vec<_> = re.find_all("1 23 4").map(|m| m.as_str()).collect();
+    assert_eq!(found, ["1", "23", "4"]);
+    assert_eq!(re.jit_cg_strategy().is_some(), utils::jit_available());
+    assert_eq!(re.find_with(&mut scratch, "x56").unwrap().as_str(), "56");
+}
+
+#[test]
+fn test_falls_back_to_the_vm() {
+    let possessive = Builder::new(r"(a*+)a|(b)")
+        .possessive_quantifiers(true)
+        .lazy_jit_after(Some(1))
+        .lazy_jit()
+        .unwrap();
+    let longest = Builder::new("a|ab")
+        .match_kind(MatchKind::LeftmostLongest)
+        .lazy_jit_after(Some(1))
+        .lazy_jit()
+        .unwrap();
+    let registers = Builder::new("(a)")
+        .jit_cg_strategy(CgStrategy::Registers)
+        .lazy_jit_after(Some(1))
+        .lazy_jit()
+        .unwrap();
+    for _ in 0..2 {
+        assert_eq!(possessive.find("aab").unwrap().as_str(), "b");
+        assert_eq!(longest.find("ab").unwrap().as_str(), "ab");
+        assert_eq!(registers.find_captures("a").unwrap()[1].len(), 1);
+    }
+    for re in [&possessive, &longest, &registers] {
+        assert!(!re.force_jit(), "{:?}", re.pattern());
+        assert_eq!(re.jit_cg_strategy(), None);
+    }
+}
+
+#[test]
+fn test_force_jit_of_the_other_engines() {
+    let vm = Regex::pike_vm("a+").unwrap();
+    assert!(!vm.force_jit());
+    let assertions = Regex::pike_vm("(?m)^$").unwrap();
+    assert!(!assertions.force_jit());
+    if utils::jit_available() {
+        assert!(Regex::pike_jit("a+").unwrap().force_jit());
+    }
+    // The rest of the pattern has its own lazy JIT, see the leading wildcard.
+    let wildcard = Builder::new(r".*(\d+)")
+        .lazy_jit_after(None)
+        .lazy_jit()
+        .unwrap();
+    assert_eq!(wildcard.force_jit(), utils::jit_available());
+    assert_eq!(&wildcard.find_captures("ab\nx12").unwrap()[1], "2");
+}
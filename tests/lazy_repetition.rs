@@ -0,0 +1,49 @@
+//! The lazy bounded repetitions, whose optional copies are unrolled like the
+//! greedy ones, see `Compiler::compile_repetition`.
+#[allow(dead_code)]
+mod utils;
+
+const PATTERNS: &[&str] = &[
+    r"(.{2,10}?)b",
+    r"(.{0,2}?)X",
+    r"(a{1,3}?)(a*)",
+    r"(?:(a)|(b)){1,4}?c",
+    r"(\w{0,3}?)(\w{0,3}?)!",
+    r"x(.{0,5}?)(y|yz)",
+    r"(.{3}?)(.{0,2}?)$",
+];
+
+const INPUTS: &[&str] = &[
+    "",
+    "abcX",
+    "aaaaaaaaaab",
+    "ab abbb aaaaaaaaaaaab",
+    "aaaa",
+    "ababbac abc",
+    "word! longer!",
+    "xyz xaaaaayz xaaaaaaay",
+    "日本語b éb",
+];
+
+#[test]
+fn test_lazy_bounded_repetitions_match_rust_regex() {
+    for pattern in PATTERNS {
+        for input in INPUTS {
+            utils::check_all_engines(pattern, input);
+        }
+    }
+}
+
+#[test]
+fn test_later_start_with_more_iterations_left() {
+    // The thread started at 0 runs out of iterations before the `X`.
+    for re in utils::compile_all(r".{0,2}?X").unwrap() {
+        let found = re.find("abcX").unwrap();
+        assert_eq!((found.start(), found.end()), (1, 4));
+    }
+    let subject = format!("{}X", "a".repeat(300));
+    for re in utils::compile_all(r"(.{0,200}?)X").unwrap() {
+        let caps = re.find_captures(subject.as_str()).unwrap();
+        assert_eq!(caps.get(1).unwrap().start(), 100);
+    }
+}
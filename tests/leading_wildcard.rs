@@ -0,0 +1,61 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use gregex::util::Input;
+
+/// Patterns starting with `.*`, searched without it first, and the inputs
+/// where the start of the match depends on the lines, or on the greediness.
+const CASES: &[(&str, &str)] = &[
+    (r".*needle", "abc\nxx needle yy needle\nzz needle"),
+    (r".*?needle", "abc\nxx needle yy needle\nzz needle"),
+    (r"(?s).*needle", "abc\nxx needle yy needle\nzz needle"),
+    (r"(?s).*?needle", "abc\nxx needle yy needle\nzz needle"),
+    (r".*needle", "no match\nat all"),
+    (r"(?s).*needle", "no match\nat all"),
+    (r".*(a)(b)?c", "xx\nyaac abc\nac"),
+    (r".*?(a)(b)?c", "xx\nyaac abc\nac"),
+    (r".*foo.*bar", "foo\nbar foo bar\nfoobar"),
+    (r".*\n", "ab\ncd\n"),
+    (r".*\nb", "ab\ncd\nb"),
+    (r".*b$", "ab\ncb"),
+    (r".*^a", "a\na"),
+    (r"(?m).*^a", "ba\na"),
+    (r".*c", "ab\n\ncd"),
+    (r".*a|b", "xb\nxa"),
+    (r".*[^\n]", "\n\nab"),
+    (r".*é", "aé\nbé"),
+    (r"(?i).*NEEDLE", "xx needle"),
+];
+
+#[test]
+fn test_leading_wildcard_matches_rust_regex() {
+    for (pattern, input) in CASES {
+        utils::check_all_engines(pattern, input);
+    }
+}
+
+#[test]
+fn test_leading_wildcard_in_sub_spans() {
+    let haystack = "ab needle\ncd needle ef\nneedle";
+    for pattern in [
+        r".*needle",
+        r".*?ne(e)dle",
+        r"(?s).*needle",
+        r"(?s).*?needle",
+    ] {
+        let general = Builder::new(pattern).specialize(false).pike_vm().unwrap();
+        for re in utils::compile_all(pattern).unwrap() {
+            for from in 0..haystack.len() {
+                let input = Input::new(haystack).span((from..haystack.len()).into());
+                let expected = utils::find_groups(&general, input.clone());
+                assert_eq!(
+                    utils::find_groups(&re, input.clone()),
+                    expected,
+                    "{pattern:?} from {from}"
+                );
+                assert_eq!(re.is_match(input), expected.is_some());
+            }
+        }
+    }
+}
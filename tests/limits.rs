@@ -0,0 +1,180 @@
+//! The limits of the compiler, see `gregex::limits`: the patterns at a limit
+//! compile on every engine, and the ones just over it are rejected with the
+//! error naming it.
+use gregex::limits;
+use gregex::thompson::bytecode::CompileError;
+use gregex::{Builder, Error, MultiRegex};
+
+/// Builds the pattern with the Pike VM and the JIT, which must agree. The
+/// size limit of the builder is lifted, the patterns at the fixed limits are
+/// over the default one, see `test_size_limit`.
+fn build(builder: &Builder) -> Result<(), Error> {
+    let builder = builder.clone().size_limit(usize::MAX);
+    let vm = builder.pike_vm();
+    let jit = builder.pike_jit();
+    assert_eq!(vm.is_ok(), jit.is_ok(), "{:?}", jit.as_ref().err());
+    vm.map(|_| ())
+}
+
+fn limit_error(builder: &Builder) -> CompileError {
+    match build(builder).expect_err("over the limit") {
+        Error::CompileLimitExceeded(err) => err,
+        err => panic!("unexpected error {err:?}"),
+    }
+}
+
+/// `n` distinct chars, which are no metacharacters.
+fn chars(n: usize) -> impl Iterator<Item = char> {
+    (0x100..).filter_map(char::from_u32).take(n)
+}
+
+#[test]
+fn test_limits_are_queryable() {
+    assert_eq!(limits::max_instructions(), limits::MAX_INSTRUCTIONS);
+    assert_eq!(
+        limits::max_unrolled_repetition_product(),
+        limits::MAX_UNROLLED_REPETITION_PRODUCT
+    );
+    assert_eq!(limits::max_fork_branches(), limits::MAX_FORK_BRANCHES);
+    assert_eq!(limits::max_outlined_classes(), limits::MAX_OUTLINED_CLASSES);
+}
+
+#[test]
+fn test_repetition_product() {
+    let max = limits::MAX_UNROLLED_REPETITION_PRODUCT;
+    let at_limit = Builder::new(format!("(?:a{{100}}){{{}}}", max / 100));
+    assert!(build(&at_limit).is_ok());
+    // The unbounded repetitions count their minimum.
+    assert!(build(&Builder::new(format!("(?:a{{100,}}){{{}}}", max / 100))).is_ok());
+
+    let over = Builder::new(format!("(?:a{{100}}){{{}}}", max / 100 + 1));
+    assert_eq!(
+        limit_error(&over),
+        CompileError::RepetitionTooLarge {
+            product: max + 100,
+            max
+        }
+    );
+    // Along the deepest path only, not summed over the branches.
+    assert!(build(&Builder::new(format!("(?:a{{{max}}}|b{{{max}}})c*"))).is_ok());
+    assert_eq!(
+        limit_error(&Builder::new(format!("x|(?:a{{2}}){{1,{}}}", max / 2 + 1))),
+        CompileError::RepetitionTooLarge {
+            product: max + 2,
+            max
+        }
+    );
+}
+
+#[test]
+fn test_instructions() {
+    let max = limits::MAX_INSTRUCTIONS;
+    // One instruction per char, and the accept.
+    let at_limit = Builder::new("a".repeat(max - 1));
+    assert!(build(&at_limit).is_ok());
+    let over = Builder::new("a".repeat(max));
+    assert_eq!(
+        limit_error(&over),
+        CompileError::TooManyInstructions {
+            instructions: max + 1,
+            max
+        }
+    );
+}
+
+#[test]
+fn test_fork_branches() {
+    let max = limits::MAX_FORK_BRANCHES;
+    let alternation = |n| {
+        let branches: Vec<_> = chars(n).map(|c| format!("{c}{c}")).collect();
+        Builder::new(branches.join("|"))
+    };
+    let at_limit = alternation(max);
+    assert!(build(&at_limit).is_ok());
+    assert_eq!(
+        limit_error(&alternation(max + 1)),
+        CompileError::TooManyForkBranches {
+            branches: max + 1,
+            max
+        }
+    );
+
+    // The patterns of a multi regex too.
+    let patterns: Vec<_> = chars(max + 1).map(String::from).collect();
+    let patterns: Vec<_> = patterns.iter().map(String::as_str).collect();
+    assert!(MultiRegex::new(&patterns[..max]).is_ok());
+    let err = MultiRegex::new(&patterns).err().unwrap();
+    assert!(matches!(
+        err,
+        Error::CompileLimitExceeded(CompileError::TooManyForkBranches { branches, .. })
+            if branches == max + 1
+    ));
+}
+
+#[test]
+fn test_outlined_classes() {
+    let max = limits::MAX_OUTLINED_CLASSES;
+    let classes = |n| {
+        let pattern: String = chars(2 * n)
+            .collect::<Vec<_>>()
+            .chunks(2)
+            .map(|pair| format!("[{}{}]", pair[0], pair[1]))
+            .collect();
+        Builder::new(pattern).force_class_outlining(true)
+    };
+    assert!(build(&classes(max)).is_ok());
+    assert_eq!(
+        limit_error(&classes(max + 1)),
+        CompileError::TooManyOutlinedClasses {
+            classes: max + 1,
+            max
+        }
+    );
+}
+
+#[test]
+fn test_size_limit() {
+    let size_error = |result: Result<_, Error>| match result.err() {
+        Some(Error::CompileLimitExceeded(CompileError::CompiledProgramTooLarge {
+            size,
+            limit,
+        })) => (size, limit),
+        err => panic!("unexpected result {err:?}"),
+    };
+    // The size of the bytecode is known before compiling it.
+    let pattern = r"(?:\w{100}){100}";
+    let (size, _) = size_error(Builder::new(pattern).size_limit(0).pike_vm());
+    let under = Builder::new(pattern).size_limit(size);
+    assert!(under.pike_vm().is_ok());
+    let over = Builder::new(pattern).size_limit(size - 1);
+    assert_eq!(size_error(over.pike_vm()), (size, size - 1));
+    let config = over.config().clone();
+    let err = gregex::validate(pattern, &config, &Default::default()).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<CompileError>(),
+        Some(&CompileError::CompiledProgramTooLarge {
+            size,
+            limit: size - 1
+        })
+    );
+
+    // The code of the JIT is larger than the bytecode, it stops once it is
+    // over the limit, and the lazy JIT keeps running the Pike VM.
+    if cfg!(all(
+        feature = "jit",
+        target_arch = "x86_64",
+        any(unix, windows)
+    )) {
+        let (jit_size, limit) = size_error(under.pike_jit());
+        assert!(jit_size > limit && limit == size, "{jit_size}");
+        assert!(Builder::new(pattern).pike_jit().is_ok());
+    }
+    let lazy = under.lazy_jit_after(None).lazy_jit().unwrap();
+    assert!(!lazy.force_jit());
+    assert!(lazy.is_match("x".repeat(10_000).as_str()));
+
+    // The default is finite, below the fixed limits.
+    let at_instruction_limit = "a".repeat(limits::MAX_INSTRUCTIONS - 1);
+    let (_, limit) = size_error(Builder::new(at_instruction_limit).pike_vm());
+    assert_eq!(limit, 10 << 20);
+}
@@ -0,0 +1,159 @@
+#[allow(dead_code)]
+mod utils;
+
+use std::ops::Range;
+
+use gregex::lint::{LintKind, LintWarning};
+use gregex::regex::{Config, MatchKind};
+
+fn warnings_with<'p>(pattern: &'p str, config: &Config) -> Vec<(LintKind, &'p str)> {
+    gregex::lint(pattern, config)
+        .into_iter()
+        .map(|warning| (warning.kind, &pattern[Range::from(warning.span)]))
+        .collect()
+}
+
+fn warnings(pattern: &str) -> Vec<(LintKind, &str)> {
+    warnings_with(pattern, &Config::default())
+}
+
+#[test]
+fn test_unescaped_dot() {
+    assert_eq!(warnings(r"example.com"), [(LintKind::UnescapedDot, ".")]);
+    // Not after a group.
+    assert_eq!(
+        warnings(r"(www.)?example.com"),
+        [(LintKind::UnescapedDot, ".")]
+    );
+    assert_eq!(warnings(r"example\.com"), []);
+    assert_eq!(warnings(r"a.*b"), []);
+    assert_eq!(warnings(r".com"), []);
+}
+
+#[test]
+fn test_suspicious_range() {
+    assert_eq!(warnings(r"[A-z]+"), [(LintKind::SuspiciousRange, "A-z")]);
+    assert_eq!(warnings(r"[0-Z]"), [(LintKind::SuspiciousRange, "0-Z")]);
+    assert_eq!(warnings(r"[A-Za-z0-9_]"), []);
+    assert_eq!(warnings(r"[!-~]"), []);
+}
+
+#[test]
+fn test_duplicate_class_range() {
+    assert_eq!(warnings(r"[a-za]"), [(LintKind::DuplicateClassRange, "a")]);
+    assert_eq!(
+        warnings(r"[a-zb-c]"),
+        [(LintKind::DuplicateClassRange, "b-c")]
+    );
+    assert_eq!(warnings(r"[xx]"), [(LintKind::DuplicateClassRange, "x")]);
+    // In nested classes too.
+    assert_eq!(warnings(r"[a[bb]]"), [(LintKind::DuplicateClassRange, "b")]);
+    assert_eq!(warnings(r"[a-cb-d]"), []);
+}
+
+#[test]
+fn test_nested_quantifier() {
+    assert_eq!(warnings(r"(a+)+"), [(LintKind::NestedQuantifier, "(a+)+")]);
+    assert_eq!(
+        warnings(r"(?:\d*){2,}"),
+        [(LintKind::NestedQuantifier, r"(?:\d*){2,}")]
+    );
+    assert_eq!(warnings(r"(a+)?"), []);
+    assert_eq!(warnings(r"(a?)+"), []);
+    assert_eq!(warnings(r"(ab+)+"), []);
+    assert_eq!(warnings(r"(?:[0-9a-f]{3}){1,2}"), []);
+    // Possessive quantifiers look like nested ones.
+    assert_eq!(warnings(r"a*+"), [(LintKind::NestedQuantifier, "a*+")]);
+    let config = Config {
+        possessive_quantifiers: true,
+        ..Config::default()
+    };
+    assert_eq!(warnings_with(r"a*+", &config), []);
+    assert_eq!(
+        warnings_with(r"(a*)+", &config),
+        [(LintKind::NestedQuantifier, "(a*)+")]
+    );
+}
+
+#[test]
+fn test_empty_alternative() {
+    assert_eq!(warnings(r"a||b"), [(LintKind::EmptyAlternative, "")]);
+    assert_eq!(warnings(r"x(a|)"), [(LintKind::EmptyAlternative, "")]);
+    let warnings = gregex::lint(r"x(|a)", &Config::default());
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(Range::from(warnings[0].span), 2..2);
+}
+
+#[test]
+fn test_misplaced_anchor() {
+    assert_eq!(warnings(r"foo$bar"), [(LintKind::MisplacedAnchor, "$")]);
+    assert_eq!(warnings(r"foo^bar"), [(LintKind::MisplacedAnchor, "^")]);
+    assert_eq!(warnings(r"^foo$|^bar$"), []);
+    // A line terminator can follow `$` in multi-line mode.
+    assert_eq!(warnings(r"(?m)foo$\n^bar"), []);
+}
+
+#[test]
+fn test_shadowed_branch() {
+    assert_eq!(
+        warnings(r"foo|foobar"),
+        [(LintKind::ShadowedBranch, "foobar")]
+    );
+    assert_eq!(warnings(r"x(a|b|ab)"), [(LintKind::ShadowedBranch, "ab")]);
+    assert_eq!(
+        warnings(r"cat|dog|cat"),
+        [(LintKind::ShadowedBranch, "cat")]
+    );
+    // Something follows the alternation.
+    assert_eq!(warnings(r"(foo|foobar)baz"), []);
+    assert_eq!(warnings(r"(foo|foobar)+"), []);
+    // The longer branch wins.
+    assert_eq!(warnings(r"foobar|foo"), []);
+    let config = Config {
+        match_kind: MatchKind::LeftmostLongest,
+        ..Config::default()
+    };
+    assert_eq!(warnings_with(r"foo|foobar", &config), []);
+}
+
+#[test]
+fn test_empty_repetition() {
+    assert_eq!(warnings(r"ab{0}c"), [(LintKind::EmptyRepetition, "b{0}")]);
+    assert_eq!(
+        warnings(r"(ab){0,0}"),
+        [(LintKind::EmptyRepetition, "(ab){0,0}")]
+    );
+    assert_eq!(warnings(r"ab{0,1}c"), []);
+}
+
+#[test]
+fn test_warnings_are_ordered_and_displayed() {
+    let warnings: Vec<LintWarning> = gregex::lint(r"[A-z]|x{0}|example.com", &Config::default());
+    let kinds: Vec<_> = warnings.iter().map(|warning| warning.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            LintKind::SuspiciousRange,
+            LintKind::EmptyRepetition,
+            LintKind::UnescapedDot
+        ]
+    );
+    assert!(
+        warnings[0].to_string().ends_with("(at 1..4)"),
+        "{}",
+        warnings[0]
+    );
+}
+
+#[test]
+fn test_invalid_patterns_have_no_warnings() {
+    assert_eq!(warnings(r"(a+)+["), []);
+    assert_eq!(warnings(r"[z-a]"), []);
+}
+
+#[test]
+fn test_corpus_is_clean() {
+    for (pattern, _) in utils::corpus::CASES {
+        assert_eq!(warnings(pattern), [], "{pattern:?}");
+    }
+}
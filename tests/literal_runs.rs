@@ -0,0 +1,18 @@
+//! The runs of literals, which the JIT matches with a single comparison.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::Input;
+
+#[test]
+fn test_literal_run_span_end() {
+    let subject = "xx Sherlock Holmes";
+    for engine in utils::compile_all("Sherlock").unwrap() {
+        let input = Input::new(subject).span((0..10).into());
+        assert!(engine.find(input).is_none());
+        let input = Input::new(subject).span((0..11).into());
+        assert_eq!(engine.find(input).unwrap().span.from, 3);
+        let input = Input::new(subject).span((3..12).into());
+        assert_eq!(engine.find(input).unwrap().span.to, 11);
+    }
+}
@@ -0,0 +1,151 @@
+//! The longest matches at the start of the haystack, see
+//! `Regex::find_longest_prefix`, on every engine: the ones other than the Pike
+//! VM search with a VM of their pattern.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::regex::Config;
+use gregex::thompson::bytecode::Compiler;
+use gregex::util::Input;
+use regex_syntax::hir::Look;
+
+/// The longest prefix, which every engine must agree on.
+fn longest_prefix<'h>(pattern: &str, haystack: &'h str) -> Option<&'h str> {
+    let mut found = None;
+    for (label, re) in utils::compile_all_labeled(pattern).unwrap() {
+        let m = re.find_longest_prefix(haystack);
+        assert!(m.is_none_or(|m| m.start() == 0), "{label}");
+        let end = m.map(|m| m.end());
+        assert_eq!(*found.get_or_insert(end), end, "{pattern:?} with {label}");
+    }
+    found.flatten().map(|end| &haystack[..end])
+}
+
+#[test]
+fn test_longest_branch() {
+    assert_eq!(longest_prefix("a|ab|abc", "abcd"), Some("abc"));
+    assert_eq!(longest_prefix("abc|ab|a", "abcd"), Some("abc"));
+    assert_eq!(longest_prefix("a|ab|abc", "abx"), Some("ab"));
+    assert_eq!(longest_prefix("(?:a|ab)(?:c|bcd)?", "abcd"), Some("abcd"));
+    assert_eq!(longest_prefix("a|ab|abc", "xabc"), None);
+    assert_eq!(longest_prefix("b", "abc"), None);
+}
+
+#[test]
+fn test_greedy_and_lazy() {
+    assert_eq!(longest_prefix("a+", "aaab"), Some("aaa"));
+    assert_eq!(longest_prefix("a+?", "aaab"), Some("aaa"));
+    assert_eq!(longest_prefix("a*?b?", "aaab"), Some("aaab"));
+    assert_eq!(longest_prefix("(?:ab)??", "ababx"), Some("ab"));
+    assert_eq!(longest_prefix("a{2,3}?", "aaaa"), Some("aaa"));
+    assert_eq!(longest_prefix("a*", "bbb"), Some(""));
+    assert_eq!(longest_prefix("a*?", ""), Some(""));
+    // The longest match isn't the one going the furthest: the threads of
+    // `.*` read the whole haystack, then die.
+    assert_eq!(longest_prefix("a.*b|a", "axxbxx"), Some("axxb"));
+}
+
+#[test]
+fn test_span() {
+    let re = Regex::pike_vm("a+|ab").unwrap();
+    let m = re
+        .find_longest_prefix(Input::new("xaab").span((1..4).into()))
+        .unwrap();
+    assert_eq!((m.start(), m.end()), (1, 3));
+    assert!(
+        re.find_longest_prefix(Input::new("xaab").span((3..4).into()))
+            .is_none()
+    );
+    // Unanchored inputs are anchored anyway.
+    assert!(
+        re.find_longest_prefix(Input::new("xaab").anchored(false))
+            .is_none()
+    );
+}
+
+#[test]
+fn test_captures() {
+    let groups = |pattern: &str, haystack: &str| {
+        let mut found = None;
+        for (label, re) in utils::compile_all_labeled(pattern).unwrap() {
+            let caps = re.find_longest_prefix_captures(haystack).unwrap();
+            let groups: Vec<_> = (0..caps.group_len())
+                .map(|i| caps.get(i).map(|m| m.as_str().to_string()))
+                .collect();
+            assert_eq!(*found.get_or_insert(groups.clone()), groups, "{label}");
+        }
+        found.unwrap()
+    };
+    let s = |group: &str| Some(group.to_string());
+    // The thread of the longest match.
+    assert_eq!(
+        groups("(a)|(ab)|(abc)", "abcd"),
+        [s("abc"), None, None, s("abc")]
+    );
+    // Both threads accept `ab`: the first one in the priority order wins.
+    assert_eq!(groups("(a|ab)(b?)", "ab"), [s("ab"), s("a"), s("b")]);
+    assert_eq!(groups("(ab|a)(b?)", "ab"), [s("ab"), s("ab"), s("")]);
+    assert_eq!(groups("(a*?)(a*)", "aa"), [s("aa"), s(""), s("aa")]);
+}
+
+/// Whether the matches of the pattern don't depend on the chars after them,
+/// so that the longest prefix is also the longest full match of a prefix.
+fn ignores_the_rest(pattern: &str) -> bool {
+    let Ok(hir) = Compiler::parse(pattern, &Config::default()) else {
+        return false;
+    };
+    hir.properties()
+        .look_set()
+        .iter()
+        .all(|look| matches!(look, Look::Start | Look::StartLF | Look::StartCRLF))
+}
+
+/// The longest prefix of the haystack which the pattern fully matches,
+/// trying every length.
+fn oracle(pattern: &str, haystack: &str) -> Option<usize> {
+    let full = regex::Regex::new(&format!(r"\A(?:{pattern})\z")).unwrap();
+    (0..=haystack.len())
+        .rev()
+        .filter(|&end| haystack.is_char_boundary(end))
+        .find(|&end| full.is_match(&haystack[..end]))
+}
+
+#[test]
+fn test_same_as_the_oracle() {
+    let patterns = utils::corpus::CASES
+        .iter()
+        .map(|(pattern, _)| *pattern)
+        .chain([
+            "a|ab|abc",
+            "(?:a|bc)*",
+            "(a|ab)(c|bcd)(d*)",
+            r"\w+?\d",
+            "(?:x*y)+?",
+            r"[^\s]+",
+            "(?s).{2,5}?b",
+        ]);
+    let haystacks = utils::corpus::CASES
+        .iter()
+        .map(|(_, haystack)| *haystack)
+        .chain(["abcd", "ababcbcd", "xxyxyyx", "a1b22c", "abcabcabb", "éé b"]);
+    let mut checked = 0;
+    for pattern in patterns.filter(|pattern| ignores_the_rest(pattern)) {
+        let Some(regexes) = utils::compile_all_labeled(pattern) else {
+            continue;
+        };
+        for haystack in haystacks.clone() {
+            let expected = oracle(pattern, haystack);
+            for (label, re) in &regexes {
+                let m = re.find_longest_prefix(haystack);
+                assert_eq!(
+                    m.map(|m| m.end()),
+                    expected,
+                    "{pattern:?} on {haystack:?} with {label}"
+                );
+            }
+            checked += 1;
+        }
+    }
+    assert!(checked > 100);
+}
@@ -0,0 +1,95 @@
+//! Matches starting at position 0, where the tree CG of the JIT records the
+//! start of group 0 as `-0`, see `CGImplTree::write_reg`.
+#[allow(dead_code)]
+mod utils;
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+use gregex::util::Input;
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+use gregex::{Builder, CgStrategy, Regex};
+
+const PATTERNS: &[&str] = &[
+    "",
+    "^",
+    "^(?:)",
+    "^a",
+    "^(a)",
+    "^(a)?",
+    "(a)|b",
+    "(?m)^(a*)",
+    r"\A(a|)(b)?",
+    "(?m)$",
+    "a",
+    "^()",
+    "(?:^|x)(a)",
+    "^(?:(a)|(b))*",
+];
+
+const HAYSTACKS: &[&str] = &["", "a", "ab", "aab", "b", "ba", "\na", "xa"];
+
+/// The groups of the first match, as bounds.
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+fn captures(re: &Regex, input: Input<'_>) -> Option<Vec<Option<(usize, usize)>>> {
+    let caps = re.find_captures(input)?;
+    Some(
+        (0..caps.group_len())
+            .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+            .collect(),
+    )
+}
+
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+fn jits(pattern: &str) -> Vec<(CgStrategy, Regex)> {
+    let strategies = [
+        CgStrategy::Auto,
+        CgStrategy::Registers,
+        CgStrategy::Tree,
+        CgStrategy::Array,
+        CgStrategy::CowArray,
+    ];
+    strategies
+        .into_iter()
+        .filter_map(|strategy| {
+            let builder = Builder::new(pattern)
+                .specialize(false)
+                .jit_cg_strategy(strategy);
+            Some((strategy, builder.pike_jit().ok()?))
+        })
+        .collect()
+}
+
+#[test]
+fn test_match_at_start_matches_rust_regex() {
+    for pattern in PATTERNS {
+        for haystack in HAYSTACKS {
+            utils::check_all_engines(pattern, haystack);
+        }
+    }
+}
+
+#[test]
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+fn test_match_at_start_every_strategy() {
+    for pattern in PATTERNS {
+        let vm = Builder::new(*pattern).specialize(false).pike_vm().unwrap();
+        let jits = jits(pattern);
+        // The registers only build without groups.
+        assert!(jits.len() >= 4, "{pattern:?}");
+        for haystack in HAYSTACKS {
+            for from in [0, 1] {
+                if from > haystack.len() {
+                    continue;
+                }
+                let input = Input::new(haystack).span((from..haystack.len()).into());
+                let expected = captures(&vm, input.clone());
+                for (strategy, re) in &jits {
+                    assert_eq!(
+                        captures(re, input.clone()),
+                        expected,
+                        "{pattern:?} on {haystack:?} from {from} with {strategy:?}"
+                    );
+                }
+            }
+        }
+    }
+}
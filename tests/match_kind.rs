@@ -0,0 +1,206 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::error::UnsupportedFeature;
+use gregex::util::Input;
+use gregex::{Builder, Error, MatchKind, Regex};
+use regex_automata::{Anchored, MatchKind as RustMatchKind, hybrid, meta};
+
+fn longest(pattern: &str) -> Regex {
+    Builder::new(pattern)
+        .match_kind(MatchKind::LeftmostLongest)
+        .pike_vm()
+        .unwrap()
+}
+
+fn find_all(re: &Regex, input: &str) -> Vec<(usize, usize)> {
+    re.find_all(input).map(|m| (m.start(), m.end())).collect()
+}
+
+/// The leftmost-longest matches found by regex-automata, iterating like
+/// `find_all`, or None if its lazy DFA can't search the input. regex-automata
+/// has no leftmost-longest mode, but the leftmost start is the same as in
+/// leftmost-first mode, and an anchored search from there reporting all the
+/// matches stops at the longest one.
+fn rust_find_all(pattern: &str, input: &str) -> Option<Vec<(usize, usize)>> {
+    let leftmost = meta::Regex::new(pattern).ok()?;
+    let dfa = hybrid::dfa::DFA::builder()
+        .configure(hybrid::dfa::DFA::config().match_kind(RustMatchKind::All))
+        .build(pattern)
+        .ok()?;
+    let mut cache = dfa.create_cache();
+    let mut matches = Vec::new();
+    let mut from = 0;
+    let mut previous_end = None;
+    while from <= input.len() {
+        let Some(m) = leftmost.find(regex_automata::Input::new(input).range(from..)) else {
+            break;
+        };
+        let anchored = regex_automata::Input::new(input)
+            .range(m.start()..)
+            .anchored(Anchored::Yes);
+        let end = dfa.try_search_fwd(&mut cache, &anchored).ok()??.offset();
+        // Like the iterators, an empty match right after the previous one
+        // is skipped.
+        if end > m.start() || previous_end != Some(end) {
+            matches.push((m.start(), end));
+            previous_end = Some(end);
+        }
+        from = if end > m.start() {
+            end
+        } else {
+            input[end..]
+                .chars()
+                .next()
+                .map_or(end + 1, |c| end + c.len_utf8())
+        };
+    }
+    Some(matches)
+}
+
+#[test]
+fn test_alternation_order_does_not_matter() {
+    let re = longest("a|ab");
+    assert_eq!(re.find("ab").unwrap().as_str(), "ab");
+    assert_eq!(longest("ab|a").find("ab").unwrap().as_str(), "ab");
+    assert_eq!(find_all(&re, "abaab"), vec![(0, 2), (2, 3), (3, 5)]);
+
+    let first = Builder::new("a|ab").pike_vm().unwrap();
+    assert_eq!(first.find("ab").unwrap().as_str(), "a");
+}
+
+#[test]
+fn test_lazy_repetitions_are_longest() {
+    assert_eq!(longest("a+?").find("aaa").unwrap().as_str(), "aaa");
+    assert_eq!(longest("a*?b?").find("aab").unwrap().as_str(), "aab");
+}
+
+#[test]
+fn test_leftmost_start_wins_over_length() {
+    // The match at 1 is found first, but the one at 0 ends later.
+    assert_eq!(longest("abcd|b").find("abcd").unwrap().as_str(), "abcd");
+    // The longer match starting later loses.
+    let re = longest("ab|bcde");
+    assert_eq!(re.find("abcde").unwrap().as_str(), "ab");
+}
+
+#[test]
+fn test_capture_groups() {
+    assert_eq!(
+        utils::find_groups(&longest("(a+)(a*)"), "aaa"),
+        Some(vec![Some((0, 3)), Some((0, 3)), Some((3, 3))])
+    );
+    assert_eq!(
+        utils::find_groups(&longest("(a|ab)(c|bcd)?"), "abcd"),
+        Some(vec![Some((0, 4)), Some((0, 1)), Some((1, 4))])
+    );
+    // Both divisions match all of "abcd": the highest priority one is kept,
+    // where POSIX would maximize the first group, i.e. (ab)(c)(d).
+    assert_eq!(
+        utils::find_groups(&longest("(a|ab)(c|bcd)(d*)"), "abcd"),
+        Some(vec![Some((0, 4)), Some((0, 1)), Some((1, 4)), Some((4, 4))])
+    );
+    assert_eq!(
+        utils::find_groups(&longest("(a)|(ab)"), "ab"),
+        Some(vec![Some((0, 2)), None, Some((0, 2))])
+    );
+}
+
+#[test]
+fn test_small_and_general_paths_agree() {
+    // find only asks for the overall match, which takes another path in the
+    // VM than find_captures.
+    for (pattern, input) in utils::corpus::CASES {
+        let Ok(re) = Builder::new(*pattern)
+            .match_kind(MatchKind::LeftmostLongest)
+            .pike_vm()
+        else {
+            continue;
+        };
+        let found = re.find(*input).map(|m| (m.start(), m.end()));
+        let captured = re
+            .find_captures(*input)
+            .map(|caps| (caps.group0().start(), caps.group0().end()));
+        assert_eq!(found, captured, "pattern {pattern:?} on {input:?}");
+        assert_eq!(re.is_match(*input), found.is_some());
+    }
+}
+
+#[test]
+fn test_against_regex_automata() {
+    let mut extra = vec![
+        ("a|ab", "abababa"),
+        ("(a|ab)(c|bcd)(d*)", "abcd abcdd"),
+        (r"\w+?|\w+\s\w+", "foo bar baz"),
+        ("x*|xx*y", "xxxy xx y"),
+        (r"(?m)^a|a$|ab", "ab\nba\nab"),
+        (r"\b\w{1,3}|\w+ing\b", "going sing a thing"),
+    ];
+    extra.extend_from_slice(utils::corpus::CASES);
+    let mut compared = 0;
+    for (pattern, input) in extra {
+        let Ok(re) = Builder::new(pattern)
+            .match_kind(MatchKind::LeftmostLongest)
+            .pike_vm()
+        else {
+            continue;
+        };
+        let Some(expected) = rust_find_all(pattern, input) else {
+            continue;
+        };
+        assert_eq!(
+            find_all(&re, input),
+            expected,
+            "pattern {pattern:?} on {input:?}"
+        );
+        let starts: Vec<_> = re
+            .find_all_captures(input)
+            .map(|caps| (caps.group0().start(), caps.group0().end()))
+            .collect();
+        assert_eq!(starts, expected, "pattern {pattern:?} on {input:?}");
+        compared += 1;
+    }
+    assert!(compared > 20);
+}
+
+#[test]
+fn test_anchored_and_sub_span_searches() {
+    let re = longest("a|ab|abc");
+    let input = "xabcab";
+    let find = |input: Input<'_>| re.find(input).map(|m| (m.start(), m.end()));
+    assert_eq!(find(Input::new(input).anchored(true)), None);
+    assert_eq!(find(Input::new(input).span((1..6).into())), Some((1, 4)));
+    assert_eq!(find(Input::new(input).span((1..3).into())), Some((1, 3)));
+    assert_eq!(
+        find(Input::new(input).span((4..6).into()).anchored(true)),
+        Some((4, 6))
+    );
+}
+
+#[test]
+fn test_jit_refuses_leftmost_longest() {
+    let builder = Builder::new("a|ab").match_kind(MatchKind::LeftmostLongest);
+    let err = match builder.pike_jit() {
+        Ok(_) => panic!("the JIT does not implement leftmost-longest"),
+        Err(err) => err,
+    };
+    if cfg!(all(
+        feature = "jit",
+        target_arch = "x86_64",
+        any(unix, windows)
+    )) {
+        let Error::Unsupported(UnsupportedFeature::MatchKind(err)) = err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(err.match_kind, MatchKind::LeftmostLongest);
+        assert!(builder.pike_jit_array().is_err());
+        assert!(builder.pike_jit_cow_array().is_err());
+
+        // Specialized engines report the same match in both modes.
+        let re = Builder::new("(?m)^|$")
+            .match_kind(MatchKind::LeftmostLongest)
+            .pike_jit()
+            .unwrap();
+        assert_eq!(find_all(&re, "a\nb").len(), 4);
+    }
+}
@@ -0,0 +1,97 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::{Input, LengthStats, MatchStats};
+use gregex::{Builder, EngineKind, Regex};
+
+/// The stats of the matches of `find_all`, collected first.
+fn expected(re: &Regex, input: Input<'_>) -> MatchStats {
+    let mut stats = MatchStats::default();
+    let matches: Vec<_> = re.find_all(input).collect();
+    for found in &matches {
+        stats.lengths.add(found.end() - found.start());
+    }
+    for pair in matches.windows(2) {
+        stats.gaps.add(pair[1].start() - pair[0].end());
+    }
+    stats
+}
+
+const CASES: &[(&str, &str)] = &[
+    // Empty matches, which the searches step over like `find_all`.
+    ("a*", "baaacaa"),
+    ("", "héllo"),
+    (r"(?m)$", "two\nlines\n"),
+    ("x?", "日本x語"),
+    (r"(?m)^", "a\nb\n\nc"),
+    // A required byte, for the prefilter.
+    (r"\d+=\d+", "1=2 33=44 x= =5 666=7777"),
+    ("foo|bar", "foobarbaz barfoo"),
+    ("[a-z]{2,}", "a bc def ghij klmnopqrstuvwxyz"),
+];
+
+#[test]
+fn test_stats_match_find_all() {
+    let cases = CASES.iter().chain(utils::corpus::CASES);
+    for (pattern, input) in cases {
+        for engine in EngineKind::ALL {
+            for prefilter in [true, false] {
+                let Ok(re) = Builder::new(*pattern).prefilter(prefilter).build(engine) else {
+                    continue;
+                };
+                let stats = re.match_stats(*input);
+                assert_eq!(
+                    stats,
+                    expected(&re, Input::new(input)),
+                    "{pattern:?} on {input:?} with {}",
+                    engine.name()
+                );
+                assert_eq!(stats.lengths.count as usize, re.find_all(*input).count());
+                let span = (input.len() / 3..input.len()).into();
+                assert_eq!(
+                    re.match_stats(Input::new(input).span(span)),
+                    expected(&re, Input::new(input).span(span)),
+                    "{pattern:?} on {input:?} in {span:?} with {}",
+                    engine.name()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stats() {
+    let re = Regex::pike_vm(r"[a-z]+").unwrap();
+    let stats = re.match_stats("a bb,,ccc   dddddddd");
+    assert_eq!(stats.lengths.count, 4);
+    assert_eq!(stats.lengths.total, 14);
+    assert_eq!((stats.lengths.min, stats.lengths.max), (1, 8));
+    assert_eq!(&stats.lengths.histogram[..5], [0, 1, 2, 0, 1]);
+    assert_eq!(stats.gaps.count, 3);
+    assert_eq!(stats.gaps.total, 6);
+    assert_eq!((stats.gaps.min, stats.gaps.max), (1, 3));
+
+    let stats = re.match_stats("123");
+    assert_eq!(stats, MatchStats::default());
+}
+
+#[test]
+fn test_histogram_buckets() {
+    let buckets: Vec<_> = [
+        0,
+        1,
+        2,
+        3,
+        4,
+        7,
+        8,
+        1 << 13,
+        (1 << 14) - 1,
+        1 << 14,
+        usize::MAX,
+    ]
+    .into_iter()
+    .map(LengthStats::bucket)
+    .collect();
+    assert_eq!(buckets, [0, 1, 2, 2, 3, 3, 4, 14, 14, 15, 15]);
+}
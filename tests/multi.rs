@@ -0,0 +1,154 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::regex::Config;
+use gregex::util::Input;
+use gregex::{Builder, MatchKind, MultiRegex};
+
+/// The spans of the groups of a match.
+type Groups = Vec<Option<(usize, usize)>>;
+
+fn find(re: &MultiRegex, input: &str) -> Option<(usize, Groups)> {
+    re.find(input)
+        .map(|(pattern, caps)| (pattern, utils::group_spans(&caps)))
+}
+
+#[test]
+fn test_leftmost_match_of_any_pattern() {
+    let re = MultiRegex::new(&["b+", "a"]).unwrap();
+    assert_eq!(find(&re, "xxabb"), Some((1, vec![Some((2, 3))])));
+    assert_eq!(find(&re, "xxbba"), Some((0, vec![Some((2, 4))])));
+    assert_eq!(find(&re, "xyz"), None);
+}
+
+#[test]
+fn test_priority_between_patterns() {
+    let re = MultiRegex::new(&["if", "[a-z]+"]).unwrap();
+    assert_eq!(find(&re, "iffy"), Some((0, vec![Some((0, 2))])));
+    let re = MultiRegex::new(&["[a-z]+", "if"]).unwrap();
+    assert_eq!(find(&re, "iffy"), Some((0, vec![Some((0, 4))])));
+    // The longest match wins, then the priority.
+    let config = Config {
+        match_kind: MatchKind::LeftmostLongest,
+        ..Config::default()
+    };
+    let re = MultiRegex::with_config(&["if", "[a-z]+", "i[a-z]+"], config).unwrap();
+    assert_eq!(find(&re, "iffy"), Some((1, vec![Some((0, 4))])));
+    assert_eq!(find(&re, "if"), Some((0, vec![Some((0, 2))])));
+}
+
+#[test]
+fn test_groups_of_each_pattern() {
+    let re = MultiRegex::new(&[r"(a)(b)?", r"(\d)", r"x", r"(c)(d)(e)?"]).unwrap();
+    assert_eq!(re.pattern_len(), 4);
+    let lens: Vec<_> = (0..4).map(|pattern| re.group_len(pattern)).collect();
+    assert_eq!(lens, [3, 2, 1, 4]);
+    assert_eq!(
+        find(&re, "ab"),
+        Some((0, vec![Some((0, 2)), Some((0, 1)), Some((1, 2))]))
+    );
+    assert_eq!(
+        find(&re, "a"),
+        Some((0, vec![Some((0, 1)), Some((0, 1)), None]))
+    );
+    assert_eq!(find(&re, "7"), Some((1, vec![Some((0, 1)), Some((0, 1))])));
+    assert_eq!(find(&re, "x"), Some((2, vec![Some((0, 1))])));
+    assert_eq!(
+        find(&re, "cd"),
+        Some((3, vec![Some((0, 2)), Some((0, 1)), Some((1, 2)), None]))
+    );
+    // Without groups, only the overall match is reported.
+    let config = Config {
+        cg: false,
+        ..Config::default()
+    };
+    let re = MultiRegex::with_config(&[r"(a)(b)?", r"(\d)"], config).unwrap();
+    assert_eq!(re.group_len(0), 1);
+    assert_eq!(find(&re, "7"), Some((1, vec![Some((0, 1))])));
+}
+
+#[test]
+fn test_single_pattern_matches_regex() {
+    for (pattern, input) in utils::corpus::CASES {
+        let Ok(re) = Builder::new(*pattern).pike_vm() else {
+            continue;
+        };
+        let multi = MultiRegex::new(&[pattern]).unwrap();
+        let expected = utils::find_groups(&re, *input).map(|groups| (0, groups));
+        assert_eq!(find(&multi, input), expected, "{pattern:?} on {input:?}");
+    }
+}
+
+#[test]
+fn test_tokenize() {
+    let lexer = MultiRegex::new(&[r"\s+", r"(\d+)(?:\.(\d+))?", r"[a-z]+", r"[-+*/=]"]).unwrap();
+    let tokens: Vec<_> = lexer
+        .tokenize("x = 3.5 * y")
+        .map(|(pattern, caps)| (pattern, utils::group_spans(&caps)))
+        .collect();
+    assert_eq!(
+        tokens,
+        [
+            (2, vec![Some((0, 1))]),
+            (0, vec![Some((1, 2))]),
+            (3, vec![Some((2, 3))]),
+            (0, vec![Some((3, 4))]),
+            (1, vec![Some((4, 7)), Some((4, 5)), Some((6, 7))]),
+            (0, vec![Some((7, 8))]),
+            (3, vec![Some((8, 9))]),
+            (0, vec![Some((9, 10))]),
+            (2, vec![Some((10, 11))]),
+        ]
+    );
+}
+
+#[test]
+fn test_tokenize_stops() {
+    let lexer = MultiRegex::new(&[r"[a-z]+", r" "]).unwrap();
+    // Tokens are anchored, the `b` isn't skipped to.
+    let mut tokens = lexer.tokenize("ab ?cd");
+    assert_eq!(tokens.by_ref().count(), 2);
+    assert_eq!(tokens.offset(), 3);
+    assert!(tokens.next().is_none());
+
+    let mut tokens = lexer.tokenize("ab cd");
+    assert_eq!(tokens.by_ref().count(), 3);
+    assert_eq!(tokens.offset(), 5);
+
+    // An empty token ends the iteration.
+    let lexer = MultiRegex::new(&[r"[a-z]+", r"\d*"]).unwrap();
+    let mut tokens = lexer.tokenize("ab?");
+    let kinds: Vec<_> = tokens.by_ref().map(|(pattern, _)| pattern).collect();
+    assert_eq!(kinds, [0, 1]);
+    assert_eq!(tokens.offset(), 2);
+
+    // In a sub-span of the input.
+    let lexer = MultiRegex::new(&[r"[a-z]", r"\d"]).unwrap();
+    let input = Input::new("ab12cd").span((1..5).into());
+    let kinds: Vec<_> = lexer.tokenize(input).map(|(pattern, _)| pattern).collect();
+    assert_eq!(kinds, [0, 1, 1, 0]);
+}
+
+#[test]
+fn test_no_patterns() {
+    let re = MultiRegex::new(&[]).unwrap();
+    assert_eq!(re.pattern_len(), 0);
+    assert_eq!(find(&re, ""), None);
+    assert_eq!(find(&re, "abc"), None);
+    assert_eq!(re.tokenize("abc").count(), 0);
+}
+
+#[test]
+fn test_invalid_pattern() {
+    assert!(MultiRegex::new(&["a", "(b"]).is_err());
+}
+
+#[test]
+fn test_possessive_patterns() {
+    let config = Config {
+        possessive_quantifiers: true,
+        ..Config::default()
+    };
+    let re = MultiRegex::with_config(&[r"a*+a", r"a+"], config).unwrap();
+    assert_eq!(find(&re, "aaa"), Some((1, vec![Some((0, 3))])));
+}
@@ -0,0 +1,135 @@
+//! Named capture groups: reported by index like the other groups, and by name
+//! through the group info of the regex.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::GroupInfo;
+use gregex::{Builder, MultiRegex, Regex};
+use utils::check_all_engines;
+
+#[test]
+fn test_against_regex_crate() {
+    let patterns = [
+        r"(?P<year>\d{4})-(?P<month>\d{2})",
+        r"(?<user>\w+)@(\w+)\.(?P<tld>com|org)",
+        r"(?P<a>x)|(?P<b>y)",
+        r"(?P<outer>a(?P<inner>b)?)+",
+        r"(?P<empty>)z",
+        r"(?P<é>é+)(?P<_1>\d)?",
+    ];
+    let inputs = [
+        "2024-03-17 and 1999-12",
+        "me@example.com, you@test.org",
+        "yx",
+        "abaab",
+        "zz",
+        "ééé1é",
+        "",
+    ];
+    for pattern in patterns {
+        for input in inputs {
+            check_all_engines(pattern, input);
+        }
+    }
+}
+
+#[test]
+fn test_captures_api() {
+    let re = Regex::pike_vm(r"(?P<year>\d{4})-(\d{2})(?:-(?P<day>\d{2}))?").unwrap();
+    assert_eq!(re.group_info().index("day"), Some(3));
+    assert_eq!(re.group_info().name(1), Some("year"));
+    assert_eq!(re.group_info().name(2), None);
+
+    let caps = re.find_captures("on 2024-03").unwrap();
+    assert_eq!(caps.name("year").unwrap().as_str(), "2024");
+    assert!(caps.name("day").is_none());
+    assert!(caps.name("nope").is_none());
+    let names: Vec<_> = caps.groups_iter().map(|(_, name, _)| name).collect();
+    assert_eq!(names, [None, Some("year"), None, Some("day")]);
+
+    // The groups which are not reported have no match, but keep their name.
+    let caps = re.find_captures_n("on 2024-03-17", 2).unwrap();
+    assert_eq!(caps.name("year").unwrap().as_str(), "2024");
+    assert!(caps.name("day").is_none());
+    assert_eq!(caps.group_names().collect::<Vec<_>>(), [None, Some("year")]);
+
+    let all: Vec<_> = re
+        .find_all_captures("2024-03-17 1999-12")
+        .map(|caps| caps.name("year").unwrap().as_str())
+        .collect();
+    assert_eq!(all, ["2024", "1999"]);
+    let owned = re.find_all_captures_owned("x 2024-03-17").next().unwrap();
+    assert_eq!(owned.name("day").unwrap().as_str(), "17");
+    assert_eq!(owned.as_captures().name("year").unwrap().as_str(), "2024");
+}
+
+#[test]
+fn test_every_engine() {
+    let builder = Builder::new(r"(?P<key>\w+)=(?P<value>\w*)");
+    for engine in gregex::EngineKind::ALL {
+        let Ok(re) = builder.build(engine) else {
+            continue;
+        };
+        let caps = re.find_captures("a= key=value").unwrap();
+        assert_eq!(caps.name("key").unwrap().as_str(), "a", "{}", engine.name());
+        assert_eq!(
+            caps.name("value").unwrap().as_str(),
+            "",
+            "{}",
+            engine.name()
+        );
+    }
+}
+
+#[test]
+fn test_without_groups() {
+    let re = Builder::new(r"(?P<x>a)(?P<y>b)")
+        .cg(false)
+        .pike_vm()
+        .unwrap();
+    assert_eq!(re.group_names().collect::<Vec<_>>(), [None]);
+    let caps = re.find_captures("ab").unwrap();
+    assert!(caps.name("x").is_none());
+}
+
+#[test]
+fn test_possessive() {
+    // The groups marking the possessive repetitions are not reported.
+    let re = Builder::new(r"(?P<x>a)*+(?P<y>b)")
+        .possessive_quantifiers(true)
+        .pike_vm()
+        .unwrap();
+    assert_eq!(
+        re.group_names().collect::<Vec<_>>(),
+        [None, Some("x"), Some("y")]
+    );
+    let caps = re.find_captures("aab").unwrap();
+    assert_eq!(caps.name("y").unwrap().as_str(), "b");
+}
+
+#[test]
+fn test_multi_regex() {
+    let re = MultiRegex::new(&[r"(?P<int>\d+)", r"(?P<word>[a-z]+)(?P<int>\d)?"]).unwrap();
+    assert_eq!(re.group_info(1).index("int"), Some(2));
+    let (pattern, caps) = re.find("  ab7").unwrap();
+    assert_eq!(pattern, 1);
+    assert_eq!(caps.name("word").unwrap().as_str(), "ab");
+    assert_eq!(caps.name("int").unwrap().as_str(), "7");
+    let (_, caps) = re.find("42").unwrap();
+    assert_eq!(caps.name("int").unwrap().as_str(), "42");
+    assert!(caps.name("word").is_none());
+}
+
+#[test]
+fn test_group_info() {
+    let info = GroupInfo::new([None, Some("a".into()), None]);
+    assert_eq!(
+        (info.len(), info.index("a"), info.name(1)),
+        (3, Some(1), Some("a"))
+    );
+    assert_eq!(info.name(3), None);
+    assert_eq!(
+        GroupInfo::unnamed(2).names().collect::<Vec<_>>(),
+        [None, None]
+    );
+}
@@ -0,0 +1,51 @@
+//! The builds without the `jit` feature, e.g. `cargo test --no-default-features
+//! --test no_jit`: the JIT constructors still exist but return an
+//! `EngineUnavailable` error, and the Pike VM does all the searches.
+#![cfg(not(feature = "jit"))]
+
+use gregex::error::UnsupportedFeature;
+use gregex::{Builder, EngineKind, Error, Regex};
+
+#[test]
+fn test_jit_constructors_are_unavailable() {
+    let builder = Builder::new(r"(\w+)-(\d+)");
+    let results = [
+        Regex::pike_jit(r"(\w+)-(\d+)"),
+        builder.pike_jit(),
+        builder.pike_jit_array(),
+        builder.pike_jit_cow_array(),
+        builder.build(EngineKind::PikeJIT),
+        builder.build(EngineKind::PikeJITArray),
+        builder.build(EngineKind::PikeJITCowArray),
+    ];
+    for result in results {
+        assert!(matches!(
+            result.err().unwrap(),
+            Error::Unsupported(UnsupportedFeature::EngineUnavailable(_))
+        ));
+    }
+    let (sensitive, insensitive) = builder.build_case_pair(EngineKind::PikeVM).unwrap();
+    assert!(sensitive.is_match("test-42") && insensitive.is_match("TEST-42"));
+    assert!(builder.build_case_pair(EngineKind::PikeJIT).is_err());
+}
+
+#[test]
+fn test_vm_searches() {
+    let re = Regex::pike_vm(r"(\w+)-(\d+)").unwrap();
+    let caps = re.find_captures("test-42").unwrap();
+    assert_eq!(caps.group0().as_str(), "test-42");
+    assert_eq!(caps.get(1).unwrap().as_str(), "test");
+    assert_eq!(caps.get(2).unwrap().as_str(), "42");
+    assert_eq!(re.find_all("a-1 b-2 c-3").count(), 3);
+    assert_eq!(re.find_longest_prefix("ab-12 c").unwrap().as_str(), "ab-12");
+    // The specialized engines and the leading wildcard don't need the JIT.
+    assert!(Regex::pike_vm("^$").unwrap().is_match(""));
+    let wildcard = Regex::pike_vm(r".*(\d)").unwrap();
+    assert_eq!(wildcard.find("a1b2c").unwrap().as_str(), "a1b2");
+}
+
+#[test]
+fn test_compat_falls_back_to_the_vm() {
+    let re = gregex::compat::Regex::new(r"\d+").unwrap();
+    assert_eq!(re.find("abc123").unwrap().as_str(), "123");
+}
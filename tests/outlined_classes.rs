@@ -0,0 +1,93 @@
+//! The large classes, which the JIT outlines and checks with a helper it
+//! calls (see `MAX_INLINE_CLASS_RANGES`), in patterns with groups. The helper
+//! and the hooks of the CG strategies share the registers of the jitted code
+//! (see `cg_implementation`), so the captures are checked against the Pike VM
+//! on a long haystack, where the threads keep forking and dying around the
+//! calls.
+#![cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+
+use gregex::{Builder, CgStrategy, Regex};
+
+const PATTERNS: &[&str] = &[
+    r"(\w+)\s+(\w+)",
+    r"((?:\p{Greek}|\d)+)-(\w*)",
+    r"(\w)(\w)?(\w)?\W",
+    r"(?:(\p{L}+)|(\d+)|(\s))+x",
+    // The outlined class decides the branch of the fork.
+    r"(\w*)(?:\p{Greek}|-)(\w)",
+    r"([^\s]+?)(\p{Greek}+)",
+    r"(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)(\w)",
+];
+
+const STRATEGIES: [CgStrategy; 3] = [CgStrategy::Tree, CgStrategy::Array, CgStrategy::CowArray];
+
+type Groups = Vec<Option<(usize, usize)>>;
+
+/// Words of every class of the patterns, picked by a xorshift generator.
+fn haystack() -> String {
+    const WORDS: &[&str] = &[
+        "alpha", "βγδ", "42", "x", "-", " ", "ab12", "ω-x", "\t", "日本", "Ωmega", "7-",
+    ];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    (0..4000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            WORDS[(state % WORDS.len() as u64) as usize]
+        })
+        .collect()
+}
+
+fn all_captures(re: &Regex, haystack: &str) -> Vec<Groups> {
+    re.find_all_captures(haystack)
+        .map(|caps| {
+            (0..caps.group_len())
+                .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_same_captures_as_the_vm() {
+    let haystack = haystack();
+    for pattern in PATTERNS {
+        let vm = Builder::new(*pattern).pike_vm().unwrap();
+        let expected = all_captures(&vm, &haystack);
+        assert!(expected.len() > 100, "{pattern:?}");
+        for strategy in STRATEGIES {
+            for outline in [false, true] {
+                let re = Builder::new(*pattern)
+                    .specialize(false)
+                    .force_class_outlining(outline)
+                    .jit_cg_strategy(strategy)
+                    .pike_jit()
+                    .unwrap();
+                assert_eq!(
+                    all_captures(&re, &haystack),
+                    expected,
+                    "{pattern:?} with {strategy:?} (forced outlining: {outline})"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_single_long_match() {
+    // One match over the whole haystack, with a thread per group alive at
+    // every char.
+    let haystack = haystack();
+    let pattern = r"^(?:(\p{L}+)|(\d+)|(\s+)|(\W))*$";
+    let vm = Builder::new(pattern).pike_vm().unwrap();
+    let expected = all_captures(&vm, &haystack);
+    assert_eq!(expected.len(), 1);
+    for strategy in STRATEGIES {
+        let re = Builder::new(pattern)
+            .jit_cg_strategy(strategy)
+            .pike_jit()
+            .unwrap();
+        assert_eq!(all_captures(&re, &haystack), expected, "{strategy:?}");
+    }
+}
@@ -0,0 +1,69 @@
+#[allow(dead_code)]
+mod utils;
+
+use std::sync::Arc;
+
+use gregex::util::{OwnedInput, OwnedMatch};
+use gregex::{Builder, Regex};
+
+const PATTERN: &str = r"(\w+)-(\d+)?";
+
+fn haystack() -> String {
+    "foo-1 bar- baz-42 日本-3 x".to_string()
+}
+
+/// The haystack only lives in this function, but the matches outlive it.
+fn words<'r>(re: &'r Regex) -> impl Iterator<Item = OwnedMatch> + 'r {
+    let haystack = haystack();
+    re.find_all_owned(haystack)
+}
+
+#[test]
+fn test_owned_iterator_outlives_haystack() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let owned: Vec<_> = words(&re).collect();
+        let haystack = haystack();
+        let borrowed: Vec<_> = re.find_all(haystack.as_str()).collect();
+        assert_eq!(owned.len(), borrowed.len());
+        for (o, b) in owned.iter().zip(&borrowed) {
+            assert_eq!((o.start(), o.end()), (b.start(), b.end()));
+            assert_eq!(o.as_str(), b.as_str());
+        }
+    }
+}
+
+#[test]
+fn test_owned_captures() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let owned: Vec<_> = {
+            let haystack = haystack();
+            re.find_all_captures_owned(haystack).collect()
+        };
+        let haystack = haystack();
+        let borrowed: Vec<_> = re.find_all_captures(haystack.as_str()).collect();
+        assert_eq!(owned.len(), borrowed.len());
+        for (o, b) in owned.iter().zip(&borrowed) {
+            assert_eq!(o.group_len(), b.group_len());
+            for i in 0..o.group_len() {
+                assert_eq!(
+                    o.get(i).map(|m| m.as_str().to_string()),
+                    b.get(i).map(|m| m.as_str().to_string())
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_owned_matches_share_the_subject() {
+    let re = Builder::new(r"\d+").pike_vm().unwrap();
+    let subject: Arc<str> = Arc::from("1 22 333");
+    let input = OwnedInput::from(subject.clone()).span((2..8).into());
+    let matches: Vec<_> = re.find_all_owned(input).collect();
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].as_str(), "22");
+    assert_eq!(matches[1].as_str(), "333");
+    for m in &matches {
+        assert!(Arc::ptr_eq(&m.subject, &subject));
+    }
+}
@@ -0,0 +1,119 @@
+//! A callback which panics during a search leaves the regex usable: the
+//! following searches, with the same regex and its pooled states, report the
+//! right matches.
+#[allow(dead_code)]
+mod utils;
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use gregex::Regex;
+use gregex::thompson::pike_vm::GroupEvent;
+
+const PATTERN: &str = r"(\w)(\d+)";
+const HAYSTACK: &str = "a1 b22 c333 d4444 e5";
+
+/// Checks every kind of search of the regex after a panic.
+fn check_usable(re: &Regex, expected: &[(usize, usize)]) {
+    let mut found = Vec::new();
+    re.for_each_match(HAYSTACK, |m| found.push((m.start(), m.end())));
+    assert_eq!(found, expected);
+    assert_eq!(utils::match_spans(re.find_all(HAYSTACK)), expected);
+    let caps = re.find_captures("xx c333").unwrap();
+    assert_eq!(caps.get(2).unwrap().as_str(), "333");
+    assert!(!re.is_match("no digits"));
+}
+
+#[test]
+fn test_panic_in_for_each_match() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let expected = utils::match_spans(re.find_all(HAYSTACK));
+        assert_eq!(expected.len(), 5);
+        let mut seen = 0;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            re.for_each_match(HAYSTACK, |_| {
+                seen += 1;
+                if seen == 3 {
+                    panic!("third match");
+                }
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(seen, 3);
+        check_usable(&re, &expected);
+    }
+}
+
+#[test]
+fn test_panic_after_nested_search() {
+    // The callback searches with the same regex, which checks out a second
+    // state, then panics while both are in use.
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let expected = utils::match_spans(re.find_all(HAYSTACK));
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            re.for_each_match(HAYSTACK, |m| {
+                let inner = re.find_captures(m.as_str()).unwrap();
+                if inner.get(2).unwrap().as_str() == "333" {
+                    panic!("third match");
+                }
+            })
+        }));
+        assert!(result.is_err());
+        check_usable(&re, &expected);
+        // Both states were dropped, new ones are built as needed.
+        check_usable(&re, &expected);
+    }
+}
+
+#[test]
+fn test_panic_in_observer() {
+    // The Pike VM reports the events in the middle of its execution.
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let expected = utils::match_spans(re.find_all(HAYSTACK));
+        let mut events = 0;
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            re.find_captures_streaming(HAYSTACK, |_: GroupEvent| {
+                events += 1;
+                if events == 3 {
+                    panic!("third event");
+                }
+            })
+        }));
+        assert!(result.is_err());
+        check_usable(&re, &expected);
+        let caps = re.find_captures_streaming("d4444", |_| ()).unwrap();
+        assert_eq!(caps.get(1).unwrap().as_str(), "d");
+    }
+}
+
+#[test]
+fn test_panic_in_replacer() {
+    let re = gregex::compat::Regex::new(PATTERN).unwrap();
+    let mut calls = 0;
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        re.replace_all(HAYSTACK, |caps: &gregex::compat::Captures<'_>| {
+            calls += 1;
+            if calls == 3 {
+                panic!("third match");
+            }
+            caps[2].to_string()
+        })
+    }));
+    assert!(result.is_err());
+    let replaced = re.replace_all(HAYSTACK, |caps: &gregex::compat::Captures<'_>| {
+        caps[2].to_string()
+    });
+    assert_eq!(replaced, "1 22 333 4444 5");
+}
+
+#[test]
+fn test_panic_in_another_thread() {
+    // A panic on another thread, while it holds a state of the regex, doesn't
+    // affect the searches of this one.
+    let re = Regex::pike_vm(PATTERN).unwrap();
+    let expected = utils::match_spans(re.find_all(HAYSTACK));
+    std::thread::scope(|scope| {
+        let handle = scope.spawn(|| re.for_each_match(HAYSTACK, |_| panic!("first match")));
+        assert!(handle.join().is_err());
+    });
+    check_usable(&re, &expected);
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "rayon")]
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use gregex::parallel::ParallelOptions;
+
+/// A xorshift generator, to build haystacks.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+fn spans<'s>(matches: impl IntoIterator<Item = gregex::util::Match<'s>>) -> Vec<(usize, usize)> {
+    matches
+        .into_iter()
+        .map(|m| (m.span.from, m.span.to))
+        .collect()
+}
+
+fn check(re: &Regex, pattern: &str, haystack: &str) {
+    let expected = spans(re.find_all(haystack));
+    for chunk_size in [1, 2, 3, 5, 8, 13, 64] {
+        for threads in [2, 3] {
+            let opts = ParallelOptions::new()
+                .chunk_size(chunk_size)
+                .threads(threads);
+            assert_eq!(
+                spans(re.find_all_parallel(haystack, opts)),
+                expected,
+                "Mismatch for pattern {pattern:?} on {haystack:?} with chunks of {chunk_size}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_find_all_parallel_corpus() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        for re in &engines {
+            check(re, pattern, haystack);
+        }
+    }
+}
+
+#[test]
+fn test_find_all_parallel_generated() {
+    const PATTERNS: &[&str] = &[
+        "ab",
+        "a|ab|b",
+        "(?:ab){1,3}",
+        "a?",
+        "b?a?",
+        "(?m)^ab$",
+        "(?m)^a|b$",
+        "é[aé]?",
+        "[ab]{2,4}?c",
+        "",
+    ];
+    const ALPHABET: &[char] = &['a', 'b', 'c', ' ', '\n', 'é'];
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+    for pattern in PATTERNS {
+        let Some(engines) = utils::compile_all(pattern) else {
+            panic!("Failed to compile {pattern:?}");
+        };
+        for _ in 0..50 {
+            let len = rng.next() % 40;
+            let haystack = (0..len)
+                .map(|_| ALPHABET[(rng.next() % ALPHABET.len() as u64) as usize])
+                .collect::<String>();
+            for re in &engines {
+                check(re, pattern, &haystack);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_find_all_parallel_unbounded_falls_back() {
+    let re = Regex::pike_vm("a+").unwrap();
+    assert_eq!(re.maximum_len(), None);
+    let haystack = "aaa b aaaa".repeat(10);
+    check(&re, "a+", &haystack);
+}
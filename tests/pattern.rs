@@ -0,0 +1,195 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::pattern::{Class, Fragment};
+use gregex::{Builder, EngineKind};
+
+/// Checks that the fragment matches like the pattern, on every available
+/// engine, and like rust-regex.
+fn check_same_as(fragment: &Fragment, pattern: &str, inputs: &[&str]) {
+    for engine in EngineKind::ALL {
+        let Ok(expected) = Builder::new(pattern).build(engine) else {
+            continue;
+        };
+        let re = fragment.build(engine, |builder| builder).unwrap();
+        for input in inputs {
+            assert_eq!(
+                utils::find_groups(&re, *input),
+                utils::find_groups(&expected, *input),
+                "{pattern:?} ({}) on {input:?}",
+                engine.name()
+            );
+        }
+    }
+    for input in inputs {
+        utils::check_all_engines(&fragment.to_string(), input);
+    }
+}
+
+fn digits() -> Fragment {
+    Fragment::class(Class::DIGIT).repeat(1, None, true)
+}
+
+#[test]
+fn test_semver() {
+    let number = digits().group(true);
+    let identifier = Fragment::class(
+        Class::range('0', '9')
+            .union(Class::range('A', 'Z'))
+            .union(Class::range('a', 'z'))
+            .union(Class::any_of(".-")),
+    )
+    .repeat(1, None, true);
+    let semver = Fragment::seq([
+        number.clone(),
+        Fragment::literal("."),
+        number.clone(),
+        Fragment::literal("."),
+        number,
+        Fragment::seq([Fragment::literal("-"), identifier.group(true)]).repeat(0, Some(1), true),
+    ]);
+    check_same_as(
+        &semver,
+        r"(\d+)\.(\d+)\.(\d+)(?:-([0-9A-Za-z.-]+))?",
+        &[
+            "1.2.3",
+            "v10.20.30-rc.1",
+            "1.2",
+            "1x2x3",
+            "release 0.1.0-alpha-2 and 2.0.0",
+            "١.٢.٣",
+        ],
+    );
+}
+
+#[test]
+fn test_email() {
+    let word = Class::WORD.union(Class::any_of("-"));
+    let local = Fragment::class(Class::WORD.union(Class::any_of(".+-"))).repeat(1, None, true);
+    let label = Fragment::class(word).repeat(1, None, true);
+    let domain = Fragment::seq([
+        label.clone(),
+        Fragment::seq([Fragment::literal("."), label]).repeat(1, None, true),
+    ]);
+    let email = Fragment::seq([
+        local.group(true),
+        Fragment::literal("@"),
+        domain.group(true),
+    ]);
+    check_same_as(
+        &email,
+        r"([\w.+-]+)@([\w-]+(?:\.[\w-]+)+)",
+        &[
+            "jane.doe+tag@example.com",
+            "write to a@b.c.d, or not",
+            "no-at-sign.example.com",
+            "root@localhost",
+            "élodie@exemple.fr",
+        ],
+    );
+}
+
+#[test]
+fn test_literals_are_not_escaped() {
+    let fragment = Fragment::literal("a.b*(c)?");
+    check_same_as(&fragment, r"a\.b\*\(c\)\?", &["a.b*(c)?", "axbbc", "ab"]);
+    let fragment = Fragment::alt([Fragment::literal("[x]"), Fragment::literal("$")]);
+    check_same_as(&fragment, r"\[x\]|\$", &["a$", "[x]", "x"]);
+}
+
+#[test]
+fn test_alternation_and_repetition() {
+    let fragment = Fragment::seq([
+        Fragment::alt([Fragment::literal("ab"), Fragment::literal("a")]).group(true),
+        Fragment::literal("b").repeat(0, None, false).group(true),
+        Fragment::literal("c").repeat(2, Some(3), true).group(false),
+    ]);
+    check_same_as(
+        &fragment,
+        r"(ab|a)(b*?)(?:c{2,3})",
+        &["abcc", "abbccc", "acccc", "ac"],
+    );
+    // The alternation of no fragment never matches, and the sequence of none
+    // matches the empty string.
+    let re = Fragment::alt([]).builder().pike_vm().unwrap();
+    assert!(!re.is_match("abc"));
+    let re = Fragment::seq([]).builder().pike_vm().unwrap();
+    assert_eq!(re.find("abc").map(|m| m.as_str()), Some(""));
+}
+
+#[test]
+fn test_group_numbering() {
+    let inner = Fragment::literal("b").group(true);
+    let fragment = Fragment::seq([
+        Fragment::seq([Fragment::literal("a"), inner.clone()]).group(true),
+        inner.group(false),
+        Fragment::literal("c").group(true),
+    ]);
+    let re = fragment.builder().pike_vm().unwrap();
+    assert_eq!(
+        utils::find_groups(&re, "xabbc"),
+        Some(vec![
+            Some((1, 5)),
+            Some((1, 3)),
+            Some((2, 3)),
+            Some((3, 4)),
+            Some((4, 5))
+        ])
+    );
+    check_same_as(&fragment, r"(a(b))(b)(c)", &["xabbc"]);
+}
+
+#[test]
+fn test_classes() {
+    let space = Fragment::class(Class::SPACE);
+    check_same_as(&space, r"\s", &["a\u{2003}b", "a\tb", "ab"]);
+    let not_hex = Fragment::class(
+        Class::range('0', '9')
+            .union(Class::range('a', 'f'))
+            .negate(),
+    );
+    check_same_as(&not_hex, r"[^0-9a-f]", &["09af", "0x1f", "é"]);
+    assert_eq!(Class::WORD.negate().negate(), Class::WORD);
+    // An inverted range is empty.
+    let re = Fragment::class(Class::range('z', 'a'))
+        .builder()
+        .pike_vm()
+        .unwrap();
+    assert!(!re.is_match("abz"));
+
+    let horizontal = Fragment::class(Class::HORIZONTAL_SPACE).repeat(1, None, true);
+    let re = horizontal.builder().pike_vm().unwrap();
+    assert_eq!(
+        re.find("a \t\u{a0}\u{3000}\nb").map(|m| m.as_str()),
+        Some(" \t\u{a0}\u{3000}")
+    );
+    let line_terminator = Fragment::class(Class::LINE_TERMINATOR);
+    let re = line_terminator.builder().pike_vm().unwrap();
+    let ends: Vec<_> = re
+        .find_all("a\nb\r\x0b\x0c\u{85}\u{2028}\u{2029} \t")
+        .map(|m| m.as_str())
+        .collect();
+    assert_eq!(
+        ends,
+        ["\n", "\r", "\x0b", "\x0c", "\u{85}", "\u{2028}", "\u{2029}"]
+    );
+}
+
+#[test]
+fn test_builder_flags() {
+    let fragment = Fragment::seq([Fragment::literal("key"), Fragment::literal("=").group(true)]);
+    let re = fragment
+        .build(EngineKind::PikeVM, |builder| {
+            builder.case_insensitive(true).cg(false)
+        })
+        .unwrap();
+    assert_eq!(utils::find_groups(&re, "KEY="), Some(vec![Some((0, 4))]));
+    assert_eq!(re.pattern(), fragment.to_string());
+}
+
+#[test]
+fn test_to_hir() {
+    let fragment = Fragment::seq([digits().group(true), Fragment::literal("-")]);
+    let parsed = regex_syntax::parse(r"(\d+)-").unwrap();
+    assert_eq!(fragment.to_hir(), parsed);
+}
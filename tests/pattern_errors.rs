@@ -0,0 +1,151 @@
+//! The errors caused by the pattern point at the part of it at fault, see
+//! `PatternError`.
+use gregex::error::UnsupportedFeature;
+use gregex::regex::{Config, PatternError};
+use gregex::thompson::bytecode::CompileError;
+use gregex::{Builder, Error};
+
+fn pattern_error(builder: &Builder) -> PatternError {
+    match builder.pike_vm().err().expect("the pattern is rejected") {
+        Error::Syntax(err) => err,
+        err => panic!("unexpected error {err:?}"),
+    }
+}
+
+fn at_fault(err: &PatternError) -> &str {
+    &err.pattern().unwrap()[err.span().unwrap()]
+}
+
+#[test]
+fn test_non_unicode_property() {
+    let err = pattern_error(&Builder::new(r"(a)\p{Greek}").unicode(false));
+    assert_eq!(err.span(), Some(3..12));
+    assert_eq!(err.pattern(), Some(r"(a)\p{Greek}"));
+    assert_eq!(
+        err.inner().downcast_ref::<CompileError>(),
+        Some(&CompileError::UnsupportedInNonUnicodeMode {
+            property: r"\p{Greek}".to_string()
+        })
+    );
+    assert_eq!(
+        err.to_string(),
+        "regex parse error:\n    (a)\\p{Greek}\n       ^^^^^^^^^\n\
+         error: Property class \\p{Greek} can't be used when unicode mode is disabled"
+    );
+
+    // The leftmost one, even nested.
+    let builder = Builder::new(r"((a)|\P{Latin})*\p{Greek}").unicode(false);
+    assert_eq!(at_fault(&pattern_error(&builder)), r"\P{Latin}");
+}
+
+#[test]
+fn test_look_around() {
+    let err = pattern_error(&Builder::new("a(?=b)c"));
+    assert_eq!(err.span(), Some(1..4));
+    assert_eq!(at_fault(&err), "(?=");
+    let source = std::error::Error::source(&err).unwrap();
+    assert!(source.downcast_ref::<regex_syntax::Error>().is_some());
+    let rendered = err.to_string();
+    assert!(rendered.starts_with("regex parse error:\n    a(?=b)c\n     ^^^\nerror: "));
+    // The message of regex-syntax, without its own snippet.
+    assert_eq!(rendered.matches("a(?=b)c").count(), 1);
+    assert!(rendered.contains("look-around"));
+}
+
+#[test]
+fn test_syntax_errors() {
+    for (pattern, fault) in [
+        ("a(b", "("),
+        ("x{2,1}", "{2,1}"),
+        (r"\p{Nope}", r"\p{Nope}"),
+    ] {
+        let err = pattern_error(&Builder::new(pattern));
+        assert_eq!(at_fault(&err), fault, "{pattern:?}");
+    }
+}
+
+#[test]
+fn test_non_ascii_and_multiline() {
+    // The carets are counted in chars.
+    let err = pattern_error(&Builder::new(r"é\p{Greek}").unicode(false));
+    assert_eq!(err.span(), Some(2..11));
+    assert!(
+        err.to_string()
+            .contains("\n    é\\p{Greek}\n     ^^^^^^^^^\n")
+    );
+
+    // Only the line of the start of the span is shown, with its number.
+    let err = pattern_error(&Builder::new("(?x)\na +\n\\p{Greek}\n|c").unicode(false));
+    assert_eq!(
+        err.to_string(),
+        "regex parse error:\n    3: \\p{Greek}\n       ^^^^^^^^^\n\
+         error: Property class \\p{Greek} can't be used when unicode mode is disabled"
+    );
+}
+
+#[test]
+fn test_through_every_builder() {
+    let builder = Builder::new(r"a\p{Greek}").unicode(false);
+    for engine in gregex::EngineKind::ALL {
+        match builder.build(engine) {
+            Err(Error::Syntax(err)) => assert_eq!(err.span(), Some(1..10)),
+            Err(Error::Unsupported(UnsupportedFeature::EngineUnavailable(_))) => {}
+            result => panic!("unexpected result {:?}", result.err()),
+        }
+    }
+    let config = Config {
+        unicode: false,
+        ..Config::default()
+    };
+    let err = gregex::MultiRegex::with_config(&["a", r"b\p{Greek}"], config)
+        .err()
+        .unwrap();
+    assert!(matches!(err, Error::Syntax(err) if err.span() == Some(1..10)));
+    let err = gregex::compat::Regex::new("a(?=b)").unwrap_err();
+    assert!(err.to_string().contains("     ^^^\n"));
+}
+
+#[test]
+fn test_word_start_and_end() {
+    // Every assertion of regex-syntax is supported.
+    for pattern in [r"\<a\>", r"\b{start}foo", r"a\b{end-half}", r"(?-u:\>)"] {
+        for engine in gregex::EngineKind::ALL {
+            match Builder::new(pattern).build(engine) {
+                Ok(_) => {}
+                Err(Error::Unsupported(UnsupportedFeature::EngineUnavailable(_))) => {}
+                Err(err) => panic!("{pattern:?} with {}: {err}", engine.name()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_error_kinds() {
+    let err = Builder::new("a(").pike_vm().err().unwrap();
+    assert!(matches!(err, Error::Syntax(_)));
+    let err = Builder::new("a")
+        .force_class_outlining(true)
+        .force_class_inlining(true)
+        .pike_vm()
+        .err()
+        .unwrap();
+    assert!(matches!(
+        err,
+        Error::Unsupported(UnsupportedFeature::Pattern(
+            CompileError::ConflictingClassCompilation
+        ))
+    ));
+    assert_eq!(
+        err.to_string(),
+        CompileError::ConflictingClassCompilation.to_string()
+    );
+    let err = Builder::new("(?:a{1000}){1000}").pike_vm().err().unwrap();
+    assert!(matches!(
+        err,
+        Error::CompileLimitExceeded(CompileError::RepetitionTooLarge { .. })
+    ));
+    // The errors of the pattern keep their source.
+    let err = Builder::new("a(?=b)").pike_vm().err().unwrap();
+    let source = std::error::Error::source(&err).unwrap();
+    assert!(source.downcast_ref::<regex_syntax::Error>().is_some());
+}
@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use gregex::thompson::bytecode::{Compiler, Instruction, encoding};
+use gregex::thompson::pike_vm::GroupEvent;
+use gregex::{Builder, MatchKind, Regex};
+
+fn possessive(pattern: &str) -> Regex {
+    Builder::new(pattern)
+        .possessive_quantifiers(true)
+        .pike_vm()
+        .unwrap()
+}
+
+fn find(re: &Regex, input: &str) -> Option<(usize, usize)> {
+    re.find(input).map(|m| (m.start(), m.end()))
+}
+
+fn groups(re: &Regex, input: &str) -> Vec<Option<(usize, usize)>> {
+    let caps = re.find_captures(input).unwrap();
+    (0..caps.group_len())
+        .map(|i| caps.get(i).map(|g| (g.start(), g.end())))
+        .collect()
+}
+
+/// The number of threads the search of `find_captures` created, and the
+/// largest number of them alive at once.
+fn thread_counts(re: &Regex, input: &str) -> (usize, usize) {
+    let mut seen = HashSet::new();
+    let (mut live, mut peak) = (0, 0);
+    re.find_captures_streaming(input, |event| match event {
+        GroupEvent::Open { candidate, .. } | GroupEvent::Close { candidate, .. } => {
+            if seen.insert(candidate) {
+                live += 1;
+                peak = usize::max(peak, live);
+            }
+        }
+        GroupEvent::Commit { .. } | GroupEvent::Abort { .. } => live -= 1,
+    });
+    (seen.len(), peak)
+}
+
+#[test]
+fn test_possessive_never_gives_back() {
+    let re = possessive("a*+a");
+    assert_eq!(find(&re, "aaa"), None);
+    assert_eq!(find(&possessive("a*a"), "aaa"), Some((0, 3)));
+
+    assert_eq!(find(&possessive("a++b"), "xaaab"), Some((1, 5)));
+    assert_eq!(find(&possessive("a?+a"), "a"), None);
+    assert_eq!(find(&possessive("a?+a"), "aa"), Some((0, 2)));
+    assert_eq!(find(&possessive("a{1,3}+a"), "aaa"), None);
+    assert_eq!(find(&possessive("a{1,3}+a"), "aaaa"), Some((0, 4)));
+    // The possessive repetition must still match, even if later parts fail
+    // at the first way it is matched.
+    assert_eq!(find(&possessive("(?:a|ab)++c"), "abc"), None);
+    assert_eq!(find(&possessive("(?:ab|a)++c"), "abc"), Some((0, 3)));
+}
+
+#[test]
+fn test_disabled_by_default() {
+    // `a*+` is `(?:a*)+`, like in the regex crate.
+    let re = Regex::pike_vm("a*+a").unwrap();
+    assert_eq!(find(&re, "aaa"), Some((0, 3)));
+    let re = Builder::new("a*+a")
+        .possessive_quantifiers(false)
+        .pike_vm()
+        .unwrap();
+    assert_eq!(find(&re, "aaa"), Some((0, 3)));
+}
+
+#[test]
+fn test_lazy_and_nested_repetitions() {
+    // A lazy repetition can't be possessive: `+?` is a lazy `+`.
+    assert_eq!(find(&possessive("a+?"), "aaa"), Some((0, 1)));
+    // The possessive repetition of a lazy one takes the first way it
+    // matches, i.e. the shortest one, so the `b` can't follow an `a`.
+    assert_eq!(find(&possessive("(?:a*?)++b"), "aab"), Some((2, 3)));
+    assert_eq!(find(&possessive("(?:a++b)+c"), "aabab abc"), Some((6, 9)));
+    assert_eq!(
+        find(&possessive("(?:\\w++\\s)++x"), "ab cd x"),
+        Some((0, 7))
+    );
+}
+
+#[test]
+fn test_capture_groups() {
+    // The groups inside a possessive repetition are still reported, and the
+    // groups are counted as without it.
+    let re = possessive("(a+)++(b)?");
+    assert_eq!(re.group_len(), 3);
+    assert_eq!(
+        groups(&re, "aab"),
+        vec![Some((0, 3)), Some((0, 2)), Some((2, 3))]
+    );
+    assert_eq!(
+        groups(&possessive("((b)*+|)(b)"), "b"),
+        vec![Some((0, 1)), Some((0, 0)), None, Some((0, 1))]
+    );
+    assert_eq!(
+        groups(&possessive("(((.){0,2}+)c)"), "bcac"),
+        vec![Some((1, 4)), Some((1, 4)), Some((1, 3)), Some((2, 3))]
+    );
+}
+
+#[test]
+fn test_leftmost_longest() {
+    let re = Builder::new("a*+a|a+b?")
+        .possessive_quantifiers(true)
+        .match_kind(MatchKind::LeftmostLongest)
+        .pike_vm()
+        .unwrap();
+    assert_eq!(find(&re, "aaab"), Some((0, 4)));
+    // The possessive repetition still matches the first way by priority,
+    // not the longest one.
+    for (possessive, expected) in [(true, (0, 2)), (false, (0, 5))] {
+        let re = Builder::new("((?:a|ab)*+)b")
+            .possessive_quantifiers(possessive)
+            .match_kind(MatchKind::LeftmostLongest)
+            .pike_vm()
+            .unwrap();
+        assert_eq!(groups(&re, "ababb")[0], Some(expected));
+    }
+}
+
+#[test]
+fn test_regressions() {
+    // Found by comparing with Python's re module, minimized.
+    for (pattern, input, expected) in [
+        (".*(?:.*c)?+b", "b", Some((0, 1))),
+        (".*(?:.*c)?+b", "acb", Some((0, 3))),
+        ("(?:..+)*+b", "bcb", Some((2, 3))),
+        ("(?:c*+a*+)?c*+a", "ca", Some((0, 2))),
+        ("(?:.+a*+.){2}", "aacc", None),
+        ("a?(?:.{0,2}+a)", "aab", None),
+        ("(?:(?:a|bc)++.)++", "acabc", Some((0, 2))),
+        ("(?:2|(.})?+)+", "c", Some((0, 0))),
+        ("c(((b)*+)+)", "cbb", Some((0, 3))),
+        ("(?:c|a.)?+a+", "aa", Some((1, 2))),
+        ("(?:b.*b)?+b", "bbb", Some((2, 3))),
+        ("(?:b+a)?+a", "ba", Some((1, 2))),
+        ("(?:(?:ba)*+b){0,2}a", "bba", Some((2, 3))),
+    ] {
+        let re = possessive(pattern);
+        assert_eq!(find(&re, input), expected, "{pattern:?} on {input:?}");
+        let caps = re
+            .find_captures(input)
+            .map(|c| (c.group0().start(), c.group0().end()));
+        assert_eq!(caps, expected, "{pattern:?} on {input:?}");
+    }
+}
+
+#[test]
+fn test_pruning_reduces_threads() {
+    // Once `a*+` left its loop, the threads which left it earlier are killed,
+    // where the greedy repetition keeps one of them at each of the 20 `a`.
+    let input = "a".repeat(1000);
+    let (greedy_total, greedy_peak) = thread_counts(&possessive("a*a{20}b"), &input);
+    let (total, peak) = thread_counts(&possessive("a*+a{20}b"), &input);
+    assert!(greedy_peak > 20, "{greedy_peak}");
+    assert!(peak < 5, "{peak}");
+    assert!(total <= greedy_total);
+}
+
+#[test]
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+fn test_jit_rejects_possessive_repetitions() {
+    use gregex::thompson::pike_jit::CompileError;
+
+    let err = match Builder::new("a*+a").possessive_quantifiers(true).pike_jit() {
+        Ok(_) => panic!("the JIT does not implement cut regions"),
+        Err(err) => err,
+    };
+    assert!(matches!(
+        err,
+        gregex::Error::Jit(CompileError::ContainsPossessiveRepetition)
+    ));
+    // The same pattern, as a repetition of a repetition.
+    assert!(Builder::new("a*+a").pike_jit().is_ok());
+}
+
+#[test]
+fn test_cut_instructions_round_trip() {
+    let builder = Builder::new("(?:a|ab)*+c").possessive_quantifiers(true);
+    let hir = Compiler::parse(builder.pattern(), builder.config()).unwrap();
+    let bytecode = Compiler::compile(hir, builder.config().clone()).unwrap();
+    let cuts = bytecode
+        .instructions
+        .iter()
+        .filter(|i| matches!(i, Instruction::CutBegin(0) | Instruction::CutEnd(0)))
+        .count();
+    assert_eq!(cuts, 2);
+    let words = encoding::encode(&bytecode);
+    assert_eq!(encoding::decode(&words).as_ref(), Ok(&bytecode));
+}
@@ -0,0 +1,145 @@
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::Input;
+use gregex::{Builder, EngineKind, Regex};
+
+fn required(pattern: &str) -> Option<Vec<u8>> {
+    let re = Builder::new(pattern).pike_vm().unwrap();
+    re.required_bytes().map(<[u8]>::to_vec)
+}
+
+/// Every engine available for the pattern, with the prefilter on and off.
+fn engines(pattern: &str) -> Vec<(EngineKind, Regex, Regex)> {
+    EngineKind::ALL
+        .into_iter()
+        .filter_map(|kind| {
+            let on = Builder::new(pattern).build(kind).ok()?;
+            let off = Builder::new(pattern).prefilter(false).build(kind).unwrap();
+            Some((kind, on, off))
+        })
+        .collect()
+}
+
+/// What is_match, find and find_captures report.
+type Found = (
+    bool,
+    Option<(usize, usize)>,
+    Option<Vec<Option<(usize, usize)>>>,
+);
+
+fn found(re: &Regex, input: Input<'_>) -> Found {
+    let is_match = re.is_match(input.clone());
+    let find = re.find(input.clone()).map(|m| (m.start(), m.end()));
+    let captures = re.find_captures(input).map(|caps| {
+        (0..caps.group_len())
+            .map(|i| caps.get(i).map(|g| (g.start(), g.end())))
+            .collect()
+    });
+    (is_match, find, captures)
+}
+
+#[test]
+fn test_required_bytes() {
+    assert_eq!(required(r"\d+=\d+"), Some(b"=".to_vec()));
+    assert_eq!(required("foo|bar"), Some(b"bf".to_vec()));
+    assert_eq!(required("(?i)x"), Some(b"Xx".to_vec()));
+    assert_eq!(required("[a-c]+"), Some(b"abc".to_vec()));
+    assert_eq!(required("(ab|cd)?e{2,}"), Some(b"e".to_vec()));
+    // The first byte of the encoding of every char.
+    assert_eq!(required("[éè]"), Some(vec![0xC3]));
+    assert_eq!(required("(x|日)"), Some(vec![b'x', 0xE6]));
+
+    assert_eq!(required("foo|bar|baz|qux"), Some(b"bfq".to_vec()));
+
+    // No known set of at most three bytes.
+    for pattern in [
+        r"\w+",
+        "a?",
+        "a*b?",
+        "foo|bar|qux|zap",
+        "[a-d]",
+        "",
+        "(?m)^$",
+        "a|",
+    ] {
+        assert_eq!(required(pattern), None, "{pattern:?}");
+    }
+
+    let re = Builder::new(r"\d+=\d+").prefilter(false).pike_vm().unwrap();
+    assert_eq!(re.required_bytes(), None);
+    assert!(re.is_match("1=2"));
+}
+
+#[test]
+fn test_rejects_without_running_the_engine() {
+    let haystack = "1234 5678 ".repeat(1000);
+    for (kind, on, off) in engines(r"\d+=\d+") {
+        assert!(!on.is_match(haystack.as_str()), "{}", kind.name());
+        assert!(on.find(haystack.as_str()).is_none());
+        assert!(on.find_captures(haystack.as_str()).is_none());
+        assert!(!off.is_match(haystack.as_str()));
+
+        let found = format!("{haystack}12=34");
+        let m = on.find(found.as_str()).unwrap();
+        assert_eq!(m.as_str(), "12=34", "{}", kind.name());
+    }
+}
+
+#[test]
+fn test_restricted_start() {
+    // The bounded length lets the search start shortly before the first '='.
+    let haystack = format!("{}1=2", "ab".repeat(100));
+    for (kind, on, _) in engines("[0-9]=[0-9]") {
+        let m = on.find(haystack.as_str()).unwrap();
+        assert_eq!((m.start(), m.end()), (200, 203), "{}", kind.name());
+        // Anchored searches keep their start, and so fail.
+        assert!(
+            on.find(Input::new(haystack.as_str()).anchored(true))
+                .is_none()
+        );
+    }
+    // Look-behind context before the restricted start is still seen.
+    for (kind, on, _) in engines(r"(?m)^=|a=") {
+        assert!(!on.is_match("b="), "{}", kind.name());
+        assert_eq!(on.find("xy\n=").unwrap().start(), 3, "{}", kind.name());
+    }
+    // The start is moved back to a char boundary.
+    for (kind, on, _) in engines("é=") {
+        assert_eq!(on.find("aé=").unwrap().start(), 1, "{}", kind.name());
+    }
+}
+
+#[test]
+fn test_corpus_with_and_without_prefilter() {
+    let mut extra = vec![
+        (r"\d+=\d+", "12 = 34, 56=78"),
+        ("foo|bar", "fobabar"),
+        ("(a|b)c{1,3}", "ccbccc"),
+        ("(?i)héllo", "HÉLLO hello"),
+        ("[0-9]=[0-9]", "x=1=2"),
+    ];
+    extra.extend_from_slice(utils::corpus::CASES);
+    for (pattern, input) in extra {
+        for (kind, on, off) in engines(pattern) {
+            // Spans starting and ending at every position, to exercise the
+            // restriction of the start.
+            let spans = (0..=input.len())
+                .filter(|i| input.is_char_boundary(*i))
+                .flat_map(|i| [i..input.len(), 0..i]);
+            for span in spans {
+                for anchored in [false, true] {
+                    let input = Input::new(input)
+                        .span(span.clone().into())
+                        .anchored(anchored);
+                    assert_eq!(
+                        found(&on, input.clone()),
+                        found(&off, input),
+                        "{} on {pattern:?} at {span:?}, anchored: {anchored}",
+                        kind.name(),
+                    );
+                }
+            }
+        }
+    }
+}
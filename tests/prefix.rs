@@ -0,0 +1,133 @@
+//! The patterns whose matches start with one of a few literals, whose
+//! searches first look for them, see `Regex::prefix_literals`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::util::{Input, Span};
+use gregex::{Builder, EngineKind, Regex};
+
+const CASES: &[(&str, &str)] = &[
+    (r"Sherlock [A-Z]\w+", "Sherlock said to Sherlock Holmes"),
+    (
+        "Sherlock Holmes|John Watson|Irene Adler|Inspector Lestrade|Professor Moriarty",
+        "Professor Moriarty met John Watson, then Sherlock Holmes",
+    ),
+    ("(abc|ab)(c|d)", "xxabdabc"),
+    ("a+b", "aaaaaaaa"),
+    ("a+b", "aaaaaaab"),
+    (r"\bfoo", "afoo foo"),
+    (r"\Bfoo", "foo afoo"),
+    ("(?m)^foo", "afoo\nfoo"),
+    ("é+x", "ééx aéx"),
+    ("(?i)straße", "STRASSE Straße"),
+    ("(?i)ab|cd", "xAbCD"),
+    ("foo|foobar", "xfoobar"),
+    ("foobar|foo", "xfoobar"),
+    (r"x(\d)?y", "x1 xy x2y"),
+    ("abc$", "abc abc"),
+];
+
+/// What find_all_captures reports.
+fn all_captures(re: &Regex, input: Input<'_>) -> Vec<Vec<Option<(usize, usize)>>> {
+    re.find_all_captures(input)
+        .map(|caps| {
+            (0..caps.group_len())
+                .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                .collect()
+        })
+        .collect()
+}
+
+fn prefixes(pattern: &str) -> Option<Vec<Vec<u8>>> {
+    let re = Builder::new(pattern).pike_vm().unwrap();
+    re.prefix_literals()
+        .map(|literals| literals.into_iter().map(<[u8]>::to_vec).collect())
+}
+
+#[test]
+fn test_prefix_literals() {
+    assert_eq!(prefixes("foo"), Some(vec![b"foo".to_vec()]));
+    assert_eq!(
+        prefixes(r"Sherlock [A-Z]\w+"),
+        Some(vec![b"Sherlock ".to_vec()])
+    );
+    assert_eq!(
+        prefixes("(?i)ab"),
+        Some(vec![
+            b"AB".to_vec(),
+            b"Ab".to_vec(),
+            b"aB".to_vec(),
+            b"ab".to_vec()
+        ])
+    );
+    assert!(prefixes(r"\bfoo|bar$").is_some());
+
+    // No small set of non-empty prefixes.
+    for pattern in [
+        r"\w+",
+        "a*",
+        "a?b*",
+        ".*foo",
+        "(?m)^$",
+        "a|b|c|d|e|f|g|h|i",
+        "",
+    ] {
+        assert_eq!(prefixes(pattern), None, "{pattern:?}");
+    }
+
+    let re = Builder::new("foo").prefilter(false).pike_vm().unwrap();
+    assert_eq!(re.prefix_literals(), None);
+}
+
+#[test]
+fn test_same_matches_as_the_full_search() {
+    for (pattern, haystack) in CASES {
+        utils::check_all_engines(pattern, haystack);
+    }
+}
+
+#[test]
+fn test_same_matches_with_the_prefilter_disabled() {
+    for (pattern, haystack) in CASES {
+        for kind in EngineKind::ALL {
+            let Ok(on) = Builder::new(*pattern).build(kind) else {
+                continue;
+            };
+            let off = Builder::new(*pattern).prefilter(false).build(kind).unwrap();
+            let label = format!("{pattern:?} on {haystack:?} ({})", kind.name());
+            let len = haystack.len();
+            let inputs = [
+                Input::new(haystack),
+                Input::new(haystack).first_match(true),
+                Input::new(haystack).anchored(true),
+                Input::new(haystack).span(Span::from(1..len)),
+                Input::new(haystack).span(Span::from(0..len.saturating_sub(1))),
+            ];
+            for input in inputs {
+                if !input.valid() {
+                    continue;
+                }
+                assert_eq!(
+                    all_captures(&on, input.clone()),
+                    all_captures(&off, input.clone()),
+                    "{label}"
+                );
+                assert_eq!(on.is_match(input.clone()), off.is_match(input), "{label}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_no_match_without_a_prefix() {
+    let haystack = "Sherloc Holmes ".repeat(1000);
+    for kind in EngineKind::ALL {
+        let Ok(re) = Builder::new(r"Sherlock [A-Z]\w+").build(kind) else {
+            continue;
+        };
+        assert!(!re.is_match(haystack.as_str()), "{}", kind.name());
+        assert_eq!(re.find_all(haystack.as_str()).count(), 0);
+        let found = format!("{haystack}Sherlock Holmes");
+        assert_eq!(re.find(found.as_str()).unwrap().start(), haystack.len());
+    }
+}
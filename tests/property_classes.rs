@@ -0,0 +1,61 @@
+//! The Unicode property classes, e.g. `\p{Greek}`, and their errors in
+//! non-unicode mode.
+#[allow(dead_code)]
+mod utils;
+
+#[test]
+fn test_property_classes() {
+    const PATTERNS: &[&str] = &[
+        r"\p{L}+",
+        r"\p{Nd}+",
+        r"\p{Greek}+",
+        r"\p{Cyrillic}+\s\p{Cyrillic}+",
+        r"\p{Han}",
+        r"\p{Emoji_Presentation}",
+        r"\P{L}+",
+        r"[\p{Greek}\p{Nd}]+",
+        r"[^\p{L}\s]+",
+        r"(\p{Lu})(\p{Ll}+)",
+        r"(?i)\p{Greek}+",
+    ];
+    const HAYSTACK: &str = "The quick brown fox, ὁ ταχὺς καφετὶ ἀλώπηξ, \
+        быстрая лиса 123 ١٢٣ ४५६, 敏捷的狐狸 🦊🐕 Ελλάδα Zürich";
+    for pattern in PATTERNS {
+        println!("Testing: {pattern}");
+        utils::check_all_engines(pattern, HAYSTACK);
+    }
+}
+
+#[test]
+fn test_property_class_non_unicode() {
+    use gregex::thompson::bytecode::CompileError;
+
+    for (pattern, property) in [
+        (r"\p{Greek}", r"\p{Greek}"),
+        (r"a\pL", r"\pL"),
+        (r"[\P{Nd}x]", r"\P{Nd}"),
+    ] {
+        let err = gregex::Builder::new(pattern)
+            .unicode(false)
+            .pike_vm()
+            .err()
+            .unwrap();
+        let gregex::Error::Syntax(err) = err else {
+            panic!("Unexpected error for {pattern}: {err}");
+        };
+        match err.inner().downcast_ref::<CompileError>() {
+            Some(CompileError::UnsupportedInNonUnicodeMode { property: p }) => {
+                assert_eq!(p, property);
+                assert_eq!(&pattern[err.span().unwrap()], property);
+            }
+            _ => panic!("Unexpected error for {pattern}: {err}"),
+        }
+    }
+    // Unicode can still be enabled locally
+    assert!(
+        gregex::Builder::new(r"(?u:\p{Greek})")
+            .unicode(false)
+            .pike_vm()
+            .is_ok()
+    );
+}
@@ -0,0 +1,111 @@
+//! Replacements of the matches, compared with the ones of rust-regex.
+#[allow(dead_code)]
+mod utils;
+
+use std::borrow::Cow;
+
+use gregex::Builder;
+use gregex::replace::NoExpand;
+use gregex::util::Captures;
+use regex as rust_regex;
+use utils::compile_all_labeled;
+
+const CASES: &[(&str, &str)] = &[
+    ("a*", "baaab"),
+    ("", "héllo"),
+    (r"\d*", "a12b3"),
+    ("(a)|(b)", "xaybz"),
+    (
+        r"(?P<user>\w+)@(?<host>\w+)\.com",
+        "me@example.com, you@test.com",
+    ),
+    ("x*|y", "yxxy"),
+    (r"(?m)^\s*$", "a\n\n  \nb\n"),
+    ("é|", "aéb"),
+    ("z", "no match here"),
+];
+
+const REPLACEMENTS: &[&str] = &[
+    "",
+    "<$0>",
+    "$2-$1",
+    "${1}x",
+    "$1x",
+    "$$",
+    "$",
+    "${",
+    "$10",
+    "[$9]",
+    "$user",
+    "${host}.$user",
+    "$nope",
+    "$$1",
+    "${0}${0}",
+];
+
+#[test]
+fn test_same_as_regex_crate() {
+    for &(pattern, haystack) in CASES {
+        let rust = rust_regex::Regex::new(pattern).unwrap();
+        for (label, ours) in compile_all_labeled(pattern).unwrap() {
+            let ctx = format!("{label} for {pattern:?} on {haystack:?}");
+            for &replacement in REPLACEMENTS {
+                assert_eq!(
+                    ours.replace_all(haystack, replacement),
+                    rust.replace_all(haystack, replacement),
+                    "{ctx} with {replacement:?}"
+                );
+                assert_eq!(
+                    ours.replace(haystack, replacement),
+                    rust.replace(haystack, replacement),
+                    "{ctx} with {replacement:?}"
+                );
+                for limit in 0..4 {
+                    assert_eq!(
+                        ours.replacen(haystack, limit, replacement),
+                        rust.replacen(haystack, limit, replacement),
+                        "{ctx} with {replacement:?}, limit {limit}"
+                    );
+                }
+            }
+            assert_eq!(
+                ours.replace_all(haystack, NoExpand("$1")),
+                rust.replace_all(haystack, rust_regex::NoExpand("$1")),
+                "{ctx}"
+            );
+            let ours_lens = ours.replace_all(haystack, |caps: &Captures| {
+                caps.group0().as_str().len().to_string()
+            });
+            let rust_lens = rust.replace_all(haystack, |caps: &rust_regex::Captures| {
+                caps[0].len().to_string()
+            });
+            assert_eq!(ours_lens, rust_lens, "{ctx}");
+        }
+    }
+}
+
+#[test]
+fn test_borrowed_when_nothing_replaced() {
+    let re = Builder::new("x").pike_vm().unwrap();
+    assert!(matches!(re.replace_all("abc", "y"), Cow::Borrowed("abc")));
+    assert!(matches!(re.replace_all("axc", "y"), Cow::Owned(_)));
+    assert!(matches!(re.replacen("abc", 2, "$0"), Cow::Borrowed("abc")));
+}
+
+#[test]
+fn test_without_groups() {
+    let re = Builder::new(r"(\w+)=(\w+)").cg(false).pike_vm().unwrap();
+    assert_eq!(re.replace_all("a=1, b=2", "$2:$1 ($0)"), ": (a=1), : (b=2)");
+}
+
+#[test]
+fn test_closure_with_state() {
+    let re = Builder::new(r"\d+").pike_vm().unwrap();
+    let mut count = 0;
+    let replaced = re.replace_all("a1b22c333", |_: &Captures| {
+        count += 1;
+        format!("#{count}")
+    });
+    assert_eq!(replaced, "a#1b#2c#3");
+    assert_eq!(count, 3);
+}
@@ -0,0 +1,94 @@
+//! The compilation is deterministic: the same pattern and config always give
+//! the same bytecode, and the same jitted code.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use gregex::thompson::bytecode::{Bytecode, Compiler};
+
+fn compile(builder: &Builder) -> Option<Bytecode> {
+    let hir = Compiler::parse(builder.pattern(), builder.config()).ok()?;
+    Compiler::compile(hir, builder.config().clone()).ok()
+}
+
+fn builders() -> Vec<Builder<'static>> {
+    utils::corpus::CASES
+        .iter()
+        .flat_map(|(pattern, _)| {
+            [
+                Builder::new(*pattern),
+                // The outlined classes get their ids in the order they are found.
+                Builder::new(*pattern).force_class_outlining(true),
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn test_corpus_bytecode_is_deterministic() {
+    let builders = builders();
+    let forward: Vec<_> = builders.iter().map(compile).collect();
+    let mut backward: Vec<_> = builders.iter().rev().map(compile).collect();
+    backward.reverse();
+    for ((builder, first), second) in builders.iter().zip(&forward).zip(&backward) {
+        assert_eq!(first, second, "{:?}", builder.pattern());
+        assert_eq!(
+            first.as_ref().map(Bytecode::content_hash),
+            second.as_ref().map(Bytecode::content_hash),
+            "{:?}",
+            builder.pattern()
+        );
+    }
+}
+
+#[test]
+fn test_content_hash() {
+    // The hashes are the same on every run and platform, they only change
+    // with the encoding or the compiler, like tests/golden/encoding.txt.
+    let hashes = ["a", "a|bc", "(a+)?b", "(?m)^[a-ce-gi-km-oq]*$"]
+        .map(|pattern| compile(&Builder::new(pattern)).unwrap().content_hash());
+    assert_eq!(
+        hashes,
+        [
+            0x31db_faab_6887_1fa7,
+            0x1b30_8d3b_0c9f_2dfe,
+            0x5762_166e_5e86_12e8,
+            0x1011_eb31_e190_f372,
+        ]
+    );
+
+    // Any difference in the config shows in the hash.
+    let insensitive = compile(&Builder::new("a").case_insensitive(true)).unwrap();
+    assert_ne!(insensitive.content_hash(), hashes[0]);
+    let no_groups = compile(&Builder::new("(a+)?b").cg(false)).unwrap();
+    assert_ne!(no_groups.content_hash(), hashes[2]);
+}
+
+#[test]
+#[cfg(all(feature = "jit", target_arch = "x86_64", any(unix, windows)))]
+fn test_jit_code_is_deterministic() {
+    use gregex::CgStrategy;
+    use gregex::regex::Config;
+    use gregex::thompson::pike_jit::JittedRegex;
+
+    let patterns = [r"(\d+)-(\d+)", r"[a-z]{2,8}@(\w+)\.com", "foo|bar|baz"];
+    let mut hashes = Vec::new();
+    for pattern in patterns {
+        for jit_cg_strategy in [CgStrategy::Auto, CgStrategy::Tree, CgStrategy::Array] {
+            let config = Config {
+                jit_cg_strategy,
+                ..Config::default()
+            };
+            let first = JittedRegex::new(pattern, config.clone()).unwrap();
+            let second = JittedRegex::new(pattern, config).unwrap();
+            assert_eq!(first.code_hash(), second.code_hash(), "{pattern:?}");
+            hashes.push(first.code_hash());
+        }
+    }
+    // The hashes tell the code apart.
+    let mut distinct = hashes.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    // Auto picks the tree for the patterns with groups.
+    assert_eq!(distinct.len(), hashes.len() - 2);
+}
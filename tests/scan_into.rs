@@ -0,0 +1,221 @@
+//! The scans reporting the matches to a sink, see `Regex::scan_into`.
+#[allow(dead_code)]
+mod utils;
+
+use std::io::{self, Write};
+use std::ops::ControlFlow;
+
+use gregex::Regex;
+use gregex::sink::{MatchError, MatchSink, ScanSummary, SpanVecSink, WriterSink};
+use gregex::util::{Input, Span};
+
+fn bounds(spans: &[Span]) -> Vec<(usize, usize)> {
+    spans.iter().map(|span| (span.from, span.to)).collect()
+}
+
+fn find_all(re: &Regex, input: Input<'_>) -> Vec<(usize, usize)> {
+    re.find_all(input).map(|m| (m.start(), m.end())).collect()
+}
+
+/// Stops after the given number of matches.
+struct Stop(usize, SpanVecSink);
+
+impl MatchSink for Stop {
+    fn on_match(&mut self, span: Span) -> ControlFlow<()> {
+        let _ = self.1.on_match(span);
+        if self.1.spans.len() == self.0 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    }
+}
+
+/// Keeps the spans of every group.
+#[derive(Default)]
+struct Groups(Vec<Vec<(usize, usize)>>);
+
+impl MatchSink for Groups {
+    fn on_match(&mut self, _: Span) -> ControlFlow<()> {
+        unreachable!("the sink wants the captures")
+    }
+
+    fn wants_captures(&self) -> bool {
+        true
+    }
+
+    fn on_captures(&mut self, spans: &[Span]) -> ControlFlow<()> {
+        self.0.push(bounds(spans));
+        ControlFlow::Continue(())
+    }
+}
+
+#[test]
+fn test_same_as_find_all() {
+    for (pattern, haystack) in utils::corpus::CASES {
+        let Some(engines) = utils::compile_all(pattern) else {
+            continue;
+        };
+        // About 16 starts over each haystack, every start of the long ones
+        // would take minutes.
+        let starts: Vec<_> = (0..=haystack.len())
+            .filter(|i| haystack.is_char_boundary(*i))
+            .collect();
+        for re in &engines {
+            for &from in starts.iter().step_by(starts.len().div_ceil(16)) {
+                let input = Input::new(haystack).span((from..haystack.len()).into());
+                let mut sink = SpanVecSink::new();
+                let summary = re.scan_into(input.clone(), &mut sink).unwrap();
+                let expected = find_all(re, input);
+                assert_eq!(bounds(&sink.spans), expected, "{pattern:?} on {haystack:?}");
+                assert_eq!(
+                    summary,
+                    ScanSummary {
+                        matches: expected.len(),
+                        position: haystack.len(),
+                        stopped: false,
+                    }
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_captures() {
+    for re in utils::compile_all(r"(\w)(\d)?").unwrap() {
+        let haystack = "a1 b c2";
+        let mut sink = Groups::default();
+        re.scan_into(haystack, &mut sink).unwrap();
+        let expected: Vec<_> = re
+            .find_all_captures(haystack)
+            .map(|caps| {
+                (0..re.group_len())
+                    .map(|i| {
+                        caps.get(i)
+                            .map_or((usize::MAX, 0), |m| (m.start(), m.end()))
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(sink.0, expected);
+        assert_eq!(sink.0[1], [(3, 4), (3, 4), (usize::MAX, 0)]);
+    }
+}
+
+#[test]
+fn test_stop() {
+    for re in utils::compile_all(r"\d+|-").unwrap() {
+        let haystack = "12 -345 6";
+        let mut sink = Stop(2, SpanVecSink::new());
+        let summary = re.scan_into(haystack, &mut sink).unwrap();
+        assert_eq!(bounds(&sink.1.spans), [(0, 2), (3, 4)]);
+        assert_eq!(
+            summary,
+            ScanSummary {
+                matches: 2,
+                position: 4,
+                stopped: true,
+            }
+        );
+
+        // Resumed where the next search starts, the scan goes on.
+        let input = Input::new(haystack).span((summary.position..haystack.len()).into());
+        let mut rest = SpanVecSink::new();
+        re.scan_into(input, &mut rest).unwrap();
+        let all = find_all(&re, Input::new(haystack));
+        assert_eq!([bounds(&sink.1.spans), bounds(&rest.spans)].concat(), all);
+
+        // Stopped by the last match, which ends the input.
+        let mut sink = Stop(all.len(), SpanVecSink::new());
+        let summary = re.scan_into(haystack, &mut sink).unwrap();
+        assert!(summary.stopped);
+        assert_eq!(summary.position, haystack.len());
+    }
+}
+
+#[test]
+fn test_stop_after_empty_match() {
+    for re in utils::compile_all("x*").unwrap() {
+        // The next search skips the char after an empty match, but not the
+        // end of the input.
+        let mut sink = Stop(1, SpanVecSink::new());
+        let summary = re.scan_into("éa", &mut sink).unwrap();
+        assert_eq!((summary.matches, summary.position), (1, 2));
+        let mut sink = Stop(3, SpanVecSink::new());
+        let summary = re.scan_into("éa", &mut sink).unwrap();
+        assert_eq!(bounds(&sink.1.spans), [(0, 0), (2, 2), (3, 3)]);
+        assert_eq!((summary.position, summary.stopped), (3, true));
+    }
+}
+
+#[test]
+fn test_invalid_span() {
+    let re = Regex::pike_vm("a").unwrap();
+    let mut sink = SpanVecSink::new();
+    let reversed = Input::new("aaa").span(Span { from: 2, to: 1 });
+    assert_eq!(
+        re.scan_into(reversed, &mut sink),
+        Err(MatchError::InvalidSpan { from: 2, to: 1 })
+    );
+    let inside_char = Input::new("éa").span((1..3).into());
+    let err = re.scan_into(inside_char, &mut sink).unwrap_err();
+    assert!(err.to_string().contains("1..3"));
+    assert!(sink.spans.is_empty());
+}
+
+#[test]
+fn test_writer_sink() {
+    for re in utils::compile_all(r"\w+").unwrap() {
+        let haystack = "one two three ".repeat(100);
+        let expected: String = re
+            .find_all(&*haystack)
+            .map(|m| format!("{}\t{}\n", m.start(), m.end()))
+            .collect();
+        // Small buffers are written many times, large ones once.
+        for capacity in [0, 10, 1 << 20] {
+            let mut sink = WriterSink::with_capacity(Vec::new(), capacity);
+            let summary = re.scan_into(&*haystack, &mut sink).unwrap();
+            assert_eq!(summary.matches, 300);
+            let written = sink.finish().unwrap();
+            assert_eq!(String::from_utf8(written).unwrap(), expected);
+        }
+        // Dropping the sink writes the buffered lines.
+        let mut written = Vec::new();
+        re.scan_into("a bc", &mut WriterSink::new(&mut written))
+            .unwrap();
+        assert_eq!(written, b"0\t1\n2\t4\n");
+    }
+}
+
+/// Accepts the given number of bytes, and fails after.
+#[derive(Debug)]
+struct Full(usize);
+
+impl Write for Full {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0 == 0 {
+            return Err(io::Error::other("full"));
+        }
+        let len = buf.len().min(self.0);
+        self.0 -= len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_writer_error() {
+    let re = Regex::pike_vm(r"\d").unwrap();
+    let mut sink = WriterSink::with_capacity(Full(12), 4);
+    // Each match fills the buffer, the write of the fourth one fails.
+    let summary = re.scan_into("1 2 3 4 5 6", &mut sink).unwrap();
+    assert_eq!((summary.matches, summary.stopped), (4, true));
+    // A failed sink stops the next scans at their first match.
+    let summary = re.scan_into("7 8", &mut sink).unwrap();
+    assert_eq!((summary.matches, summary.stopped), (1, true));
+    assert_eq!(sink.finish().unwrap_err().to_string(), "full");
+}
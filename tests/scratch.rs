@@ -0,0 +1,75 @@
+//! The searches with a state owned by the caller, see `Regex::scratch_state`.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use gregex::EngineKind;
+
+#[test]
+fn test_same_as_pooled_searches() {
+    let cases = [
+        (r"\d+", ["abc 123 def", "none", "4"]),
+        (r"(?P<k>\w+)=(\w*)", ["a= b=c", "=", "key=value"]),
+        (r"(?m)^foo$", ["bar\nfoo\n", "foobar", ""]),
+        ("a.*b", ["xxaxxbxxb", "ba", "ab"]),
+    ];
+    for (pattern, haystacks) in cases {
+        for engine in EngineKind::ALL {
+            let Ok(re) = Builder::new(pattern).build(engine) else {
+                continue;
+            };
+            let ctx = format!("{} for {pattern:?}", engine.name());
+            let mut scratch = re.scratch_state();
+            let mut locs = re.capture_locations();
+            let mut expected_locs = re.capture_locations();
+            for haystack in haystacks {
+                assert_eq!(
+                    re.is_match_with(&mut scratch, haystack),
+                    re.is_match(haystack),
+                    "{ctx} on {haystack:?}"
+                );
+                assert_eq!(
+                    re.find_with(&mut scratch, haystack)
+                        .map(|m| (m.start(), m.end())),
+                    re.find(haystack).map(|m| (m.start(), m.end())),
+                    "{ctx} on {haystack:?}"
+                );
+                assert_eq!(
+                    re.captures_read_with(&mut scratch, &mut locs, haystack)
+                        .map(|m| (m.start(), m.end())),
+                    re.captures_read(&mut expected_locs, haystack)
+                        .map(|m| (m.start(), m.end())),
+                    "{ctx} on {haystack:?}"
+                );
+                for i in 0..locs.len() {
+                    assert_eq!(locs.get(i), expected_locs.get(i), "{ctx} on {haystack:?}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_one_per_thread() {
+    let re = Builder::new(r"\w+@\w+").pike_vm().unwrap();
+    std::thread::scope(|scope| {
+        for i in 0..4 {
+            let re = &re;
+            scope.spawn(move || {
+                let mut scratch = re.scratch_state();
+                let haystack = format!("{i} user{i}@host");
+                let found = re.find_with(&mut scratch, haystack.as_str()).unwrap();
+                assert_eq!(found.as_str(), format!("user{i}@host"));
+            });
+        }
+    });
+}
+
+#[test]
+#[should_panic(expected = "ScratchState used with a regex which didn't create it")]
+fn test_state_of_another_regex() {
+    let a = Builder::new("a").pike_vm().unwrap();
+    let b = Builder::new("a").pike_vm().unwrap();
+    let mut scratch = a.scratch_state();
+    b.find_with(&mut scratch, "a");
+}
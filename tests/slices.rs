@@ -0,0 +1,130 @@
+use gregex::util::MultiSliceMatch;
+use gregex::{Builder, EngineKind, Regex};
+
+const PARAGRAPHS: &[&str] = &["first line", "the second one", "", "é and last"];
+
+fn regexes(pattern: &str) -> Vec<(EngineKind, Regex)> {
+    let mut regexes = Vec::new();
+    for specialize in [true, false] {
+        let builder = Builder::new(pattern).specialize(specialize);
+        for engine in EngineKind::ALL {
+            if let Ok(re) = builder.build(engine) {
+                regexes.push((engine, re));
+            }
+        }
+    }
+    regexes
+}
+
+fn coordinates(m: &MultiSliceMatch<'_>) -> ((usize, usize), (usize, usize)) {
+    (m.start(), m.end())
+}
+
+fn find(pattern: &str, slices: &[&str], separator: Option<char>) -> Option<(usize, usize)> {
+    let re = Builder::new(pattern).pike_vm().unwrap();
+    re.find_in_slices(slices, separator)
+        .map(|m| (m.span.from, m.span.to))
+}
+
+#[test]
+fn test_match_inside_one_slice() {
+    let re = Regex::pike_vm("sec[a-z]+").unwrap();
+    let m = re.find_in_slices(PARAGRAPHS, Some('\n')).unwrap();
+    assert_eq!((m.span.from, m.span.to), (15, 21));
+    assert_eq!(coordinates(&m), ((1, 4), (1, 10)));
+    assert_eq!(m.extract_to_string(), "second");
+
+    let m = Regex::pike_vm("and").unwrap();
+    let m = m.find_in_slices(PARAGRAPHS, Some('\n')).unwrap();
+    assert_eq!(coordinates(&m), ((3, 3), (3, 6)));
+}
+
+#[test]
+fn test_match_spanning_slices_without_separator() {
+    let slices = ["ab", "c", "", "de"];
+    let re = Regex::pike_vm("bcd").unwrap();
+    let m = re.find_in_slices(&slices, None).unwrap();
+    assert_eq!((m.span.from, m.span.to), (1, 4));
+    assert_eq!(coordinates(&m), ((0, 1), (3, 1)));
+    assert_eq!(m.extract_to_string(), "bcd");
+
+    // Matches at the boundaries start in the next slice, and end in the
+    // previous one.
+    let m = Regex::pike_vm("c").unwrap();
+    let m = m.find_in_slices(&slices, None).unwrap();
+    assert_eq!(coordinates(&m), ((1, 0), (1, 1)));
+}
+
+#[test]
+fn test_match_spanning_separator() {
+    let re = Regex::pike_vm(r"line\sthe").unwrap();
+    let m = re.find_in_slices(PARAGRAPHS, Some('\n')).unwrap();
+    assert_eq!(coordinates(&m), ((0, 6), (1, 3)));
+    assert_eq!(m.extract_to_string(), "line\nthe");
+
+    // The empty slice puts two separators next to each other.
+    let re = Regex::pike_vm(r"one\n\né").unwrap();
+    let m = re.find_in_slices(PARAGRAPHS, Some('\n')).unwrap();
+    assert_eq!(coordinates(&m), ((1, 11), (3, 2)));
+    assert_eq!(m.extract_to_string(), "one\n\né");
+
+    // Just the separator.
+    let slices = ["a", "b"];
+    let re = Regex::pike_vm("🦀").unwrap();
+    let m = re.find_in_slices(&slices, Some('🦀')).unwrap();
+    assert_eq!((m.span.from, m.span.to), (1, 5));
+    assert_eq!(coordinates(&m), ((0, 1), (1, 0)));
+    assert_eq!(m.extract_to_string(), "🦀");
+    assert_eq!(find("ab", &slices, Some('🦀')), None);
+    assert_eq!(find("ab", &slices, None), Some((0, 2)));
+}
+
+#[test]
+fn test_anchors_at_slice_boundaries() {
+    let slices = ["ab", "cd"];
+    // With a newline separator, the slices are lines.
+    assert_eq!(find("(?m)^c", &slices, Some('\n')), Some((3, 4)));
+    assert_eq!(find("(?m)b$", &slices, Some('\n')), Some((1, 2)));
+    assert_eq!(find("(?m)^[a-z]+$", &slices, Some('\n')), Some((0, 2)));
+    // Not with another one, or none.
+    assert_eq!(find("(?m)^c", &slices, Some(' ')), None);
+    assert_eq!(find("(?m)^c", &slices, None), None);
+    assert_eq!(find("(?m)b$", &slices, None), None);
+    // The slices are one haystack, which only starts and ends once.
+    assert_eq!(find(r"\Ac", &slices, Some('\n')), None);
+    assert_eq!(find(r"b\z", &slices, Some('\n')), None);
+    assert_eq!(find(r"d\z", &slices, Some('\n')), Some((4, 5)));
+    assert_eq!(find(r"\A", &[], None), Some((0, 0)));
+}
+
+#[test]
+fn test_same_as_joined_haystack() {
+    let patterns = [
+        r"[a-z]+\n[a-z]+",
+        r"(?m)^$",
+        r"(?m)^.*$",
+        r"\s+",
+        "e",
+        r"o\n*é",
+        "",
+        "(?m)$",
+    ];
+    for pattern in patterns {
+        for separator in [None, Some('\n'), Some('é')] {
+            let joined = PARAGRAPHS.join(&separator.map(String::from).unwrap_or_default());
+            for (engine, re) in regexes(pattern) {
+                let expected = re.find(joined.as_str());
+                let found = re.find_in_slices(PARAGRAPHS, separator);
+                assert_eq!(
+                    found.map(|m| (m.span.from, m.span.to)),
+                    expected.map(|m| (m.start(), m.end())),
+                    "{} on {pattern:?} with {separator:?}",
+                    engine.name()
+                );
+                if let (Some(found), Some(expected)) = (found, expected) {
+                    assert_eq!(found.extract_to_string(), expected.as_str());
+                }
+            }
+        }
+    }
+}
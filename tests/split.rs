@@ -0,0 +1,71 @@
+//! Splits of haystacks on the matches, compared with the ones of rust-regex.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Regex;
+use regex as rust_regex;
+use utils::{check_all_engines, compile_all_labeled};
+
+const INPUTS: &[&str] = &[
+    "a, b,,c,",
+    ", leading and trailing ,",
+    "  spaced   out  ",
+    "xxaxbx",
+    "line\nbreaks\n",
+    "aéé",
+    "",
+];
+
+#[test]
+fn test_against_regex_crate() {
+    let patterns = [r",\s*", r"\s+", "", r"(?m)^", "$", "é"];
+    for pattern in patterns {
+        for input in INPUTS {
+            check_all_engines(pattern, input);
+        }
+    }
+}
+
+/// The patterns with empty matches right after other matches, which
+/// `find_all` reports but the splits skip, like rust-regex does.
+#[test]
+fn test_empty_matches_after_a_match() {
+    for pattern in ["x*", "a|", r"\s*", "(?:b|)"] {
+        let rust = rust_regex::Regex::new(pattern).unwrap();
+        for (label, ours) in compile_all_labeled(pattern).unwrap() {
+            for input in INPUTS {
+                let ctx = format!("{label} for {pattern:?} on {input:?}");
+                let expected: Vec<&str> = rust.split(input).collect();
+                assert_eq!(ours.split(input).collect::<Vec<_>>(), expected, "{ctx}");
+                for limit in 0..=(expected.len() + 1) {
+                    assert_eq!(
+                        ours.splitn(input, limit).collect::<Vec<_>>(),
+                        rust.splitn(input, limit).collect::<Vec<_>>(),
+                        "{ctx}, limit {limit}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_borrows_the_haystack() {
+    let re = Regex::pike_vm(r"\d").unwrap();
+    let haystack = String::from("a1b2c");
+    let pieces: Vec<&str> = re.split(&haystack).collect();
+    assert_eq!(pieces, ["a", "b", "c"]);
+    // The pieces outlive the iterator, and point into the haystack.
+    assert!(std::ptr::eq(pieces[2], &haystack[4..]));
+}
+
+#[test]
+fn test_splitn_stops_searching() {
+    let re = Regex::pike_vm(",").unwrap();
+    assert_eq!(re.splitn("a,b,c", 0).count(), 0);
+    assert_eq!(re.splitn("a,b,c", 1).collect::<Vec<_>>(), ["a,b,c"]);
+    assert_eq!(re.splitn("a,b,c", 9).collect::<Vec<_>>(), ["a", "b", "c"]);
+    let mut split = re.splitn("a,b", 2);
+    assert_eq!(split.by_ref().collect::<Vec<_>>(), ["a", "b"]);
+    assert_eq!(split.next(), None);
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use gregex::Builder;
+use gregex::thompson::pike_vm::GroupEvent;
+
+const CASES: &[(&str, &str)] = &[
+    (r"(\w+)-(\d+)", "test-42"),
+    (r"(\d+)?", ""),
+    (r"(a)|(b)|((c)(d)?)", "xxcd"),
+    (r"(a|ab)(c|bcd)(d*)", "abcd"),
+    (r"(?:(a)|b)+", "abab"),
+    (r"(a*)*", "aaa"),
+    (r"(a*)+?(b)", "aab"),
+    (
+        r"BEGIN (\w+)(.*?)END",
+        "xx BEGIN header lots of text END tail END",
+    ),
+    (r"(?m)^(\w+): (.*)$", "key: value\nother: thing"),
+    (
+        r"(\s*)((?:# [Nn][Oo][Qq][Aa])(?::\s?(([A-Z]+[0-9]+(?:[,\s]+)?)+))?)",
+        "  # noqa:A12, B3",
+    ),
+    (r"(x)(y)?", "no match here"),
+    (r"^", "abc"),
+    (r"(^)|($)", "abc"),
+];
+
+fn bounds(
+    live: &mut HashMap<usize, Vec<(usize, usize)>>,
+    candidate: usize,
+    group: usize,
+) -> &mut (usize, usize) {
+    let spans = live.entry(candidate).or_default();
+    if spans.len() <= group {
+        // Unset bounds, as in Span::invalid()
+        spans.resize(group + 1, (usize::MAX, 0));
+    }
+    &mut spans[group]
+}
+
+/// Replays the events, checking that every candidate ends exactly once, and
+/// returns the groups of the committed candidate.
+fn replay(events: &[GroupEvent], group_len: usize) -> Option<Vec<Option<(usize, usize)>>> {
+    let mut live: HashMap<usize, Vec<(usize, usize)>> = HashMap::new();
+    let mut ended = Vec::new();
+    let mut committed = None;
+    for event in events {
+        match *event {
+            GroupEvent::Open {
+                candidate,
+                group,
+                position,
+            } => {
+                assert!(!ended.contains(&candidate), "event after the end");
+                bounds(&mut live, candidate, group).0 = position;
+            }
+            GroupEvent::Close {
+                candidate,
+                group,
+                position,
+            } => {
+                assert!(!ended.contains(&candidate), "event after the end");
+                bounds(&mut live, candidate, group).1 = position;
+            }
+            GroupEvent::Commit { candidate } => {
+                assert!(committed.is_none(), "several commits");
+                assert!(!ended.contains(&candidate), "candidate ended twice");
+                ended.push(candidate);
+                committed = live.remove(&candidate);
+            }
+            GroupEvent::Abort { candidate } => {
+                assert!(!ended.contains(&candidate), "candidate ended twice");
+                ended.push(candidate);
+                live.remove(&candidate);
+            }
+        }
+    }
+    assert!(live.is_empty(), "candidates never ended: {:?}", live.keys());
+    committed.map(|mut spans| {
+        spans.resize(group_len, (usize::MAX, 0));
+        spans
+            .into_iter()
+            .map(|(from, to)| (from <= to).then_some((from, to)))
+            .collect()
+    })
+}
+
+#[test]
+fn test_streaming_events_reconstruct_captures() {
+    for (pattern, input) in CASES {
+        for specialize in [false, true] {
+            let builder = Builder::new(*pattern).specialize(specialize);
+            let mut engines = vec![builder.pike_vm().unwrap()];
+            engines.extend(builder.pike_jit());
+            for re in engines {
+                let mut events = Vec::new();
+                let streamed = re.find_captures_streaming(*input, |e| events.push(e));
+                let expected = re.find_captures(*input);
+                let expected = expected.map(|caps| {
+                    (0..caps.group_len())
+                        .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                        .collect::<Vec<_>>()
+                });
+                let streamed = streamed.map(|caps| {
+                    (0..caps.group_len())
+                        .map(|i| caps.get(i).map(|m| (m.start(), m.end())))
+                        .collect::<Vec<_>>()
+                });
+                assert_eq!(streamed, expected, "{pattern:?} on {input:?}");
+                let group_len = expected.as_ref().map_or(0, Vec::len);
+                assert_eq!(
+                    replay(&events, group_len),
+                    expected,
+                    "{pattern:?} on {input:?} (specialize: {specialize})"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_streaming_reports_groups_before_the_end() {
+    let re = Builder::new(r"BEGIN (\w+) .*END").pike_vm().unwrap();
+    let input = format!("BEGIN header {}END", "x".repeat(10_000));
+    let mut events = Vec::new();
+    re.find_captures_streaming(input.as_str(), |event| events.push(event))
+        .unwrap();
+    let final_close = events
+        .iter()
+        .position(|event| {
+            matches!(
+                event,
+                GroupEvent::Close {
+                    group: 1,
+                    position: 12,
+                    ..
+                }
+            )
+        })
+        .unwrap();
+    // Group 1 is reported as soon as it is closed, long before the match ends
+    assert!(final_close < 100);
+    assert!(events.len() > 10_000);
+}
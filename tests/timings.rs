@@ -0,0 +1,158 @@
+//! The timings of the builds and of the searches, see `Builder::timings` and
+//! `Regex::find_timed`. Only their consistency is checked, not their values.
+#[allow(dead_code)]
+mod utils;
+
+use std::time::Duration;
+
+use gregex::util::{BuildTimings, ExecTimings, Input, Match};
+use gregex::{Builder, EngineKind, Regex};
+
+const PATTERN: &str = r"(\w+)@(\w+)\.(?:com|org)";
+const HAYSTACK: &str = "mail bob@example.com or alice@example.org";
+
+fn phases(timings: &BuildTimings) -> Duration {
+    timings.parse + timings.compile + timings.assemble
+}
+
+/// Every engine which can be built, with the timings enabled.
+fn timed(builder: Builder) -> Vec<Regex> {
+    let builder = builder.timings(true);
+    EngineKind::ALL
+        .into_iter()
+        .filter_map(|engine| builder.build(engine).ok())
+        .collect()
+}
+
+#[test]
+fn test_disabled_by_default() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        assert_eq!(re.build_timings(), None);
+    }
+}
+
+#[test]
+fn test_build_timings() {
+    for re in timed(Builder::new(PATTERN)) {
+        let timings = re.build_timings().unwrap();
+        let name = re.engine_kind().name();
+        assert!(timings.parse > Duration::ZERO, "{name}");
+        assert!(timings.compile > Duration::ZERO, "{name}");
+        // Only the JIT assembles code.
+        let jit = re.engine_kind() != EngineKind::PikeVM;
+        assert_eq!(timings.assemble > Duration::ZERO, jit, "{name}");
+        assert!(timings.total >= phases(&timings), "{name}: {timings:?}");
+    }
+}
+
+#[test]
+fn test_build_timings_of_specialized_engines() {
+    // The specialized engines only report the total.
+    for re in timed(Builder::new("(?m)^$")) {
+        let timings = re.build_timings().unwrap();
+        assert_eq!(phases(&timings), Duration::ZERO);
+        assert!(timings.total > Duration::ZERO);
+    }
+    // A leading wildcard reports the phases of the engine of the pattern.
+    for re in timed(Builder::new(".*(a)b")) {
+        let timings = re.build_timings().unwrap();
+        assert!(timings.parse > Duration::ZERO);
+        assert!(timings.total >= phases(&timings));
+    }
+}
+
+#[test]
+fn test_rebuild_keeps_timings() {
+    let re = Builder::new("a+").timings(true).pike_vm().unwrap();
+    let rebuilt = re
+        .rebuild(|builder| builder.case_insensitive(true))
+        .unwrap();
+    assert!(rebuilt.build_timings().is_some());
+    let untimed = re.rebuild(|builder| builder.timings(false)).unwrap();
+    assert_eq!(untimed.build_timings(), None);
+}
+
+#[test]
+fn test_exec_timings() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let mut timings = ExecTimings::default();
+        let mut previous = timings;
+        for haystack in [HAYSTACK, "no address", ""] {
+            let found = re.find_timed(haystack, Some(&mut timings));
+            let expected = re.find(haystack);
+            let bounds = |m: gregex::util::Match| (m.start(), m.end());
+            assert_eq!(found.map(bounds), expected.map(bounds));
+            // The durations add up over the searches.
+            assert!(timings.setup >= previous.setup);
+            assert!(timings.scan >= previous.scan);
+            assert!(timings.extract >= previous.extract);
+            assert!(timings.total > previous.total);
+            previous = timings;
+        }
+        assert_eq!(timings.searches, 3);
+        let parts = timings.setup + timings.scan + timings.extract;
+        assert!(timings.total >= parts, "{timings:?}");
+        assert!(timings.scan > Duration::ZERO);
+    }
+}
+
+#[test]
+fn test_captures_timed() {
+    for re in utils::compile_all(PATTERN).unwrap() {
+        let mut timings = ExecTimings::default();
+        let caps = re
+            .find_captures_timed(HAYSTACK, Some(&mut timings))
+            .unwrap();
+        assert_eq!(timings.searches, 1);
+        assert_eq!(caps.get(1).unwrap().as_str(), "bob");
+        assert_eq!(caps.get(2).unwrap().as_str(), "example");
+
+        // Without timings, this is `find_captures`.
+        let caps = re.find_captures_timed(HAYSTACK, None).unwrap();
+        assert_eq!(caps.group0().as_str(), "bob@example.com");
+        assert!(re.find_timed("nothing", None).is_none());
+    }
+}
+
+#[test]
+fn test_timed_like_untimed_after_a_match() {
+    // The empty match right after the previous match is skipped either way.
+    for re in utils::compile_all("a*").unwrap() {
+        for haystack in ["ab", "aa", "baab"] {
+            let mut input = Input::new(haystack);
+            input.advance_past(&re.find(input.clone()).unwrap());
+            let mut timings = ExecTimings::default();
+            let span = |m: Match<'_>| (m.start(), m.end());
+            let expected = re.find(input.clone()).map(span);
+            let found = re.find_timed(input.clone(), Some(&mut timings));
+            assert_eq!(found.map(span), expected, "{haystack:?}");
+            let caps = re.find_captures_timed(input.clone(), Some(&mut timings));
+            assert_eq!(caps.map(|caps| span(caps.group0())), expected);
+        }
+    }
+}
+
+#[test]
+fn test_prefiltered_search() {
+    // The prefilter rejects the haystack without the engine, which is still
+    // timed as the scan.
+    let re = Regex::pike_vm("@").unwrap();
+    assert!(re.required_bytes().is_some());
+    let mut timings = ExecTimings::default();
+    assert!(re.find_timed("no at sign", Some(&mut timings)).is_none());
+    assert_eq!(timings.searches, 1);
+    assert!(timings.total >= timings.setup + timings.scan + timings.extract);
+}
+
+#[test]
+fn test_comparison_report() {
+    let report = gregex::bench::compare(PATTERN, HAYSTACK, 2);
+    for engine in &report.engines {
+        assert!(engine.build.total >= phases(&engine.build));
+        assert!(engine.build.parse > Duration::ZERO);
+        assert_eq!(engine.first_search.searches, 1);
+    }
+    let table = report.to_string();
+    assert!(table.contains("assemble"));
+    assert!(table.contains("extract"));
+}
@@ -1,36 +1,226 @@
-use gregex::{Builder, Regex};
+use gregex::util::{Captures, Input, Match, Span};
+use gregex::{Builder, CgStrategy, EngineKind, Regex};
 use regex as rust_regex;
+use regex_automata::{Anchored, meta};
 
-/// Compile a given pattern on all gregex engines. Return Some if it compiles
-/// for all engines, or None if it fails to compile for all of them. Panics if
-/// an inconcistency is detected.
-pub fn compile_all(pattern: &str) -> Option<Vec<Regex>> {
-    let mut engines = Vec::new();
-    let mut must_fail = false;
-    // Try PikeVM
-    match Regex::pike_vm(pattern) {
-        Ok(re) => engines.push(re),
-        Err(_) => {
-            must_fail = true;
+pub mod corpus;
+
+/// The checks performed by [`check_all_engines`], used to suppress the
+/// expected divergences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    IsMatch,
+    Anchored,
+    SubSpan,
+    FirstMatch,
+}
+
+/// Known divergences from rust-regex, as (pattern, check, reason). A check
+/// listed here is still run, but its result is not compared. Remove the
+/// entry once the underlying issue is fixed.
+const EXPECTED_DIVERGENCES: &[(&str, Check, &str)] = &[];
+
+pub fn is_expected_divergence(pattern: &str, check: Check) -> bool {
+    EXPECTED_DIVERGENCES
+        .iter()
+        .any(|(p, c, _)| *p == pattern && *c == check)
+}
+
+/// A handful of deterministic sub-spans of the input, snapped to char
+/// boundaries.
+fn sub_spans(input: &str) -> Vec<Span> {
+    let len = input.len();
+    let floor = |mut i: usize| {
+        while !input.is_char_boundary(i) {
+            i -= 1;
         }
+        i
+    };
+    let mut spans: Vec<Span> = [
+        (0, len / 2),
+        (len / 3, len),
+        (len / 4, 3 * len / 4),
+        (1.min(len), len.saturating_sub(1)),
+        (len / 2, len / 2),
+    ]
+    .into_iter()
+    .map(|(from, to)| (floor(from)..floor(to).max(floor(from))).into())
+    .collect();
+    spans.dedup_by_key(|span| (span.from, span.to));
+    spans
+}
+
+/// How classes are compiled, see `Builder::force_class_outlining`. The
+/// engines must behave the same in every mode, so any divergence between
+/// them is a bug.
+const CLASS_MODES: &[(&str, bool, bool)] = &[
+    ("default", false, false),
+    ("outlined", true, false),
+    ("inlined", false, true),
+];
+
+/// The engines, with the CG strategy of the JIT (see
+/// `Builder::jit_cg_strategy`) and whether it also compiles the pattern for
+/// the overall match only (see `Builder::match_only_jit`). The registers are
+/// left to `Auto`, which picks them for the patterns without groups, since
+/// they fail on the others. The lazy JIT switches to the JIT on its second
+/// search (see `Builder::lazy_jit_after`), so the checks run both of its
+/// engines, including across the switch in the iterators.
+const ENGINES: &[(&str, EngineKind, CgStrategy, bool)] = &[
+    ("pike_vm", EngineKind::PikeVM, CgStrategy::Auto, false),
+    ("pike_jit", EngineKind::PikeJIT, CgStrategy::Auto, false),
+    (
+        "pike_jit_tree",
+        EngineKind::PikeJIT,
+        CgStrategy::Tree,
+        false,
+    ),
+    (
+        "pike_jit_array",
+        EngineKind::PikeJIT,
+        CgStrategy::Array,
+        false,
+    ),
+    (
+        "pike_jit_cow_array",
+        EngineKind::PikeJIT,
+        CgStrategy::CowArray,
+        false,
+    ),
+    (
+        "pike_jit_match_only",
+        EngineKind::PikeJIT,
+        CgStrategy::Auto,
+        true,
+    ),
+    ("lazy_jit", EngineKind::LazyJIT, CgStrategy::Auto, false),
+];
+
+/// The flags of the builders, set both on ours and on the ones of rust-regex,
+/// see [`check_all_engines_with`]. The inline flags of the pattern override
+/// them.
+#[derive(Debug, Clone, Copy)]
+pub struct Flags {
+    pub case_insensitive: bool,
+    pub unicode: bool,
+}
+
+impl Default for Flags {
+    fn default() -> Self {
+        Self {
+            case_insensitive: false,
+            unicode: true,
+        }
+    }
+}
+
+impl Flags {
+    fn builder<'s>(&self, pattern: &'s str) -> Builder<'s> {
+        Builder::new(pattern)
+            .case_insensitive(self.case_insensitive)
+            .unicode(self.unicode)
     }
-    // Try PikeJit
-    match Regex::pike_jit(pattern) {
-        Ok(re) if !must_fail => engines.push(re),
-        Err(_) if must_fail => (),
-        _ => panic!("Inconsistency detected"),
+
+    fn rust(&self, pattern: &str) -> Result<rust_regex::Regex, rust_regex::Error> {
+        rust_regex::RegexBuilder::new(pattern)
+            .case_insensitive(self.case_insensitive)
+            .unicode(self.unicode)
+            .build()
     }
 
-    match Builder::new(pattern).pike_jit_array() {
-        Ok(re) if !must_fail => engines.push(re),
-        Err(_) if must_fail => (),
-        _ => panic!("Inconsistency detected"),
+    fn meta(&self, pattern: &str) -> meta::Regex {
+        let syntax = regex_automata::util::syntax::Config::new()
+            .case_insensitive(self.case_insensitive)
+            .unicode(self.unicode);
+        meta::Builder::new().syntax(syntax).build(pattern).unwrap()
     }
+}
+
+/// Whether the JIT engines are available on this platform.
+pub fn jit_available() -> bool {
+    cfg!(all(
+        feature = "jit",
+        target_arch = "x86_64",
+        any(unix, windows)
+    ))
+}
+
+/// The spans of the groups of the first match, `None` for the groups which
+/// did not participate in it.
+pub fn find_groups<'s>(
+    re: &Regex,
+    input: impl Into<Input<'s>>,
+) -> Option<Vec<Option<(usize, usize)>>> {
+    re.find_captures(input).map(|caps| group_spans(&caps))
+}
+
+/// The spans of the groups of the captures, `None` for the groups which did
+/// not participate in the match.
+pub fn group_spans(caps: &Captures<'_>) -> Vec<Option<(usize, usize)>> {
+    caps.all_groups()
+        .into_iter()
+        .map(|g| g.map(|m| (m.start(), m.end())))
+        .collect()
+}
+
+/// The spans of the matches.
+pub fn match_spans<'s>(matches: impl IntoIterator<Item = Match<'s>>) -> Vec<(usize, usize)> {
+    matches.into_iter().map(|m| (m.start(), m.end())).collect()
+}
+
+/// Compile a given pattern on all gregex engines, both with and without
+/// specialized engines. Return Some if it compiles for all engines, or None if
+/// it fails to compile for all of them. Panics if an inconcistency is detected.
+pub fn compile_all(pattern: &str) -> Option<Vec<Regex>> {
+    compile_labeled(pattern, &CLASS_MODES[..1], Flags::default())
+        .map(|engines| engines.into_iter().map(|(_, re)| re).collect())
+}
+
+/// Like [`compile_all`], but also compiles the general engines in every class
+/// mode, and labels each regex with its engine and configuration, for failure
+/// messages.
+pub fn compile_all_labeled(pattern: &str) -> Option<Vec<(String, Regex)>> {
+    compile_labeled(pattern, CLASS_MODES, Flags::default())
+}
 
-    match Builder::new(pattern).pike_jit_cow_array() {
-        Ok(re) if !must_fail => engines.push(re),
-        Err(_) if must_fail => (),
-        _ => panic!("Inconsistency detected"),
+fn compile_labeled(
+    pattern: &str,
+    class_modes: &[(&str, bool, bool)],
+    flags: Flags,
+) -> Option<Vec<(String, Regex)>> {
+    let mut engines = Vec::new();
+    let mut must_fail = false;
+    for specialize in [true, false] {
+        for (mode, outline, inline) in class_modes {
+            // The specialized engines don't have classes
+            if specialize && *mode != "default" {
+                continue;
+            }
+            let builder = flags
+                .builder(pattern)
+                .specialize(specialize)
+                .force_class_outlining(*outline)
+                .force_class_inlining(*inline);
+            for (name, kind, strategy, match_only) in ENGINES {
+                // The JIT engines are not available on every platform, the
+                // lazy JIT then runs the Pike VM
+                if !matches!(kind, EngineKind::PikeVM | EngineKind::LazyJIT) && !jit_available() {
+                    continue;
+                }
+                let label = format!("{name} (specialize: {specialize}, classes: {mode})");
+                let builder = builder
+                    .clone()
+                    .jit_cg_strategy(*strategy)
+                    .match_only_jit(*match_only)
+                    .lazy_jit_after(Some(2));
+                match builder.build(*kind) {
+                    Ok(re) if !must_fail => engines.push((label, re)),
+                    Err(_) if engines.is_empty() => must_fail = true,
+                    Err(_) if must_fail => (),
+                    _ => panic!("Inconsistency detected on {label}"),
+                }
+            }
+        }
     }
 
     if must_fail { None } else { Some(engines) }
@@ -39,20 +229,25 @@ pub fn compile_all(pattern: &str) -> Option<Vec<Regex>> {
 /// Match a pattern agains a given input on all engines,
 /// including rust-regex, and compare the result of both compilation and execution.
 pub fn check_all_engines(pattern: &str, input: &str) {
+    check_all_engines_with(pattern, input, Flags::default())
+}
+
+/// Like [`check_all_engines`], with the given flags set on the builders.
+pub fn check_all_engines_with(pattern: &str, input: &str, flags: Flags) {
     // Reference engine
-    let rust = rust_regex::Regex::new(pattern);
-    let ours = compile_all(pattern);
+    let rust = flags.rust(pattern);
+    let ours = compile_labeled(pattern, CLASS_MODES, flags);
 
     match (rust, ours) {
         (Ok(rust_re), Some(our_engines)) => {
             // find
             let rust_match = rust_re.find(input).map(|m| (m.start(), m.end()));
-            for engine in &our_engines {
+            for (label, engine) in &our_engines {
                 let my_match = engine.find(input).map(|m| (m.span.from, m.span.to));
                 assert_eq!(
                     my_match, rust_match,
-                    "Mismatch for pattern {:?} input {:?} (find)",
-                    pattern, input
+                    "Mismatch on {} for pattern {:?} input {:?} (find)",
+                    label, pattern, input
                 );
             }
 
@@ -61,66 +256,233 @@ pub fn check_all_engines(pattern: &str, input: &str) {
                 .find_iter(input)
                 .map(|m| (m.start(), m.end()))
                 .collect();
-            for engine in &our_engines {
+            for (label, engine) in &our_engines {
                 let my_all: Vec<_> = engine
                     .find_all(input)
                     .map(|m| (m.span.from, m.span.to))
                     .collect();
                 assert_eq!(
                     my_all, rust_all,
-                    "Mismatch for pattern {:?} input {:?} (find_all)",
-                    pattern, input
+                    "Mismatch on {} for pattern {:?} input {:?} (find_all)",
+                    label, pattern, input
                 );
             }
 
             // find_captures
             let rust_caps = rust_re.captures(input);
             let rust_groups = rust_caps.as_ref().map(|caps| {
-                (0..caps.len())
-                    .map(|i| caps.get(i).map(|m| m.as_str()))
+                caps.iter()
+                    .map(|g| g.map(|g| g.as_str()))
                     .collect::<Vec<_>>()
             });
-            for engine in &our_engines {
+            for (label, engine) in &our_engines {
                 let my_caps = engine.find_captures(input);
                 let my_groups = my_caps.as_ref().map(|caps| {
-                    (0..caps.group_len())
-                        .map(|i| caps.get(i).map(|g| g.as_str()))
+                    caps.groups_iter()
+                        .map(|(_, _, g)| g.map(|g| g.as_str()))
                         .collect::<Vec<_>>()
                 });
                 assert_eq!(
                     my_groups, rust_groups,
-                    "Mismatch for pattern {:?} input {:?} (find_captures)",
-                    pattern, input
+                    "Mismatch on {} for pattern {:?} input {:?} (find_captures)",
+                    label, pattern, input
+                );
+                let my_participation = my_caps.map(|caps| caps.participation().collect());
+                let rust_participation = rust_groups
+                    .as_ref()
+                    .map(|groups| groups.iter().map(Option::is_some).collect::<Vec<_>>());
+                assert_eq!(
+                    my_participation, rust_participation,
+                    "Mismatch on {} for pattern {:?} input {:?} (participation)",
+                    label, pattern, input
                 );
             }
 
+            // The named groups, by name
+            let rust_names: Vec<Option<&str>> = rust_re.capture_names().collect();
+            for (label, engine) in &our_engines {
+                let my_names: Vec<Option<&str>> = engine.group_names().collect();
+                assert_eq!(
+                    my_names, rust_names,
+                    "Mismatch on {} for pattern {:?} (group names)",
+                    label, pattern
+                );
+                let my_caps = engine.find_captures(input);
+                for name in rust_names.iter().flatten() {
+                    let rust_group = rust_caps
+                        .as_ref()
+                        .and_then(|caps| caps.name(name))
+                        .map(|m| m.as_str());
+                    let my_group = my_caps
+                        .as_ref()
+                        .and_then(|caps| caps.name(name))
+                        .map(|m| m.as_str());
+                    assert_eq!(
+                        my_group, rust_group,
+                        "Mismatch on {} for pattern {:?} input {:?} (group {:?})",
+                        label, pattern, input, name
+                    );
+                }
+            }
+
+            // find_captures_n, for every n (including out of range ones)
+            if let Some(rust_groups) = &rust_groups {
+                for n in 0..=(rust_groups.len() + 1) {
+                    let expected = &rust_groups[..n.clamp(1, rust_groups.len())];
+                    for (label, engine) in &our_engines {
+                        let my_caps = engine.find_captures_n(input, n).unwrap();
+                        let my_groups = my_caps
+                            .groups_iter()
+                            .map(|(_, _, g)| g.map(|g| g.as_str()))
+                            .collect::<Vec<_>>();
+                        assert_eq!(
+                            my_groups, expected,
+                            "Mismatch on {} for pattern {:?} input {:?} (find_captures_n, n={})",
+                            label, pattern, input, n
+                        );
+                    }
+                }
+            }
+
             //// find_all_captures
             let rust_all_caps: Vec<Vec<Option<&str>>> = rust_re
                 .captures_iter(input)
-                .map(|caps| {
-                    (0..caps.len())
-                        .map(|i| caps.get(i).map(|m| m.as_str()))
-                        .collect()
-                })
+                .map(|caps| caps.iter().map(|g| g.map(|g| g.as_str())).collect())
                 .collect();
-            for engine in &our_engines {
+            for (label, engine) in &our_engines {
                 let my_all_caps: Vec<Vec<Option<&str>>> = engine
                     .find_all_captures(input)
                     .map(|caps| {
-                        (0..caps.group_len())
-                            .map(|i| caps.get(i).map(|g| g.as_str()))
+                        caps.all_groups()
+                            .into_iter()
+                            .map(|g| g.map(|g| g.as_str()))
                             .collect()
                     })
                     .collect();
                 assert_eq!(
                     my_all_caps, rust_all_caps,
-                    "Mismatch for pattern {:?} input {:?} (find_all_captures)",
-                    pattern, input
+                    "Mismatch on {} for pattern {:?} input {:?} (find_all_captures)",
+                    label, pattern, input
+                );
+            }
+
+            // split and splitn
+            let rust_split: Vec<&str> = rust_re.split(input).collect();
+            for (label, engine) in &our_engines {
+                let my_split: Vec<&str> = engine.split(input).collect();
+                assert_eq!(
+                    my_split, rust_split,
+                    "Mismatch on {} for pattern {:?} input {:?} (split)",
+                    label, pattern, input
                 );
+                for limit in 0..=(rust_split.len() + 1) {
+                    let my_split: Vec<&str> = engine.splitn(input, limit).collect();
+                    let rust_split: Vec<&str> = rust_re.splitn(input, limit).collect();
+                    assert_eq!(
+                        my_split, rust_split,
+                        "Mismatch on {} for pattern {:?} input {:?} (splitn, limit={})",
+                        label, pattern, input, limit
+                    );
+                }
             }
+
+            check_inputs(pattern, input, &our_engines, flags);
         }
         (Err(_), None) => {} // All failed, that's good
         (Ok(_), None) => panic!("Our engines failed to compile but rust-regex succeeded"),
         (Err(e), Some(_)) => panic!("rust-regex failed to compile but our engines succeeded: {e}"),
     }
 }
+
+/// Compare is_match, anchored searches, searches restricted to sub-spans and
+/// first_match searches against rust-regex. The sub-span searches use
+/// regex-automata, which, like us, looks outside of the span for the
+/// assertions.
+fn check_inputs(pattern: &str, input: &str, our_engines: &[(String, Regex)], flags: Flags) {
+    let rust_re = flags.meta(pattern);
+    let rust_find = |span: Span, anchored: bool| {
+        let rust_input = regex_automata::Input::new(input)
+            .range(span.from..span.to)
+            .anchored(if anchored {
+                Anchored::Yes
+            } else {
+                Anchored::No
+            });
+        rust_re.find(rust_input).map(|m| (m.start(), m.end()))
+    };
+    let full: Span = (0..input.len()).into();
+
+    for (label, engine) in our_engines {
+        // is_match
+        if !is_expected_divergence(pattern, Check::IsMatch) {
+            assert_eq!(
+                engine.is_match(input),
+                rust_re.is_match(input),
+                "Mismatch on {} for pattern {:?} input {:?} (is_match)",
+                label,
+                pattern,
+                input
+            );
+        }
+
+        // anchored
+        if !is_expected_divergence(pattern, Check::Anchored) {
+            let my_match = engine
+                .find(Input::new(input).anchored(true))
+                .map(|m| (m.span.from, m.span.to));
+            assert_eq!(
+                my_match,
+                rust_find(full, true),
+                "Mismatch on {} for pattern {:?} input {:?} (anchored)",
+                label,
+                pattern,
+                input
+            );
+        }
+
+        // sub-spans, both anchored and not
+        if !is_expected_divergence(pattern, Check::SubSpan) {
+            for span in sub_spans(input) {
+                for anchored in [false, true] {
+                    let my_input = Input::new(input).span(span).anchored(anchored);
+                    let my_match = engine
+                        .find(my_input.clone())
+                        .map(|m| (m.span.from, m.span.to));
+                    assert_eq!(
+                        my_match,
+                        rust_find(span, anchored),
+                        "Mismatch on {} for pattern {:?} input {:?} (span {:?}, anchored: {})",
+                        label,
+                        pattern,
+                        input,
+                        span,
+                        anchored
+                    );
+                    assert_eq!(
+                        engine.is_match(my_input),
+                        my_match.is_some(),
+                        "Mismatch on {} for pattern {:?} input {:?} (is_match, span {:?}, anchored: {})",
+                        label,
+                        pattern,
+                        input,
+                        span,
+                        anchored
+                    );
+                }
+            }
+        }
+
+        // first_match must find something iff find does
+        if !is_expected_divergence(pattern, Check::FirstMatch) {
+            let first = engine.find(Input::new(input).first_match(true));
+            assert_eq!(
+                first.is_some(),
+                engine.find(input).is_some(),
+                "Mismatch on {} for pattern {:?} input {:?} (first_match)",
+                label,
+                pattern,
+                input
+            );
+        }
+    }
+}
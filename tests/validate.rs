@@ -0,0 +1,371 @@
+//! The checks of patterns without compiling them, see `gregex::validate`: the
+//! instruction count is the one of the compiled bytecode, and validating
+//! takes linear time even for the patterns whose bytecode would be huge.
+#[allow(dead_code)]
+mod utils;
+
+use std::time::{Duration, Instant};
+
+use gregex::Builder;
+use gregex::regex::{Config, PatternError};
+use gregex::thompson::bytecode::{CompileError, Compiler};
+use gregex::validate::{LimitExceeded, PatternInfo, ValidateLimits, validate};
+
+const NO_LIMITS: ValidateLimits = ValidateLimits {
+    max_pattern_len: usize::MAX,
+    max_instructions: usize::MAX,
+};
+
+/// Patterns going through each way the compiler emits instructions, besides
+/// the ones of the corpus.
+const PATTERNS: &[&str] = &[
+    "",
+    "a{0}",
+    "(a){0}(b)",
+    "a{3}",
+    "a{2,5}",
+    "a{2,5}?",
+    "(?:ab){3,}",
+    "(a|b)*",
+    "(a|)*",
+    "(?:a|)+",
+    "(?:a*)*",
+    "(?:(a)|b)*?c",
+    "x|y|\\d",
+    "x|yz|w",
+    "x|ab|a|b",
+    "x|(y)z|w",
+    "foo|foo|bar",
+    "a|a",
+    "a||b",
+    "(?:a|)|b",
+    "a|\\w|é|(?i)k",
+    "(?:x|y){2,4}(?:ab|a|b)",
+    "[^\\s\\S]",
+    "(?m)^a$|\\b",
+    "\\p{Greek}+|\\d+",
+    "(?s).+?",
+    "((((a))))",
+];
+
+fn configs() -> Vec<Config> {
+    let default = Builder::new("").config().clone();
+    vec![
+        default.clone(),
+        Config {
+            cg: false,
+            ..default.clone()
+        },
+        Config {
+            case_insensitive: true,
+            ..default.clone()
+        },
+        Config {
+            force_class_outlining: true,
+            ..default.clone()
+        },
+        Config {
+            possessive_quantifiers: true,
+            ..default
+        },
+    ]
+}
+
+/// The number of instructions of the compiled bytecode.
+fn compiled_len(pattern: &str, config: &Config) -> Option<usize> {
+    let hir = Compiler::parse(pattern, config).ok()?;
+    let bytecode = Compiler::compile(hir, config.clone()).ok()?;
+    Some(bytecode.instructions.len())
+}
+
+fn assert_exact(pattern: &str, config: &Config) {
+    let info = validate(pattern, config, &NO_LIMITS);
+    let compiled = compiled_len(pattern, config);
+    assert_eq!(
+        info.as_ref().ok().map(|info| info.estimated_instructions),
+        compiled,
+        "{pattern:?} with {config:?}"
+    );
+    if let Ok(info) = info {
+        let re = Builder::new(pattern).pike_vm().ok();
+        if config.cg
+            && let Some(re) = re
+        {
+            assert_eq!(info.capture_count, re.group_len(), "{pattern:?}");
+        }
+    }
+}
+
+#[test]
+fn test_exact_count() {
+    let corpus = utils::corpus::CASES.iter().map(|(pattern, _)| *pattern);
+    let possessive = ["a*+b", "(?:ab|a)?+b", "a{1,3}+", "(a++)+"];
+    for config in configs() {
+        for pattern in corpus.clone().chain(PATTERNS.iter().copied()) {
+            assert_exact(pattern, &config);
+        }
+        if config.possessive_quantifiers {
+            for pattern in possessive {
+                assert_exact(pattern, &config);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_info() {
+    let config = Config::default();
+    let info = |pattern| validate(pattern, &config, &NO_LIMITS).unwrap();
+    assert_eq!(
+        info(r"^([0-9]{4})-([0-9]{2})"),
+        PatternInfo {
+            capture_count: 3,
+            estimated_instructions: 13,
+            min_len: Some(7),
+            max_len: Some(7),
+            has_assertions: true,
+            anchored_start: true,
+        }
+    );
+    let unbounded = info("a+(b)?");
+    assert_eq!((unbounded.min_len, unbounded.max_len), (Some(1), None));
+    assert!(!unbounded.has_assertions && !unbounded.anchored_start);
+    assert_eq!(info("[^\\s\\S]").min_len, None);
+    // The lengths are in bytes.
+    assert_eq!(info("é").max_len, Some(2));
+    assert_eq!(info(r"\d").max_len, Some(4));
+    // Only the start of the haystack anchors the matches.
+    assert!(!info("(?m)^a").anchored_start);
+    assert!(!info("^a|b").anchored_start);
+    assert!(info("^a|^b").anchored_start);
+    assert!(info(r"\Aa").anchored_start);
+    assert!(info(r"a$").has_assertions);
+    // Rejected like by the regex constructors.
+    let conflicting = Config {
+        force_class_outlining: true,
+        force_class_inlining: true,
+        ..Config::default()
+    };
+    let err = validate("a", &conflicting, &NO_LIMITS).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<CompileError>(),
+        Some(&CompileError::ConflictingClassCompilation)
+    );
+    let no_groups = Config {
+        cg: false,
+        ..Config::default()
+    };
+    let info = validate("(a)(b)", &no_groups, &NO_LIMITS).unwrap();
+    assert_eq!((info.capture_count, info.estimated_instructions), (1, 3));
+}
+
+#[test]
+fn test_same_errors_as_the_compiler() {
+    let nested = Builder::new("((((a))))").nest_limit(3);
+    let non_unicode = Builder::new(r"\p{Greek}").unicode(false);
+    let conflicting = Builder::new("a")
+        .force_class_outlining(true)
+        .force_class_inlining(true);
+    let syntax = Builder::new("a(");
+    for builder in [nested, non_unicode, conflicting, syntax] {
+        let expected = builder.pike_vm().err().unwrap();
+        let err = validate(builder.pattern(), builder.config(), &NO_LIMITS).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            expected.to_string(),
+            "{}",
+            builder.pattern()
+        );
+    }
+    let non_unicode = Config {
+        unicode: false,
+        ..Config::default()
+    };
+    let err = validate(r"a\p{Greek}", &non_unicode, &NO_LIMITS).unwrap_err();
+    let err = err.downcast::<PatternError>().unwrap();
+    assert_eq!(err.span(), Some(1..10));
+    assert!(matches!(
+        err.inner().downcast_ref::<CompileError>(),
+        Some(CompileError::UnsupportedInNonUnicodeMode { .. })
+    ));
+}
+
+#[test]
+fn test_limits() {
+    let config = Config::default();
+    let limits = ValidateLimits {
+        max_pattern_len: 8,
+        max_instructions: 10,
+    };
+    let exceeded = |pattern| {
+        let err = validate(pattern, &config, &limits).unwrap_err();
+        *err.downcast::<LimitExceeded>().unwrap()
+    };
+    assert_eq!(
+        exceeded("abcdefghi"),
+        LimitExceeded::PatternTooLong { len: 9, max: 8 }
+    );
+    // Checked before parsing.
+    assert_eq!(
+        exceeded("(((((((((("),
+        LimitExceeded::PatternTooLong { len: 10, max: 8 }
+    );
+    assert_eq!(
+        exceeded("a{10}"),
+        LimitExceeded::TooManyInstructions {
+            instructions: 11,
+            max: 10
+        }
+    );
+    assert_eq!(
+        validate("a{9}", &config, &limits)
+            .unwrap()
+            .estimated_instructions,
+        10
+    );
+    // The counts saturate instead of overflowing. Without limits of its
+    // own, the pattern is over the ones of the compiler.
+    let huge = "(?:".repeat(7) + "a" + &"){1000}".repeat(7);
+    let err = validate(&huge, &config, &NO_LIMITS).unwrap_err();
+    assert_eq!(
+        *err.downcast::<CompileError>().unwrap(),
+        CompileError::RepetitionTooLarge {
+            product: usize::MAX,
+            max: gregex::limits::MAX_UNROLLED_REPETITION_PRODUCT
+        }
+    );
+    let err = validate(&huge, &config, &ValidateLimits::default()).unwrap_err();
+    assert_eq!(
+        *err.downcast::<LimitExceeded>().unwrap(),
+        LimitExceeded::TooManyInstructions {
+            instructions: usize::MAX,
+            max: 100_000
+        }
+    );
+}
+
+/// A xorshift generator, good enough to pick pattern fragments.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+#[test]
+fn test_random_patterns() {
+    const ATOMS: &[&str] = &[
+        "a", "b", "é", "ab", r"\w", r"\d", "[^a]", "[ac-e]", ".", "^", "$", r"\b", "", "(", "(?:",
+        "|", "|", "x|y",
+    ];
+    const QUANTIFIERS: &[&str] = &["", "", "*", "+", "?", "??", "{2}", "{1,3}", "{2,}", "{0}"];
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let configs = configs();
+    for i in 0..2000 {
+        let mut pattern = String::new();
+        let mut depth = 0;
+        for _ in 0..rng.next() % 12 {
+            let atom = rng.pick(ATOMS);
+            pattern.push_str(atom);
+            if atom.starts_with('(') {
+                depth += 1;
+                continue;
+            }
+            if depth > 0 && rng.next().is_multiple_of(3) {
+                pattern.push(')');
+                depth -= 1;
+            }
+            pattern.push_str(rng.pick(QUANTIFIERS));
+        }
+        pattern.push_str(&")".repeat(depth));
+        assert_exact(&pattern, &configs[i % configs.len()]);
+    }
+}
+
+/// The shortest of a few runs of `validate`, which must accept the pattern,
+/// or reject it for a limit of the compiler, which is checked once the
+/// instructions are counted.
+fn time_validate(pattern: &str) -> Duration {
+    (0..3)
+        .map(|_| {
+            let started = Instant::now();
+            if let Err(err) = validate(pattern, &Config::default(), &NO_LIMITS) {
+                assert!(matches!(
+                    err.downcast_ref::<CompileError>(),
+                    Some(
+                        CompileError::TooManyInstructions { .. }
+                            | CompileError::RepetitionTooLarge { .. }
+                    )
+                ));
+            }
+            started.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[test]
+fn test_linear_time() {
+    // Chars which are not adjacent, so that their classes don't collapse
+    // into a single range.
+    let sparse = |i: u32| char::from_u32(0x4E00 + 2 * i).unwrap();
+    let families: &[(&str, &dyn Fn(u32) -> String)] = &[
+        ("sparse single chars", &|n| {
+            (0..n)
+                .map(|i| sparse(i).to_string())
+                .collect::<Vec<_>>()
+                .join("|")
+        }),
+        ("blocked single chars", &|n| {
+            (0..n)
+                .map(|i| format!("{0}|{0}x", sparse(i)))
+                .collect::<Vec<_>>()
+                .join("|")
+        }),
+        ("nested alternation", &|n| {
+            (0..n)
+                .map(|i| format!("{0}|(?:{0}y|z)", sparse(i)))
+                .collect::<Vec<_>>()
+                .join("|")
+        }),
+        ("unrolled repetitions", &|n| {
+            "(?:(?:[a-c]{1000}x){1000}|b)".repeat(n as usize)
+        }),
+        ("large classes", &|n| {
+            r"\w|\d|[^a]|\p{Greek}".repeat(n as usize)
+        }),
+    ];
+    for (name, family) in families {
+        let small = time_validate(&family(500));
+        let large = time_validate(&family(4000));
+        // 8 times the pattern, with a lot of slack for a noisy machine, but
+        // well under the 64 times of a quadratic validation.
+        assert!(
+            large < small * 24 + Duration::from_millis(20),
+            "{name}: {small:?} then {large:?}"
+        );
+    }
+}
+
+#[test]
+fn test_deep_nesting_within_limits() {
+    // Nested as deep as the default limit allows, each level with an
+    // alternation which looks at the first chars of the next one.
+    let depth = Config::default().nest_limit as usize / 4 - 1;
+    let pattern = "(?:a|b|(?:c".repeat(depth) + &"))".repeat(depth);
+    let started = Instant::now();
+    let info = validate(&pattern, &Config::default(), &NO_LIMITS).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(
+        Some(info.estimated_instructions),
+        compiled_len(&pattern, &Config::default())
+    );
+}
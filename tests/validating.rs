@@ -0,0 +1,112 @@
+use gregex::util::Utf8ErrorAt;
+use gregex::{Builder, EngineKind, Regex};
+
+/// Every engine available for the pattern, including the specialized ones.
+fn engines(pattern: &str) -> Vec<(String, Regex)> {
+    let mut engines = Vec::new();
+    for kind in EngineKind::ALL {
+        for specialize in [true, false] {
+            if let Ok(re) = Builder::new(pattern).specialize(specialize).build(kind) {
+                engines.push((format!("{kind:?} specialize {specialize}"), re));
+            }
+        }
+    }
+    engines
+}
+
+fn find_validating(re: &Regex, haystack: &[u8]) -> Result<Option<(usize, usize)>, usize> {
+    re.find_validating(haystack)
+        .map(|m| m.map(|m| (m.start(), m.end())))
+        .map_err(|err| err.offset)
+}
+
+fn check(pattern: &str, haystack: &[u8], expected: Result<Option<(usize, usize)>, usize>) {
+    for (name, re) in engines(pattern) {
+        assert_eq!(
+            find_validating(&re, haystack),
+            expected,
+            "{pattern:?} on {haystack:?} with {name}"
+        );
+    }
+}
+
+#[test]
+fn test_valid_haystacks() {
+    for (pattern, haystack) in [
+        (r"\d+", "abc 123"),
+        ("€.", "é€😀"),
+        ("(?m)^$", "a\n\nb"),
+        ("x", "é€😀"),
+        (r"\w+", ""),
+    ] {
+        for (name, re) in engines(pattern) {
+            let expected = re.find(haystack).map(|m| (m.start(), m.end()));
+            let found = re.find_validating(haystack.as_bytes()).unwrap();
+            assert_eq!(found.map(|m| (m.start(), m.end())), expected, "{name}");
+        }
+    }
+}
+
+#[test]
+fn test_invalid_before_the_match() {
+    check(r"\d+", b"\xFFabc 123", Err(0));
+    check(r"\d+", b"ab\xC3(c 123", Err(2));
+    check("(?m)^$", b"a\xFF\n\nb", Err(1));
+}
+
+#[test]
+fn test_invalid_inside_the_match() {
+    check("a.c", b"xa\xFFc", Err(2));
+    // A truncated char at the end of the haystack.
+    check(r"\d+\D", b"12\xE2\x82", Err(2));
+}
+
+#[test]
+fn test_invalid_after_the_match() {
+    // The search stops before the invalid bytes.
+    check(r"\d+", b"12 x\xFF", Ok(Some((0, 2))));
+    let re = Regex::pike_vm(r"\d+").unwrap();
+    let m = re.find_validating(b"12 x\xFF").unwrap().unwrap();
+    assert_eq!(m.as_str(), "12");
+    // The char after the match is read, to know that it ends there.
+    check(r"\d+", b"12\xFF", Err(2));
+    // Without a match, the whole haystack is read.
+    check(r"\d", b"abc\xFF", Err(3));
+}
+
+#[test]
+fn test_invalid_sequences() {
+    // Each of them is invalid at its start, like std reports it.
+    let sequences: [&[u8]; 9] = [
+        b"\x80",
+        b"\xC0\x80",
+        b"\xC2",
+        b"\xE0\x80\x80",
+        b"\xED\xA0\x80",
+        b"\xEF\xBF",
+        b"\xF0\x8F\xBF\xBF",
+        b"\xF4\x90\x80\x80",
+        b"\xF5\x80\x80\x80",
+    ];
+    for sequence in sequences {
+        let mut haystack = "é€😀".as_bytes().to_vec();
+        let offset = haystack.len();
+        haystack.extend_from_slice(sequence);
+        haystack.extend_from_slice("a".as_bytes());
+        let std_offset = std::str::from_utf8(&haystack).unwrap_err().valid_up_to();
+        assert_eq!(std_offset, offset);
+        check("z", &haystack, Err(offset));
+        let err = Regex::pike_vm("z")
+            .unwrap()
+            .find_validating(&haystack)
+            .unwrap_err();
+        assert_eq!(err, Utf8ErrorAt { offset });
+    }
+    // The largest chars of each length are valid.
+    check("z", "\u{7F}\u{7FF}\u{FFFF}\u{10FFFF}".as_bytes(), Ok(None));
+    check(
+        "\u{D7FF}\u{E000}",
+        "\u{D7FF}\u{E000}".as_bytes(),
+        Ok(Some((0, 6))),
+    );
+}
@@ -0,0 +1,127 @@
+//! Word boundary assertions, `\b` and `\B`, in their Unicode and ASCII forms,
+//! and the word starts and ends. The other assertions are also covered by the
+//! matrix over every assertion of regex-syntax.
+#[allow(dead_code)]
+mod utils;
+
+use gregex::Builder;
+use regex_syntax::hir::{Look, LookSet};
+use utils::check_all_engines;
+
+/// A pattern for each assertion of regex-syntax.
+const LOOKS: &[(Look, &str)] = &[
+    (Look::Start, r"\A"),
+    (Look::End, r"\z"),
+    (Look::StartLF, "(?m)^"),
+    (Look::EndLF, "(?m)$"),
+    (Look::StartCRLF, "(?mR)^"),
+    (Look::EndCRLF, "(?mR)$"),
+    (Look::WordAscii, r"(?-u:\b)"),
+    (Look::WordAsciiNegate, r"(?-u:\B)"),
+    (Look::WordUnicode, r"\b"),
+    (Look::WordUnicodeNegate, r"\B"),
+    (Look::WordStartAscii, r"(?-u:\<)"),
+    (Look::WordEndAscii, r"(?-u:\>)"),
+    (Look::WordStartUnicode, r"\<"),
+    (Look::WordEndUnicode, r"\b{end}"),
+    (Look::WordStartHalfAscii, r"(?-u:\b{start-half})"),
+    (Look::WordEndHalfAscii, r"(?-u:\b{end-half})"),
+    (Look::WordStartHalfUnicode, r"\b{start-half}"),
+    (Look::WordEndHalfUnicode, r"\b{end-half}"),
+];
+
+#[test]
+fn test_every_look() {
+    let mut covered = LookSet::empty();
+    for &(look, pattern) in LOOKS {
+        let hir = regex_syntax::parse(pattern).unwrap();
+        assert_eq!(hir.properties().look_set(), LookSet::singleton(look));
+        covered = covered.insert(look);
+    }
+    assert!(LookSet::full().iter().all(|look| covered.contains(look)));
+
+    let contexts = ["{}", r"{}\w", r"\w{}", r"({})\w+({})", "{}y|x{}"];
+    let inputs = [
+        "",
+        "a",
+        "xy y",
+        "ab cd",
+        "_x_",
+        "aé éa",
+        "1 é2",
+        "a\r\nb\n\r",
+        "日本 x",
+        ".a.",
+    ];
+    for &(_, look) in LOOKS {
+        for context in contexts {
+            let pattern = context.replace("{}", look);
+            for input in inputs {
+                check_all_engines(&pattern, input);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_against_regex_crate() {
+    let patterns = [
+        r"\b",
+        r"\B",
+        r"\bfoo\b",
+        r"\Bfoo\B",
+        r"\b\w+\b",
+        r"\w+\b",
+        r"\B\w",
+        r"\b.",
+        r"(?-u:\b)\w+",
+        r"(?-u:\b)",
+        r"(?-u:\B)",
+        r"(?-u:\b)é",
+        r"\bé+\b",
+        r"(\b)(a|é)(\B)",
+        r"\b(?:ab|cd)\b|x",
+        r"(?m)^\b\w+\b$",
+        r"(?i)\bSTRASSE\b",
+        r"\b\d+\b|\B\d",
+        r"a\b|\ba",
+        r"(?:\b|x)+a",
+        r"\B\B",
+        r"\b\B",
+        r"\<\w+\>",
+        r"\b{start}foo|bar\b{end}",
+        r"(?-u:\<)\w|\w(?-u:\>)",
+        r"\b{start-half}\d+\b{end-half}",
+        r"(?-u:\b{start-half})\w(?-u:\b{end-half})",
+        r"\<\>|\>\<",
+    ];
+    let inputs = [
+        "",
+        "foo",
+        " foo ",
+        "foobar foo_ xfoo foo.",
+        "héllo wörld",
+        "é",
+        "aé éa",
+        "12 a3 4b 5",
+        "x\na\nbb\n",
+        "σ 日本 𝔸x",
+        "strasse Straße STRASSE",
+        "  ",
+        "_a_ -a- a",
+    ];
+    for pattern in patterns {
+        for input in inputs {
+            check_all_engines(pattern, input);
+        }
+    }
+}
+
+#[test]
+fn test_at_the_bounds_of_the_span() {
+    // The chars around the span count, like with `^` in multi-line mode.
+    let re = Builder::new(r"\bfoo\b").pike_vm().unwrap();
+    let input = |from, to| gregex::util::Input::new("xfoo foo").span((from..to).into());
+    assert!(re.find(input(1, 4)).is_none());
+    assert_eq!(re.find(input(5, 8)).map(|m| m.start()), Some(5));
+}